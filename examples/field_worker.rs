@@ -0,0 +1,56 @@
+extern crate multipart;
+
+use std::fs;
+use std::io::{self, Read};
+use std::sync::mpsc;
+use std::thread;
+
+use multipart::server::{Multipart, OwnedField, SaveBuilderConfig};
+
+const BODY: &[u8] = b"--boundary\r\n\
+                      Content-Disposition: form-data; name=\"photo\"; filename=\"a.jpg\"\r\n\
+                      Content-Type: image/jpeg\r\n\r\n\
+                      not really a jpeg, but long enough to be spooled to a file\r\n\
+                      --boundary\r\n\
+                      Content-Disposition: form-data; name=\"caption\"\r\n\r\n\
+                      Hello, world!\r\n\
+                      --boundary--\r\n";
+
+/// Stands in for slow work like making a thumbnail, done while the next field is parsed.
+fn process(field: OwnedField) -> io::Result<()> {
+    let mut data = Vec::new();
+    field.data.readable()?.read_to_end(&mut data)?;
+
+    println!("worker: {} ({} bytes)", field.summary(), data.len());
+
+    // the spooled file belongs to us now
+    if let Some(path) = field.as_path() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let (sender, receiver) = mpsc::channel::<OwnedField>();
+
+    let worker = thread::spawn(move || {
+        for field in receiver {
+            process(field).unwrap();
+        }
+    });
+
+    let spool = SaveBuilderConfig::config().memory_threshold(32);
+    let mut multipart = Multipart::with_body(BODY, "boundary");
+
+    while let Some(field) = multipart.into_entry().into_result().unwrap() {
+        println!("parser: read field {:?}", field.headers.name);
+
+        let (owned, rest) = field.into_owned(&spool).unwrap();
+        sender.send(owned).unwrap();
+        multipart = rest;
+    }
+
+    drop(sender);
+    worker.join().unwrap();
+}
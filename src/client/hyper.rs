@@ -11,7 +11,8 @@
 //!
 //! Also see: [`lazy::Multipart::client_request()`](../lazy/struct.Multipart.html#method.client_request)
 //! and [`lazy::Multipart::client_request_mut()`](../lazy/struct.Multipart.html#method.client_request_mut)
-//! (adaptors for `hyper::client::RequestBuilder`).
+//! (adaptors for `hyper::client::RequestBuilder`), and their `client_request_with*()` variants
+//! for methods other than POST.
 use hyper::client::request::Request;
 use hyper::client::response::Response;
 use hyper::header::{ContentType, ContentLength};
@@ -29,12 +30,11 @@ impl HttpRequest for Request<Fresh> {
     type Stream = Request<Streaming>;
     type Error = HyperError;
 
-    /// # Panics
-    /// If `self.method() != Method::Post`.
+    /// Returns `false` if the request method is not one of `Post`, `Put` or `Patch`.
     fn apply_headers(&mut self, boundary: &str, content_len: Option<u64>) -> bool {
-        if self.method() != Method::Post {
+        if !allows_body(&self.method()) {
             error!(
-                "Expected Hyper request method to be `Post`, was actually `{:?}`",
+                "Expected Hyper request method to be `Post`, `Put` or `Patch`, was actually `{:?}`",
                 self.method()
             );
 
@@ -70,6 +70,11 @@ impl HttpStream for Request<Streaming> {
     }
 }
 
+/// Whether a multipart body can be sent with `method`.
+fn allows_body(method: &Method) -> bool {
+    matches!(*method, Method::Post | Method::Put | Method::Patch)
+}
+
 /// Create a `Content-Type: multipart/form-data;boundary={bound}`
 pub fn content_type(bound: &str) -> ContentType {
     ContentType(multipart_mime(bound))
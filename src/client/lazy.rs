@@ -12,6 +12,12 @@ use std::{fmt, io};
 
 use super::{HttpRequest, HttpStream};
 
+#[cfg(feature = "http")]
+use http::HeaderMap;
+
+#[cfg(feature = "server")]
+use server::Entries;
+
 macro_rules! try_lazy (
     ($field:expr, $try:expr) => (
         match $try {
@@ -127,6 +133,7 @@ impl<'a, E: fmt::Display> fmt::Display for LazyError<'a, E> {
 #[derive(Debug, Default)]
 pub struct Multipart<'n, 'd> {
     fields: Vec<Field<'n, 'd>>,
+    max_body_size: Option<u64>,
 }
 
 impl<'n, 'd> Multipart<'n, 'd> {
@@ -191,6 +198,46 @@ impl<'n, 'd> Multipart<'n, 'd> {
         self
     }
 
+    /// Add the fields saved from a request, e.g. to forward it to another server.
+    ///
+    /// A field without a filename whose data is text in memory is added as with `add_text()`;
+    /// any other is added as a stream with its filename and content type (or
+    /// `application/octet-stream`). Use [`estimate_body_size()`](fn.estimate_body_size.html)
+    /// to get the size of the resulting body beforehand.
+    ///
+    /// Returns an error if the data of a field can't be read, e.g. a `SavedData::Streamed`.
+    #[cfg(feature = "server")]
+    pub fn add_entries(&mut self, entries: &'n Entries) -> io::Result<&mut Self> where 'n: 'd {
+        for field in entries.fields.values().flatten() {
+            let name = &*field.headers.name;
+
+            match field.as_text() {
+                Some(text) if field.headers.filename.is_none() => {
+                    self.add_text(name, text);
+                },
+                _ => {
+                    self.add_stream(name, field.data.readable()?, field.headers.filename.as_deref(),
+                                    field.headers.content_type.clone());
+                },
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Set the maximum size of the request body in bytes, or `None` for no limit (the default).
+    ///
+    /// `prepare()` (and so `send()`) returns an error if the size of the text and file fields
+    /// (measured from file metadata) already exceeds the limit. Streams have no known size,
+    /// so reading the prepared body returns an error once it goes over the limit.
+    ///
+    /// In either case the error can be downcast to [`BodyTooLargeError`](struct.BodyTooLargeError.html),
+    /// which names the field that went over the limit.
+    pub fn max_body_size<L: Into<Option<u64>>>(&mut self, max_body_size: L) -> &mut Self {
+        self.max_body_size = max_body_size.into();
+        self
+    }
+
     /// Convert `req` to `HttpStream`, write out the fields in this request, and finish the
     /// request, returning the response if successful, or the first error encountered.
     ///
@@ -216,7 +263,179 @@ impl<'n, 'd> Multipart<'n, 'd> {
     /// During this step, if any files were added by path then they will be opened for reading
     /// and their length measured.
     pub fn prepare(&mut self) -> LazyIoResult<'n, PreparedFields<'d>> {
-        PreparedFields::from_fields(&mut self.fields)
+        PreparedFields::from_fields(&mut self.fields, self.max_body_size)
+    }
+
+    /// Get a request body which writes out the parts from `parts` as they become available,
+    /// e.g. from the receiving end of a channel which another thread sends parts into,
+    /// so the upload can start before all the parts exist.
+    ///
+    /// The body ends with the closing boundary once `parts` does. If it yields an error instead,
+    /// reading the body returns that error, which aborts the request.
+    ///
+    /// Reading blocks while waiting for the next part (or the data of the current one),
+    /// so the producer is held back by how fast the request is sent.
+    ///
+    /// ```rust
+    /// # use multipart::client::lazy::{Multipart, StreamPart};
+    /// # use std::io::Read;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// let (send, recv) = mpsc::sync_channel(1);
+    ///
+    /// thread::spawn(move || {
+    ///     for i in 0 .. 3 {
+    ///         let chunk = format!("chunk {}", i).into_bytes();
+    ///         send.send(Ok(StreamPart::new("chunk", ::std::io::Cursor::new(chunk)))).unwrap();
+    ///     }
+    /// });
+    ///
+    /// let mut body = Multipart::from_part_stream(recv);
+    /// let content_type = body.content_type();
+    ///
+    /// let mut data = String::new();
+    /// body.read_to_string(&mut data).unwrap();
+    /// assert!(data.contains("chunk 2"));
+    /// ```
+    pub fn from_part_stream<I>(parts: I) -> PartStream<'d, I::IntoIter>
+    where I: IntoIterator<Item = io::Result<StreamPart<'d>>> {
+        PartStream::new(parts.into_iter())
+    }
+
+    /// If every field is a text field, serialize them as an `application/x-www-form-urlencoded`
+    /// body instead, following the [WHATWG `urlencoded` serializer][serializer].
+    ///
+    /// Returns the body and its content type. If any files or streams were added, this request
+    /// has to be sent as multipart instead, which is returned in the error.
+    ///
+    /// `max_body_size` is not checked.
+    ///
+    /// ```rust
+    /// # use multipart::client::lazy::Multipart;
+    /// let mut multipart = Multipart::new();
+    /// multipart.add_text("name", "Jane Doe").add_text("motto", "100% café");
+    ///
+    /// let (body, content_type) = multipart.into_urlencoded().unwrap();
+    /// assert_eq!(body, "name=Jane+Doe&motto=100%25+caf%C3%A9");
+    /// assert_eq!(content_type, "application/x-www-form-urlencoded");
+    /// ```
+    ///
+    /// [serializer]: https://url.spec.whatwg.org/#concept-urlencoded-serializer
+    pub fn into_urlencoded(self) -> Result<(String, String), NotTextOnly<'n, 'd>> {
+        let mut body = String::new();
+
+        for field in &self.fields {
+            let text = match field.data {
+                Data::Text(ref text) => text,
+                _ => return Err(NotTextOnly {
+                    field_name: field.name.to_string(),
+                    multipart: self,
+                }),
+            };
+
+            if !body.is_empty() {
+                body.push('&');
+            }
+
+            urlencode(&field.name, &mut body);
+            body.push('=');
+            urlencode(text, &mut body);
+        }
+
+        Ok((body, "application/x-www-form-urlencoded".into()))
+    }
+}
+
+/// The exact size of the body that `Multipart::add_entries()` followed by `prepare()` produces
+/// for `entries` with a boundary of `boundary_len` bytes (not counting the leading `--`),
+/// without reading any data. Boundaries generated by this crate are always the same length,
+/// which `PreparedFields::boundary()` returns.
+///
+/// The size is computed from the field headers and `SavedData::size()` as this version
+/// of the crate writes them. A `SavedData::Streamed` is counted, even though it can't be added.
+#[cfg(feature = "server")]
+pub fn estimate_body_size(entries: &Entries, boundary_len: usize) -> u64 {
+    // `\r\n--{boundary}`
+    let delimiter = 4 + boundary_len as u64;
+    let mut size = 0;
+
+    for field in entries.fields.values().flatten() {
+        let headers = &field.headers;
+
+        // `\r\nContent-Disposition: form-data; name="{name}"`, then `\r\n\r\n` after any others
+        size += delimiter + 40 + headers.name.len() as u64 + 1 + 4 + field.data.size();
+
+        if field.as_text().is_some() && headers.filename.is_none() {
+            continue;
+        }
+
+        if let Some(ref filename) = headers.filename {
+            // `; filename="{filename}"`
+            size += 12 + filename.len() as u64 + 1;
+        }
+
+        let content_type = headers.content_type.as_ref().unwrap_or(&mime::APPLICATION_OCTET_STREAM);
+        // `\r\nContent-Type: {content_type}`
+        size += 16 + content_type.as_ref().len() as u64;
+    }
+
+    if size > 0 {
+        // the closing boundary
+        size += delimiter + 2;
+    }
+
+    size
+}
+
+/// Append `text` to `out` percent-encoded with the `application/x-www-form-urlencoded`
+/// percent-encode set, and spaces as `+`.
+fn urlencode(text: &str, out: &mut String) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    for &byte in text.as_bytes() {
+        match byte {
+            b'*' | b'-' | b'.' | b'_' | b'0' ..= b'9' | b'A' ..= b'Z' | b'a' ..= b'z' =>
+                out.push(byte as char),
+            b' ' => out.push('+'),
+            _ => {
+                out.push('%');
+                out.push(HEX[(byte >> 4) as usize] as char);
+                out.push(HEX[(byte & 0xF) as usize] as char);
+            }
+        }
+    }
+}
+
+/// The error returned by [`Multipart::into_urlencoded()`](struct.Multipart.html#method.into_urlencoded)
+/// if the request has a file or stream field, so it has to be sent as multipart.
+#[derive(Debug)]
+pub struct NotTextOnly<'n, 'd> {
+    /// The name of the first field which isn't text.
+    pub field_name: String,
+    multipart: Multipart<'n, 'd>,
+}
+
+impl<'n, 'd> NotTextOnly<'n, 'd> {
+    /// Get back the request, to send it as multipart.
+    pub fn into_multipart(self) -> Multipart<'n, 'd> {
+        self.multipart
+    }
+}
+
+impl<'n, 'd> fmt::Display for NotTextOnly<'n, 'd> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "field {:?} is not a text field, so the request must be sent as multipart",
+            self.field_name
+        )
+    }
+}
+
+impl<'n, 'd> Error for NotTextOnly<'n, 'd> {
+    fn description(&self) -> &str {
+        "a field is not a text field, so the request must be sent as multipart"
     }
 }
 
@@ -255,17 +474,21 @@ struct Stream<'n, 'd> {
 /// Individual files/streams are dropped as they are read to completion.
 ///
 /// ### Note
-/// The fields in the request may have been reordered to simplify the preparation step.
-/// No compliant server implementation will be relying on the specific ordering of fields anyways.
+/// The fields in the request may have been reordered to simplify the preparation step:
+/// the text fields are written first, followed by the files and streams in the order they were
+/// added. No compliant server implementation will be relying on the specific ordering of fields anyways.
 pub struct PreparedFields<'d> {
     text_data: Cursor<Vec<u8>>,
     streams: Vec<PreparedField<'d>>,
     end_boundary: Cursor<String>,
     content_len: Option<u64>,
+    max_body_size: Option<u64>,
+    bytes_read: u64,
 }
 
 impl<'d> PreparedFields<'d> {
-    fn from_fields<'n>(fields: &mut Vec<Field<'n, 'd>>) -> Result<Self, LazyIoError<'n>> {
+    fn from_fields<'n>(fields: &mut Vec<Field<'n, 'd>>, max_body_size: Option<u64>)
+                       -> Result<Self, LazyIoError<'n>> {
         debug!("Field count: {}", fields.len());
 
         // One of the two RFCs specifies that any bytes before the first boundary are to be
@@ -274,6 +497,7 @@ impl<'d> PreparedFields<'d> {
 
         let mut text_data = Vec::new();
         let mut streams = Vec::new();
+        // the length of the files and of the stream headers; text is measured separately
         let mut content_len = 0u64;
         let mut use_len = true;
 
@@ -287,24 +511,39 @@ impl<'d> PreparedFields<'d> {
                 )
                 .unwrap(),
                 Data::File(file) => {
-                    let (stream, len) = PreparedField::from_path(field.name, &file, &boundary)?;
+                    let (stream, len) = PreparedField::from_path(&field.name, &file, &boundary)?;
                     content_len += len;
                     streams.push(stream);
                 }
                 Data::Stream(stream) => {
                     use_len = false;
 
-                    streams.push(PreparedField::from_stream(
+                    let stream = PreparedField::from_stream(
                         &field.name,
                         &boundary,
                         &stream.content_type,
                         stream.filename.as_ref().map(|f| &**f),
                         stream.stream,
+                    );
+
+                    content_len += stream.header.get_ref().len() as u64;
+                    streams.push(stream);
+                }
+            }
+
+            if let Some(max_body_size) = max_body_size {
+                if content_len + text_data.len() as u64 > max_body_size {
+                    return Err(LazyError::with_field(
+                        field.name.clone(),
+                        BodyTooLargeError::new(max_body_size, Some(&field.name)),
                     ));
                 }
             }
         }
 
+        // `read()` takes streams off the end, so they're written in the order they were added
+        streams.reverse();
+
         // So we don't write a spurious end boundary
         if text_data.is_empty() && streams.is_empty() {
             boundary = String::new();
@@ -312,13 +551,21 @@ impl<'d> PreparedFields<'d> {
             boundary.push_str("--");
         }
 
-        content_len += boundary.len() as u64;
+        content_len += (text_data.len() + boundary.len()) as u64;
+
+        if let Some(max_body_size) = max_body_size {
+            if content_len > max_body_size {
+                return Err(LazyError::without_field(BodyTooLargeError::new(max_body_size, None)));
+            }
+        }
 
         Ok(PreparedFields {
             text_data: Cursor::new(text_data),
             streams,
             end_boundary: Cursor::new(boundary),
             content_len: if use_len { Some(content_len) } else { None },
+            max_body_size,
+            bytes_read: 0,
         })
     }
 
@@ -335,6 +582,26 @@ impl<'d> PreparedFields<'d> {
         // Get just the bare boundary string
         &boundary[4..boundary.len() - 2]
     }
+
+    /// Get the value for the `Content-Type` header of this request,
+    /// i.e. `multipart/form-data; boundary={boundary}`.
+    ///
+    /// The body does not depend on the request method, so this and `content_len()` are all
+    /// that's needed to send it with any HTTP client, as PUT or PATCH as well as POST.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary())
+    }
+
+    /// Check bytes read from the stream at the top of `self.streams`, which may not be sized.
+    fn check_stream_read(&self, read: usize) -> io::Result<()> {
+        match self.max_body_size {
+            Some(max_body_size) if self.bytes_read + read as u64 > max_body_size => {
+                let field_name = self.streams.last().map(|field| &*field.name);
+                Err(BodyTooLargeError::new(max_body_size, field_name).into())
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl<'d> Read for PreparedFields<'d> {
@@ -349,48 +616,256 @@ impl<'d> Read for PreparedFields<'d> {
         while total_read < buf.len() && !cursor_at_end(&self.end_boundary) {
             let buf = &mut buf[total_read..];
 
-            total_read += if !cursor_at_end(&self.text_data) {
+            let read = if !cursor_at_end(&self.text_data) {
                 self.text_data.read(buf)?
             } else if let Some(mut field) = self.streams.pop() {
-                match field.read(buf) {
+                let read = match field.read(buf) {
                     Ok(0) => continue,
                     res => {
                         self.streams.push(field);
                         res
                     }
-                }?
+                }?;
+
+                self.check_stream_read(read)?;
+                read
             } else {
                 self.end_boundary.read(buf)?
             };
+
+            self.bytes_read += read as u64;
+            total_read += read;
         }
 
         Ok(total_read)
     }
 }
 
+/// A part to be written out by [`PartStream`](struct.PartStream.html), with data
+/// of unknown length.
+pub struct StreamPart<'d> {
+    name: String,
+    filename: Option<String>,
+    content_type: Mime,
+    #[cfg(feature = "http")]
+    headers: Option<HeaderMap>,
+    data: Box<dyn Read + Send + 'd>,
+}
+
+impl<'d> StreamPart<'d> {
+    /// A part with the given name and data, without a filename and with the content type
+    /// `application/octet-stream`.
+    pub fn new<N: Into<String>, R: Read + Send + 'd>(name: N, data: R) -> Self {
+        StreamPart {
+            name: name.into(),
+            filename: None,
+            content_type: mime::APPLICATION_OCTET_STREAM,
+            #[cfg(feature = "http")]
+            headers: None,
+            data: Box::new(data),
+        }
+    }
+
+    /// Set the filename of this part.
+    pub fn filename<F: Into<String>>(self, filename: F) -> Self {
+        StreamPart { filename: Some(filename.into()), ..self }
+    }
+
+    /// Set the content type of this part.
+    pub fn content_type(self, content_type: Mime) -> Self {
+        StreamPart { content_type, ..self }
+    }
+
+    /// Write `headers` as the headers of this part, e.g. from the server's
+    /// `FieldHeaders::to_header_map()`, instead of the ones made from its name, filename and
+    /// content type. Enabled with the `http` feature.
+    ///
+    /// The headers are written in the order of `headers`, with their names in lowercase
+    /// as `http` stores them. The name passed to `new()` is then only used for logging.
+    ///
+    /// ## Errors
+    /// An error of kind `InvalidInput` if `headers` doesn't have exactly one
+    /// `Content-Disposition` header of type `form-data`, or if a header value contains a CR,
+    /// LF or NUL byte (which `http` only allows when its checks are skipped); the same checks
+    /// as `Multipart::write_part()` for the other client.
+    #[cfg(feature = "http")]
+    pub fn headers(self, headers: HeaderMap) -> io::Result<Self> {
+        super::check_part_headers(&headers)?;
+        Ok(StreamPart { headers: Some(headers), ..self })
+    }
+}
+
+impl<'d> fmt::Debug for StreamPart<'d> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("StreamPart");
+        debug.field("name", &self.name)
+            .field("filename", &self.filename)
+            .field("content_type", &self.content_type);
+
+        #[cfg(feature = "http")]
+        debug.field("headers", &self.headers);
+
+        debug.field("data", &"Box<Read>").finish()
+    }
+}
+
+/// The result of [`Multipart::from_part_stream()`](struct.Multipart.html#method.from_part_stream).
+///
+/// Implements `Read`, writing out each part as it is taken from the iterator.
+pub struct PartStream<'d, I> {
+    parts: I,
+    /// With the preceding CRLF.
+    boundary: String,
+    current: Option<PreparedField<'d>>,
+    /// The closing boundary, once `parts` has ended.
+    end_boundary: Option<Cursor<String>>,
+    started: bool,
+}
+
+impl<'d, I: Iterator<Item = io::Result<StreamPart<'d>>>> PartStream<'d, I> {
+    fn new(parts: I) -> Self {
+        PartStream {
+            parts,
+            boundary: format!("\r\n--{}", super::gen_boundary()),
+            current: None,
+            end_boundary: None,
+            started: false,
+        }
+    }
+
+    /// Get the boundary that is used to serialize the request.
+    pub fn boundary(&self) -> &str {
+        &self.boundary[4..]
+    }
+
+    /// Get the value for the `Content-Type` header of this request,
+    /// i.e. `multipart/form-data; boundary={boundary}`.
+    ///
+    /// The length of the body isn't known in advance, so it has to be sent chunked.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary())
+    }
+
+    /// Convert `req` to `HttpStream`, write out the parts as they become available, and finish
+    /// the request, returning the response if successful, or the first error encountered,
+    /// including an error from the iterator.
+    pub fn send<R: HttpRequest>(mut self, mut req: R)
+        -> Result<<R::Stream as HttpStream>::Response, LazyError<'static, <R::Stream as HttpStream>::Error>> {
+        req.apply_headers(self.boundary(), None);
+
+        let mut stream = try_lazy!(req.open_stream());
+
+        if let Err(e) = io::copy(&mut self, &mut stream) {
+            let field_name = self.current.as_ref().map(|field| Cow::Owned(field.name.clone()));
+
+            return Err(match field_name {
+                Some(field_name) => LazyError::with_field(field_name, e),
+                None => LazyError::without_field(e),
+            });
+        }
+
+        stream.finish().map_err(LazyError::without_field)
+    }
+}
+
+impl<'d, I: Iterator<Item = io::Result<StreamPart<'d>>>> Read for PartStream<'d, I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if let Some(ref mut end_boundary) = self.end_boundary {
+                return end_boundary.read(buf);
+            }
+
+            if let Some(ref mut field) = self.current {
+                match field.read(buf)? {
+                    0 => (),
+                    read => return Ok(read),
+                }
+            }
+
+            self.current = None;
+
+            match self.parts.next() {
+                Some(Ok(part)) => {
+                    debug!("PartStream: writing part {:?}", part.name);
+
+                    self.started = true;
+                    self.current = Some(PreparedField::from_part(part, &self.boundary));
+                },
+                Some(Err(e)) => return Err(e),
+                // so we don't write a spurious end boundary
+                None => self.end_boundary = Some(Cursor::new(if self.started {
+                    format!("{}--", self.boundary)
+                } else {
+                    String::new()
+                })),
+            }
+        }
+    }
+}
+
+impl<'d, I> fmt::Debug for PartStream<'d, I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PartStream")
+            .field("boundary", &self.boundary)
+            .field("current", &self.current)
+            .field("end_boundary", &self.end_boundary)
+            .finish()
+    }
+}
+
 struct PreparedField<'d> {
+    name: String,
     header: Cursor<Vec<u8>>,
     stream: Box<dyn Read + 'd>,
 }
 
 impl<'d> PreparedField<'d> {
     fn from_path<'n>(
-        name: Cow<'n, str>,
+        name: &Cow<'n, str>,
         path: &Path,
         boundary: &str,
     ) -> Result<(Self, u64), LazyIoError<'n>> {
         let (content_type, filename) = super::mime_filename(&path);
 
-        let file = try_lazy!(name, File::open(path));
-        let content_len = try_lazy!(name, file.metadata()).len();
+        let file = try_lazy!(name.clone(), File::open(path));
+        let content_len = try_lazy!(name.clone(), file.metadata()).len();
 
-        let stream = Self::from_stream(&name, boundary, &content_type, filename, Box::new(file));
+        let stream = Self::from_stream(name, boundary, &content_type, filename, Box::new(file));
 
         let content_len = content_len + (stream.header.get_ref().len() as u64);
 
         Ok((stream, content_len))
     }
 
+    fn from_part(part: StreamPart<'d>, boundary: &str) -> Self {
+        #[cfg(feature = "http")]
+        {
+            if let Some(ref headers) = part.headers {
+                let mut header = format!("{}\r\n", boundary).into_bytes();
+                super::write_part_headers(headers, &mut header).unwrap();
+                header.extend_from_slice(b"\r\n");
+
+                return PreparedField {
+                    name: part.name,
+                    header: Cursor::new(header),
+                    stream: part.data,
+                };
+            }
+        }
+
+        PreparedField::from_stream(
+            &part.name,
+            boundary,
+            &part.content_type,
+            part.filename.as_deref(),
+            part.data,
+        )
+    }
+
     fn from_stream(
         name: &str,
         boundary: &str,
@@ -414,6 +889,7 @@ impl<'d> PreparedField<'d> {
         write!(header, "\r\nContent-Type: {}\r\n\r\n", content_type).unwrap();
 
         PreparedField {
+            name: name.to_owned(),
             header: Cursor::new(header),
             stream,
         }
@@ -435,12 +911,64 @@ impl<'d> Read for PreparedField<'d> {
 impl<'d> fmt::Debug for PreparedField<'d> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("PreparedField")
+            .field("name", &self.name)
             .field("header", &self.header)
             .field("stream", &"Box<Read>")
             .finish()
     }
 }
 
+/// The error returned when a request body exceeds the size set with
+/// [`Multipart::max_body_size()`](struct.Multipart.html#method.max_body_size).
+///
+/// Returned in `LazyError` by `prepare()` and wrapped in `io::Error` when reading `PreparedFields`.
+#[derive(Debug)]
+pub struct BodyTooLargeError {
+    /// The maximum body size that was set.
+    pub max_body_size: u64,
+    /// The name of the field which pushed the body over the limit,
+    /// or `None` if it was the closing boundary.
+    pub field_name: Option<String>,
+}
+
+impl BodyTooLargeError {
+    fn new(max_body_size: u64, field_name: Option<&str>) -> Self {
+        BodyTooLargeError {
+            max_body_size,
+            field_name: field_name.map(Into::into),
+        }
+    }
+}
+
+impl fmt::Display for BodyTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.field_name {
+            Some(ref field_name) => write!(
+                f,
+                "request body exceeds the maximum size of {} bytes at field {:?}",
+                self.max_body_size, field_name
+            ),
+            None => write!(
+                f,
+                "request body exceeds the maximum size of {} bytes",
+                self.max_body_size
+            ),
+        }
+    }
+}
+
+impl Error for BodyTooLargeError {
+    fn description(&self) -> &str {
+        "request body exceeds the maximum size"
+    }
+}
+
+impl From<BodyTooLargeError> for io::Error {
+    fn from(err: BodyTooLargeError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
 /// Conversion trait necessary for `Multipart::add_file()` to accept borrowed or owned strings
 /// and borrowed or owned paths
 pub trait IntoCowPath<'a> {
@@ -485,6 +1013,7 @@ fn cursor_at_end<T: AsRef<[u8]>>(cursor: &Cursor<T>) -> bool {
 #[cfg(feature = "hyper")]
 mod hyper {
     use hyper::client::{Body, Client, IntoUrl, RequestBuilder, Response};
+    use hyper::method::Method;
     use hyper::Result as HyperResult;
 
     impl<'n, 'd> super::Multipart<'n, 'd> {
@@ -513,6 +1042,39 @@ mod hyper {
             url: U,
             mut_fn: F,
         ) -> HyperResult<Response> {
+            self.client_request_with_mut(client, Method::Post, url, mut_fn)
+        }
+
+        /// #### Feature: `hyper`
+        /// Complete a request with the given `hyper::client::Client`, method and URL,
+        /// e.g. `Method::Put` or `Method::Patch` for APIs which don't take POST.
+        ///
+        /// Otherwise the same as `client_request()`.
+        pub fn client_request_with<U: IntoUrl>(
+            &mut self,
+            client: &Client,
+            method: Method,
+            url: U,
+        ) -> HyperResult<Response> {
+            self.client_request_with_mut(client, method, url, |r| r)
+        }
+
+        /// #### Feature: `hyper`
+        /// Complete a request with the given `hyper::client::Client`, method and URL;
+        /// allows mutating the `hyper::client::RequestBuilder` via the passed closure.
+        ///
+        /// Otherwise the same as `client_request_mut()`.
+        pub fn client_request_with_mut<U, F>(
+            &mut self,
+            client: &Client,
+            method: Method,
+            url: U,
+            mut_fn: F,
+        ) -> HyperResult<Response>
+        where
+            U: IntoUrl,
+            F: FnOnce(RequestBuilder) -> RequestBuilder,
+        {
             let mut fields = match self.prepare() {
                 Ok(fields) => fields,
                 Err(err) => {
@@ -521,7 +1083,7 @@ mod hyper {
                 }
             };
 
-            mut_fn(client.post(url))
+            mut_fn(client.request(method, url))
                 .header(::client::hyper::content_type(fields.boundary()))
                 .body(fields.to_body())
                 .send()
@@ -543,4 +1105,347 @@ mod hyper {
             }
         }
     }
+
+    /// Spawn a Hyper server which parses each request with `server::Multipart` and echoes
+    /// back the method followed by `name:filename:data` for every field.
+    #[cfg(all(test, feature = "server"))]
+    fn echo_server() -> ::hyper::server::Listening {
+        use hyper::server::{Request, Response, Server};
+        use server::Multipart;
+        use std::io::Read;
+
+        Server::http("127.0.0.1:0")
+            .unwrap()
+            .handle(|req: Request, resp: Response| {
+                let mut echo = format!("{}\n", req.method);
+
+                match Multipart::from_request(req) {
+                    Ok(mut multipart) => multipart
+                        .foreach_entry(|mut field| {
+                            let mut data = String::new();
+                            field.data.read_to_string(&mut data).unwrap();
+                            echo.push_str(&format!(
+                                "{}:{}:{}\n",
+                                field.headers.name,
+                                field.headers.filename.as_ref().map_or("", |f| &**f),
+                                data
+                            ));
+                        })
+                        .unwrap(),
+                    Err(_) => echo.push_str("not multipart\n"),
+                }
+
+                resp.send(echo.as_bytes()).unwrap();
+            })
+            .unwrap()
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn put_with_file() {
+        use std::io::{Read, Write};
+
+        let mut server = echo_server();
+
+        let mut file = ::tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        file.write_all(b"updated contents").unwrap();
+        let filename = file.path().file_name().unwrap().to_str().unwrap().to_owned();
+
+        let mut multipart = super::Multipart::new();
+        multipart.add_text("id", "42").add_file("object", file.path());
+
+        let url = format!("http://{}/", server.socket);
+        let mut resp = multipart
+            .client_request_with(&Client::new(), Method::Put, &*url)
+            .unwrap();
+
+        let mut echo = String::new();
+        resp.read_to_string(&mut echo).unwrap();
+        server.close().unwrap();
+
+        assert_eq!(
+            echo,
+            format!("PUT\nid::42\nobject:{}:updated contents\n", filename)
+        );
+    }
+}
+
+#[cfg(test)]
+fn body_too_large(err: &io::Error) -> &BodyTooLargeError {
+    err.get_ref()
+        .and_then(|err| err.downcast_ref::<BodyTooLargeError>())
+        .unwrap_or_else(|| panic!("expected BodyTooLargeError, got {:?}", err))
+}
+
+#[test]
+fn content_len_includes_text() {
+    let mut multipart = Multipart::new();
+    multipart.add_text("text", "Hello, world!");
+
+    let mut prepared = multipart.prepare().unwrap();
+
+    let mut body = Vec::new();
+    prepared.read_to_end(&mut body).unwrap();
+
+    assert_eq!(prepared.content_len(), Some(body.len() as u64));
+}
+
+#[test]
+fn max_body_size_predicted() {
+    let mut file = ::tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&[0; 1000]).unwrap();
+
+    let mut multipart = Multipart::new();
+    multipart
+        .max_body_size(500)
+        .add_text("text", "Hello, world!")
+        .add_file("file", file.path());
+
+    let err = match multipart.prepare() {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => err,
+    };
+    assert_eq!(err.field_name.as_deref(), Some("file"));
+
+    let err = body_too_large(&err.error);
+    assert_eq!(err.max_body_size, 500);
+    assert_eq!(err.field_name.as_deref(), Some("file"));
+}
+
+#[test]
+fn max_body_size_streamed() {
+    let mut multipart = Multipart::new();
+    multipart
+        .max_body_size(1000)
+        .add_text("text", "Hello, world!")
+        .add_stream("stream", &[0u8; 2000][..], None::<&str>, None);
+
+    // the size of the stream is unknown
+    let mut prepared = multipart.prepare().unwrap();
+
+    let mut body = Vec::new();
+    let err = prepared.read_to_end(&mut body).unwrap_err();
+
+    assert!(body.len() <= 1000);
+
+    let err = body_too_large(&err);
+    assert_eq!(err.field_name.as_deref(), Some("stream"));
+}
+
+#[test]
+fn urlencoded_serialization() {
+    // compared against `new URLSearchParams(...).toString()`
+    for &(name, text, expected) in &[
+        ("a", "b", "a=b"),
+        ("a b", "c+d", "a+b=c%2Bd"),
+        ("safe", "*-._AZaz09", "safe=*-._AZaz09"),
+        ("punct", "~!'()&=%/?#", "punct=%7E%21%27%28%29%26%3D%25%2F%3F%23"),
+        ("lines", "one\r\ntwo\n", "lines=one%0D%0Atwo%0A"),
+        ("ünïcödé", "€ 😀", "%C3%BCn%C3%AFc%C3%B6d%C3%A9=%E2%82%AC+%F0%9F%98%80"),
+        ("", "", "="),
+    ] {
+        let mut multipart = Multipart::new();
+        multipart.add_text(name, text);
+
+        let (body, _) = multipart.into_urlencoded().unwrap();
+        assert_eq!(body, expected);
+    }
+
+    let mut multipart = Multipart::new();
+    multipart.add_text("a", "1").add_text("b", "2").add_text("a", "3");
+    assert_eq!(multipart.into_urlencoded().unwrap().0, "a=1&b=2&a=3");
+
+    assert_eq!(Multipart::new().into_urlencoded().unwrap().0, "");
+}
+
+#[test]
+fn urlencoded_not_text_only() {
+    let mut multipart = Multipart::new();
+    multipart
+        .add_text("text", "Hello, world!")
+        .add_stream("stream", &b"streamed data"[..], None::<&str>, None);
+
+    let err = multipart.into_urlencoded().unwrap_err();
+    assert_eq!(err.field_name, "stream");
+
+    // the request can still be sent as multipart
+    let mut body = Vec::new();
+    err.into_multipart().prepare().unwrap().read_to_end(&mut body).unwrap();
+
+    let body = String::from_utf8(body).unwrap();
+    assert!(body.contains("Hello, world!") && body.contains("streamed data"), "{}", body);
+}
+
+#[test]
+fn streams_written_in_order() {
+    let mut file = ::tempfile::NamedTempFile::new().unwrap();
+    file.write_all(b"file data").unwrap();
+
+    let mut multipart = Multipart::new();
+    multipart
+        .add_stream("first", &b"first data"[..], None::<&str>, None)
+        .add_text("text", "text data")
+        .add_file("second", file.path())
+        .add_stream("third", &b"third data"[..], Some("third.txt"), None);
+
+    let mut body = Vec::new();
+    multipart.prepare().unwrap().read_to_end(&mut body).unwrap();
+    let body = String::from_utf8(body).unwrap();
+
+    let positions: Vec<usize> = ["text data", "first data", "file data", "third data"].iter()
+        .map(|data| body.find(data).unwrap_or_else(|| panic!("{:?} not in {}", data, body)))
+        .collect();
+
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]), "{}", body);
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn part_stream_round_trip() {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    let (send, recv) = mpsc::sync_channel(1);
+
+    let producer = thread::spawn(move || {
+        for i in 0 .. 4 {
+            // the body shouldn't end while the producer is still working
+            thread::sleep(Duration::from_millis(20));
+
+            let data = format!("part {} data", i).into_bytes();
+            let part = StreamPart::new(format!("field{}", i), Cursor::new(data));
+            let part = if i % 2 == 1 {
+                part.filename(format!("file{}.txt", i)).content_type(mime::TEXT_PLAIN)
+            } else {
+                part
+            };
+
+            send.send(Ok(part)).unwrap();
+        }
+    });
+
+    let stream = Multipart::from_part_stream(recv);
+    let boundary = stream.boundary().to_string();
+    assert_eq!(stream.content_type(), format!("multipart/form-data; boundary={}", boundary));
+
+    let mut multipart = ::server::Multipart::with_body(stream, boundary);
+    let mut count = 0;
+
+    while let Some(mut field) = multipart.read_entry().unwrap() {
+        assert_eq!(&*field.headers.name, &*format!("field{}", count));
+
+        if count % 2 == 1 {
+            assert_eq!(field.headers.filename, Some(format!("file{}.txt", count)));
+            assert_eq!(field.headers.content_type, Some(mime::TEXT_PLAIN));
+        } else {
+            assert_eq!(field.headers.filename, None);
+        }
+
+        let mut data = String::new();
+        field.data.read_to_string(&mut data).unwrap();
+        assert_eq!(data, format!("part {} data", count));
+
+        count += 1;
+    }
+
+    assert_eq!(count, 4);
+    assert!(multipart.finished_cleanly());
+    producer.join().unwrap();
+}
+
+#[test]
+fn part_stream_empty() {
+    let mut body = Vec::new();
+    Multipart::from_part_stream(Vec::new()).read_to_end(&mut body).unwrap();
+    assert!(body.is_empty());
+}
+
+#[test]
+fn part_stream_error_aborts() {
+    let parts = vec![
+        Ok(StreamPart::new("first", Cursor::new("first data"))),
+        Err(io::Error::other("producer failed")),
+        Ok(StreamPart::new("second", Cursor::new("second data"))),
+    ];
+
+    let mut stream = Multipart::from_part_stream(parts);
+    let boundary = stream.boundary().to_string();
+
+    let mut body = Vec::new();
+    let err = stream.read_to_end(&mut body).unwrap_err();
+    assert_eq!(err.to_string(), "producer failed");
+
+    let body = String::from_utf8(body).unwrap();
+    assert!(body.contains("first data"));
+    assert!(!body.contains("second"));
+    // the body was never closed
+    assert!(!body.contains(&format!("{}--", boundary)));
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn estimate_body_size_exact() {
+    use server::{Multipart as ServerMultipart, SaveBuilderConfig};
+
+    let bodies: &[&[u8]] = &[
+        // no fields at all
+        b"--boundary--\r\n",
+        b"--boundary\r\n\
+          Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+          value\r\n\
+          --boundary\r\n\
+          Content-Disposition: form-data; name=\"empty\"\r\n\r\n\
+          \r\n\
+          --boundary\r\n\
+          Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+          another value\r\n\
+          --boundary--\r\n",
+        "--boundary\r\n\
+         Content-Disposition: form-data; name=\"n\u{E4}me \u{1F600}\"\r\n\r\n\
+         caf\u{E9}\r\n\
+         --boundary\r\n\
+         Content-Disposition: form-data; name=\"upload\"; filename=\"r\u{E9}sum\u{E9}.txt\"\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n\
+         file contents\r\n\
+         --boundary\r\n\
+         Content-Disposition: form-data; name=\"empty file\"; filename=\"empty.bin\"\r\n\r\n\
+         \r\n\
+         --boundary\r\n\
+         Content-Disposition: form-data; name=\"binary\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\n\
+         \x00\x01\x02\r\n\
+         --boundary--\r\n".as_bytes(),
+    ];
+
+    for body in bodies {
+        // in memory, then with every field in a file
+        for &memory_threshold in &[u64::MAX, 0] {
+            let config = SaveBuilderConfig::config().memory_threshold(memory_threshold);
+            let entries = ServerMultipart::with_body(*body, "boundary").save_with(&config)
+                .temp().into_result_strict().unwrap();
+
+            let mut multipart = Multipart::new();
+            multipart.add_entries(&entries).unwrap();
+            let mut prepared = multipart.prepare().unwrap();
+
+            let mut sent = Vec::new();
+            prepared.read_to_end(&mut sent).unwrap();
+
+            // `boundary()` is only valid with at least one field
+            let boundary_len = if sent.is_empty() { 16 } else { prepared.boundary().len() };
+
+            assert_eq!(estimate_body_size(&entries, boundary_len), sent.len() as u64,
+                       "{}", String::from_utf8_lossy(&sent));
+
+            // and the server reads the same fields back
+            if !sent.is_empty() {
+                let resent = ServerMultipart::with_body(&sent[..], prepared.boundary()).save()
+                    .temp().into_result_strict().unwrap();
+                assert_eq!(resent.fields_count(), entries.fields_count());
+                assert_eq!(resent.totals().disk_bytes + resent.totals().memory_bytes,
+                           entries.totals().disk_bytes + entries.totals().memory_bytes);
+            }
+        }
+    }
 }
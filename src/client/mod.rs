@@ -16,6 +16,9 @@ use std::io::prelude::*;
 
 use std::path::Path;
 
+#[cfg(feature = "http")]
+use http::header::{self, HeaderMap};
+
 #[cfg(feature = "hyper")]
 pub mod hyper;
 
@@ -25,6 +28,9 @@ mod sized;
 
 pub use self::sized::SizedRequest;
 
+#[cfg(feature = "server")]
+pub use self::lazy::estimate_body_size;
+
 const BOUNDARY_LEN: usize = 16;
 
 macro_rules! map_self {
@@ -131,6 +137,30 @@ impl<S: HttpStream> Multipart<S> {
         )
     }
 
+    /// Write a field with the given headers, e.g. from the server's
+    /// `FieldHeaders::to_header_map()`, and the contents of `stream` as its data.
+    /// Enabled with the `http` feature.
+    ///
+    /// The headers are written in the order of `headers`, with their names in lowercase
+    /// as `http` stores them.
+    ///
+    /// See `write_stream()` for the requirements on `stream`.
+    ///
+    /// ## Errors
+    /// An error of kind `InvalidInput`, before anything is written, if `headers` doesn't have
+    /// exactly one `Content-Disposition` header of type `form-data`, or if a header value contains
+    /// a CR, LF or NUL byte (which `http` only allows when its checks are skipped).
+    ///
+    /// Otherwise, if the reader returned an error, or if something went wrong with the HTTP stream.
+    #[cfg(feature = "http")]
+    pub fn write_part<St: Read>(
+        &mut self,
+        headers: &HeaderMap,
+        stream: &mut St,
+    ) -> Result<&mut Self, S::Error> {
+        map_self!(self, self.writer.write_part(headers, stream))
+    }
+
     /// Finalize the request and return the response from the server, or the last error if set.
     pub fn send(self) -> Result<S::Response, S::Error> {
         self.writer
@@ -272,6 +302,20 @@ impl<'a, W: Write> MultipartWriter<'a, W> {
         }
     }
 
+    #[cfg(feature = "http")]
+    fn write_part<S: Read>(&mut self, headers: &HeaderMap, stream: &mut S) -> io::Result<()> {
+        check_part_headers(headers)?;
+
+        chain_result! {
+            self.write_boundary(),
+            { self.data_written = true; Ok(()) },
+            write_part_headers(headers, &mut self.inner),
+            self.inner.write_all(b"\r\n"),
+            io::copy(stream, &mut self.inner),
+            Ok(())
+        }
+    }
+
     fn write_field_headers(
         &mut self,
         name: &str,
@@ -304,6 +348,42 @@ impl<'a, W: Write> MultipartWriter<'a, W> {
     }
 }
 
+/// Check that `headers` can be written as the headers of a field.
+#[cfg(feature = "http")]
+fn check_part_headers(headers: &HeaderMap) -> io::Result<()> {
+    let invalid = |msg: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+
+    let mut dispositions = headers.get_all(header::CONTENT_DISPOSITION).iter();
+
+    let disposition = match (dispositions.next(), dispositions.next()) {
+        (Some(disposition), None) => disposition.as_bytes(),
+        _ => return invalid("a field must have exactly one Content-Disposition header"),
+    };
+
+    let disp_type = disposition.split(|&b| b == b';').next().unwrap_or(disposition);
+
+    if !String::from_utf8_lossy(disp_type).trim().eq_ignore_ascii_case("form-data") {
+        return invalid("the Content-Disposition of a field must be `form-data`");
+    }
+
+    if headers.values().any(|value| value.as_bytes().iter().any(|&b| b == b'\r' || b == b'\n' || b == 0)) {
+        return invalid("header values must not contain CR, LF or NUL");
+    }
+
+    Ok(())
+}
+
+/// Write `headers` as the header lines of a field, in order and each ending with CRLF.
+#[cfg(feature = "http")]
+fn write_part_headers<W: Write>(headers: &HeaderMap, out: &mut W) -> io::Result<()> {
+    headers.iter().try_for_each(|(name, value)| chain_result! {
+        out.write_all(name.as_str().as_bytes()),
+        out.write_all(b": "),
+        out.write_all(value.as_bytes()),
+        out.write_all(b"\r\n")
+    })
+}
+
 fn mime_filename(path: &Path) -> (Mime, Option<&str>) {
     let content_type = ::mime_guess::from_path(path);
     let filename = opt_filename(path);
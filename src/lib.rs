@@ -14,7 +14,11 @@
 //! * `server`: The server-side abstractions for parsing multipart requests.
 //!
 //! * `mock`: Provides mock implementations of core `client` and `server` traits for debugging
-//! or non-standard use.
+//!   or non-standard use, as well as `FaultyReader` and `FaultyWriter` for injecting I/O faults.
+//!
+//! * `http` (off by default): Conversions between field headers and [`http::HeaderMap`](https://crates.io/crates/http)
+//!   1.x, with `FieldHeaders::to_header_map()` and `from_header_map()` on the server, and
+//!   `Multipart::write_part()` and `lazy::StreamPart::headers()` on the client.
 //!
 //! * `hyper`: Integration with the [Hyper](https://crates.io/crates/hyper) HTTP library
 //! for client and/or server depending on which other feature flags are set.
@@ -29,6 +33,38 @@
 //! * `tiny_http`: Integration with the [`tiny_http`](https://crates.io/crates/tiny_http)
 //! crate. See the [`server::tiny_http`](server/tiny_http/index.html) module for more information.
 //!
+//! * `buf_redux` (enabled by default): Use [`buf_redux`](https://crates.io/crates/buf_redux)
+//!   for buffering requests on the server. Without it, or with the `std-buffer` feature, a reader
+//!   using only `std` is used instead, which copies buffered data around a bit more often.
+//!
+//! * `infer` (off by default): `server::InferSniffer`, detecting the type of saved fields
+//!   with the [`infer`](https://crates.io/crates/infer) crate. See `SaveBuilder::sniffer()`.
+//!
+//! * `unicode-normalization` (off by default): `TextNorm::Nfc`, normalizing saved text fields
+//!   to NFC with the [`unicode-normalization`](https://crates.io/crates/unicode-normalization)
+//!   crate. See `SaveBuilder::text_normalization()`.
+//!
+//! * `digest` (off by default): Verifying the digests clients declare for fields with
+//!   `SaveBuilder::verify_digests()`, the SHA-256 digests of `SavedData::Validated`, and
+//!   `Entries::content_hash()`, using the [`md-5`](https://crates.io/crates/md-5),
+//!   [`sha2`](https://crates.io/crates/sha2) and [`base64`](https://crates.io/crates/base64) crates.
+//!
+//! * `camino` (off by default): UTF-8 paths for saved fields with the
+//!   [`camino`](https://crates.io/crates/camino) crate: `SavedData::utf8_path()`,
+//!   `SaveDir::as_utf8_path()` and `SaveBuilder::with_dir_utf8()`.
+//!
+//! * `multer` (off by default, implies `client` and `server`): Converting between
+//!   [`multer`](https://crates.io/crates/multer)'s fields and `Entries`. See the
+//!   [`server::multer`](server/multer/index.html) module for more information.
+//!
+//! * `archive` (off by default): Writing saved fields out as tar and zip archives, with
+//!   `Entries::write_tar()`, `Entries::write_zip()` and `SaveBuilder::archive_to()`, using the
+//!   [`tar`](https://crates.io/crates/tar), [`crc32fast`](https://crates.io/crates/crc32fast)
+//!   and [`serde_json`](https://crates.io/crates/serde_json) crates.
+//!
+//! * `io-uring` (Linux only, off by default): Alternative `io_uring` backend for writing files
+//!   during save operations. See `SaveBuilder::io_uring()` for more information.
+//!
 //! ### Note: Work in Progress
 //! I have left a number of Request-for-Comments (RFC) questions on various APIs and other places
 //! in the code as there are some cases where I'm not sure what the desirable behavior is.
@@ -57,6 +93,32 @@ extern crate quick_error;
 #[cfg(feature = "server")]
 extern crate safemem;
 
+#[cfg(feature = "http")]
+extern crate http;
+
+#[cfg(feature = "infer")]
+extern crate infer;
+
+#[cfg(feature = "unicode-normalization")]
+extern crate unicode_normalization;
+
+#[cfg(feature = "digest")]
+extern crate base64;
+#[cfg(feature = "digest")]
+extern crate md5;
+#[cfg(feature = "digest")]
+extern crate sha2;
+
+#[cfg(feature = "archive")]
+extern crate crc32fast;
+#[cfg(feature = "archive")]
+extern crate serde_json;
+#[cfg(feature = "archive")]
+extern crate tar;
+
+#[cfg(feature = "camino")]
+extern crate camino;
+
 #[cfg(feature = "hyper")]
 extern crate hyper;
 
@@ -66,6 +128,15 @@ extern crate iron;
 #[cfg(feature = "tiny_http")]
 extern crate tiny_http;
 
+#[cfg(feature = "multer")]
+extern crate multer;
+
+#[cfg(feature = "multer")]
+extern crate futures_executor;
+
+#[cfg(feature = "multer")]
+extern crate futures_util;
+
 #[cfg(test)]
 extern crate env_logger;
 
@@ -114,16 +185,44 @@ pub mod client;
 #[cfg(feature = "server")]
 pub mod server;
 
+pub mod prelude;
+
 #[cfg(all(test, feature = "client", feature = "server"))]
 mod local_test;
 
 fn random_alphanumeric(len: usize) -> String {
+    #[cfg(test)]
+    {
+        let seeded = SEEDED_RNG.with(|rng| rng.borrow_mut().as_mut().map(|rng| {
+            rng.sample_iter(&rand::distributions::Alphanumeric).take(len).collect()
+        }));
+
+        if let Some(seeded) = seeded {
+            return seeded;
+        }
+    }
+
     rand::thread_rng()
         .sample_iter(&rand::distributions::Alphanumeric)
         .take(len)
         .collect()
 }
 
+#[cfg(test)]
+thread_local! {
+    /// Set by `seed_random_names()`.
+    static SEEDED_RNG: ::std::cell::RefCell<Option<rand::rngs::StdRng>> = const { ::std::cell::RefCell::new(None) };
+}
+
+/// Make `random_alphanumeric()` on this thread repeat the same sequence for the same `seed`,
+/// or go back to `thread_rng()` with `None`.
+#[cfg(test)]
+fn seed_random_names(seed: Option<u64>) {
+    use rand::SeedableRng;
+
+    SEEDED_RNG.with(|rng| *rng.borrow_mut() = seed.map(rand::rngs::StdRng::seed_from_u64));
+}
+
 #[cfg(test)]
 fn init_log() {
     let _ = env_logger::try_init();
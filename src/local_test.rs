@@ -494,3 +494,122 @@ fn rand_mime() -> Mime {
     .unwrap()
     .clone()
 }
+
+#[cfg(feature = "http")]
+#[test]
+fn header_map_client_to_server() {
+    use client::Multipart;
+    use server::Multipart as ServerMultipart;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mut headers = FieldHeaders::new("upload");
+    headers.filename = Some("résumé.pdf".into());
+    headers.content_type = Some(mime::APPLICATION_PDF);
+    headers.size = Some(5);
+    headers.modification_date = Some(UNIX_EPOCH + Duration::from_secs(855_782_991));
+    headers.extra_params.push(("x-note".into(), "a \"quoted\" note".into()));
+
+    let map = headers.to_header_map().unwrap();
+
+    let mut multipart = Multipart::from_request(ClientRequest::default()).unwrap();
+    multipart.write_part(&map, &mut &b"hello"[..]).unwrap();
+    multipart.write_text("text", "world").unwrap();
+    let buf = multipart.send().unwrap();
+
+    let mut multipart = ServerMultipart::from_request(buf.for_server())
+        .unwrap_or_else(|_| panic!("Buffer should be multipart!"));
+
+    let mut field = multipart.read_entry_mut().unwrap_opt().expect("expected a field");
+    assert_eq!(format!("{:?}", field.headers), format!("{:?}", headers));
+    assert_eq!(field.headers.to_header_map().unwrap(), map);
+
+    let mut data = String::new();
+    field.data.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "hello");
+
+    let field = multipart.read_entry_mut().unwrap_opt().expect("expected a field");
+    assert_eq!(&*field.headers.name, "text");
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn header_map_client_invalid() {
+    use client::Multipart;
+
+    use http::header::{self, HeaderMap, HeaderValue};
+
+    use std::io;
+
+    let mut no_disposition = HeaderMap::new();
+    no_disposition.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+    let mut attachment = HeaderMap::new();
+    attachment.insert(header::CONTENT_DISPOSITION, HeaderValue::from_static("attachment; name=\"a\""));
+
+    let mut duplicate = HeaderMap::new();
+    duplicate.append(header::CONTENT_DISPOSITION, HeaderValue::from_static("form-data; name=\"a\""));
+    duplicate.append(header::CONTENT_DISPOSITION, HeaderValue::from_static("form-data; name=\"b\""));
+
+    for headers in &[no_disposition, attachment, duplicate] {
+        let mut multipart = Multipart::from_request(ClientRequest::default()).unwrap();
+
+        match multipart.write_part(headers, &mut io::empty()) {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => (),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected an error for {:?}", headers),
+        }
+
+        // nothing but the closing boundary
+        let buf = multipart.send().unwrap();
+        assert_eq!(buf.buf, format!("--{}--\r\n", buf.boundary).into_bytes());
+    }
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn header_map_stream_part() {
+    use client::lazy::{Multipart, StreamPart};
+    use server::Multipart as ServerMultipart;
+
+    use http::header::{self, HeaderMap, HeaderValue};
+
+    use std::io;
+
+    let mut headers = FieldHeaders::new("upload");
+    headers.filename = Some("notes.txt".into());
+    headers.content_type = Some(mime::TEXT_PLAIN);
+    headers.extra_params.push(("x-note".into(), "kept".into()));
+
+    let map = headers.to_header_map().unwrap();
+
+    let parts = vec![
+        StreamPart::new("upload", &b"hello"[..]).headers(map.clone()),
+        Ok(StreamPart::new("text", &b"world"[..])),
+    ];
+
+    let stream = Multipart::from_part_stream(parts);
+    let boundary = stream.boundary().to_string();
+    let mut multipart = ServerMultipart::with_body(stream, boundary);
+
+    let mut field = multipart.read_entry_mut().unwrap_opt().expect("expected a field");
+    assert_eq!(format!("{:?}", field.headers), format!("{:?}", headers));
+    assert_eq!(field.headers.to_header_map().unwrap(), map);
+
+    let mut data = String::new();
+    field.data.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "hello");
+
+    let field = multipart.read_entry_mut().unwrap_opt().expect("expected a field");
+    assert_eq!(&*field.headers.name, "text");
+    assert!(multipart.read_entry_mut().unwrap_opt().is_none());
+
+    let mut attachment = HeaderMap::new();
+    attachment.insert(header::CONTENT_DISPOSITION, HeaderValue::from_static("attachment; name=\"a\""));
+
+    match StreamPart::new("a", io::empty()).headers(attachment) {
+        Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => (),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(part) => panic!("expected an error, got {:?}", part),
+    }
+}
@@ -5,8 +5,9 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 //! Mocked types for client-side and server-side APIs.
+use std::collections::VecDeque;
 use std::io::{self, Read, Write};
-use std::fmt;
+use std::{cmp, fmt};
 
 use rand::{self, Rng};
 use rand::prelude::ThreadRng;
@@ -201,3 +202,137 @@ impl<'s, W: Write> Write for StdoutTee<'s, W> {
         self.stdout.flush()
     }
 }
+
+/// What a `FaultyReader` or `FaultyWriter` does on one call to `read()` or `write()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Pass the call through to the inner reader or writer.
+    Pass,
+    /// Pass the call through with at most this many bytes, e.g. a short read or write.
+    /// `Short(0)` is the same as `Zero`.
+    Short(usize),
+    /// Fail with `io::ErrorKind::Interrupted`.
+    Interrupted,
+    /// Fail with `io::ErrorKind::WouldBlock`.
+    WouldBlock,
+    /// Return `Ok(0)` without calling the inner reader or writer.
+    Zero,
+}
+
+impl Fault {
+    /// All the faults, with short transfers of each of `lens`.
+    pub fn all(lens: &[usize]) -> Vec<Fault> {
+        let mut faults = vec![Fault::Pass, Fault::Interrupted, Fault::WouldBlock, Fault::Zero];
+        faults.extend(lens.iter().map(|&len| Fault::Short(len)));
+        faults
+    }
+
+    /// Apply this fault to a call with a buffer of `buf_len` bytes, passing the call on to
+    /// `call` with the number of bytes to transfer.
+    fn apply<F>(self, buf_len: usize, call: F) -> io::Result<usize> where F: FnOnce(usize) -> io::Result<usize> {
+        match self {
+            Fault::Pass => call(buf_len),
+            Fault::Short(0) | Fault::Zero => Ok(0),
+            Fault::Short(len) => call(cmp::min(len, buf_len)),
+            Fault::Interrupted => Err(io::Error::new(io::ErrorKind::Interrupted, "injected fault")),
+            Fault::WouldBlock => Err(io::Error::new(io::ErrorKind::WouldBlock, "injected fault")),
+        }
+    }
+}
+
+/// A `Read` adapter which follows a script of `Fault`s, one for each call to `read()`,
+/// for testing how reading handles errors and short reads.
+///
+/// Once the script runs out, calls are passed through.
+#[derive(Debug)]
+pub struct FaultyReader<R> {
+    inner: R,
+    script: VecDeque<Fault>,
+    delivered: u64,
+}
+
+impl<R> FaultyReader<R> {
+    /// Wrap `inner` with the given script.
+    pub fn new(inner: R, script: Vec<Fault>) -> Self {
+        FaultyReader { inner, script: script.into(), delivered: 0 }
+    }
+
+    /// The number of bytes returned from `read()` so far.
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// The faults which haven't been used yet.
+    pub fn remaining_script(&self) -> &VecDeque<Fault> {
+        &self.script
+    }
+
+    /// Get the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for FaultyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let fault = self.script.pop_front().unwrap_or(Fault::Pass);
+        let inner = &mut self.inner;
+        let read = fault.apply(buf.len(), |len| inner.read(&mut buf[..len]))?;
+
+        self.delivered += read as u64;
+        Ok(read)
+    }
+}
+
+/// A `Write` adapter which follows a script of `Fault`s, one for each call to `write()`,
+/// for testing how writing handles errors and short writes.
+///
+/// Once the script runs out, calls are passed through. `flush()` is always passed through.
+#[derive(Debug)]
+pub struct FaultyWriter<W> {
+    inner: W,
+    script: VecDeque<Fault>,
+    written: u64,
+}
+
+impl<W> FaultyWriter<W> {
+    /// Wrap `inner` with the given script.
+    pub fn new(inner: W, script: Vec<Fault>) -> Self {
+        FaultyWriter { inner, script: script.into(), written: 0 }
+    }
+
+    /// The number of bytes accepted by `write()` so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// The faults which haven't been used yet.
+    pub fn remaining_script(&self) -> &VecDeque<Fault> {
+        &self.script
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for FaultyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let fault = self.script.pop_front().unwrap_or(Fault::Pass);
+        let inner = &mut self.inner;
+        let written = fault.apply(buf.len(), |len| inner.write(&buf[..len]))?;
+
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
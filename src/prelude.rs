@@ -0,0 +1,27 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+//! The types most users of this crate need, for a single glob import.
+//!
+//! ```
+//! # #[cfg(feature = "server")]
+//! use multipart::prelude::*;
+//! ```
+//!
+//! Everything here is re-exported from where it's defined, so the longer paths keep working;
+//! this module only changes when those types are added, renamed or removed.
+//!
+//! `client::Multipart` is exported as `ClientMultipart`, as the server's `Multipart` is the one
+//! most consumers use.
+
+#[cfg(feature = "server")]
+pub use server::{FieldHeaders, Multipart, MultipartData, MultipartField};
+
+#[cfg(feature = "server")]
+pub use server::save::{Entries, PartialReason, SaveBuilder, SaveDir, SaveResult, SavedData, SavedField};
+
+#[cfg(feature = "client")]
+pub use client::Multipart as ClientMultipart;
@@ -0,0 +1,887 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Writing saved fields out as tar and zip archives, for `Entries::write_tar()`,
+//! `Entries::write_zip()` and `SaveBuilder::archive_to()`.
+//!
+//! Tar archives are written with the `tar` crate. Zip archives are written here, as
+//! `archive_to()` needs to stream each field into a writer which can't seek, and to leave out
+//! a field which wasn't finished: only regular files in the root of the archive are supported,
+//! stored without compression, with their CRC and sizes after the data (in a data descriptor)
+//! and Zip64 records where an entry or the archive reaches 4 GiB.
+
+use crc32fast::Hasher as Crc32;
+use tar;
+
+use super::date::date_from_days;
+use super::field::FieldHeaders;
+use super::filename::last_component;
+use super::save::{Entries, SavedData, SavedField};
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The name of the entry `SaveBuilder::archive_to()` writes the text fields into.
+pub const MANIFEST_NAME: &str = "fields.json";
+
+/// The longest entry name in bytes, which fits the name field of a ustar header.
+const MAX_NAME_LEN: usize = 100;
+
+/// An extension longer than this is cut off with the rest of the name if it's too long.
+const MAX_EXTENSION_LEN: usize = 16;
+
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_CENTRAL_DIR: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIR: u32 = 0x0606_4b50;
+const ZIP64_END_LOCATOR: u32 = 0x0706_4b50;
+
+/// The header ID of the Zip64 extra field.
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// The sizes and CRC follow the data (bit 3), and the name is UTF-8 (bit 11).
+const ZIP_FLAGS: u16 = 0x0808;
+/// Version 2.0, the first with data descriptors.
+const ZIP_VERSION: u16 = 20;
+/// Version 4.5, the first with Zip64.
+const ZIP64_VERSION: u16 = 45;
+/// Made by Unix, so the permissions in the external attributes are used.
+const ZIP_MADE_BY_UNIX: u16 = 3 << 8;
+
+/// A size or offset of at least this is stored in a Zip64 field, with this in its place.
+const ZIP64_LIMIT: u64 = 0xFFFF_FFFF;
+/// The same for the number of entries.
+const ZIP64_COUNT_LIMIT: u64 = 0xFFFF;
+
+/// `-rw-r--r--` for every entry.
+const FILE_MODE: u32 = 0o644;
+
+/// Write the fields of `entries` with data as a tar archive, for `Entries::write_tar()`.
+pub fn write_tar<W: Write>(entries: &Entries, dest: W) -> io::Result<u64> {
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let mut names = EntryNames::new();
+    let mut tar = tar::Builder::new(Counted { inner: dest, written: 0 });
+
+    for field in archived_fields(entries) {
+        let len = field.data.size();
+
+        let mut header = tar::Header::new_ustar();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(len);
+        header.set_mode(FILE_MODE);
+        header.set_mtime(mtime);
+
+        let data = ExactLen::new(field.data.readable()?, len);
+        tar.append_data(&mut header, names.name_for(&field.headers), data)
+            .map_err(|e| field_error(field, e))?;
+    }
+
+    let mut dest = tar.into_inner()?;
+    dest.flush()?;
+    Ok(dest.written)
+}
+
+/// Write the fields of `entries` with data as a zip archive, for `Entries::write_zip()`.
+pub fn write_zip<W: Write>(entries: &Entries, dest: W) -> io::Result<u64> {
+    let mut names = EntryNames::new();
+    let mut zip = ZipWriter::new(dest);
+
+    for field in archived_fields(entries) {
+        zip.start_entry(names.name_for(&field.headers));
+
+        let mut data = ExactLen::new(field.data.readable()?, field.data.size());
+        let copied = io::copy(&mut data, &mut zip).and_then(|_| zip.finish_entry());
+        copied.map_err(|e| field_error(field, e))?;
+    }
+
+    zip.finish()
+}
+
+/// The fields whose data can be read, skipping those which were streamed, redacted or only
+/// validated.
+fn archived_fields(entries: &Entries) -> impl Iterator<Item = &SavedField> {
+    entries.fields.values().flatten().filter(|field| !matches!(
+        field.data,
+        SavedData::Streamed { .. } | SavedData::Redacted { .. } | SavedData::Validated { .. }
+    ))
+}
+
+fn field_error(field: &SavedField, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("failed to archive field {:?}: {}", field.headers.name, e))
+}
+
+/// Reads the `len` bytes of a field's data, failing if there are fewer: a file which shrank
+/// after it was saved would leave the archive inconsistent.
+struct ExactLen<R> {
+    inner: io::Take<R>,
+    len: u64,
+}
+
+impl<R: Read> ExactLen<R> {
+    fn new(inner: R, len: u64) -> Self {
+        ExactLen { inner: inner.take(len), len }
+    }
+}
+
+impl<R: Read> Read for ExactLen<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+
+        if read == 0 && !buf.is_empty() && self.inner.limit() > 0 {
+            let copied = self.len - self.inner.limit();
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      format!("expected {} bytes of data but read {}", self.len, copied)));
+        }
+
+        Ok(read)
+    }
+}
+
+/// Counts the bytes written to `inner`, for the size of a tar archive.
+struct Counted<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A zip archive which `SaveBuilder::archive_to()` writes file fields into as they're read.
+pub trait FieldArchive {
+    /// Start an entry for the field with `headers`, dropping an unfinished one,
+    /// and return the writer for its data.
+    fn start_field(&mut self, headers: &FieldHeaders) -> &mut dyn Write;
+
+    /// Finish the entry of the field whose data was written.
+    fn finish_field(&mut self) -> io::Result<()>;
+}
+
+/// The `FieldArchive` of `SaveBuilder::archive_to()`.
+pub struct ZipFields<W> {
+    zip: ZipWriter<W>,
+    names: EntryNames,
+    manifest: bool,
+}
+
+impl<W: Write> ZipFields<W> {
+    /// Write the fields to `dest`, with the text fields in `MANIFEST_NAME` if `manifest` is set.
+    pub fn new(dest: W, manifest: bool) -> Self {
+        let mut names = EntryNames::new();
+
+        if manifest {
+            names.reserve(MANIFEST_NAME);
+        }
+
+        ZipFields { zip: ZipWriter::new(dest), names, manifest }
+    }
+
+    /// Drop the field which wasn't finished, if any, write the text fields of `entries` to the
+    /// manifest and finish the archive, so it's readable up to the last finished field.
+    pub fn finish(mut self, entries: Option<&Entries>) -> io::Result<u64> {
+        self.zip.abandon_entry();
+
+        if let (true, Some(entries)) = (self.manifest, entries) {
+            self.zip.start_entry(MANIFEST_NAME.to_string());
+            serde_json::to_writer(&mut self.zip, &manifest(entries))?;
+            self.zip.write_all(b"\n")?;
+            self.zip.finish_entry()?;
+        }
+
+        self.zip.finish()
+    }
+}
+
+impl<W: Write> FieldArchive for ZipFields<W> {
+    fn start_field(&mut self, headers: &FieldHeaders) -> &mut dyn Write {
+        self.zip.start_entry(self.names.name_for(headers));
+        &mut self.zip
+    }
+
+    fn finish_field(&mut self) -> io::Result<()> {
+        self.zip.finish_entry()
+    }
+}
+
+/// The text fields of `entries` (those without a filename whose data is text), mapping each
+/// name to its values, in order, for a JSON object.
+fn manifest(entries: &Entries) -> BTreeMap<&str, Vec<&str>> {
+    entries.fields.iter()
+        .map(|(name, fields)| {
+            let values: Vec<_> = fields.iter()
+                .filter(|field| field.headers.filename.is_none())
+                .filter_map(SavedField::as_text)
+                .collect();
+            (&**name, values)
+        })
+        .filter(|(_, values)| !values.is_empty())
+        .collect()
+}
+
+/// Assigns each field a unique name in the archive: its filename, or else its field name,
+/// made safe to extract.
+///
+/// A name which was already used (ignoring case) gets a number before its extension,
+/// e.g. `photo (1).jpg`.
+struct EntryNames {
+    used: HashSet<String>,
+}
+
+impl EntryNames {
+    fn new() -> Self {
+        EntryNames { used: HashSet::new() }
+    }
+
+    fn reserve(&mut self, name: &str) {
+        self.used.insert(name.to_lowercase());
+    }
+
+    fn name_for(&mut self, headers: &FieldHeaders) -> String {
+        let name = headers.filename.as_ref()
+            .map(|filename| sanitize(last_component(filename)))
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| sanitize(&headers.name.replace(&['/', '\\'][..], "_")));
+        let name = if name.is_empty() { "field".to_string() } else { name };
+
+        let (stem, extension) = split_extension(&name);
+        let mut candidate = fit_name(stem, "", extension);
+        let mut count = 0u64;
+
+        while !self.used.insert(candidate.to_lowercase()) {
+            count += 1;
+            candidate = fit_name(stem, &format!(" ({})", count), extension);
+        }
+
+        candidate
+    }
+}
+
+/// Drop control and bidirectional formatting characters and the trailing dots and spaces
+/// which Windows strips; `.` and `..` become empty.
+fn sanitize(name: &str) -> String {
+    let name: String = name.chars()
+        .filter(|&c| !c.is_control() && !is_bidi_control(c))
+        .collect();
+    let name = name.trim_end_matches(&['.', ' '][..]).trim_start_matches(' ');
+
+    name.to_string()
+}
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}' ..= '\u{202E}' | '\u{2066}' ..= '\u{2069}')
+}
+
+/// Split off the extension with its dot, unless the name is only an extension, e.g. `.bashrc`.
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 && name.len() - dot <= MAX_EXTENSION_LEN => name.split_at(dot),
+        _ => (name, ""),
+    }
+}
+
+/// `stem`, `suffix` and `extension`, with `stem` cut off to fit `MAX_NAME_LEN`.
+fn fit_name(stem: &str, suffix: &str, extension: &str) -> String {
+    let mut len = MAX_NAME_LEN.saturating_sub(suffix.len() + extension.len()).min(stem.len());
+
+    while !stem.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    format!("{}{}{}", &stem[..len], suffix, extension)
+}
+
+
+/// Writes stored entries into a zip archive, each followed by a data descriptor so its size
+/// needn't be known beforehand.
+///
+/// An entry is only added to the central directory once it's finished, so one which is
+/// abandoned is skipped by readers.
+struct ZipWriter<W> {
+    dest: W,
+    offset: u64,
+    dos_time: u16,
+    dos_date: u16,
+    entries: Vec<ZipEntry>,
+    current: Option<CurrentEntry>,
+}
+
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    len: u64,
+    offset: u64,
+}
+
+struct CurrentEntry {
+    name: String,
+    offset: u64,
+    crc: Crc32,
+    len: u64,
+    /// The local header has been written.
+    started: bool,
+}
+
+impl<W: Write> ZipWriter<W> {
+    fn new(dest: W) -> Self {
+        let (dos_time, dos_date) = dos_date_time(SystemTime::now());
+        ZipWriter { dest, offset: 0, dos_time, dos_date, entries: Vec::new(), current: None }
+    }
+
+    /// Start a new entry; its local header is written with the first data.
+    fn start_entry(&mut self, name: String) {
+        self.abandon_entry();
+        self.current = Some(CurrentEntry { name, offset: 0, crc: Crc32::new(), len: 0, started: false });
+    }
+
+    /// Drop the current entry without adding it to the central directory.
+    fn abandon_entry(&mut self) {
+        if let Some(entry) = self.current.take() {
+            if entry.started {
+                warn!("Dropping unfinished entry {:?} from the archive", entry.name);
+            }
+        }
+    }
+
+    fn write_local_header(&mut self) -> io::Result<()> {
+        let mut entry = self.current.take().expect("no entry was started");
+        entry.offset = self.offset;
+
+        let mut header = Vec::with_capacity(30 + entry.name.len());
+        put_u32(&mut header, ZIP_LOCAL_HEADER);
+        put_u16(&mut header, ZIP_VERSION);
+        put_u16(&mut header, ZIP_FLAGS);
+        put_u16(&mut header, 0); // stored
+        put_u16(&mut header, self.dos_time);
+        put_u16(&mut header, self.dos_date);
+        // the CRC and sizes are in the data descriptor
+        header.extend_from_slice(&[0; 12]);
+        put_u16(&mut header, entry.name.len() as u16);
+        put_u16(&mut header, 0); // extra field length
+        header.extend_from_slice(entry.name.as_bytes());
+
+        entry.started = true;
+        self.current = Some(entry);
+        self.write_raw(&header)
+    }
+
+    /// Write the data descriptor of the current entry and add it to the central directory.
+    ///
+    /// The sizes in the descriptor of an entry of 4 GiB or more are 8 bytes each, as Zip64
+    /// readers expect of an entry with a Zip64 extra field in the central directory.
+    fn finish_entry(&mut self) -> io::Result<()> {
+        if !self.current.as_ref().expect("no entry was started").started {
+            self.write_local_header()?;
+        }
+
+        let entry = self.current.take().expect("no entry was started");
+        let crc = entry.crc.finalize();
+
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, ZIP_DATA_DESCRIPTOR);
+        put_u32(&mut descriptor, crc);
+
+        if entry.len >= ZIP64_LIMIT {
+            put_u64(&mut descriptor, entry.len); // compressed
+            put_u64(&mut descriptor, entry.len);
+        } else {
+            put_u32(&mut descriptor, entry.len as u32); // compressed
+            put_u32(&mut descriptor, entry.len as u32);
+        }
+
+        self.write_raw(&descriptor)?;
+
+        self.entries.push(ZipEntry { name: entry.name, crc, len: entry.len, offset: entry.offset });
+        Ok(())
+    }
+
+    /// Write the central directory and return the size of the archive.
+    fn finish(mut self) -> io::Result<u64> {
+        self.abandon_entry();
+
+        let start = self.offset;
+        let mut central = Vec::new();
+
+        for entry in &self.entries {
+            let mut zip64 = Vec::new();
+
+            if entry.len >= ZIP64_LIMIT {
+                put_u64(&mut zip64, entry.len);
+                put_u64(&mut zip64, entry.len); // compressed
+            }
+
+            if entry.offset >= ZIP64_LIMIT {
+                put_u64(&mut zip64, entry.offset);
+            }
+
+            let version = if zip64.is_empty() { ZIP_VERSION } else { ZIP64_VERSION };
+            let extra_len = if zip64.is_empty() { 0 } else { 4 + zip64.len() as u16 };
+
+            put_u32(&mut central, ZIP_CENTRAL_HEADER);
+            put_u16(&mut central, ZIP_MADE_BY_UNIX | version);
+            put_u16(&mut central, version);
+            put_u16(&mut central, ZIP_FLAGS);
+            put_u16(&mut central, 0); // stored
+            put_u16(&mut central, self.dos_time);
+            put_u16(&mut central, self.dos_date);
+            put_u32(&mut central, entry.crc);
+            put_u32(&mut central, zip64_u32(entry.len)); // compressed
+            put_u32(&mut central, zip64_u32(entry.len));
+            put_u16(&mut central, entry.name.len() as u16);
+            put_u16(&mut central, extra_len);
+            central.extend_from_slice(&[0; 6]); // comment length, disk, internal attributes
+            put_u32(&mut central, (0o100_000 | FILE_MODE) << 16);
+            put_u32(&mut central, zip64_u32(entry.offset));
+            central.extend_from_slice(entry.name.as_bytes());
+
+            if !zip64.is_empty() {
+                put_u16(&mut central, ZIP64_EXTRA_ID);
+                put_u16(&mut central, zip64.len() as u16);
+                central.extend_from_slice(&zip64);
+            }
+        }
+
+        let count = self.entries.len() as u64;
+        let central_len = central.len() as u64;
+
+        if count >= ZIP64_COUNT_LIMIT || central_len >= ZIP64_LIMIT || start >= ZIP64_LIMIT {
+            let zip64_end = start + central_len;
+
+            put_u32(&mut central, ZIP64_END_OF_CENTRAL_DIR);
+            put_u64(&mut central, 44); // the size of the rest of the record
+            put_u16(&mut central, ZIP_MADE_BY_UNIX | ZIP64_VERSION);
+            put_u16(&mut central, ZIP64_VERSION);
+            central.extend_from_slice(&[0; 8]); // disk numbers
+            put_u64(&mut central, count);
+            put_u64(&mut central, count);
+            put_u64(&mut central, central_len);
+            put_u64(&mut central, start);
+
+            put_u32(&mut central, ZIP64_END_LOCATOR);
+            put_u32(&mut central, 0); // the disk of the Zip64 end of central directory
+            put_u64(&mut central, zip64_end);
+            put_u32(&mut central, 1); // disks
+        }
+
+        put_u32(&mut central, ZIP_END_OF_CENTRAL_DIR);
+        central.extend_from_slice(&[0; 4]); // disk numbers
+        put_u16(&mut central, count.min(ZIP64_COUNT_LIMIT) as u16);
+        put_u16(&mut central, count.min(ZIP64_COUNT_LIMIT) as u16);
+        put_u32(&mut central, zip64_u32(central_len));
+        put_u32(&mut central, zip64_u32(start));
+        put_u16(&mut central, 0); // comment length
+
+        self.write_raw(&central)?;
+        self.dest.flush()?;
+        Ok(self.offset)
+    }
+
+    fn write_raw(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.dest.write_all(buf)?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ZipWriter<W> {
+    /// Write data of the current entry.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.current.as_ref().expect("no entry was started").started {
+            self.write_local_header()?;
+        }
+
+        let entry = self.current.as_mut().expect("no entry was started");
+        let written = self.dest.write(buf)?;
+        entry.crc.update(&buf[..written]);
+        entry.len += written as u64;
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.dest.flush()
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// `value` for a 4-byte field, or `0xFFFF_FFFF` if it's in a Zip64 field instead.
+fn zip64_u32(value: u64) -> u32 {
+    value.min(ZIP64_LIMIT) as u32
+}
+
+/// The MS-DOS time and date of `time` in UTC, as zip headers store it; before 1980 is 1980.
+fn dos_date_time(time: SystemTime) -> (u16, u16) {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (year, month, day) = date_from_days((secs / 86_400) as i64);
+
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+
+    let secs = secs % 86_400;
+    let dos_time = ((secs / 3600) << 11) | (((secs / 60) % 60) << 5) | ((secs % 60) / 2);
+    let dos_date = (((year - 1980).min(127) as u32) << 9) | (month << 5) | day;
+
+    (dos_time as u16, dos_date as u16)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use server::Multipart;
+    use server::save::{PartialReason, SaveResult};
+
+    use std::str;
+
+    /// The fields of the fixture: name, filename and data.
+    fn fixture() -> Vec<(&'static str, Option<&'static str>, Vec<u8>)> {
+        vec![
+            ("greeting", None, b"hello \"world\"\n".to_vec()),
+            ("upload", Some("report.txt"), (0 .. 5000u32).map(|i| (i % 251) as u8).collect()),
+            ("upload", Some("Report.TXT"), b"the same name, ignoring case".to_vec()),
+            ("other", Some("../../etc/passwd"), b"root:x:0:0".to_vec()),
+            ("evil", Some(".."), Vec::new()),
+            ("tag", None, b"cold storage".to_vec()),
+        ]
+    }
+
+    fn fixture_body() -> Vec<u8> {
+        let mut body = Vec::new();
+
+        for (name, filename, data) in fixture() {
+            body.extend_from_slice(b"--boundary\r\nContent-Disposition: form-data; name=\"");
+            body.extend_from_slice(name.as_bytes());
+
+            if let Some(filename) = filename {
+                body.extend_from_slice(b"\"; filename=\"");
+                body.extend_from_slice(filename.as_bytes());
+            }
+
+            body.extend_from_slice(b"\"\r\n\r\n");
+            body.extend_from_slice(&data);
+            body.extend_from_slice(b"\r\n");
+        }
+
+        body.extend_from_slice(b"--boundary--\r\n");
+        body
+    }
+
+    /// The data of the fixture field with `filename`.
+    fn data_of(filename: &str) -> Vec<u8> {
+        fixture().into_iter().find(|field| field.1 == Some(filename)).unwrap().2
+    }
+
+    /// Read the entries of a tar archive written by `write_tar()`.
+    fn read_tar(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        tar::Archive::new(archive).entries().unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                assert_eq!(entry.header().entry_type(), tar::EntryType::Regular);
+                assert_eq!(entry.header().mode().unwrap(), FILE_MODE);
+
+                let name = entry.path().unwrap().to_str().unwrap().to_string();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                (name, data)
+            })
+            .collect()
+    }
+
+    fn u16_at(buf: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([buf[at], buf[at + 1]])
+    }
+
+    fn u32_at(buf: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+    }
+
+    /// Read the entries in the central directory of a zip archive written by `ZipWriter`,
+    /// checking their local headers, CRCs and data descriptors.
+    fn read_zip(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(archive, end), ZIP_END_OF_CENTRAL_DIR);
+
+        let count = u16_at(archive, end + 10);
+        let mut at = u32_at(archive, end + 16) as usize;
+        assert_eq!(at + u32_at(archive, end + 12) as usize, end);
+
+        let mut files = Vec::new();
+
+        for _ in 0 .. count {
+            assert_eq!(u32_at(archive, at), ZIP_CENTRAL_HEADER);
+            let crc = u32_at(archive, at + 16);
+            let len = u32_at(archive, at + 24) as usize;
+            let name_len = u16_at(archive, at + 28) as usize;
+            let offset = u32_at(archive, at + 42) as usize;
+            let name = str::from_utf8(&archive[at + 46 .. at + 46 + name_len]).unwrap().to_string();
+            at += 46 + name_len;
+
+            assert_eq!(u32_at(archive, offset), ZIP_LOCAL_HEADER);
+            assert_eq!(u16_at(archive, offset + 6), ZIP_FLAGS);
+            assert_eq!(&archive[offset + 30 .. offset + 30 + name_len], name.as_bytes());
+
+            let start = offset + 30 + name_len;
+            let data = archive[start .. start + len].to_vec();
+
+            assert_eq!(crc32fast::hash(&data), crc, "{}", name);
+
+            let descriptor = start + len;
+            assert_eq!(u32_at(archive, descriptor), ZIP_DATA_DESCRIPTOR);
+            assert_eq!(u32_at(archive, descriptor + 4), crc);
+            assert_eq!(u32_at(archive, descriptor + 12) as usize, len);
+
+            files.push((name, data));
+        }
+
+        files
+    }
+
+    /// The archived files of the fixture saved to `Entries`, in the order of `Entries::fields`.
+    fn expected_files() -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("evil".to_string(), Vec::new()),
+            ("greeting".to_string(), b"hello \"world\"\n".to_vec()),
+            ("passwd".to_string(), data_of("../../etc/passwd")),
+            ("tag".to_string(), b"cold storage".to_vec()),
+            ("report.txt".to_string(), data_of("report.txt")),
+            ("Report (1).TXT".to_string(), data_of("Report.TXT")),
+        ]
+    }
+
+    #[test]
+    fn test_write_tar() {
+        ::init_log();
+
+        let body = fixture_body();
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(100)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+        assert!(entries.fields["upload"][0].data.is_file());
+
+        let mut archive = Vec::new();
+        let written = entries.write_tar(&mut archive).unwrap();
+
+        assert_eq!(written, archive.len() as u64);
+        assert_eq!(archive.len() % 512, 0);
+        assert_eq!(read_tar(&archive), expected_files());
+    }
+
+    #[test]
+    fn test_write_zip() {
+        ::init_log();
+
+        let body = fixture_body();
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(100)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        let mut archive = Vec::new();
+        let written = entries.write_zip(&mut archive).unwrap();
+
+        assert_eq!(written, archive.len() as u64);
+        assert_eq!(read_zip(&archive), expected_files());
+
+        // a streamed field has no data to archive
+        let mut streamed = Vec::new();
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .stream_field_to("other", &mut streamed)
+            .into_result_strict()
+            .unwrap();
+
+        let mut archive = Vec::new();
+        entries.write_zip(&mut archive).unwrap();
+        let names: Vec<_> = read_zip(&archive).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["evil", "greeting", "tag", "report.txt", "Report (1).TXT"]);
+    }
+
+    #[test]
+    fn test_archive_to() {
+        ::init_log();
+
+        let body = fixture_body();
+        let mut archive = Vec::new();
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .archive_to(&mut archive, true)
+            .into_result_strict()
+            .unwrap();
+
+        // in the order the fields were read, then the manifest
+        let expected = vec![
+            ("report.txt".to_string(), data_of("report.txt")),
+            ("Report (1).TXT".to_string(), data_of("Report.TXT")),
+            ("passwd".to_string(), data_of("../../etc/passwd")),
+            ("evil".to_string(), Vec::new()),
+            (MANIFEST_NAME.to_string(),
+             b"{\"greeting\":[\"hello \\\"world\\\"\\n\"],\"tag\":[\"cold storage\"]}\n".to_vec()),
+        ];
+        assert_eq!(read_zip(&archive), expected);
+
+        assert_eq!(entries.fields["greeting"][0].as_text(), Some("hello \"world\"\n"));
+        assert!(entries.files_dir().is_none());
+
+        let upload = &entries.fields["upload"][0];
+        match upload.data {
+            SavedData::Streamed { bytes } => assert_eq!(bytes, 5000),
+            ref other => panic!("expected Streamed, got {:?}", other),
+        }
+
+        // a file named like the manifest is renamed
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"FIELDS.json\"\r\n\r\n\
+                     {}\r\n--boundary--\r\n";
+        let mut archive = Vec::new();
+        Multipart::with_body(&body[..], "boundary").save()
+            .archive_to(&mut archive, true)
+            .into_result_strict()
+            .unwrap();
+
+        let names: Vec<_> = read_zip(&archive).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["FIELDS (1).json", MANIFEST_NAME]);
+    }
+
+    #[test]
+    fn test_archive_to_partial() {
+        ::init_log();
+
+        let body = fixture_body();
+        let mut archive = Vec::new();
+
+        match Multipart::with_body(&body[..], "boundary").save()
+            .size_limit(1000)
+            .archive_to(&mut archive, true)
+        {
+            SaveResult::Partial(partial, PartialReason::SizeLimit { .. }) => {
+                let field = partial.partial.unwrap();
+                assert_eq!(field.source.headers.filename.as_deref(), Some("report.txt"));
+            },
+            SaveResult::Partial(_, reason) => panic!("unexpected reason {:?}", reason),
+            SaveResult::Full(_) => panic!("expected Partial"),
+            SaveResult::Error(e) => panic!("{}", e),
+        }
+
+        // the partial field is written but left out of the central directory
+        assert_eq!(read_zip(&archive), vec![
+            (MANIFEST_NAME.to_string(), b"{\"greeting\":[\"hello \\\"world\\\"\\n\"]}\n".to_vec()),
+        ]);
+        assert!(archive.len() > 1000);
+
+        // archived fields count toward the total, as do the text fields in memory
+        let mut archive = Vec::new();
+
+        match Multipart::with_body(&body[..], "boundary").save()
+            .total_size_limit(5020)
+            .archive_to(&mut archive, false)
+        {
+            SaveResult::Partial(partial, PartialReason::TotalSizeLimit { limit: 5020, saved: 5020 }) => {
+                let field = partial.partial.unwrap();
+                assert_eq!(field.source.headers.filename.as_deref(), Some("Report.TXT"));
+            },
+            SaveResult::Partial(_, reason) => panic!("unexpected reason {:?}", reason),
+            SaveResult::Full(_) => panic!("expected Partial"),
+            SaveResult::Error(e) => panic!("{}", e),
+        }
+
+        assert_eq!(read_zip(&archive), vec![("report.txt".to_string(), data_of("report.txt"))]);
+    }
+
+    #[test]
+    fn test_entry_names() {
+        let mut names = EntryNames::new();
+        let mut name_for = |name: &str, filename: Option<&str>| {
+            let mut headers = FieldHeaders::new(name);
+            headers.filename = filename.map(String::from);
+            names.name_for(&headers)
+        };
+
+        assert_eq!(name_for("a/b", None), "a_b");
+        assert_eq!(name_for("file", Some("C:\\Users\\me\\notes.txt")), "notes.txt");
+        assert_eq!(name_for("file", Some("notes.TXT. . ")), "notes (1).TXT");
+        assert_eq!(name_for("file", Some("in\u{202E}fdp.exe")), "infdp.exe");
+        assert_eq!(name_for("file", Some(".bashrc")), ".bashrc");
+        assert_eq!(name_for("file", Some(".BASHRC")), ".BASHRC (1)");
+        assert_eq!(name_for("\u{0}", Some("..")), "field");
+
+        let long = format!("{}.jpeg", "é".repeat(60));
+        let name = name_for("file", Some(&long));
+        assert_eq!(name.len(), 99);
+        assert!(name.ends_with("é.jpeg"));
+
+        let name = name_for("file", Some(&long));
+        assert!(name.len() <= MAX_NAME_LEN);
+        assert!(name.ends_with("é (1).jpeg"));
+    }
+
+    #[test]
+    fn test_short_data() {
+        let mut data = ExactLen::new(&b"short"[..], 10);
+        let mut read = Vec::new();
+        let err = data.read_to_end(&mut read).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(err.to_string(), "expected 10 bytes of data but read 5");
+        assert_eq!(read, b"short");
+    }
+
+    #[test]
+    fn test_zip64_entry_count() {
+        let mut archive = Vec::new();
+        let mut zip = ZipWriter::new(&mut archive);
+
+        for i in 0 .. ZIP64_COUNT_LIMIT {
+            zip.start_entry(i.to_string());
+            zip.finish_entry().unwrap();
+        }
+
+        let written = zip.finish().unwrap();
+        assert_eq!(written, archive.len() as u64);
+
+        // the counts in the end of central directory are in the Zip64 record instead
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), ZIP_END_OF_CENTRAL_DIR);
+        assert_eq!(u16_at(&archive, end + 10), 0xFFFF);
+
+        let locator = end - 20;
+        assert_eq!(u32_at(&archive, locator), ZIP64_END_LOCATOR);
+        let zip64_end = u32_at(&archive, locator + 8) as usize;
+        assert_eq!(zip64_end, locator - 56);
+
+        let u64_at = |at: usize| u64::from(u32_at(&archive, at)) | u64::from(u32_at(&archive, at + 4)) << 32;
+        assert_eq!(u32_at(&archive, zip64_end), ZIP64_END_OF_CENTRAL_DIR);
+        assert_eq!(u64_at(zip64_end + 32), ZIP64_COUNT_LIMIT);
+
+        // the central directory starts where the record says and ends at the record
+        let start = u64_at(zip64_end + 48) as usize;
+        assert_eq!(start + u64_at(zip64_end + 40) as usize, zip64_end);
+        assert_eq!(u32_at(&archive, start), ZIP_CENTRAL_HEADER);
+    }
+}
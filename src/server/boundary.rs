@@ -9,12 +9,17 @@
 
 use ::safemem;
 
-use super::buf_redux::BufReader;
-use super::buf_redux::policy::MinBuffered;
+use super::buffer::{MinBufRead, MinBufReader};
+use super::diagnostics::Diagnostics;
+use super::raw::RawSpool;
 use super::twoway;
 
 use std::cmp;
 use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
 
 use std::io;
 use std::io::prelude::*;
@@ -30,13 +35,106 @@ enum State {
     AtEnd
 }
 
+/// A step of the parser, passed to the sink set with `Multipart::set_event_sink()`.
+///
+/// Offsets are in bytes from the start of the request body (counting from where a
+/// `Checkpoint` was taken, for a resumed `Multipart`) and are only emitted as the parser
+/// advances, so the data of a field comes in `PartDataChunk`s as it is read or discarded.
+///
+/// For a well-formed body, each part is emitted as `PartStart`, `HeadersDone`, any number of
+/// `PartDataChunk`s and `PartEnd`, in that order, and the body ends with `BodyEnd`.
+/// The CRLF which precedes each boundary lies between `PartEnd` and the next `PartStart`
+/// (or `BodyEnd`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseEvent {
+    /// A part begins with the boundary line at `offset`.
+    PartStart {
+        /// The offset of the boundary's leading `--`.
+        offset: u64,
+    },
+    /// The header block of the current part was read.
+    ///
+    /// For a part without headers or data which is skipped (see
+    /// `ParseLimits::max_empty_parts`), this is just the blank line.
+    HeadersDone {
+        /// The offset of the first header line.
+        offset: u64,
+        /// The length of the header block, including the blank line which ends it.
+        len: u64,
+    },
+    /// Data of the current part was consumed.
+    PartDataChunk {
+        /// The offset of the first byte of the chunk.
+        offset: u64,
+        /// The length of the chunk.
+        len: u64,
+    },
+    /// The data of the current part ended before the next boundary (or the end of the body).
+    PartEnd {
+        /// The offset right after the last byte of data.
+        offset: u64,
+    },
+    /// The body ended; nothing is read after this.
+    BodyEnd {
+        /// The offset right after the closing boundary, or the end of the body.
+        offset: u64,
+        /// Whether the closing boundary was read; see `Multipart::finished_cleanly()`.
+        clean: bool,
+    },
+}
+
+/// The callback set with `Multipart::set_event_sink()`.
+///
+/// It is only called with `&mut` access, so the lock is never contended; it's there so
+/// `BoundaryReader` stays `Sync`.
+struct EventSink(Mutex<Box<dyn FnMut(ParseEvent) + Send>>);
+
+impl fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("EventSink")
+    }
+}
+
 /// A struct implementing `Read` and `BufRead` that will yield bytes until it sees a given sequence.
 #[derive(Debug)]
 pub struct BoundaryReader<R> {
-    source: BufReader<R, MinBuffered>,
+    source: MinBufReader<R>,
     boundary: Vec<u8>,
     search_idx: usize,
     state: State,
+    /// Whether at least one boundary has been consumed, i.e. we're past the preamble.
+    in_body: bool,
+    /// Whether the headers of the current field are still being read.
+    in_headers: bool,
+    strict_crlf: bool,
+    payload_bytes: u64,
+    overhead_bytes: u64,
+    /// `(payload_bytes, overhead_bytes)` at the start of the last boundary consumed.
+    part_start: (u64, u64),
+    max_drain_bytes: u64,
+    /// The number of bytes discarded while searching for the next boundary.
+    drained: u64,
+    /// Whether the closing boundary was consumed, as opposed to the body ending without it.
+    closed: bool,
+    max_empty_parts: u32,
+    /// The number of parts without headers or data which were skipped.
+    empty_parts: u32,
+    event_sink: Option<EventSink>,
+    /// The message of the panic the event sink was dropped after, failing every read since.
+    sink_panic: Option<String>,
+    /// The offset where the current part's headers begin, for `ParseEvent::HeadersDone`.
+    headers_start: u64,
+    /// A copy of every byte consumed from `source`, for `Multipart::capture_raw()`.
+    raw: Option<RawSpool>,
+    diagnostics: Option<Box<Diagnostics>>,
+    /// The number of zero-length reads in a row to retry before believing the source ended.
+    max_empty_reads: u32,
+    /// Whether the source returned data after a zero-length read, so that isn't its end.
+    stalls: bool,
+    /// Whether the source returned nothing more after `max_empty_reads` retries.
+    source_ended: bool,
+    /// The index of the field being read, for `NoProgressError`.
+    field_index: Option<u32>,
 }
 
 impl<R> BoundaryReader<R> where R: Read {
@@ -44,18 +142,344 @@ impl<R> BoundaryReader<R> where R: Read {
     pub fn from_reader<B: Into<Vec<u8>>>(reader: R, boundary: B) -> BoundaryReader<R> {
         let mut boundary = boundary.into();
         safemem::prepend(b"--", &mut boundary);
-        let source = BufReader::new(reader).set_policy(MinBuffered(MIN_BUF_SIZE));
+        let source = MinBufReader::new(reader, MIN_BUF_SIZE);
 
         BoundaryReader {
             source,
             boundary,
             search_idx: 0,
             state: Searching,
+            in_body: false,
+            in_headers: false,
+            strict_crlf: false,
+            payload_bytes: 0,
+            overhead_bytes: 0,
+            part_start: (0, 0),
+            max_drain_bytes: u64::MAX,
+            drained: 0,
+            closed: false,
+            max_empty_parts: u32::MAX,
+            empty_parts: 0,
+            event_sink: None,
+            sink_panic: None,
+            headers_start: 0,
+            raw: None,
+            diagnostics: None,
+            max_empty_reads: super::DEFAULT_MAX_EMPTY_READS,
+            stalls: false,
+            source_ended: false,
+            field_index: None,
+        }
+    }
+
+    /// If `true`, return an error for a boundary which is not preceded by CRLF
+    /// (except the first).
+    pub fn set_strict_crlf(&mut self, strict_crlf: bool) {
+        self.strict_crlf = strict_crlf;
+    }
+
+    pub fn strict_crlf(&self) -> bool {
+        self.strict_crlf
+    }
+
+    /// Return an error from `consume_boundary()` instead of discarding more than this many bytes
+    /// to find the next boundary.
+    pub fn set_max_drain_bytes(&mut self, max_drain_bytes: u64) {
+        self.max_drain_bytes = max_drain_bytes;
+    }
+
+    /// Return an error from `skip_empty_part()` instead of skipping more than this many parts.
+    pub fn set_max_empty_parts(&mut self, max_empty_parts: u32) {
+        self.max_empty_parts = max_empty_parts;
+    }
+
+    /// Retry up to this many zero-length reads in a row from the source before taking it
+    /// as the end; see `ParseLimits::max_empty_reads`.
+    pub fn set_max_empty_reads(&mut self, max_empty_reads: u32) {
+        self.max_empty_reads = max_empty_reads;
+    }
+
+    /// Set the index of the field being read, for `NoProgressError`.
+    pub fn set_field_index(&mut self, index: u32) {
+        self.field_index = Some(index);
+    }
+
+    /// The number of parts without headers or data skipped so far.
+    pub fn empty_parts(&self) -> u32 {
+        self.empty_parts
+    }
+
+    /// Mark the end of the current field's headers; bytes consumed after this are field data.
+    pub fn end_headers(&mut self) {
+        self.in_headers = false;
+
+        let offset = self.headers_start;
+        let len = self.offset() - offset;
+        self.emit(ParseEvent::HeadersDone { offset, len });
+    }
+
+    /// Call `sink` with each `ParseEvent` from now on.
+    pub fn set_event_sink(&mut self, sink: Box<dyn FnMut(ParseEvent) + Send>) {
+        self.event_sink = Some(EventSink(Mutex::new(sink)));
+    }
+
+    /// Record `Diagnostics` from now on, keeping the last `max_events`.
+    pub fn record_diagnostics(&mut self, max_events: usize) {
+        self.diagnostics = Some(Box::new(Diagnostics::new(max_events, self.boundary.len() - 2)));
+    }
+
+    pub fn diagnostics(&self) -> Option<&Diagnostics> {
+        self.diagnostics.as_deref()
+    }
+
+    /// Record `err` in the `Diagnostics`, if any, along with the names of the header lines
+    /// buffered if it occurred before the headers of the current part were consumed.
+    pub fn record_error(&mut self, err: &io::Error) {
+        let offset = self.offset();
+
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            if self.in_headers && offset == self.headers_start {
+                diagnostics.header_names(self.source.buffer(), offset);
+            }
+
+            diagnostics.record_error(err, offset);
+        }
+    }
+
+    /// Copy every byte consumed from the source from now on into `spool`.
+    pub fn set_raw_spool(&mut self, spool: RawSpool) {
+        self.raw = Some(spool);
+    }
+
+    /// Returns `true` if a spool was set with `set_raw_spool()` and not taken yet.
+    pub fn has_raw_spool(&self) -> bool {
+        self.raw.is_some()
+    }
+
+    /// Read the rest of the source into the spool set with `set_raw_spool()` and return it,
+    /// or `None` if there isn't one.
+    ///
+    /// Nothing more can be read after this.
+    pub fn take_raw_spool(&mut self) -> io::Result<Option<RawSpool>> {
+        if self.raw.is_none() {
+            return Ok(None);
+        }
+
+        loop {
+            self.fill_source()?;
+            let len = self.source.buf_len();
+            if len == 0 { break; }
+
+            self.consume_source(len);
+            self.overhead_bytes += len as u64;
+        }
+
+        self.state = AtEnd;
+        Ok(self.raw.take())
+    }
+
+    /// Fill the source's buffer, retrying zero-length reads up to `max_empty_reads` times
+    /// in a row, as some sources return `Ok(0)` when they have no data *yet*.
+    ///
+    /// `MinBufReader` stops at the first zero-length read, so a buffer left short of the
+    /// minimum means the source returned one. Once the retries run out, `source_ended` is set
+    /// and the source isn't retried again until it returns more data.
+    fn fill_source(&mut self) -> io::Result<()> {
+        if self.state == AtEnd {
+            return self.source.fill_buf().map(|_| ());
+        }
+
+        let mut empty_reads = 0u32;
+
+        loop {
+            let before = self.source.buf_len();
+            let len = self.source.fill_buf()?.len();
+            let new_data = len > before;
+
+            // the source didn't end after all
+            if new_data && (empty_reads > 0 || self.source_ended) {
+                self.stalls = true;
+                self.source_ended = false;
+            }
+
+            if len >= self.source.min_buffered() || (self.source_ended && !new_data) {
+                break;
+            }
+
+            empty_reads = if new_data { 1 } else { empty_reads + 1 };
+
+            if empty_reads > self.max_empty_reads {
+                self.source_ended = true;
+                break;
+            }
+
+            debug!("Zero-length read from the source ({} in a row), retrying", empty_reads);
+        }
+
+        Ok(())
+    }
+
+    /// The error for a source which ended before the body, if it has returned `Ok(0)`
+    /// before more data earlier so the end can't be told apart from a stall.
+    fn check_progress(&self) -> io::Result<()> {
+        if self.stalls && self.source_ended && self.max_empty_reads > 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, NoProgressError {
+                field: if self.in_body { self.field_index } else { None },
+                offset: self.offset() + self.source.buf_len() as u64,
+                empty_reads: self.max_empty_reads.saturating_add(1),
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Consume `amt` bytes from the source, copying them to the raw spool if there is one.
+    fn consume_source(&mut self, amt: usize) {
+        if let Some(ref mut raw) = self.raw {
+            raw.write(&self.source.buffer()[..amt]);
+        }
+
+        self.source.consume(amt);
+    }
+
+    #[inline]
+    fn emit(&mut self, event: ParseEvent) {
+        let panicked = match self.event_sink {
+            Some(ref mut sink) => {
+                let sink = sink.0.get_mut().unwrap_or_else(|e| e.into_inner());
+                panic::catch_unwind(AssertUnwindSafe(|| sink(event))).err()
+            },
+            None => None,
+        };
+
+        // unwinding from here could leave the reader halfway through a boundary
+        if let Some(payload) = panicked {
+            self.event_sink = None;
+            self.sink_panic = Some(super::panic_message(&*payload).to_string());
+        }
+
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            diagnostics.parse_event(event);
         }
     }
 
+    /// Fail with `EventSinkPanicked` once the event sink has panicked.
+    fn check_event_sink(&self) -> io::Result<()> {
+        match self.sink_panic {
+            Some(ref message) => Err(io::Error::other(EventSinkPanicked { message: message.clone() })),
+            None => Ok(()),
+        }
+    }
+
+    /// The number of bytes consumed so far, i.e. the offset of the next byte.
+    pub fn offset(&self) -> u64 {
+        self.payload_bytes + self.overhead_bytes
+    }
+
+    /// The number of bytes of field data consumed so far.
+    pub fn payload_bytes(&self) -> u64 {
+        self.payload_bytes
+    }
+
+    /// The number of bytes consumed so far which were not field data.
+    pub fn overhead_bytes(&self) -> u64 {
+        self.overhead_bytes
+    }
+
+    /// The values of `(payload_bytes(), overhead_bytes())` at the start of the last boundary
+    /// consumed, i.e. the start of the current field.
+    pub fn part_start(&self) -> (u64, u64) {
+        self.part_start
+    }
+
+    /// The boundary, without the leading `--`.
+    pub fn boundary(&self) -> &[u8] {
+        &self.boundary[2..]
+    }
+
+    /// Whether the preamble has been consumed.
+    pub fn in_body(&self) -> bool {
+        self.in_body
+    }
+
+    /// Whether the closing boundary has been consumed.
+    pub fn finished_cleanly(&self) -> bool {
+        self.closed
+    }
+
+    /// Set the state for a source which has been positioned at the start of a boundary.
+    pub fn resume(&mut self, payload_bytes: u64, overhead_bytes: u64, in_body: bool) {
+        self.payload_bytes = payload_bytes;
+        self.overhead_bytes = overhead_bytes;
+        self.part_start = (payload_bytes, overhead_bytes);
+        self.in_body = in_body;
+    }
+
+    /// Count `amt` bytes about to be discarded by `consume_boundary()`.
+    ///
+    /// The count is only reset once the boundary is consumed, so after this returns an error
+    /// it will keep doing so.
+    fn count_drained(&mut self, amt: usize) -> io::Result<()> {
+        let drained = self.drained.saturating_add(amt as u64);
+
+        if drained > self.max_drain_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                DrainLimitExceeded { limit: self.max_drain_bytes },
+            ));
+        }
+
+        self.drained = drained;
+        Ok(())
+    }
+
+    fn count_consumed(&mut self, amt: usize) {
+        if self.in_body && !self.in_headers {
+            if amt > 0 {
+                let offset = self.offset();
+                self.emit(ParseEvent::PartDataChunk { offset, len: amt as u64 });
+            }
+
+            self.payload_bytes += amt as u64;
+        } else {
+            self.overhead_bytes += amt as u64;
+        }
+    }
+
+    /// End the body at EOF without the closing boundary, discarding anything held back
+    /// (e.g. the CRLF after the last field).
+    fn end_unclosed(&mut self) -> io::Result<()> {
+        self.fill_source()?;
+        let rest = self.source.buf_len();
+        self.consume_source(rest);
+
+        // without any boundary there was no part to end
+        if self.in_body {
+            let offset = self.offset();
+            self.emit(ParseEvent::PartEnd { offset });
+        }
+
+        self.overhead_bytes += rest as u64;
+        self.state = AtEnd;
+
+        let offset = self.offset();
+        self.emit(ParseEvent::BodyEnd { offset, clean: false });
+        Ok(())
+    }
+
     fn read_to_boundary(&mut self) -> io::Result<&[u8]> {
-        let buf = self.source.fill_buf()?;
+        self.check_event_sink()?;
+        self.fill_source()?;
+
+        if self.state == Searching && self.source_ended {
+            // the rest of the body may be buffered already
+            let buf = self.source.buffer();
+            if find_boundary(&buf[cmp::min(self.search_idx, buf.len())..], &self.boundary).is_err() {
+                self.check_progress()?;
+            }
+        }
+
+        let buf = self.source.buffer();
 
         trace!("Buf: {:?}", String::from_utf8_lossy(buf));
 
@@ -97,6 +521,14 @@ impl<R> BoundaryReader<R> where R: Read {
             }
         }
 
+        // a CR at the very end of the buffer may be the start of the CRLF preceding the boundary,
+        // so hold it back until we have more data
+        if self.state == Searching && self.search_idx > 0 && self.search_idx == buf.len()
+            && buf[self.search_idx - 1] == b'\r' {
+            debug!("Subtract one!");
+            self.search_idx -= 1;
+        }
+
         let ret_buf = &buf[..self.search_idx];
 
         trace!("Returning buf: {:?}", String::from_utf8_lossy(ret_buf));
@@ -104,14 +536,63 @@ impl<R> BoundaryReader<R> where R: Read {
         Ok(ret_buf)
     }
 
+    /// The capacity of the underlying buffer, which never shrinks.
+    #[cfg(test)]
+    pub fn buf_capacity(&self) -> usize {
+        self.source.capacity()
+    }
+
     pub fn set_min_buf_size(&mut self, min_buf_size: usize) {
         // ensure the minimum buf size is at least enough to find a boundary with some extra
         let min_buf_size = cmp::max(self.boundary.len() * 2, min_buf_size);
 
-        self.source.policy_mut().0 = min_buf_size;
+        self.source.set_min_buffered(min_buf_size);
+
+        trace!("Minimum buffer size set to {} (capacity: {})", min_buf_size, self.source.capacity());
+    }
+
+    /// After a boundary, check for a part with no headers and no data, i.e. a blank line
+    /// followed right away by the next boundary, without parsing any headers.
+    ///
+    /// If there is one, the blank line is consumed, leaving the boundary for
+    /// `consume_boundary()`, and `true` is returned; in strict mode, or if it would be more than
+    /// `max_empty_parts`, an error is returned instead. Otherwise nothing is consumed.
+    pub fn skip_empty_part(&mut self) -> io::Result<bool> {
+        {
+            self.fill_source()?;
+            let buf = self.source.buffer();
+
+            let is_empty = buf.starts_with(b"\r\n\r\n") && buf[4..].starts_with(&self.boundary);
+            if !is_empty {
+                return Ok(false);
+            }
+        }
+
+        let count = self.empty_parts.saturating_add(1);
+
+        if self.strict_crlf || count > self.max_empty_parts {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                EmptyPartError { count, limit: if self.strict_crlf { None } else { Some(self.max_empty_parts) } },
+            ));
+        }
+
+        self.empty_parts = count;
+        self.consume_source(2);
+        self.overhead_bytes += 2;
+        self.end_headers();
+
+        Ok(true)
     }
 
     pub fn consume_boundary(&mut self) -> io::Result<bool> {
+        let consumed = self.consume_next_boundary();
+        // the events of the boundary itself are emitted in the meantime
+        self.check_event_sink()?;
+        consumed
+    }
+
+    fn consume_next_boundary(&mut self) -> io::Result<bool> {
         if self.state == AtEnd {
             return Ok(false);
         }
@@ -122,17 +603,42 @@ impl<R> BoundaryReader<R> where R: Read {
             let buf_len = self.read_to_boundary()?.len();
 
             if buf_len == 0 && self.state == Searching {
+                // only the CRLF ending the last part is held back, so nothing but the closing
+                // boundary is missing; anything else means the body was cut off within a part
+                let after_crlf = self.source.buffer() == b"\r\n";
+
+                if !self.strict_crlf && (!self.in_body || after_crlf) {
+                    if self.in_body {
+                        warn!("Request body ended without the closing boundary");
+                    } else {
+                        warn!("Request body ended without any boundary, read as having no parts");
+                    }
+
+                    self.end_unclosed()?;
+                    return Ok(false);
+                }
+
+                if !self.in_body {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, MissingBoundaryError));
+                }
+
                 return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
                                           "unexpected end of request body"));
             }
 
             debug!("Discarding {} bytes", buf_len);
 
+            self.count_drained(buf_len)?;
             self.consume(buf_len);
         }
 
+        // the data left before the boundary is discarded along with it
+        let search_idx = self.search_idx;
+        self.count_drained(search_idx)?;
+
         let consume_amt = {
-            let buf = self.source.fill_buf()?;
+            self.fill_source()?;
+            let buf = self.source.buffer();
 
             // if the boundary is found we should have at least this much in-buffer
             let mut consume_amt = self.search_idx + self.boundary.len();
@@ -140,7 +646,9 @@ impl<R> BoundaryReader<R> where R: Read {
             // we don't care about data before the cursor
             let bnd_segment = &buf[self.search_idx..];
 
-            if bnd_segment.starts_with(b"\r\n") {
+            let preceding_crlf = bnd_segment.starts_with(b"\r\n");
+
+            if preceding_crlf {
                 // preceding CRLF needs to be consumed as well
                 consume_amt += 2;
 
@@ -160,20 +668,33 @@ impl<R> BoundaryReader<R> where R: Read {
             }
 
             // we have enough bytes to verify
-            self.state = Searching;
-
             let last_two = &buf[consume_amt - 2 .. consume_amt];
 
-            match last_two {
-                b"\r\n" => self.state = Searching,
-                b"--" => self.state = AtEnd,
+            let state = match last_two {
+                b"\r\n" => Searching,
+                b"--" => AtEnd,
                 _ => return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
                     format!("unexpected bytes following multipart boundary: {:X} {:X}",
                             last_two[0], last_two[1])
                 )),
+            };
+
+            // the first boundary may be at the very start of the body
+            if self.in_body && !preceding_crlf {
+                if self.strict_crlf {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        MissingCrlfError { closing: state == AtEnd },
+                    ));
+                }
+
+                debug!("Boundary not preceded by CRLF; field ends at the preceding byte");
             }
 
+            self.closed = state == AtEnd;
+            self.state = state;
+
             consume_amt
         };
 
@@ -181,13 +702,33 @@ impl<R> BoundaryReader<R> where R: Read {
                consume_amt,
                String::from_utf8_lossy(self.source.buffer()));
 
-        self.source.consume(consume_amt);
+        self.consume_source(consume_amt);
+
+        // any data left before the boundary belongs to the previous field (or the preamble)
+        self.count_consumed(search_idx);
+        self.part_start = (self.payload_bytes, self.overhead_bytes);
 
-        if cfg!(debug_assertions) {
+        if self.in_body {
+            let offset = self.offset();
+            self.emit(ParseEvent::PartEnd { offset });
+        }
 
+        // the boundary itself starts after the preceding CRLF
+        let boundary_start = self.offset() + (consume_amt - search_idx - self.boundary.len() - 2) as u64;
+        self.overhead_bytes += (consume_amt - search_idx) as u64;
+
+        if self.state == AtEnd {
+            let offset = self.offset();
+            self.emit(ParseEvent::BodyEnd { offset, clean: true });
+        } else {
+            self.emit(ParseEvent::PartStart { offset: boundary_start });
+            self.headers_start = self.offset();
         }
 
         self.search_idx = 0;
+        self.drained = 0;
+        self.in_body = true;
+        self.in_headers = self.state == Searching;
 
         trace!("Consumed boundary (state: {:?}), remaining buf: {:?}", self.state,
                String::from_utf8_lossy(self.source.buffer()));
@@ -196,6 +737,153 @@ impl<R> BoundaryReader<R> where R: Read {
     }
 }
 
+/// The error returned (wrapped in `io::Error`) in strict mode when a boundary
+/// is not preceded by CRLF.
+///
+/// Set with `Multipart::strict_crlf()`.
+#[derive(Debug)]
+pub struct MissingCrlfError {
+    /// `true` if the offending boundary was the closing boundary, which is the most common case.
+    pub closing: bool,
+}
+
+impl fmt::Display for MissingCrlfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.closing {
+            f.write_str("closing boundary not preceded by CRLF")
+        } else {
+            f.write_str("boundary not preceded by CRLF")
+        }
+    }
+}
+
+impl Error for MissingCrlfError {
+    fn description(&self) -> &str {
+        "boundary not preceded by CRLF"
+    }
+}
+
+/// The error returned (wrapped in `io::Error`) in strict mode when the request body ends
+/// without containing the boundary at all, e.g. an empty body or `----`.
+///
+/// Set with `Multipart::strict_crlf()`.
+#[derive(Debug)]
+pub struct MissingBoundaryError;
+
+impl fmt::Display for MissingBoundaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("request body ended without any boundary")
+    }
+}
+
+impl Error for MissingBoundaryError {
+    fn description(&self) -> &str {
+        "no boundary in the request body"
+    }
+}
+
+/// The error returned (wrapped in `io::Error`) when more than `ParseLimits::max_drain_bytes`
+/// of unread data would have to be discarded to find the next boundary.
+///
+/// The request cannot be read any further.
+#[derive(Debug)]
+pub struct DrainLimitExceeded {
+    /// The limit which was exceeded.
+    pub limit: u64,
+}
+
+impl fmt::Display for DrainLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "more than {} bytes of unread data before the next boundary", self.limit)
+    }
+}
+
+impl Error for DrainLimitExceeded {
+    fn description(&self) -> &str {
+        "drain limit exceeded"
+    }
+}
+
+/// The error returned (wrapped in `io::Error`) by every read after the sink set with
+/// `Multipart::set_event_sink()` panicked.
+///
+/// The panic is caught so it doesn't unwind through the parser; the sink is dropped.
+#[derive(Debug)]
+pub struct EventSinkPanicked {
+    /// The message the sink panicked with, if it was a string.
+    pub message: String,
+}
+
+impl fmt::Display for EventSinkPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the event sink panicked: {}", self.message)
+    }
+}
+
+impl Error for EventSinkPanicked {
+    fn description(&self) -> &str {
+        "the event sink panicked"
+    }
+}
+
+/// The error returned (wrapped in `io::Error`) for a part without headers or data,
+/// in strict mode or once there are more than `ParseLimits::max_empty_parts` of them.
+#[derive(Debug)]
+pub struct EmptyPartError {
+    /// The number of empty parts in the request so far, including this one.
+    pub count: u32,
+    /// The limit which was exceeded, or `None` in strict mode.
+    pub limit: Option<u32>,
+}
+
+impl fmt::Display for EmptyPartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.limit {
+            Some(limit) => write!(f, "more than {} empty parts in the request", limit),
+            None => f.write_str("part without headers or data in the request"),
+        }
+    }
+}
+
+impl Error for EmptyPartError {
+    fn description(&self) -> &str {
+        "empty part in the request"
+    }
+}
+
+/// The error returned (wrapped in `io::Error` of kind `UnexpectedEof`) when the body stops
+/// producing data before it's complete, from a source which returns `Ok(0)` when it has
+/// no data yet; see `ParseLimits::max_empty_reads`.
+///
+/// The request cannot be read any further.
+#[derive(Debug)]
+pub struct NoProgressError {
+    /// The index of the field being read, counting from 0 as for `UnnamedFieldError`,
+    /// or `None` if it happened before the first boundary.
+    pub field: Option<u32>,
+    /// The offset in the body right after the last byte the source returned.
+    pub offset: u64,
+    /// The number of zero-length reads in a row from the source.
+    pub empty_reads: u32,
+}
+
+impl fmt::Display for NoProgressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no progress after {} zero-length reads at offset {}", self.empty_reads, self.offset)?;
+
+        match self.field {
+            Some(field) => write!(f, " in field {}", field),
+            None => f.write_str(" before the first boundary"),
+        }
+    }
+}
+
+impl Error for NoProgressError {
+    fn description(&self) -> &str {
+        "no progress reading the request body"
+    }
+}
+
 /// Find the boundary occurrence or the highest length to safely yield
 fn find_boundary(buf: &[u8], boundary: &[u8]) -> Result<usize, usize> {
     if let Some(idx) = twoway::find_bytes(buf, boundary) {
@@ -214,6 +902,43 @@ fn find_boundary(buf: &[u8], boundary: &[u8]) -> Result<usize, usize> {
     Err(buf.len())
 }
 
+impl<R: Read + Seek> BoundaryReader<R> {
+    /// The position in the source of the next byte to be consumed.
+    pub fn position(&mut self) -> io::Result<u64> {
+        let buffered = self.source.buf_len() as u64;
+        let pos = self.source.get_mut().stream_position()?;
+        Ok(pos - buffered)
+    }
+
+    /// Pass every byte from `position()` to the end of the source to `with_buf`, then seek
+    /// back, without parsing or counting anything. Returns the number of bytes passed.
+    pub fn read_ahead_raw<F: FnMut(&[u8])>(&mut self, mut with_buf: F) -> io::Result<u64> {
+        let start = self.position()?;
+        // discards the buffer, which would be read again anyway
+        self.source.seek(io::SeekFrom::Start(start))?;
+        // so reading the buffer again isn't taken for a stall
+        self.source_ended = false;
+
+        let mut buf = vec![0; MIN_BUF_SIZE.max(8 * 1024)];
+        let mut total = 0;
+
+        loop {
+            match self.source.get_mut().read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => {
+                    with_buf(&buf[..read]);
+                    total += read as u64;
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.source.seek(io::SeekFrom::Start(start))?;
+        Ok(total)
+    }
+}
+
 #[cfg(feature = "bench")]
 impl<'a> BoundaryReader<io::Cursor<&'a [u8]>> {
     fn new_with_bytes(bytes: &'a [u8], boundary: &str) -> Self {
@@ -257,20 +982,34 @@ impl<R> BufRead for BoundaryReader<R> where R: Read {
 
         debug!("Consume! amt: {} true amt: {}", amt, true_amt);
 
-        self.source.consume(true_amt);
+        if let Some(ref mut diagnostics) = self.diagnostics {
+            let buf = self.source.buffer();
+            let offset = self.payload_bytes + self.overhead_bytes;
+
+            if self.in_headers {
+                diagnostics.header_names(&buf[..true_amt], offset);
+            } else {
+                diagnostics.scan_data(buf, true_amt, offset, &self.boundary);
+            }
+        }
+
+        self.consume_source(true_amt);
         self.search_idx -= true_amt;
+        self.count_consumed(true_amt);
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::BoundaryReader;
+    use super::{BoundaryReader, MissingCrlfError, NoProgressError};
+
+    use mock::{Fault, FaultyReader};
 
     use std::io;
     use std::io::prelude::*;
 
-    const BOUNDARY: &'static str = "boundary";
-    const TEST_VAL: &'static str = "--boundary\r\n\
+    const BOUNDARY: &str = "boundary";
+    const TEST_VAL: &str = "--boundary\r\n\
                                     dashed-value-1\r\n\
                                     --boundary\r\n\
                                     dashed-value-2\r\n\
@@ -299,10 +1038,7 @@ mod test {
         fn split(data: &'a [u8], at: usize) -> SplitReader<'a> {
             let (left, right) = data.split_at(at);
 
-            SplitReader { 
-                left: left,
-                right: right,
-            }
+            SplitReader { left, right }
         }
     }
 
@@ -379,11 +1115,11 @@ mod test {
         // empty body contains closing boundary only
         let mut body: &[u8] = b"--boundary--";
 
-        let ref mut buf = String::new();
+        let buf = &mut String::new();
         let mut reader = BoundaryReader::from_reader(&mut body, BOUNDARY);
 
         debug!("Consume 1");
-        assert_eq!(reader.consume_boundary().unwrap(), false);
+        assert!(!reader.consume_boundary().unwrap());
 
         debug!("Read 1");
         let _ = reader.read_to_string(buf).unwrap();
@@ -391,7 +1127,7 @@ mod test {
         buf.clear();
 
         debug!("Consume 2");
-        assert_eq!(reader.consume_boundary().unwrap(), false);
+        assert!(!reader.consume_boundary().unwrap());
     }
 
     #[test]
@@ -402,12 +1138,12 @@ mod test {
                          asdf1234\
                          \r\n\r\n--boundary--";
 
-        let ref mut buf = String::new();
+        let buf = &mut String::new();
         let mut reader = BoundaryReader::from_reader(&mut body, BOUNDARY);
 
 
         debug!("Consume 1");
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+        assert!(reader.consume_boundary().unwrap());
 
         debug!("Read 1");
         let _ = reader.read_to_string(buf).unwrap();
@@ -415,7 +1151,7 @@ mod test {
         buf.clear();
 
         debug!("Consume 2");
-        assert_eq!(reader.consume_boundary().unwrap(), false);
+        assert!(!reader.consume_boundary().unwrap());
 
         debug!("Read 2 (empty)");
         let _ = reader.read_to_string(buf).unwrap();
@@ -431,11 +1167,11 @@ mod test {
                          \r\n\r\n--boundary\r\n\
                          hjkl5678\r\n--boundary--";
 
-        let ref mut buf = String::new();
+        let buf = &mut String::new();
         let mut reader = BoundaryReader::from_reader(&mut body, BOUNDARY);
 
         debug!("Consume 1");
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+        assert!(reader.consume_boundary().unwrap());
 
         debug!("Read 1");
 
@@ -450,7 +1186,7 @@ mod test {
         buf.clear();
 
         debug!("Consume 2");
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+        assert!(reader.consume_boundary().unwrap());
 
         debug!("Read 2");
         let _ = reader.read_to_string(buf).unwrap();
@@ -458,7 +1194,7 @@ mod test {
         buf.clear();
 
         debug!("Consume 3");
-        assert_eq!(reader.consume_boundary().unwrap(), false);
+        assert!(!reader.consume_boundary().unwrap());
 
         debug!("Read 3 (empty)");
         let _ = reader.read_to_string(buf).unwrap();
@@ -475,11 +1211,11 @@ mod test {
                          \n\n\r\n--boundary\r\n\
                          hjkl5678\r\n--boundary--";
 
-        let ref mut buf = String::new();
+        let buf = &mut String::new();
         let mut reader = BoundaryReader::from_reader(&mut body, BOUNDARY);
 
         debug!("Consume 1");
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+        assert!(reader.consume_boundary().unwrap());
 
         debug!("Read 1");
 
@@ -493,7 +1229,7 @@ mod test {
         buf.clear();
 
         debug!("Consume 2");
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+        assert!(reader.consume_boundary().unwrap());
 
         debug!("Read 2");
         let _ = reader.read_to_string(buf).unwrap();
@@ -501,7 +1237,7 @@ mod test {
         buf.clear();
 
         debug!("Consume 3");
-        assert_eq!(reader.consume_boundary().unwrap(), false);
+        assert!(!reader.consume_boundary().unwrap());
 
         debug!("Read 3 (empty)");
         let _ = reader.read_to_string(buf).unwrap();
@@ -513,38 +1249,80 @@ mod test {
     fn test_unterminated_body() {
         ::init_log();
 
-        let mut body: &[u8] = b"--boundary\r\n\
+        let body: &[u8] = b"--boundary\r\n\
                          asdf1234\
                          \n\n\r\n--boundary\r\n\
                          hjkl5678  ";
 
-        let ref mut buf = String::new();
-        let mut reader = BoundaryReader::from_reader(&mut body, BOUNDARY);
+        for &strict in &[false, true] {
+            let buf = &mut String::new();
+            let mut reader = BoundaryReader::from_reader(body, BOUNDARY);
+            reader.set_strict_crlf(strict);
 
-        debug!("Consume 1");
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+            debug!("Consume 1");
+            assert!(reader.consume_boundary().unwrap());
 
-        debug!("Read 1");
+            debug!("Read 1");
 
-        // same as above
-        let buf1 = reader.read_to_boundary().unwrap().to_owned();
-        let buf2 = reader.read_to_boundary().unwrap().to_owned();
-        assert_eq!(buf1, buf2);
+            // same as above
+            let buf1 = reader.read_to_boundary().unwrap().to_owned();
+            let buf2 = reader.read_to_boundary().unwrap().to_owned();
+            assert_eq!(buf1, buf2);
 
-        let _ = reader.read_to_string(buf).unwrap();
-        assert_eq!(buf, "asdf1234\n\n");
-        buf.clear();
+            let _ = reader.read_to_string(buf).unwrap();
+            assert_eq!(buf, "asdf1234\n\n");
+            buf.clear();
 
-        debug!("Consume 2");
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+            debug!("Consume 2");
+            assert!(reader.consume_boundary().unwrap());
 
-        debug!("Read 2");
-        let _ = reader.read_to_string(buf).unwrap();
-        assert_eq!(buf, "hjkl5678  ");
-        buf.clear();
+            debug!("Read 2");
+            let _ = reader.read_to_string(buf).unwrap();
+            assert_eq!(buf, "hjkl5678  ");
+            buf.clear();
 
-        debug!("Consume 3 - expecting error");
-        reader.consume_boundary().unwrap_err();
+            // cut off within the field, even in lenient mode
+            debug!("Consume 3 - expecting error");
+            reader.consume_boundary().unwrap_err();
+        }
+    }
+
+    #[test]
+    fn test_unterminated_after_crlf() {
+        ::init_log();
+
+        let body: &[u8] = b"--boundary\r\n\
+                         asdf1234\r\n\
+                         --boundary\r\n\
+                         hjkl5678\r\n";
+
+        for &strict in &[false, true] {
+            let buf = &mut String::new();
+            let mut reader = BoundaryReader::from_reader(body, BOUNDARY);
+            reader.set_strict_crlf(strict);
+
+            assert!(reader.consume_boundary().unwrap());
+            let _ = reader.read_to_string(buf).unwrap();
+            assert_eq!(buf, "asdf1234");
+            buf.clear();
+
+            assert!(reader.consume_boundary().unwrap());
+            let _ = reader.read_to_string(buf).unwrap();
+            assert_eq!(buf, "hjkl5678");
+
+            if strict {
+                debug!("Consume 3 - expecting error");
+                reader.consume_boundary().unwrap_err();
+            } else {
+                debug!("Consume 3 - ending without the closing boundary");
+                assert!(!reader.consume_boundary().unwrap());
+                assert!(!reader.finished_cleanly());
+            }
+        }
+
+        // a CR alone doesn't end the part
+        let mut reader = BoundaryReader::from_reader(&body[..body.len() - 1], BOUNDARY);
+        assert_eq!(read_fields(&mut reader).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
     }
 
     #[test]
@@ -566,15 +1344,178 @@ mod test {
         let mut body: &[u8] = b"--boundary\r\nfield1\r\n--boundary\r\nfield2\r\n--boundary--";
         let mut reader = BoundaryReader::from_reader(&mut body, "boundary");
 
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+        assert!(reader.consume_boundary().unwrap());
         // skip `field1`
-        assert_eq!(reader.consume_boundary().unwrap(), true);
+        assert!(reader.consume_boundary().unwrap());
 
         let mut buf = String::new();
         reader.read_to_string(&mut buf).unwrap();
         assert_eq!(buf, "field2");
 
-        assert_eq!(reader.consume_boundary().unwrap(), false);
+        assert!(!reader.consume_boundary().unwrap());
+    }
+
+    /// Read the data of each field, retrying after `WouldBlock` errors.
+    fn read_fields<R: Read>(reader: &mut BoundaryReader<R>) -> io::Result<Vec<String>> {
+        let mut fields = Vec::new();
+
+        loop {
+            match reader.consume_boundary() {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e),
+            }
+
+            // consume whole buffers like `SaveBuilder` does
+            let mut buf = Vec::new();
+
+            loop {
+                let len = match reader.fill_buf() {
+                    Ok(data) => {
+                        buf.extend_from_slice(data);
+                        data.len()
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if len == 0 { break; }
+                reader.consume(len);
+            }
+
+            fields.push(String::from_utf8(buf).unwrap());
+        }
+
+        Ok(fields)
+    }
+
+    const FIELD_1: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+    const FIELD_2: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ\r\n9876543210";
+
+    // The final field lacks the CRLF before the closing boundary
+    #[test]
+    fn test_missing_final_crlf() {
+        ::init_log();
+
+        let body = format!("--boundary\r\n{}\r\n--boundary\r\n{}--boundary--", FIELD_1, FIELD_2);
+
+        for split_at in 0 .. body.len() {
+            debug!("Testing split at: {}", split_at);
+
+            // keep the buffer small so the split actually affects what is searched
+            let mut reader = BoundaryReader::from_reader(
+                SplitReader::split(body.as_bytes(), split_at), BOUNDARY
+            );
+            reader.set_min_buf_size(0);
+
+            assert_eq!(read_fields(&mut reader).unwrap(), [FIELD_1, FIELD_2], "split at {}", split_at);
+
+            let mut reader = BoundaryReader::from_reader(
+                SplitReader::split(body.as_bytes(), split_at), BOUNDARY
+            );
+            reader.set_min_buf_size(0);
+            reader.set_strict_crlf(true);
+
+            let err = read_fields(&mut reader).unwrap_err();
+            let err = err.get_ref().and_then(|e| e.downcast_ref::<MissingCrlfError>())
+                .expect("expected `MissingCrlfError`");
+            assert!(err.closing);
+        }
+    }
+
+    // Well-formed terminators must not leak into the field data regardless of alignment
+    #[test]
+    fn test_final_crlf_split() {
+        ::init_log();
+
+        let body = format!("--boundary\r\n{}\r\n--boundary\r\n{}\r\n--boundary--", FIELD_1, FIELD_2);
+
+        for split_at in 0 .. body.len() {
+            debug!("Testing split at: {}", split_at);
+
+            for &strict in &[false, true] {
+                let mut reader = BoundaryReader::from_reader(
+                    SplitReader::split(body.as_bytes(), split_at), BOUNDARY
+                );
+                reader.set_min_buf_size(0);
+                reader.set_strict_crlf(strict);
+
+                assert_eq!(read_fields(&mut reader).unwrap(), [FIELD_1, FIELD_2],
+                           "split at {}", split_at);
+            }
+        }
+    }
+
+    fn two_fields_body() -> String {
+        format!("--boundary\r\n{}\r\n--boundary\r\n{}\r\n--boundary--\r\n", FIELD_1, FIELD_2)
+    }
+
+    #[test]
+    fn test_transient_zero_reads() {
+        ::init_log();
+
+        let body = two_fields_body();
+
+        for &len in &[1, 5, 13] {
+            for &would_block in &[false, true] {
+                // runs of up to `DEFAULT_MAX_EMPTY_READS` zero-length reads between short reads
+                let mut script = Vec::new();
+                for i in 0 .. body.len() / len + 1 {
+                    script.push(Fault::Short(len));
+
+                    for _ in 0 ..= i % ::server::DEFAULT_MAX_EMPTY_READS as usize {
+                        script.push(Fault::Zero);
+                    }
+
+                    // resets the count
+                    if would_block {
+                        script.push(Fault::WouldBlock);
+                        script.extend(vec![Fault::Zero; 10]);
+                    }
+                }
+
+                let mut reader = BoundaryReader::from_reader(FaultyReader::new(body.as_bytes(), script), BOUNDARY);
+
+                assert_eq!(read_fields(&mut reader).unwrap(), [FIELD_1, FIELD_2],
+                           "len: {}, would block: {}", len, would_block);
+                assert!(reader.finished_cleanly());
+            }
+        }
+    }
+
+    #[test]
+    fn test_stalled_source() {
+        ::init_log();
+
+        let body = two_fields_body();
+        // cut off in the second field
+        let body = &body.as_bytes()[..body.len() - 20];
+
+        let mut reader = BoundaryReader::from_reader(FaultyReader::new(body, vec![Fault::Short(10), Fault::Zero]), BOUNDARY);
+
+        let err = read_fields(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let err = err.get_ref().and_then(|e| e.downcast_ref::<NoProgressError>())
+            .expect("expected `NoProgressError`");
+        assert_eq!(err.offset, body.len() as u64);
+        assert_eq!(err.empty_reads, ::server::DEFAULT_MAX_EMPTY_READS + 1);
+
+        // a source which has never stalled just ended, within the field
+        let is_no_progress = |err: &io::Error| err.get_ref().is_some_and(|e| e.is::<NoProgressError>());
+
+        let mut reader = BoundaryReader::from_reader(body, BOUNDARY);
+        let err = read_fields(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(!is_no_progress(&err), "{:?}", err);
+
+        // as does any source without retries
+        let mut reader = BoundaryReader::from_reader(FaultyReader::new(body, vec![Fault::Short(10), Fault::Zero]), BOUNDARY);
+        reader.set_max_empty_reads(0);
+        let err = read_fields(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(!is_no_progress(&err), "{:?}", err);
     }
 
     #[cfg(feature = "bench")]
@@ -0,0 +1,290 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The buffered reader under `BoundaryReader` and `PartsSource`, which must keep a minimum
+//! number of bytes buffered so a boundary is never split across reads.
+//!
+//! Backed by `buf_redux` by default. With the `std-buffer` feature, or without `buf_redux`,
+//! a small reader using only `std` is used instead (`std::io::BufReader` only reads when its
+//! buffer is empty). Both move the unconsumed bytes to the front of the buffer before reading
+//! when fewer than `min_buffered()` bytes are buffered.
+
+use std::io::BufRead;
+
+/// A `BufRead` which reads until at least `min_buffered()` bytes are buffered
+/// (or the end of the source) whenever `fill_buf()` is called.
+pub trait MinBufRead: BufRead {
+    /// The bytes currently buffered, without reading more.
+    fn buffer(&self) -> &[u8];
+
+    /// The number of bytes currently buffered.
+    fn buf_len(&self) -> usize {
+        self.buffer().len()
+    }
+
+    /// The number of bytes the buffer can hold without reallocating.
+    fn capacity(&self) -> usize;
+
+    fn min_buffered(&self) -> usize;
+
+    fn set_min_buffered(&mut self, min_buffered: usize);
+}
+
+#[cfg(all(feature = "buf_redux", not(feature = "std-buffer")))]
+mod imp {
+    use super::super::buf_redux::BufReader;
+    use super::super::buf_redux::policy::MinBuffered;
+
+    use std::io::{self, BufRead, Read};
+
+    use super::MinBufRead;
+
+    /// The `MinBufRead` implementation using `buf_redux`.
+    #[derive(Debug)]
+    pub struct MinBufReader<R>(BufReader<R, MinBuffered>);
+
+    impl<R: Read> MinBufReader<R> {
+        pub fn new(inner: R, min_buffered: usize) -> Self {
+            MinBufReader(BufReader::new(inner).set_policy(MinBuffered(min_buffered)))
+        }
+    }
+
+    impl<R> MinBufReader<R> {
+        pub fn get_ref(&self) -> &R {
+            self.0.get_ref()
+        }
+
+        pub fn get_mut(&mut self) -> &mut R {
+            self.0.get_mut()
+        }
+    }
+
+    impl<R: Read> MinBufRead for MinBufReader<R> {
+        fn buffer(&self) -> &[u8] {
+            self.0.buffer()
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.capacity()
+        }
+
+        fn min_buffered(&self) -> usize {
+            self.0.policy().0
+        }
+
+        fn set_min_buffered(&mut self, min_buffered: usize) {
+            self.0.policy_mut().0 = min_buffered;
+        }
+    }
+
+    impl<R: Read> Read for MinBufReader<R> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            self.0.read(out)
+        }
+    }
+
+    impl<R: Read> BufRead for MinBufReader<R> {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            // `MinBuffered` only compacts when the whole minimum fits in the free space; with a
+            // full tail it would read into an empty slice, which looks like the end of the source.
+            if self.0.buf_len() < self.min_buffered() {
+                self.0.make_room();
+            }
+
+            self.0.fill_buf()
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.0.consume(amt)
+        }
+    }
+
+    impl<R: io::Seek> io::Seek for MinBufReader<R> {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+}
+
+#[cfg(any(not(feature = "buf_redux"), feature = "std-buffer"))]
+mod imp {
+    use std::io::{self, BufRead, Read};
+    use std::{cmp, fmt};
+
+    use super::MinBufRead;
+
+    /// The same as `buf_redux`.
+    const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+    /// The `MinBufRead` implementation using only `std`.
+    pub struct MinBufReader<R> {
+        inner: R,
+        /// The bytes in `buf[pos..]` are buffered.
+        buf: Vec<u8>,
+        pos: usize,
+        min_buffered: usize,
+    }
+
+    impl<R> MinBufReader<R> {
+        pub fn new(inner: R, min_buffered: usize) -> Self {
+            MinBufReader {
+                inner,
+                buf: Vec::with_capacity(DEFAULT_CAPACITY),
+                pos: 0,
+                min_buffered,
+            }
+        }
+
+        pub fn get_ref(&self) -> &R {
+            &self.inner
+        }
+
+        pub fn get_mut(&mut self) -> &mut R {
+            &mut self.inner
+        }
+    }
+
+    impl<R: Read> MinBufReader<R> {
+        /// Move the buffered bytes to the start of the buffer, so all free space can be read into.
+        fn make_room(&mut self) {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+
+        /// Read once into the free space at the end of the buffer.
+        fn read_into_buf(&mut self) -> io::Result<usize> {
+            let filled = self.buf.len();
+            let capacity = self.buf.capacity();
+            self.buf.resize(capacity, 0);
+
+            let res = self.inner.read(&mut self.buf[filled..]);
+            let read = *res.as_ref().unwrap_or(&0);
+            self.buf.truncate(filled + read);
+
+            res
+        }
+    }
+
+    impl<R: Read> MinBufRead for MinBufReader<R> {
+        fn buffer(&self) -> &[u8] {
+            &self.buf[self.pos..]
+        }
+
+        fn capacity(&self) -> usize {
+            self.buf.capacity()
+        }
+
+        fn min_buffered(&self) -> usize {
+            self.min_buffered
+        }
+
+        fn set_min_buffered(&mut self, min_buffered: usize) {
+            self.min_buffered = min_buffered;
+        }
+    }
+
+    impl<R: Read> Read for MinBufReader<R> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let read = self.fill_buf()?.read(out)?;
+            self.consume(read);
+            Ok(read)
+        }
+    }
+
+    impl<R: Read> BufRead for MinBufReader<R> {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            while self.buf_len() < self.min_buffered {
+                self.make_room();
+
+                if self.capacity() < self.min_buffered {
+                    self.buf.reserve(self.min_buffered);
+                }
+
+                match self.read_into_buf() {
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            Ok(self.buffer())
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.pos = cmp::min(self.pos + amt, self.buf.len());
+        }
+    }
+
+    impl<R: io::Seek> io::Seek for MinBufReader<R> {
+        /// Seeks the source and discards the buffer.
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            let pos = match pos {
+                // the source is ahead by the bytes that are buffered
+                io::SeekFrom::Current(offset) =>
+                    io::SeekFrom::Current(offset - (self.buf.len() - self.pos) as i64),
+                pos => pos,
+            };
+
+            let res = self.inner.seek(pos)?;
+            self.buf.clear();
+            self.pos = 0;
+            Ok(res)
+        }
+    }
+
+    impl<R: fmt::Debug> fmt::Debug for MinBufReader<R> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("MinBufReader")
+                .field("inner", &self.inner)
+                .field("buf_len", &(self.buf.len() - self.pos))
+                .field("capacity", &self.buf.capacity())
+                .field("min_buffered", &self.min_buffered)
+                .finish()
+        }
+    }
+}
+
+pub use self::imp::MinBufReader;
+
+#[cfg(test)]
+mod test {
+    use super::{MinBufRead, MinBufReader};
+
+    use std::io::{self, BufRead, Read};
+
+    /// Returns at most 3 bytes per read.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let len = ::std::cmp::min(3, out.len());
+            self.0.read(&mut out[..len])
+        }
+    }
+
+    #[test]
+    fn test_min_buffered() {
+        let data: Vec<u8> = (0 .. 100).collect();
+        let mut reader = MinBufReader::new(Trickle(&data), 10);
+
+        assert_eq!(reader.min_buffered(), 10);
+        assert!(reader.fill_buf().unwrap().len() >= 10);
+        reader.consume(8);
+        assert!(reader.fill_buf().unwrap().len() >= 10);
+        assert_eq!(reader.buffer()[0], 8);
+
+        reader.set_min_buffered(200);
+        assert_eq!(reader.fill_buf().unwrap().len(), 92);
+        assert!(reader.capacity() >= 200);
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[8..]);
+        assert_eq!(reader.buf_len(), 0);
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Decoding of text fields in the charsets HTML forms are submitted in,
+//! for `SaveBuilder::honor_charset_field()`.
+//!
+//! Only UTF-8 and windows-1252 are supported.
+
+/// A charset text fields may be decoded from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    /// Also used for `ISO-8859-1` and `US-ASCII`, as browsers do.
+    Windows1252,
+}
+
+const UTF_8_LABELS: &[&str] = &[
+    "unicode-1-1-utf-8", "unicode11utf8", "unicode20utf8", "utf-8", "utf8", "x-unicode20utf8",
+];
+
+const WINDOWS_1252_LABELS: &[&str] = &[
+    "ansi_x3.4-1968", "ascii", "cp1252", "cp819", "csisolatin1", "ibm819", "iso-8859-1",
+    "iso-ir-100", "iso8859-1", "iso88591", "iso_8859-1", "iso_8859-1:1987", "l1", "latin1",
+    "us-ascii", "windows-1252", "x-cp1252",
+];
+
+/// The code points of bytes `0x80 ..= 0x9F` in windows-1252; the rest match ISO-8859-1.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+impl Charset {
+    /// Look up a charset by label as per the WHATWG Encoding Standard
+    /// (case-insensitive, surrounding whitespace ignored).
+    pub fn from_label(label: &str) -> Option<Charset> {
+        let label = label.trim().to_ascii_lowercase();
+
+        if UTF_8_LABELS.contains(&&*label) {
+            Some(Charset::Utf8)
+        } else if WINDOWS_1252_LABELS.contains(&&*label) {
+            Some(Charset::Windows1252)
+        } else {
+            None
+        }
+    }
+
+    /// Decode `bytes` from a single-byte charset, appending to `out`.
+    ///
+    /// Panics if called on `Utf8`, which needs validation instead.
+    pub fn decode_to(self, bytes: &[u8], out: &mut String) {
+        assert_ne!(self, Charset::Utf8, "UTF-8 is not a single-byte charset");
+
+        out.reserve(bytes.len());
+        out.extend(bytes.iter().map(|&b| match b {
+            0x80 ..= 0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+            _ => b as char,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Charset;
+
+    #[test]
+    fn test_from_label() {
+        assert_eq!(Charset::from_label("UTF-8"), Some(Charset::Utf8));
+        assert_eq!(Charset::from_label(" ISO-8859-1 "), Some(Charset::Windows1252));
+        assert_eq!(Charset::from_label("Windows-1252"), Some(Charset::Windows1252));
+        assert_eq!(Charset::from_label("Shift_JIS"), None);
+        assert_eq!(Charset::from_label(""), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut out = String::new();
+        Charset::Windows1252.decode_to(b"Caf\xE9 \x80\x9F\x81 \xFF", &mut out);
+        assert_eq!(out, "Caf\u{E9} \u{20AC}\u{178}\u{81} \u{FF}");
+    }
+}
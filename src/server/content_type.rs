@@ -0,0 +1,266 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Detection of `multipart/*` request content types.
+
+use std::borrow::Cow;
+
+/// The subtype of a `multipart/*` content type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultipartKind {
+    /// `multipart/form-data`, the only kind `Multipart` is meant for.
+    FormData,
+    /// `multipart/mixed`
+    Mixed,
+    /// `multipart/related`; see the `type` and `start` parameters.
+    Related,
+    /// `multipart/byteranges`
+    ByteRanges,
+    /// `multipart/x-mixed-replace`
+    XMixedReplace,
+    /// Any other subtype, in lowercase.
+    Other(String),
+}
+
+impl MultipartKind {
+    fn from_subtype(subtype: &str) -> MultipartKind {
+        let subtype = subtype.to_ascii_lowercase();
+
+        match &*subtype {
+            "form-data" => MultipartKind::FormData,
+            "mixed" => MultipartKind::Mixed,
+            "related" => MultipartKind::Related,
+            "byteranges" => MultipartKind::ByteRanges,
+            "x-mixed-replace" => MultipartKind::XMixedReplace,
+            _ => MultipartKind::Other(subtype),
+        }
+    }
+}
+
+/// A parsed `multipart/*` content type, returned by `parse_multipart_type()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultipartType<'a> {
+    /// The subtype.
+    pub kind: MultipartKind,
+    /// The parameters in the order they appeared, as `(name, value)`.
+    ///
+    /// Names are as written; values have quotes removed and escapes resolved.
+    pub params: Vec<(&'a str, Cow<'a, str>)>,
+}
+
+impl<'a> MultipartType<'a> {
+    /// Get the value of the first parameter with the given name (case-insensitive).
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|&&(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &**value)
+    }
+
+    /// Get the `boundary` parameter.
+    pub fn boundary(&self) -> Option<&str> {
+        self.param("boundary")
+    }
+
+    /// Get the `boundary` parameter borrowed from the original string.
+    ///
+    /// Returns `None` if the boundary contains escapes, which no valid boundary does.
+    pub fn into_boundary(self) -> Option<&'a str> {
+        self.params.into_iter().find(|&(param, _)| param.eq_ignore_ascii_case("boundary"))
+            .and_then(|(_, value)| match value {
+                Cow::Borrowed(value) => Some(value),
+                Cow::Owned(_) => None,
+            })
+    }
+}
+
+/// Check if `content_type` (the value of a `Content-Type` header) is a `multipart/*` type,
+/// and if so, which one.
+///
+/// See `parse_multipart_type()` for details.
+pub fn is_multipart(content_type: &str) -> Option<MultipartKind> {
+    parse_multipart_type(content_type).map(|parsed| parsed.kind)
+}
+
+/// Parse `content_type` (the value of a `Content-Type` header) if it is a `multipart/*` type.
+///
+/// The type, subtype and parameter names are matched case-insensitively as per
+/// [IETF RFC 7231, Section 3.1.1.1](https://tools.ietf.org/html/rfc7231#section-3.1.1.1).
+/// Whitespace around the separators is tolerated, as are empty parameters;
+/// parameters without a value are skipped.
+pub fn parse_multipart_type(content_type: &str) -> Option<MultipartType<'_>> {
+    let (media_type, rest) = split_at_byte(content_type, b';');
+    let (type_, subtype) = split_at_byte(media_type, b'/');
+
+    let (type_, subtype) = (type_.trim(), subtype.get(1..)?.trim());
+
+    if !type_.eq_ignore_ascii_case("multipart") || subtype.is_empty() {
+        return None;
+    }
+
+    Some(MultipartType {
+        kind: MultipartKind::from_subtype(subtype),
+        params: parse_params(rest),
+    })
+}
+
+/// Parse the parameters of a header value, starting at the `;` after the value itself
+/// (`rest` may also be empty).
+///
+/// Whitespace around the separators is tolerated, as are empty parameters;
+/// parameters without a value are skipped.
+pub fn parse_params(mut rest: &str) -> Vec<(&str, Cow<'_, str>)> {
+    let mut params = Vec::new();
+
+    while !rest.is_empty() {
+        // skip the `;` and any whitespace
+        rest = rest[1..].trim_start();
+
+        let (name, after_name) = split_at_byte(rest, b'=');
+
+        if after_name.is_empty() || name.contains(';') {
+            // no value; skip to the next parameter
+            rest = split_at_byte(rest, b';').1;
+            continue;
+        }
+
+        let after_eq = after_name[1..].trim_start();
+
+        let (value, after_value) = if after_eq.starts_with('"') {
+            parse_quoted(after_eq)
+        } else {
+            let (value, after_value) = split_at_byte(after_eq, b';');
+            (Cow::Borrowed(value.trim_end()), after_value)
+        };
+
+        params.push((name.trim(), value));
+
+        // anything between a quoted string and the next `;` is ignored
+        rest = split_at_byte(after_value, b';').1;
+    }
+
+    params
+}
+
+/// Split at the first occurrence of `byte`, which stays at the start of the second half.
+fn split_at_byte(s: &str, byte: u8) -> (&str, &str) {
+    s.split_at(s.bytes().position(|b| b == byte).unwrap_or(s.len()))
+}
+
+/// Parse a quoted string starting at `s[0]`, returning its contents and the rest of `s`.
+///
+/// An unterminated string runs to the end of `s`.
+fn parse_quoted(s: &str) -> (Cow<'_, str>, &str) {
+    let s = &s[1..];
+    let mut owned: Option<String> = None;
+    let mut chars = s.char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => {
+                let value = owned.map_or(Cow::Borrowed(&s[..idx]), Cow::Owned);
+                return (value, &s[idx + 1..]);
+            },
+            '\\' => {
+                let owned = owned.get_or_insert_with(|| s[..idx].to_owned());
+
+                if let Some((_, escaped)) = chars.next() {
+                    owned.push(escaped);
+                }
+            },
+            c => if let Some(ref mut owned) = owned { owned.push(c) },
+        }
+    }
+
+    (owned.map_or(Cow::Borrowed(s), Cow::Owned), "")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MultipartKind, is_multipart, parse_multipart_type};
+    use super::MultipartKind::*;
+
+    /// `Content-Type` values sent by browsers and HTTP libraries, or seen in the wild.
+    const REAL_WORLD: &[(&str, MultipartKind, Option<&str>)] = &[
+        // Chrome, Safari
+        ("multipart/form-data; boundary=----WebKitFormBoundary7MA4YWxkTrZu0gW",
+         FormData, Some("----WebKitFormBoundary7MA4YWxkTrZu0gW")),
+        // Firefox
+        ("multipart/form-data; boundary=---------------------------974767299852498929531610575",
+         FormData, Some("---------------------------974767299852498929531610575")),
+        // curl
+        ("multipart/form-data; boundary=------------------------d74496d66958873e",
+         FormData, Some("------------------------d74496d66958873e")),
+        // Python requests
+        ("multipart/form-data; boundary=a3b8c1f5e2d94b8b9c2f1e0d7a6b5c4d",
+         FormData, Some("a3b8c1f5e2d94b8b9c2f1e0d7a6b5c4d")),
+        // .NET HttpClient quotes the boundary
+        ("multipart/form-data; boundary=\"4e9c9e70-5a32-4c7e-9a2b-1f6c0d8e2b7a\"",
+         FormData, Some("4e9c9e70-5a32-4c7e-9a2b-1f6c0d8e2b7a")),
+        // Apache HttpClient with a charset first
+        ("multipart/form-data; charset=UTF-8; boundary=Gx1tLVqmgWTpOa8hQ1Mk5z6ivtGiMW",
+         FormData, Some("Gx1tLVqmgWTpOa8hQ1Mk5z6ivtGiMW")),
+        // hand-written clients
+        ("Multipart/Form-Data;boundary=xyz", FormData, Some("xyz")),
+        ("MULTIPART/FORM-DATA; BOUNDARY=xyz", FormData, Some("xyz")),
+        ("multipart/form-data ;  boundary=xyz ; foo=bar", FormData, Some("xyz")),
+        ("multipart/form-data;;boundary=xyz;", FormData, Some("xyz")),
+        ("multipart/form-data; boundary=\"with space\"", FormData, Some("with space")),
+        ("multipart/form-data", FormData, None),
+        ("multipart/form-data; charset", FormData, None),
+        // SOAP with attachments (MTOM)
+        ("multipart/related; type=\"application/xop+xml\"; \
+          start=\"<rootpart@soapui.org>\"; start-info=\"text/xml\"; \
+          boundary=\"----=_Part_0_1044880532.1555065233312\"",
+         Related, Some("----=_Part_0_1044880532.1555065233312")),
+        // email
+        ("multipart/mixed; boundary=\"gc0p4Jq0M2Yt08jU534c0p\"", Mixed, Some("gc0p4Jq0M2Yt08jU534c0p")),
+        // range requests
+        ("multipart/byteranges; boundary=3d6b6a416f9b5", ByteRanges, Some("3d6b6a416f9b5")),
+        // MJPEG cameras
+        ("multipart/x-mixed-replace;boundary=--myboundary", XMixedReplace, Some("--myboundary")),
+    ];
+
+    #[test]
+    fn test_real_world() {
+        for &(content_type, ref kind, boundary) in REAL_WORLD {
+            let parsed = parse_multipart_type(content_type)
+                .unwrap_or_else(|| panic!("failed to parse {:?}", content_type));
+
+            assert_eq!(parsed.kind, *kind, "{:?}", content_type);
+            assert_eq!(parsed.boundary(), boundary, "{:?}", content_type);
+            assert_eq!(parsed.into_boundary(), boundary, "{:?}", content_type);
+            assert_eq!(is_multipart(content_type).as_ref(), Some(kind));
+        }
+    }
+
+    #[test]
+    fn test_not_multipart() {
+        for &content_type in &["application/x-www-form-urlencoded", "text/plain; charset=utf-8",
+                               "", "multipart", "multipart/", "multipart/ ; boundary=xyz",
+                               "application/multipart", "multipartform-data; boundary=xyz"] {
+            assert_eq!(is_multipart(content_type), None, "{:?}", content_type);
+        }
+    }
+
+    #[test]
+    fn test_params() {
+        let parsed = parse_multipart_type(
+            "multipart/related; type=\"application/xop+xml\"; start=\"<root>\"; \
+             boundary=\"a\\\"b\"; x=\"unterminated"
+        ).unwrap();
+
+        assert_eq!(parsed.param("TYPE"), Some("application/xop+xml"));
+        assert_eq!(parsed.param("start"), Some("<root>"));
+        assert_eq!(parsed.boundary(), Some("a\"b"));
+        assert_eq!(parsed.param("x"), Some("unterminated"));
+        assert_eq!(parsed.params.len(), 4);
+
+        // an escaped boundary can't be borrowed
+        assert_eq!(parsed.into_boundary(), None);
+
+        assert_eq!(is_multipart("multipart/Alternative"), Some(Other("alternative".into())));
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Parsing and formatting of the dates in `Content-Disposition` parameters
+//! ([IETF RFC 2183, Section 2.4](https://tools.ietf.org/html/rfc2183#section-2.4)).
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// The days of the week, starting from that of 1970-01-01.
+#[cfg(feature = "http")]
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+/// Parse a date-time as per [IETF RFC 5322, Section 3.3](https://tools.ietf.org/html/rfc5322#section-3.3),
+/// e.g. `Wed, 12 Feb 1997 16:29:51 -0500`, including the obsolete two-digit years and
+/// named zones.
+pub fn parse_date(date: &str) -> Option<SystemTime> {
+    // the day of the week is optional and redundant
+    let date = match date.find(',') {
+        Some(idx) => &date[idx + 1..],
+        None => date,
+    };
+
+    let mut parts = date.split_whitespace();
+
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?.to_ascii_lowercase();
+    let month = MONTHS.iter().position(|&m| m == month)? as u32 + 1;
+    let year = parse_year(parts.next()?)?;
+
+    let mut time = parts.next()?.split(':');
+    let hour: u32 = time.next()?.parse().ok()?;
+    let minute: u32 = time.next()?.parse().ok()?;
+    let second: u32 = time.next().map_or(Some(0), |s| s.parse().ok())?;
+
+    let offset = parse_zone(parts.next()?)?;
+
+    if parts.next().is_some() || time.next().is_some() || day < 1 || day > days_in_month(year, month)
+        || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let secs = days_from_epoch(year, month, day) * 86_400
+        + i64::from(hour * 3600 + minute * 60 + second) - offset;
+
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.wrapping_neg() as u64))
+    }
+}
+
+/// Format a date-time in UTC as `parse_date()` accepts it, e.g. `Wed, 12 Feb 1997 21:29:51 +0000`.
+///
+/// Fractions of a second are dropped. Returns `None` for years which don't have four digits.
+#[cfg(feature = "http")]
+pub fn format_date(date: SystemTime) -> Option<String> {
+    let secs = match date.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        // round down rather than toward the epoch
+        Err(e) => {
+            let before = e.duration();
+            -(before.as_secs() as i64) - if before.subsec_nanos() > 0 { 1 } else { 0 }
+        },
+    };
+
+    let days = if secs >= 0 { secs / 86_400 } else { (secs - 86_399) / 86_400 };
+    let secs = secs - days * 86_400;
+    let (year, month, day) = date_from_days(days);
+
+    if !(1000..=9999).contains(&year) {
+        return None;
+    }
+
+    let month = MONTHS[month as usize - 1];
+
+    Some(format!(
+        "{}, {:02} {}{} {} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[(days % 7 + 7) as usize % 7], day, month[..1].to_ascii_uppercase(), &month[1..],
+        year, secs / 3600, secs / 60 % 60, secs % 60
+    ))
+}
+
+fn parse_year(year: &str) -> Option<i64> {
+    let value: i64 = year.parse().ok()?;
+
+    // obsolete two- and three-digit years, RFC 5322 Section 4.3
+    match year.len() {
+        2 if value < 50 => Some(value + 2000),
+        2 | 3 => Some(value + 1900),
+        4 => Some(value),
+        _ => None,
+    }
+}
+
+/// The offset of the zone from UTC in seconds.
+fn parse_zone(zone: &str) -> Option<i64> {
+    let bytes = zone.as_bytes();
+
+    if bytes.len() == 5 && (bytes[0] == b'+' || bytes[0] == b'-')
+        && bytes[1..].iter().all(u8::is_ascii_digit) {
+        let hours: i64 = zone[1..3].parse().ok()?;
+        let minutes: i64 = zone[3..].parse().ok()?;
+        let offset = hours * 3600 + minutes * 60;
+        return Some(if bytes[0] == b'-' { -offset } else { offset });
+    }
+
+    let hours = match &*zone.to_ascii_uppercase() {
+        "UT" | "GMT" | "Z" => 0,
+        "EDT" => -4,
+        "EST" | "CDT" => -5,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" => -8,
+        // military zones are to be treated as UTC since their signs were mixed up
+        military if military.len() == 1 && military.bytes().all(|b| b.is_ascii_alphabetic()) => 0,
+        _ => return None,
+    };
+
+    Some(hours * 3600)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days from 1970-01-01 to the given date in the proleptic Gregorian calendar.
+fn days_from_epoch(year: i64, month: u32, day: u32) -> i64 {
+    // count years from March so the leap day is at the end
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The inverse of `days_from_epoch()`: the year, month and day of a number of days from 1970-01-01.
+#[cfg(any(feature = "http", feature = "archive"))]
+pub fn date_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // counting from March
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_date;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn secs(secs: u64) -> Option<::std::time::SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn test_parse_date() {
+        // the example in RFC 2183
+        assert_eq!(parse_date("Wed, 12 Feb 1997 16:29:51 -0500"), secs(855_782_991));
+        assert_eq!(parse_date("12 Feb 1997 21:29:51 GMT"), secs(855_782_991));
+        assert_eq!(parse_date("Thu, 01 Jan 1970 00:00 +0000"), secs(0));
+        assert_eq!(parse_date("29 feb 2000 12:00:00 Z"), secs(951_825_600));
+        assert_eq!(parse_date("1 Jan 01 00:00:00 PST"), secs(978_336_000));
+        assert_eq!(parse_date("31 Dec 1969 23:59:59 UT"), Some(UNIX_EPOCH - Duration::from_secs(1)));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_format_date() {
+        use super::format_date;
+
+        assert_eq!(format_date(secs(855_782_991).unwrap()).unwrap(), "Wed, 12 Feb 1997 21:29:51 +0000");
+        assert_eq!(format_date(secs(951_825_600).unwrap()).unwrap(), "Tue, 29 Feb 2000 12:00:00 +0000");
+        assert_eq!(format_date(UNIX_EPOCH - Duration::from_millis(500)).unwrap(),
+                   "Wed, 31 Dec 1969 23:59:59 +0000");
+
+        // every day over a few centuries
+        for days in -80_000 .. 80_000 {
+            let date = if days >= 0 {
+                UNIX_EPOCH + Duration::from_secs(days as u64 * 86_400 + 43_200)
+            } else {
+                UNIX_EPOCH - Duration::from_secs((-days) as u64 * 86_400 - 43_200)
+            };
+            assert_eq!(parse_date(&format_date(date).unwrap()), Some(date), "{:?}", date);
+        }
+
+        assert_eq!(format_date(UNIX_EPOCH + Duration::from_secs(400 * 366 * 86_400 * 25)), None);
+    }
+
+    #[test]
+    fn test_parse_date_invalid() {
+        for &date in &["", "yesterday", "12 Feb 1997", "12 Feb 1997 16:29:51",
+                       "30 Feb 1997 16:29:51 GMT", "12 Foo 1997 16:29:51 GMT",
+                       "12 Feb 1997 24:00:00 GMT", "12 Feb 1997 16:29:51 +05",
+                       "12 Feb 1997 16:29:51 GMT trailing", "12 Feb 19970 16:29:51 GMT"] {
+            assert_eq!(parse_date(date), None, "{:?}", date);
+        }
+    }
+}
@@ -0,0 +1,463 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A transcript of parser decisions for bug reports, recorded with `Multipart::record_diagnostics()`.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+
+use super::boundary::{DrainLimitExceeded, EmptyPartError, MissingBoundaryError, MissingCrlfError, ParseEvent};
+use super::field::{self, UnnamedFieldError};
+
+/// The most header names recorded for one part; any more are only counted.
+const MAX_HEADER_NAMES: usize = 16;
+
+/// Header names are truncated to this many bytes.
+const MAX_NAME_LEN: usize = 40;
+
+/// A step recorded in [`Diagnostics`](struct.Diagnostics.html).
+///
+/// None of these contain header values or bytes of the body, only offsets, lengths
+/// and header names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiagnosticEvent {
+    /// A `ParseEvent`; consecutive `PartDataChunk`s are merged into one.
+    Parse(ParseEvent),
+    /// The names of the header lines of a part, in lowercase, in the order they appeared.
+    ///
+    /// A line which isn't `name: value` is recorded as `?`, and a name which isn't a valid
+    /// token is recorded with the invalid bytes replaced by `?`.
+    HeaderNames {
+        /// The offset of the first header line.
+        offset: u64,
+        /// The names, up to 16.
+        names: Vec<String>,
+        /// The number of header lines after the first 16.
+        more: usize,
+    },
+    /// A line starting with `--` which is not the boundary, e.g. the boundary of another request
+    /// or one which differs from the one in the request's `Content-Type`.
+    NearMiss {
+        /// The offset of the leading `--`.
+        offset: u64,
+        /// How many bytes after the `--` match the boundary, before the first which doesn't
+        /// (or the end of what was buffered).
+        matched: usize,
+    },
+    /// `read_entry()` returned an error.
+    Error {
+        /// The offset at which parsing stopped.
+        offset: u64,
+        /// The kind of the error.
+        kind: io::ErrorKind,
+        /// A short name for the cause, e.g. `missing-crlf`; this doesn't include the message,
+        /// which may contain header values.
+        cause: &'static str,
+    },
+}
+
+/// A bounded, privacy-preserving transcript of how a request body was parsed,
+/// recorded with `Multipart::record_diagnostics()`.
+///
+/// It is meant to be pasted into a bug report in place of a body which can't be shared:
+/// the `Display` form lists the parser's events with their offsets and lengths, the names (but not
+/// the values) of field headers, lines which look like a boundary but aren't, and the error
+/// which stopped parsing, if any. Only the last `max_events` events are kept.
+#[derive(Clone, Debug)]
+pub struct Diagnostics {
+    events: VecDeque<DiagnosticEvent>,
+    max_events: usize,
+    dropped: u64,
+    boundary_len: usize,
+    /// Whether the next byte consumed is at the start of a line.
+    line_start: bool,
+}
+
+impl Diagnostics {
+    pub(super) fn new(max_events: usize, boundary_len: usize) -> Self {
+        Diagnostics {
+            events: VecDeque::with_capacity(max_events.min(1024)),
+            max_events,
+            dropped: 0,
+            boundary_len,
+            line_start: true,
+        }
+    }
+
+    /// The events recorded, oldest first.
+    pub fn events(&self) -> impl Iterator<Item = &DiagnosticEvent> {
+        self.events.iter()
+    }
+
+    /// The number of events dropped from the start to stay within `max_events`.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// The error which stopped parsing, if any, as `(kind, cause)`.
+    pub fn error(&self) -> Option<(io::ErrorKind, &'static str)> {
+        self.events.iter().rev().filter_map(|event| match *event {
+            DiagnosticEvent::Error { kind, cause, .. } => Some((kind, cause)),
+            _ => None,
+        }).next()
+    }
+
+    fn push(&mut self, event: DiagnosticEvent) {
+        if self.max_events == 0 {
+            self.dropped += 1;
+            return;
+        }
+
+        if self.events.len() == self.max_events {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+
+        self.events.push_back(event);
+    }
+
+    pub(super) fn parse_event(&mut self, event: ParseEvent) {
+        if let ParseEvent::PartDataChunk { offset, len } = event {
+            if let Some(&mut DiagnosticEvent::Parse(ParseEvent::PartDataChunk { offset: last, len: ref mut last_len }))
+                = self.events.back_mut() {
+                if last + *last_len == offset {
+                    *last_len += len;
+                    return;
+                }
+            }
+        }
+
+        self.push(DiagnosticEvent::Parse(event));
+    }
+
+    /// Look for lines starting with `--` in the first `amt` bytes of `buf`, consumed at `offset`
+    /// outside of headers. The rest of `buf` is only used to compare those lines to the boundary.
+    ///
+    /// `boundary` includes the leading `--`.
+    pub(super) fn scan_data(&mut self, buf: &[u8], amt: usize, offset: u64, boundary: &[u8]) {
+        let mut line_start = self.line_start;
+
+        for (idx, &byte) in buf[..amt].iter().enumerate() {
+            if line_start && buf[idx..].starts_with(b"--") {
+                let rest = &buf[idx + 2..];
+                let matched = rest.iter().zip(&boundary[2..]).take_while(|&(a, b)| a == b).count();
+
+                if matched < self.boundary_len {
+                    self.push(DiagnosticEvent::NearMiss { offset: offset + idx as u64, matched });
+                }
+            }
+
+            line_start = byte == b'\n';
+        }
+
+        if amt > 0 {
+            self.line_start = line_start;
+        }
+    }
+
+    /// Record the names of the header lines in `block`, which starts at `offset`,
+    /// up to the blank line which ends it.
+    pub(super) fn header_names(&mut self, block: &[u8], offset: u64) {
+        let mut names = Vec::new();
+        let mut more = 0;
+
+        for line in block.split(|&b| b == b'\n') {
+            let line = if line.ends_with(b"\r") { &line[..line.len() - 1] } else { line };
+
+            if line.is_empty() {
+                break;
+            }
+
+            if names.len() == MAX_HEADER_NAMES {
+                more += 1;
+                continue;
+            }
+
+            names.push(match line.iter().position(|&b| b == b':') {
+                Some(end) if end > 0 => line[..end.min(MAX_NAME_LEN)].iter()
+                    .map(|&b| if is_token(b) { b.to_ascii_lowercase() as char } else { '?' })
+                    .collect(),
+                _ => "?".into(),
+            });
+        }
+
+        // the line after a part's headers is always at a line start
+        self.line_start = true;
+        self.push(DiagnosticEvent::HeaderNames { offset, names, more });
+    }
+
+    pub(super) fn record_error(&mut self, err: &io::Error, offset: u64) {
+        self.push(DiagnosticEvent::Error { offset, kind: err.kind(), cause: error_cause(err) });
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "multipart diagnostics v1: boundary {} bytes", self.boundary_len)?;
+
+        if self.dropped > 0 {
+            write!(f, ", {} earlier events dropped", self.dropped)?;
+        }
+
+        for event in &self.events {
+            f.write_str("\n")?;
+
+            match *event {
+                DiagnosticEvent::Parse(ParseEvent::PartStart { offset }) => write!(f, "@{} part", offset)?,
+                DiagnosticEvent::Parse(ParseEvent::HeadersDone { offset, len }) =>
+                    write!(f, "@{} headers-done +{}", offset, len)?,
+                DiagnosticEvent::Parse(ParseEvent::PartDataChunk { offset, len }) =>
+                    write!(f, "@{} data +{}", offset, len)?,
+                DiagnosticEvent::Parse(ParseEvent::PartEnd { offset }) => write!(f, "@{} part-end", offset)?,
+                DiagnosticEvent::Parse(ParseEvent::BodyEnd { offset, clean }) =>
+                    write!(f, "@{} body-end {}", offset, if clean { "clean" } else { "unclosed" })?,
+                DiagnosticEvent::HeaderNames { offset, ref names, more } => {
+                    write!(f, "@{} header-names {}", offset, names.join(","))?;
+
+                    if more > 0 {
+                        write!(f, ",+{}", more)?;
+                    }
+                },
+                DiagnosticEvent::NearMiss { offset, matched } =>
+                    write!(f, "@{} near-miss {}/{}", offset, matched, self.boundary_len)?,
+                DiagnosticEvent::Error { offset, kind, cause } =>
+                    write!(f, "@{} error {:?} {}", offset, kind, cause)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A header name character, as per [IETF RFC 7230, Section 3.2.6](https://tools.ietf.org/html/rfc7230#section-3.2.6).
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn error_cause(err: &io::Error) -> &'static str {
+    let inner = match err.get_ref() {
+        Some(inner) => inner,
+        None => return "io",
+    };
+
+    if inner.is::<MissingCrlfError>() {
+        "missing-crlf"
+    } else if inner.is::<MissingBoundaryError>() {
+        "missing-boundary"
+    } else if inner.is::<EmptyPartError>() {
+        "empty-part"
+    } else if inner.is::<DrainLimitExceeded>() {
+        "drain-limit"
+    } else if inner.is::<UnnamedFieldError>() {
+        "unnamed-field"
+    } else {
+        field::header_error_cause(inner).unwrap_or("other")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DiagnosticEvent;
+    use server::{Multipart, ParseEvent, ParseLimits};
+
+    use std::fs;
+    use std::io::{self, Read};
+    use std::path::Path;
+
+    /// Parse `body` with diagnostics, reading every field, and return the `Display` form.
+    fn transcript(body: &[u8], boundary: &str, strict: bool) -> String {
+        let mut multipart = Multipart::with_body(body, boundary);
+        multipart.strict_crlf(strict)
+            .limits(ParseLimits::default().max_header_bytes(1024))
+            .record_diagnostics(64);
+
+        let _ = multipart.foreach_entry(|mut field| {
+            let _ = field.data.read_to_end(&mut Vec::new());
+        });
+
+        let diagnostics = multipart.diagnostics().unwrap().to_string();
+        check_format(&diagnostics);
+        diagnostics
+    }
+
+    /// Check that every line is one of the forms written by `Display`, with nothing but
+    /// numbers, fixed words and header names.
+    fn check_format(diagnostics: &str) {
+        let mut lines = diagnostics.lines();
+        assert!(lines.next().unwrap().starts_with("multipart diagnostics v1: boundary "));
+
+        let is_number = |word: &str| word.parse::<u64>().is_ok();
+        let is_len = |word: &str| word.starts_with('+') && is_number(&word[1..]);
+
+        for line in lines {
+            let words: Vec<&str> = line.split(' ').collect();
+            assert!(words[0].starts_with('@') && is_number(&words[0][1..]), "{:?}", line);
+
+            let valid = match words[1..] {
+                ["part"] | ["part-end"] | ["body-end", "clean"] | ["body-end", "unclosed"] => true,
+                ["headers-done", len] | ["data", len] => is_len(len),
+                ["near-miss", matched] => matched.split('/').all(is_number),
+                ["error", kind, cause] => kind.chars().all(|c| c.is_ascii_alphabetic())
+                    && cause.chars().all(|c| c.is_ascii_lowercase() || c == '-'),
+                ["header-names", names] => names.split(',').all(|name| name.len() <= super::MAX_NAME_LEN
+                    && name.bytes().all(|b| super::is_token(b) || b == b'?')),
+                _ => false,
+            };
+
+            assert!(valid, "invalid line: {:?}", line);
+        }
+    }
+
+    /// Bodies with `SECRET` in every place the client controls other than header names.
+    const SECRET_BODIES: &[&[u8]] = &[
+        b"SECRET preamble\r\n--SECRET\r\n--boundary\r\n\
+          Content-Disposition: form-data; name=\"SECRET\"; filename=\"SECRET.txt\"\r\n\
+          Content-Type: text/SECRET; charset=SECRET\r\n\r\n\
+          SECRET data\r\n--SECRET and --boundar\r\n--boundary\r\n\
+          Content-Disposition: form-data; name=\"b\"\r\n\r\nSECRET\r\n--boundary--\r\nSECRET epilogue",
+        // errors while reading headers
+        b"--boundary\r\nContent-Disposition: form-data; name=\"SECRET\"\r\nX-Private: SECRET\0\r\n\r\nSECRET\r\n--boundary--",
+        b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\nContent-Type: SECRET\r\n\r\nSECRET\r\n--boundary--",
+        b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\nSECRET\rSECRET\r\n\r\nSECRET\r\n--boundary--",
+        b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\n SECRET fold\r\n\r\n--boundary--",
+        b"--boundary\r\nSECRET without a colon\r\n\r\nSECRET\r\n--boundary--",
+        b"--boundary\r\nContent-Disposition: form-data; name=\"SECRET",
+        // errors at boundaries
+        b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nSECRET--boundary--",
+        b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nSECRET\r\n--boundarySECRET",
+        b"SECRET without a boundary",
+        b"",
+    ];
+
+    #[test]
+    fn no_values_or_data() {
+        ::init_log();
+
+        let mut long_header = b"--boundary\r\nContent-Disposition: form-data; name=\"".to_vec();
+        long_header.extend(b"SECRET".iter().cycle().take(4096));
+
+        for body in SECRET_BODIES.iter().cloned().chain(Some(&long_header[..])) {
+            for &strict in &[false, true] {
+                let diagnostics = transcript(body, "boundary", strict);
+                assert!(!diagnostics.to_lowercase().contains("secret"), "{:?}:\n{}",
+                        String::from_utf8_lossy(body), diagnostics);
+            }
+        }
+    }
+
+    #[test]
+    fn corpus() {
+        ::init_log();
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus");
+        let manifest = fs::read_to_string(dir.join("manifest.txt")).unwrap();
+        let mut name = None;
+
+        for line in manifest.lines() {
+            if line.starts_with('[') {
+                name = Some(line.trim_matches(|c| c == '[' || c == ']'));
+            } else if line.starts_with("boundary = ") {
+                let body = fs::read(dir.join(format!("{}.multipart", name.unwrap()))).unwrap();
+                let boundary = line.trim_start_matches("boundary = ");
+
+                for &strict in &[false, true] {
+                    let diagnostics = transcript(&body, boundary, strict);
+                    assert!(!diagnostics.contains(boundary), "{}:\n{}", name.unwrap(), diagnostics);
+                    assert!(!diagnostics.contains(" error "), "{}:\n{}", name.unwrap(), diagnostics);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn events() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"a\"\r\n\
+                     Content-Type: text/plain\r\n\r\n\
+                     --boundarx\r\n\
+                     --boundary--";
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.record_diagnostics(16);
+
+        // data read in small pieces is recorded as one chunk
+        multipart.foreach_entry(|mut field| {
+            let mut buf = [0; 3];
+            while field.data.read(&mut buf).unwrap() > 0 {}
+        }).unwrap();
+
+        let diagnostics = multipart.diagnostics().unwrap();
+        assert_eq!(diagnostics.events().cloned().collect::<Vec<_>>(), vec![
+            DiagnosticEvent::Parse(ParseEvent::PartStart { offset: 0 }),
+            DiagnosticEvent::HeaderNames {
+                offset: 12,
+                names: vec!["content-disposition".into(), "content-type".into()],
+                more: 0,
+            },
+            DiagnosticEvent::Parse(ParseEvent::HeadersDone { offset: 12, len: 70 }),
+            DiagnosticEvent::NearMiss { offset: 82, matched: 7 },
+            DiagnosticEvent::Parse(ParseEvent::PartDataChunk { offset: 82, len: 10 }),
+            DiagnosticEvent::Parse(ParseEvent::PartEnd { offset: 92 }),
+            DiagnosticEvent::Parse(ParseEvent::BodyEnd { offset: body.len() as u64, clean: true }),
+        ]);
+        assert_eq!(diagnostics.error(), None);
+        assert_eq!(diagnostics.dropped(), 0);
+    }
+
+    #[test]
+    fn error() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"a\"\r\n\
+                     Content-Disposition: form-data; name=\"b\"\r\n\r\n\
+                     data\r\n\
+                     --boundary--";
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.strict_crlf(true).record_diagnostics(16);
+        assert!(multipart.foreach_entry(|_| ()).is_err());
+
+        let diagnostics = multipart.diagnostics().unwrap();
+        assert_eq!(diagnostics.error(), Some((io::ErrorKind::InvalidData, "duplicate-header")));
+        assert_eq!(diagnostics.to_string(), "multipart diagnostics v1: boundary 8 bytes\n\
+                                             @0 part\n\
+                                             @12 header-names content-disposition,content-disposition\n\
+                                             @98 error InvalidData duplicate-header");
+    }
+
+    #[test]
+    fn bounded() {
+        ::init_log();
+
+        let mut body = Vec::new();
+
+        for i in 0 .. 100 {
+            body.extend(format!("--boundary\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", i, i).bytes());
+        }
+
+        body.extend(b"--boundary--");
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.record_diagnostics(10);
+        multipart.foreach_entry(|_| ()).unwrap();
+
+        // each field is recorded as 5 events, and the end as one
+        let diagnostics = multipart.diagnostics().unwrap();
+        assert_eq!(diagnostics.events().count(), 10);
+        assert_eq!(diagnostics.dropped(), 100 * 5 + 1 - 10);
+        assert!(diagnostics.to_string().starts_with("multipart diagnostics v1: boundary 8 bytes, 491 earlier events dropped\n"));
+
+        // not recorded unless enabled
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.foreach_entry(|_| ()).unwrap();
+        assert!(multipart.diagnostics().is_none());
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Streaming MD5 and SHA-256 and base64, for verifying the digests declared by `Content-MD5`
+//! and `Digest` field headers, and for `Entries::content_hash()`. Enabled with the `digest`
+//! feature.
+
+use base64::Engine;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+/// Standard base64, accepting values with or without the padding when decoding,
+/// as clients differ on that.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// A digest algorithm supported for verification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    /// Look up an algorithm by its name in the `Digest` header (case-insensitive).
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        if name.eq_ignore_ascii_case("md5") {
+            Some(Algorithm::Md5)
+        } else if name.eq_ignore_ascii_case("sha-256") {
+            Some(Algorithm::Sha256)
+        } else {
+            None
+        }
+    }
+}
+
+/// A running digest computation.
+pub enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Hasher {
+        match algorithm {
+            Algorithm::Md5 => Hasher::Md5(Md5::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match *self {
+            Hasher::Md5(ref mut md5) => md5.update(data),
+            Hasher::Sha256(ref mut sha256) => sha256.update(data),
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(md5) => md5.finalize().to_vec(),
+            Hasher::Sha256(sha256) => sha256.finalize().to_vec(),
+        }
+    }
+}
+
+/// Encode `bytes` as standard base64, with padding.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    BASE64.encode(bytes)
+}
+
+/// Decode standard base64, with or without padding.
+pub fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    BASE64.decode(s).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Algorithm, Hasher, base64_decode, base64_encode};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn digest(algorithm: Algorithm, data: &[u8]) -> String {
+        let mut hasher = Hasher::new(algorithm);
+        for piece in data.chunks(7) {
+            hasher.update(piece);
+        }
+        hex(&hasher.finish())
+    }
+
+    #[test]
+    fn test_digests() {
+        assert_eq!(digest(Algorithm::Md5, b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(digest(Algorithm::Sha256, b"abc"),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_base64() {
+        for &(plain, encoded) in &[(&b""[..], ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v")] {
+            assert_eq!(base64_encode(plain), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), plain);
+            assert_eq!(base64_decode(encoded.trim_end_matches('=')).unwrap(), plain);
+        }
+
+        assert_eq!(base64_decode("Zm9v!"), None);
+        assert_eq!(base64_decode("Zm9vY"), None);
+    }
+}
@@ -8,18 +8,30 @@
 //! `multipart` field header parsing.
 use mime::Mime;
 
+use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
+use std::hash::{Hash, Hasher};
 use std::io::{self, BufRead, Read};
-use std::{fmt, str};
+use std::{cmp, fmt, str};
 
 use std::sync::Arc;
+use std::time::SystemTime;
 
+use super::content_type::parse_params;
+use super::date::parse_date;
+use super::filename::last_component;
+#[cfg(feature = "http")]
+use super::date::format_date;
 use super::httparse::{self, Error as HttparseError, Header, Status, EMPTY_HEADER};
 
 use self::ReadEntryResult::*;
 
+use super::newline::{NewlineFilter, NewlinePolicy};
 use super::save::SaveBuilder;
 
+#[cfg(feature = "http")]
+use http::header::{self, HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue};
+
 const EMPTY_STR_HEADER: StrHeader<'static> = StrHeader { name: "", val: "" };
 
 macro_rules! invalid_cont_disp {
@@ -50,7 +62,16 @@ impl<'s, 'a: 's> fmt::Display for DisplayHeaders<'s, 'a> {
     }
 }
 
-fn with_headers<R, F, Ret>(r: &mut R, closure: F) -> Result<Ret, ParseHeaderError>
+/// Field headers are checked before they are parsed:
+///
+/// * a NUL byte anywhere in the headers is an error;
+/// * a header line starting with a space or tab (obsolete line folding) is an error;
+/// * a CR which is not followed by LF is an error if `strict`, otherwise it is part of the header
+///   value (`httparse` rejects it, so these headers are split by `parse_headers_lenient()`);
+/// * a header line longer than `max_header_bytes` is an error as soon as that many bytes of it
+///   are buffered, whether or not its line ending was found, as are longer headers altogether.
+fn with_headers<R, F, Ret>(r: &mut R, strict: bool, max_header_bytes: usize, closure: F)
+    -> Result<Ret, ParseHeaderError>
 where
     R: BufRead,
     F: FnOnce(&[StrHeader]) -> Ret,
@@ -71,13 +92,31 @@ where
             return Err(ParseHeaderError::TooLarge);
         }
 
+        let bare_cr = match check_headers(buf, strict, max_header_bytes)? {
+            Some(bare_cr) => bare_cr,
+            // read more and try again
+            None => {
+                last_len = buf.len();
+                continue;
+            }
+        };
+
+        let mut headers = [EMPTY_STR_HEADER; HEADER_LEN];
+
+        if bare_cr {
+            let (consume_, headers) = parse_headers_lenient(buf, &mut headers)?;
+            debug!("Parsed headers (lenient): {:?}", headers);
+            consume = consume_;
+            ret = closure(headers);
+            break;
+        }
+
         let mut raw_headers = [EMPTY_HEADER; HEADER_LEN];
 
         match httparse::parse_headers(buf, &mut raw_headers)? {
             // read more and try again
             Status::Partial => last_len = buf.len(),
             Status::Complete((consume_, raw_headers)) => {
-                let mut headers = [EMPTY_STR_HEADER; HEADER_LEN];
                 let headers = copy_headers(raw_headers, &mut headers)?;
                 debug!("Parsed headers: {:?}", headers);
                 consume = consume_;
@@ -91,6 +130,145 @@ where
     Ok(ret)
 }
 
+/// Check the header lines in `buf` for the cases described on `with_headers()`.
+///
+/// Returns `None` if the end of the headers isn't in `buf` yet,
+/// otherwise whether there was a bare CR (only if not `strict`).
+fn check_headers(buf: &[u8], strict: bool, max_header_bytes: usize)
+    -> Result<Option<bool>, ParseHeaderError> {
+    let mut bare_cr = false;
+
+    for line in header_lines(buf) {
+        let line = match line {
+            Some(line) => line,
+            None => {
+                // the bytes after the last LF are a line which isn't terminated yet
+                let unterminated = buf.len() - buf.iter().rposition(|&b| b == b'\n').map_or(0, |lf| lf + 1);
+
+                if unterminated > max_header_bytes {
+                    return Err(ParseHeaderError::HeaderLineTooLong(max_header_bytes));
+                }
+
+                if buf.len() > max_header_bytes {
+                    return Err(ParseHeaderError::TooLarge);
+                }
+
+                return Ok(None);
+            }
+        };
+
+        if line.len() > max_header_bytes {
+            return Err(ParseHeaderError::HeaderLineTooLong(max_header_bytes));
+        }
+
+        if line.is_empty() {
+            // the length of the header lines before this one, including their line endings
+            if line.as_ptr() as usize - buf.as_ptr() as usize > max_header_bytes {
+                return Err(ParseHeaderError::TooLarge);
+            }
+
+            return Ok(Some(bare_cr));
+        }
+
+        if line[0] == b' ' || line[0] == b'\t' {
+            return Err(ParseHeaderError::ObsoleteFold);
+        }
+
+        if line.contains(&0) {
+            return Err(ParseHeaderError::NulByte);
+        }
+
+        if line.contains(&b'\r') {
+            if strict {
+                return Err(ParseHeaderError::BareCr);
+            }
+
+            bare_cr = true;
+        }
+    }
+
+    // `header_lines()` only ends after the empty line or an incomplete one
+    unreachable!()
+}
+
+/// Iterate the lines in `buf`, without their line endings (LF or CRLF).
+///
+/// Yields `None` for a line which is not terminated yet, and then ends.
+fn header_lines<'a>(mut buf: &'a [u8]) -> impl Iterator<Item = Option<&'a [u8]>> + 'a {
+    let mut done = false;
+
+    ::std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+
+        let line = match buf.iter().position(|&b| b == b'\n') {
+            Some(lf) => {
+                let line = &buf[..lf];
+                buf = &buf[lf + 1..];
+                Some(if line.last() == Some(&b'\r') { &line[..lf - 1] } else { line })
+            },
+            None => None,
+        };
+
+        done = match line {
+            Some(line) => line.is_empty(),
+            None => true,
+        };
+
+        Some(line)
+    })
+}
+
+/// Split complete headers which `httparse` won't accept because of a bare CR, which is kept
+/// in the header value. Other than that, as strict as `httparse`.
+fn parse_headers_lenient<'h, 'b: 'h>(
+    buf: &'b [u8],
+    headers: &'h mut [StrHeader<'b>],
+) -> Result<(usize, &'h [StrHeader<'b>]), ParseHeaderError> {
+    fn is_token(b: u8) -> bool {
+        b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+    }
+
+    fn is_value(b: u8) -> bool {
+        b == b'\t' || b == b'\r' || (b >= b' ' && b != 0x7F)
+    }
+
+    let mut consumed = 0;
+
+    for (count, line) in header_lines(buf).enumerate() {
+        let line = line.expect("headers should be complete");
+        consumed += line.len();
+        // plus the line ending
+        consumed += if buf.get(consumed) == Some(&b'\r') { 2 } else { 1 };
+
+        if line.is_empty() {
+            return Ok((consumed, &headers[..count]));
+        }
+
+        let colon = line.iter().position(|&b| b == b':')
+            .ok_or(ParseHeaderError::TokenizeError(HttparseError::HeaderName))?;
+
+        let (name, val) = (&line[..colon], &line[colon + 1..]);
+
+        if name.is_empty() || !name.iter().cloned().all(is_token) {
+            return Err(ParseHeaderError::TokenizeError(HttparseError::HeaderName));
+        }
+
+        if !val.iter().cloned().all(is_value) {
+            return Err(ParseHeaderError::TokenizeError(HttparseError::HeaderValue));
+        }
+
+        let header = headers.get_mut(count)
+            .ok_or(ParseHeaderError::TokenizeError(HttparseError::TooManyHeaders))?;
+
+        header.name = io_str_utf8(name)?;
+        header.val = io_str_utf8(val)?.trim_matches(|c| c == ' ' || c == '\t');
+    }
+
+    unreachable!("headers should be complete")
+}
+
 fn copy_headers<'h, 'b: 'h>(
     raw: &[Header<'b>],
     headers: &'h mut [StrHeader<'b>],
@@ -103,6 +281,27 @@ fn copy_headers<'h, 'b: 'h>(
     Ok(&headers[..raw.len()])
 }
 
+/// Shorten `name` to at most `max_len` bytes, ending it with `~` and a hash of the whole name
+/// so that long names which only differ after `max_len` don't end up the same.
+///
+/// If `max_len` is too small for the hash, `name` is just cut off.
+fn truncate_name(name: &str, max_len: usize) -> String {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    let suffix = format!("~{:016x}", hasher.finish());
+
+    let (max_len, suffix) = if max_len < suffix.len() {
+        (max_len, "")
+    } else {
+        (max_len - suffix.len(), &*suffix)
+    };
+
+    let mut end = max_len;
+    while !name.is_char_boundary(end) { end -= 1; }
+
+    format!("{}{}", &name[..end], suffix)
+}
+
 /// The headers that (may) appear before a `multipart/form-data` field.
 ///
 /// ### Warning: Values are Client-Provided
@@ -111,10 +310,26 @@ fn copy_headers<'h, 'b: 'h>(
 #[derive(Clone, Debug)]
 pub struct FieldHeaders {
     /// The field's name from the form.
+    ///
+    /// If the client did not supply a name (or supplied an empty one), this is the name assigned
+    /// according to the [`UnnamedPolicy`](enum.UnnamedPolicy.html) in effect.
     pub name: Arc<str>,
 
+    /// The field's name as declared by the client, or `None` if the `name` parameter
+    /// was missing or empty.
+    ///
+    /// This is percent-decoded if `percent_decode_names()` was applied; see `name_raw()`.
+    pub declared_name: Option<Arc<str>>,
+
+    /// The type of the `Content-Disposition` header, normally `form-data`.
+    ///
+    /// The parameters are parsed the same way for every type.
+    pub disposition: DispositionType,
+
     /// The filename of this entry, if supplied. This is not guaranteed to match the original file
     /// or even to be a valid filename for the current platform.
+    ///
+    /// This may be a full path on the client; use `client_filename()` for the last component.
     pub filename: Option<String>,
 
     /// The MIME type (`Content-Type` value) of this file, if supplied by the client.
@@ -124,31 +339,529 @@ pub struct FieldHeaders {
     /// should not be implicitly trusted. This crate makes no attempt to identify or validate
     /// the content-type of the actual field data.
     pub content_type: Option<Mime>,
+
+    /// The parameters of `Content-Type` as `(name, value)`, in the order they appeared,
+    /// with values unquoted; see `content_type_params()`.
+    ///
+    /// These are parsed by this crate rather than taken from `content_type`. Only the first of
+    /// parameters with the same name (case-insensitive) is kept; see `had_duplicate_type_params`.
+    pub type_params: Vec<(String, String)>,
+
+    /// Whether a `Content-Type` parameter appeared more than once; the first is used.
+    pub had_duplicate_type_params: bool,
+
+    /// The digests of the field data declared in the `Content-MD5` and `Digest` headers,
+    /// if supplied by the client.
+    ///
+    /// These can be checked while saving with `SaveBuilder::verify_digests()`, with the `digest` feature.
+    pub digests: Vec<FieldDigest>,
+
+    /// The size of the field data in bytes, if supplied in the `size` parameter of
+    /// `Content-Disposition` ([IETF RFC 2183, Section 2.7](https://tools.ietf.org/html/rfc2183#section-2.7)).
+    ///
+    /// `SaveBuilder` uses this to preallocate memory for fields which are saved to memory, but
+    /// it is not checked against the actual size of the data.
+    pub size: Option<u64>,
+
+    /// The `creation-date` parameter of `Content-Disposition`, if supplied and valid
+    /// ([IETF RFC 2183, Section 2.4](https://tools.ietf.org/html/rfc2183#section-2.4)).
+    pub creation_date: Option<SystemTime>,
+
+    /// The `modification-date` parameter of `Content-Disposition`, if supplied and valid.
+    pub modification_date: Option<SystemTime>,
+
+    /// The `read-date` parameter of `Content-Disposition`, if supplied and valid.
+    pub read_date: Option<SystemTime>,
+
+    /// Any other parameters of `Content-Disposition` as `(name, value)`, in the order they
+    /// appeared, with names in lowercase and values unquoted.
+    pub extra_params: Vec<(String, String)>,
+
+    /// Whether the `Content-Disposition` or `Content-Type` header appeared more than once.
+    ///
+    /// The first occurrence is used. This is an error in strict mode (see
+    /// `Multipart::strict_crlf()`), since another parser could pick a different one.
+    pub had_duplicate_headers: bool,
+
+    /// Whether the name or filename was longer than `ParseLimits::max_name_len` and was truncated.
+    ///
+    /// A truncated value ends with `~` and a hash of the whole value, so that long names
+    /// which only differ after the limit still (most likely) differ. This is an error
+    /// in strict mode (see `Multipart::strict_crlf()`).
+    pub name_truncated: bool,
+
+    /// Whether `percent_decode_names()` was applied, so it isn't applied again.
+    pub names_percent_decoded: bool,
+
+    /// The name as declared by the client, if `percent_decode_names()` decoded it.
+    pub encoded_name: Option<Arc<str>>,
+
+    /// The filename as supplied by the client, if `percent_decode_names()` decoded it.
+    pub encoded_filename: Option<String>,
 }
 
 impl FieldHeaders {
+    /// Create the headers of a field with the given name and nothing else,
+    /// e.g. for a [`PartsSource`](struct.PartsSource.html).
+    ///
+    /// An empty name is treated as undeclared, as it would be in a request.
+    pub fn new<N: Into<Arc<str>>>(name: N) -> Self {
+        let name = name.into();
+
+        FieldHeaders {
+            declared_name: if name.is_empty() { None } else { Some(name.clone()) },
+            name,
+            disposition: DispositionType::FormData,
+            filename: None,
+            content_type: None,
+            type_params: Vec::new(),
+            had_duplicate_type_params: false,
+            digests: Vec::new(),
+            size: None,
+            creation_date: None,
+            modification_date: None,
+            read_date: None,
+            extra_params: Vec::new(),
+            had_duplicate_headers: false,
+            name_truncated: false,
+            names_percent_decoded: false,
+            encoded_name: None,
+            encoded_filename: None,
+        }
+    }
+
+    /// The last component of the filename supplied by the client, if any.
+    ///
+    /// Some clients, e.g. old versions of Internet Explorer, send the full path of the file
+    /// on the client, like `C:\Users\bob\report.xls`. Regardless of the platform of either
+    /// side, this strips a leading drive letter and everything up to the last `/` or `\`,
+    /// giving `report.xls`. A component which is empty, `.` or `..` gives `None`.
+    ///
+    /// The result is still client-provided; it contains no separators, but is not otherwise
+    /// guaranteed to be a valid or safe filename.
+    pub fn client_filename(&self) -> Option<&str> {
+        match last_component(self.filename.as_ref()?) {
+            "" | "." | ".." => None,
+            name => Some(name),
+        }
+    }
+
+    /// The parameters of the `Content-Type` header as `(name, value)`, in the order
+    /// they appeared; the same as `type_params`.
+    ///
+    /// Unlike the parameters of `content_type`, these don't depend on the version of the
+    /// `mime` crate. They are parsed like those of the request's `Content-Type` (see
+    /// `parse_multipart_type()`): values are unquoted, and only the first of parameters
+    /// with the same name is included.
+    pub fn content_type_params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.type_params.iter().map(|(name, value)| (&**name, &**value))
+    }
+
+    /// Get the value of the `Content-Type` parameter with the given name (case-insensitive),
+    /// e.g. `charset`.
+    pub fn content_type_param(&self, name: &str) -> Option<&str> {
+        self.content_type_params().find(|&(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// The filename exactly as supplied by the client, if any; the same as `filename` unless
+    /// it was percent-decoded by `percent_decode_names()`.
+    ///
+    /// See `client_filename()` for just the last component.
+    pub fn filename_raw(&self) -> Option<&str> {
+        self.encoded_filename.as_ref().or(self.filename.as_ref()).map(|filename| &**filename)
+    }
+
+    /// The name exactly as declared by the client, if any; the same as `declared_name` unless
+    /// it was percent-decoded by `percent_decode_names()`.
+    pub fn name_raw(&self) -> Option<&str> {
+        self.encoded_name.as_ref().or(self.declared_name.as_ref()).map(|name| &**name)
+    }
+
+    /// Percent-decode the declared name and the filename, for clients which URL-encode them,
+    /// e.g. sending `user%5Bemail%5D` for `user[email]`. Returns whether either was decoded.
+    ///
+    /// Each is only decoded if every `%` in it starts an escape like `%5B` and the result is
+    /// valid UTF-8 without control characters; otherwise, like `100% cotton` or `%E9t%E9`
+    /// (Latin-1), it most likely wasn't encoded and is left as it is. The values as sent are
+    /// kept in `encoded_name` and `encoded_filename` and returned by `name_raw()` and
+    /// `filename_raw()`.
+    ///
+    /// This only applies once, so a name which is still encoded after decoding, like
+    /// `%255B`, keeps the rest (`%5B`). See also `Multipart::percent_decode_names()`
+    /// and `SaveBuilder::percent_decode_names()`.
+    pub fn percent_decode_names(&mut self) -> bool {
+        if self.names_percent_decoded {
+            return false;
+        }
+
+        self.names_percent_decoded = true;
+
+        let name = self.declared_name.as_ref().and_then(|name| percent_decode(name));
+        let filename = self.filename.as_ref().and_then(|filename| percent_decode(filename));
+        let decoded = name.is_some() || filename.is_some();
+
+        if let Some(name) = name {
+            let name: Arc<str> = name.into();
+            self.encoded_name = self.declared_name.replace(name.clone());
+            self.name = name;
+        }
+
+        if let Some(filename) = filename {
+            self.encoded_filename = self.filename.replace(filename);
+        }
+
+        decoded
+    }
+
     /// Parse the field headers from the passed `BufRead`, consuming the relevant bytes.
-    fn read_from<R: BufRead>(r: &mut R) -> Result<Self, ParseHeaderError> {
-        with_headers(r, Self::parse)?
+    ///
+    /// A duplicate `Content-Disposition` or `Content-Type` header is an error if `strict`,
+    /// as is a name or filename longer than `max_name_len` (after percent-decoding,
+    /// if `decode_names`).
+    fn read_from<R: BufRead>(r: &mut R, strict: bool, max_name_len: usize, max_header_bytes: usize,
+                             decode_names: bool) -> Result<Self, ParseHeaderError> {
+        with_headers(r, strict, max_header_bytes, |headers| {
+            let duplicate = find_duplicate(headers);
+
+            if let Some(name) = duplicate {
+                if strict {
+                    return Err(ParseHeaderError::DuplicateHeader(name));
+                }
+
+                warn!("Duplicate {:?} header in field headers, using the first", name);
+            }
+
+            let mut parsed = Self::parse(headers)?;
+            parsed.had_duplicate_headers = duplicate.is_some();
+
+            if decode_names {
+                parsed.percent_decode_names();
+            }
+
+            parsed.limit_name_len(max_name_len, strict)?;
+            Ok(parsed)
+        })?
     }
 
     fn parse(headers: &[StrHeader]) -> Result<FieldHeaders, ParseHeaderError> {
         let cont_disp = ContentDisp::parse_required(headers)?;
+        let declared_name: Option<Arc<str>> = cont_disp.field_name.map(Into::into);
+        let (type_params, had_duplicate_type_params) = parse_type_params(headers);
 
         Ok(FieldHeaders {
-            name: cont_disp.field_name.into(),
+            // replaced according to the `UnnamedPolicy` if not declared
+            name: declared_name.clone().unwrap_or_else(|| "".into()),
+            declared_name,
+            disposition: cont_disp.disposition,
             filename: cont_disp.filename,
             content_type: parse_content_type(headers)?,
+            type_params,
+            had_duplicate_type_params,
+            digests: parse_digests(headers),
+            size: cont_disp.size,
+            creation_date: cont_disp.creation_date,
+            modification_date: cont_disp.modification_date,
+            read_date: cont_disp.read_date,
+            extra_params: cont_disp.extra_params,
+            had_duplicate_headers: false,
+            name_truncated: false,
+            names_percent_decoded: false,
+            encoded_name: None,
+            encoded_filename: None,
         })
     }
+
+    /// Apply `ParseLimits::max_name_len` to the name and filename: an error if `strict`,
+    /// otherwise they are truncated with `truncate_name()`.
+    fn limit_name_len(&mut self, max_len: usize, strict: bool) -> Result<(), ParseHeaderError> {
+        let name_too_long = self.declared_name.as_ref().is_some_and(|name| name.len() > max_len);
+        let filename_too_long = self.filename.as_ref().is_some_and(|name| name.len() > max_len);
+
+        if strict {
+            if name_too_long {
+                return Err(ParseHeaderError::NameTooLong("name", max_len));
+            }
+
+            if filename_too_long {
+                return Err(ParseHeaderError::NameTooLong("filename", max_len));
+            }
+        }
+
+        if name_too_long {
+            let name: Arc<str> = truncate_name(&self.name, max_len).into();
+            warn!("Field name longer than {} bytes, truncated to {:?}", max_len, name);
+            self.declared_name = Some(name.clone());
+            self.name = name;
+        }
+
+        if filename_too_long {
+            let filename = self.filename.as_ref().map(|name| truncate_name(name, max_len));
+            warn!("Filename longer than {} bytes, truncated to {:?}", max_len, filename);
+            self.filename = filename;
+        }
+
+        self.name_truncated = name_too_long || filename_too_long;
+        Ok(())
+    }
+}
+
+impl FieldHeaders {
+    /// Parse field headers from `(name, value)` pairs, as for `from_header_map()`.
+    #[cfg(feature = "multer")]
+    pub(super) fn from_pairs<'a, I>(pairs: I) -> io::Result<FieldHeaders>
+    where I: IntoIterator<Item = (&'a str, &'a [u8])> {
+        Self::parse_pairs(pairs).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    #[cfg(any(feature = "http", feature = "multer"))]
+    fn parse_pairs<'a, I>(pairs: I) -> Result<FieldHeaders, ParseHeaderError>
+    where I: IntoIterator<Item = (&'a str, &'a [u8])> {
+        let mut headers = Vec::new();
+
+        for (name, value) in pairs {
+            headers.push(StrHeader { name, val: io_str_utf8(value)? });
+        }
+
+        let duplicate = find_duplicate(&headers);
+
+        if let Some(name) = duplicate {
+            warn!("Duplicate {:?} header in header map, using the first", name);
+        }
+
+        let mut parsed = Self::parse(&headers)?;
+        parsed.had_duplicate_headers = duplicate.is_some();
+        Ok(parsed)
+    }
+}
+
+/// Conversions to and from `http::HeaderMap`. Enabled with the `http` feature.
+#[cfg(feature = "http")]
+impl FieldHeaders {
+    /// Get these headers as a `HeaderMap`, e.g. to pass them on to other middleware or to write
+    /// them back out with the client's `Multipart::write_part()`.
+    ///
+    /// `Content-Disposition` is reconstructed from `disposition`, `declared_name`, `filename`,
+    /// `size`, the dates and `extra_params`, in that order, and the digests are combined into a `Digest` header.
+    ///
+    /// The name and filename are quoted as browsers do it: `"`, CR and LF are percent-encoded,
+    /// since this crate's parser (like many others) doesn't unescape them. Dates which can't be
+    /// formatted with a four-digit year are left out with a warning.
+    ///
+    /// ## Errors
+    /// If a value which isn't reconstructed this way contains control characters,
+    /// which `http` doesn't allow in header values.
+    pub fn to_header_map(&self) -> Result<HeaderMap, InvalidHeaderValue> {
+        let mut map = HeaderMap::new();
+
+        map.insert(header::CONTENT_DISPOSITION, HeaderValue::from_str(&self.content_disposition())?);
+
+        if let Some(ref content_type) = self.content_type {
+            map.insert(header::CONTENT_TYPE, HeaderValue::from_str(content_type.as_ref())?);
+        }
+
+        if !self.digests.is_empty() {
+            let digests: Vec<String> = self.digests.iter()
+                .map(|digest| format!("{}={}", digest.algorithm, digest.value))
+                .collect();
+
+            map.insert(HeaderName::from_static("digest"), HeaderValue::from_str(&digests.join(", "))?);
+        }
+
+        Ok(map)
+    }
+
+    /// Parse field headers from a `HeaderMap`, as they would be parsed from a request in lenient
+    /// mode: a duplicate `Content-Disposition` or `Content-Type` header sets
+    /// `had_duplicate_headers` and the first is used.
+    ///
+    /// `name` is left empty if the `Content-Disposition` doesn't declare one, as no
+    /// `UnnamedPolicy` applies.
+    ///
+    /// ## Errors
+    /// An error of kind `InvalidData` if the headers would be invalid in a request, e.g. without
+    /// a `Content-Disposition` header, or if a header value is not valid UTF-8.
+    pub fn from_header_map(map: &HeaderMap) -> io::Result<FieldHeaders> {
+        Self::parse_header_map(map).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn parse_header_map(map: &HeaderMap) -> Result<FieldHeaders, ParseHeaderError> {
+        Self::parse_pairs(map.iter().map(|(name, value)| (name.as_str(), value.as_bytes())))
+    }
+
+    fn content_disposition(&self) -> String {
+        use std::fmt::Write;
+
+        let mut value = self.disposition.to_string();
+
+        if let Some(ref name) = self.declared_name {
+            let _ = write!(value, "; name=\"{}\"", FormQuoted(name));
+        }
+
+        if let Some(ref filename) = self.filename {
+            let _ = write!(value, "; filename=\"{}\"", FormQuoted(filename));
+        }
+
+        if let Some(size) = self.size {
+            let _ = write!(value, "; size={}", size);
+        }
+
+        let dates = [
+            ("creation-date", self.creation_date),
+            ("modification-date", self.modification_date),
+            ("read-date", self.read_date),
+        ];
+
+        for &(param, date) in &dates {
+            match date.map(format_date) {
+                Some(Some(date)) => { let _ = write!(value, "; {}=\"{}\"", param, date); },
+                Some(None) => warn!("Leaving out {} which can't be formatted: {:?}", param, date),
+                None => (),
+            }
+        }
+
+        for (param, param_value) in &self.extra_params {
+            let _ = write!(value, "; {}=\"{}\"", param, QuotedString(param_value));
+        }
+
+        value
+    }
+}
+
+/// Writes a name or filename for a quoted `Content-Disposition` parameter the way browsers do,
+/// with `"`, CR and LF percent-encoded.
+#[cfg(feature = "http")]
+struct FormQuoted<'a>(&'a str);
+
+#[cfg(feature = "http")]
+impl<'a> fmt::Display for FormQuoted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("%22")?,
+                '\r' => f.write_str("%0D")?,
+                '\n' => f.write_str("%0A")?,
+                c => fmt::Write::write_char(f, c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the contents of a quoted string, escaping `"` and `\`.
+#[cfg(feature = "http")]
+struct QuotedString<'a>(&'a str);
+
+#[cfg(feature = "http")]
+impl<'a> fmt::Display for QuotedString<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            if c == '"' || c == '\\' {
+                f.write_str("\\")?;
+            }
+
+            fmt::Write::write_char(f, c)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The type of a `Content-Disposition` header
+/// ([IETF RFC 2183, Section 2](https://tools.ietf.org/html/rfc2183#section-2)).
+///
+/// Parts of `multipart/form-data` requests are `form-data`; other `multipart` bodies,
+/// e.g. from mail gateways, may use `attachment` or `inline`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DispositionType {
+    /// `form-data`
+    FormData,
+    /// `attachment`
+    Attachment,
+    /// `inline`
+    Inline,
+    /// Any other type, in lowercase.
+    Ext(Arc<str>),
+}
+
+impl DispositionType {
+    /// Parse a type, ignoring case, or `None` if it's not a valid token.
+    fn parse(disp_type: &str) -> Option<DispositionType> {
+        let is_tchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+
+        if disp_type.is_empty() || !disp_type.chars().all(is_tchar) {
+            return None;
+        }
+
+        let disp_type = disp_type.to_ascii_lowercase();
+
+        Some(match &*disp_type {
+            "form-data" => DispositionType::FormData,
+            "attachment" => DispositionType::Attachment,
+            "inline" => DispositionType::Inline,
+            _ => DispositionType::Ext(disp_type.into()),
+        })
+    }
+
+    /// The type as it appears in the header, in lowercase.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            DispositionType::FormData => "form-data",
+            DispositionType::Attachment => "attachment",
+            DispositionType::Inline => "inline",
+            DispositionType::Ext(ref disp_type) => disp_type,
+        }
+    }
+}
+
+impl fmt::Display for DispositionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A digest of a field's data declared by the client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldDigest {
+    /// The name of the algorithm in lowercase, e.g. `md5` or `sha-256`.
+    ///
+    /// `md5` for the `Content-MD5` header.
+    pub algorithm: String,
+    /// The digest value as declared, normally base64-encoded.
+    pub value: String,
+}
+
+/// Prints the name, then the filename and content type if present, e.g.
+/// `"upload", filename "a.bin", application/octet-stream`.
+impl fmt::Display for FieldHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.name)?;
+
+        if let Some(ref filename) = self.filename {
+            write!(f, ", filename {:?}", filename)?;
+        }
+
+        if let Some(ref content_type) = self.content_type {
+            write!(f, ", {}", content_type)?;
+        }
+
+        Ok(())
+    }
 }
 
 /// The `Content-Disposition` header.
 struct ContentDisp {
-    /// The name of the `multipart/form-data` field.
-    field_name: String,
+    disposition: DispositionType,
+    /// The name of the `multipart/form-data` field, if supplied and not empty.
+    field_name: Option<String>,
     /// The optional filename for this field.
     filename: Option<String>,
+    size: Option<u64>,
+    creation_date: Option<SystemTime>,
+    modification_date: Option<SystemTime>,
+    read_date: Option<SystemTime>,
+    extra_params: Vec<(String, String)>,
 }
 
 impl ContentDisp {
@@ -162,40 +875,67 @@ impl ContentDisp {
         };
 
         // Content-Disposition: ?
-        let after_disp_type = match split_once(header.val, ';') {
-            Some((disp_type, after_disp_type)) => {
-                // assert Content-Disposition: form-data
-                // but needs to be parsed out to trim the spaces (allowed by spec IIRC)
-                if disp_type.trim() != "form-data" {
-                    invalid_cont_disp!("unexpected Content-Disposition value", disp_type);
-                }
-                after_disp_type
-            }
-            None => invalid_cont_disp!(
-                "expected additional data after Content-Disposition type",
-                header.val
-            ),
-        };
+        // a bare `form-data` has no parameters, and so no name
+        let (disp_type, after_disp_type) = split_once(header.val, ';').unwrap_or((header.val, ""));
 
-        // Content-Disposition: form-data; name=?
-        let (field_name, filename) = match get_str_after("name=", ';', after_disp_type) {
-            None => invalid_cont_disp!(
-                "expected field name and maybe filename, got",
-                after_disp_type
-            ),
-            // Content-Disposition: form-data; name={field_name}; filename=?
-            Some((field_name, after_field_name)) => {
-                let field_name = trim_quotes(field_name);
-                let filename = get_str_after("filename=", ';', after_field_name)
-                    .map(|(filename, _)| trim_quotes(filename).to_owned());
-                (field_name, filename)
-            }
+        // usually `form-data`, but needs to be parsed out to trim the spaces (allowed by spec IIRC)
+        let disposition = match DispositionType::parse(disp_type.trim()) {
+            Some(disposition) => disposition,
+            None => invalid_cont_disp!("invalid Content-Disposition type", disp_type),
         };
 
-        Ok(ContentDisp {
-            field_name: field_name.to_owned(),
+        // Content-Disposition: form-data; name={field_name}; filename=?
+        let field_name = get_str_after("name=", ';', after_disp_type)
+            .map(|(field_name, _)| trim_quotes(field_name))
+            .filter(|field_name| !field_name.is_empty())
+            .map(str::to_owned);
+
+        let filename = get_str_after("filename=", ';', after_disp_type)
+            .map(|(filename, _)| trim_quotes(filename).to_owned());
+
+        let mut cont_disp = ContentDisp {
+            disposition,
+            field_name,
             filename,
-        })
+            size: None,
+            creation_date: None,
+            modification_date: None,
+            read_date: None,
+            extra_params: Vec::new(),
+        };
+
+        // invalid values are ignored rather than failing the whole request
+        for (name, value) in parse_params(after_disp_type) {
+            let name = name.to_ascii_lowercase();
+
+            let date = match &*name {
+                "name" | "filename" => continue,
+                "size" => {
+                    cont_disp.size = value.trim().parse().ok();
+
+                    if cont_disp.size.is_none() {
+                        warn!("Ignoring invalid size in Content-Disposition: {:?}", value);
+                    }
+
+                    continue;
+                },
+                "creation-date" => &mut cont_disp.creation_date,
+                "modification-date" => &mut cont_disp.modification_date,
+                "read-date" => &mut cont_disp.read_date,
+                _ => {
+                    cont_disp.extra_params.push((name, value.into_owned()));
+                    continue;
+                },
+            };
+
+            *date = parse_date(&value);
+
+            if date.is_none() {
+                warn!("Ignoring invalid {} in Content-Disposition: {:?}", name, value);
+            }
+        }
+
+        Ok(cont_disp)
     }
 }
 
@@ -211,6 +951,55 @@ fn parse_content_type(headers: &[StrHeader]) -> Result<Option<Mime>, ParseHeader
     }
 }
 
+/// Parse the parameters of `Content-Type`, keeping the first of each name; also returns
+/// whether there were duplicates.
+fn parse_type_params(headers: &[StrHeader]) -> (Vec<(String, String)>, bool) {
+    let header = match find_header(headers, "Content-Type") {
+        Some(header) => header,
+        None => return (Vec::new(), false),
+    };
+
+    let rest = header.val.find(';').map_or("", |idx| &header.val[idx..]);
+    let mut params: Vec<(String, String)> = Vec::new();
+    let mut had_duplicates = false;
+
+    for (name, value) in parse_params(rest) {
+        if params.iter().any(|(param, _)| param.eq_ignore_ascii_case(name)) {
+            warn!("Duplicate {:?} parameter in Content-Type, using the first: {:?}", name, header.val);
+            had_duplicates = true;
+        } else {
+            params.push((name.into(), value.into_owned()));
+        }
+    }
+
+    (params, had_duplicates)
+}
+
+/// Parse `Content-MD5: <base64>` and `Digest: <algorithm>=<value>, ...`
+/// ([IETF RFC 3230](https://tools.ietf.org/html/rfc3230#section-4.3.2)).
+fn parse_digests(headers: &[StrHeader]) -> Vec<FieldDigest> {
+    let mut digests = Vec::new();
+
+    if let Some(header) = find_header(headers, "Content-MD5") {
+        digests.push(FieldDigest { algorithm: "md5".into(), value: header.val.trim().into() });
+    }
+
+    if let Some(header) = find_header(headers, "Digest") {
+        for digest in header.val.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            // base64 values may end with `=`
+            match split_once(digest, '=') {
+                Some((algorithm, value)) => digests.push(FieldDigest {
+                    algorithm: algorithm.trim().to_ascii_lowercase(),
+                    value: value[1..].trim().into(),
+                }),
+                None => warn!("Ignoring malformed digest in Digest header: {:?}", digest),
+            }
+        }
+    }
+
+    digests
+}
+
 /// A field in a multipart request with its associated headers and data.
 #[derive(Debug)]
 pub struct MultipartField<M: ReadEntry> {
@@ -280,6 +1069,9 @@ impl<M: ReadEntry> MultipartField<M> {
 #[derive(Debug)]
 pub struct MultipartData<M> {
     inner: Option<M>,
+    newlines: Option<NewlineFilter>,
+    /// The offset of the field's headers in the body, for `SaveBuilder::collect_raw_ranges()`.
+    headers_start: Option<u64>,
 }
 
 const DATA_INNER_ERR: &str = "MultipartFile::inner taken and not replaced; this is likely \
@@ -312,6 +1104,31 @@ where
         self.inner_mut().set_min_buf_size(min_buf_size)
     }
 
+    /// Convert the line endings of the rest of the data according to `policy` as it's read,
+    /// as `SaveBuilder::normalize_newlines()` does for text fields.
+    ///
+    /// Has no effect if line endings are already being converted.
+    pub fn normalize_newlines(&mut self, policy: NewlinePolicy) {
+        if self.newlines.is_none() {
+            self.newlines = NewlineFilter::new(policy);
+        }
+    }
+
+    /// The offset in the request body of the first header line of this field, or `None` if
+    /// the field wasn't parsed from a raw body, e.g. by `Multipart::from_parts()`.
+    ///
+    /// Offsets count from the start of the body, as for `ParseEvent`.
+    pub fn headers_offset(&self) -> Option<u64> {
+        self.headers_start
+    }
+
+    /// The offset in the request body of the next byte of data to be read from this field,
+    /// which is the end of the data once it has all been read, or `None` if the field
+    /// wasn't parsed from a raw body.
+    pub fn data_offset(&self) -> Option<u64> {
+        self.inner.as_ref().and_then(|inner| inner.raw_offset())
+    }
+
     fn inner_mut(&mut self) -> &mut M {
         self.inner.as_mut().expect(DATA_INNER_ERR)
     }
@@ -327,7 +1144,19 @@ where
 
 impl<M: ReadEntry> Read for MultipartData<M> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner_mut().source_mut().read(buf)
+        if self.newlines.is_none() {
+            return self.inner_mut().source_mut().read(buf);
+        }
+
+        let read = {
+            let data = self.fill_buf()?;
+            let read = cmp::min(data.len(), buf.len());
+            buf[..read].copy_from_slice(&data[..read]);
+            read
+        };
+
+        self.consume(read);
+        Ok(read)
     }
 }
 
@@ -336,11 +1165,19 @@ impl<M: ReadEntry> Read for MultipartData<M> {
 /// Use `set_min_buf_size()` if you require a minimum buffer length.
 impl<M: ReadEntry> BufRead for MultipartData<M> {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        self.inner_mut().source_mut().fill_buf()
+        let source = self.inner.as_mut().expect(DATA_INNER_ERR).source_mut();
+
+        match self.newlines {
+            Some(ref mut newlines) => newlines.fill_buf(source),
+            None => source.fill_buf(),
+        }
     }
 
     fn consume(&mut self, amt: usize) {
-        self.inner_mut().source_mut().consume(amt)
+        match self.newlines {
+            Some(ref mut newlines) => newlines.consume(amt),
+            None => self.inner_mut().source_mut().consume(amt),
+        }
     }
 }
 
@@ -353,12 +1190,19 @@ fn trim_quotes(s: &str) -> &str {
 }
 
 /// Get the string after `needle` in `haystack`, stopping before `end_val_delim`
+///
+/// `needle` only matches at the start of a parameter, so `name=` will not match `filename=`.
 fn get_str_after<'a>(
     needle: &str,
     end_val_delim: char,
     haystack: &'a str,
 ) -> Option<(&'a str, &'a str)> {
-    let val_start_idx = try_opt!(haystack.find(needle)) + needle.len();
+    let val_start_idx = try_opt!(haystack.match_indices(needle).map(|(idx, _)| idx).find(|&idx| {
+        match haystack[..idx].chars().next_back() {
+            Some(c) => c == end_val_delim || c.is_whitespace(),
+            None => true,
+        }
+    })) + needle.len();
     let val_end_idx = haystack[val_start_idx..]
         .find(end_val_delim)
         .map_or(haystack.len(), |end_idx| end_idx + val_start_idx);
@@ -380,6 +1224,42 @@ fn find_header<'a, 'b>(headers: &'a [StrHeader<'b>], name: &str) -> Option<&'a S
         .find(|header| header.name.eq_ignore_ascii_case(name))
 }
 
+/// Percent-decode `value` for `FieldHeaders::percent_decode_names()`, or `None` if it has no
+/// escapes, has a `%` which doesn't start one, or doesn't decode to UTF-8 without control
+/// characters.
+fn percent_decode(value: &str) -> Option<String> {
+    if !value.contains('%') {
+        return None;
+    }
+
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut bytes = value.bytes();
+    let mut decoded = Vec::with_capacity(value.len());
+
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hi = hex(bytes.next()?)?;
+            let lo = hex(bytes.next()?)?;
+            decoded.push(hi << 4 | lo);
+        } else {
+            decoded.push(b);
+        }
+    }
+
+    String::from_utf8(decoded).ok().filter(|decoded| !decoded.chars().any(char::is_control))
+}
+
+/// The headers which must not appear more than once in a field, as parsers disagree on
+/// which one counts.
+const UNIQUE_HEADERS: [&str; 2] = ["Content-Disposition", "Content-Type"];
+
+/// Find the first of `UNIQUE_HEADERS` which appears more than once.
+fn find_duplicate(headers: &[StrHeader]) -> Option<&'static str> {
+    UNIQUE_HEADERS.iter().cloned().find(|name| {
+        headers.iter().filter(|header| header.name.eq_ignore_ascii_case(name)).count() > 1
+    })
+}
+
 /// Common trait for `Multipart` and `&mut Multipart`
 pub trait ReadEntry: PrivReadEntry + Sized {
     /// Attempt to read the next entry in the multipart stream.
@@ -388,11 +1268,43 @@ pub trait ReadEntry: PrivReadEntry + Sized {
 
         debug!("ReadEntry::read_entry()");
 
-        if !try_read_entry!(self; self.consume_boundary()) {
-            return End(self);
-        }
+        let mut headers_start;
+
+        let field_headers = loop {
+            if !try_read_entry!(self; self.consume_boundary()) {
+                return End(self);
+            }
 
-        let field_headers: FieldHeaders = try_read_entry!(self; self.read_headers());
+            if try_read_entry!(self; self.skip_empty_part()) {
+                continue;
+            }
+
+            headers_start = self.raw_offset();
+            let mut field_headers: FieldHeaders = try_read_entry!(self; self.read_headers());
+            self.end_headers();
+
+            let field_idx = self.count_field();
+
+            if field_headers.declared_name.is_some() {
+                break field_headers;
+            }
+
+            match self.unnamed_policy() {
+                UnnamedPolicy::Reject => {
+                    let err = io::Error::new(io::ErrorKind::InvalidData, UnnamedFieldError { index: field_idx });
+                    self.record_error(&err);
+                    return Error(self, err);
+                },
+                UnnamedPolicy::Skip => {
+                    // the next call to `consume_boundary()` discards the field data
+                    debug!("Skipping unnamed field {}: {:?}", field_idx, field_headers);
+                },
+                UnnamedPolicy::Synthesize => {
+                    field_headers.name = format!("_unnamed_{}", field_idx).into();
+                    break field_headers;
+                },
+            }
+        };
 
         if let Some(ct) = field_headers.content_type.as_ref() {
             if ct.type_() == mime::MULTIPART {
@@ -410,7 +1322,7 @@ pub trait ReadEntry: PrivReadEntry + Sized {
 
         Entry(MultipartField {
             headers: field_headers,
-            data: MultipartData { inner: Some(self) },
+            data: MultipartData { inner: Some(self), newlines: None, headers_start },
         })
     }
 
@@ -418,25 +1330,116 @@ pub trait ReadEntry: PrivReadEntry + Sized {
     fn read_entry_mut(&mut self) -> ReadEntryResult<&mut Self> {
         ReadEntry::read_entry(self)
     }
-}
+}
+
+impl<T> ReadEntry for T where T: PrivReadEntry {}
+
+/// Public trait but not re-exported.
+pub trait PrivReadEntry {
+    type Source: BufRead;
+
+    fn source_mut(&mut self) -> &mut Self::Source;
+
+    fn set_min_buf_size(&mut self, min_buf_size: usize);
+
+    /// Consume the next boundary.
+    /// Returns `true` if a field should follow, `false` otherwise.
+    fn consume_boundary(&mut self) -> io::Result<bool>;
+
+    /// What to do with fields that don't have a name.
+    fn unnamed_policy(&self) -> UnnamedPolicy;
+
+    /// Return the index of the field that was just read and increment the count.
+    fn count_field(&mut self) -> u32;
+
+    /// Called after the headers of a field have been read.
+    fn end_headers(&mut self);
+
+    /// Called with an error about to be returned from `read_entry()`.
+    fn record_error(&mut self, _err: &io::Error) {}
+
+    fn payload_bytes(&self) -> u64;
+
+    fn overhead_bytes(&self) -> u64;
+
+    /// The offset of the next byte to be consumed from the body, or `None` if the source
+    /// isn't a raw body.
+    fn raw_offset(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether the request is parsed in strict mode; see `Multipart::strict_crlf()`.
+    fn is_strict(&self) -> bool;
+
+    /// The maximum length of a field name or filename; see `ParseLimits::max_name_len`.
+    fn max_name_len(&self) -> usize {
+        usize::MAX
+    }
+
+    /// The maximum length of the headers of a part; see `ParseLimits::max_header_bytes`.
+    fn max_header_bytes(&self) -> usize {
+        super::DEFAULT_MAX_HEADER_BYTES
+    }
 
-impl<T> ReadEntry for T where T: PrivReadEntry {}
+    /// Whether to percent-decode names and filenames; see `Multipart::percent_decode_names()`.
+    fn percent_decode_names(&self) -> bool {
+        false
+    }
 
-/// Public trait but not re-exported.
-pub trait PrivReadEntry {
-    type Source: BufRead;
+    /// Whether the source ended properly, e.g. with the closing boundary.
+    fn finished_cleanly(&self) -> bool;
 
-    fn source_mut(&mut self) -> &mut Self::Source;
+    /// Skip a part without headers or data after the boundary just consumed, if there is one.
+    /// Returns `true` if it was skipped.
+    fn skip_empty_part(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
 
-    fn set_min_buf_size(&mut self, min_buf_size: usize);
+    /// The number of parts skipped by `skip_empty_part()`.
+    fn empty_parts(&self) -> u32 {
+        0
+    }
 
-    /// Consume the next boundary.
-    /// Returns `true` if a field should follow, `false` otherwise.
-    fn consume_boundary(&mut self) -> io::Result<bool>;
+    /// Whether a copy of the raw body is being kept, see `Multipart::capture_raw()`.
+    fn captures_raw(&self) -> bool {
+        false
+    }
 
+    /// Read the headers of the current part, raising the minimum buffer size from
+    /// `MIN_BUF_SIZE` as needed (up to `max_header_bytes() + MIN_BUF_SIZE`) so the headers fit.
     fn read_headers(&mut self) -> Result<FieldHeaders, io::Error> {
-        FieldHeaders::read_from(self.source_mut())
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        let strict = self.is_strict();
+        let max_name_len = self.max_name_len();
+        let max_header_bytes = self.max_header_bytes();
+        let decode_names = self.percent_decode_names();
+
+        let mut min_buf_size = super::boundary::MIN_BUF_SIZE;
+
+        let res = loop {
+            match FieldHeaders::read_from(self.source_mut(), strict, max_name_len, max_header_bytes, decode_names) {
+                // nothing was consumed, so try again with a larger buffer
+                Err(ParseHeaderError::TooLarge) if min_buf_size <= max_header_bytes => {
+                    // with room to spare, as `BoundaryReader` holds back the end of its buffer
+                    min_buf_size = cmp::min(
+                        min_buf_size.saturating_mul(2),
+                        max_header_bytes.saturating_add(super::boundary::MIN_BUF_SIZE),
+                    );
+                    debug!("Field headers incomplete, minimum buffer size raised to {}", min_buf_size);
+                    self.set_min_buf_size(min_buf_size);
+                }
+                res => break res,
+            }
+        };
+
+        if min_buf_size > super::boundary::MIN_BUF_SIZE {
+            self.set_min_buf_size(super::boundary::MIN_BUF_SIZE);
+        }
+
+        res.map_err(|e| match e {
+            // from the source, e.g. `WouldBlock` or `NoProgressError`, rather than the headers
+            ParseHeaderError::Io(e) if e.kind() != io::ErrorKind::InvalidData => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        })
     }
 
     fn read_to_string(&mut self) -> io::Result<String> {
@@ -463,6 +1466,110 @@ impl<'a, M: ReadEntry> PrivReadEntry for &'a mut M {
     fn consume_boundary(&mut self) -> io::Result<bool> {
         (**self).consume_boundary()
     }
+
+    fn unnamed_policy(&self) -> UnnamedPolicy {
+        (**self).unnamed_policy()
+    }
+
+    fn is_strict(&self) -> bool {
+        (**self).is_strict()
+    }
+
+    fn max_name_len(&self) -> usize {
+        (**self).max_name_len()
+    }
+
+    fn max_header_bytes(&self) -> usize {
+        (**self).max_header_bytes()
+    }
+
+    fn percent_decode_names(&self) -> bool {
+        (**self).percent_decode_names()
+    }
+
+    fn finished_cleanly(&self) -> bool {
+        (**self).finished_cleanly()
+    }
+
+    fn skip_empty_part(&mut self) -> io::Result<bool> {
+        (**self).skip_empty_part()
+    }
+
+    fn empty_parts(&self) -> u32 {
+        (**self).empty_parts()
+    }
+
+    fn captures_raw(&self) -> bool {
+        (**self).captures_raw()
+    }
+
+    fn count_field(&mut self) -> u32 {
+        (**self).count_field()
+    }
+
+    fn end_headers(&mut self) {
+        (**self).end_headers()
+    }
+
+    fn record_error(&mut self, err: &io::Error) {
+        (**self).record_error(err)
+    }
+
+    fn payload_bytes(&self) -> u64 {
+        (**self).payload_bytes()
+    }
+
+    fn overhead_bytes(&self) -> u64 {
+        (**self).overhead_bytes()
+    }
+
+    fn raw_offset(&self) -> Option<u64> {
+        (**self).raw_offset()
+    }
+
+    fn read_headers(&mut self) -> Result<FieldHeaders, io::Error> {
+        (**self).read_headers()
+    }
+}
+
+/// What to do with a field whose `Content-Disposition` header has a missing or empty `name`.
+///
+/// This applies to parts of any `DispositionType`, e.g. `attachment` parts which only have
+/// a filename.
+///
+/// Set with `Multipart::on_unnamed()`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnnamedPolicy {
+    /// Return an error which can be downcast to [`UnnamedFieldError`](struct.UnnamedFieldError.html).
+    Reject,
+    /// Discard the field and continue with the next one.
+    Skip,
+    /// Name the field `_unnamed_<index>` where `<index>` is the zero-based position of the field
+    /// in the request. `FieldHeaders::declared_name` will be `None`.
+    ///
+    /// This is the default.
+    #[default]
+    Synthesize,
+}
+
+/// The error returned (wrapped in `io::Error`) for a field without a name
+/// under `UnnamedPolicy::Reject`.
+#[derive(Debug)]
+pub struct UnnamedFieldError {
+    /// The zero-based position of the offending field in the request.
+    pub index: u32,
+}
+
+impl fmt::Display for UnnamedFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "field {} in the request has no name", self.index)
+    }
+}
+
+impl Error for UnnamedFieldError {
+    fn description(&self) -> &str {
+        "field has no name"
+    }
 }
 
 /// Ternary result type returned by `ReadEntry::next_entry()`,
@@ -559,6 +1666,37 @@ quick_error! {
         TooLarge {
             description("field headers section ridiculously long or missing trailing CRLF-CRLF")
         }
+        /// A NUL byte in the field headers
+        NulByte {
+            description("NUL byte in field headers")
+            display("NUL byte in field headers")
+        }
+        /// A CR not followed by LF in the field headers, in strict mode
+        BareCr {
+            description("CR not followed by LF in field headers")
+            display("CR not followed by LF in field headers")
+        }
+        /// A `Content-Disposition` or `Content-Type` header appearing twice, in strict mode
+        DuplicateHeader(name: &'static str) {
+            description("duplicate header in field headers")
+            display("duplicate {:?} header in field headers", name)
+        }
+        /// A `name` or `filename` parameter longer than `ParseLimits::max_name_len`, in strict mode
+        NameTooLong(param: &'static str, limit: usize) {
+            description("field name or filename too long")
+            display("{:?} parameter longer than the limit of {} bytes", param, limit)
+        }
+        /// A field header line longer than `ParseLimits::max_header_bytes`,
+        /// or one which doesn't end within that many bytes
+        HeaderLineTooLong(limit: usize) {
+            description("field header line too long")
+            display("field header line longer than the limit of {} bytes", limit)
+        }
+        /// A header line starting with whitespace, which is not supported
+        ObsoleteFold {
+            description("field header line starts with whitespace (obsolete line folding)")
+            display("field header line starts with whitespace (obsolete line folding)")
+        }
         /// IO error
         Io(err: io::Error) {
             description("an io error occurred while parsing the headers")
@@ -569,6 +1707,26 @@ quick_error! {
     }
 }
 
+/// A short name for `err` if it is a `ParseHeaderError`, for `Diagnostics`.
+pub fn header_error_cause(err: &(dyn Error + Send + Sync + 'static)) -> Option<&'static str> {
+    use self::ParseHeaderError::*;
+
+    Some(match *err.downcast_ref::<ParseHeaderError>()? {
+        MissingContentDisposition(_) => "missing-content-disposition",
+        InvalidContDisp(..) => "invalid-content-disposition",
+        TokenizeError(_) => "header-syntax",
+        MimeError(_) => "invalid-content-type",
+        TooLarge => "headers-too-large",
+        NulByte => "nul-byte",
+        BareCr => "bare-cr",
+        DuplicateHeader(_) => "duplicate-header",
+        NameTooLong(..) => "name-too-long",
+        HeaderLineTooLong(_) => "header-line-too-long",
+        ObsoleteFold => "obsolete-fold",
+        Io(_) => "header-io",
+    })
+}
+
 #[test]
 fn test_find_header() {
     let headers = [
@@ -601,3 +1759,665 @@ fn test_find_header() {
         "binary"
     );
 }
+
+#[test]
+fn test_disposition_params() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let parse = |cont_disp| FieldHeaders::parse(&[StrHeader {
+        name: "Content-Disposition",
+        val: cont_disp,
+    }]).unwrap();
+
+    let headers = parse("form-data; name=\"file\"; filename=\"a.txt\"; size=12345; \
+                         creation-date=\"Wed, 12 Feb 1997 16:29:51 -0500\"; \
+                         Modification-Date=\"12 Feb 1997 21:29:51 GMT\"; \
+                         X-Custom=\"a; b\"; flag=yes");
+
+    let date = Some(UNIX_EPOCH + Duration::from_secs(855_782_991));
+
+    assert_eq!(&*headers.name, "file");
+    assert_eq!(headers.filename, Some("a.txt".into()));
+    assert_eq!(headers.size, Some(12345));
+    assert_eq!(headers.creation_date, date);
+    assert_eq!(headers.modification_date, date);
+    assert_eq!(headers.read_date, None);
+    assert_eq!(headers.extra_params, vec![("x-custom".to_string(), "a; b".to_string()),
+                                          ("flag".to_string(), "yes".to_string())]);
+
+    // junk values are ignored
+    let headers = parse("form-data; name=f; size=\"12\"; read-date=yesterday; \
+                         creation-date=\"30 Feb 1997 00:00:00 GMT\"");
+    assert_eq!(&*headers.name, "f");
+    assert_eq!(headers.size, Some(12));
+    assert_eq!(headers.creation_date, None);
+    assert_eq!(headers.read_date, None);
+    assert!(headers.extra_params.is_empty());
+
+    let headers = parse("form-data; name=f; size=-1");
+    assert_eq!(headers.size, None);
+    let headers = parse("form-data; name=f; size=lots");
+    assert_eq!(headers.size, None);
+}
+
+#[test]
+fn test_content_type_params() {
+    let parse = |cont_type| FieldHeaders::parse(&[
+        StrHeader { name: "Content-Disposition", val: "form-data; name=\"f\"" },
+        StrHeader { name: "Content-Type", val: cont_type },
+    ]).unwrap();
+
+    let headers = parse("application/vnd.api+json; profile=\"https://a.example/x; y\"; \
+                         Header=present; q=\"a=b\"");
+    assert_eq!(headers.content_type_params().collect::<Vec<_>>(),
+               vec![("profile", "https://a.example/x; y"), ("Header", "present"), ("q", "a=b")]);
+    assert_eq!(headers.content_type_param("header"), Some("present"));
+    assert_eq!(headers.content_type_param("PROFILE"), Some("https://a.example/x; y"));
+    assert_eq!(headers.content_type_param("charset"), None);
+    assert!(!headers.had_duplicate_type_params);
+
+    // the first of duplicate parameters wins
+    let headers = parse("text/csv; charset=utf-8; header=absent; Charset=latin1; header=present");
+    assert_eq!(headers.content_type_params().collect::<Vec<_>>(),
+               vec![("charset", "utf-8"), ("header", "absent")]);
+    assert_eq!(headers.content_type_param("charset"), Some("utf-8"));
+    assert!(headers.had_duplicate_type_params);
+
+    let headers = parse("text/plain");
+    assert_eq!(headers.content_type_params().count(), 0);
+    assert!(!headers.had_duplicate_type_params);
+
+    assert_eq!(FieldHeaders::new("f").content_type_param("charset"), None);
+}
+
+#[test]
+fn test_disposition_type() {
+    let parse = |cont_disp| FieldHeaders::parse(&[StrHeader {
+        name: "Content-Disposition",
+        val: cont_disp,
+    }]);
+
+    let types = [
+        ("form-data; name=\"a\"", DispositionType::FormData),
+        ("Form-Data; name=\"a\"", DispositionType::FormData),
+        ("attachment; filename=\"a.txt\"", DispositionType::Attachment),
+        (" INLINE ; filename=a.txt", DispositionType::Inline),
+        ("X-Bogus; filename=a.txt", DispositionType::Ext("x-bogus".into())),
+    ];
+
+    for &(cont_disp, ref disposition) in &types {
+        let headers = parse(cont_disp).unwrap();
+        assert_eq!(headers.disposition, *disposition, "{:?}", cont_disp);
+
+        // only `form-data` is expected to have a name
+        if *disposition == DispositionType::FormData {
+            assert_eq!(headers.declared_name.as_deref(), Some("a"));
+            assert_eq!(headers.filename, None);
+        } else {
+            assert_eq!(headers.declared_name, None, "{:?}", cont_disp);
+            assert_eq!(headers.filename, Some("a.txt".into()), "{:?}", cont_disp);
+        }
+    }
+
+    assert_eq!(DispositionType::Ext("x-bogus".into()).to_string(), "x-bogus");
+
+    for &cont_disp in &["", "; name=a", "form data; name=a", "form-data/x; name=a"] {
+        match parse(cont_disp) {
+            Err(ParseHeaderError::InvalidContDisp(..)) => (),
+            other => panic!("{:?}: unexpected result: {:?}", cont_disp, other),
+        }
+    }
+}
+
+#[test]
+fn test_client_filename() {
+    let filenames = [
+        ("C:\\dir\\file.txt", Some("file.txt")),
+        ("/home/u/file.txt", Some("file.txt")),
+        ("..\\..\\evil.txt", Some("evil.txt")),
+        ("../mixed\\sep/evil.txt", Some("evil.txt")),
+        ("C:file.txt", Some("file.txt")),
+        ("file.txt", Some("file.txt")),
+        ("C:\\dir\\", None),
+        ("..", None),
+        ("", None),
+    ];
+
+    for &(raw, expected) in &filenames {
+        let mut headers = FieldHeaders::new("file");
+        headers.filename = Some(raw.into());
+
+        assert_eq!(headers.client_filename(), expected, "{:?}", raw);
+        assert_eq!(headers.filename_raw(), Some(raw));
+    }
+
+    assert_eq!(FieldHeaders::new("file").client_filename(), None);
+}
+
+#[test]
+fn test_percent_decode_names() {
+    let parse = |cont_disp: &str| FieldHeaders::parse(&[StrHeader {
+        name: "Content-Disposition",
+        val: cont_disp,
+    }]).unwrap();
+
+    let mut headers = parse("form-data; name=\"user%5Bemail%5D\"; filename=\"na%C3%AFve%20cv.pdf\"");
+    assert!(headers.percent_decode_names());
+    assert_eq!(&*headers.name, "user[email]");
+    assert_eq!(headers.declared_name.as_deref(), Some("user[email]"));
+    assert_eq!(headers.name_raw(), Some("user%5Bemail%5D"));
+    assert_eq!(headers.filename, Some("na\u{EF}ve cv.pdf".into()));
+    assert_eq!(headers.filename_raw(), Some("na%C3%AFve%20cv.pdf"));
+    assert_eq!(headers.client_filename(), Some("na\u{EF}ve cv.pdf"));
+
+    // decoded only once
+    let mut headers = parse("form-data; name=\"a%255B\"");
+    assert!(headers.percent_decode_names());
+    assert!(!headers.percent_decode_names());
+    assert_eq!(&*headers.name, "a%5B");
+    assert_eq!(headers.name_raw(), Some("a%255B"));
+
+    // not encoded, or not decodable to anything sensible
+    for &name in &["100% cotton", "rate%", "%5", "%zz", "caf%E9", "a%0Ab", "a%00b", "plain"] {
+        let mut headers = parse(&format!("form-data; name=\"{}\"; filename=\"{}\"", name, name));
+        assert!(!headers.percent_decode_names(), "{:?}", name);
+        assert_eq!(&*headers.name, name);
+        assert_eq!(headers.name_raw(), Some(name));
+        assert_eq!(headers.encoded_name, None);
+        assert_eq!(headers.filename_raw(), Some(name));
+        assert_eq!(headers.encoded_filename, None);
+    }
+
+    // only the one which is encoded
+    let mut headers = parse("form-data; name=\"100%\"; filename=\"a%2Eb\"");
+    assert!(headers.percent_decode_names());
+    assert_eq!(&*headers.name, "100%");
+    assert_eq!(headers.filename, Some("a.b".into()));
+
+    // an undeclared name is left to the `UnnamedPolicy`
+    let mut headers = parse("form-data; filename=\"a%2Eb\"");
+    assert!(headers.percent_decode_names());
+    assert_eq!(&*headers.name, "");
+    assert_eq!(headers.name_raw(), None);
+}
+
+/// Exact behavior for malformed or malicious field headers, in lenient and strict mode.
+#[cfg(test)]
+mod adversarial_headers {
+    use super::{FieldHeaders, HttparseError, ParseHeaderError, ReadEntry};
+
+    use server::{Multipart, ParseLimits};
+
+    use std::cell::Cell;
+    use std::io;
+    use std::rc::Rc;
+
+    const MODES: [bool; 2] = [false, true];
+
+    fn read_headers(headers: &[u8], strict: bool) -> io::Result<FieldHeaders> {
+        ::init_log();
+
+        let mut body = b"--boundary\r\n".to_vec();
+        body.extend_from_slice(headers);
+        body.extend_from_slice(b"data\r\n--boundary--\r\n");
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.strict_crlf(strict);
+
+        let field = multipart.read_entry_mut().into_result()?.expect("expected a field");
+        Ok(field.headers)
+    }
+
+    fn header_err(headers: &[u8], strict: bool) -> ParseHeaderError {
+        let err = read_headers(headers, strict).expect_err("expected an error");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        *err.into_inner().and_then(|err| err.downcast().ok())
+            .expect("expected `ParseHeaderError`")
+    }
+
+    macro_rules! assert_header_err (
+        ($headers:expr, $strict:expr, $pat:pat) => (
+            match header_err($headers, $strict) {
+                $pat => (),
+                other => panic!("strict: {}, unexpected error: {:?}", $strict, other),
+            }
+        )
+    );
+
+    #[test]
+    fn nul_in_value() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Disposition: form-data; name=\"a\0b\"\r\n\r\n",
+                               strict, ParseHeaderError::NulByte);
+        }
+    }
+
+    #[test]
+    fn nul_in_name() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Dis\0position: form-data; name=\"ab\"\r\n\r\n",
+                               strict, ParseHeaderError::NulByte);
+        }
+    }
+
+    #[test]
+    fn nul_with_bare_cr() {
+        let headers = b"Content-Disposition: form-data; name=\"a\rb\"\r\nX: \0\r\n\r\n";
+
+        // the lenient fallback for bare CRs does not let a NUL through
+        assert_header_err!(headers, false, ParseHeaderError::NulByte);
+        // the first offending byte is reported
+        assert_header_err!(headers, true, ParseHeaderError::BareCr);
+    }
+
+    #[test]
+    fn bare_cr_in_value() {
+        let headers = b"Content-Disposition: form-data; name=\"a\rb\"\r\n\r\n";
+
+        assert_eq!(&*read_headers(headers, false).unwrap().name, "a\rb");
+        assert_header_err!(headers, true, ParseHeaderError::BareCr);
+    }
+
+    #[test]
+    fn bare_cr_smuggled_header() {
+        // a parser which treats CR as a line break would see a `Content-Type` header
+        let headers = b"Content-Disposition: form-data; name=\"ab\"\rContent-Type: text/html\r\n\r\n";
+
+        // the CR stays part of the `Content-Disposition` value
+        let parsed = read_headers(headers, false).unwrap();
+        assert_eq!(&*parsed.name, "ab\"\rContent-Type: text/html");
+        assert_eq!(parsed.content_type, None);
+
+        assert_header_err!(headers, true, ParseHeaderError::BareCr);
+    }
+
+    #[test]
+    fn bare_cr_in_name() {
+        assert_header_err!(b"Content\r-Disposition: form-data; name=\"ab\"\r\n\r\n", false,
+                           ParseHeaderError::TokenizeError(HttparseError::HeaderName));
+        assert_header_err!(b"Content\r-Disposition: form-data; name=\"ab\"\r\n\r\n", true,
+                           ParseHeaderError::BareCr);
+    }
+
+    #[test]
+    fn bare_lf_line_endings() {
+        for &strict in &MODES {
+            let headers = read_headers(b"Content-Disposition: form-data; name=\"ab\"\n\
+                                         Content-Type: text/plain\n\n", strict).unwrap();
+
+            assert_eq!(&*headers.name, "ab");
+            assert_eq!(headers.content_type, Some(mime::TEXT_PLAIN));
+        }
+    }
+
+    #[test]
+    fn obsolete_fold_space() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Disposition: form-data;\r\n name=\"ab\"\r\n\r\n",
+                               strict, ParseHeaderError::ObsoleteFold);
+        }
+    }
+
+    #[test]
+    fn obsolete_fold_tab() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Disposition: form-data;\r\n\tname=\"ab\"\r\n\r\n",
+                               strict, ParseHeaderError::ObsoleteFold);
+        }
+    }
+
+    #[test]
+    fn leading_whitespace() {
+        for &strict in &MODES {
+            assert_header_err!(b" Content-Disposition: form-data; name=\"ab\"\r\n\r\n",
+                               strict, ParseHeaderError::ObsoleteFold);
+        }
+    }
+
+    #[test]
+    fn control_chars() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Disposition: form-data; name=\"a\x01b\"\r\n\r\n",
+                               strict, ParseHeaderError::TokenizeError(HttparseError::HeaderValue));
+            assert_header_err!(b"Content-Disposition: form-data; name=\"a\x7fb\"\r\n\r\n",
+                               strict, ParseHeaderError::TokenizeError(HttparseError::HeaderValue));
+        }
+
+        // the same in the lenient parser used for bare CRs
+        assert_header_err!(b"Content-Disposition: form-data; name=\"a\x01\rb\"\r\n\r\n",
+                           false, ParseHeaderError::TokenizeError(HttparseError::HeaderValue));
+    }
+
+    #[test]
+    fn malformed_lines() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Disposition : form-data; name=\"ab\"\r\n\r\n",
+                               strict, ParseHeaderError::TokenizeError(HttparseError::HeaderName));
+            assert_header_err!(b"Content-Disposition: form-data; name=\"ab\"\r\nGarbage\r\n\r\n",
+                               strict, ParseHeaderError::TokenizeError(HttparseError::HeaderName));
+        }
+    }
+
+    #[test]
+    fn too_many_headers() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Disposition: form-data; name=\"ab\"\r\n\
+                                 A: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\n\r\n",
+                               strict, ParseHeaderError::TokenizeError(HttparseError::TooManyHeaders));
+        }
+
+        assert_header_err!(b"Content-Disposition: form-data; name=\"a\rb\"\r\n\
+                             A: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\n\r\n",
+                           false, ParseHeaderError::TokenizeError(HttparseError::TooManyHeaders));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        for &strict in &MODES {
+            assert_header_err!(b"Content-Disposition: form-data; name=\"\xe9\"\r\n\r\n",
+                               strict, ParseHeaderError::Io(_));
+        }
+    }
+
+    #[test]
+    fn line_longer_than_limit() {
+        let mut headers = b"Content-Disposition: form-data; name=\"".to_vec();
+        headers.extend_from_slice(&[b'x'; 100 * 1024]);
+        headers.extend_from_slice(b"\"\r\n\r\n");
+
+        for &strict in &MODES {
+            assert_header_err!(&headers, strict, ParseHeaderError::HeaderLineTooLong(8192));
+        }
+    }
+
+    #[test]
+    fn headers_longer_than_limit() {
+        let mut headers = b"Content-Disposition: form-data; name=\"a\"\r\n".to_vec();
+
+        for _ in 0 .. 200 {
+            headers.extend_from_slice(b"X-Padding: 0123456789012345678901234567890123456789\r\n");
+        }
+
+        headers.extend_from_slice(b"\r\n");
+
+        for &strict in &MODES {
+            assert_header_err!(&headers, strict, ParseHeaderError::TooLarge);
+        }
+    }
+
+    /// Reads header-like bytes forever, without a line ending, and counts how many were read.
+    struct EndlessLine {
+        read: Rc<Cell<u64>>,
+    }
+
+    impl io::Read for EndlessLine {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            for byte in out.iter_mut() {
+                *byte = b'x';
+            }
+
+            self.read.set(self.read.get() + out.len() as u64);
+            Ok(out.len())
+        }
+    }
+
+    #[test]
+    fn unterminated_line_bounded() {
+        const STREAM_LEN: u64 = 100 * 1024 * 1024;
+
+        for &strict in &MODES {
+            let read = Rc::new(Cell::new(0));
+            let body = io::Read::chain(
+                &b"--boundary\r\nContent-Disposition: form-data; name=\""[..],
+                io::Read::take(EndlessLine { read: read.clone() }, STREAM_LEN),
+            );
+
+            let mut multipart = Multipart::with_body(body, "boundary");
+            multipart.strict_crlf(strict).limits(ParseLimits::default().max_header_bytes(4096));
+
+            let err = match multipart.read_entry_mut().into_result() {
+                Ok(_) => panic!("expected an error"),
+                Err(err) => err,
+            };
+
+            match *err.into_inner().and_then(|err| err.downcast().ok())
+                .expect("expected `ParseHeaderError`") {
+                ParseHeaderError::HeaderLineTooLong(4096) => (),
+                other => panic!("strict: {}, unexpected error: {:?}", strict, other),
+            }
+
+            // stopped just past the limit rather than buffering the whole stream
+            assert!(read.get() < 64 * 1024, "read {} bytes", read.get());
+            assert!(multipart.reader.buf_capacity() < 64 * 1024,
+                    "buffer capacity grew to {}", multipart.reader.buf_capacity());
+        }
+    }
+
+    #[test]
+    fn duplicate_headers() {
+        let cont_disps: &[u8] = b"Content-Disposition: form-data; name=\"a\"\r\n\
+                                  Content-Disposition: form-data; name=\"b\"\r\n\r\n";
+        let cont_types: &[u8] = b"Content-Type: text/plain\r\n\
+                                  Content-Disposition: form-data; name=\"a\"\r\n\
+                                  content-type: text/html\r\n\r\n";
+
+        // the first one wins, whichever it is
+        let headers = read_headers(cont_disps, false).unwrap();
+        assert_eq!(&*headers.name, "a");
+        assert!(headers.had_duplicate_headers);
+
+        let headers = read_headers(cont_types, false).unwrap();
+        assert_eq!(headers.content_type, Some(mime::TEXT_PLAIN));
+        assert!(headers.had_duplicate_headers);
+
+        let headers = read_headers(b"Content-Type: text/html\r\n\
+                                     Content-Disposition: form-data; name=\"a\"\r\n\
+                                     Content-Type: text/plain\r\n\r\n", false).unwrap();
+        assert_eq!(headers.content_type, Some(mime::TEXT_HTML));
+
+        let headers = read_headers(b"Content-Disposition: form-data; name=\"a\"\r\n\
+                                     Content-MD5: x\r\nContent-MD5: y\r\n\r\n", false).unwrap();
+        assert!(!headers.had_duplicate_headers);
+
+        assert_header_err!(cont_disps, true, ParseHeaderError::DuplicateHeader("Content-Disposition"));
+        assert_header_err!(cont_types, true, ParseHeaderError::DuplicateHeader("Content-Type"));
+    }
+
+    #[test]
+    fn valid_utf8() {
+        for &strict in &MODES {
+            let headers = read_headers("Content-Disposition: form-data; name=\"é\"\r\n\r\n"
+                                           .as_bytes(), strict).unwrap();
+
+            assert_eq!(&*headers.name, "é");
+        }
+    }
+
+    #[test]
+    fn long_name_and_filename() {
+        let cont_disp = |name: &str, filename: &str| format!(
+            "Content-Disposition: form-data; name=\"{}\"{}\r\n\r\n", name, filename);
+
+        // a whole megabyte is over `max_header_bytes`, regardless of `max_name_len`
+        let huge = cont_disp(&"é".repeat(512 * 1024), "");
+
+        for &strict in &MODES {
+            assert_header_err!(huge.as_bytes(), strict, ParseHeaderError::HeaderLineTooLong(8192));
+        }
+
+        let long = "é".repeat(2048);
+        let name = cont_disp(&long, "");
+        let filename = cont_disp("a", &format!("; filename=\"{}\"", long));
+
+        let headers = read_headers(name.as_bytes(), false).unwrap();
+        assert!(headers.name_truncated);
+        assert!(headers.name.len() <= 256);
+        assert!(headers.name.starts_with("éé"));
+        assert_eq!(headers.declared_name.as_ref(), Some(&headers.name));
+
+        let headers = read_headers(filename.as_bytes(), false).unwrap();
+        assert!(headers.name_truncated);
+        assert_eq!(&*headers.name, "a");
+        assert!(headers.filename.unwrap().len() <= 256);
+
+        assert_header_err!(name.as_bytes(), true, ParseHeaderError::NameTooLong("name", 256));
+        assert_header_err!(filename.as_bytes(), true, ParseHeaderError::NameTooLong("filename", 256));
+
+        for &strict in &MODES {
+            let headers = read_headers(cont_disp("a", "; filename=\"a.txt\"").as_bytes(), strict).unwrap();
+            assert!(!headers.name_truncated);
+        }
+    }
+
+    #[test]
+    fn truncate_name() {
+        use super::truncate_name;
+
+        let a = truncate_name(&"a".repeat(100), 40);
+        let b = truncate_name(&format!("{}b", "a".repeat(99)), 40);
+        assert_eq!(a.len(), 40);
+        assert_eq!(&a[..23], &b[..23]);
+        assert_ne!(a, b);
+
+        // not cut in the middle of a character, and too short for the hash
+        assert_eq!(truncate_name("éééé", 7), "ééé");
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+mod header_map {
+    use super::{DispositionType, FieldDigest, FieldHeaders, ParseHeaderError};
+
+    use http::header::{self, HeaderMap, HeaderValue};
+
+    fn parse_err(map: &HeaderMap) -> ParseHeaderError {
+        let err = FieldHeaders::from_header_map(map).expect_err("expected an error");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        *err.into_inner().and_then(|err| err.downcast().ok())
+            .expect("expected `ParseHeaderError`")
+    }
+
+    use std::io;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Headers with everything set.
+    fn full_headers() -> FieldHeaders {
+        let mut headers = FieldHeaders::new("upload");
+        headers.filename = Some("résumé (final).pdf".into());
+        headers.content_type = Some("application/pdf".parse().unwrap());
+        headers.digests = vec![
+            FieldDigest { algorithm: "sha-256".into(), value: "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".into() },
+            FieldDigest { algorithm: "md5".into(), value: "1B2M2Y8AsgTpgAmY7PhCfg==".into() },
+        ];
+        headers.size = Some(1234);
+        headers.creation_date = Some(UNIX_EPOCH + Duration::from_secs(855_782_991));
+        headers.read_date = Some(UNIX_EPOCH + Duration::from_secs(951_825_600));
+        headers.extra_params = vec![
+            ("x-note".into(), "say \"hi\"; then \\ leave".into()),
+            ("x-empty".into(), "".into()),
+        ];
+        headers
+    }
+
+    fn assert_same(actual: &FieldHeaders, expected: &FieldHeaders) {
+        // every field is in the `Debug` output
+        assert_eq!(format!("{:?}", actual), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn round_trip() {
+        ::init_log();
+
+        let headers = full_headers();
+        let map = headers.to_header_map().unwrap();
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[header::CONTENT_TYPE], "application/pdf");
+        assert_eq!(map["digest"], "sha-256=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=, md5=1B2M2Y8AsgTpgAmY7PhCfg==");
+
+        let parsed = FieldHeaders::from_header_map(&map).unwrap();
+        assert_same(&parsed, &headers);
+        assert_eq!(parsed.to_header_map().unwrap(), map);
+
+        // an undeclared name stays undeclared
+        let mut unnamed = FieldHeaders::new("");
+        unnamed.filename = Some("a.txt".into());
+
+        let map = unnamed.to_header_map().unwrap();
+        assert_eq!(map[header::CONTENT_DISPOSITION], "form-data; filename=\"a.txt\"");
+        assert_same(&FieldHeaders::from_header_map(&map).unwrap(), &unnamed);
+
+        // as does the disposition type
+        unnamed.disposition = DispositionType::Attachment;
+
+        let map = unnamed.to_header_map().unwrap();
+        assert_eq!(map[header::CONTENT_DISPOSITION], "attachment; filename=\"a.txt\"");
+        assert_same(&FieldHeaders::from_header_map(&map).unwrap(), &unnamed);
+    }
+
+    #[test]
+    fn quoting() {
+        ::init_log();
+
+        let mut headers = FieldHeaders::new("a \"quoted\" name");
+        headers.filename = Some("evil\r\nContent-Type: text/html.txt".into());
+
+        let map = headers.to_header_map().unwrap();
+        assert_eq!(map[header::CONTENT_DISPOSITION],
+                   "form-data; name=\"a %22quoted%22 name\"; filename=\"evil%0D%0AContent-Type: text/html.txt\"");
+
+        let parsed = FieldHeaders::from_header_map(&map).unwrap();
+        assert_eq!(&*parsed.name, "a %22quoted%22 name");
+        assert_eq!(parsed.content_type, None);
+    }
+
+    #[test]
+    fn invalid_values() {
+        ::init_log();
+
+        let mut headers = FieldHeaders::new("a");
+        headers.extra_params.push(("x-note".into(), "line\nbreak".into()));
+        assert!(headers.to_header_map().is_err());
+
+        let mut headers = FieldHeaders::new("a");
+        headers.digests.push(FieldDigest { algorithm: "md5".into(), value: "\0".into() });
+        assert!(headers.to_header_map().is_err());
+    }
+
+    #[test]
+    fn from_header_map_errors() {
+        ::init_log();
+
+        let mut map = HeaderMap::new();
+        map.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+        match parse_err(&map) {
+            ParseHeaderError::MissingContentDisposition(_) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        map.insert(header::CONTENT_DISPOSITION, HeaderValue::from_bytes(b"form-data; name=\"\xFF\"").unwrap());
+
+        match parse_err(&map) {
+            ParseHeaderError::Io(ref e) if e.kind() == io::ErrorKind::InvalidData => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        map.insert(header::CONTENT_DISPOSITION, HeaderValue::from_static("form data; name=\"a\""));
+
+        match parse_err(&map) {
+            ParseHeaderError::InvalidContDisp(..) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // the first of duplicate headers is used
+        map.insert(header::CONTENT_DISPOSITION, HeaderValue::from_static("form-data; name=\"a\""));
+        map.append(header::CONTENT_DISPOSITION, HeaderValue::from_static("form-data; name=\"b\""));
+
+        let headers = FieldHeaders::from_header_map(&map).unwrap();
+        assert_eq!(&*headers.name, "a");
+        assert!(headers.had_duplicate_headers);
+    }
+}
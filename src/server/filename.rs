@@ -0,0 +1,392 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Detection of filenames which disguise their real extension, for
+//! `SaveBuilder::reject_suspicious_filenames()`, and sanitizing of filenames for
+//! `SaveBuilder::use_client_filename()`.
+
+/// Extensions which only mark a compressed file, so e.g. `.tar.gz` isn't a double extension.
+const COMPRESSION_EXTENSIONS: &[&str] = &["br", "bz2", "gz", "lz", "lzma", "xz", "z", "zst"];
+
+/// Characters which Windows doesn't allow in file names; `:` would also name an alternate
+/// data stream on NTFS.
+const RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Names of devices on Windows, which refer to the device with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The maximum length in bytes of a name from `sanitize_filename()`, which most filesystems allow.
+const MAX_SANITIZED_LEN: usize = 255;
+
+/// The longest extension `sanitize_filename()` keeps when it shortens a name, with the dot.
+const MAX_KEPT_EXTENSION_LEN: usize = 16;
+
+/// U+202E RIGHT-TO-LEFT OVERRIDE, which displays the text after it reversed.
+const RLO: char = '\u{202E}';
+/// U+202C POP DIRECTIONAL FORMATTING, which ends an override.
+const PDF: char = '\u{202C}';
+
+/// What `analyze_filename()` found out about a filename.
+///
+/// The *actual* name is the one a filesystem or a program would act on: the last path component,
+/// cut off at the first NUL (literal or `%00`), without bidirectional control characters
+/// and without the trailing dots and spaces which Windows strips.
+///
+/// The *visible* name is the one a person would likely see when it is displayed:
+/// with NUL bytes left out, and the text after a right-to-left override reversed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FilenameReport {
+    /// The extensions of the actual name in order and in lowercase,
+    /// e.g. `["pdf", "exe"]` for `invoice.pdf.exe`.
+    pub extensions: Vec<String>,
+    /// The last extension of the actual name, if any.
+    pub actual_extension: Option<String>,
+    /// The last extension of the visible name in lowercase, if any.
+    pub visible_extension: Option<String>,
+    /// The name contains a NUL byte, which ends a string for C APIs.
+    pub nul: bool,
+    /// The name contains `%00`, a NUL once percent-decoded.
+    pub percent_encoded_nul: bool,
+    /// The name ends with dots or spaces, which Windows strips, e.g. `shell.php.`.
+    pub trailing_dots: bool,
+    /// The name contains Unicode bidirectional control characters, which can make it display
+    /// out of order, e.g. `invoice\u{202E}fdp.exe` as `invoiceexe.pdf`.
+    pub bidi_controls: bool,
+    /// The actual extension follows another extension of a known file type, e.g. `invoice.pdf.exe`.
+    ///
+    /// Compression extensions don't count, e.g. `archive.tar.gz` or `data.json.gz`.
+    pub double_extension: bool,
+}
+
+impl FilenameReport {
+    /// Whether the visible extension differs from the actual extension.
+    pub fn extension_mismatch(&self) -> bool {
+        self.visible_extension != self.actual_extension
+    }
+
+    /// Whether any of the signals enabled in `checks` was found.
+    pub fn is_suspicious(&self, checks: &FilenameChecks) -> bool {
+        (checks.nul && (self.nul || self.percent_encoded_nul))
+            || (checks.trailing_dots && self.trailing_dots)
+            || (checks.bidi_controls && self.bidi_controls)
+            || (checks.extension_mismatch && self.extension_mismatch())
+            || (checks.double_extension && self.double_extension)
+    }
+}
+
+/// Which signals of a `FilenameReport` make a filename suspicious.
+///
+/// The default enables all of them; disable any which reject names your users legitimately
+/// send, e.g. `double_extension` for `photo.jpg.png`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FilenameChecks {
+    /// A NUL byte, literal or percent-encoded.
+    pub nul: bool,
+    /// Trailing dots or spaces.
+    pub trailing_dots: bool,
+    /// Bidirectional control characters.
+    pub bidi_controls: bool,
+    /// A visible extension which differs from the actual one.
+    pub extension_mismatch: bool,
+    /// An executable or other extension appended to that of a known file type.
+    pub double_extension: bool,
+}
+
+impl FilenameChecks {
+    /// No checks; no filename is suspicious.
+    pub fn none() -> Self {
+        FilenameChecks {
+            nul: false,
+            trailing_dots: false,
+            bidi_controls: false,
+            extension_mismatch: false,
+            double_extension: false,
+        }
+    }
+}
+
+impl Default for FilenameChecks {
+    fn default() -> Self {
+        FilenameChecks {
+            nul: true,
+            trailing_dots: true,
+            bidi_controls: true,
+            extension_mismatch: true,
+            double_extension: true,
+        }
+    }
+}
+
+/// Analyze a client-supplied filename for tricks which disguise its real extension.
+///
+/// See [`FilenameReport`](struct.FilenameReport.html) for what is detected.
+///
+/// ```rust
+/// # use multipart::server::save::analyze_filename;
+/// let report = analyze_filename("image.svg%00.png");
+/// assert!(report.percent_encoded_nul);
+/// assert_eq!(report.actual_extension.as_deref(), Some("svg"));
+/// assert_eq!(report.visible_extension.as_deref(), Some("png"));
+/// ```
+pub fn analyze_filename(filename: &str) -> FilenameReport {
+    let name = last_component(filename);
+
+    let nul = name.contains('\0');
+    let percent_encoded_nul = name.contains("%00");
+    let bidi_controls = name.chars().any(is_bidi_control);
+
+    let actual = name.split('\0').next().unwrap_or(name);
+    let actual = actual.split("%00").next().unwrap_or(actual);
+    let actual: String = actual.chars().filter(|&c| !is_bidi_control(c)).collect();
+    let trimmed = actual.trim_end_matches(&['.', ' '][..]);
+
+    let extensions: Vec<String> = trimmed.trim_start_matches('.').split('.').skip(1)
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let double_extension = extensions.len() >= 2 && {
+        let (prev, last) = (&extensions[extensions.len() - 2], &extensions[extensions.len() - 1]);
+        !COMPRESSION_EXTENSIONS.contains(&&**last) && ::mime_guess::from_ext(prev).first().is_some()
+    };
+
+    FilenameReport {
+        actual_extension: extensions.last().cloned(),
+        visible_extension: last_extension(&visible_name(name)),
+        extensions,
+        nul,
+        percent_encoded_nul,
+        trailing_dots: trimmed.len() < actual.len(),
+        bidi_controls,
+        double_extension,
+    }
+}
+
+/// The part of a client's path after the last `/` or `\` and any drive letter,
+/// for `FieldHeaders::client_filename()`.
+pub fn last_component(path: &str) -> &str {
+    let bytes = path.as_bytes();
+    let path = if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        &path[2..]
+    } else {
+        path
+    };
+
+    path.rsplit(&['/', '\\'][..]).next().unwrap_or(path)
+}
+
+/// Make a client-supplied filename safe to create in a directory, as
+/// `SaveBuilder::use_client_filename()` does; the result is empty if nothing usable is left.
+///
+/// * Only the last component of a path is kept, after the last `/` or `\` and any drive letter,
+///   so the name can't point outside the directory.
+/// * NULs and other control characters and bidirectional formatting characters are dropped.
+/// * Characters which Windows doesn't allow in names (`<>:"|?*`) become `_`.
+/// * Leading dots and spaces and the trailing ones which Windows strips are dropped,
+///   so `.` and `..` become empty and the name can't be a hidden file like `.htaccess`.
+/// * A name which Windows reserves for a device, like `CON` or `nul.txt`, gets a leading `_`.
+/// * A name longer than 255 bytes is cut off, keeping its extension.
+///
+/// The result may still have any extension, so it shouldn't be served from a directory where
+/// the web server runs scripts; see `analyze_filename()` for names which disguise their
+/// extension.
+///
+/// ```rust
+/// # use multipart::server::save::sanitize_filename;
+/// assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+/// assert_eq!(sanitize_filename("C:\\windows\\foo"), "foo");
+/// assert_eq!(sanitize_filename("report\u{0}.pdf"), "report.pdf");
+/// assert_eq!(sanitize_filename(".htaccess"), "htaccess");
+/// assert_eq!(sanitize_filename(".."), "");
+/// ```
+pub fn sanitize_filename(filename: &str) -> String {
+    let name: String = last_component(filename).chars()
+        .filter(|&c| !c.is_control() && !is_bidi_control(c))
+        .map(|c| if RESERVED_CHARS.contains(&c) { '_' } else { c })
+        .collect();
+    let name = name.trim_start_matches(&['.', ' '][..]).trim_end_matches(&['.', ' '][..]);
+
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    let name = if RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved)) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    };
+
+    if name.len() <= MAX_SANITIZED_LEN {
+        return name;
+    }
+
+    let extension = match name.rfind('.') {
+        Some(dot) if name.len() - dot <= MAX_KEPT_EXTENSION_LEN => &name[dot..],
+        _ => "",
+    };
+
+    let mut len = MAX_SANITIZED_LEN - extension.len();
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    format!("{}{}", name[..len].trim_end_matches(&['.', ' '][..]), extension)
+}
+
+/// Approximate how `name` is displayed: NULs are left out, and the text after a right-to-left
+/// override is reversed up to the end of the override.
+fn visible_name(name: &str) -> String {
+    let mut visible = String::with_capacity(name.len());
+    let mut rest = name;
+
+    while let Some(start) = rest.find(RLO) {
+        visible.push_str(&rest[..start]);
+        rest = &rest[start + RLO.len_utf8()..];
+
+        let end = rest.find(PDF).unwrap_or(rest.len());
+        visible.extend(rest[..end].chars().rev());
+        rest = &rest[end..];
+    }
+
+    visible.push_str(rest);
+    visible.retain(|c| c != '\0' && !is_bidi_control(c));
+    visible
+}
+
+/// The text after the last dot in lowercase, unless it's empty.
+///
+/// Trailing whitespace isn't visible, so it's ignored.
+fn last_extension(name: &str) -> Option<String> {
+    let name = name.trim_start_matches('.').trim_end();
+    let dot = name.rfind('.')?;
+    Some(name[dot + 1..].to_lowercase()).filter(|ext| !ext.is_empty())
+}
+
+/// The Unicode bidirectional formatting characters.
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}' ..= '\u{202E}' | '\u{2066}' ..= '\u{2069}')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FilenameChecks, analyze_filename, sanitize_filename};
+
+    /// Names which disguise their extension: the name, its actual and visible extensions,
+    /// and a check which catches it on its own.
+    const KNOWN_BAD: &[(&str, &str, Option<&str>, &str)] = &[
+        ("invoice.pdf.exe", "exe", Some("exe"), "double_extension"),
+        ("resume.doc.js", "js", Some("js"), "double_extension"),
+        ("shell.php.", "php", None, "trailing_dots"),
+        ("shell.php. . .", "php", None, "extension_mismatch"),
+        ("payload.asp ", "asp", Some("asp"), "trailing_dots"),
+        ("image.svg%00.png", "svg", Some("png"), "nul"),
+        ("image.svg%00.png", "svg", Some("png"), "extension_mismatch"),
+        ("image.php\0.jpg", "php", Some("jpg"), "nul"),
+        ("invoice\u{202E}fdp.exe", "exe", Some("pdf"), "bidi_controls"),
+        ("invoice\u{202E}fdp.exe", "exe", Some("pdf"), "extension_mismatch"),
+        ("photo\u{202E}gpj\u{202C}.scr", "scr", Some("scr"), "bidi_controls"),
+        ("C:\\Users\\x\\report.xlsx.bat", "bat", Some("bat"), "double_extension"),
+    ];
+
+    fn only(check: &str) -> FilenameChecks {
+        let mut checks = FilenameChecks::none();
+
+        *match check {
+            "nul" => &mut checks.nul,
+            "trailing_dots" => &mut checks.trailing_dots,
+            "bidi_controls" => &mut checks.bidi_controls,
+            "extension_mismatch" => &mut checks.extension_mismatch,
+            "double_extension" => &mut checks.double_extension,
+            _ => unreachable!(),
+        } = true;
+
+        checks
+    }
+
+    #[test]
+    fn test_known_bad() {
+        for &(name, actual, visible, check) in KNOWN_BAD {
+            let report = analyze_filename(name);
+            assert_eq!(report.actual_extension.as_deref(), Some(actual), "{:?}", name);
+            assert_eq!(report.visible_extension.as_deref(), visible, "{:?}", name);
+
+            let checks = only(check);
+            assert!(report.is_suspicious(&checks), "{:?} not caught by {:?}", name, checks);
+            assert!(report.is_suspicious(&FilenameChecks::default()), "{:?}", name);
+            assert!(!report.is_suspicious(&FilenameChecks::none()), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_benign() {
+        for &name in &["photo.jpg", "archive.tar.gz", "data.json.gz", "jquery.min.js", "report.v2.pdf",
+                       ".bashrc", "README", "../../etc/passwd", "naïve café.txt", "100%.txt"] {
+            let report = analyze_filename(name);
+            assert!(!report.is_suspicious(&FilenameChecks::default()), "{:?}: {:?}", name, report);
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = analyze_filename("a/b/Invoice.PDF.exe");
+        assert_eq!(report.extensions, ["pdf", "exe"]);
+        assert!(report.double_extension && !report.extension_mismatch());
+
+        let report = analyze_filename("invoice\u{202E}fdp.exe");
+        assert_eq!(report.extensions, ["exe"]);
+        assert!(report.bidi_controls && !report.double_extension);
+
+        assert_eq!(analyze_filename("archive.tar.gz").extensions, ["tar", "gz"]);
+        assert_eq!(analyze_filename(".bashrc").extensions, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_sanitize_escapes() {
+        for &(name, sanitized) in &[
+            ("../../etc/passwd", "passwd"),
+            ("..\\..\\etc\\passwd", "passwd"),
+            ("C:\\windows\\foo", "foo"),
+            ("C:foo", "foo"),
+            ("/etc/", ""),
+            ("\\\\server\\share\\x.txt", "x.txt"),
+            ("..", ""),
+            (". . .", ""),
+            ("..\u{0}", ""),
+            ("a/..", ""),
+            ("", ""),
+        ] {
+            assert_eq!(sanitize_filename(name), sanitized, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_sanitize() {
+        for &(name, sanitized) in &[
+            ("photo.jpg", "photo.jpg"),
+            ("naïve café.txt", "naïve café.txt"),
+            ("shell.php\u{0}.jpg", "shell.php.jpg"),
+            ("line\r\nbreak.txt", "linebreak.txt"),
+            ("in\u{202E}fdp.exe", "infdp.exe"),
+            ("shell.php. . ", "shell.php"),
+            (" .bashrc", "bashrc"),
+            ("ab:c?.txt", "ab_c_.txt"),
+            ("CON", "_CON"),
+            ("nul.tar.gz", "_nul.tar.gz"),
+            ("lpt1 .txt", "_lpt1 .txt"),
+            ("console.log", "console.log"),
+        ] {
+            assert_eq!(sanitize_filename(name), sanitized, "{:?}", name);
+        }
+
+        let long = format!("{}.jpeg", "é".repeat(200));
+        let name = sanitize_filename(&long);
+        assert!(name.len() <= 255 && name.ends_with("é.jpeg"), "{:?}", name);
+
+        let name = sanitize_filename(&"x".repeat(300));
+        assert_eq!(name.len(), 255);
+    }
+}
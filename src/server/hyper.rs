@@ -10,13 +10,16 @@
 //! Also contains an implementation of [`HttpRequest`](../trait.HttpRequest.html)
 //! for `hyper::server::Request` and `&mut hyper::server::Request`.
 use hyper::net::Fresh;
-use hyper::header::ContentType;
 use hyper::method::Method;
 use hyper::server::{Handler, Request, Response};
 
 pub use hyper::server::Request as HyperRequest;
 
-use hyper::mime::{Mime, TopLevel, SubLevel, Attr, Value};
+/// Multipart bodies are accepted with `Post`, `Put` or `Patch`.
+fn allows_body(method: &Method) -> bool {
+    matches!(*method, Method::Post | Method::Put | Method::Patch)
+}
+
 
 use super::{Multipart, HttpRequest};
 
@@ -73,29 +76,15 @@ impl<'a, 'b> HttpRequest for HyperRequest<'a, 'b> {
     type Body = Self;
 
     fn multipart_boundary(&self) -> Option<&str> {
-        if self.method != Method::Post {
+        if !allows_body(&self.method) {
             return None;
         }
 
-        self.headers.get::<ContentType>().and_then(|ct| {
-            let ContentType(ref mime) = *ct;
-            let params = match *mime {
-                Mime(TopLevel::Multipart, SubLevel::FormData, ref params) => params,
-                _ => return None,
-            };
-
-            params.iter().find(|&&(ref name, _)|
-                match *name {
-                    Attr::Boundary => true,
-                    _ => false,
-                }
-            ).and_then(|&(_, ref val)|
-                match *val {
-                    Value::Ext(ref val) => Some(&**val),
-                    _ => None,
-                }
-            )
-        })
+        super::raw_form_data_boundary(self.headers.get_raw("Content-Type"))
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        super::raw_content_type(self.headers.get_raw("Content-Type"))
     }
 
     fn body(self) -> Self {
@@ -107,29 +96,15 @@ impl<'r, 'a, 'b> HttpRequest for &'r mut HyperRequest<'a, 'b> {
     type Body = Self;
 
     fn multipart_boundary(&self) -> Option<&str> {
-        if self.method != Method::Post {
+        if !allows_body(&self.method) {
             return None;
         }
 
-        self.headers.get::<ContentType>().and_then(|ct| {
-            let ContentType(ref mime) = *ct;
-            let params = match *mime {
-                Mime(TopLevel::Multipart, SubLevel::FormData, ref params) => params,
-                _ => return None,
-            };
-
-            params.iter().find(|&&(ref name, _)|
-                match *name {
-                    Attr::Boundary => true,
-                    _ => false,
-                }
-            ).and_then(|&(_, ref val)|
-                match *val {
-                    Value::Ext(ref val) => Some(&**val),
-                    _ => None,
-                }
-            )
-        })
+        super::raw_form_data_boundary(self.headers.get_raw("Content-Type"))
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        super::raw_content_type(self.headers.get_raw("Content-Type"))
     }
 
     fn body(self) -> Self::Body {
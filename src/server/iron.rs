@@ -3,30 +3,27 @@
 //! Not shown here: `impl `[`HttpRequest`](../trait.HttpRequest.html#implementors)` for
 //! iron::Request`.
 
-use iron::headers::ContentType;
-use iron::mime::{Mime, TopLevel, SubLevel};
 use iron::request::{Body as IronBody, Request as IronRequest};
 use iron::typemap::Key;
 use iron::{BeforeMiddleware, IronError, IronResult};
 
 use std::path::PathBuf;
 use std::{error, fmt, io};
-use tempfile;
+use tempfile::{self, TempDir};
 
 use super::{FieldHeaders, HttpRequest, Multipart};
-use super::save::{Entries, PartialReason, TempDir};
+use super::save::{Entries, PartialReason, SaveBuilder, SaveBuilderConfig};
 use super::save::SaveResult::*;
 
 impl<'r, 'a, 'b> HttpRequest for &'r mut IronRequest<'a, 'b> {
     type Body = &'r mut IronBody<'a, 'b>;
 
     fn multipart_boundary(&self) -> Option<&str> {
-        let content_type = try_opt!(self.headers.get::<ContentType>());
-        if let Mime(TopLevel::Multipart, SubLevel::FormData, _) = **content_type {
-            content_type.get_param("boundary").map(|b| b.as_str())
-        } else {
-            None
-        }
+        super::raw_form_data_boundary(self.headers.get_raw("Content-Type"))
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        super::raw_content_type(self.headers.get_raw("Content-Type"))
     }
 
     fn body(self) -> &'r mut IronBody<'a, 'b> {
@@ -71,7 +68,6 @@ pub const DEFAULT_FILE_COUNT_LIMIT: u32 = 16;
 /// ```
 ///
 /// Any errors during which occur during reading will be passed on as `IronError`.
-#[derive(Debug)]
 pub struct Intercept {
     /// The parent directory for all temporary directories created by this middleware.
     /// Will be created if it doesn't exist (lazy).
@@ -96,6 +92,13 @@ pub struct Intercept {
     ///
     /// See [`LimitBehavior`](enum.limitbehavior.html) for more info.
     pub limit_behavior: LimitBehavior,
+    /// Other settings for saving requests, e.g. a memory threshold or filename checks,
+    /// shared with other integrations or handlers through `SaveBuilderConfig::apply()`.
+    ///
+    /// `file_size_limit` and `file_count_limit` take precedence over the limits set here.
+    ///
+    /// Default value: `SaveBuilderConfig::config()`.
+    pub save_config: SaveBuilderConfig,
 }
 
 impl Intercept {
@@ -119,6 +122,18 @@ impl Intercept {
         Intercept { limit_behavior: behavior, .. self }
     }
 
+    /// Set the `save_config` for this middleware.
+    pub fn save_config(self, config: SaveBuilderConfig) -> Self {
+        Intercept { save_config: config, .. self }
+    }
+
+    fn saver<'m, 'r, 'a, 'b>(&self, multipart: &'m mut IronMultipart<'r, 'a, 'b>)
+                             -> SaveBuilder<&'m mut IronMultipart<'r, 'a, 'b>> {
+        multipart.save_with(&self.save_config)
+            .size_limit(self.file_size_limit)
+            .max_parts(self.file_count_limit)
+    }
+
     fn read_request(&self, req: &mut IronRequest) -> IronResult<Option<Entries>> {
         let multipart = match Multipart::from_request(req) {
             Ok(multipart) => multipart,
@@ -139,14 +154,11 @@ impl Intercept {
     }
 
     fn read_request_strict(&self, mut multipart: IronMultipart, tempdir: TempDir) -> IronResult<Option<Entries>> {
-        match multipart.save().size_limit(self.file_size_limit)
-                              .count_limit(self.file_count_limit)
-                              .with_temp_dir(tempdir) {
+        match self.saver(&mut multipart).with_temp_dir(tempdir) {
             Full(entries) => Ok(Some(entries)),
-            Partial(_, PartialReason::Utf8Error(_)) => unreachable!(),
-            Partial(_, PartialReason::IoError(err)) => Err(io_to_iron(err, "Error midway through request")),
-            Partial(_, PartialReason::CountLimit) => Err(FileCountLimitError(self.file_count_limit).into()),
-            Partial(partial, PartialReason::SizeLimit) =>  {
+            Partial(_, PartialReason::IoError(err)) | Partial(_, PartialReason::InsufficientStorage { error: err, .. }) => Err(io_to_iron(err, "Error midway through request")),
+            Partial(_, PartialReason::CountLimit { limit }) => Err(FileCountLimitError(limit).into()),
+            Partial(partial, PartialReason::SizeLimit { .. }) =>  {
                 let partial = partial.partial.expect(EXPECT_PARTIAL_FILE);
                 Err(
                     FileSizeLimitError {
@@ -154,26 +166,27 @@ impl Intercept {
                     }.into()
                 )
             },
+            // only possible with settings from `save_config`
+            Partial(_, reason) => {
+                let desc = reason.to_string();
+                Err(io_to_iron(io::Error::new(io::ErrorKind::InvalidData, desc.clone()), desc))
+            },
             Error(err) => Err(io_to_iron(err, "Error at start of request")),
         }
     }
 
     fn read_request_lenient(&self, mut multipart: IronMultipart, tempdir: TempDir) -> IronResult<Option<Entries>> {
-        let mut entries = match multipart.save().size_limit(self.file_size_limit)
-                                                .count_limit(self.file_count_limit)
-                                                .with_temp_dir(tempdir) {
+        let mut entries = match self.saver(&mut multipart).with_temp_dir(tempdir) {
             Full(entries) => return Ok(Some(entries)),
-            Partial(_, PartialReason::IoError(err)) => return Err(io_to_iron(err, "Error midway through request")),
+            Partial(_, PartialReason::IoError(err)) | Partial(_, PartialReason::InsufficientStorage { error: err, .. }) => return Err(io_to_iron(err, "Error midway through request")),
             Partial(partial, _) =>  partial.keep_partial(),
             Error(err) => return Err(io_to_iron(err, "Error at start of request")),
         };
 
         loop {
-            entries = match multipart.save().size_limit(self.file_size_limit)
-                                            .count_limit(self.file_count_limit)
-                                            .with_entries(entries) {
+            entries = match self.saver(&mut multipart).with_entries(entries) {
                 Full(entries) => return Ok(Some(entries)),
-                Partial(_, PartialReason::IoError(err)) => return Err(io_to_iron(err, "Error midway through request")),
+                Partial(_, PartialReason::IoError(err)) | Partial(_, PartialReason::InsufficientStorage { error: err, .. }) => return Err(io_to_iron(err, "Error midway through request")),
                 Partial(partial, _) => partial.keep_partial(),
                 Error(err) => return Err(io_to_iron(err, "Error at start of request")),
             };
@@ -193,10 +206,22 @@ impl Default for Intercept {
             file_size_limit: DEFAULT_FILE_SIZE_LIMIT,
             file_count_limit: DEFAULT_FILE_COUNT_LIMIT,
             limit_behavior: LimitBehavior::ThrowError,
+            save_config: SaveBuilderConfig::config(),
         }
     }
 }
 
+impl fmt::Debug for Intercept {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Intercept")
+            .field("temp_dir_path", &self.temp_dir_path)
+            .field("file_size_limit", &self.file_size_limit)
+            .field("file_count_limit", &self.file_count_limit)
+            .field("limit_behavior", &self.limit_behavior)
+            .finish()
+    }
+}
+
 impl BeforeMiddleware for Intercept {
     fn before(&self, req: &mut IronRequest) -> IronResult<()> {
         self.read_request(req)?
@@ -10,24 +10,57 @@
 //! to accept, parse, and serve HTTP `multipart/form-data` requests (file uploads).
 //!
 //! See the `Multipart` struct for more info.
+//!
+//! If the whole request body is already in memory, `parse_form_data()` parses it straight into
+//! `Entries` without touching the filesystem.
 
+/// Only public for `save::BufReader`; not part of this crate's API.
+#[cfg(feature = "buf_redux")]
+#[doc(hidden)]
 pub extern crate buf_redux;
 extern crate httparse;
 extern crate twoway;
 
+use std::any::Any;
 use std::borrow::Borrow;
+use std::error::Error;
 use std::io::prelude::*;
-use std::io;
+use std::io::SeekFrom;
+use std::str::FromStr;
+use std::{fmt, io, str};
+
+use mime::Mime;
 
 use self::boundary::BoundaryReader;
 
+pub use self::boundary::{DrainLimitExceeded, EmptyPartError, EventSinkPanicked, MissingBoundaryError, MissingCrlfError,
+                         NoProgressError, ParseEvent};
+
+pub use self::diagnostics::{DiagnosticEvent, Diagnostics};
+
+pub use self::content_type::{MultipartKind, MultipartType, is_multipart, parse_multipart_type};
+
 use self::field::PrivReadEntry;
 
-pub use self::field::{FieldHeaders, MultipartField, MultipartData, ReadEntry, ReadEntryResult};
+pub use self::field::{DispositionType, FieldDigest, FieldHeaders, MultipartField, MultipartData, ReadEntry, ReadEntryResult,
+                      UnnamedFieldError, UnnamedPolicy};
+
+pub use self::newline::NewlinePolicy;
+
+pub use self::parts::{Part, PartsSource};
+
+#[cfg(feature = "infer")]
+pub use self::sniff::InferSniffer;
+
+pub use self::sniff::{MagicSniffer, TypeSniffer};
+
+pub use self::throttle::{Throttle, Throttled};
 
 use self::save::SaveBuilder;
 
-pub use self::save::{Entries, SaveResult, SavedField};
+pub use self::save::{Abort, BufferPool, ByteSize, CancelToken, DirLimitExceeded, Entries, EntriesSummary, FieldErrorPolicy, FieldFailure, FieldMeta, FieldMatcher, FieldSummary, FieldTiming, FieldTimingStats, FileSize, MultipartError,
+                     OwnedField, RawRange, RecoveryReport, ResponseParts, RetryPolicy, SaveBuilderConfig, SavePhase, SaveResult, SaveStats, SaveTotals, SavedField, SharedSaveRoot, SavedFieldError, TempDirError, TextNorm, parse_form_data,
+                     parse_form_data_with_limit, read_raw_ranges, recover_staging};
 
 macro_rules! try_opt (
     ($expr:expr) => (
@@ -51,13 +84,35 @@ macro_rules! try_read_entry {
     ($self_:expr; $try:expr) => (
         match $try {
             Ok(res) => res,
-            Err(err) => return ::server::ReadEntryResult::Error($self_, err),
+            Err(err) => {
+                $self_.record_error(&err);
+                return ::server::ReadEntryResult::Error($self_, err);
+            },
         }
     )
 }
 
+#[cfg(feature = "archive")]
+mod archive;
+
 mod boundary;
+mod buffer;
+mod charset;
+mod content_type;
+mod date;
+mod diagnostics;
+#[cfg(feature = "digest")]
+mod digest;
 mod field;
+mod filename;
+mod newline;
+mod parts;
+mod raw;
+mod sniff;
+mod throttle;
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
 
 #[cfg(feature = "hyper")]
 pub mod hyper;
@@ -71,6 +126,9 @@ pub mod tiny_http;
 #[cfg(feature = "nickel")]
 pub mod nickel;
 
+#[cfg(feature = "multer")]
+pub mod multer;
+
 pub mod save;
 
 /// The server-side implementation of `multipart/form-data` requests.
@@ -78,6 +136,14 @@ pub mod save;
 /// Implements `Borrow<R>` to allow access to the request body, if desired.
 pub struct Multipart<R> {
     reader: BoundaryReader<R>,
+    unnamed_policy: UnnamedPolicy,
+    fields_read: u32,
+    /// The value of `fields_read` when the current field's boundary was consumed.
+    part_index: u32,
+    content_type: Option<Mime>,
+    max_name_len: usize,
+    max_header_bytes: usize,
+    percent_decode_names: bool,
 }
 
 impl Multipart<()> {
@@ -91,8 +157,60 @@ impl Multipart<()> {
             None => return Err(req),
         };
 
-        Ok(Multipart::with_body(req.body(), boundary))        
-    }   
+        let content_type = req.content_type().and_then(|ct| ct.parse().ok());
+
+        let mut multipart = Multipart::with_body(req.body(), boundary);
+        multipart.content_type = content_type;
+        Ok(multipart)
+    }
+
+    /// If `content_type` (the value of the request's `Content-Type` header) is
+    /// `multipart/form-data` with a boundary, return `body` wrapped in the multipart reader.
+    /// Otherwise, returns `body`.
+    ///
+    /// See [`parse_multipart_type()`](fn.parse_multipart_type.html) for how the header is parsed.
+    pub fn from_content_type<R: Read>(body: R, content_type: &str) -> Result<Multipart<R>, R> {
+        match form_data_boundary(content_type) {
+            Some(boundary) => {
+                let mut multipart = Multipart::with_body(body, boundary);
+                multipart.content_type = content_type.parse().ok();
+                Ok(multipart)
+            },
+            None => Err(body),
+        }
+    }
+}
+
+/// Get the boundary from a `multipart/form-data` `Content-Type`,
+/// for the `HttpRequest::multipart_boundary()` impls.
+fn form_data_boundary(content_type: &str) -> Option<&str> {
+    let parsed = parse_multipart_type(content_type)?;
+
+    if parsed.kind != MultipartKind::FormData {
+        return None;
+    }
+
+    parsed.into_boundary().filter(|boundary| !boundary.is_empty())
+}
+
+/// `form_data_boundary()` for the raw values of a `Content-Type` header as stored by Hyper.
+#[cfg(any(feature = "hyper", feature = "iron", feature = "nickel"))]
+fn raw_form_data_boundary(raw: Option<&[Vec<u8>]>) -> Option<&str> {
+    form_data_boundary(raw_content_type(raw)?)
+}
+
+/// The first value of a `Content-Type` header as stored by Hyper, if it is valid UTF-8.
+#[cfg(any(feature = "hyper", feature = "iron", feature = "nickel"))]
+fn raw_content_type(raw: Option<&[Vec<u8>]>) -> Option<&str> {
+    ::std::str::from_utf8(raw?.first()?).ok()
+}
+
+/// The message a callback panicked with, as caught by `catch_unwind()`.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map_or("Box<dyn Any>", |message| message),
+    }
 }
 
 impl<R: Read> Multipart<R> {
@@ -110,9 +228,188 @@ impl<R: Read> Multipart<R> {
 
         info!("Multipart::with_boundary(_, {:?})", boundary);
 
-        Multipart { 
+        let mut multipart = Multipart {
             reader: BoundaryReader::from_reader(body, boundary),
-        }
+            unnamed_policy: UnnamedPolicy::default(),
+            fields_read: 0,
+            part_index: 0,
+            content_type: None,
+            max_name_len: DEFAULT_MAX_NAME_LEN,
+            max_header_bytes: DEFAULT_MAX_HEADER_BYTES,
+            percent_decode_names: false,
+        };
+
+        multipart.limits(ParseLimits::default());
+        multipart
+    }
+
+    /// The boundary separating the fields of the request, without the leading `--`.
+    pub fn boundary(&self) -> &str {
+        str::from_utf8(self.reader.boundary()).expect("the boundary was passed as a `String`")
+    }
+
+    /// The parsed `Content-Type` of the request, if this was constructed with
+    /// `from_request()` (and the `HttpRequest` impl provides it) or `from_content_type()`.
+    ///
+    /// `None` for `with_body()`, which is only given the boundary.
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.content_type.as_ref()
+    }
+
+    /// Set the limits applied while reading the request.
+    ///
+    /// See [`ParseLimits`](struct.ParseLimits.html) for the limits and their defaults.
+    pub fn limits(&mut self, limits: ParseLimits) -> &mut Self {
+        self.reader.set_max_drain_bytes(limits.max_drain_bytes);
+        self.reader.set_max_empty_parts(limits.max_empty_parts);
+        self.reader.set_max_empty_reads(limits.max_empty_reads);
+        self.max_name_len = limits.max_name_len;
+        self.max_header_bytes = limits.max_header_bytes;
+        self
+    }
+
+    /// Set what to do with fields which don't have a name (or have an empty one).
+    ///
+    /// See [`UnnamedPolicy`](enum.UnnamedPolicy.html) for the options;
+    /// defaults to `UnnamedPolicy::Synthesize`.
+    pub fn on_unnamed(&mut self, policy: UnnamedPolicy) -> &mut Self {
+        self.unnamed_policy = policy;
+        self
+    }
+
+    /// Set whether to percent-decode the name and filename of each field as it's read, for
+    /// clients which URL-encode them; off by default.
+    ///
+    /// See `FieldHeaders::percent_decode_names()` for when a value is decoded. `max_name_len`
+    /// (see `ParseLimits`) applies to the decoded values. `SaveBuilder::percent_decode_names()`
+    /// does the same for a request which is saved, and doesn't decode the names again.
+    pub fn percent_decode_names(&mut self, decode: bool) -> &mut Self {
+        self.percent_decode_names = decode;
+        self
+    }
+
+    /// Set whether to parse the request strictly, rejecting the deviations from RFC 7578 which
+    /// are tolerated by default. Despite the name, this is the one switch for all of them,
+    /// not only for line endings; each is described below.
+    ///
+    /// Some clients omit the CRLF between the end of the final field and the closing boundary.
+    /// By default this is accepted and the field ends at the byte preceding the boundary.
+    /// In strict mode, this returns an error which can be downcast to
+    /// [`MissingCrlfError`](struct.MissingCrlfError.html).
+    ///
+    /// By default a bare CR in a field header is kept as part of the header value;
+    /// in strict mode it is an error. Regardless of this setting, a NUL byte or a header line
+    /// starting with whitespace (obsolete line folding) is always an error.
+    ///
+    /// Likewise, a body which ends without the closing boundary, right after the CRLF ending
+    /// a field, is accepted by default, ending after that field; see `finished_cleanly()`.
+    /// In strict mode it is an error. A body which ends anywhere else within a field is always
+    /// an error (`io::ErrorKind::UnexpectedEof`).
+    /// A body which doesn't contain the boundary at all (e.g. an empty body, or `----`)
+    /// is read as having no fields by default; in strict mode it is an error which can be
+    /// downcast to [`MissingBoundaryError`](struct.MissingBoundaryError.html).
+    /// A body which is just the closing boundary is valid in either mode, with no fields.
+    ///
+    /// A field with more than one `Content-Disposition` or `Content-Type` header is an error
+    /// in strict mode; by default the first is used and `FieldHeaders::had_duplicate_headers`
+    /// is set.
+    ///
+    /// A part with no headers and no data (two boundaries separated only by a blank line)
+    /// is an error in strict mode; by default it is skipped, up to `ParseLimits::max_empty_parts`.
+    pub fn strict_crlf(&mut self, strict: bool) -> &mut Self {
+        self.reader.set_strict_crlf(strict);
+        self
+    }
+
+    /// Call `sink` with each [`ParseEvent`](enum.ParseEvent.html) as the request is read,
+    /// giving the offsets of each boundary, header block and chunk of field data in the body.
+    ///
+    /// Without a sink, this costs nothing more than checking for one.
+    ///
+    /// If `sink` panics, the panic is caught rather than unwinding through the parser: the sink
+    /// is dropped and every read of the request from then on fails with an `io::Error` wrapping
+    /// [`EventSinkPanicked`](struct.EventSinkPanicked.html). A save stops with
+    /// `PartialReason::CallbackPanicked`.
+    ///
+    /// ```rust
+    /// # use multipart::server::{Multipart, ParseEvent};
+    /// # use std::sync::mpsc;
+    /// let body = b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n--boundary--";
+    /// let (send, recv) = mpsc::channel();
+    ///
+    /// let mut multipart = Multipart::with_body(&body[..], "boundary");
+    /// multipart.set_event_sink(move |event| send.send(event).unwrap());
+    /// multipart.foreach_entry(|_| ()).unwrap();
+    /// drop(multipart);
+    ///
+    /// assert_eq!(recv.iter().last(), Some(ParseEvent::BodyEnd { offset: body.len() as u64, clean: true }));
+    /// ```
+    pub fn set_event_sink<F>(&mut self, sink: F) -> &mut Self where F: FnMut(ParseEvent) + Send + 'static {
+        self.reader.set_event_sink(Box::new(sink));
+        self
+    }
+
+    /// Start recording [`Diagnostics`](struct.Diagnostics.html): a transcript of the parser's
+    /// events, the names of field headers and lines which look like a boundary but aren't,
+    /// which can be shared in a bug report instead of the request body.
+    ///
+    /// Only the last `max_events` events are kept. Without this, nothing is recorded.
+    ///
+    /// ```rust
+    /// # use multipart::server::Multipart;
+    /// let body = b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n--boundray--";
+    ///
+    /// let mut multipart = Multipart::with_body(&body[..], "boundary");
+    /// multipart.strict_crlf(true).record_diagnostics(256);
+    ///
+    /// if multipart.foreach_entry(|_| ()).is_err() {
+    ///     // paste this into the bug report
+    ///     println!("{}", multipart.diagnostics().unwrap());
+    /// }
+    /// # assert!(multipart.diagnostics().unwrap().to_string().contains("near-miss 5/8"));
+    /// ```
+    pub fn record_diagnostics(&mut self, max_events: usize) -> &mut Self {
+        self.reader.record_diagnostics(max_events);
+        self
+    }
+
+    /// The `Diagnostics` recorded since `record_diagnostics()` was called, if it was.
+    pub fn diagnostics(&self) -> Option<&Diagnostics> {
+        self.reader.diagnostics()
+    }
+
+    /// Whether the request body ended with the closing boundary (`--boundary--`).
+    ///
+    /// This is `false` until the end of the request is reached. Unless `strict_crlf()` is set,
+    /// a body which ends after a field's trailing CRLF without the closing boundary is not
+    /// an error, but that often means the upload was truncated; check this once `read_entry()`
+    /// returns `None`.
+    pub fn finished_cleanly(&self) -> bool {
+        self.reader.finished_cleanly()
+    }
+
+    /// The number of bytes of field data read from the request so far.
+    ///
+    /// This includes the data of fields which were skipped or not read to the end,
+    /// which is discarded when the next field is read.
+    pub fn payload_bytes(&self) -> u64 {
+        self.reader.payload_bytes()
+    }
+
+    /// The number of bytes read from the request so far which were not field data:
+    /// the preamble, boundary lines and field headers.
+    ///
+    /// `payload_bytes() + overhead_bytes()` is the total number of bytes consumed from the body.
+    /// Anything following the closing boundary (the epilogue) is not read.
+    pub fn overhead_bytes(&self) -> u64 {
+        self.reader.overhead_bytes()
+    }
+
+    /// The number of parts with no headers and no data which were skipped so far.
+    ///
+    /// See `ParseLimits::max_empty_parts`.
+    pub fn empty_parts(&self) -> u32 {
+        self.reader.empty_parts()
     }
 
     /// Read the next entry from this multipart request, returning a struct with the field's name and
@@ -153,6 +450,117 @@ impl<R: Read> Multipart<R> {
     pub fn save(&mut self) -> SaveBuilder<&mut Self> {
         SaveBuilder::new(self)
     }
+
+    /// Get a builder for saving this request with the settings of `config`.
+    ///
+    /// ```rust
+    /// # use multipart::server::{Multipart, SaveBuilderConfig};
+    /// # let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"foo\"\r\n\r\nbar\r\n--boundary--\r\n"[..];
+    /// // configured once, e.g. at startup
+    /// let config = SaveBuilderConfig::config().size_limit(1024 * 1024).max_parts(16);
+    ///
+    /// let mut multipart = Multipart::with_body(body, "boundary");
+    /// let entries = multipart.save_with(&config).temp().into_result_strict().unwrap();
+    /// assert_eq!(entries.fields["foo"][0].as_text(), Some("bar"));
+    /// ```
+    pub fn save_with(&mut self, config: &SaveBuilderConfig) -> SaveBuilder<&mut Self> {
+        config.bind(self)
+    }
+}
+
+/// Checkpointing and two-pass reading for bodies which can be read again, e.g. requests
+/// spooled to disk.
+impl<R: Read + Seek> Multipart<R> {
+    /// Pass the raw bytes of the request to `verify` before anything is parsed or saved,
+    /// e.g. to compute an HMAC over the whole body, then rewind it for the second pass,
+    /// which saves it with the settings of `then`.
+    ///
+    /// The first pass covers everything not read yet, i.e. the whole body for a new `Multipart`,
+    /// including the preamble and epilogue. The request is only parsed once `SecondPass::save()`
+    /// is called; to reject it instead, drop the `SecondPass`.
+    ///
+    /// ```rust
+    /// # use multipart::server::{Multipart, SaveBuilderConfig};
+    /// # use std::io::Cursor;
+    /// # let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"foo\"\r\n\r\nbar\r\n--boundary--\r\n"[..];
+    /// let config = SaveBuilderConfig::config().size_limit(1024 * 1024);
+    ///
+    /// // a stand-in for a real MAC
+    /// let mut sum = 0u64;
+    /// let second_pass = Multipart::with_body(Cursor::new(body), "boundary")
+    ///     .two_pass(|buf| sum += buf.iter().map(|&b| b as u64).sum::<u64>(), &config)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(second_pass.raw_len(), body.len() as u64);
+    ///
+    /// if sum == body.iter().map(|&b| b as u64).sum::<u64>() {
+    ///     let entries = second_pass.save().temp().into_result_strict().unwrap();
+    ///     assert_eq!(entries.fields["foo"][0].as_text(), Some("bar"));
+    /// }
+    /// ```
+    ///
+    /// ### Errors
+    /// If the source can't actually seek (e.g. a pipe), the error wraps a `NotSeekableError`;
+    /// otherwise, it's the error from reading the source.
+    /// `capture_raw()` can spool a copy of such a request to check it after it's saved instead.
+    pub fn two_pass<V: FnMut(&[u8])>(mut self, verify: V, then: &SaveBuilderConfig) -> io::Result<SecondPass<R>> {
+        // a pipe or socket fails here, before anything is read
+        self.reader.position().map_err(|cause| io::Error::new(cause.kind(), NotSeekableError { cause }))?;
+
+        let raw_len = self.reader.read_ahead_raw(verify)?;
+
+        Ok(SecondPass {
+            multipart: self,
+            config: then.bind(()),
+            raw_len,
+        })
+    }
+
+    /// Get a `Checkpoint` from which the request can be read again with `resume()`.
+    ///
+    /// Checkpoints can only be taken at field boundaries: the checkpoint is at the start of the
+    /// current field, so resuming reads the current field again from its headers, even if some
+    /// of it has already been read. If the current field has been saved, read the next
+    /// field before taking a checkpoint.
+    ///
+    /// Note that `SaveBuilder::with_entries()` reads the headers of the next field before
+    /// checking `max_parts`, so after it returns `PartialReason::CountLimit` the checkpoint is
+    /// at the start of the first field which was not saved.
+    pub fn checkpoint(&mut self) -> io::Result<Checkpoint> {
+        let (payload_bytes, overhead_bytes) = self.reader.part_start();
+
+        // how far we are past the start of the current field
+        let read_since = self.reader.payload_bytes() + self.reader.overhead_bytes()
+            - payload_bytes - overhead_bytes;
+
+        Ok(Checkpoint {
+            offset: self.reader.position()? - read_since,
+            fields_read: self.part_index,
+            payload_bytes,
+            overhead_bytes,
+            in_body: self.reader.in_body(),
+            boundary: String::from_utf8_lossy(self.reader.boundary()).into_owned(),
+        })
+    }
+
+    /// Continue reading a request from the given checkpoint.
+    ///
+    /// `source` must be the same body that the checkpoint was taken from;
+    /// it is seeked to the position in the checkpoint.
+    ///
+    /// Fields which were completed before the checkpoint are not read again. To continue
+    /// saving the request, pass the `Entries` already saved to `SaveBuilder::with_entries()`.
+    pub fn resume(mut source: R, checkpoint: &Checkpoint) -> io::Result<Self> {
+        source.seek(SeekFrom::Start(checkpoint.offset))?;
+
+        let mut multipart = Multipart::with_body(source, &*checkpoint.boundary);
+        multipart.reader.resume(checkpoint.payload_bytes, checkpoint.overhead_bytes,
+                                checkpoint.in_body);
+        multipart.fields_read = checkpoint.fields_read;
+        multipart.part_index = checkpoint.fields_read;
+
+        Ok(multipart)
+    }
 }
 
 impl<R> Borrow<R> for Multipart<R> {
@@ -176,7 +584,312 @@ impl<R: Read> PrivReadEntry for Multipart<R> {
     /// Returns `true` if a field should follow this boundary, `false` otherwise.
     fn consume_boundary(&mut self) -> io::Result<bool> {
         debug!("Consume boundary!");
-        self.reader.consume_boundary()
+        let res = self.reader.consume_boundary();
+        self.part_index = self.fields_read;
+        self.reader.set_field_index(self.fields_read);
+        res
+    }
+
+    fn unnamed_policy(&self) -> UnnamedPolicy {
+        self.unnamed_policy
+    }
+
+    fn is_strict(&self) -> bool {
+        self.reader.strict_crlf()
+    }
+
+    fn max_name_len(&self) -> usize {
+        self.max_name_len
+    }
+
+    fn max_header_bytes(&self) -> usize {
+        self.max_header_bytes
+    }
+
+    fn percent_decode_names(&self) -> bool {
+        self.percent_decode_names
+    }
+
+    fn finished_cleanly(&self) -> bool {
+        self.reader.finished_cleanly()
+    }
+
+    fn skip_empty_part(&mut self) -> io::Result<bool> {
+        self.reader.skip_empty_part()
+    }
+
+    fn empty_parts(&self) -> u32 {
+        self.reader.empty_parts()
+    }
+
+    fn captures_raw(&self) -> bool {
+        self.reader.has_raw_spool()
+    }
+
+    fn count_field(&mut self) -> u32 {
+        let idx = self.fields_read;
+        self.fields_read = self.fields_read.saturating_add(1);
+        idx
+    }
+
+    fn end_headers(&mut self) {
+        self.reader.end_headers()
+    }
+
+    fn record_error(&mut self, err: &io::Error) {
+        self.reader.record_error(err)
+    }
+
+    fn payload_bytes(&self) -> u64 {
+        self.reader.payload_bytes()
+    }
+
+    fn overhead_bytes(&self) -> u64 {
+        self.reader.overhead_bytes()
+    }
+
+    fn raw_offset(&self) -> Option<u64> {
+        Some(self.reader.offset())
+    }
+}
+
+/// The default for [`ParseLimits::max_drain_bytes`](struct.ParseLimits.html#structfield.max_drain_bytes),
+/// in bytes.
+pub const DEFAULT_MAX_DRAIN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// The default for [`ParseLimits::max_name_len`](struct.ParseLimits.html#structfield.max_name_len),
+/// in bytes.
+pub const DEFAULT_MAX_NAME_LEN: usize = 256;
+
+/// The default for [`ParseLimits::max_header_bytes`](struct.ParseLimits.html#structfield.max_header_bytes),
+/// in bytes.
+pub const DEFAULT_MAX_HEADER_BYTES: usize = 8 * 1024;
+
+/// The default for [`ParseLimits::max_empty_reads`](struct.ParseLimits.html#structfield.max_empty_reads).
+pub const DEFAULT_MAX_EMPTY_READS: u32 = 16;
+
+/// Limits applied while reading a request, independent of how its fields are read or saved.
+///
+/// Set with `Multipart::limits()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// The maximum number of bytes which will be discarded to find the next boundary.
+    ///
+    /// Any data of a field which hasn't been read when the next field is requested
+    /// (including a field skipped by `UnnamedPolicy::Skip`) has to be read and discarded,
+    /// as does the preamble before the first boundary. If this would exceed the limit, an error which can be downcast to
+    /// [`DrainLimitExceeded`](struct.DrainLimitExceeded.html) is returned instead and the
+    /// request cannot be read any further.
+    ///
+    /// Default value: [`DEFAULT_MAX_DRAIN_BYTES`](constant.DEFAULT_MAX_DRAIN_BYTES.html)
+    pub max_drain_bytes: u64,
+    /// The maximum number of parts with no headers and no data which will be skipped.
+    ///
+    /// These are recognized from the boundaries alone, without parsing or allocating any headers,
+    /// and aren't returned as fields. Once there are more, an error which can be downcast to
+    /// [`EmptyPartError`](struct.EmptyPartError.html) is returned instead.
+    /// In strict mode (see `Multipart::strict_crlf()`) any empty part is an error.
+    ///
+    /// Default value: `u32::MAX` (no limit)
+    pub max_empty_parts: u32,
+    /// The maximum length in bytes of the `name` and `filename` parameters of a field.
+    ///
+    /// A longer name or filename is truncated and `FieldHeaders::name_truncated` is set.
+    /// In strict mode (see `Multipart::strict_crlf()`) it is an error instead.
+    ///
+    /// Default value: [`DEFAULT_MAX_NAME_LEN`](constant.DEFAULT_MAX_NAME_LEN.html)
+    pub max_name_len: usize,
+    /// The maximum length in bytes of the header block of a part, and so of any one header line.
+    ///
+    /// No more than this (plus one read) is buffered while looking for the end of a header line,
+    /// so a line which never ends is an error instead of growing the buffer without bound.
+    /// Headers which are too long are an error in both lenient and strict mode.
+    ///
+    /// Default value: [`DEFAULT_MAX_HEADER_BYTES`](constant.DEFAULT_MAX_HEADER_BYTES.html)
+    pub max_header_bytes: usize,
+    /// The number of zero-length reads in a row from the body which are retried before
+    /// taking it to have ended.
+    ///
+    /// A read of zero bytes is the end of the body, as for `std::io::Read`, but some body
+    /// adapters return `Ok(0)` when they have no data *yet*, which would otherwise cut the
+    /// body short. Retrying only costs extra reads at the real end of the body.
+    ///
+    /// A source which has returned data after a zero-length read can't signal the end that way,
+    /// so if it doesn't return any more data within the retries and the rest of the body isn't
+    /// buffered, an error which can be downcast to [`NoProgressError`](struct.NoProgressError.html)
+    /// is returned instead, in lenient as well as strict mode. Errors like `WouldBlock` from
+    /// the source are returned as they are and don't count toward this.
+    ///
+    /// `0` takes every zero-length read as the end.
+    ///
+    /// Default value: [`DEFAULT_MAX_EMPTY_READS`](constant.DEFAULT_MAX_EMPTY_READS.html)
+    pub max_empty_reads: u32,
+}
+
+impl ParseLimits {
+    /// Set `max_drain_bytes`, or remove the limit with `None`.
+    pub fn max_drain_bytes<L: Into<Option<u64>>>(self, limit: L) -> Self {
+        ParseLimits { max_drain_bytes: limit.into().unwrap_or(u64::MAX), .. self }
+    }
+
+    /// Set `max_empty_parts`, or remove the limit with `None`.
+    pub fn max_empty_parts<L: Into<Option<u32>>>(self, limit: L) -> Self {
+        ParseLimits { max_empty_parts: limit.into().unwrap_or(u32::MAX), .. self }
+    }
+
+    /// Set `max_name_len`, or remove the limit with `None`.
+    pub fn max_name_len<L: Into<Option<usize>>>(self, limit: L) -> Self {
+        ParseLimits { max_name_len: limit.into().unwrap_or(usize::MAX), .. self }
+    }
+
+    /// Set `max_header_bytes`.
+    ///
+    /// Unlike the other limits this can't be removed, as it bounds the buffer.
+    pub fn max_header_bytes(self, limit: usize) -> Self {
+        ParseLimits { max_header_bytes: limit, .. self }
+    }
+
+    /// Set `max_empty_reads`.
+    pub fn max_empty_reads(self, limit: u32) -> Self {
+        ParseLimits { max_empty_reads: limit, .. self }
+    }
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_drain_bytes: DEFAULT_MAX_DRAIN_BYTES,
+            max_empty_parts: u32::MAX,
+            max_name_len: DEFAULT_MAX_NAME_LEN,
+            max_header_bytes: DEFAULT_MAX_HEADER_BYTES,
+            max_empty_reads: DEFAULT_MAX_EMPTY_READS,
+        }
+    }
+}
+
+/// The position of a `Multipart` at the start of a field, to continue reading from later.
+///
+/// Returned by `Multipart::checkpoint()` and passed to `Multipart::resume()`.
+///
+/// Use the `Display` and `FromStr` impls to store a checkpoint, e.g. alongside a spooled request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    offset: u64,
+    fields_read: u32,
+    payload_bytes: u64,
+    overhead_bytes: u64,
+    in_body: bool,
+    boundary: String,
+}
+
+impl Checkpoint {
+    /// The position in the source which reading will continue from.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The number of fields in the request before the checkpoint.
+    pub fn fields_read(&self) -> u32 {
+        self.fields_read
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the boundary goes last as it may contain spaces
+        write!(f, "{} {} {} {} {} {}", self.offset, self.fields_read, self.payload_bytes,
+               self.overhead_bytes, self.in_body as u8, self.boundary)
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = ParseCheckpointError;
+
+    fn from_str(s: &str) -> Result<Self, ParseCheckpointError> {
+        let mut parts = s.splitn(6, ' ');
+
+        macro_rules! next_part (
+            () => (
+                parts.next().and_then(|part| part.parse().ok()).ok_or(ParseCheckpointError)?
+            )
+        );
+
+        let offset = next_part!();
+        let fields_read = next_part!();
+        let payload_bytes = next_part!();
+        let overhead_bytes = next_part!();
+        let in_body: u8 = next_part!();
+
+        Ok(Checkpoint {
+            offset,
+            fields_read,
+            payload_bytes,
+            overhead_bytes,
+            in_body: in_body != 0,
+            boundary: parts.next().filter(|b| !b.is_empty()).ok_or(ParseCheckpointError)?.into(),
+        })
+    }
+}
+
+/// The error returned when parsing a `Checkpoint` from a string which was not produced by its
+/// `Display` impl.
+#[derive(Debug)]
+pub struct ParseCheckpointError;
+
+impl fmt::Display for ParseCheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid multipart checkpoint")
+    }
+}
+
+impl Error for ParseCheckpointError {}
+
+/// The rewound request returned by `Multipart::two_pass()`, which can only be saved.
+pub struct SecondPass<R> {
+    multipart: Multipart<R>,
+    config: SaveBuilderConfig,
+    raw_len: u64,
+}
+
+impl<R: Read> SecondPass<R> {
+    /// The number of bytes passed to the verifier in the first pass.
+    pub fn raw_len(&self) -> u64 {
+        self.raw_len
+    }
+
+    /// Get a builder for saving the request with the settings passed to `two_pass()`.
+    pub fn save(self) -> SaveBuilder<Multipart<R>> {
+        self.config.bind(self.multipart)
+    }
+}
+
+/// The error returned (wrapped in `io::Error`) by `Multipart::two_pass()` when the body couldn't
+/// be read twice.
+///
+/// To check a request which can only be read once, use `Multipart::capture_raw()` to spool a copy
+/// of it while saving, and check that before using the saved fields.
+#[derive(Debug)]
+pub struct NotSeekableError {
+    cause: io::Error,
+}
+
+impl NotSeekableError {
+    /// The error from seeking or reading the source.
+    pub fn cause(&self) -> &io::Error {
+        &self.cause
+    }
+}
+
+impl fmt::Display for NotSeekableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the request body can't be read twice ({}); \
+                   use `Multipart::capture_raw()` to spool a copy instead", self.cause)
+    }
+}
+
+impl Error for NotSeekableError {
+    fn description(&self) -> &str {
+        "request body not seekable"
     }
 }
 
@@ -194,6 +907,11 @@ pub trait HttpRequest {
     /// `Content-Type: multipart/form-data; boundary={boundary}`.
     fn multipart_boundary(&self) -> Option<&str>;
 
+    /// Get the whole value of the `Content-Type` header, for `Multipart::content_type()`.
+    ///
+    /// Defaults to `None`.
+    fn content_type(&self) -> Option<&str> { None }
+
     /// Return the request body for reading.
     fn body(self) -> Self::Body;
 }
@@ -213,9 +931,14 @@ fn issue_104() {
     Content-Disposition: form-data; name=\"field2\"; filename=\"example.txt\"\r\n\r\n\
     value2 ";
 
-    let request = Cursor::new(body);
+    let mut multipart = Multipart::with_body(Cursor::new(body), "boundary");
+    let mut fields = 0;
+    multipart.foreach_entry(|_field| fields += 1).unwrap();
+    assert_eq!(fields, 0);
+    assert!(!multipart.finished_cleanly());
 
-    let mut multipart = Multipart::with_body(request, "boundary");
+    let mut multipart = Multipart::with_body(Cursor::new(body), "boundary");
+    multipart.strict_crlf(true);
     multipart.foreach_entry(|_field| {/* Do nothing */}).unwrap_err();
 }
 
@@ -288,3 +1011,888 @@ fn issue_114() {
     }*/)
     .expect("Unable to iterate multipart?")
 }
+
+#[test]
+fn boundary_and_content_type() {
+    ::init_log();
+
+    struct Request(&'static str);
+
+    impl HttpRequest for Request {
+        type Body = &'static [u8];
+
+        fn multipart_boundary(&self) -> Option<&str> { form_data_boundary(self.0) }
+
+        fn content_type(&self) -> Option<&str> { Some(self.0) }
+
+        fn body(self) -> &'static [u8] { b"" }
+    }
+
+    let multipart = Multipart::with_body(&b""[..], "boundary");
+    assert_eq!(multipart.boundary(), "boundary");
+    assert_eq!(multipart.content_type(), None);
+
+    let content_type = "multipart/form-data; boundary=\"quoted boundary\"";
+
+    let multipart = Multipart::from_content_type(&b""[..], content_type).unwrap();
+    assert_eq!(multipart.boundary(), "quoted boundary");
+    let mime = multipart.content_type().unwrap();
+    assert_eq!(mime.essence_str(), "multipart/form-data");
+    assert_eq!(mime.get_param("boundary").unwrap(), "quoted boundary");
+
+    let request = Request(content_type);
+    let multipart = Multipart::from_request(request).unwrap_or_else(|_| panic!("not multipart"));
+    assert_eq!(multipart.boundary(), "quoted boundary");
+    assert_eq!(multipart.content_type().unwrap().essence_str(), "multipart/form-data");
+
+    // `HttpRequest` impls which don't provide the header still get the boundary
+    let request = ::mock::ServerRequest::new(b"", "boundary");
+    let multipart = Multipart::from_request(request).unwrap_or_else(|_| panic!("not multipart"));
+    assert_eq!(multipart.boundary(), "boundary");
+    assert_eq!(multipart.content_type(), None);
+}
+
+#[cfg(test)]
+const UNNAMED_BODY: &str = "\
+    --boundary\r\n\
+    Content-Disposition: form-data; name=\"field1\"\r\n\r\n\
+    value1\r\n\
+    --boundary\r\n\
+    Content-Disposition: form-data\r\n\r\n\
+    unnamed1\r\n\
+    --boundary\r\n\
+    Content-Disposition: form-data; name=\"\"; filename=\"file.txt\"\r\n\r\n\
+    unnamed2\r\n\
+    --boundary\r\n\
+    Content-Disposition: form-data; name=\"field2\"\r\n\r\n\
+    value2\r\n\
+    --boundary--\r\n";
+
+#[cfg(test)]
+fn read_unnamed(policy: UnnamedPolicy) -> io::Result<Vec<(String, Option<String>, String)>> {
+    let mut multipart = Multipart::with_body(UNNAMED_BODY.as_bytes(), "boundary");
+    multipart.on_unnamed(policy);
+
+    let mut fields = Vec::new();
+
+    while let Some(mut field) = multipart.read_entry_mut().into_result()? {
+        let mut data = String::new();
+        field.data.read_to_string(&mut data)?;
+        fields.push((field.headers.name.to_string(),
+                     field.headers.declared_name.map(|n| n.to_string()),
+                     data));
+    }
+
+    Ok(fields)
+}
+
+#[test]
+fn unnamed_synthesize() {
+    ::init_log();
+
+    let fields = read_unnamed(UnnamedPolicy::Synthesize).unwrap();
+
+    assert_eq!(fields, vec![
+        ("field1".to_string(), Some("field1".to_string()), "value1".to_string()),
+        ("_unnamed_1".to_string(), None, "unnamed1".to_string()),
+        ("_unnamed_2".to_string(), None, "unnamed2".to_string()),
+        ("field2".to_string(), Some("field2".to_string()), "value2".to_string()),
+    ]);
+}
+
+#[test]
+fn unnamed_skip() {
+    ::init_log();
+
+    let fields = read_unnamed(UnnamedPolicy::Skip).unwrap();
+
+    assert_eq!(fields, vec![
+        ("field1".to_string(), Some("field1".to_string()), "value1".to_string()),
+        ("field2".to_string(), Some("field2".to_string()), "value2".to_string()),
+    ]);
+}
+
+#[test]
+fn unnamed_reject() {
+    ::init_log();
+
+    let err = read_unnamed(UnnamedPolicy::Reject).unwrap_err();
+    let err = err.get_ref().and_then(|e| e.downcast_ref::<UnnamedFieldError>())
+        .expect("expected `UnnamedFieldError`");
+
+    assert_eq!(err.index, 1);
+}
+
+#[cfg(test)]
+fn checkpoint_body() -> Vec<u8> {
+    let mut body = b"preamble\r\n".to_vec();
+
+    body.extend_from_slice(b"--boundary\r\n\
+                             Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                             value1\r\n\
+                             --boundary\r\n\
+                             Content-Disposition: form-data\r\n\r\n\
+                             unnamed\r\n\
+                             --boundary\r\n\
+                             Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+                             Content-Type: application/octet-stream\r\n\r\n");
+    // larger than the buffer so a checkpoint is taken with data left unread in the source
+    body.extend((0 .. 3000).map(|i| (i % 256) as u8));
+    body.extend_from_slice(b"\r\n\
+                             --boundary\r\n\
+                             Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                             value2\r\n\
+                             --boundary--\r\n");
+    body
+}
+
+#[cfg(test)]
+fn entries_contents(entries: &save::Entries) -> Vec<(String, Option<String>, Vec<u8>)> {
+    let mut contents = Vec::new();
+
+    for (name, fields) in &entries.fields {
+        for field in fields {
+            let mut data = Vec::new();
+            field.data.readable().unwrap().read_to_end(&mut data).unwrap();
+            contents.push((name.to_string(), field.headers.filename.clone(), data));
+        }
+    }
+
+    contents.sort();
+    contents
+}
+
+#[test]
+fn checkpoint_resume() {
+    ::init_log();
+
+    use self::save::PartialReason;
+    use std::io::Cursor;
+
+    let body = checkpoint_body();
+
+    let expected = Multipart::with_body(Cursor::new(&body[..]), "boundary").save().temp()
+        .into_result_strict().unwrap();
+
+    assert_eq!(expected.fields_count(), 4);
+
+    for saved in 0 ..= 4 {
+        let mut multipart = Multipart::with_body(Cursor::new(&body[..]), "boundary");
+
+        let entries = match multipart.save().max_parts(saved).temp() {
+            SaveResult::Partial(partial, PartialReason::CountLimit { .. }) => partial.keep_partial(),
+            other => panic!("expected count limit, got {:?}", other.into_opt_both()),
+        };
+
+        assert_eq!(entries.fields_count(), saved);
+
+        // the checkpoint survives being stored
+        let checkpoint: Checkpoint = multipart.checkpoint().unwrap().to_string().parse().unwrap();
+        assert_eq!(checkpoint.fields_read(), saved);
+        drop(multipart);
+
+        let mut resumed = Multipart::resume(Cursor::new(&body[..]), &checkpoint).unwrap();
+        let entries = resumed.save().with_entries(entries).into_result_strict().unwrap();
+
+        assert_eq!(entries_contents(&entries), entries_contents(&expected));
+        assert_eq!(entries.stats.payload_bytes, expected.stats.payload_bytes);
+        assert_eq!(entries.stats.overhead_bytes, expected.stats.overhead_bytes);
+    }
+}
+
+/// HMAC-SHA-256 of the concatenation of `chunks`, as a verifier might compute it.
+#[cfg(all(test, feature = "digest"))]
+struct HmacSha256 {
+    inner: digest::Hasher,
+    outer_key: Vec<u8>,
+}
+
+#[cfg(all(test, feature = "digest"))]
+impl HmacSha256 {
+    fn new(key: &[u8]) -> Self {
+        use self::digest::{Algorithm, Hasher};
+
+        let mut padded = key.to_vec();
+        padded.resize(64, 0);
+
+        let mut inner = Hasher::new(Algorithm::Sha256);
+        inner.update(&padded.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+
+        HmacSha256 { inner, outer_key: padded.iter().map(|b| b ^ 0x5c).collect() }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut outer = digest::Hasher::new(digest::Algorithm::Sha256);
+        outer.update(&self.outer_key);
+        outer.update(&self.inner.finish());
+        outer.finish()
+    }
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn two_pass_hmac() {
+    ::init_log();
+
+    use std::io::Cursor;
+
+    let body = checkpoint_body();
+
+    let mut expected_mac = HmacSha256::new(b"secret");
+    expected_mac.inner.update(&body);
+    let expected_mac = expected_mac.finish();
+
+    let expected = Multipart::with_body(Cursor::new(&body[..]), "boundary").save().temp()
+        .into_result_strict().unwrap();
+
+    let mut mac = HmacSha256::new(b"secret");
+    let second_pass = Multipart::with_body(Cursor::new(&body[..]), "boundary")
+        .two_pass(|buf| mac.inner.update(buf), &SaveBuilderConfig::config().memory_threshold(0))
+        .unwrap();
+
+    assert_eq!(second_pass.raw_len(), body.len() as u64);
+    assert_eq!(mac.finish(), expected_mac);
+
+    let entries = second_pass.save().temp().into_result_strict().unwrap();
+    assert_eq!(entries_contents(&entries), entries_contents(&expected));
+    assert_eq!(entries.stats.payload_bytes, expected.stats.payload_bytes);
+
+    // after a field was read, only the rest is verified
+    let mut multipart = Multipart::with_body(Cursor::new(&body[..]), "boundary");
+    multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+    let mut rest = Vec::new();
+    let second_pass = multipart.two_pass(|buf| rest.extend_from_slice(buf), &SaveBuilderConfig::config()).unwrap();
+    assert!(body.ends_with(&rest) && rest.len() < body.len());
+    assert_eq!(second_pass.save().temp().into_result_strict().unwrap().fields_count(), 3);
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn two_pass_rejected() {
+    ::init_log();
+
+    use std::fs;
+    use std::io::Cursor;
+
+    let dir = ::tempfile::tempdir().unwrap();
+    let config = SaveBuilderConfig::config().memory_threshold(0).temp_parent(dir.path());
+
+    let mut body = checkpoint_body();
+    let mut expected_mac = HmacSha256::new(b"secret");
+    expected_mac.inner.update(&body);
+    let expected_mac = expected_mac.finish();
+
+    // tampered with in transit
+    let len = body.len();
+    body[len - 30] ^= 1;
+
+    let mut mac = HmacSha256::new(b"secret");
+    let second_pass = Multipart::with_body(Cursor::new(&body[..]), "boundary")
+        .two_pass(|buf| mac.inner.update(buf), &config)
+        .unwrap();
+
+    assert_ne!(mac.finish(), expected_mac);
+    drop(second_pass);
+
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn two_pass_not_seekable() {
+    ::init_log();
+
+    /// A pipe: readable, but fails to seek.
+    struct Pipe<'a>(&'a [u8]);
+
+    impl<'a> Read for Pipe<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl<'a> Seek for Pipe<'a> {
+        fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+            Err(io::Error::other("illegal seek"))
+        }
+    }
+
+    let body = checkpoint_body();
+    let mut verified = 0;
+
+    let err = match Multipart::with_body(Pipe(&body), "boundary")
+        .two_pass(|buf| verified += buf.len(), &SaveBuilderConfig::config()) {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => err,
+    };
+
+    let err = err.get_ref().and_then(|e| e.downcast_ref::<NotSeekableError>())
+        .expect("expected `NotSeekableError`");
+    assert_eq!(err.cause().to_string(), "illegal seek");
+    assert!(err.to_string().contains("capture_raw()"));
+    assert_eq!(verified, 0);
+}
+
+/// A body with a `signature` field holding the hex HMAC-SHA-256 of the raw parts before it,
+/// headers included, as a client signing its request might send it.
+#[cfg(all(test, feature = "digest"))]
+fn signed_body(key: &[u8]) -> Vec<u8> {
+    let mut parts = vec![
+        b"Content-Disposition: form-data; name=\"amount\"\r\n\r\n100".to_vec(),
+        b"Content-Disposition: form-data; name=\"invoice\"; filename=\"invoice.bin\"\r\n\
+          Content-Type: application/octet-stream\r\n\r\n".to_vec(),
+    ];
+    // larger than the buffer, so the data is read in several chunks
+    parts[1].extend((0 .. 3000).map(|i| (i % 256) as u8));
+
+    let mut mac = HmacSha256::new(key);
+    for part in &parts {
+        mac.inner.update(part);
+    }
+    let signature: String = mac.finish().iter().map(|b| format!("{:02x}", b)).collect();
+    parts.push(format!("Content-Disposition: form-data; name=\"signature\"\r\n\r\n{}", signature).into_bytes());
+
+    let mut body = b"preamble\r\n".to_vec();
+    for part in &parts {
+        body.extend_from_slice(b"--boundary\r\n");
+        body.extend_from_slice(part);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"--boundary--\r\n");
+    body
+}
+
+/// Check the `signature` field of `entries` against the raw parts before it in `source`.
+#[cfg(all(test, feature = "digest"))]
+fn verify_signature<R: Read>(entries: &save::Entries, source: R, key: &[u8]) -> bool {
+    let ranges: Vec<_> = entries.raw_ranges().into_iter()
+        .take_while(|&(field, _)| &*field.headers.name != "signature")
+        .map(|(_, range)| range)
+        .collect();
+    assert_eq!(ranges.len(), 2);
+
+    let mut mac = HmacSha256::new(key);
+    let passed = read_raw_ranges(source, &ranges, true, |buf| mac.inner.update(buf)).unwrap();
+    assert_eq!(passed, ranges.iter().map(|range| range.len(true)).sum::<u64>());
+
+    let signature: String = mac.finish().iter().map(|b| format!("{:02x}", b)).collect();
+    entries.fields["signature"][0].as_text() == Some(&*signature)
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn raw_ranges_signature() {
+    ::init_log();
+
+    let body = signed_body(b"secret");
+    let entries = Multipart::with_body(&body[..], "boundary").save().collect_raw_ranges(true)
+        .in_memory().into_result_strict().unwrap();
+
+    let range = entries.fields["amount"][0].raw_range.unwrap();
+    assert_eq!(&body[range.headers_start as usize - 12 .. range.headers_start as usize], b"--boundary\r\n");
+    assert_eq!(&body[range.data_start as usize .. range.end as usize], b"100");
+
+    let range = entries.fields["invoice"][0].raw_range.unwrap();
+    assert_eq!(range.len(false), 3000);
+    assert_eq!(&body[range.end as usize ..][.. 12], b"\r\n--boundary");
+
+    assert!(verify_signature(&entries, &body[..], b"secret"));
+    assert!(!verify_signature(&entries, &body[..], b"wrong key"));
+
+    // tampered with in transit: the amount, then a byte of the file
+    let amount = body.windows(3).position(|w| w == b"100").unwrap();
+    for &offset in &[amount, body.len() - 500] {
+        let mut tampered = body.clone();
+        tampered[offset] ^= 1;
+
+        let entries = Multipart::with_body(&tampered[..], "boundary").save().collect_raw_ranges(true)
+            .in_memory().into_result_strict().unwrap();
+        assert!(!verify_signature(&entries, &tampered[..], b"secret"));
+    }
+
+    // not collected by default
+    let entries = Multipart::with_body(&body[..], "boundary").save().in_memory().into_result_strict().unwrap();
+    assert!(entries.raw_ranges().is_empty());
+}
+
+#[cfg(feature = "digest")]
+#[test]
+fn raw_ranges_captured() {
+    ::init_log();
+
+    let body = signed_body(b"secret");
+    let dir = ::tempfile::tempdir().unwrap();
+
+    let mut multipart = Multipart::with_body(&body[..], "boundary");
+    multipart.capture_raw(&SaveBuilderConfig::config().memory_threshold(0).temp_parent(dir.path()));
+
+    let entries = multipart.save().collect_raw_ranges(true).in_memory().into_result_strict().unwrap();
+    let raw = multipart.raw_body().unwrap().unwrap();
+    assert!(raw.is_file());
+
+    assert!(verify_signature(&entries, raw.readable().unwrap(), b"secret"));
+}
+
+#[test]
+fn read_raw_ranges_errors() {
+    ::init_log();
+
+    use self::save::RawRange;
+
+    let body = b"0123456789";
+    let range = |start, end| RawRange { headers_start: start, data_start: start + 1, end };
+
+    let mut read = Vec::new();
+    assert_eq!(read_raw_ranges(&body[..], &[range(1, 3), range(5, 8)], false, |buf| read.extend_from_slice(buf)).unwrap(), 3);
+    assert_eq!(read, b"267");
+    assert_eq!(read_raw_ranges(&body[..], &[range(1, 3), range(5, 8)], true, |_| ()).unwrap(), 5);
+
+    let err = read_raw_ranges(&body[..], &[range(5, 8), range(1, 3)], true, |_| ()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let err = read_raw_ranges(&body[..], &[range(5, 12)], true, |_| ()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    let err = read_raw_ranges(&body[..], &[range(11, 12)], true, |_| ()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[cfg(test)]
+fn drain_body(data_len: usize) -> Vec<u8> {
+    let mut body = b"--boundary\r\n\
+                     Content-Disposition: form-data\r\n\r\n".to_vec();
+    body.resize(body.len() + data_len, b'x');
+    body.extend_from_slice(b"\r\n\
+                             --boundary\r\n\
+                             Content-Disposition: form-data; name=\"field\"\r\n\r\n\
+                             value\r\n\
+                             --boundary--\r\n");
+    body
+}
+
+#[test]
+fn drain_limit_skip() {
+    ::init_log();
+
+    let body = drain_body(4096);
+
+    let read_skipped = |limit: u64| {
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.on_unnamed(UnnamedPolicy::Skip)
+            .limits(ParseLimits::default().max_drain_bytes(limit));
+        multipart.read_entry_mut().into_result().map(|field| field.map(|field| field.headers.name.to_string()))
+    };
+
+    assert_eq!(read_skipped(4096).unwrap(), Some("field".to_string()));
+
+    let err = read_skipped(4095).unwrap_err();
+    let err = err.get_ref().and_then(|e| e.downcast_ref::<DrainLimitExceeded>())
+        .expect("expected `DrainLimitExceeded`");
+    assert_eq!(err.limit, 4095);
+}
+
+#[test]
+fn drain_limit_unread() {
+    ::init_log();
+
+    let body = drain_body(4096);
+
+    let mut multipart = Multipart::with_body(&body[..], "boundary");
+    multipart.limits(ParseLimits::default().max_drain_bytes(1024));
+
+    {
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+        // data which was read doesn't count against the limit
+        let mut buf = [0; 3072];
+        field.data.read_exact(&mut buf).unwrap();
+    }
+
+    // the rest of the field is just within the limit
+    assert_eq!(&*multipart.read_entry_mut().into_result().unwrap().unwrap().headers.name, "field");
+
+    let mut multipart = Multipart::with_body(&body[..], "boundary");
+    multipart.limits(ParseLimits::default().max_drain_bytes(1024));
+    multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+    // the parse can't continue after the limit is exceeded
+    for _ in 0 .. 2 {
+        let err = multipart.read_entry_mut().into_result().map(|_| ()).unwrap_err();
+        assert!(err.get_ref().is_some_and(|e| e.is::<DrainLimitExceeded>()), "{:?}", err);
+    }
+}
+
+#[test]
+fn no_progress() {
+    use mock::{Fault, FaultyReader};
+
+    ::init_log();
+
+    let body = b"--boundary\r\n\
+                 Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+                 1\r\n\
+                 --boundary\r\n\
+                 Content-Disposition: form-data; name=\"b\"\r\n\r\n\
+                 2222222222\r\n\
+                 --boundary--\r\n";
+
+    // stalls between every read
+    let script = [Fault::Short(7), Fault::Zero, Fault::Zero].repeat(body.len() / 7 + 1);
+    let entries = Multipart::with_body(FaultyReader::new(&body[..], script), "boundary").save()
+        .in_memory()
+        .into_result_strict()
+        .unwrap();
+    assert_eq!(entries.fields["b"][0].as_text(), Some("2222222222"));
+    assert!(entries.clean_termination);
+
+    // stalls, then never returns the rest
+    let body = &body[..body.len() - 30];
+    let script = vec![Fault::Short(7), Fault::Zero];
+    let mut multipart = Multipart::with_body(FaultyReader::new(body, script), "boundary");
+
+    assert_eq!(&*multipart.read_entry_mut().into_result().unwrap().unwrap().headers.name, "a");
+
+    let err = multipart.read_entry_mut().into_result().map(|_| ()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    let err = err.get_ref().and_then(|e| e.downcast_ref::<NoProgressError>())
+        .expect("expected `NoProgressError`");
+    assert_eq!(err.field, Some(1));
+    assert_eq!(err.offset, body.len() as u64);
+    assert_eq!(err.to_string(), format!("no progress after 17 zero-length reads at offset {} in field 1", body.len()));
+}
+
+/// `count` parts without headers or data around a field, and one more before the closing boundary.
+#[cfg(test)]
+fn empty_parts_body(count: usize) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    for _ in 0 .. count {
+        body.extend_from_slice(b"--boundary\r\n\r\n\r\n");
+    }
+
+    body.extend_from_slice(b"--boundary\r\n\
+                             Content-Disposition: form-data; name=\"field\"\r\n\r\n\
+                             value\r\n\
+                             --boundary\r\n\r\n\
+                             \r\n--boundary--\r\n");
+    body
+}
+
+#[test]
+fn empty_parts_skipped() {
+    ::init_log();
+
+    let body = empty_parts_body(100_000);
+    let start = ::std::time::Instant::now();
+
+    let entries = Multipart::with_body(&body[..], "boundary").save().temp()
+        .into_result_strict().unwrap();
+
+    assert_eq!(entries.fields_count(), 1);
+    assert_eq!(entries.fields["field"][0].as_text(), Some("value"));
+    assert_eq!(entries.stats.empty_parts, 100_001);
+    // all but the CRLF after the closing boundary
+    assert_eq!(entries.stats.payload_bytes + entries.stats.overhead_bytes, body.len() as u64 - 2);
+    assert!(entries.clean_termination);
+
+    // no worse than reading the same amount of data in a field
+    assert!(start.elapsed() < ::std::time::Duration::from_secs(5), "{:?}", start.elapsed());
+}
+
+#[test]
+fn empty_parts_limit() {
+    ::init_log();
+
+    let body = empty_parts_body(10);
+
+    let read_all = |limits: ParseLimits, strict: bool| {
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.limits(limits).strict_crlf(strict);
+
+        let mut names = Vec::new();
+        let res = multipart.foreach_entry(|field| names.push(field.headers.name.to_string()));
+        (res, names, multipart.empty_parts())
+    };
+
+    let (res, names, empty_parts) = read_all(ParseLimits::default().max_empty_parts(11), false);
+    res.unwrap();
+    assert_eq!((names, empty_parts), (vec!["field".to_string()], 11));
+
+    // the empty part after the field is one too many
+    let (res, names, empty_parts) = read_all(ParseLimits::default().max_empty_parts(10), false);
+    let err = res.unwrap_err();
+    let err = err.get_ref().and_then(|e| e.downcast_ref::<EmptyPartError>())
+        .expect("expected `EmptyPartError`");
+    assert_eq!((err.count, err.limit), (11, Some(10)));
+    assert_eq!((names, empty_parts), (vec!["field".to_string()], 10));
+
+    // strict mode rejects the first one
+    let (res, names, _) = read_all(ParseLimits::default(), true);
+    let err = res.unwrap_err();
+    let err = err.get_ref().and_then(|e| e.downcast_ref::<EmptyPartError>())
+        .expect("expected `EmptyPartError`");
+    assert_eq!((err.count, err.limit), (1, None));
+    assert!(names.is_empty());
+
+    // a part with no headers but some data is still an error
+    let body = b"--boundary\r\n\r\ndata\r\n--boundary--\r\n";
+    assert!(Multipart::with_body(&body[..], "boundary").into_entry().into_result().is_err());
+}
+
+#[test]
+fn checkpoint_parse() {
+    let checkpoint = Checkpoint {
+        offset: 1024,
+        fields_read: 3,
+        payload_bytes: 900,
+        overhead_bytes: 124,
+        in_body: true,
+        boundary: "a boundary with spaces".into(),
+    };
+
+    assert_eq!(checkpoint.to_string().parse::<Checkpoint>().unwrap(), checkpoint);
+
+    assert!("".parse::<Checkpoint>().is_err());
+    assert!("1024 3 900 124 1".parse::<Checkpoint>().is_err());
+    assert!("1024 3 nine 124 1 boundary".parse::<Checkpoint>().is_err());
+}
+
+#[test]
+fn from_content_type() {
+    let body: &[u8] = b"--xyz\r\nContent-Disposition: form-data; name=\"field\"\r\n\r\nvalue\r\n--xyz--\r\n";
+
+    let mut multipart = Multipart::from_content_type(body, "Multipart/Form-Data ; Boundary=\"xyz\"")
+        .unwrap_or_else(|_| panic!("expected a multipart body"));
+    assert_eq!(&*multipart.read_entry_mut().into_result().unwrap().unwrap().headers.name, "field");
+
+    for &content_type in &["multipart/mixed; boundary=xyz", "multipart/form-data",
+                           "multipart/form-data; boundary=", "text/plain; boundary=xyz"] {
+        assert!(Multipart::from_content_type(body, content_type).is_err(), "{:?}", content_type);
+    }
+}
+
+#[test]
+fn finished_cleanly() {
+    ::init_log();
+
+    let fields = "--boundary\r\n\
+                  Content-Disposition: form-data; name=\"field1\"\r\n\r\n\
+                  value1\r\n\
+                  --boundary\r\n\
+                  Content-Disposition: form-data; name=\"field2\"\r\n\r\n\
+                  value2";
+
+    let bodies = [
+        (format!("{}\r\n--boundary--\r\n", fields), true),
+        (format!("{}\r\n--boundary--", fields), true),
+        // EOF right after the final field's CRLF
+        (format!("{}\r\n", fields), false),
+    ];
+
+    for &(ref body, clean) in &bodies {
+        let mut multipart = Multipart::with_body(body.as_bytes(), "boundary");
+        let mut values = Vec::new();
+
+        assert!(!multipart.finished_cleanly());
+
+        while let Some(mut field) = multipart.read_entry_mut().into_result().unwrap() {
+            let mut value = String::new();
+            field.data.read_to_string(&mut value).unwrap();
+            values.push(value);
+        }
+
+        assert_eq!(values, ["value1", "value2"], "{:?}", body);
+        assert_eq!(multipart.finished_cleanly(), clean, "{:?}", body);
+
+        if !clean {
+            // a trailing CRLF is consumed too
+            assert_eq!(multipart.payload_bytes() + multipart.overhead_bytes(), body.len() as u64);
+        }
+
+        let entries = Multipart::with_body(body.as_bytes(), "boundary").save().temp()
+            .into_result_strict().unwrap();
+        assert_eq!(entries.fields_count(), 2);
+        assert_eq!(entries.clean_termination, clean, "{:?}", body);
+
+        // strict mode requires the closing boundary
+        let mut multipart = Multipart::with_body(body.as_bytes(), "boundary");
+        multipart.strict_crlf(true);
+        assert_eq!(multipart.foreach_entry(|_| ()).is_ok(), clean, "{:?}", body);
+    }
+
+    // cut off within the last field, which isn't taken as complete
+    let mut multipart = Multipart::with_body(fields.as_bytes(), "boundary");
+    let err = multipart.foreach_entry(|mut field| { let _ = io::copy(&mut field.data, &mut io::sink()); })
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(!multipart.finished_cleanly());
+
+    match Multipart::with_body(fields.as_bytes(), "boundary").save().temp() {
+        SaveResult::Partial(partial, save::PartialReason::IoError(e)) => {
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+            // the error comes from looking for the next boundary, after the field was saved
+            assert_eq!(partial.entries.fields_count(), 2);
+            assert!(!partial.entries.clean_termination);
+        },
+        other => panic!("expected an IoError, got {:?}", other.into_opt_both()),
+    }
+}
+
+#[test]
+fn zero_parts() {
+    ::init_log();
+
+    let bodies = [
+        "--boundary--",
+        "--boundary--\r\n",
+        "preamble\r\n--boundary--\r\n",
+        "--boundary--\r\nepilogue",
+        "preamble\r\n--boundary--\r\nepilogue\r\n",
+    ];
+
+    for &body in &bodies {
+        for &strict in &[false, true] {
+            let mut multipart = Multipart::with_body(body.as_bytes(), "boundary");
+            multipart.strict_crlf(strict);
+            assert!(multipart.read_entry_mut().into_result().unwrap().is_none(), "{:?}", body);
+            assert!(multipart.finished_cleanly(), "{:?}", body);
+
+            let mut multipart = Multipart::with_body(body.as_bytes(), "boundary");
+            multipart.strict_crlf(strict);
+
+            let entries = match multipart.save().temp() {
+                save::SaveResult::Full(entries) => entries,
+                other => panic!("{:?}: expected `Full`, got {:?}", body, other.into_opt_both()),
+            };
+
+            assert!(entries.is_empty(), "{:?}", body);
+            assert_eq!(entries.part_count(), 0, "{:?}", body);
+            assert!(entries.clean_termination, "{:?}", body);
+        }
+    }
+}
+
+#[test]
+fn no_boundary() {
+    ::init_log();
+
+    for &body in &["----", "", "--other--\r\n"] {
+        // neither a part nor the closing boundary: no fields, but not a clean end
+        let entries = Multipart::with_body(body.as_bytes(), "boundary").save().temp()
+            .into_result_strict().unwrap();
+        assert!(entries.is_empty(), "{:?}", body);
+        assert!(!entries.clean_termination, "{:?}", body);
+
+        let mut multipart = Multipart::with_body(body.as_bytes(), "boundary");
+        multipart.strict_crlf(true);
+
+        let err = match multipart.read_entry_mut().into_result() {
+            Ok(_) => panic!("{:?}: expected an error", body),
+            Err(err) => err,
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        err.get_ref().and_then(|e| e.downcast_ref::<MissingBoundaryError>())
+            .expect("expected `MissingBoundaryError`");
+    }
+}
+
+/// The extents of each part in a body with the boundary `boundary`, found by searching for it:
+/// `(boundary line, headers, data, end of data)`.
+#[cfg(test)]
+fn part_extents(body: &[u8]) -> Vec<(u64, u64, u64, u64)> {
+    let delim = b"--boundary";
+
+    let mut starts: Vec<usize> = (0 .. body.len() - delim.len() + 1)
+        .filter(|&i| body[i..].starts_with(delim) && (i == 0 || body[..i].ends_with(b"\r\n")))
+        .collect();
+
+    // an unclosed body ends as if the closing boundary followed
+    if !body[*starts.last().unwrap()..].starts_with(b"--boundary--") {
+        starts.push(if body.ends_with(b"\r\n") { body.len() } else { body.len() + 2 });
+    }
+
+    starts.windows(2).map(|pair| {
+        let headers = pair[0] + delim.len() + 2;
+        let data = if body[headers..].starts_with(b"\r\n") {
+            headers + 2
+        } else {
+            headers + body[headers..].windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4
+        };
+
+        (pair[0] as u64, headers as u64, data as u64, pair[1] as u64 - 2)
+    }).collect()
+}
+
+#[test]
+fn parse_events() {
+    use std::sync::{Arc, Mutex};
+
+    ::init_log();
+
+    let mut large = b"--boundary\r\n\
+                      Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n".to_vec();
+    large.extend((0 .. 20_000).map(|i| (i % 251) as u8));
+    large.extend_from_slice(b"\r\n--boundary--");
+
+    let fixtures: Vec<(Vec<u8>, bool)> = vec![
+        (checkpoint_body(), true),
+        (large, true),
+        (b"--boundary\r\n\
+           Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+           \r\n\
+           --boundary\r\n\
+           \r\n\
+           \r\n\
+           --boundary\r\n\
+           Content-Disposition: form-data; name=\"b\"\r\n\
+           Content-Type: text/plain\r\n\r\n\
+           b\r\n\
+           --boundary--".to_vec(), true),
+        (b"--boundary\r\n\
+           Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+           unclosed\r\n".to_vec(), false),
+    ];
+
+    for (idx, &(ref body, clean)) in fixtures.iter().enumerate() {
+        // with every field read, and with every field discarded unread
+        for &read in &[true, false] {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let sink = events.clone();
+
+            let mut multipart = Multipart::with_body(&body[..], "boundary");
+            multipart.set_event_sink(move |event| sink.lock().unwrap().push(event));
+            multipart.foreach_entry(|mut field| if read {
+                field.data.read_to_end(&mut Vec::new()).unwrap();
+            }).unwrap();
+
+            let mut parts = Vec::new();
+            let mut current = None;
+            let mut body_end = None;
+
+            for event in events.lock().unwrap().iter() {
+                assert!(body_end.is_none(), "{}: {:?} after the end", idx, event);
+
+                match (*event, current) {
+                    (ParseEvent::PartStart { offset }, None) => current = Some((offset, 0, 0, 0)),
+                    (ParseEvent::HeadersDone { offset, len }, Some((start, 0, 0, 0))) =>
+                        current = Some((start, offset, offset + len, offset + len)),
+                    (ParseEvent::PartDataChunk { offset, len }, Some((start, headers, data, end))) => {
+                        assert_eq!(offset, end, "{}: chunks must be contiguous", idx);
+                        current = Some((start, headers, data, end + len));
+                    },
+                    (ParseEvent::PartEnd { offset }, Some(part)) => {
+                        assert_eq!(offset, part.3, "{}: data must end at `PartEnd`", idx);
+                        parts.push(part);
+                        current = None;
+                    },
+                    (ParseEvent::BodyEnd { offset, clean }, None) => body_end = Some((offset, clean)),
+                    (event, current) => panic!("{}: unexpected {:?} in part {:?}", idx, event, current),
+                }
+            }
+
+            assert_eq!(parts, part_extents(body), "{}", idx);
+            // the epilogue after the closing boundary isn't read
+            let end = body.windows(12).position(|w| w == b"--boundary--").map_or(body.len(), |pos| pos + 12);
+            assert_eq!(body_end, Some((end as u64, clean)), "{}", idx);
+        }
+    }
+}
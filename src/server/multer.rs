@@ -0,0 +1,345 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Conversions between [`multer`](https://github.com/rwf2/multer) and `Entries` with the
+//! `multer` feature (optional).
+//!
+//! [`save()`](fn.save.html) drains a `multer::Multipart` into `Entries` with a `SaveBuilder`,
+//! and [`to_multer()`](fn.to_multer.html) goes the other way, e.g. to feed saved requests
+//! to code (or tests) written against `multer`.
+//!
+//! `multer` is async; its futures are run to completion on the current thread with
+//! `futures_executor::block_on()`, so call these from a thread which may block, e.g. with
+//! `tokio::task::spawn_blocking()`.
+
+pub use multer::Multipart as MulterMultipart;
+
+use futures_executor::block_on;
+use futures_util::stream;
+
+use multer::bytes::Bytes;
+use multer::Field;
+
+use client::lazy;
+
+use super::field::FieldHeaders;
+use super::parts::{Part, PartsSource};
+use super::save::{Entries, EntriesSaveResult, PartialEntries, PartialReason, SaveBuilder};
+use super::save::SaveResult::*;
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read};
+use std::rc::Rc;
+
+/// The boundary of the body from `to_multer()` when there are no fields.
+const EMPTY_BOUNDARY: &str = "multipart-no-fields";
+
+/// The state shared between `MulterParts` and the reader of its current field.
+struct Shared {
+    field: Option<Field<'static>>,
+    error: Option<io::Error>,
+}
+
+/// The fields of a `multer::Multipart` as an iterator of parts for a `PartsSource`.
+///
+/// The headers of each field are parsed like those of a `Multipart`. If `multer` fails
+/// to read the next field, the iterator ends and [`take_error()`](#method.take_error)
+/// returns the error; [`save()`](fn.save.html) checks it for you.
+pub struct MulterParts {
+    multipart: MulterMultipart<'static>,
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl MulterParts {
+    /// Read the fields of `multipart`, in order.
+    pub fn new(multipart: MulterMultipart<'static>) -> Self {
+        MulterParts {
+            multipart,
+            shared: Rc::new(RefCell::new(Shared { field: None, error: None })),
+        }
+    }
+
+    /// Take the error which ended the fields early, if any.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.shared.borrow_mut().error.take()
+    }
+
+    /// Get a handle to check for the error which ended the fields early after `self`
+    /// has been moved into a `PartsSource`.
+    fn error_handle(&self) -> Rc<RefCell<Shared>> {
+        self.shared.clone()
+    }
+}
+
+impl Iterator for MulterParts {
+    type Item = Part;
+
+    fn next(&mut self) -> Option<Part> {
+        let mut shared = self.shared.borrow_mut();
+
+        // `multer` won't yield the next field while the current one is still alive
+        shared.field = None;
+
+        if shared.error.is_some() {
+            return None;
+        }
+
+        let field = match block_on(self.multipart.next_field()) {
+            Ok(Some(field)) => field,
+            Ok(None) => return None,
+            Err(e) => {
+                shared.error = Some(multer_error(e));
+                return None;
+            },
+        };
+
+        let headers = FieldHeaders::from_pairs(
+            field.headers().iter().map(|(name, value)| (name.as_str(), value.as_bytes()))
+        );
+
+        let headers = match headers {
+            Ok(headers) => headers,
+            Err(e) => {
+                shared.error = Some(e);
+                return None;
+            },
+        };
+
+        shared.field = Some(field);
+
+        let reader = FieldReader { shared: self.shared.clone(), chunk: Bytes::new(), pos: 0 };
+        Some((headers, Box::new(reader)))
+    }
+}
+
+/// Reads the data of the current field of `MulterParts` chunk by chunk.
+struct FieldReader {
+    shared: Rc<RefCell<Shared>>,
+    chunk: Bytes,
+    pos: usize,
+}
+
+impl Read for FieldReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = {
+            let available = self.fill_buf()?;
+            let read = available.len().min(buf.len());
+            buf[..read].copy_from_slice(&available[..read]);
+            read
+        };
+
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl BufRead for FieldReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.chunk.len() {
+            let mut shared = self.shared.borrow_mut();
+
+            // `None` once `MulterParts` has moved on to the next field
+            let field = match shared.field.as_mut() {
+                Some(field) => field,
+                None => break,
+            };
+
+            match block_on(field.chunk()) {
+                Ok(Some(chunk)) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                },
+                Ok(None) => {
+                    shared.field = None;
+                    break;
+                },
+                Err(e) => return Err(multer_error(e)),
+            }
+        }
+
+        Ok(&self.chunk[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.chunk.len());
+    }
+}
+
+fn multer_error(err: multer::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Save the fields of `multipart` with the `SaveBuilder` given to `save`, which picks the
+/// settings and the directory, e.g. `|builder| config.apply(builder).temp()`.
+///
+/// An error from `multer` between fields is returned as `PartialReason::IoError` with the
+/// fields saved until then, and `clean_termination` is `false` as for a truncated body.
+///
+/// ```rust,no_run
+/// # extern crate multipart;
+/// # extern crate multer;
+/// # use multipart::server::SaveBuilderConfig;
+/// # use multipart::server::multer::{save, MulterMultipart};
+/// # fn main() {
+/// # let multipart: MulterMultipart<'static> = unimplemented!();
+/// let config = SaveBuilderConfig::config().size_limit(16 * 1024 * 1024);
+///
+/// let entries = save(multipart, |builder| config.apply(builder).temp())
+///     .into_result_strict()
+///     .unwrap();
+/// # }
+/// ```
+pub fn save<F>(multipart: MulterMultipart<'static>, save: F) -> EntriesSaveResult<PartsSource<MulterParts>>
+where F: FnOnce(SaveBuilder<PartsSource<MulterParts>>) -> EntriesSaveResult<PartsSource<MulterParts>> {
+    let parts = MulterParts::new(multipart);
+    let errors = parts.error_handle();
+
+    let result = save(SaveBuilder::new(PartsSource::new(parts)));
+    let error = errors.borrow_mut().error.take();
+
+    match (result, error) {
+        (Full(mut entries), Some(e)) => {
+            entries.clean_termination = false;
+            Partial(PartialEntries { entries, partial: None }, PartialReason::IoError(e))
+        },
+        (Partial(mut partial, reason), Some(e)) => {
+            warn!("Error from multer after the save stopped: {}", e);
+            partial.entries.clean_termination = false;
+            Partial(partial, reason)
+        },
+        (result, _) => result,
+    }
+}
+
+/// Write `entries` out as a multipart body and read it back with `multer`, e.g. to pass
+/// saved requests to code written against `multer`.
+///
+/// Fields are written as by [`lazy::Multipart::add_entries()`]
+/// (../../client/lazy/struct.Multipart.html#method.add_entries), so a file without a
+/// content type comes back as `application/octet-stream`. The whole body is held in memory.
+pub fn to_multer(entries: &Entries) -> io::Result<MulterMultipart<'static>> {
+    let mut body = Vec::new();
+
+    let boundary = if entries.fields_count() == 0 {
+        body.extend_from_slice(format!("--{}--\r\n", EMPTY_BOUNDARY).as_bytes());
+        EMPTY_BOUNDARY.to_string()
+    } else {
+        let mut multipart = lazy::Multipart::new();
+        multipart.add_entries(entries)?;
+
+        let mut prepared = multipart.prepare().map_err(|e| e.error)?;
+        prepared.read_to_end(&mut body)?;
+        prepared.boundary().to_string()
+    };
+
+    let body = stream::iter(Some(Ok::<_, io::Error>(body)));
+    Ok(MulterMultipart::new(body, boundary))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use server::ReadEntry;
+
+    fn multer_body(chunks: Vec<&'static [u8]>, boundary: &str) -> MulterMultipart<'static> {
+        let chunks = chunks.into_iter().map(Ok::<_, io::Error>);
+        MulterMultipart::new(stream::iter(chunks), boundary.to_string())
+    }
+
+    #[test]
+    fn save_chunked() {
+        ::init_log();
+
+        let body = multer_body(vec![
+            b"--boundary\r\nContent-Disposition: form-data; name=\"gre",
+            b"eting\"\r\n\r\nhel",
+            b"lo\r\n--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n",
+            b"Content-Type: application/octet-stream\r\n\r\n\x00\x01",
+            b"\x02\r\n--boundary--\r\n",
+        ], "boundary");
+
+        let entries = save(body, |builder| builder.temp()).into_result_strict().unwrap();
+
+        assert!(entries.clean_termination);
+        assert_eq!(entries.fields["greeting"][0].as_text(), Some("hello"));
+
+        let file = &entries.fields["file"][0];
+        assert_eq!(file.headers.filename.as_deref(), Some("a.bin"));
+        assert_eq!(file.headers.content_type, Some(mime::APPLICATION_OCTET_STREAM));
+
+        let mut data = Vec::new();
+        file.data.readable().unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"\x00\x01\x02");
+    }
+
+    #[test]
+    fn unread_fields() {
+        ::init_log();
+
+        let body = multer_body(vec![
+            b"--boundary\r\nContent-Disposition: form-data; name=\"skipped\"\r\n\r\nunread\r\n",
+            b"--boundary\r\nContent-Disposition: form-data; name=\"kept\"\r\n\r\nok\r\n--boundary--\r\n",
+        ], "boundary");
+
+        let mut parts = PartsSource::new(MulterParts::new(body));
+
+        let field = parts.read_entry_mut().into_result().unwrap().unwrap();
+        assert_eq!(&*field.headers.name, "skipped");
+
+        let mut field = parts.read_entry_mut().into_result().unwrap().unwrap();
+        assert_eq!(&*field.headers.name, "kept");
+
+        let mut data = String::new();
+        field.data.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "ok");
+
+        assert!(parts.read_entry_mut().into_result().unwrap().is_none());
+    }
+
+    #[test]
+    fn save_truncated() {
+        ::init_log();
+
+        let body = multer_body(vec![
+            b"--boundary\r\nContent-Disposition: form-data; name=\"first\"\r\n\r\none\r\n",
+            b"--boundary\r\nContent-Disposition: form-data; name=\"second\"\r\n\r\ntw",
+        ], "boundary");
+
+        match save(body, |builder| builder.temp()) {
+            Partial(partial, _) => {
+                assert_eq!(partial.entries.fields["first"][0].as_text(), Some("one"));
+                assert!(!partial.entries.clean_termination);
+            },
+            _ => panic!("expected Partial"),
+        }
+
+        let body = multer_body(vec![
+            b"--boundary\r\nContent-Disposition: form-data; name=\"first\"\r\n\r\none\r\n",
+            b"--boundary\r\nContent-Disposition",
+        ], "boundary");
+
+        match save(body, |builder| builder.temp()) {
+            Partial(partial, PartialReason::IoError(e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                assert!(partial.partial.is_none());
+                assert_eq!(partial.entries.fields["first"][0].as_text(), Some("one"));
+                assert!(!partial.entries.clean_termination);
+            },
+            _ => panic!("expected Partial(IoError)"),
+        }
+    }
+
+    #[test]
+    fn to_multer_empty() {
+        ::init_log();
+
+        let multipart = to_multer(&Entries::new(::server::save::SaveDir::Memory)).unwrap();
+        let entries = save(multipart, |builder| builder.temp()).into_result_strict().unwrap();
+        assert!(entries.is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Converting the line endings of text fields as they're read,
+//! for `SaveBuilder::normalize_newlines()`.
+
+use std::io::{self, BufRead};
+
+/// Which line endings text fields are saved with, set with `SaveBuilder::normalize_newlines()`.
+///
+/// `\r\n`, `\n` and a lone `\r` are each treated as one line ending.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NewlinePolicy {
+    /// Keep line endings as they are.
+    Preserve,
+    /// Convert all line endings to `\n`.
+    Lf,
+    /// Convert all line endings to `\r\n`.
+    CrLf,
+}
+
+/// The state of converting line endings in a stream, kept by `MultipartData` between reads.
+#[derive(Debug)]
+pub struct NewlineFilter {
+    newline: &'static [u8],
+    /// The converted data, of which `buf[pos..]` hasn't been consumed yet.
+    buf: Vec<u8>,
+    pos: usize,
+    /// The last byte of the previous input was `\r`, so a `\n` at the start of the next input
+    /// belongs to the same line ending.
+    pending_cr: bool,
+}
+
+impl NewlineFilter {
+    /// Returns `None` for `NewlinePolicy::Preserve`, which needs no conversion.
+    pub fn new(policy: NewlinePolicy) -> Option<Self> {
+        let newline: &'static [u8] = match policy {
+            NewlinePolicy::Preserve => return None,
+            NewlinePolicy::Lf => b"\n",
+            NewlinePolicy::CrLf => b"\r\n",
+        };
+
+        Some(NewlineFilter { newline, buf: Vec::new(), pos: 0, pending_cr: false })
+    }
+
+    /// Return the converted data, converting more from `src` if it was all consumed.
+    ///
+    /// Only returns an empty buffer once `src` does.
+    pub fn fill_buf<R: BufRead>(&mut self, src: &mut R) -> io::Result<&[u8]> {
+        while self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+
+            let consumed = {
+                let input = src.fill_buf()?;
+
+                if input.is_empty() {
+                    if self.pending_cr {
+                        self.pending_cr = false;
+                        self.buf.extend_from_slice(self.newline);
+                        continue;
+                    }
+
+                    break;
+                }
+
+                self.convert(input);
+                input.len()
+            };
+
+            src.consume(consumed);
+        }
+
+        Ok(&self.buf[self.pos..])
+    }
+
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = ::std::cmp::min(self.pos + amt, self.buf.len());
+    }
+
+    /// Append `input` to `buf` with its line endings converted.
+    fn convert(&mut self, mut input: &[u8]) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            self.buf.extend_from_slice(self.newline);
+
+            if input[0] == b'\n' {
+                input = &input[1..];
+            }
+        }
+
+        while let Some(idx) = input.iter().position(|&b| b == b'\r' || b == b'\n') {
+            self.buf.extend_from_slice(&input[..idx]);
+
+            let len = match (input[idx], input.get(idx + 1)) {
+                (b'\r', Some(&b'\n')) => 2,
+                // wait for the next input to tell if this is a lone `\r`
+                (b'\r', None) => {
+                    self.pending_cr = true;
+                    return;
+                },
+                _ => 1,
+            };
+
+            self.buf.extend_from_slice(self.newline);
+            input = &input[idx + len..];
+        }
+
+        self.buf.extend_from_slice(input);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NewlineFilter, NewlinePolicy};
+
+    use std::io::BufReader;
+
+    /// Convert `input` read in chunks of `chunk` bytes.
+    fn convert(policy: NewlinePolicy, input: &[u8], chunk: usize) -> Vec<u8> {
+        let mut src = BufReader::with_capacity(chunk, input);
+        let mut filter = NewlineFilter::new(policy).unwrap();
+        let mut out = Vec::new();
+
+        loop {
+            let len = {
+                let buf = filter.fill_buf(&mut src).unwrap();
+                out.extend_from_slice(buf);
+                buf.len()
+            };
+
+            if len == 0 { return out; }
+            filter.consume(len);
+        }
+    }
+
+    #[test]
+    fn test_newline_filter() {
+        let input = b"a\r\nb\nc\rd\r\r\ne\n\r";
+
+        for chunk in 1 .. input.len() + 1 {
+            assert_eq!(convert(NewlinePolicy::Lf, input, chunk), b"a\nb\nc\nd\n\ne\n\n",
+                       "chunk size {}", chunk);
+            assert_eq!(convert(NewlinePolicy::CrLf, input, chunk),
+                       &b"a\r\nb\r\nc\r\nd\r\n\r\ne\r\n\r\n"[..], "chunk size {}", chunk);
+        }
+
+        // a `\r` at the very end is a line ending of its own
+        assert_eq!(convert(NewlinePolicy::Lf, b"a\r", 1), b"a\n");
+        assert_eq!(convert(NewlinePolicy::CrLf, b"", 1), b"");
+        assert!(NewlineFilter::new(NewlinePolicy::Preserve).is_none());
+    }
+}
@@ -1,9 +1,6 @@
 //! Support for `multipart/form-data` bodies in [Nickel](https://nickel.rs).
 pub extern crate nickel;
 
-use self::nickel::hyper;
-use self::hyper::header::ContentType;
-
 pub use self::nickel::Request as NickelRequest;
 pub use self::nickel::hyper::server::Request as HyperRequest;
 
@@ -20,8 +17,11 @@ impl<'r, 'mw: 'r, 'server: 'mw, D: 'mw> HttpRequest for Maybe<'r, 'mw, 'server,
 
     fn multipart_boundary(&self) -> Option<&str> {
         // we can't use the impl from the `hyper` module because it might be the wrong version
-        let cont_type = try_opt!(self.0.origin.headers.get::<ContentType>());
-        cont_type.get_param("boundary").map(|v| v.as_str())
+        super::raw_form_data_boundary(self.0.origin.headers.get_raw("Content-Type"))
+    }
+
+    fn content_type(&self) -> Option<&str> {
+        super::raw_content_type(self.0.origin.headers.get_raw("Content-Type"))
     }
 
     fn body(self) -> Self::Body {
@@ -0,0 +1,166 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Reading fields which arrive already split into headers and data, without a multipart body.
+
+use std::io::{self, BufRead, Read};
+
+use super::boundary::MIN_BUF_SIZE;
+use super::buffer::{MinBufRead, MinBufReader};
+use super::field::{FieldHeaders, PrivReadEntry, UnnamedPolicy};
+use super::save::SaveBuilder;
+
+/// A field from a `PartsSource`: its headers and a reader for its data.
+pub type Part = (FieldHeaders, Box<dyn BufRead>);
+
+/// An adapter which reads fields from an iterator of parts instead of a multipart body,
+/// e.g. when each field's headers and data arrive as separate messages.
+///
+/// Implements `ReadEntry`, so the fields can be read and saved like those of a `Multipart`:
+///
+/// ```rust
+/// # use multipart::server::{FieldHeaders, PartsSource};
+/// # use std::io::BufRead;
+/// let parts = vec![
+///     (FieldHeaders::new("greeting"), Box::new(&b"hello"[..]) as Box<dyn BufRead>),
+/// ];
+///
+/// let entries = PartsSource::new(parts).save().temp().into_result_strict().unwrap();
+/// assert_eq!(entries.fields["greeting"][0].as_text(), Some("hello"));
+/// ```
+///
+/// Unlike with `Multipart`, a field's data doesn't need to be read to get to the next one;
+/// its reader is simply dropped.
+pub struct PartsSource<I> {
+    parts: I,
+    reader: PartReader,
+    headers: Option<FieldHeaders>,
+    unnamed_policy: UnnamedPolicy,
+    fields_read: u32,
+    ended: bool,
+}
+
+impl<I: Iterator<Item = Part>> PartsSource<I> {
+    /// Read fields from `parts`, in order.
+    pub fn new<P: IntoIterator<IntoIter = I, Item = Part>>(parts: P) -> Self {
+        PartsSource {
+            parts: parts.into_iter(),
+            reader: PartReader {
+                inner: MinBufReader::new(Box::new(io::empty()) as Box<dyn BufRead>, MIN_BUF_SIZE),
+                payload_bytes: 0,
+            },
+            headers: None,
+            unnamed_policy: UnnamedPolicy::default(),
+            fields_read: 0,
+            ended: false,
+        }
+    }
+
+    /// Set what to do with parts whose headers have no `declared_name`.
+    ///
+    /// See [`UnnamedPolicy`](enum.UnnamedPolicy.html) for the options. Default is `Synthesize`.
+    pub fn on_unnamed(&mut self, policy: UnnamedPolicy) -> &mut Self {
+        self.unnamed_policy = policy;
+        self
+    }
+
+    /// Get a builder type for saving the fields to the filesystem.
+    ///
+    /// See [`SaveBuilder`](save/struct.SaveBuilder.html) for more information.
+    pub fn save(&mut self) -> SaveBuilder<&mut Self> {
+        SaveBuilder::new(self)
+    }
+}
+
+impl<I: Iterator<Item = Part>> PrivReadEntry for PartsSource<I> {
+    type Source = PartReader;
+
+    fn source_mut(&mut self) -> &mut PartReader {
+        &mut self.reader
+    }
+
+    fn set_min_buf_size(&mut self, min_buf_size: usize) {
+        self.reader.inner.set_min_buffered(min_buf_size);
+    }
+
+    /// Move on to the next part, dropping the rest of the current one.
+    fn consume_boundary(&mut self) -> io::Result<bool> {
+        let (headers, data) = match self.parts.next() {
+            Some(part) => part,
+            None => {
+                self.ended = true;
+                return Ok(false);
+            },
+        };
+
+        let min_buf_size = self.reader.inner.min_buffered();
+        self.reader.inner = MinBufReader::new(data, min_buf_size);
+        self.headers = Some(headers);
+
+        Ok(true)
+    }
+
+    fn unnamed_policy(&self) -> UnnamedPolicy {
+        self.unnamed_policy
+    }
+
+    fn count_field(&mut self) -> u32 {
+        let idx = self.fields_read;
+        self.fields_read = self.fields_read.saturating_add(1);
+        idx
+    }
+
+    fn end_headers(&mut self) {}
+
+    fn payload_bytes(&self) -> u64 {
+        self.reader.payload_bytes
+    }
+
+    fn overhead_bytes(&self) -> u64 {
+        0
+    }
+
+    fn is_strict(&self) -> bool {
+        false
+    }
+
+    /// Parts have no closing boundary, so this is `true` once they run out.
+    fn finished_cleanly(&self) -> bool {
+        self.ended
+    }
+
+    fn read_headers(&mut self) -> io::Result<FieldHeaders> {
+        self.headers.take().ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput, "headers of the current part were already read"
+        ))
+    }
+}
+
+/// The data of the current field of a `PartsSource`.
+pub struct PartReader {
+    inner: MinBufReader<Box<dyn BufRead>>,
+    payload_bytes: u64,
+}
+
+impl Read for PartReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.payload_bytes += read as u64;
+        Ok(read)
+    }
+}
+
+impl BufRead for PartReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.payload_bytes += amt as u64;
+    }
+}
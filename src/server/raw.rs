@@ -0,0 +1,112 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Keeping a copy of every byte read from the request, for `Multipart::capture_raw()`.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use server::save::{FileSize, SavedData};
+
+/// The length of the random name of the file the body is spooled to, the same as for saved fields.
+const RANDOM_FILENAME_LEN: usize = 12;
+
+/// The raw bytes of a request, kept in memory until they exceed `memory_threshold`
+/// and then written to a file.
+#[derive(Debug)]
+pub struct RawSpool {
+    memory_threshold: u64,
+    temp_parent: Option<PathBuf>,
+    buf: Vec<u8>,
+    file: Option<(BufWriter<File>, PathBuf)>,
+    len: u64,
+    /// The first error writing to the file, returned by `finish()`;
+    /// nothing more is written after it.
+    error: Option<io::Error>,
+}
+
+impl RawSpool {
+    /// The file is created in `temp_parent`, or the OS temporary directory if `None`.
+    pub fn new(memory_threshold: u64, temp_parent: Option<PathBuf>) -> Self {
+        RawSpool {
+            memory_threshold,
+            temp_parent,
+            buf: Vec::new(),
+            file: None,
+            len: 0,
+            error: None,
+        }
+    }
+
+    /// Append `bytes` to the copy.
+    ///
+    /// This can't fail, as it's called where the bytes are consumed; an error is kept
+    /// for `finish()` instead.
+    pub fn write(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() || self.error.is_some() {
+            return;
+        }
+
+        self.len += bytes.len() as u64;
+
+        if self.file.is_none() && self.len <= self.memory_threshold {
+            self.buf.extend_from_slice(bytes);
+            return;
+        }
+
+        if let Err(e) = self.write_file(bytes) {
+            self.error = Some(e);
+        }
+    }
+
+    fn write_file(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.file.is_none() {
+            let path = self.temp_parent.clone().unwrap_or_else(env::temp_dir)
+                .join(::random_alphanumeric(RANDOM_FILENAME_LEN));
+
+            let mut file = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(&path)?);
+            let res = file.write_all(&self.buf);
+
+            // kept even if writing failed, so `finish()` removes it
+            self.buf = Vec::new();
+            self.file = Some((file, path));
+            res?;
+        }
+
+        self.file_mut().write_all(bytes)
+    }
+
+    fn file_mut(&mut self) -> &mut BufWriter<File> {
+        &mut self.file.as_mut().expect("the file should be created first").0
+    }
+
+    /// Get the copy, or the first error that occurred writing it (removing any file).
+    pub fn finish(self) -> io::Result<SavedData> {
+        let (mut file, path) = match self.file {
+            Some(file) => file,
+            None => return match self.error {
+                Some(e) => Err(e),
+                None => Ok(SavedData::Bytes(self.buf.into())),
+            },
+        };
+
+        let res = match self.error {
+            Some(e) => Err(e),
+            None => file.flush(),
+        };
+
+        match res {
+            Ok(()) => Ok(SavedData::File(path, FileSize::new(self.len))),
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                Err(e)
+            },
+        }
+    }
+}
@@ -5,20 +5,64 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 //! Utilities for saving request entries to the filesystem.
+//!
+//! ### Thread Safety
+//! The results of saving, i.e. `Entries`, `SavedField`, `SavedData`, `SaveDir` and the
+//! `PartialReason` of a partial save, are `Send + Sync + 'static`, so they can be handed off
+//! to other threads for processing; this is checked at compile time. `DataReader<'a>` is too,
+//! but borrows the `SavedData` it reads unless it came from `SavedData::into_body_reader()`.
+//! Fields which are still being read, i.e. `MultipartField<M>` and `SaveBuilder<S>`, are only
+//! `Send` if the request `M` or source `S` is, and are tied to the request while it's read.
 
-pub use server::buf_redux::BufReader;
+/// The buffered reader used by `DataReader` for files: `buf_redux::BufReader`,
+/// or `std::io::BufReader` with the `std-buffer` feature or without `buf_redux`.
+#[cfg(all(feature = "buf_redux", not(feature = "std-buffer")))]
+pub type BufReader<R> = ::server::buf_redux::BufReader<R>;
 
-pub use tempfile::TempDir;
+/// The buffered reader used by `DataReader` for files: `buf_redux::BufReader`,
+/// or `std::io::BufReader` with the `std-buffer` feature or without `buf_redux`.
+#[cfg(any(not(feature = "buf_redux"), feature = "std-buffer"))]
+pub type BufReader<R> = io::BufReader<R>;
 
-use std::collections::HashMap;
+/// Re-exported by mistake, tying this crate's API to the version of `tempfile` it depends on.
+#[deprecated(since = "0.17.2", note = "use `tempfile::TempDir` directly")]
+pub type TempDir = ::tempfile::TempDir;
+
+pub use server::filename::{FilenameChecks, FilenameReport, analyze_filename, sanitize_filename};
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::ffi::OsString;
 use std::io::prelude::*;
 use std::fs::{self, File, OpenOptions};
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::{cmp, env, io, mem, str, u32, u64};
+use std::ops::{Deref, Range};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{cmp, env, fmt, io, mem, str, u32, u64};
 use tempfile;
+#[cfg(feature = "camino")]
+use camino::{Utf8Path, Utf8PathBuf};
+use mime::{self, Mime};
 
+
+use server::{EventSinkPanicked, Multipart, MultipartKind, parse_multipart_type};
+#[cfg(feature = "archive")]
+use server::archive::{self, FieldArchive, ZipFields};
+use server::charset::Charset;
+#[cfg(feature = "digest")]
+use server::digest::{self, Algorithm, Hasher};
 use server::field::{FieldHeaders, MultipartField, MultipartData, ReadEntry, ReadEntryResult};
+#[cfg(feature = "digest")]
+use server::field::FieldDigest;
+use server::newline::NewlinePolicy;
+use server::raw::RawSpool;
+use server::sniff::{SNIFF_LEN, TypeSniffer};
+use server::throttle::Throttle;
 
 use self::SaveResult::*;
 use self::TextPolicy::*;
@@ -26,37 +70,21 @@ use self::PartialReason::*;
 
 const RANDOM_FILENAME_LEN: usize = 12;
 
-fn rand_filename() -> String {
-    ::random_alphanumeric(RANDOM_FILENAME_LEN)
-}
+/// The number of random names to try for a saved file before giving up.
+const NAME_ATTEMPTS: u32 = 8;
 
-macro_rules! try_start (
-    ($try:expr) => (
-        match $try {
-            Ok(val) => val,
-            Err(e) => return Error(e),
-        }
-    )
-);
+/// The most memory preallocated for a field based on its declared size, which could be a lie.
+const MAX_PREALLOC: u64 = 1024 * 1024;
 
-macro_rules! try_full (
-    ($try:expr) => {
-        match $try {
-            Full(full) => full,
-            other => return other,
-        }
-    }
-);
+/// The prefix of the staging directories of `SaveBuilder::transactional()`.
+const STAGING_PREFIX: &str = ".staging-";
 
-macro_rules! try_partial (
-    ($try:expr) => {
-        match $try {
-            Full(full) => return Full(full.into()),
-            Partial(partial, reason) => (partial, reason),
-            Error(e) => return Error(e),
-        }
-    }
-);
+/// The file in a staging directory listing the files being moved out of it.
+const COMMIT_MARKER: &str = ".commit";
+
+fn rand_filename() -> String {
+    ::random_alphanumeric(RANDOM_FILENAME_LEN)
+}
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum TextPolicy {
@@ -68,6 +96,32 @@ enum TextPolicy {
     Ignore
 }
 
+/// Where `SaveBuilder::save_entries()` writes fields instead of saving them.
+enum Stream<'a> {
+    /// The first field with the name, for `stream_field_to()`.
+    Field(&'a str, &'a mut dyn Write),
+    /// Each file field, as an entry of the archive, for `archive_to()`.
+    #[cfg(feature = "archive")]
+    Archive(&'a mut dyn FieldArchive),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FileBackend {
+    /// Write files with regular blocking writes on the current thread
+    Std,
+    /// Hand writes off to an `io_uring` instance with the given queue depth
+    #[cfg(feature = "io-uring")]
+    IoUring(u32),
+}
+
+/// The default for `SaveBuilder::memory_threshold()`: 10 KiB, as used by
+/// [Apache Commons](https://commons.apache.org/proper/commons-fileupload/apidocs/org/apache/commons/fileupload/disk/DiskFileItemFactory.html).
+pub const DEFAULT_MEMORY_THRESHOLD: u64 = 10 * 1024;
+
+/// The default for `SaveBuilder::text_size_limit()`: 256 KiB, far more than a form field
+/// typed by a person needs.
+pub const DEFAULT_TEXT_SIZE_LIMIT: u64 = 256 * 1024;
+
 /// A builder for saving a file or files to the local filesystem.
 ///
 /// ### `OpenOptions`
@@ -85,12 +139,13 @@ enum TextPolicy {
 /// You can set a size limit for individual fields with `size_limit()`, which takes either `u64`
 /// or `Option<u64>`.
 ///
-/// You can also set the maximum number of fields to process with `count_limit()`, which
-/// takes either `u32` or `Option<u32>`. This only has an effect when using
-/// `SaveBuilder<[&mut] Multipart>`.
+/// You can also set the maximum number of fields to process with `max_parts()`, which
+/// takes either `u32` or `Option<u32>`, and separately the maximum number of files and of
+/// other fields with `max_file_parts()` and `max_text_parts()`. These only have an effect when
+/// using `SaveBuilder<[&mut] Multipart>`.
 ///
 /// By default, these limits are set conservatively to limit the maximum memory and disk space
-/// usage of a single request. You should set `count_limit` specifically for each request endpoint
+/// usage of a single request. You should set `max_parts` specifically for each request endpoint
 /// based on the number of fields you're expecting (exactly to that number if you're not expecting
 /// duplicate fields).
 ///
@@ -98,7 +153,7 @@ enum TextPolicy {
 /// By default, small fields (a few kilobytes or smaller) will be read directly to memory
 /// without creating a file. This behavior is controlled by the `memory_threshold()` setter. You can
 /// *roughly* tune the maximum memory a single request uses by tuning
-/// `count_limit * memory_threshold`
+/// `max_parts * memory_threshold`
 ///
 /// If a field appears to contain text data (its content-type is `text/*` or it doesn't declare
 /// one), `SaveBuilder` can read it to a string instead of saving the raw bytes as long as it falls
@@ -118,6 +173,46 @@ enum TextPolicy {
 /// unnecessary work if most fields end up being written to the filesystem, however, but this
 /// can be turned off with `ignore_text()` if it fits the use-case.)
 ///
+/// ### Chunked Files
+/// If the files a field is saved to must not exceed a certain size, set `chunk_files()`;
+/// fields larger than the chunk size are then split across several files and returned as
+/// `SavedData::Chunked`, which can still be read back as a single stream with `readable()`.
+///
+/// ### Open Files
+/// Each file is closed before the next one is opened, so a save normally holds at most one
+/// file open at a time. To guarantee a hard cap (e.g. `0` to never open files at all), set
+/// `max_open_files()`; the peak is reported in `SaveStats::peak_open_files`.
+///
+/// ### Created Directories
+/// Any missing directories in the paths of saved files are created. To guard against a bug
+/// in how paths are chosen creating lots of them, set `max_created_dirs()`. If a save fails or
+/// stops partway, the directories it created are removed again as long as they're empty;
+/// see `cleanup_created_dirs()`. The directories created are listed in `SaveStats::created_dirs`,
+/// and can be given specific permissions with `dir_mode()`.
+///
+/// ### Throttling
+/// To keep large uploads from starving other work (e.g. disk I/O), `throttle()` limits how
+/// fast field data is read and saved. This sleeps in the thread doing the save, so the save
+/// takes that much longer in total, and time spent sleeping counts toward any read timeouts
+/// set on the connection.
+///
+/// ### Buffer Pool
+/// Fields kept in memory normally get a freshly allocated buffer each. For services handling
+/// large numbers of small fields, a shared [`BufferPool`](struct.BufferPool.html) can be set with
+/// `buffer_pool()`; in-memory fields are then read directly into buffers taken from the pool
+/// and returned as `SavedData::Pooled`, which gives the buffer back to the pool when dropped.
+///
+/// ### Suspicious Filenames
+/// Filenames are never used for the saved files, but they may be used by your code later.
+/// Set `reject_suspicious_filenames()` to stop saving at a field whose filename disguises its
+/// real extension, e.g. `invoice.pdf.exe` or `shell.php.`; see
+/// [`analyze_filename()`](fn.analyze_filename.html).
+///
+/// ### Required Fields
+/// To check a field like an authentication token before accepting anything else from a request,
+/// set `require_first()`. Fields which arrive before the required ones are held back, up to
+/// a budget, and only kept if the check passes.
+///
 /// ### Warning: Do **not** trust user input!
 /// It is a serious security risk to create files or directories with paths based on user input.
 /// A malicious user could craft a path which can be used to overwrite important files, such as
@@ -132,40 +227,177 @@ enum TextPolicy {
 #[must_use = "nothing saved to the filesystem yet"]
 pub struct SaveBuilder<S> {
     savable: S,
+    opts: SaveOptions,
+    require_first: Option<RequireFirst>,
+    /// Only locked while saving a whole request; see `RequireFirst::check`.
+    validate_redacted: Option<Mutex<RedactedCheck>>,
+}
+
+/// The settings of a `SaveBuilder`, apart from its checks, which the builders for the
+/// fields of a request are made with and `SaveBuilderConfig::apply()` copies.
+#[derive(Clone)]
+struct SaveOptions {
     open_opts: OpenOptions,
     size_limit: u64,
+    text_size_limit: u64,
+    total_size_limit: u64,
     count_limit: u32,
+    max_file_parts: u32,
+    max_text_parts: u32,
     memory_threshold: u64,
     text_policy: TextPolicy,
+    file_backend: FileBackend,
+    buffer_pool: Option<Arc<BufferPool>>,
+    chunk_size: u64,
+    verify_digests: bool,
+    honor_charset_field: bool,
+    keep_charset_field: bool,
+    retry_policy: RetryPolicy,
+    flush_every: Option<u64>,
+    memory_only: bool,
+    dry_run: bool,
+    max_open_files: u32,
+    open_files: Arc<OpenFiles>,
+    max_created_dirs: u32,
+    cleanup_created_dirs: bool,
+    created_dirs: Arc<CreatedDirs>,
+    dir_mode: Option<u32>,
+    temp_parent: Option<PathBuf>,
+    temp_fallbacks: Vec<PathBuf>,
+    throttle: Option<Throttle>,
+    cancel: Option<CancelToken>,
+    filename_checks: Option<FilenameChecks>,
+    sniffer: Option<Arc<dyn TypeSniffer>>,
+    field_error_policy: FieldErrorPolicy,
+    text_norm: TextNorm,
+    newlines: NewlinePolicy,
+    name_prefix: String,
+    name_collisions: Arc<AtomicU32>,
+    use_client_filename: bool,
+    percent_decode_names: bool,
+    file_namer: Option<FileNamer>,
+    /// Where the field timings in `SavedField::timing` are measured from; replaced in tests.
+    clock: fn() -> Instant,
+    redact_fields: Option<FieldMatcher>,
+    allowed_fields: Option<Arc<HashSet<String>>>,
+    transactional: bool,
+    collect_raw_ranges: bool,
+    /// Called with the number of files moved so far before each rename when committing a
+    /// `transactional()` save; replaced in tests to simulate a crash.
+    rename_hook: fn(usize),
 }
 
-/// Common methods for whole requests as well as individual fields.
-impl<S> SaveBuilder<S> {
-    /// Implementation detail but not problematic to have accessible.
-    #[doc(hidden)]
-    pub fn new(savable: S) -> SaveBuilder<S> {
+impl SaveOptions {
+    /// The settings for saving `field` on its own: it's only read as text if it looks like text,
+    /// and a dry run of the request is done by `dry_run_field()` instead.
+    fn for_field<M: ReadEntry>(&self, field: &MultipartField<M>) -> SaveOptions {
+        SaveOptions {
+            text_policy: if field.is_text() { self.text_policy } else { Ignore },
+            dry_run: false,
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
         let mut open_opts = OpenOptions::new();
         open_opts.write(true).create_new(true);
 
-        SaveBuilder {
-            savable,
+        SaveOptions {
             open_opts,
             // 8 MiB, on the conservative end compared to most frameworks
             size_limit: 8 * 1024 * 1024,
+            text_size_limit: DEFAULT_TEXT_SIZE_LIMIT,
+            total_size_limit: u64::MAX,
             // Arbitrary, I have no empirical data for this
             count_limit: 256,
-            // 10KiB, used by Apache Commons
-            // https://commons.apache.org/proper/commons-fileupload/apidocs/org/apache/commons/fileupload/disk/DiskFileItemFactory.html
-            memory_threshold: 10 * 1024,
+            max_file_parts: u32::MAX,
+            max_text_parts: u32::MAX,
+            memory_threshold: DEFAULT_MEMORY_THRESHOLD,
             text_policy: TextPolicy::Try,
+            file_backend: FileBackend::Std,
+            buffer_pool: None,
+            chunk_size: u64::MAX,
+            verify_digests: false,
+            honor_charset_field: false,
+            keep_charset_field: false,
+            retry_policy: RetryPolicy::default(),
+            flush_every: None,
+            memory_only: false,
+            dry_run: false,
+            max_open_files: u32::MAX,
+            open_files: Arc::new(OpenFiles::default()),
+            max_created_dirs: u32::MAX,
+            cleanup_created_dirs: true,
+            created_dirs: Arc::new(CreatedDirs::default()),
+            dir_mode: None,
+            temp_parent: None,
+            temp_fallbacks: Vec::new(),
+            throttle: None,
+            cancel: None,
+            filename_checks: None,
+            sniffer: None,
+            field_error_policy: FieldErrorPolicy::Abort,
+            text_norm: TextNorm::None,
+            newlines: NewlinePolicy::Preserve,
+            name_prefix: String::new(),
+            name_collisions: Arc::new(AtomicU32::new(0)),
+            use_client_filename: false,
+            percent_decode_names: false,
+            file_namer: None,
+            clock: Instant::now,
+            redact_fields: None,
+            allowed_fields: None,
+            transactional: false,
+            collect_raw_ranges: false,
+            rename_hook: |_| (),
         }
     }
+}
+
+/// Common methods for whole requests as well as individual fields and `SaveBuilderConfig`.
+impl<S> SaveBuilder<S> {
+    /// Implementation detail but not problematic to have accessible.
+    #[doc(hidden)]
+    pub fn new(savable: S) -> SaveBuilder<S> {
+        SaveBuilder { savable, opts: SaveOptions::default(), require_first: None, validate_redacted: None }
+    }
 
     /// Set the maximum number of bytes to write out *per file*.
     ///
     /// Can be `u64` or `Option<u64>`. If `None` or `u64::MAX`, clears the limit.
     pub fn size_limit<L: Into<Option<u64>>>(mut self, limit: L) -> Self {
-        self.size_limit = limit.into().unwrap_or(u64::MAX);
+        self.opts.size_limit = limit.into().unwrap_or(u64::MAX);
+        self
+    }
+
+    /// Set the maximum number of bytes to save of a field without a filename, which is
+    /// usually kept in memory; the field which crosses it stops saving with
+    /// `PartialReason::TextSizeLimit`, with what was saved of it in `PartialEntries::partial`.
+    /// Only applies to whole requests, and not to a field streamed with `stream_field_to()`.
+    ///
+    /// `size_limit()` still applies if it is lower.
+    ///
+    /// Can be `u64` or `Option<u64>`. If `None` or `u64::MAX`, clears the limit.
+    /// Defaults to [`DEFAULT_TEXT_SIZE_LIMIT`](constant.DEFAULT_TEXT_SIZE_LIMIT.html).
+    pub fn text_size_limit<L: Into<Option<u64>>>(mut self, limit: L) -> Self {
+        self.opts.text_size_limit = limit.into().unwrap_or(u64::MAX);
+        self
+    }
+
+    /// Set the maximum number of bytes to save across all fields of the request, whether to
+    /// files or in memory; the field which crosses it stops saving with
+    /// `PartialReason::TotalSizeLimit`, with what was saved of it in `PartialEntries::partial`.
+    /// Only applies to whole requests.
+    ///
+    /// The fields already in the `Entries` passed to `with_entries()` count toward the limit.
+    /// Fields streamed with `stream_field_to()` or matched by `redact_fields()` aren't saved,
+    /// so they don't; those written to the archive of `archive_to()` do.
+    ///
+    /// Can be `u64` or `Option<u64>`. If `None` or `u64::MAX` (the default), clears the limit.
+    pub fn total_size_limit<L: Into<Option<u64>>>(mut self, limit: L) -> Self {
+        self.opts.total_size_limit = limit.into().unwrap_or(u64::MAX);
         self
     }
 
@@ -174,18 +406,39 @@ impl<S> SaveBuilder<S> {
     /// The `write` flag will be reset to `true` after the closure returns. (It'd be pretty
     /// pointless otherwise, right?)
     pub fn mod_open_opts<F: FnOnce(&mut OpenOptions)>(mut self, opts_fn: F) -> Self {
-        opts_fn(&mut self.open_opts);
-        self.open_opts.write(true);
+        opts_fn(&mut self.opts.open_opts);
+        self.opts.open_opts.write(true);
         self
     }
 
     /// Set the threshold at which to switch from copying a field into memory to copying
     /// it to disk.
     ///
-    /// If `0`, forces fields to save directly to the filesystem.
+    /// If `0`, forces fields to save directly to the filesystem, creating a file even for
+    /// an empty field (except with `chunk_files()`, which only creates files for data).
     /// If `u64::MAX`, effectively forces fields to always save to memory.
-    pub fn memory_threshold(self, memory_threshold: u64) -> Self {
-        Self { memory_threshold, ..self }
+    pub fn memory_threshold(mut self, memory_threshold: u64) -> Self {
+        self.opts.memory_threshold = memory_threshold;
+        self
+    }
+
+    /// Set the memory threshold from the environment variable `var`, e.g. `16MiB` or `max`;
+    /// see [`parse_size_str()`](fn.parse_size_str.html) for the format.
+    ///
+    /// If `var` isn't set or can't be parsed, the threshold is set to
+    /// [`DEFAULT_MEMORY_THRESHOLD`](constant.DEFAULT_MEMORY_THRESHOLD.html) instead,
+    /// with a warning logged for an invalid value.
+    pub fn memory_threshold_from_env(mut self, var: &str) -> Self {
+        let memory_threshold = match env::var(var) {
+            Ok(value) => parse_size_str(&value).unwrap_or_else(|| {
+                warn!("Invalid size in ${}: {:?}; using the default memory threshold", var, value);
+                DEFAULT_MEMORY_THRESHOLD
+            }),
+            Err(_) => DEFAULT_MEMORY_THRESHOLD,
+        };
+
+        self.opts.memory_threshold = memory_threshold;
+        self
     }
 
     /// When encountering a field that is apparently text, try to read it to a string or fall
@@ -195,8 +448,9 @@ impl<S> SaveBuilder<S> {
     /// always attempt to decode text regardless of the field's `Content-Type`.
     ///
     /// Has no effect once `memory_threshold` has been reached.
-    pub fn try_text(self) -> Self {
-        Self { text_policy: TextPolicy::Try, ..self }
+    pub fn try_text(mut self) -> Self {
+        self.opts.text_policy = TextPolicy::Try;
+        self
     }
 
     /// When encountering a field that is apparently text, read it to a string or return an error.
@@ -205,819 +459,5845 @@ impl<S> SaveBuilder<S> {
     /// always attempt to decode text regardless of the field's `Content-Type`.
     ///
     /// (RFC: should this continue to validate UTF-8 when writing to the filesystem?)
-    pub fn force_text(self) -> Self {
-        Self { text_policy: TextPolicy::Force, ..self}
+    pub fn force_text(mut self) -> Self {
+        self.opts.text_policy = TextPolicy::Force;
+        self
     }
 
     /// Don't try to read or validate any field data as UTF-8.
-    pub fn ignore_text(self) -> Self {
-        Self { text_policy: TextPolicy::Ignore, ..self }
+    pub fn ignore_text(mut self) -> Self {
+        self.opts.text_policy = TextPolicy::Ignore;
+        self
     }
-}
 
-/// Save API for whole multipart requests.
-impl<M> SaveBuilder<M> where M: ReadEntry {
-    /// Set the maximum number of fields to process.
+    /// Set how text read by `try_text()` or `force_text()` is checked or normalized beyond
+    /// being valid UTF-8.
     ///
-    /// Can be `u32` or `Option<u32>`. If `None` or `u32::MAX`, clears the limit.
-    pub fn count_limit<L: Into<Option<u32>>>(mut self, count_limit: L) -> Self {
-        self.count_limit = count_limit.into().unwrap_or(u32::MAX);
+    /// See [`TextNorm`](enum.TextNorm.html); defaults to `TextNorm::None`.
+    pub fn text_normalization(mut self, text_norm: TextNorm) -> Self {
+        self.opts.text_norm = text_norm;
         self
     }
 
-    /// Save all fields in the request using a new temporary directory prefixed with
-    /// `multipart-rs` in the OS temporary directory.
+    /// Convert the line endings of text fields as they're read, e.g. to `\n` for uploaded CSV
+    /// files which mix `\r\n` and `\n`.
     ///
-    /// For more options, create a `TempDir` yourself and pass it to `with_temp_dir()` instead.
+    /// Only applies to fields which would be read as text (see `try_text()`), whether they end up
+    /// in memory or in files, but not with `ignore_text()`. Size limits and `SavedData::size()`
+    /// count the converted bytes. Defaults to `NewlinePolicy::Preserve`.
+    pub fn normalize_newlines(mut self, newlines: NewlinePolicy) -> Self {
+        self.opts.newlines = newlines;
+        self
+    }
+
+    /// #### Feature: `io-uring`
+    /// Write files through an `io_uring` instance driven from a dedicated thread instead of
+    /// blocking the current thread on each write.
     ///
-    /// See `with_entries()` for more info.
+    /// At most `queue_depth` buffers will be queued or in flight at any time, which also bounds
+    /// the extra memory used per field. Results and byte counts are the same as with regular
+    /// writes.
     ///
-    /// ### Note: Temporary
-    /// See `SaveDir` for more info (the type of `Entries::save_dir`).
-    pub fn temp(self) -> EntriesSaveResult<M> {
-        self.temp_with_prefix("multipart-rs")
+    /// Falls back to regular writes on platforms other than Linux, or if the ring cannot be
+    /// created (e.g. the kernel does not support `io_uring`).
+    #[cfg(feature = "io-uring")]
+    pub fn io_uring(mut self, queue_depth: u32) -> Self {
+        self.opts.file_backend = FileBackend::IoUring(queue_depth);
+        self
     }
 
-    /// Save all fields in the request using a new temporary directory with the given string
-    /// as a prefix in the OS temporary directory.
+    /// Write files with `file_backend`, for running the tests against each backend.
+    #[cfg(test)]
+    fn file_backend(mut self, file_backend: FileBackend) -> Self {
+        self.opts.file_backend = file_backend;
+        self
+    }
+
+    /// Read fields which are kept in memory into buffers taken from `pool`, returning them as
+    /// `SavedData::Pooled`.
     ///
-    /// For more options, create a `TempDir` yourself and pass it to `with_temp_dir()` instead.
+    /// The pool's buffer capacity should be at least `memory_threshold` (or `size_limit`,
+    /// if lower) so buffers don't need to be reallocated.
+    pub fn buffer_pool(mut self, pool: Arc<BufferPool>) -> Self {
+        self.opts.buffer_pool = Some(pool);
+        self
+    }
+
+    /// Split fields saved to the filesystem into files of at most `chunk_size` bytes each.
     ///
-    /// See `with_entries()` for more info.
+    /// The chunks are named after the path the field would have been saved to, with `.part000`,
+    /// `.part001`, etc. appended, and returned as `SavedData::Chunked`. A chunk is only created
+    /// if there is data left to write to it.
     ///
-    /// ### Note: Temporary
-    /// See `SaveDir` for more info (the type of `Entries::save_dir`).
-    pub fn temp_with_prefix(self, prefix: &str) -> EntriesSaveResult<M> {
-        match tempfile::Builder::new().prefix(prefix).tempdir() {
-            Ok(tempdir) => self.with_temp_dir(tempdir),
-            Err(e) => SaveResult::Error(e),
-        }
+    /// `size_limit` still applies to the field as a whole. Chunked fields are always written
+    /// with regular blocking writes, regardless of `io_uring()`.
+    pub fn chunk_files(mut self, chunk_size: u64) -> Self {
+        self.opts.chunk_size = cmp::max(chunk_size, 1);
+        self
     }
 
-    /// Save all fields in the request using the given `TempDir`.
+    /// Retry writes to files (or the destination of `write_to()`) which fail with one of the
+    /// error kinds in `policy`, such as the transient errors of network filesystems.
     ///
-    /// See `with_entries()` for more info.
+    /// Only the part of a buffer which hasn't been written yet is retried, so no data is
+    /// duplicated. Once the attempts are exhausted, the error is returned as usual.
     ///
-    /// The `TempDir` is returned in the result under `Entries::save_dir`.
-    pub fn with_temp_dir(self, tempdir: TempDir) -> EntriesSaveResult<M> {
-        self.with_entries(Entries::new(SaveDir::Temp(tempdir)))
+    /// See [`RetryPolicy`](struct.RetryPolicy.html) for details.
+    pub fn retry_writes(mut self, retry_policy: RetryPolicy) -> Self {
+        self.opts.retry_policy = retry_policy;
+        self
     }
 
-    /// Save the file fields in the request to a new permanent directory with the given path.
+    /// Set the maximum number of files the save may have open for writing at once.
     ///
-    /// Any nonexistent directories in the path will be created.
+    /// Every file is closed before the next is opened, so this only matters if set to `0`,
+    /// in which case a field which would be written to a file fails with an I/O error
+    /// instead (`PartialReason::IoError` when saving a whole request).
     ///
-    /// See `with_entries()` for more info.
-    pub fn with_dir<P: Into<PathBuf>>(self, dir: P) -> EntriesSaveResult<M> {
-        let dir = dir.into();
-
-        try_start!(create_dir_all(&dir));
-
-        self.with_entries(Entries::new(SaveDir::Perm(dir)))
+    /// Can be `u32` or `Option<u32>`. If `None` or `u32::MAX`, clears the limit.
+    pub fn max_open_files<L: Into<Option<u32>>>(mut self, max_open_files: L) -> Self {
+        self.opts.max_open_files = max_open_files.into().unwrap_or(u32::MAX);
+        self
     }
 
-    /// Commence the save operation using the existing `Entries` instance.
+    /// Limit how fast field data is read to `bytes_per_sec`, sleeping as necessary.
     ///
-    /// May be used to resume a saving operation after handling an error.
+    /// When saving a whole request, the rate applies to all of its fields together.
+    /// Headers and boundaries aren't counted. Reads of up to a second's worth of bytes may
+    /// go through at once after a pause, but never faster than the rate on average.
     ///
-    /// If `count_limit` is set, only reads that many fields before returning an error.
-    /// If you wish to resume from `PartialReason::CountLimit`, simply remove some entries.
+    /// Can be `u64` or `Option<u64>`. If `None`, clears the limit.
     ///
-    /// Note that `PartialReason::CountLimit` will still be returned if the number of fields
-    /// reaches `u32::MAX`, but this would be an extremely degenerate case.
-    pub fn with_entries(self, mut entries: Entries) -> EntriesSaveResult<M> {
-        let SaveBuilder {
-            savable, open_opts, count_limit, size_limit,
-            memory_threshold, text_policy
-        } = self;
-
-        let mut res = ReadEntry::read_entry(savable);
-
-        let _ = entries.recount_fields();
-
-        let save_field = |field: &mut MultipartField<M>, entries: &Entries| {
-            let text_policy = if field.is_text() { text_policy } else { Ignore };
-
-            let mut saver = SaveBuilder {
-                savable: &mut field.data, open_opts: open_opts.clone(),
-                count_limit, size_limit, memory_threshold, text_policy
-            };
-
-            saver.with_dir(entries.save_dir.as_path())
-        };
-
-        while entries.fields_count < count_limit {
-            let mut field: MultipartField<M> = match res {
-                ReadEntryResult::Entry(field) => field,
-                ReadEntryResult::End(_) => return Full(entries), // normal exit point
-                ReadEntryResult::Error(_, e) => return Partial (
-                    PartialEntries {
-                        entries,
-                        partial: None,
-                    },
-                    e.into(),
-                )
-            };
-
-            let (dest, reason) = match save_field(&mut field, &entries) {
-                Full(saved) => {
-                    entries.push_field(field.headers, saved);
-                    res = ReadEntry::read_entry(field.data.into_inner());
-                    continue;
-                },
-                Partial(saved, reason) => (Some(saved), reason),
-                Error(error) => (None, PartialReason::IoError(error)),
-            };
-
-            return Partial(
-                PartialEntries {
-                    entries,
-                    partial: Some(PartialSavedField {
-                        source: field,
-                        dest,
-                    }),
-                },
-                reason
-            );
-        }
-
-        Partial(
-            PartialEntries {
-                entries,
-                partial: None,
-            },
-            PartialReason::CountLimit
-        )
+    /// ### Panics
+    /// If `bytes_per_sec` is `0`.
+    pub fn throttle<L: Into<Option<u64>>>(mut self, bytes_per_sec: L) -> Self {
+        self.opts.throttle = bytes_per_sec.into().map(Throttle::new);
+        self
     }
-}
 
-/// Save API for individual fields.
-impl<'m, M: 'm> SaveBuilder<&'m mut MultipartData<M>> where MultipartData<M>: BufRead {
-    /// Save the field data, potentially using a file with a random name in the
-    /// OS temporary directory.
+    /// Flush the writer passed to `write_to()` (or `stream_field_to()`) after at most `bytes`
+    /// have been written to it since the last flush, and at the end of the field, e.g. so
+    /// a buffered network writer passes data on as it arrives instead of when the field ends.
     ///
-    /// See `with_path()` for more details.
-    pub fn temp(&mut self) -> FieldSaveResult {
-        let path = env::temp_dir().join(rand_filename());
-        self.with_path(path)
+    /// A failed flush is handled like a failed write, including `retry_writes()`. It doesn't
+    /// apply to files saved by this crate.
+    ///
+    /// Can be `u64` or `Option<u64>`. If `None`, only the writer itself decides when to flush.
+    /// `0` is treated as `1`.
+    pub fn flush_every<L: Into<Option<u64>>>(mut self, bytes: L) -> Self {
+        self.opts.flush_every = bytes.into().map(|bytes| cmp::max(bytes, 1));
+        self
     }
 
-    /// Save the field data, potentially using a file with the given name in
-    /// the OS temporary directory.
+    /// Stop saving with `PartialReason::Cancelled` once `token` is cancelled, e.g. to end
+    /// uploads promptly on shutdown.
     ///
-    /// See `with_path()` for more details.
-    pub fn with_filename(&mut self, filename: &str) -> FieldSaveResult {
-        let mut tempdir = env::temp_dir();
-        tempdir.set_file_name(filename);
+    /// The token is checked after each buffer of field data is saved, and before each field
+    /// of a whole request. Saving can't stop while waiting for data from the client, so
+    /// how promptly it stops depends on the timeouts of the connection.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.opts.cancel = Some(token);
+        self
+    }
 
-        self.with_path(tempdir)
+    /// Set whether to stop at a field with a suspicious filename, with
+    /// `PartialReason::SuspiciousFilename`.
+    ///
+    /// A filename is suspicious if [`analyze_filename()`](fn.analyze_filename.html) finds
+    /// a NUL (literal or `%00`), trailing dots or spaces, bidirectional control characters,
+    /// a visible extension which differs from the actual one, or a double extension like
+    /// `invoice.pdf.exe`. Use `filename_checks()` to pick which of these count.
+    ///
+    /// Applies to saving whole requests and to `MultipartField::into_owned()`; the field is not
+    /// read. Default is `false`.
+    pub fn reject_suspicious_filenames(mut self, reject: bool) -> Self {
+        self.opts.filename_checks = if reject { Some(FilenameChecks::default()) } else { None };
+        self
     }
 
-    /// Save the field data, potentially using a file with a random alphanumeric name
-    /// in the given directory.
+    /// Stop at a field with a filename which fails any of `checks`.
     ///
-    /// See `with_path()` for more details.
-    pub fn with_dir<P: AsRef<Path>>(&mut self, dir: P) -> FieldSaveResult {
-        let path = dir.as_ref().join(rand_filename());
-        self.with_path(path)
+    /// See `reject_suspicious_filenames()`.
+    pub fn filename_checks(mut self, checks: FilenameChecks) -> Self {
+        self.opts.filename_checks = Some(checks);
+        self
     }
 
-    /// Save the field data, potentially using a file with the given path.
+    /// Detect the type of each field from its first bytes with `sniffer`, regardless of
+    /// the content type declared by the client, and store it in `SavedField::detected_type`.
     ///
-    /// Creates any missing directories in the path (RFC: skip this step?).
-    /// Uses the contained `OpenOptions` to create the file.
-    /// Truncates the file to the given `size_limit`, if set.
+    /// [`MagicSniffer`](../struct.MagicSniffer.html) recognizes a few common formats;
+    /// with the `infer` feature, `InferSniffer` recognizes many more. A closure taking the
+    /// first bytes and the field headers works as well.
     ///
-    /// The no directories or files will be created until the set `memory_threshold` is reached.
-    /// If `size_limit` is set and less than or equal to `memory_threshold`,
-    /// then the disk will never be touched.
-    pub fn with_path<P: Into<PathBuf>>(&mut self, path: P) -> FieldSaveResult {
-        let bytes = if self.text_policy != Ignore {
-            let (text, reason) = try_partial!(self.save_text());
-            match reason {
-                SizeLimit if !self.cmp_size_limit(text.len()) => text.into_bytes(),
-                Utf8Error(_) if self.text_policy != Force => text.into_bytes(),
-                other => return Partial(text.into(), other),
-            }
-        } else {
-            Vec::new()
-        };
-
-        let (bytes, reason) = try_partial!(self.save_mem(bytes));
+    /// Useful on a `SaveBuilderConfig`, where it also applies to `MultipartField::into_owned()`.
+    pub fn sniffer<T: TypeSniffer + 'static>(mut self, sniffer: T) -> Self {
+        self.opts.sniffer = Some(Arc::new(sniffer));
+        self
+    }
 
-        match reason {
-            SizeLimit if !self.cmp_size_limit(bytes.len()) => (),
-            other => return Partial(bytes.into(), other)
-        }
+    /// Set what to do when saving a single field fails while the request itself can still
+    /// be read, e.g. when its file can't be created or `force_text()` finds it isn't UTF-8.
+    /// Only applies to whole requests.
+    ///
+    /// See [`FieldErrorPolicy`](enum.FieldErrorPolicy.html); defaults to
+    /// `FieldErrorPolicy::Abort`.
+    pub fn on_field_error(mut self, policy: FieldErrorPolicy) -> Self {
+        self.opts.field_error_policy = policy;
+        self
+    }
 
-        let path = path.into();
+    /// Set the maximum number of directories the save may create for the files it saves to.
+    ///
+    /// Only directories which didn't exist count, e.g. saving to `a/b/c/file` creates three
+    /// if none of them exist. A file which would need more is not created; an I/O error wrapping
+    /// [`DirLimitExceeded`](struct.DirLimitExceeded.html) is returned instead
+    /// (`PartialReason::IoError` when saving a whole request).
+    ///
+    /// Can be `u32` or `Option<u32>`. If `None` or `u32::MAX`, clears the limit.
+    pub fn max_created_dirs<L: Into<Option<u32>>>(mut self, max_created_dirs: L) -> Self {
+        self.opts.max_created_dirs = max_created_dirs.into().unwrap_or(u32::MAX);
+        self
+    }
 
-        let mut file = match create_dir_all(&path).and_then(|_| self.open_opts.open(&path)) {
-            Ok(file) => file,
-            Err(e) => return Error(e),
-        };
+    /// Set whether to remove the directories the save created if it doesn't complete,
+    /// as long as they're empty. Default is `true`.
+    ///
+    /// Directories still holding files, e.g. of the fields saved before the save stopped,
+    /// are left alone.
+    pub fn cleanup_created_dirs(mut self, cleanup_created_dirs: bool) -> Self {
+        self.opts.cleanup_created_dirs = cleanup_created_dirs;
+        self
+    }
 
-        let data = try_full!(
-            try_write_all(&bytes, &mut file)
-                .map(move |size| SavedData::File(path, size as u64))
-        );
+    /// Set the permissions of the directories the save creates, e.g. `0o2770` for directories
+    /// which are writable by the group and pass it on to the files in them.
+    ///
+    /// Unlike `DirBuilder::mode()`, this is not masked by the process umask, and may include
+    /// the setuid, setgid and sticky bits. Directories which already exist are left alone.
+    /// Each directory is created with at most the owner's permissions in `mode` before it is
+    /// given `mode`, so it is never more accessible than `mode` allows.
+    ///
+    /// Only supported on Unix; ignored on other platforms.
+    pub fn dir_mode(mut self, mode: u32) -> Self {
+        self.opts.dir_mode = Some(mode);
+        self
+    }
 
-        self.write_to(file).map(move |written| data.add_size(written))
+    /// Set the directory `temp()` creates temporary directories (or, for a single field,
+    /// files) in, instead of the OS temporary directory, e.g. to keep them on a scratch volume.
+    ///
+    /// Useful on a `SaveBuilderConfig`, where it also applies to `MultipartField::into_owned()`.
+    pub fn temp_parent<P: Into<PathBuf>>(mut self, parent: P) -> Self {
+        self.opts.temp_parent = Some(parent.into());
+        self
     }
 
+    /// Start the random names of saved files with `prefix`, e.g. the process ID or host name,
+    /// so processes saving into the same directory can never pick the same name.
+    ///
+    /// `prefix` must be valid in a file name, i.e. not contain a path separator.
+    ///
+    /// Regardless of this, a random name which is already taken is replaced with another one,
+    /// up to 8 times; these are counted in `SaveStats::name_collisions` and logged at the
+    /// `warn` level, as more than the odd one suggests something is wrong with the random
+    /// number generator.
+    pub fn name_prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.opts.name_prefix = prefix.into();
+        self
+    }
 
-    /// Write out the field data to `dest`, truncating if a limit was set.
+    /// Save files under the filename the client sent (see `FieldHeaders::filename`) instead of
+    /// a random name, after passing it through [`sanitize_filename()`](fn.sanitize_filename.html)
+    /// so it stays in the directory it's saved to.
     ///
-    /// Returns the number of bytes copied, and whether or not the limit was reached
-    /// (tested by `MultipartFile::fill_buf().is_empty()` so no bytes are consumed).
+    /// A field gets a random name as before if it has no filename, nothing is left of it, or a
+    /// file with that name already exists, e.g. from another field of the same request; files
+    /// are never replaced. Then `Entries` is the only record of which file is which.
     ///
-    /// Retries on interrupts.
-    pub fn write_to<W: Write>(&mut self, mut dest: W) -> SaveResult<u64, u64> {
-        if self.size_limit < u64::MAX {
-            try_copy_limited(&mut self.savable, |buf| try_write_all(buf, &mut dest), self.size_limit)
-        } else {
-            try_read_buf(&mut self.savable, |buf| try_write_all(buf, &mut dest))
-        }
+    /// Applies to `with_dir()` and `temp()` for whole requests, and `with_dir()` for a field,
+    /// except with `chunk_files()`. With `transactional(true)`, a file whose name is taken in the
+    /// target directory by the time it's moved there gets a random name instead.
+    pub fn use_client_filename(mut self, use_client_filename: bool) -> Self {
+        self.opts.use_client_filename = use_client_filename;
+        self
     }
 
-    fn save_mem(&mut self, mut bytes: Vec<u8>) -> SaveResult<Vec<u8>, Vec<u8>> {
-        let pre_read = bytes.len() as u64;
-        match self.read_mem(|buf| { bytes.extend_from_slice(buf); Full(buf.len()) }, pre_read) {
-            Full(_) => Full(bytes),
-            Partial(_, reason) => Partial(bytes, reason),
-            Error(e) => if !bytes.is_empty() { Partial(bytes, e.into()) }
-            else { Error(e) }
-        }
+    /// Percent-decode the name and filename of each field, for clients which URL-encode them,
+    /// so e.g. a field sent as `user%5Bemail%5D` is saved under `user[email]` in `Entries`.
+    /// Only applies to whole requests.
+    ///
+    /// See `FieldHeaders::percent_decode_names()` for when a value is decoded; the values as
+    /// sent are still available from `FieldHeaders::name_raw()` and `filename_raw()`. Names which
+    /// were already decoded by `Multipart::percent_decode_names()` aren't decoded again.
+    ///
+    /// The decoded name is used for everything which matches names, e.g. `redact_fields()`
+    /// and `require_first()`.
+    pub fn percent_decode_names(mut self, percent_decode_names: bool) -> Self {
+        self.opts.percent_decode_names = percent_decode_names;
+        self
+    }
 
+    /// Save files under the path returned by `namer` for each field, relative to the directory
+    /// the request is saved to, instead of a random name. Only applies to whole requests.
+    ///
+    /// `namer` is passed the headers of the field and its index in the request, starting at 0
+    /// and counting the fields already in `Entries` when resuming with `with_entries()`.
+    /// It's called before each field is read, so also for fields which end up in memory,
+    /// but not for fields which are redacted or streamed, or in a dry run.
+    ///
+    /// Any missing directories in the path are created as for `with_dir()`. A path which isn't
+    /// relative or would leave the directory, e.g. with `..`, is an error of kind `InvalidInput`.
+    /// If the file already exists, the `OpenOptions` decide what happens: by default
+    /// (`create_new(true)`) it's an error of kind `AlreadyExists` rather than another name
+    /// being picked. Either way the namer is at fault rather than the request, so saving stops
+    /// with `SaveResult::Error` regardless of `on_field_error()`.
+    ///
+    /// This takes precedence over `use_client_filename()`. With `chunk_files()`, the chunks are
+    /// named after the path. Can't be combined with `transactional(true)`.
+    ///
+    /// ```rust
+    /// # use multipart::server::SaveBuilderConfig;
+    /// # use std::path::PathBuf;
+    /// let request_id = "req-42";
+    /// let config = SaveBuilderConfig::config()
+    ///     .with_file_namer(move |_headers, index| PathBuf::from(format!("{}-{:03}", request_id, index)));
+    /// ```
+    pub fn with_file_namer<F>(mut self, namer: F) -> Self
+    where F: FnMut(&FieldHeaders, u32) -> PathBuf + Send + 'static {
+        self.opts.file_namer = Some(Arc::new(Mutex::new(namer)));
+        self
     }
 
-    fn save_text(&mut self) -> SaveResult<String, String> {
-        let mut string = String::new();
+    /// A random name for a saved file, starting with `name_prefix`.
+    fn random_filename(&self) -> String {
+        format!("{}{}", self.opts.name_prefix, rand_filename())
+    }
 
-        // incrementally validate UTF-8 to do as much work as possible during network activity
-        let res = self.read_mem(|buf| {
-            match str::from_utf8(buf) {
-                Ok(s) => { string.push_str(s); Full(buf.len()) },
-                // buffer should always be bigger
-                Err(e) => if buf.len() < 4 {
-                        Partial(0, e.into())
-                    } else {
-                        string.push_str(str::from_utf8(&buf[..e.valid_up_to()]).unwrap());
-                        Full(e.valid_up_to())
-                    }
-            }
-        }, 0);
+    /// Never keep the data of fields matched by `matcher`, e.g. passwords which are checked
+    /// and then discarded, when saving a whole request.
+    ///
+    /// The data of a matching field is read and dropped, and the field is added to `Entries`
+    /// as `SavedData::Redacted` with only its length, so it isn't written to memory outside
+    /// of the read buffers, to disk or to the logs. Text fields can still be checked first
+    /// with `validate_redacted()`. Matching fields are subject to `size_limit()` as usual.
+    ///
+    /// Saving returns an error if the request is also being copied with
+    /// `Multipart::capture_raw()`, as the copy would keep the data of the redacted fields.
+    ///
+    /// ```rust
+    /// # use multipart::server::{FieldMatcher, Multipart};
+    /// # use multipart::server::save::SavedData;
+    /// let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nhunter2\r\n--boundary--\r\n"[..];
+    ///
+    /// let entries = Multipart::with_body(body, "boundary").save()
+    ///     .redact_fields(FieldMatcher::new().name("password").prefix("secret_"))
+    ///     .temp()
+    ///     .into_result_strict()
+    ///     .unwrap();
+    ///
+    /// match entries.fields["password"][0].data {
+    ///     SavedData::Redacted { original_len } => assert_eq!(original_len, 7),
+    ///     ref other => panic!("not redacted: {:?}", other),
+    /// }
+    /// ```
+    pub fn redact_fields(mut self, matcher: FieldMatcher) -> Self {
+        self.opts.redact_fields = Some(matcher);
+        self
+    }
 
-        match res {
-            Full(_) => Full(string),
-            Partial(_, reason) => Partial(string, reason),
-            Error(e) => Error(e),
-        }
+    /// Only save fields with these names (case-sensitive); the data of any other field is read
+    /// and discarded, without buffering it or creating a file, and its headers are added to
+    /// `Entries::ignored`. Only applies to whole requests.
+    ///
+    /// Ignored fields still count toward `max_parts()`, so a request can't be padded with
+    /// any number of them, but not toward any other limit. Names are matched after
+    /// `percent_decode_names()`, and a field passed to `stream_field_to()` must be allowed too.
+    ///
+    /// ```rust
+    /// # use multipart::server::Multipart;
+    /// let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"junk\"\r\n\r\nxxxx\r\n\
+    ///               --boundary\r\nContent-Disposition: form-data; name=\"description\"\r\n\r\nhi\r\n\
+    ///               --boundary--\r\n"[..];
+    ///
+    /// let entries = Multipart::with_body(body, "boundary").save()
+    ///     .allowed_fields(vec!["file".to_string(), "description".to_string()])
+    ///     .in_memory()
+    ///     .into_result_strict()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(entries.fields["description"][0].as_text(), Some("hi"));
+    /// assert_eq!(&*entries.ignored[0].name, "junk");
+    /// ```
+    pub fn allowed_fields<I: IntoIterator<Item = String>>(mut self, names: I) -> Self {
+        self.opts.allowed_fields = Some(Arc::new(names.into_iter().collect()));
+        self
     }
 
-    fn read_mem<Wb: FnMut(&[u8]) -> SaveResult<usize, usize>>(&mut self, with_buf: Wb, pre_read: u64) -> SaveResult<u64, u64> {
-        let limit = cmp::min(self.size_limit, self.memory_threshold)
-            .saturating_sub(pre_read);
-        try_copy_limited(&mut self.savable, with_buf, limit)
+    /// Set directories for `temp()` to try in order if a temporary directory can't be created
+    /// in the one set with `temp_parent()` (or the OS temporary directory), e.g. because it is
+    /// missing or read-only.
+    ///
+    /// Which one was used can be found with `Entries::save_dir.parent()`. If none of them work,
+    /// the error can be downcast to [`TempDirError`](struct.TempDirError.html). If the first
+    /// failed because its filesystem was full, the result is `PartialReason::InsufficientStorage`
+    /// with `SavePhase::DirSetup` instead of an error.
+    ///
+    /// This doesn't apply to saving a single field, nor to `temp_in()`.
+    pub fn temp_fallbacks<I>(mut self, parents: I) -> Self where I: IntoIterator, I::Item: Into<PathBuf> {
+        self.opts.temp_fallbacks = parents.into_iter().map(Into::into).collect();
+        self
     }
 
-    fn cmp_size_limit(&self, size: usize) -> bool {
-        size as u64 >= self.size_limit
+    /// Set the maximum number of fields to process. Only applies to whole requests.
+    ///
+    /// Deprecated in favor of `max_parts()`, which does the same: this counts every field
+    /// of the request, not only files (see `max_file_parts()` for that).
+    #[deprecated(note = "use `max_parts()`, which counts every field the same way")]
+    pub fn count_limit<L: Into<Option<u32>>>(self, count_limit: L) -> Self {
+        self.max_parts(count_limit)
     }
-}
 
-/// A field that has been saved (to memory or disk) from a multipart request.
-#[derive(Debug)]
-pub struct SavedField {
-    /// The headers of the field that was saved.
-    pub headers: FieldHeaders,
-    /// The data of the field which may reside in memory or on disk.
-    pub data: SavedData,
-}
+    /// Set the maximum number of fields to process, files or not; if the request has more,
+    /// saving stops with `PartialReason::CountLimit`. Only applies to whole requests.
+    ///
+    /// Can be `u32` or `Option<u32>`. If `None` or `u32::MAX`, clears the limit.
+    /// Defaults to `256`.
+    pub fn max_parts<L: Into<Option<u32>>>(mut self, limit: L) -> Self {
+        self.opts.count_limit = limit.into().unwrap_or(u32::MAX);
+        self
+    }
 
-/// A saved field's data container (in memory or on disk)
-#[derive(Clone, Debug)]
-pub enum SavedData {
-    /// Validated UTF-8 text data.
-    Text(String),
-    /// Binary data.
-    Bytes(Vec<u8>),
-    /// A path to a file on the filesystem and its size as written by `multipart`.
-    File(PathBuf, u64),
-}
+    /// Set the maximum number of files to process, i.e. fields with a `filename`; a file
+    /// beyond the limit stops saving with `PartialReason::FilePartLimit`, with the field in
+    /// `PartialEntries::partial`. Only applies to whole requests.
+    ///
+    /// Can be `u32` or `Option<u32>`. If `None` or `u32::MAX` (the default), clears the limit.
+    pub fn max_file_parts<L: Into<Option<u32>>>(mut self, limit: L) -> Self {
+        self.opts.max_file_parts = limit.into().unwrap_or(u32::MAX);
+        self
+    }
 
-impl SavedData {
-    /// Get an adapter for this data which implements `Read`.
+    /// Set the maximum number of fields without a `filename` to process; a field beyond
+    /// the limit stops saving with `PartialReason::TextPartLimit`, with the field in
+    /// `PartialEntries::partial`. Only applies to whole requests.
     ///
-    /// If the data is in a file, the file is opened in read-only mode.
-    pub fn readable(&self) -> io::Result<DataReader> {
-        use self::SavedData::*;
+    /// Can be `u32` or `Option<u32>`. If `None` or `u32::MAX` (the default), clears the limit.
+    pub fn max_text_parts<L: Into<Option<u32>>>(mut self, limit: L) -> Self {
+        self.opts.max_text_parts = limit.into().unwrap_or(u32::MAX);
+        self
+    }
 
-        match *self {
-            Text(ref text) => Ok(DataReader::Bytes(text.as_ref())),
-            Bytes(ref bytes) => Ok(DataReader::Bytes(bytes)),
-            File(ref path, _) => Ok(DataReader::File(BufReader::new(fs::File::open(path)?))),
-        }
+    /// Check each field against the digests declared in its `Content-MD5` or `Digest` headers
+    /// (see `FieldHeaders::digests`) once it has been saved.
+    ///
+    /// `md5` and `sha-256` are supported; other algorithms are skipped with a warning.
+    /// A declared digest which is not valid base64 never matches.
+    ///
+    /// If a field does not match, any files it was saved to are deleted and
+    /// `PartialReason::DigestMismatch` is returned, with the field in `PartialEntries::partial`
+    /// (without `dest`) so the rest of the request may still be read.
+    ///
+    /// Files are read back to compute their digests. Only applies to whole requests.
+    ///
+    /// #### Feature: `digest`
+    #[cfg(feature = "digest")]
+    pub fn verify_digests(mut self, verify_digests: bool) -> Self {
+        self.opts.verify_digests = verify_digests;
+        self
     }
 
-    /// Get the size of the data, in memory or on disk.
+    /// Make `with_dir()` all-or-nothing: the fields are saved to a hidden staging directory,
+    /// `.staging-<random>` in the target directory, and only moved into the target once the
+    /// whole request was saved, so it never has some of the files of a request but not others.
     ///
-    /// #### Note
-    /// The size on disk may not match the size of the file if it is externally modified.
-    pub fn size(&self) -> u64 {
-        use self::SavedData::*;
+    /// Each file is moved with a rename, which is atomic, from the last to the first. A marker
+    /// listing the files is written to the staging directory beforehand, so if the process dies
+    /// while they are being moved, `recover_staging()` can finish the job.
+    ///
+    /// If the save fails, the staging directory is deleted along with the files saved so far
+    /// (so the paths in the result no longer exist), and nothing is moved into the target.
+    ///
+    /// Only applies to `with_dir()`.
+    pub fn transactional(mut self, transactional: bool) -> Self {
+        self.opts.transactional = transactional;
+        self
+    }
 
-        match *self {
-            Text(ref text) => text.len() as u64,
-            Bytes(ref bytes) => bytes.len() as u64,
-            File(_, size) => size,
-        }
+    /// Record where each saved field was in the request body, in `SavedField::raw_range`,
+    /// e.g. to check a signature sent in one field against the raw bytes of the others with
+    /// `read_raw_ranges()`.
+    ///
+    /// Only fields parsed from a raw body by `Multipart` get a range, once all their data was
+    /// read; see [`RawRange`](struct.RawRange.html) for what it covers. Applies to whole
+    /// requests and `MultipartField::into_owned()`.
+    pub fn collect_raw_ranges(mut self, collect_raw_ranges: bool) -> Self {
+        self.opts.collect_raw_ranges = collect_raw_ranges;
+        self
     }
 
-    /// Returns `true` if the data is known to be in memory (`Text | Bytes`)
-    pub fn is_memory(&self) -> bool {
-        use self::SavedData::*;
+    /// Decode text fields in the charset named by a preceding text field named `_charset_`,
+    /// as HTML forms with such a (hidden) field submit them.
+    ///
+    /// Only applies to text fields without a `charset` parameter in their `Content-Type`.
+    /// Charset names are matched as per the WHATWG Encoding Standard; besides UTF-8, only
+    /// windows-1252 (which browsers also use for `ISO-8859-1` and `US-ASCII`) is supported.
+    /// Other charsets fall back to UTF-8 with a warning.
+    ///
+    /// Fields which are saved to the filesystem are never decoded.
+    ///
+    /// The `_charset_` field itself is left out of `Entries` unless `keep_charset_field(true)`
+    /// is set. Only applies to whole requests.
+    pub fn honor_charset_field(mut self, honor_charset_field: bool) -> Self {
+        self.opts.honor_charset_field = honor_charset_field;
+        self
+    }
 
-        match *self {
-            Text(_) | Bytes(_) => true,
-            File(_, _) => false,
-        }
+    /// With `honor_charset_field(true)`, also add the `_charset_` field to `Entries`.
+    pub fn keep_charset_field(mut self, keep_charset_field: bool) -> Self {
+        self.opts.keep_charset_field = keep_charset_field;
+        self
     }
 
-    fn add_size(self, add: u64) -> Self {
-        use self::SavedData::File;
+    /// Create any missing directories for a file at `path`, up to `max_created_dirs`.
+    fn create_dirs_for(&self, path: &Path) -> io::Result<()> {
+        self.opts.created_dirs.create_parents(path, self.opts.max_created_dirs, self.opts.dir_mode)
+    }
 
-        match self {
-            File(path, size) => File(path, size.saturating_add(add)),
-            other => other
+    /// Remove the empty directories created so far, if `cleanup_created_dirs` is set.
+    fn clean_up_dirs(&self) {
+        if self.opts.cleanup_created_dirs {
+            self.opts.created_dirs.remove_empty();
         }
     }
-}
 
-impl From<String> for SavedData {
-    fn from(s: String) -> Self {
-        SavedData::Text(s)
+    /// A builder for saving `field` with the same settings, as `with_entries()` does.
+    fn for_field<'f, M: ReadEntry>(&self, field: &'f mut MultipartField<M>) -> SaveBuilder<&'f mut MultipartData<M>> {
+        let opts = self.opts.for_field(field);
+        SaveBuilder { savable: &mut field.data, opts, require_first: None, validate_redacted: None }
     }
 }
 
-impl From<Vec<u8>> for SavedData {
-    fn from(b: Vec<u8>) -> Self {
-        SavedData::Bytes(b)
+/// Save API for whole multipart requests.
+impl<M> SaveBuilder<M> where M: ReadEntry {
+    /// Hold back all other fields until each of `field_names` has been saved and `check`
+    /// approves of them, e.g. to authenticate a request by its `token` field before accepting
+    /// any uploads.
+    ///
+    /// Fields which arrive before all of `field_names` are saved as usual but kept out of
+    /// `Entries`; their total size may not exceed `spool_budget`. `check` is passed the `Entries`
+    /// with the required fields (and any fields it already had) but none of the held-back ones.
+    ///
+    /// If `check` returns `Ok`, the held-back fields are added in the order they arrived
+    /// and saving continues as usual. Otherwise, saving stops with:
+    ///
+    /// * `PartialReason::Aborted` if `check` returns `Err`,
+    /// * `PartialReason::SpoolBudget` if the held-back fields exceed `spool_budget`,
+    /// * `PartialReason::RequiredFieldMissing` if the request ends before all of `field_names`.
+    ///
+    /// In each of these cases the held-back and required fields are taken back out of `Entries`
+    /// and any files they were saved to are deleted, so nothing from the request is kept.
+    /// If saving stops for another reason before `check` runs, only the held-back fields
+    /// are deleted.
+    ///
+    /// With no `field_names`, `check` runs before the first field is read.
+    pub fn require_first<F>(self, field_names: &[&str], spool_budget: u64, check: F) -> Self
+    where F: FnMut(&Entries) -> Result<(), Abort> + Send + 'static {
+        let require_first = RequireFirst {
+            field_names: field_names.iter().map(|&name| name.to_string()).collect(),
+            spool_budget,
+            check: Mutex::new(Box::new(check)),
+        };
+
+        Self { require_first: Some(require_first), ..self }
     }
-}
 
-/// A `Read` (and `BufRead`) adapter for `SavedData`
-pub enum DataReader<'a> {
-    /// In-memory data source (`SavedData::Bytes | Text`)
-    Bytes(&'a [u8]),
-    /// On-disk data source (`SavedData::File`)
-    File(BufReader<File>),
-}
+    /// Pass the data of each text field matched by `redact_fields()` to `check` once before
+    /// it is dropped, e.g. to verify a password.
+    ///
+    /// The data is read into memory up to `size_limit()` for this, a chunk at a time through a
+    /// buffer which is zeroed after each one. It's collected in a buffer which is zeroed
+    /// afterwards, and whenever it's outgrown. The parser's own read buffer isn't cleared. If `check` returns `Err`, saving stops with `PartialReason::Aborted`.
+    /// File fields are dropped without being checked.
+    ///
+    /// Has no effect without `redact_fields()`.
+    pub fn validate_redacted<F>(self, check: F) -> Self
+    where F: FnMut(&FieldHeaders, &[u8]) -> Result<(), Abort> + Send + 'static {
+        Self { validate_redacted: Some(Mutex::new(Box::new(check))), ..self }
+    }
 
-impl<'a> Read for DataReader<'a> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        use self::DataReader::*;
+    /// Save all fields in the request to memory, without touching the filesystem.
+    ///
+    /// Instead of being written to a file, a field which is larger than `memory_threshold`
+    /// (and `size_limit`) returns `PartialReason::MemoryLimit`, with the field in
+    /// `PartialEntries::partial`. Set `memory_threshold()` to the size of the largest field you
+    /// want to accept.
+    ///
+    /// `Entries::save_dir` will be `SaveDir::Memory`.
+    pub fn in_memory(mut self) -> EntriesSaveResult<M> {
+        self.opts.memory_only = true;
+        self.with_entries(Entries::new(SaveDir::Memory))
+    }
 
-        match *self {
-            Bytes(ref mut bytes) => bytes.read(buf),
-            File(ref mut file) => file.read(buf),
-        }
+    /// Read and check the whole request as a save would, but without writing anything to the
+    /// filesystem or keeping the data of the fields, e.g. to validate a large upload before
+    /// accepting it into permanent storage.
+    ///
+    /// The limits and checks all apply as with `with_dir()`, including `size_limit()`,
+    /// the part limits, `filename_checks()`, `force_text()`, `sniffer()` and `verify_digests()`,
+    /// and return the same `PartialReason`s. The data of each field is `SavedData::Validated`,
+    /// with its length, detected type and (with the `digest` feature) SHA-256 digest; only its
+    /// first bytes are kept, for the sniffer.
+    ///
+    /// With `honor_charset_field()`, `_charset_` fields are still read into memory (up to
+    /// `memory_threshold`) to decode the fields after them. Fields matched by `redact_fields()`
+    /// are redacted as usual.
+    ///
+    /// `Entries::save_dir` will be `SaveDir::Memory`.
+    pub fn dry_run(mut self) -> EntriesSaveResult<M> {
+        self.opts.dry_run = true;
+        self.opts.memory_only = false;
+        self.with_entries(Entries::new(SaveDir::Memory))
     }
-}
 
-impl<'a> BufRead for DataReader<'a> {
-    fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        use self::DataReader::*;
+    /// Save all fields in the request using a new temporary directory prefixed with
+    /// `multipart-rs` in the OS temporary directory, or the one set with `temp_parent()`.
+    ///
+    /// If it can't be created there, each directory set with `temp_fallbacks()` is tried.
+    ///
+    /// For more options, create a `TempDir` yourself and pass it to `with_temp_dir()` instead.
+    ///
+    /// See `with_entries()` for more info.
+    ///
+    /// ### Note: Temporary
+    /// See `SaveDir` for more info (the type of `Entries::save_dir`).
+    pub fn temp(self) -> EntriesSaveResult<M> {
+        self.temp_with_prefix("multipart-rs")
+    }
 
-        match *self {
-            Bytes(ref mut bytes) => bytes.fill_buf(),
-            File(ref mut file) => file.fill_buf(),
+    /// Save all fields in the request using a new temporary directory with the given string
+    /// as a prefix in the OS temporary directory, or the one set with `temp_parent()`.
+    ///
+    /// For more options, create a `TempDir` yourself and pass it to `with_temp_dir()` instead.
+    ///
+    /// See `with_entries()` for more info.
+    ///
+    /// ### Note: Temporary
+    /// See `SaveDir` for more info (the type of `Entries::save_dir`).
+    pub fn temp_with_prefix(self, prefix: &str) -> EntriesSaveResult<M> {
+        match self.create_temp_dir(prefix) {
+            Ok(tempdir) => self.with_temp_dir(tempdir),
+            Err(e) => dir_setup_failed(e),
         }
     }
 
-    fn consume(&mut self, amt: usize) {
-        use self::DataReader::*;
+    /// Create a temporary directory in `temp_parent` (or the OS temporary directory),
+    /// or else the first of `temp_fallbacks` where it can be created.
+    fn create_temp_dir(&self, prefix: &str) -> io::Result<::tempfile::TempDir> {
+        let os_temp_dir = self.opts.temp_parent.is_none();
+        let first = self.opts.temp_parent.clone().unwrap_or_else(env::temp_dir);
+        let mut attempts = Vec::new();
 
-        match *self {
-            Bytes(ref mut bytes) => bytes.consume(amt),
-            File(ref mut file) => file.consume(amt),
+        for parent in Some(first).into_iter().chain(self.opts.temp_fallbacks.iter().cloned()) {
+            match tempfile::Builder::new().prefix(prefix).tempdir_in(&parent) {
+                Ok(tempdir) => {
+                    if !attempts.is_empty() {
+                        warn!("Created temporary directory in fallback {:?}: {}", parent,
+                              TempDirError { attempts, os_temp_dir });
+                    }
+
+                    return Ok(tempdir);
+                },
+                Err(e) => attempts.push((parent, e)),
+            }
         }
+
+        let kind = attempts[0].1.kind();
+        Err(io::Error::new(kind, TempDirError { attempts, os_temp_dir }))
     }
-}
 
-/// A result of `Multipart::save()`.
-#[derive(Debug)]
-pub struct Entries {
-    /// The fields of the multipart request, mapped by field name -> value.
-    ///
-    /// A field name may have multiple actual fields associated with it, but the most
-    /// common case is a single field.
+    /// Save all fields in the request using a new temporary directory prefixed with
+    /// `multipart-rs` in `parent`, which must exist.
     ///
-    /// Each vector is guaranteed not to be empty unless externally modified.
-    // Even though individual fields might only have one entry, it's better to limit the
-    // size of a value type in `HashMap` to improve cache efficiency in lookups.
-        pub fields: HashMap<Arc<str>, Vec<SavedField>>,
-    /// The directory that the entries in `fields` were saved into.
-    pub save_dir: SaveDir,
-    fields_count: u32,
-}
-
-impl Entries {
-    /// Create a new `Entries` with the given `SaveDir`
-    pub fn new(save_dir: SaveDir) -> Self {
-        Entries {
-            fields: HashMap::new(),
-            save_dir,
-            fields_count: 0,
-        }
+    /// See `temp()` for more info.
+    pub fn temp_in<P: AsRef<Path>>(self, parent: P) -> EntriesSaveResult<M> {
+        self.temp_in_with_prefix(parent, "multipart-rs")
     }
 
-    /// Returns `true` if `fields` is empty, `false` otherwise.
-    pub fn is_empty(&self) -> bool {
-        self.fields.is_empty()
+    /// Save all fields in the request using a new temporary directory with the given string
+    /// as a prefix in `parent`, which must exist.
+    ///
+    /// See `temp()` for more info.
+    pub fn temp_in_with_prefix<P: AsRef<Path>>(self, parent: P, prefix: &str) -> EntriesSaveResult<M> {
+        match tempfile::Builder::new().prefix(prefix).tempdir_in(parent) {
+            Ok(tempdir) => self.with_temp_dir(tempdir),
+            Err(e) => dir_setup_failed(e),
+        }
     }
 
-    /// The number of actual fields contained within this `Entries`.
+    /// Save all fields in the request using the given `TempDir`.
     ///
-    /// Effectively `self.fields.values().map(Vec::len).sum()` but maintained separately.
+    /// See `with_entries()` for more info.
     ///
-    /// ## Note
-    /// This will be incorrect if `fields` is modified externally. Call `recount_fields()`
-    /// to get the correct count.
-    pub fn fields_count(&self) -> u32 {
-        self.fields_count
+    /// The `TempDir` is returned in the result under `Entries::save_dir`.
+    pub fn with_temp_dir(self, tempdir: ::tempfile::TempDir) -> EntriesSaveResult<M> {
+        self.with_entries(Entries::new(SaveDir::Temp(tempdir)))
     }
 
-    /// Sum the number of fields in this `Entries` and then return the updated value.
-    pub fn recount_fields(&mut self) -> u32 {
-        let fields_count = self.fields.values().map(Vec::len).sum();
-        // saturating cast
-        self.fields_count = cmp::min(u32::MAX as usize, fields_count) as u32;
-        self.fields_count
-    }
+    /// Save the file fields in the request to a new permanent directory with the given path.
+    ///
+    /// Any nonexistent directories in the path will be created.
+    ///
+    /// See `with_entries()` for more info.
+    pub fn with_dir<P: Into<PathBuf>>(self, dir: P) -> EntriesSaveResult<M> {
+        let dir = dir.into();
 
-    fn push_field(&mut self, mut headers: FieldHeaders, data: SavedData) {
-        use std::collections::hash_map::Entry::*;
+        if let Err(e) = self.create_dirs_for(&dir) {
+            self.clean_up_dirs();
+            return dir_setup_failed(e);
+        }
 
-        match self.fields.entry(headers.name.clone()) {
-            Vacant(vacant) => { vacant.insert(vec![SavedField { headers, data }]); },
-            Occupied(occupied) => {
-                // dedup the field name by reusing the key's `Arc`
-                headers.name = occupied.key().clone();
-                occupied.into_mut().push({ SavedField { headers, data }});
-            },
+        if self.opts.transactional {
+            return self.save_staged(dir);
         }
 
-        self.fields_count = self.fields_count.saturating_add(1);
+        self.with_entries(Entries::new(SaveDir::Perm(dir)))
     }
 
-    /// Print all fields and their contents to stdout. Mostly for testing purposes.
-    pub fn print_debug(&self) -> io::Result<()> {
-        let stdout = io::stdout();
-        let stdout_lock = stdout.lock();
-        self.write_debug(stdout_lock)
-    }
+    /// `with_dir()` with `transactional(true)`.
+    fn save_staged(self, dir: PathBuf) -> EntriesSaveResult<M> {
+        if self.opts.file_namer.is_some() {
+            self.clean_up_dirs();
+            return Error(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "`with_file_namer()` can't be combined with `transactional(true)`"
+            ));
+        }
 
-    /// Write all fields and their contents to the given output. Mostly for testing purposes.
-    pub fn write_debug<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        for (name, entries) in &self.fields {
-            writeln!(writer, "Field {:?} has {} entries:", name, entries.len())?;
+        let staging = dir.join(format!("{}{}", STAGING_PREFIX, rand_filename()));
+        let created_dirs = self.opts.created_dirs.clone();
+        let cleanup_created_dirs = self.opts.cleanup_created_dirs;
+        let rename_hook = self.opts.rename_hook;
+        let name_prefix = self.opts.name_prefix.clone();
 
-            for (idx, field) in entries.iter().enumerate() {
-                let mut data = field.data.readable()?;
-                let headers = &field.headers;
-                writeln!(writer, "{}: {:?} ({:?}):", idx, headers.filename, headers.content_type)?;
-                io::copy(&mut data, &mut writer)?;
+        // not `save_all()`, as the created directories can only be cleaned up without `staging`
+        let mut res = match self.save_entries(Entries::new(SaveDir::Perm(staging.clone())), None) {
+            Full(mut entries) => match commit_staged(&mut entries, &staging, &dir, &name_prefix, rename_hook) {
+                Ok(()) => return Full(entries),
+                Err(e) => Partial(PartialEntries { entries, partial: None }, e.into()),
+            },
+            other => other,
+        };
+
+        // nothing was moved into `dir`
+        match fs::remove_dir_all(&staging) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => warn!("Failed to remove staging directory {:?}: {}", staging, e),
+            Ok(()) => (),
+        }
+
+        if cleanup_created_dirs {
+            created_dirs.remove_empty();
+
+            if let Partial(ref mut partial, _) = res {
+                partial.entries.stats.created_dirs = created_dirs.created();
             }
         }
 
-        Ok(())
+        res
     }
-}
 
-/// The save directory for `Entries`. May be temporary (delete-on-drop) or permanent.
-#[derive(Debug)]
-pub enum SaveDir {
-    /// This directory is temporary and will be deleted, along with its contents, when this wrapper
-    /// is dropped.
-    Temp(TempDir),
-    /// This directory is permanent and will be left on the filesystem when this wrapper is dropped.
+    /// `with_dir()` for a UTF-8 path. Enabled with the `camino` feature.
     ///
-    /// **N.B.** If this directory is in the OS temporary directory then it may still be
-    /// deleted at any time.
-    Perm(PathBuf),
-}
+    /// The files saved in it have random alphanumeric names (or sanitized client filenames,
+    /// which are UTF-8 too), so `SavedData::utf8_path()` and `SaveDir::as_utf8_path()`
+    /// always return `Some` for the result.
+    #[cfg(feature = "camino")]
+    pub fn with_dir_utf8<P: Into<Utf8PathBuf>>(self, dir: P) -> EntriesSaveResult<M> {
+        self.with_dir(dir.into().into_std_path_buf())
+    }
 
-impl SaveDir {
-    /// Get the path of this directory, either temporary or permanent.
-    pub fn as_path(&self) -> &Path {
-        use self::SaveDir::*;
-        match *self {
-            Temp(ref tempdir) => tempdir.path(),
-            Perm(ref pathbuf) => &*pathbuf,
+    /// Save all fields in the request to a new subdirectory of `root`, with a random name.
+    ///
+    /// This avoids creating a directory for each request in the OS temporary directory.
+    /// The subdirectory is not deleted when the returned `Entries` is dropped;
+    /// use `Entries::save_dir.delete()` to remove it.
+    ///
+    /// See `with_entries()` for more info.
+    pub fn with_shared_root(self, root: &SharedSaveRoot) -> EntriesSaveResult<M> {
+        match root.create_subdir() {
+            Ok(sub) => self.with_entries(Entries::new(SaveDir::PermIn { root: root.clone(), sub })),
+            Err(e) => dir_setup_failed(e),
         }
     }
 
-    /// Returns `true` if this is a temporary directory which will be deleted on-drop.
-    pub fn is_temporary(&self) -> bool {
-        use self::SaveDir::*;
-        match *self {
-            Temp(_) => true,
-            Perm(_) => false,
+    /// Commence the save operation using the existing `Entries` instance.
+    ///
+    /// May be used to resume a saving operation after handling an error.
+    ///
+    /// If `max_parts` is set, only reads that many fields before returning an error.
+    /// If you wish to resume from `PartialReason::CountLimit`, simply remove some entries.
+    ///
+    /// Note that `PartialReason::CountLimit` will still be returned if the number of fields
+    /// reaches `u32::MAX`, but this would be an extremely degenerate case.
+    ///
+    /// If `entries` already has `max_parts` fields (e.g. if the limit is `0`),
+    /// `PartialReason::CountLimit` is returned without reading anything from the request.
+    ///
+    /// `max_file_parts` and `max_text_parts` are checked once the headers of each field are
+    /// read, counting the fields and `failures` already in `entries`.
+    pub fn with_entries(self, entries: Entries) -> EntriesSaveResult<M> {
+        self.save_all(entries, None)
+    }
+
+    /// Write the first field named `field_name` to `dest` as it's read, and save all other
+    /// fields in a new temporary directory as with `temp()`, e.g. to pass a large upload
+    /// straight on to object storage while collecting the small fields around it.
+    ///
+    /// `size_limit` applies to the streamed field as to any other. Its headers are added
+    /// to `Entries::fields` with `SavedData::Streamed`, recording the number of bytes written.
+    /// Any later fields with the same name are saved normally.
+    ///
+    /// If the request has no such field, `Entries::streamed_field_missing` is set;
+    /// this isn't an error.
+    pub fn stream_field_to<W: Write>(self, field_name: &str, mut dest: W) -> EntriesSaveResult<M> {
+        match self.create_temp_dir("multipart-rs") {
+            Ok(tempdir) => self.save_all(Entries::new(SaveDir::Temp(tempdir)), Some(Stream::Field(field_name, &mut dest))),
+            Err(e) => dir_setup_failed(e),
         }
     }
 
-    /// Unwrap the `PathBuf` from `self`; if this is a temporary directory,
-    /// it will be converted to a permanent one.
-    pub fn into_path(self) -> PathBuf {
-        use self::SaveDir::*;
-
-        match self {
-            Temp(tempdir) => tempdir.into_path(),
-            Perm(pathbuf) => pathbuf,
-        }
-    }
-
-    /// If this `SaveDir` is temporary, convert it to permanent.
-    /// This is a no-op if it already is permanent.
+    /// Write each file field into a zip archive written to `dest` as it's read, instead of
+    /// saving it, e.g. to put all the uploads of a request in cold storage as one file.
     ///
-    /// ### Warning: Potential Data Loss
-    /// Even though this will prevent deletion on-drop, the temporary folder on most OSes
-    /// (where this directory is created by default) can be automatically cleared by the OS at any
-    /// time, usually on reboot or when free space is low.
+    /// The fields are added to `Entries::fields` with `SavedData::Streamed`, recording the
+    /// number of bytes written. Each entry in the archive is named after the field's filename
+    /// as in `Entries::write_zip()`. Text fields are saved in memory as with `in_memory()`;
+    /// with `manifest`, those with UTF-8 text are also written to a `fields.json` entry at
+    /// the end, as a JSON object mapping each field name to an array of its values.
     ///
-    /// It is recommended that you relocate the files from a request which you want to keep to a
-    /// permanent folder on the filesystem.
-    pub fn keep(&mut self) {
-        use self::SaveDir::*;
-        *self = match mem::replace(self, Perm(PathBuf::new())) {
-            Temp(tempdir) => Perm(tempdir.into_path()),
-            old_self => old_self,
-        };
-    }
-
-    /// Delete this directory and its contents, regardless of its permanence.
+    /// Limits apply to each field as usual, and the archived fields count toward
+    /// `total_size_limit()`. If the save stops partway, the archive is still finished with the
+    /// fields saved up to then; the field which was being written is left out of the central
+    /// directory, so readers skip it. An error finishing the archive after a full save returns
+    /// `PartialReason::IoError`.
     ///
-    /// ### Warning: Potential Data Loss
-    /// This is very likely irreversible, depending on the OS implementation.
+    /// It's a zip rather than a tar, as a tar header needs the size of the entry before its data.
+    /// The archive is written without compression, with Zip64 records for fields or archives
+    /// of 4 GiB or more. `verify_digests()` and `sniffer()` don't apply to the archived fields.
     ///
-    /// Files deleted programmatically are deleted directly from disk, as compared to most file
-    /// manager applications which use a staging area from which deleted files can be safely
-    /// recovered (i.e. Windows' Recycle Bin, OS X's Trash Can, etc.).
-    pub fn delete(self) -> io::Result<()> {
-        use self::SaveDir::*;
-        match self {
-            Temp(tempdir) => tempdir.close(),
-            Perm(pathbuf) => fs::remove_dir_all(&pathbuf),
+    /// #### Feature: `archive`
+    #[cfg(feature = "archive")]
+    pub fn archive_to<W: Write>(mut self, dest: W, manifest: bool) -> EntriesSaveResult<M> {
+        let mut archive = ZipFields::new(dest, manifest);
+
+        self.opts.memory_only = true;
+        let res = self.save_all(Entries::new(SaveDir::Memory), Some(Stream::Archive(&mut archive)));
+
+        let saved = match res {
+            Full(ref entries) => Some(entries),
+            Partial(ref partial, _) => Some(&partial.entries),
+            Error(_) => None,
+        };
+
+        match (archive.finish(saved), res) {
+            (Ok(_), res) => res,
+            (Err(e), Full(entries)) => Partial(PartialEntries { entries, partial: None }, IoError(e)),
+            (Err(e), res) => {
+                warn!("Failed to finish the archive after the save stopped: {}", e);
+                res
+            },
         }
     }
-}
 
-impl AsRef<Path> for SaveDir {
-    fn as_ref(&self) -> &Path {
-        self.as_path()
-    }
-}
+    /// `with_entries()`, optionally streaming fields to a writer for `stream_field_to()`
+    /// or `archive_to()`.
+    fn save_all(self, entries: Entries, stream: Option<Stream>) -> EntriesSaveResult<M> {
+        let created_dirs = self.opts.created_dirs.clone();
+        let cleanup_created_dirs = self.opts.cleanup_created_dirs;
 
-/// The reason the save operation quit partway through.
-#[derive(Debug)]
-pub enum PartialReason {
-    /// The count limit for files in the request was hit.
-    ///
-    /// The associated file has not been saved to the filesystem.
-    CountLimit,
-    /// The size limit for an individual file was hit.
-    ///
-    /// The file was partially written to the filesystem.
-    SizeLimit,
-    /// An error occurred during the operation.
-    IoError(io::Error),
-    /// An error returned from validating a field as UTF-8 due to `SaveBuilder::force_text()`
-    Utf8Error(str::Utf8Error),
-}
+        let mut res = self.save_entries(entries, stream);
 
-impl From<io::Error> for PartialReason {
-    fn from(e: io::Error) -> Self {
-        IoError(e)
-    }
-}
+        match res {
+            Full(_) => (),
+            _ if cleanup_created_dirs => {
+                created_dirs.remove_empty();
 
-impl From<str::Utf8Error> for PartialReason {
-    fn from(e: str::Utf8Error) -> Self {
-        Utf8Error(e)
-    }
-}
+                if let Partial(ref mut partial, _) = res {
+                    partial.entries.stats.created_dirs = created_dirs.created();
+                }
+            },
+            _ => (),
+        }
 
-impl PartialReason {
-    /// Return `io::Error` in the `IoError` case or panic otherwise.
-    pub fn unwrap_err(self) -> io::Error {
-        self.expect_err("`PartialReason` was not `IoError`")
+        res
     }
 
-    /// Return `io::Error` in the `IoError` case or panic with the given
-    /// message otherwise.
-    pub fn expect_err(self, msg: &str) -> io::Error {
-        match self {
-            PartialReason::IoError(e) => e,
-            _ => panic!("{}: {:?}", msg, self),
+    fn save_entries(self, entries: Entries, stream: Option<Stream>) -> EntriesSaveResult<M> {
+        let SaveBuilder { savable, opts, require_first, validate_redacted } = self;
+
+        if opts.redact_fields.is_some() && savable.captures_raw() {
+            return Error(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "`redact_fields()` can't be combined with `Multipart::capture_raw()`, \
+                 which would keep a copy of the redacted fields"
+            ));
         }
+
+        let mut request = RequestSave::new(&opts, entries, stream, validate_redacted.as_ref());
+        request.record(&savable);
+
+        if let Err(reason) = request.start(require_first) {
+            return request.stop(None, reason);
+        }
+
+        let mut res = ReadEntry::read_entry(savable);
+
+        while request.fields_seen() < opts.count_limit {
+            let field = match res {
+                ReadEntryResult::Entry(field) => field,
+                ReadEntryResult::End(savable) => return request.finish(savable), // normal exit point
+                ReadEntryResult::Error(savable, e) => {
+                    request.record(&savable);
+                    return request.stop(None, e.into());
+                },
+            };
+
+            res = match request.save_field(field) {
+                Ok(savable) => ReadEntry::read_entry(savable),
+                Err(stop) => return match stop.reason {
+                    Ok(reason) => request.stop(stop.partial, reason),
+                    Err(e) => Error(e),
+                },
+            };
+        }
+
+        request.stop(None, CountLimit { limit: opts.count_limit })
     }
 }
 
-/// The field that was being read when the save operation quit.
-///
-/// May be partially saved to the filesystem if `dest` is `Some`.
-#[derive(Debug)]
-pub struct PartialSavedField<M: ReadEntry> {
-    /// The field that was being read.
-    ///
-    /// May be partially read if `dest` is `Some`.
-    pub source: MultipartField<M>,
-    /// The data from the saving operation, if it got that far.
-    pub dest: Option<SavedData>,
+/// The state of `SaveBuilder::with_entries()` while it saves the fields of a request.
+struct RequestSave<'a, 's> {
+    opts: &'a SaveOptions,
+    validate_redacted: Option<&'a Mutex<RedactedCheck>>,
+    stream: Option<Stream<'s>>,
+    entries: Entries,
+    /// `Some` until the fields required by `require_first()` have been checked
+    gate: Option<Gate>,
+    /// For `max_file_parts` and `max_text_parts`, counting the fields already in `entries`
+    file_parts: u32,
+    text_parts: u32,
+    /// For `total_size_limit`
+    total_saved: u64,
+    /// Set by a `_charset_` field if `honor_charset_field` is set
+    default_charset: Charset,
 }
 
-/// The partial result type for `Multipart::save*()`.
-///
-/// Contains the successfully saved entries as well as the partially
-/// saved file that was in the process of being read when the error occurred,
-/// if applicable.
-#[derive(Debug)]
-pub struct PartialEntries<M: ReadEntry> {
-    /// The entries that were saved successfully.
-    pub entries: Entries,
-    /// The field that was in the process of being read. `None` if the error
-    /// occurred between entries.
-    pub partial: Option<PartialSavedField<M>>,
+/// Why `RequestSave` stopped before the end of the request; boxed where it's returned,
+/// as it's large.
+struct Stop<M: ReadEntry> {
+    /// The field being saved, if there was one
+    partial: Option<PartialSavedField<M>>,
+    /// The reason for `SaveResult::Partial`, or the error for `SaveResult::Error`
+    reason: io::Result<PartialReason>,
 }
 
-/// Discards `partial`
-impl<M: ReadEntry> Into<Entries> for PartialEntries<M> {
-    fn into(self) -> Entries {
-        self.entries
+impl<M: ReadEntry> Stop<M> {
+    /// Stop at `field`, with what was saved of it.
+    fn at(field: MultipartField<M>, dest: Option<SavedData>, reason: PartialReason) -> Box<Self> {
+        Box::new(Stop { partial: Some(PartialSavedField { source: field, dest }), reason: Ok(reason) })
     }
-}
 
-impl<M: ReadEntry> PartialEntries<M> {
-    /// If `partial` is present and contains a `SavedFile` then just
-    /// add it to the `Entries` instance and return it.
-    ///
-    /// Otherwise, returns `self.entries`
-    pub fn keep_partial(mut self) -> Entries {
-        if let Some(partial) = self.partial {
-            if let Some(saved) = partial.dest {
-                self.entries.push_field(partial.source.headers, saved);
-            }
-        }
+    /// Stop between fields.
+    fn partial(reason: PartialReason) -> Box<Self> {
+        Box::new(Stop { partial: None, reason: Ok(reason) })
+    }
 
-        self.entries
+    fn error(error: io::Error) -> Box<Self> {
+        Box::new(Stop { partial: None, reason: Err(error) })
     }
 }
 
-/// The ternary result type used for the `SaveBuilder<_>` API.
-#[derive(Debug)]
-pub enum SaveResult<Success, Partial> {
-    /// The operation was a total success. Contained is the complete result.
-    Full(Success),
-    /// The operation quit partway through. Included is the partial
-    /// result along with the reason.
-    Partial(Partial, PartialReason),
-    /// An error occurred at the start of the operation, before anything was done.
-    Error(io::Error),
+/// How `RequestSave` saves a field, decided from its headers.
+struct FieldPlan {
+    /// Matched by `redact_fields()`
+    redacted: bool,
+    /// Written to the archive of `archive_to()`
+    archived: bool,
+    /// Written to the archive or the writer of `stream_field_to()`
+    streamed: bool,
+    /// Held back by `require_first()`
+    held_back: bool,
+    /// Where the field is cut off; the lowest of `size_limit` and the limits below
+    limit: u64,
+    /// What is left of the spool budget of `require_first()`
+    spool_limit: u64,
+    /// `text_size_limit`, if it applies
+    text_limit: u64,
 }
 
-/// Shorthand result for methods that return `Entries`
-pub type EntriesSaveResult<M> = SaveResult<Entries, PartialEntries<M>>;
+impl<'a, 's> RequestSave<'a, 's> {
+    fn new(opts: &'a SaveOptions, entries: Entries, stream: Option<Stream<'s>>,
+           validate_redacted: Option<&'a Mutex<RedactedCheck>>) -> Self {
+        let (mut file_parts, mut text_parts) = (0u32, 0u32);
 
-/// Shorthand result for methods that return `FieldData`s.
-///
-/// The `MultipartData` is not provided here because it is not necessary to return
-/// a borrow when the owned version is probably in the same scope. This hopefully
-/// saves some headache with the borrow-checker.
-pub type FieldSaveResult = SaveResult<SavedData, SavedData>;
+        for headers in entries.fields.values().flatten().map(|field| &field.headers)
+            .chain(entries.failures.iter().map(|failure| &failure.headers)) {
+            if headers.filename.is_some() {
+                file_parts = file_parts.saturating_add(1);
+            } else {
+                text_parts = text_parts.saturating_add(1);
+            }
+        }
 
-impl<M: ReadEntry> EntriesSaveResult<M> {
-    /// Take the `Entries` from `self`, if applicable, and discarding
-    /// the error, if any.
-    pub fn into_entries(self) -> Option<Entries> {
-        match self {
-            Full(entries) | Partial(PartialEntries { entries, .. }, _) => Some(entries),
-            Error(_) => None,
+        let total_saved = entries.fields.values().flatten()
+            .fold(0u64, |total, field| total.saturating_add(field.data.written()));
+
+        RequestSave {
+            opts, validate_redacted, stream, entries,
+            gate: None,
+            file_parts, text_parts, total_saved,
+            default_charset: Charset::Utf8,
         }
     }
-}
 
-impl<S, P> SaveResult<S, P> where P: Into<S> {
-    /// Convert `self` to `Option<S>`; there may still have been an error.
-    pub fn okish(self) -> Option<S> {
-        self.into_opt_both().0
-    }
+    /// Check that the request can be saved at all, and set up `require_first()`.
+    fn start(&mut self, require_first: Option<RequireFirst>) -> Result<(), PartialReason> {
+        if self.entries.recount_fields() >= self.opts.count_limit {
+            return Err(CountLimit { limit: self.opts.count_limit });
+        }
 
-    /// Map the `Full` or `Partial` values to a new type, retaining the reason
-    /// in the `Partial` case.
-    pub fn map<T, Map>(self, map: Map) -> SaveResult<T, T> where Map: FnOnce(S) -> T {
-        match self {
-            Full(full) => Full(map(full)),
-            Partial(partial, reason) => Partial(map(partial.into()), reason),
-            Error(e) => Error(e),
+        if self.is_cancelled() {
+            return Err(Cancelled);
         }
+
+        self.gate = require_first.map(Gate::new);
+        self.open_gate_if_complete()
     }
 
-    /// Decompose `self` to `(Option<S>, Option<io::Error>)`
-    pub fn into_opt_both(self) -> (Option<S>, Option<io::Error>) {
-        match self {
-            Full(full)  => (Some(full), None),
-            Partial(partial, IoError(e)) => (Some(partial.into()), Some(e)),
-            Partial(partial, _) => (Some(partial.into()), None),
-            Error(error) => (None, Some(error)),
-        }
+    /// The fields counted toward `max_parts()`; held-back and failed fields count as well.
+    fn fields_seen(&self) -> u32 {
+        self.entries.fields_count.saturating_add(self.gate.as_ref().map_or(0, Gate::held_count))
+            .saturating_add(cmp::min(self.entries.failures.len(), u32::MAX as usize) as u32)
+            .saturating_add(cmp::min(self.entries.ignored.len(), u32::MAX as usize) as u32)
     }
 
-    /// Map `self` to an `io::Result`, discarding the error in the `Partial` case.
-    pub fn into_result(self) -> io::Result<S> {
-        match self {
-            Full(entries) => Ok(entries),
-            Partial(partial, _) => Ok(partial.into()),
-            Error(error) => Err(error),
-        }
+    fn is_cancelled(&self) -> bool {
+        self.opts.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
     }
 
-    /// Pessimistic version of `into_result()` which will return an error even
-    /// for the `Partial` case.
-    ///
-    /// ### Note: Possible Storage Leak
-    /// It's generally not a good idea to ignore the `Partial` case, as there may still be a
-    /// partially written file on-disk. If you're not using a temporary directory
-    /// (OS-managed or via `TempDir`) then partially written files will remain on-disk until
-    /// explicitly removed which could result in excessive disk usage if not monitored closely.
-    pub fn into_result_strict(self) -> io::Result<S> {
-        match self {
-            Full(entries) => Ok(entries),
-            Partial(_, PartialReason::IoError(e)) | Error(e) => Err(e),
-            Partial(partial, _) => Ok(partial.into()),
-        }
+    fn record<M: ReadEntry>(&mut self, savable: &M) {
+        let opts = self.opts;
+        self.entries.stats.record(savable, &opts.open_files, &opts.created_dirs, &opts.name_collisions);
     }
-}
 
-fn create_dir_all(path: &Path) -> io::Result<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-    } else {
-        // RFC: return an error instead?
-        warn!("Attempting to save file in what looks like a root directory. File path: {:?}", path);
-        Ok(())
+    /// Run the check of `require_first()` once all of the required fields are in.
+    fn open_gate_if_complete(&mut self) -> Result<(), PartialReason> {
+        match self.gate.take() {
+            Some(gate) if gate.is_complete() => gate.open(&mut self.entries),
+            gate => {
+                self.gate = gate;
+                Ok(())
+            },
+        }
     }
-}
 
-fn try_copy_limited<R: BufRead, Wb: FnMut(&[u8]) -> SaveResult<usize, usize>>(src: R, mut with_buf: Wb, limit: u64) -> SaveResult<u64, u64> {
-    let mut copied = 0u64;
-    try_read_buf(src, |buf| {
-        let new_copied = copied.saturating_add(buf.len() as u64);
-        if new_copied > limit { return Partial(0, PartialReason::SizeLimit) }
-        copied = new_copied;
+    /// The request ended after the field last saved.
+    fn finish<M: ReadEntry>(mut self, savable: M) -> EntriesSaveResult<M> {
+        self.record(&savable);
+        self.entries.clean_termination = savable.finished_cleanly();
+        self.entries.streamed_field_missing = matches!(self.stream, Some(Stream::Field(..)));
 
-        with_buf(buf)
-    })
-}
+        if let Some(gate) = self.gate.take() {
+            let field = gate.missing[0].as_str().into();
+            gate.discard(&mut self.entries);
+            return Partial(PartialEntries { entries: self.entries, partial: None }, RequiredFieldMissing { field });
+        }
 
-fn try_read_buf<R: BufRead, Wb: FnMut(&[u8]) -> SaveResult<usize, usize>>(mut src: R, mut with_buf: Wb) -> SaveResult<u64, u64> {
-    let mut total_copied = 0u64;
+        Full(self.entries)
+    }
 
-    macro_rules! try_here (
-        ($try:expr) => (
-            match $try {
-                Ok(val) => val,
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                Err(e) => return if total_copied == 0 { Error(e) }
-                                 else { Partial(total_copied, e.into()) },
-            }
-        )
-    );
+    /// Stop saving the request, with the field being saved if there was one.
+    fn stop<M: ReadEntry>(mut self, partial: Option<PartialSavedField<M>>, reason: PartialReason) -> EntriesSaveResult<M> {
+        keep_held_if_panicked(&reason, &mut self.gate, &mut self.entries);
 
-    loop {
-        let res = {
-            let buf = try_here!(src.fill_buf());
-            if buf.is_empty() { break; }
-            with_buf(buf)
-        };
+        // the rest of the stats need the reader, which stays with the field
+        self.entries.stats.name_collisions = self.opts.name_collisions.load(Ordering::Relaxed);
 
-        match res {
-            Full(copied) => { src.consume(copied); total_copied += copied as u64; }
-            Partial(copied, reason) => {
-                src.consume(copied); total_copied += copied as u64;
-                return Partial(total_copied, reason);
-            },
-            Error(err) => {
-                return Partial(total_copied, err.into());
-            }
-        }
+        Partial(PartialEntries { entries: self.entries, partial }, reason)
     }
 
-    Full(total_copied)
-}
+    /// Save `field` into `entries`, returning the reader for the next field.
+    fn save_field<M: ReadEntry>(&mut self, mut field: MultipartField<M>) -> Result<M, Box<Stop<M>>> {
+        // before anything looks at the name
+        if self.opts.percent_decode_names {
+            field.headers.percent_decode_names();
+        }
 
-fn try_write_all<W: Write>(mut buf: &[u8], mut dest: W) -> SaveResult<usize, usize> {
-    let mut total_copied = 0;
+        if self.is_cancelled() {
+            return Err(Stop::at(field, None, Cancelled));
+        }
+
+        if self.opts.allowed_fields.as_ref().is_some_and(|allowed| !allowed.contains(&*field.headers.name)) {
+            return self.ignore_field(field);
+        }
+
+        let index = match self.count_part(&field.headers) {
+            Ok(index) => index,
+            Err(reason) => return Err(Stop::at(field, None, reason)),
+        };
+
+        if let Some(reason) = self.opts.filename_checks.and_then(|checks| check_filename(&field.headers, &checks)) {
+            return Err(Stop::at(field, None, reason));
+        }
+
+        self.save_data(field, index)
+    }
+
+    /// Read and discard a field which isn't in `allowed_fields()`.
+    fn ignore_field<M: ReadEntry>(&mut self, mut field: MultipartField<M>) -> Result<M, Box<Stop<M>>> {
+        if let Err(e) = io::copy(&mut field.data, &mut io::sink()) {
+            let savable = field.data.into_inner();
+            self.record(&savable);
+            return Err(Stop::partial(e.into()));
+        }
+
+        debug!("Ignoring field {:?}, which isn't in `allowed_fields()`", field.headers.name);
+        self.entries.ignored.push(field.headers);
+
+        let savable = field.data.into_inner();
+        self.record(&savable);
+        Ok(savable)
+    }
+
+    /// Count the field with `headers` toward `max_file_parts` or `max_text_parts`,
+    /// returning its index in the request for `with_file_namer()`.
+    fn count_part(&mut self, headers: &FieldHeaders) -> Result<u32, PartialReason> {
+        let opts = self.opts;
+        let index = self.file_parts.saturating_add(self.text_parts);
+
+        let is_file = headers.filename.is_some();
+        let parts = if is_file { &mut self.file_parts } else { &mut self.text_parts };
+
+        if *parts >= if is_file { opts.max_file_parts } else { opts.max_text_parts } {
+            return Err(if is_file {
+                FilePartLimit { limit: opts.max_file_parts }
+            } else {
+                TextPartLimit { limit: opts.max_text_parts }
+            });
+        }
+
+        *parts = parts.saturating_add(1);
+        Ok(index)
+    }
+
+    /// Decide how to save the field with `headers`.
+    fn plan_field(&self, headers: &FieldHeaders) -> FieldPlan {
+        let opts = self.opts;
+
+        // a redacted field isn't passed to `stream_field_to()` either
+        let redacted = opts.redact_fields.as_ref().is_some_and(|matcher| matcher.matches(&headers.name));
+
+        #[cfg(feature = "archive")]
+        let archived = !redacted && headers.filename.is_some() && matches!(self.stream, Some(Stream::Archive(_)));
+        #[cfg(not(feature = "archive"))]
+        let archived = false;
+
+        let streamed = archived || match self.stream {
+            Some(Stream::Field(name, _)) => !redacted && *headers.name == *name,
+            _ => false,
+        };
+
+        // a streamed field isn't spooled, so it's not limited by the spool budget
+        let held_back = self.gate.as_ref().is_some_and(|gate| !gate.is_required(&headers.name));
+        let spool_limit = match self.gate {
+            Some(ref gate) if held_back && !streamed => gate.remaining(),
+            _ => u64::MAX,
+        };
+        let text_limit = if headers.filename.is_none() && !streamed {
+            opts.text_size_limit
+        } else {
+            u64::MAX
+        };
+        // archived fields are kept, in the archive
+        let total_limit = if (streamed && !archived) || redacted {
+            u64::MAX
+        } else {
+            opts.total_size_limit.saturating_sub(self.total_saved)
+        };
+        let limit = cmp::min(cmp::min(opts.size_limit, spool_limit), cmp::min(text_limit, total_limit));
+
+        FieldPlan { redacted, archived, streamed, held_back, limit, spool_limit, text_limit }
+    }
+
+    /// Save the data of `field`, the one at `index` in the request, once its headers have
+    /// passed the checks.
+    fn save_data<M: ReadEntry>(&mut self, mut field: MultipartField<M>, index: u32) -> Result<M, Box<Stop<M>>> {
+        let opts = self.opts;
+        let plan = self.plan_field(&field.headers);
+
+        // a path the namer shouldn't have returned is a bug in the application rather than
+        // a problem with the request, so it stops the save with an error
+        let named_file = match opts.file_namer {
+            Some(ref namer) if !plan.redacted && !plan.streamed && !opts.dry_run => {
+                let path = match catch_callback("with_file_namer()", || namer_path(namer, &field.headers, index)) {
+                    Ok(path) => path,
+                    Err(panicked) => return Err(Stop::at(field, None, panicked)),
+                };
+
+                if !is_contained(&path) {
+                    return Err(Stop::error(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("file namer returned {:?}, which isn't a relative path within the directory", path)
+                    )));
+                }
+
+                Some(path)
+            },
+            _ => None,
+        };
+        let named = named_file.is_some();
+
+        let stream_dest: Option<&mut dyn Write> = match self.stream {
+            Some(Stream::Field(..)) if plan.streamed => match self.stream.take() {
+                Some(Stream::Field(_, dest)) => Some(dest),
+                _ => None,
+            },
+            #[cfg(feature = "archive")]
+            Some(Stream::Archive(ref mut archive)) if plan.archived => Some(archive.start_field(&field.headers)),
+            _ => None,
+        };
+
+        let data_start = if opts.collect_raw_ranges { field.data.data_offset() } else { None };
+
+        // timed from here, once the headers are parsed, so waiting for the field isn't counted
+        let started = (opts.clock)();
+        let (saved, timing) = match first_byte_after(&mut field.data, started, opts.clock) {
+            Ok(first_byte) => {
+                let saved = if plan.redacted {
+                    redact_field(&mut field, plan.limit, self.validate_redacted)
+                } else {
+                    save_field_data(opts, &mut field, &self.entries, named_file, self.default_charset,
+                                    plan.limit, stream_dest)
+                };
+                let last_byte = (opts.clock)().saturating_duration_since(started);
+                (saved, Some(FieldTiming { first_byte, last_byte }))
+            },
+            Err(e) => (Error(e), None),
+        };
+
+        let (dest, reason) = match saved {
+            Full(saved) => match self.check_saved(&field.headers, &saved, &plan) {
+                Ok(None) => return self.keep_saved(field, saved, &plan, timing, data_start),
+                Ok(Some(mismatch)) => {
+                    remove_files(&saved);
+                    (None, mismatch)
+                },
+                Err(e) => (Some(saved), e.into()),
+            },
+            Partial(saved, reason) => self.partial_reason(&field.headers, saved, reason, &plan),
+            // as is a file name from the namer which is already taken
+            Error(error) => if named && error.kind() == io::ErrorKind::AlreadyExists {
+                return Err(Stop::error(error));
+            } else {
+                (None, error.into())
+            },
+        };
+
+        if opts.field_error_policy == FieldErrorPolicy::SkipAndContinue && reason.is_field_error() {
+            return self.skip_failed(field, dest, reason);
+        }
+
+        Err(Stop::at(field, dest, reason))
+    }
+
+    /// Check the digests of a field which was saved in full, or finish its entry in the archive.
+    fn check_saved(&mut self, headers: &FieldHeaders, saved: &SavedData, plan: &FieldPlan)
+                   -> io::Result<Option<PartialReason>> {
+        // a dry run checks the digests as it reads
+        if plan.archived {
+            finish_archived(&mut self.stream).map(|()| None)
+        } else if self.opts.verify_digests && !plan.streamed && !plan.redacted && !saved.is_validated() {
+            check_digests(headers, saved)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Add a field which was saved in full to `entries`, or hold it back for `require_first()`.
+    fn keep_saved<M: ReadEntry>(&mut self, field: MultipartField<M>, saved: SavedData, plan: &FieldPlan,
+                                timing: Option<FieldTiming>, data_start: Option<u64>) -> Result<M, Box<Stop<M>>> {
+        let opts = self.opts;
+
+        // an archived field is `Streamed`, but kept in the archive
+        let kept = if plan.archived { saved.size() } else { saved.written() };
+        self.total_saved = self.total_saved.saturating_add(kept);
+
+        let is_charset_field = opts.honor_charset_field
+            && &*field.headers.name == "_charset_" && field.is_text();
+
+        if is_charset_field {
+            self.default_charset = charset_field_value(&saved);
+        }
+
+        if !is_charset_field || opts.keep_charset_field {
+            let sniffed = match saved {
+                SavedData::Validated { ref detected_type, .. } => Ok(detected_type.clone()),
+                _ => match opts.sniffer.as_ref().filter(|_| !plan.streamed && !plan.redacted) {
+                    Some(sniffer) => catch_callback("sniffer()", || {
+                        sniff_type(&**sniffer, &field.headers, &saved)
+                    }),
+                    None => Ok(None),
+                },
+            };
+
+            // the field is saved, so it's returned with its data
+            let detected_type = match sniffed {
+                Ok(detected_type) => detected_type,
+                Err(panicked) => return Err(Stop::at(field, Some(saved), panicked)),
+            };
+            let raw_range = raw_range_of(&field.data, data_start);
+            let saved = SavedField { headers: field.headers, data: saved, detected_type, timing, raw_range };
+
+            match self.gate {
+                Some(ref mut gate) => if plan.held_back {
+                    gate.hold(saved);
+                } else {
+                    gate.add_required(&mut self.entries, saved);
+                },
+                None => self.entries.push_saved(saved),
+            }
+        }
+
+        let savable = field.data.into_inner();
+        self.record(&savable);
+
+        if let Err(reason) = self.open_gate_if_complete() {
+            return Err(Stop::partial(reason));
+        }
+
+        Ok(savable)
+    }
+
+    /// What stops the save after a field was cut off or failed partway, and what is kept of it.
+    fn partial_reason(&mut self, headers: &FieldHeaders, saved: SavedData, reason: PartialReason, plan: &FieldPlan)
+                      -> (Option<SavedData>, PartialReason) {
+        let opts = self.opts;
+
+        match reason {
+            // the field was cut off by the spool budget rather than `size_limit`
+            SizeLimit { written, .. } if plan.limit < opts.size_limit && plan.limit == plan.spool_limit => {
+                remove_files(&saved);
+
+                let gate = self.gate.take().expect("held back a field without a gate");
+                let reason = SpoolBudget {
+                    limit: gate.spool_budget,
+                    spooled: gate.spooled_bytes.saturating_add(written),
+                };
+                gate.discard(&mut self.entries);
+
+                (None, reason)
+            },
+            SizeLimit { written, .. } if plan.limit < opts.size_limit && plan.limit == plan.text_limit => {
+                let reason = TextSizeLimit { field: headers.name.clone(), limit: opts.text_size_limit, written };
+                (Some(saved), reason)
+            },
+            SizeLimit { written, .. } if plan.limit < opts.size_limit => {
+                let reason = TotalSizeLimit { limit: opts.total_size_limit, saved: self.total_saved.saturating_add(written) };
+                (Some(saved), reason)
+            },
+            // from a dry run; nothing is kept, as when `check_digests()` fails
+            mismatch @ DigestMismatch { .. } => (None, mismatch),
+            reason => (Some(saved), reason),
+        }
+    }
+
+    /// Skip a field which failed, with `FieldErrorPolicy::SkipAndContinue`.
+    fn skip_failed<M: ReadEntry>(&mut self, mut field: MultipartField<M>, dest: Option<SavedData>,
+                                 reason: PartialReason) -> Result<M, Box<Stop<M>>> {
+        if let Some(ref saved) = dest {
+            remove_files(saved);
+        }
+
+        // if the rest of the field can't be read, the request itself is at fault
+        if let Err(e) = io::copy(&mut field.data, &mut io::sink()) {
+            let savable = field.data.into_inner();
+            self.record(&savable);
+            return Err(Stop::partial(e.into()));
+        }
+
+        warn!("Skipping field {:?}: {}", field.headers.name, reason);
+        self.entries.failures.push(FieldFailure { headers: field.headers, reason });
+
+        let savable = field.data.into_inner();
+        self.record(&savable);
+        Ok(savable)
+    }
+}
+
+/// Save the data of `field` as part of a request, to `dest` if it's streamed, with `size_limit`
+/// in place of the one in `opts`.
+fn save_field_data<M: ReadEntry>(opts: &SaveOptions, field: &mut MultipartField<M>, entries: &Entries,
+                                 named_file: Option<PathBuf>, default_charset: Charset, size_limit: u64,
+                                 dest: Option<&mut dyn Write>) -> FieldSaveResult {
+    let is_charset_field = opts.honor_charset_field
+        && &*field.headers.name == "_charset_" && field.is_text();
+
+    let has_charset = field.headers.content_type_param("charset").is_some();
+    let charset = if has_charset { Charset::Utf8 } else { default_charset };
+    let context = FieldContext { named_file, ..FieldContext::new(&field.headers, charset, opts.use_client_filename) };
+
+    let field_opts = SaveOptions {
+        size_limit,
+        // cleaned up once the whole request is saved
+        cleanup_created_dirs: false,
+        // run once the field is saved, or by `dry_run_field()` below
+        sniffer: None,
+        // redacted and ignored fields aren't saved with this
+        redact_fields: None,
+        allowed_fields: None,
+        ..opts.for_field(field)
+    };
+    let mut saver = SaveBuilder { savable: &mut field.data, opts: field_opts, require_first: None, validate_redacted: None };
+
+    match dest {
+        Some(dest) => match saver.write_field(dest, &context) {
+            Full(bytes) => Full(SavedData::Streamed { bytes }),
+            Partial(bytes, reason) => Partial(SavedData::Streamed { bytes }, reason),
+            Error(e) => Error(e),
+        },
+        None if opts.dry_run && !is_charset_field => saver.dry_run_field(&field.headers, opts.sniffer.as_deref(), &context),
+        None => {
+            // the value of a `_charset_` field is needed even in a dry run
+            saver.opts.memory_only |= opts.dry_run;
+            saver.save_in_dir(entries.save_dir.as_path(), &context)
+        },
+    }
+}
+
+/// Keep the fields held back by `require_first()` in `entries`, unchecked, if `reason` is
+/// `CallbackPanicked`; otherwise they're deleted with the gate as usual.
+fn keep_held_if_panicked(reason: &PartialReason, gate: &mut Option<Gate>, entries: &mut Entries) {
+    if let CallbackPanicked(_) = *reason {
+        if let Some(gate) = gate.take() {
+            gate.release(entries);
+        }
+    }
+}
+
+/// Finish the entry of the field just written to the archive of `SaveBuilder::archive_to()`.
+#[cfg(feature = "archive")]
+fn finish_archived(stream: &mut Option<Stream>) -> io::Result<()> {
+    match *stream {
+        Some(Stream::Archive(ref mut archive)) => archive.finish_field(),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "archive"))]
+fn finish_archived(_stream: &mut Option<Stream>) -> io::Result<()> {
+    Ok(())
+}
+
+/// Read the data of a field matched by `SaveBuilder::redact_fields()` and drop it, passing
+/// a text field to `validate` first.
+fn redact_field<M: ReadEntry>(field: &mut MultipartField<M>, size_limit: u64,
+                              validate: Option<&Mutex<RedactedCheck>>) -> FieldSaveResult {
+    let validate = validate.filter(|_| field.headers.filename.is_none());
+    let mut data = Vec::with_capacity(match validate {
+        Some(_) => cmp::min(size_limit.saturating_add(1), MAX_PREALLOC) as usize,
+        None => 0,
+    });
+
+    let read = {
+        let mut limited = (&mut field.data).take(size_limit.saturating_add(1));
+        let mut buf = [0u8; 8 * 1024];
+        let mut read = 0u64;
+
+        loop {
+            match limited.read(&mut buf) {
+                Ok(0) => break Ok(read),
+                Ok(len) => {
+                    read += len as u64;
+
+                    if validate.is_some() {
+                        extend_zeroing(&mut data, &buf[..len]);
+                    }
+                    zero(&mut buf[..len]);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => break Err(e),
+            }
+        }
+    };
+
+    let res = match read {
+        Err(e) => Partial(SavedData::Redacted { original_len: data.len() as u64 }, e.into()),
+        Ok(read) if read > size_limit => Partial(
+            SavedData::Redacted { original_len: size_limit },
+            SizeLimit { field: field.headers.name.clone(), limit: size_limit, written: size_limit }
+        ),
+        Ok(read) => {
+            let checked = match validate {
+                Some(validate) => catch_callback("validate_redacted()", || {
+                    (validate.lock().unwrap_or_else(|e| e.into_inner()))(&field.headers, &data)
+                }),
+                None => Ok(Ok(())),
+            };
+
+            match checked {
+                Ok(Ok(())) => Full(SavedData::Redacted { original_len: read }),
+                Ok(Err(abort)) => Partial(SavedData::Redacted { original_len: read }, Aborted(abort)),
+                Err(panicked) => Partial(SavedData::Redacted { original_len: read }, panicked),
+            }
+        },
+    };
+
+    // don't leave the data behind in freed memory
+    zero(&mut data);
+
+    res
+}
+
+/// Append `chunk` to `data`, moving it into a larger buffer first if it doesn't fit and
+/// zeroing the old one, so reallocating doesn't leave a copy of the data behind.
+fn extend_zeroing(data: &mut Vec<u8>, chunk: &[u8]) {
+    if data.capacity() - data.len() < chunk.len() {
+        let mut grown = Vec::with_capacity(cmp::max(data.capacity() * 2, data.len() + chunk.len()));
+        grown.extend_from_slice(data);
+        zero(data);
+        *data = grown;
+    }
+
+    data.extend_from_slice(chunk);
+}
+
+/// Overwrite `buf` with zeroes in a way the compiler can't optimize out.
+fn zero(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        *byte = 0;
+    }
+    ::std::hint::black_box(buf);
+}
+
+/// The check passed to `SaveBuilder::validate_redacted()`.
+type RedactedCheck = Box<dyn FnMut(&FieldHeaders, &[u8]) -> Result<(), Abort> + Send>;
+
+/// The check passed to `SaveBuilder::require_first()`.
+type RequiredFieldsCheck = Box<dyn FnMut(&Entries) -> Result<(), Abort> + Send>;
+
+/// The callback set with `SaveBuilder::with_file_namer()`.
+type FileNamer = Arc<Mutex<dyn FnMut(&FieldHeaders, u32) -> PathBuf + Send>>;
+
+/// Call `namer` for the field with `headers` at `index` in the request.
+fn namer_path(namer: &FileNamer, headers: &FieldHeaders, index: u32) -> PathBuf {
+    (namer.lock().unwrap_or_else(|e| e.into_inner()))(headers, index)
+}
+
+/// Call `callback`, the one passed to the `SaveBuilder` method `method`, catching a panic as
+/// `PartialReason::CallbackPanicked`.
+fn catch_callback<T, F: FnOnce() -> T>(method: &str, callback: F) -> Result<T, PartialReason> {
+    panic::catch_unwind(AssertUnwindSafe(callback)).map_err(|payload| {
+        let message = super::panic_message(&*payload);
+        CallbackPanicked(format!("the callback passed to `{}` panicked: {}", method, message))
+    })
+}
+
+/// Whether `path` names something inside the directory it's relative to.
+fn is_contained(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// What the field-save methods need to know about the field being saved, beyond the settings
+/// of the builder. The public methods for individual fields don't have the headers, so they
+/// use the default.
+#[derive(Debug)]
+struct FieldContext {
+    /// For the details of `SizeLimit` and `MemoryLimit`.
+    name: Arc<str>,
+    /// What text fields kept in memory are decoded from.
+    charset: Charset,
+    /// The `Content-Length` of the field, if the client sent one.
+    size_hint: u64,
+    /// The sanitized filename of the field, with `use_client_filename()`.
+    client_filename: Option<String>,
+    /// The path `with_file_namer()` returned for the field.
+    named_file: Option<PathBuf>,
+}
+
+impl FieldContext {
+    fn new(headers: &FieldHeaders, charset: Charset, use_client_filename: bool) -> Self {
+        FieldContext {
+            name: headers.name.clone(),
+            charset,
+            size_hint: headers.size.unwrap_or(0),
+            client_filename: if use_client_filename { client_filename(headers) } else { None },
+            named_file: None,
+        }
+    }
+}
+
+impl Default for FieldContext {
+    fn default() -> Self {
+        FieldContext {
+            name: "".into(),
+            charset: Charset::Utf8,
+            size_hint: 0,
+            client_filename: None,
+            named_file: None,
+        }
+    }
+}
+
+/// The settings of `SaveBuilder::require_first()`.
+struct RequireFirst {
+    field_names: Vec<String>,
+    spool_budget: u64,
+    /// Only locked once it's moved into a `Gate`; this keeps `SaveBuilder` `Sync`
+    /// so a `SaveBuilderConfig` can be shared.
+    check: Mutex<RequiredFieldsCheck>,
+}
+
+/// The fields held back by `SaveBuilder::require_first()` until its check runs.
+///
+/// The files of any fields still held back are deleted when this is dropped.
+struct Gate {
+    check: RequiredFieldsCheck,
+    spool_budget: u64,
+    /// The required fields which have yet to arrive.
+    missing: Vec<String>,
+    /// The names of the required fields added to `Entries`, to take back out if necessary.
+    required: Vec<Arc<str>>,
+    held: Vec<SavedField>,
+    spooled_bytes: u64,
+}
+
+impl Gate {
+    fn new(require_first: RequireFirst) -> Self {
+        Gate {
+            check: require_first.check.into_inner().unwrap_or_else(|e| e.into_inner()),
+            spool_budget: require_first.spool_budget,
+            missing: require_first.field_names,
+            required: Vec::new(),
+            held: Vec::new(),
+            spooled_bytes: 0,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    fn is_required(&self, name: &str) -> bool {
+        self.missing.iter().any(|missing| missing == name)
+    }
+
+    fn held_count(&self) -> u32 {
+        cmp::min(u32::MAX as usize, self.held.len()) as u32
+    }
+
+    /// How much more may be held back.
+    fn remaining(&self) -> u64 {
+        self.spool_budget.saturating_sub(self.spooled_bytes)
+    }
+
+    fn hold(&mut self, field: SavedField) {
+        self.spooled_bytes = self.spooled_bytes.saturating_add(field.data.written());
+        self.held.push(field);
+    }
+
+    fn add_required(&mut self, entries: &mut Entries, field: SavedField) {
+        self.missing.retain(|missing| **missing != *field.headers.name);
+        self.required.push(field.headers.name.clone());
+        entries.push_saved(field);
+    }
+
+    /// Run the check, then add the held-back fields to `entries` or discard everything.
+    fn open(mut self, entries: &mut Entries) -> Result<(), PartialReason> {
+        match catch_callback("require_first()", || (self.check)(entries)) {
+            Ok(Ok(())) => {
+                self.release(entries);
+                Ok(())
+            },
+            Ok(Err(abort)) => {
+                self.discard(entries);
+                Err(Aborted(abort))
+            },
+            Err(panicked) => {
+                self.release(entries);
+                Err(panicked)
+            },
+        }
+    }
+
+    /// Add the held-back fields to `entries`, checked or not.
+    fn release(mut self, entries: &mut Entries) {
+        for field in self.held.drain(..) {
+            entries.push_saved(field);
+        }
+    }
+
+    /// Take the required fields back out of `entries`, deleting their files
+    /// along with those of the held-back fields.
+    fn discard(self, entries: &mut Entries) {
+        for name in &self.required {
+            // the required field is the last one of its name, as the others are held back
+            let now_empty = match entries.fields.get_mut(name) {
+                Some(fields) => {
+                    if let Some(field) = fields.pop() {
+                        remove_files(&field.data);
+                    }
+
+                    fields.is_empty()
+                },
+                None => false,
+            };
+
+            if now_empty {
+                entries.fields.remove(name);
+            }
+        }
+
+        entries.recount_fields();
+        entries.recount_totals();
+    }
+}
+
+impl Drop for Gate {
+    fn drop(&mut self) {
+        for field in &self.held {
+            remove_files(&field.data);
+        }
+    }
+}
+
+/// The settings of a `SaveBuilder` without a request to save, for saving fields one at a time
+/// with `MultipartField::into_owned()`, or for sharing one configuration between many saves
+/// with `apply()`.
+pub type SaveBuilderConfig = SaveBuilder<()>;
+
+impl SaveBuilder<()> {
+    /// Get the default settings, which can be changed with the setters common to all builders.
+    pub fn config() -> SaveBuilderConfig {
+        SaveBuilder::new(())
+    }
+
+    /// Replace the settings of `builder` with these, e.g. to configure every save done by
+    /// a framework integration in one place.
+    ///
+    /// ```rust
+    /// # use multipart::server::{Multipart, SaveBuilderConfig};
+    /// # let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"foo\"\r\n\r\nbar\r\n--boundary--\r\n"[..];
+    /// let config = SaveBuilderConfig::config().size_limit(1024 * 1024).max_parts(16);
+    ///
+    /// let mut multipart = Multipart::with_body(body, "boundary");
+    /// let entries = config.apply(multipart.save()).temp().into_result_strict().unwrap();
+    /// assert_eq!(entries.fields["foo"][0].as_text(), Some("bar"));
+    /// ```
+    ///
+    /// Settings can still be changed on the result. The check passed to `require_first()`
+    /// can't be shared, so `builder` keeps its own.
+    ///
+    /// Also see `bind()` and `Multipart::save_with()`.
+    pub fn apply<S>(&self, builder: SaveBuilder<S>) -> SaveBuilder<S> {
+        let opts = SaveOptions {
+            // counted per save
+            open_files: builder.opts.open_files,
+            created_dirs: builder.opts.created_dirs,
+            name_collisions: builder.opts.name_collisions,
+            ..self.opts.clone()
+        };
+
+        SaveBuilder { opts, ..builder }
+    }
+
+    /// Get a builder for `savable` with these settings; the same as `apply()` to a new builder.
+    ///
+    /// This doesn't consume `self`, so one configuration can be bound to any number of requests,
+    /// including from several threads at once (`SaveBuilderConfig` is `Send + Sync`).
+    pub fn bind<S>(&self, savable: S) -> SaveBuilder<S> {
+        self.apply(SaveBuilder::new(savable))
+    }
+}
+
+/// Keeping an exact copy of the request body, e.g. to forward it unchanged.
+impl<R: Read> Multipart<R> {
+    /// Keep a copy of every byte read from the request body from now on, including the preamble,
+    /// boundaries, field headers and anything the parser skips or discards, to be returned
+    /// by `raw_body()`.
+    ///
+    /// The copy is kept in memory up to `spool`'s memory threshold and in a file with a random
+    /// name in the OS temporary directory (or `spool`'s `temp_parent()`) beyond that. The caller
+    /// is responsible for deleting the file. The other settings of `spool` aren't used.
+    ///
+    /// Only bytes read after this call are copied, so it should be called before reading
+    /// any fields.
+    ///
+    /// ```rust
+    /// # use multipart::server::{Multipart, SaveBuilderConfig};
+    /// # use std::io::Read;
+    /// let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"foo\"\r\n\r\nbar\r\n--boundary--\r\n"[..];
+    ///
+    /// let mut multipart = Multipart::with_body(body, "boundary");
+    /// multipart.capture_raw(&SaveBuilderConfig::config());
+    ///
+    /// let entries = multipart.save().temp().into_result_strict().unwrap();
+    /// assert_eq!(entries.fields["foo"][0].as_text(), Some("bar"));
+    ///
+    /// let mut raw = Vec::new();
+    /// multipart.raw_body().unwrap().unwrap().readable().unwrap().read_to_end(&mut raw).unwrap();
+    /// assert_eq!(raw, body);
+    /// ```
+    pub fn capture_raw(&mut self, spool: &SaveBuilderConfig) -> &mut Self {
+        self.reader.set_raw_spool(RawSpool::new(spool.opts.memory_threshold, spool.opts.temp_parent.clone()));
+        self
+    }
+
+    /// Read the rest of the request body, including anything after the closing boundary,
+    /// and return the copy started by `capture_raw()`, or `None` if it wasn't called.
+    ///
+    /// No more fields can be read after this, and the copy stops.
+    ///
+    /// ## Errors
+    /// If reading the rest of the body or writing the copy to a file failed; any file is deleted.
+    pub fn raw_body(&mut self) -> io::Result<Option<SavedData>> {
+        match self.reader.take_raw_spool()? {
+            Some(spool) => spool.finish().map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A field read out of the request by `MultipartField::into_owned()`.
+///
+/// It doesn't borrow the request, so it's `Send + 'static` and can be handed to another thread.
+pub type OwnedField = SavedField;
+
+impl<M: ReadEntry> MultipartField<M> {
+    /// Read all of this field's data with the settings of `spool`, returning it along with
+    /// the `Multipart` (or `&mut Multipart`) to continue reading fields from.
+    ///
+    /// Data over `spool`'s memory threshold is written to a file with a random name in the OS
+    /// temporary directory (or `spool`'s `temp_parent()`), which the caller is responsible for
+    /// deleting.
+    ///
+    /// If a limit is reached, any file is deleted and an error of kind `InvalidData` is returned,
+    /// as it is for a suspicious filename if `spool` rejects them.
+    ///
+    /// ```rust
+    /// # use multipart::server::{Multipart, SaveBuilderConfig};
+    /// # let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"foo\"\r\n\r\nbar\r\n--boundary--"[..];
+    /// let spool = SaveBuilderConfig::config().size_limit(1024 * 1024);
+    /// let multipart = Multipart::with_body(body, "boundary");
+    ///
+    /// let field = multipart.into_entry().unwrap_opt().unwrap();
+    /// let (owned, multipart) = field.into_owned(&spool).unwrap();
+    ///
+    /// ::std::thread::spawn(move || assert_eq!(owned.as_text(), Some("bar"))).join().unwrap();
+    /// assert!(multipart.into_entry().unwrap_opt().is_none());
+    /// ```
+    pub fn into_owned(mut self, spool: &SaveBuilderConfig) -> io::Result<(OwnedField, M)> {
+        if let Some(reason) = spool.opts.filename_checks.and_then(|checks| check_filename(&self.headers, &checks)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, reason.to_string()));
+        }
+
+        let data_start = if spool.opts.collect_raw_ranges { self.data.data_offset() } else { None };
+        let started = (spool.opts.clock)();
+        let first_byte = first_byte_after(&mut self.data, started, spool.opts.clock)?;
+        let context = FieldContext::new(&self.headers, Charset::Utf8, spool.opts.use_client_filename);
+        let res = spool.for_field(&mut self).save_temp(&context);
+        let timing = FieldTiming { first_byte, last_byte: (spool.opts.clock)().saturating_duration_since(started) };
+
+        let data = match res {
+            Full(data) => data,
+            Partial(data, reason) => {
+                remove_files(&data);
+
+                return Err(match reason {
+                    IoError(e) | InsufficientStorage { error: e, .. } => e,
+                    reason => io::Error::new(io::ErrorKind::InvalidData, reason.to_string()),
+                });
+            },
+            Error(e) => return Err(e),
+        };
+
+        let detected_type = spool.opts.sniffer.as_ref().and_then(|s| sniff_type(&**s, &self.headers, &data));
+        let raw_range = raw_range_of(&self.data, data_start);
+        Ok((SavedField { headers: self.headers, data, detected_type, timing: Some(timing), raw_range }, self.data.into_inner()))
+    }
+}
+
+/// Save API for individual fields.
+impl<'m, M: 'm> SaveBuilder<&'m mut MultipartData<M>> where M: ReadEntry {
+    /// Save the field data, potentially using a file with a random name in the
+    /// OS temporary directory, or the one set with `temp_parent()`.
+    ///
+    /// See `with_path()` for more details.
+    pub fn temp(&mut self) -> FieldSaveResult {
+        self.save_temp(&FieldContext::default())
+    }
+
+    /// `temp()` for the field described by `context`.
+    fn save_temp(&mut self, context: &FieldContext) -> FieldSaveResult {
+        let path = self.opts.temp_parent.clone().unwrap_or_else(env::temp_dir).join(self.random_filename());
+        self.save_at(path, true, context)
+    }
+
+    /// Save the field data, potentially using a file with the given name in
+    /// the OS temporary directory.
+    ///
+    /// See `with_path()` for more details.
+    pub fn with_filename(&mut self, filename: &str) -> FieldSaveResult {
+        let mut tempdir = env::temp_dir();
+        tempdir.set_file_name(filename);
+
+        self.with_path(tempdir)
+    }
+
+    /// Save the field data, potentially using a file with a random alphanumeric name
+    /// (or the client's filename, with `use_client_filename()`) in the given directory.
+    ///
+    /// See `with_path()` for more details.
+    pub fn with_dir<P: AsRef<Path>>(&mut self, dir: P) -> FieldSaveResult {
+        self.save_in_dir(dir.as_ref(), &FieldContext::default())
+    }
+
+    /// `with_dir()` for the field described by `context`, using the path from the file namer
+    /// or the client's filename if it has one.
+    fn save_in_dir(&mut self, dir: &Path, context: &FieldContext) -> FieldSaveResult {
+        if let Some(ref path) = context.named_file {
+            return self.save_at(dir.join(path), false, context);
+        }
+
+        let name = match context.client_filename {
+            Some(ref name) if self.opts.chunk_size == u64::MAX => name.clone(),
+            _ => self.random_filename(),
+        };
+
+        self.save_at(dir.join(name), true, context)
+    }
+
+    /// Save the field data, potentially using a file with the given path.
+    ///
+    /// Creates any missing directories in the path (RFC: skip this step?).
+    /// Uses the contained `OpenOptions` to create the file.
+    /// Truncates the file to the given `size_limit`, if set.
+    ///
+    /// The no directories or files will be created until the set `memory_threshold` is reached.
+    /// If `size_limit` is set and less than or equal to `memory_threshold`,
+    /// then the disk will never be touched.
+    pub fn with_path<P: Into<PathBuf>>(&mut self, path: P) -> FieldSaveResult {
+        self.save_at(path.into(), false, &FieldContext::default())
+    }
+
+    /// Save the field data in memory only, as `SaveBuilder::in_memory()` does: a field larger
+    /// than `memory_threshold` is returned with `PartialReason::MemoryLimit`, or `SizeLimit`
+    /// if that's the smaller limit.
+    fn in_mem(&mut self) -> FieldSaveResult {
+        self.opts.memory_only = true;
+        self.save_at(PathBuf::new(), false, &FieldContext::default())
+    }
+
+    /// `with_path()`; if `random_name`, the file name may be replaced if it's already taken.
+    fn save_at(&mut self, path: PathBuf, random_name: bool, context: &FieldContext) -> FieldSaveResult {
+        match self.save_path(path, random_name, context) {
+            Full(saved) => Full(saved),
+            Partial(saved, reason) => {
+                self.clean_up_dirs();
+                let reason = self.limit_details(reason, saved.written(), &context.name);
+                Partial(saved, reason)
+            },
+            Error(e) => {
+                self.clean_up_dirs();
+                Error(e)
+            },
+        }
+    }
+
+    fn save_path(&mut self, path: PathBuf, random_name: bool, context: &FieldContext) -> FieldSaveResult {
+        self.filter_newlines();
+
+        // with a threshold of zero, even an empty field gets a file
+        if self.opts.memory_threshold == 0 && !self.opts.memory_only {
+            let bytes = self.new_buf(context.size_hint);
+            return self.save_to_path(bytes, path, random_name, context);
+        }
+
+        let bytes = if self.opts.text_policy != Ignore && context.charset != Charset::Utf8 {
+            // decoding can't fail, but only fields kept in memory are decoded
+            let (bytes, reason) = match self.save_mem(self.new_buf(context.size_hint)) {
+                Full(bytes) => return Full(self.saved_decoded(bytes, context.charset)),
+                Partial(bytes, reason) => (bytes, reason),
+                Error(e) => return Error(e),
+            };
+            match reason {
+                SizeLimit { .. } if !self.cmp_size_limit(bytes.len()) => bytes,
+                other => return Partial(self.saved_decoded(bytes, context.charset), other),
+            }
+        } else if self.opts.text_policy != Ignore {
+            let (text, reason) = match self.save_text(context.size_hint) {
+                Full(text) => return Full(self.saved_text(text)),
+                Partial(text, reason) => (text, reason),
+                Error(e) => return Error(e),
+            };
+            match reason {
+                SizeLimit { .. } if !self.cmp_size_limit(text.len()) => text.into_bytes(),
+                Utf8Error(_) | Noncharacter(_) if self.opts.text_policy != Force => text.into_bytes(),
+                other => return Partial(self.saved_text(text), other),
+            }
+        } else {
+            self.new_buf(context.size_hint)
+        };
+
+        let (bytes, reason) = match self.save_mem(bytes) {
+            Full(bytes) => return Full(self.saved_bytes(bytes)),
+            Partial(bytes, reason) => (bytes, reason),
+            Error(e) => return Error(e),
+        };
+
+        match reason {
+            // whole buffers are read, so `bytes` may not have reached either limit
+            SizeLimit { .. } if self.opts.memory_only && self.opts.size_limit > self.opts.memory_threshold =>
+                return Partial(self.saved_bytes(bytes), PartialReason::memory_limit()),
+            SizeLimit { .. } if self.opts.memory_only => return Partial(self.saved_bytes(bytes), reason),
+            SizeLimit { .. } if !self.cmp_size_limit(bytes.len()) => (),
+            other => return Partial(self.saved_bytes(bytes), other)
+        }
+
+        self.save_to_path(bytes, path, random_name, context)
+    }
+
+    /// Write `bytes` already read into memory and then the rest of the field to `path`.
+    ///
+    /// If `random_name`, a taken file name is replaced with another (not for `chunk_files()`).
+    fn save_to_path(&mut self, bytes: Vec<u8>, mut path: PathBuf, random_name: bool, context: &FieldContext)
+                    -> FieldSaveResult {
+        let res = if self.opts.chunk_size < u64::MAX {
+            match self.create_dirs_for(&path) {
+                Ok(()) => self.save_chunked(&bytes, &path),
+                Err(e) => Error(e),
+            }
+        } else {
+            match self.create_dirs_for(&path).and_then(|_| self.open_new_file(&mut path, random_name, context)) {
+                Ok((file, _open)) => match self.opts.file_backend {
+                    FileBackend::Std => self.save_file(&bytes, path, file),
+                    #[cfg(feature = "io-uring")]
+                    FileBackend::IoUring(queue_depth) =>
+                        self.save_file_ring(&bytes, path, file, queue_depth),
+                },
+                Err(e) => Error(e),
+            }
+        };
+
+        if let Some(ref pool) = self.opts.buffer_pool {
+            pool.put(bytes);
+        }
+
+        res
+    }
+
+    /// Convert the line endings of the field according to `normalize_newlines()`,
+    /// if it's read as text.
+    fn filter_newlines(&mut self) {
+        if self.opts.text_policy != Ignore {
+            self.savable.normalize_newlines(self.opts.newlines);
+        }
+    }
+
+    /// Open a file with `open_opts`, unless that would exceed `max_open_files`.
+    ///
+    /// The file counts as open until the returned guard is dropped.
+    fn open_file(&self, path: &Path) -> io::Result<(File, OpenFileGuard)> {
+        let guard = OpenFiles::open(&self.opts.open_files, self.opts.max_open_files)?;
+        Ok((self.opts.open_opts.open(path)?, guard))
+    }
+
+    /// `open_file()`, but if `random_name` and the file already exists, try another random name
+    /// in the same directory, updating `path`.
+    fn open_new_file(&self, path: &mut PathBuf, random_name: bool, context: &FieldContext)
+                     -> io::Result<(File, OpenFileGuard)> {
+        let mut attempts = 1;
+
+        loop {
+            match self.open_file(path) {
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists
+                    && random_name && attempts < NAME_ATTEMPTS => {
+                    let retry = path.with_file_name(self.random_filename());
+
+                    // only a random name colliding is a concern
+                    if attempts == 1 && context.client_filename.as_ref().is_some_and(|name| path.ends_with(name)) {
+                        debug!("Client filename {:?} already taken, trying {:?}", path, retry);
+                    } else {
+                        warn!("Random file name {:?} already taken, trying {:?}", path, retry);
+                        self.opts.name_collisions.fetch_add(1, Ordering::Relaxed);
+                    }
+                    attempts += 1;
+                    *path = retry;
+                },
+                res => return res,
+            }
+        }
+    }
+
+    fn save_file(&mut self, bytes: &[u8], path: PathBuf, mut file: File) -> FieldSaveResult {
+        let res = match try_write_all(bytes, &mut file, &self.opts.retry_policy) {
+            Full(pre_written) => {
+                let pre_written = pre_written as u64;
+
+                match self.write_rest(&mut file, pre_written, None) {
+                    Error(e) if pre_written > 0 => Partial(pre_written, e.into()),
+                    other => other.map(|written| pre_written + written),
+                }
+            },
+            other => other.map(|written| written as u64),
+        };
+
+        res.map(|written| saved_file(path, &mut file, written))
+    }
+
+    /// Write `bytes` followed by the rest of the field to files of at most `chunk_size` bytes.
+    fn save_chunked(&mut self, mut bytes: &[u8], path: &Path) -> FieldSaveResult {
+        let mut chunks: Vec<(PathBuf, u64)> = Vec::new();
+        let mut total = 0u64;
+
+        macro_rules! try_chunk (
+            ($try:expr) => (
+                match $try {
+                    Ok(val) => val,
+                    Err(e) => return if chunks.is_empty() { Error(e) }
+                                     else { Partial(SavedData::Chunked(chunks), e.into()) },
+                }
+            )
+        );
+
+        loop {
+            // don't create a chunk without any data to write to it
+            if bytes.is_empty() && try_chunk!(self.savable.fill_buf()).is_empty() {
+                return Full(SavedData::Chunked(chunks));
+            }
+
+            if total >= self.opts.size_limit {
+                return Partial(SavedData::Chunked(chunks), PartialReason::size_limit());
+            }
+
+            let chunk_path = chunk_path(path, chunks.len());
+            let (mut file, _open) = try_chunk!(self.open_file(&chunk_path));
+            chunks.push((chunk_path, 0));
+
+            let room = cmp::min(self.opts.chunk_size, self.opts.size_limit - total);
+            let from_mem = cmp::min(bytes.len() as u64, room) as usize;
+
+            let mut res = try_write_all(&bytes[..from_mem], &mut file, &self.opts.retry_policy)
+                .map(|written| written as u64);
+            bytes = &bytes[from_mem..];
+
+            if let Full(mut written) = res {
+                res = if bytes.is_empty() && written < room {
+                    let retry_policy = &self.opts.retry_policy;
+                    let throttle = self.opts.throttle.as_ref();
+                    let cancel = self.opts.cancel.as_ref();
+
+                    try_read_buf(&mut self.savable, |buf| {
+                        let len = cmp::min(buf.len() as u64, room - written) as usize;
+                        let res = paced(try_write_all(&buf[..len], &mut file, retry_policy), throttle);
+
+                        match checked(res, cancel) {
+                            // the chunk is full, or the limit was reached
+                            Full(copied) if copied < buf.len() => {
+                                written += copied as u64;
+                                Partial(copied, PartialReason::size_limit())
+                            },
+                            Full(copied) => { written += copied as u64; Full(copied) },
+                            Partial(copied, Cancelled) => { written += copied as u64; Partial(copied, Cancelled) },
+                            other => other,
+                        }
+                    }).map(|_| written)
+                } else if bytes.is_empty() {
+                    Full(written)
+                } else {
+                    Partial(written, PartialReason::size_limit())
+                };
+            }
+
+            let (written, reason) = match res {
+                // the end of the field was reached
+                Full(written) => (written, None),
+                // the loop checks if the field continues in the next chunk
+                Partial(written, reason) => (written, Some(reason)),
+                Error(e) => (0, Some(e.into())),
+            };
+
+            chunks.last_mut().expect("chunk was just pushed").1 = written;
+            total += written;
+
+            match reason {
+                None => return Full(SavedData::Chunked(chunks)),
+                Some(SizeLimit { .. }) => (),
+                Some(reason) => return Partial(SavedData::Chunked(chunks), reason),
+            }
+        }
+    }
+
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    fn save_file_ring(&mut self, bytes: &[u8], path: PathBuf, mut file: File, queue_depth: u32)
+                      -> FieldSaveResult {
+        let mut ring = match ::server::uring::RingWriter::new(&file, queue_depth) {
+            Ok(ring) => ring,
+            Err(e) => {
+                warn!("Failed to set up io_uring, falling back to blocking writes: {}", e);
+                return self.save_file(bytes, path, file);
+            }
+        };
+
+        let res = match try_write_all(bytes, &mut ring, &self.opts.retry_policy) {
+            Full(written) => self.write_rest(&mut ring, written as u64, None).map(|_| ()),
+            other => other.map(|_| ()),
+        };
+
+        // the ring knows how much was actually written to disk
+        let (written, ring_err) = ring.finish();
+        let data = saved_file(path, &mut file, written);
+
+        match (res, ring_err) {
+            (Full(_), None) => Full(data),
+            (Partial(_, reason), None) => Partial(data, reason),
+            (Partial(_, IoError(_)), Some(e)) | (Partial(_, InsufficientStorage { .. }), Some(e))
+            | (Full(_), Some(e)) => Partial(data, e.into()),
+            (Partial(_, reason), Some(_)) => Partial(data, reason),
+            (Error(e), _) => if written > 0 { Partial(data, e.into()) } else { Error(e) },
+        }
+    }
+
+    #[cfg(all(feature = "io-uring", not(target_os = "linux")))]
+    fn save_file_ring(&mut self, bytes: &[u8], path: PathBuf, file: File, _queue_depth: u32)
+                      -> FieldSaveResult {
+        self.save_file(bytes, path, file)
+    }
+
+
+    /// Write out the field data to `dest`, truncating if a limit was set.
+    ///
+    /// Returns the number of bytes copied, and whether or not the limit was reached:
+    /// a field of exactly `size_limit` bytes is `Full`, while a longer one is `Partial`
+    /// with exactly `size_limit` bytes written. The end of the field is found with
+    /// `MultipartData::fill_buf()`, which only returns an empty buffer at the boundary
+    /// (or the end of the request).
+    ///
+    /// Retries on interrupts. With `flush_every()`, `dest` is flushed along the way.
+    pub fn write_to<W: Write>(&mut self, dest: W) -> SaveResult<u64, u64> {
+        self.write_field(dest, &FieldContext::default())
+    }
+
+    /// `write_to()` for the field described by `context`.
+    fn write_field<W: Write>(&mut self, dest: W, context: &FieldContext) -> SaveResult<u64, u64> {
+        self.filter_newlines();
+
+        let flush_every = self.opts.flush_every;
+
+        match self.write_rest(dest, 0, flush_every) {
+            Partial(written, reason) => Partial(written, self.limit_details(reason, written, &context.name)),
+            other => other,
+        }
+    }
+
+    /// Write out the field data into `file` starting at `offset`, e.g. to assemble an upload
+    /// sent in pieces as separate fields into one preallocated file. Otherwise the same as
+    /// `write_to()`, including the `size_limit`.
+    ///
+    /// Uses positional writes where the platform has them (`pwrite()` on Unix, `WriteFile()`
+    /// with an offset on Windows), so the same `File` can be written to from several threads
+    /// at once, as long as they write to separate ranges. On Unix the file's cursor is left
+    /// as it was; on Windows it's moved to the end of the data written. On other platforms
+    /// this seeks before each write, so the writes must not happen concurrently.
+    pub fn write_at(&mut self, file: &File, offset: u64) -> SaveResult<u64, u64> {
+        self.write_to(FileAt { file, offset })
+    }
+
+    /// Read the field for `SaveBuilder::dry_run()`, checking it as `with_dir()` would,
+    /// but keeping only `SavedData::Validated`.
+    fn dry_run_field(&mut self, headers: &FieldHeaders, sniffer: Option<&dyn TypeSniffer>, context: &FieldContext)
+                     -> FieldSaveResult {
+        // only fields kept in memory are checked as text
+        let check_text = self.opts.text_policy == Force && context.charset == Charset::Utf8 && self.opts.memory_threshold > 0;
+
+        let mut sink = DryRunSink {
+            len: 0,
+            prefix: Vec::new(),
+            digests: DryRunDigests::new(headers, self.opts.verify_digests),
+            text: if check_text {
+                Some(TextCheck::new(self.opts.text_norm == TextNorm::RejectNoncharacters, self.opts.memory_threshold))
+            } else {
+                None
+            },
+        };
+
+        let res = self.write_field(&mut sink, context);
+
+        let DryRunSink { len, prefix, digests, text } = sink;
+        let (digest, mismatch) = digests.finish(headers);
+
+        let reason = match res {
+            Full(_) => text.and_then(TextCheck::finish).or(mismatch),
+            Partial(_, reason) => Some(reason),
+            Error(e) => return Error(e),
+        };
+
+        // only complete fields are sniffed, as in a real save
+        let (detected_type, reason) = match (reason, sniffer) {
+            (None, Some(sniffer)) => match catch_callback("sniffer()", || sniffer.sniff(&prefix, headers)) {
+                Ok(detected_type) => (detected_type, None),
+                Err(panicked) => (None, Some(panicked)),
+            },
+            (reason, _) => (None, reason),
+        };
+
+        let validated = SavedData::Validated { len, detected_type, digest };
+
+        match reason {
+            None => Full(validated),
+            Some(reason) => Partial(validated, reason),
+        }
+    }
+
+    /// Fill in the details of a `SizeLimit` or `MemoryLimit` for the field named `field`,
+    /// of which `written` bytes were saved.
+    fn limit_details(&self, reason: PartialReason, written: u64, field: &Arc<str>) -> PartialReason {
+        let field = field.clone();
+
+        match reason {
+            SizeLimit { .. } => SizeLimit { field, limit: self.opts.size_limit, written },
+            MemoryLimit { .. } => MemoryLimit { field, limit: self.opts.memory_threshold, written },
+            other => other,
+        }
+    }
+
+    /// `write_to()` after `pre_written` bytes of the field were already saved,
+    /// flushing `dest` after every `flush_every` bytes and at the end if set.
+    fn write_rest<W: Write>(&mut self, mut dest: W, pre_written: u64, flush_every: Option<u64>)
+                            -> SaveResult<u64, u64> {
+        let retry_policy = &self.opts.retry_policy;
+        let throttle = self.opts.throttle.as_ref();
+        let cancel = self.opts.cancel.as_ref();
+        let mut unflushed = 0;
+
+        let res = {
+            let dest = &mut dest;
+            let with_buf = |buf: &[u8]| {
+                let res = match flush_every {
+                    Some(flush_every) => try_write_flushing(buf, &mut *dest, retry_policy, flush_every, &mut unflushed),
+                    None => try_write_all(buf, &mut *dest, retry_policy),
+                };
+
+                checked(paced(res, throttle), cancel)
+            };
+
+            if self.opts.size_limit < u64::MAX {
+                let limit = self.opts.size_limit.saturating_sub(pre_written);
+                try_copy_limited(&mut self.savable, with_buf, limit)
+            } else {
+                try_read_buf(&mut self.savable, with_buf)
+            }
+        };
+
+        if flush_every.is_none() {
+            return res;
+        }
+
+        // the first error is the one reported
+        match (res, try_flush(&mut dest, retry_policy)) {
+            (Full(written), Err(e)) => if written > 0 { Partial(written, e.into()) } else { Error(e) },
+            (res, _) => res,
+        }
+    }
+
+    fn save_mem(&mut self, mut bytes: Vec<u8>) -> SaveResult<Vec<u8>, Vec<u8>> {
+        let pre_read = bytes.len() as u64;
+        match self.read_mem(|buf| { bytes.extend_from_slice(buf); Full(buf.len()) }, pre_read) {
+            Full(_) => Full(bytes),
+            Partial(_, reason) => Partial(bytes, reason),
+            Error(e) => if !bytes.is_empty() { Partial(bytes, e.into()) }
+            else { Error(e) }
+        }
+
+    }
+
+    fn save_text(&mut self, size_hint: u64) -> SaveResult<String, String> {
+        // an empty buffer is always valid UTF-8
+        let mut string = String::from_utf8(self.new_buf(size_hint)).expect("pooled buffer not empty");
+        let reject_nonchars = self.opts.text_norm == TextNorm::RejectNoncharacters;
+
+        // incrementally validate UTF-8 to do as much work as possible during network activity
+        let res = self.read_mem(|buf| {
+            let (valid, res) = match str::from_utf8(buf) {
+                Ok(s) => (s, Full(buf.len())),
+                Err(e) => {
+                    let valid = e.valid_up_to();
+
+                    // a character may be split at the end of the buffer, but the buffer should
+                    // always be bigger than one character
+                    let res = if e.error_len().is_none() && valid > 0 {
+                        Full(valid)
+                    } else {
+                        Partial(valid, e.into())
+                    };
+
+                    (str::from_utf8(&buf[..valid]).unwrap(), res)
+                },
+            };
+
+            if reject_nonchars {
+                if let Some((idx, c)) = valid.char_indices().find(|&(_, c)| is_noncharacter(c)) {
+                    string.push_str(&valid[..idx]);
+                    return Partial(idx, Noncharacter(c));
+                }
+            }
+
+            string.push_str(valid);
+            res
+        }, 0);
+
+        match res {
+            Full(_) => Full(self.normalized(string)),
+            Partial(_, reason) => Partial(string, reason),
+            Error(e) => Error(e),
+        }
+    }
+
+    fn read_mem<Wb: FnMut(&[u8]) -> SaveResult<usize, usize>>(&mut self, mut with_buf: Wb, pre_read: u64) -> SaveResult<u64, u64> {
+        let limit = cmp::min(self.opts.size_limit, self.opts.memory_threshold)
+            .saturating_sub(pre_read);
+        let throttle = self.opts.throttle.as_ref();
+        let cancel = self.opts.cancel.as_ref();
+        try_copy_limited(&mut self.savable, |buf| checked(paced(with_buf(buf), throttle), cancel), limit)
+    }
+
+    fn cmp_size_limit(&self, size: usize) -> bool {
+        size as u64 >= self.opts.size_limit
+    }
+
+    /// A buffer for a field which the client said is `size_hint` bytes long, or 0 if unknown.
+    fn new_buf(&self, size_hint: u64) -> Vec<u8> {
+        match self.opts.buffer_pool {
+            Some(ref pool) => pool.get(),
+            None => {
+                let capacity = cmp::min(size_hint, cmp::min(self.opts.memory_threshold, self.opts.size_limit));
+                Vec::with_capacity(cmp::min(capacity, MAX_PREALLOC) as usize)
+            },
+        }
+    }
+
+    fn saved_text(&self, text: String) -> SavedData {
+        match self.opts.buffer_pool {
+            Some(ref pool) => SavedData::Pooled(PooledBuf::new(pool.clone(), text.into_bytes(), true)),
+            None => SavedData::Text(text.into()),
+        }
+    }
+
+    /// Apply `TextNorm::Nfc` to text read in full.
+    #[cfg(feature = "unicode-normalization")]
+    fn normalized(&self, text: String) -> String {
+        use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+        if self.opts.text_norm != TextNorm::Nfc || is_nfc_quick(text.chars()) == IsNormalized::Yes {
+            return text;
+        }
+
+        text.nfc().collect()
+    }
+
+    #[cfg(not(feature = "unicode-normalization"))]
+    fn normalized(&self, text: String) -> String {
+        text
+    }
+
+    fn saved_decoded(&self, bytes: Vec<u8>, charset: Charset) -> SavedData {
+        let mut text = String::new();
+        charset.decode_to(&bytes, &mut text);
+        let text = self.normalized(text);
+
+        if let Some(ref pool) = self.opts.buffer_pool {
+            pool.put(bytes);
+        }
+
+        self.saved_text(text)
+    }
+
+    fn saved_bytes(&self, bytes: Vec<u8>) -> SavedData {
+        match self.opts.buffer_pool {
+            Some(ref pool) => SavedData::Pooled(PooledBuf::new(pool.clone(), bytes, false)),
+            None => SavedData::Bytes(bytes.into()),
+        }
+    }
+}
+
+/// How text fields are checked or normalized beyond being valid UTF-8,
+/// set with `SaveBuilder::text_normalization()`.
+///
+/// This only applies to fields read as text, i.e. kept in memory with `try_text()` or
+/// `force_text()`; fields written to files are never changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextNorm {
+    /// Keep any valid UTF-8 as it is.
+    None,
+    /// Reject the Unicode noncharacters: U+FDD0 to U+FDEF and the last two code points of
+    /// each plane (U+FFFE, U+FFFF, U+1FFFE and so on).
+    ///
+    /// These are checked as the field is read, along with the UTF-8. Like invalid UTF-8,
+    /// a field containing one is saved as bytes with `try_text()`, or returns
+    /// `PartialReason::Noncharacter` with `force_text()`.
+    RejectNoncharacters,
+    /// Normalize text to Unicode Normalization Form C (NFC), e.g. `e` followed by
+    /// U+0301 COMBINING ACUTE ACCENT becomes `\u{e9}`. Requires the `unicode-normalization`
+    /// feature.
+    ///
+    /// A field can only be normalized once it's been read in full, which may take another
+    /// buffer the size of the field; fields which are already in NFC aren't copied.
+    #[cfg(feature = "unicode-normalization")]
+    Nfc,
+}
+
+/// Whether `c` is a Unicode noncharacter, for `TextNorm::RejectNoncharacters`.
+fn is_noncharacter(c: char) -> bool {
+    match c as u32 {
+        0xFDD0 ..= 0xFDEF => true,
+        c => c & 0xFFFE == 0xFFFE,
+    }
+}
+
+/// What `SaveBuilder::with_entries()` does when saving a single field fails,
+/// set with `SaveBuilder::on_field_error()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldErrorPolicy {
+    /// Stop saving and return the error as a `PartialReason`, with the field in
+    /// `PartialEntries::partial`.
+    Abort,
+    /// Delete anything saved of the field, read past the rest of its data and record it in
+    /// `Entries::failures`, then continue with the next field.
+    ///
+    /// This applies to `PartialReason::IoError`, `Utf8Error`, `Noncharacter` and `DigestMismatch`; limits and
+    /// rejections like `SuspiciousFilename` still stop the request. If the rest of the field
+    /// can't be read, the error is taken to come from the request itself and saving stops
+    /// with `PartialReason::IoError`. Failed fields count toward `max_parts()`.
+    SkipAndContinue,
+}
+
+/// A field which couldn't be saved under `FieldErrorPolicy::SkipAndContinue`.
+#[derive(Debug)]
+pub struct FieldFailure {
+    /// The headers of the field.
+    pub headers: FieldHeaders,
+    /// Why it couldn't be saved.
+    pub reason: PartialReason,
+}
+
+/// When and how often to retry failed writes, set with `SaveBuilder::retry_writes()`.
+///
+/// `io::ErrorKind::Interrupted` is always retried immediately, regardless of the policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of times a write is retried before giving up.
+    ///
+    /// Reset whenever a write succeeds, so this applies to each failing write separately.
+    pub attempts: u32,
+    /// How long to wait before the first retry, doubling for each following retry.
+    pub backoff: Duration,
+    /// The kinds of errors which are retried.
+    ///
+    /// Default value: `WouldBlock` and `TimedOut`
+    pub kinds: Vec<io::ErrorKind>,
+}
+
+impl RetryPolicy {
+    /// Retry failing writes up to `attempts` times, waiting `backoff` before the first retry.
+    pub fn new(attempts: u32, backoff: Duration) -> Self {
+        RetryPolicy { attempts, backoff, .. RetryPolicy::default() }
+    }
+
+    /// Set the kinds of errors which are retried.
+    pub fn kinds(self, kinds: Vec<io::ErrorKind>) -> Self {
+        RetryPolicy { kinds, .. self }
+    }
+
+    /// The delay before the given retry (starting from `0`), or `None` if `err` shouldn't be
+    /// retried.
+    fn delay(&self, retry: u32, err: &io::Error) -> Option<Duration> {
+        if retry >= self.attempts || !self.kinds.contains(&err.kind()) {
+            return None;
+        }
+
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+        Some(self.backoff.checked_mul(factor).unwrap_or_else(|| Duration::from_secs(u64::MAX)))
+    }
+}
+
+/// Doesn't retry at all.
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 0,
+            backoff: Duration::from_millis(0),
+            kinds: vec![io::ErrorKind::WouldBlock, io::ErrorKind::TimedOut],
+        }
+    }
+}
+
+/// A bounded, thread-safe pool of fixed-capacity buffers for in-memory field data.
+///
+/// Share one instance (in an `Arc`) between requests and pass it to `SaveBuilder::buffer_pool()`.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    buf_capacity: usize,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Create a pool which hands out buffers with capacity `buf_capacity` and keeps at most
+    /// `max_buffers` idle buffers around.
+    pub fn new(buf_capacity: usize, max_buffers: usize) -> Self {
+        BufferPool {
+            buffers: Mutex::new(Vec::new()),
+            buf_capacity,
+            max_buffers,
+        }
+    }
+
+    /// Take an empty buffer from the pool, or allocate a new one if the pool is empty.
+    pub fn get(&self) -> Vec<u8> {
+        self.lock().pop().unwrap_or_else(|| Vec::with_capacity(self.buf_capacity))
+    }
+
+    /// Return a buffer to the pool.
+    ///
+    /// The buffer is dropped instead if the pool is full or its capacity has changed.
+    pub fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() != self.buf_capacity {
+            return;
+        }
+
+        let mut buffers = self.lock();
+
+        if buffers.len() < self.max_buffers {
+            buf.clear();
+            buffers.push(buf);
+        }
+    }
+
+    /// The number of idle buffers currently held by the pool.
+    pub fn available(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // the buffers are always in a consistent state, even if another thread panicked
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("buf_capacity", &self.buf_capacity)
+            .field("max_buffers", &self.max_buffers)
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+/// A buffer taken from a [`BufferPool`](struct.BufferPool.html), which is returned to the pool
+/// when it and all of its clones are dropped.
+///
+/// Clones share the buffer, so cloning is cheap. Dereferences to `[u8]`.
+#[derive(Clone)]
+pub struct PooledBuf(Arc<PooledInner>);
+
+struct PooledInner {
+    pool: Arc<BufferPool>,
+    buf: Vec<u8>,
+    text: bool,
+}
+
+impl PooledBuf {
+    fn new(pool: Arc<BufferPool>, buf: Vec<u8>, text: bool) -> Self {
+        PooledBuf(Arc::new(PooledInner { pool, buf, text }))
+    }
+
+    /// Get the contents as a string, if they were read and validated as text.
+    pub fn as_str(&self) -> Option<&str> {
+        if self.0.text {
+            // validated when the field was saved
+            str::from_utf8(&self.0.buf).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if `self` and `other` share the same buffer.
+    pub fn ptr_eq(&self, other: &PooledBuf) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for PooledBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0.buf
+    }
+}
+
+impl AsRef<[u8]> for PooledBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.0.buf
+    }
+}
+
+impl Drop for PooledInner {
+    fn drop(&mut self) {
+        self.pool.put(mem::take(&mut self.buf));
+    }
+}
+
+/// Does not print the contents, only their size.
+impl fmt::Debug for PooledBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PooledBuf").field(&ElidedData(self.0.buf.len())).finish()
+    }
+}
+
+/// A field that has been saved (to memory or disk) from a multipart request.
+///
+/// Use the borrowing accessors (`as_text()`, `as_bytes()`, `as_path()`) or the `TryFrom`
+/// conversions to get at the data without matching on `SavedData`:
+///
+/// ```rust
+/// # use multipart::server::parse_form_data;
+/// use std::convert::TryFrom;
+/// use std::error::Error;
+///
+/// fn handle_form(body: &[u8], content_type: &str) -> Result<(), Box<dyn Error>> {
+///     let mut entries = parse_form_data(body, content_type)?;
+///
+///     let greeting = entries.fields["greeting"][0].as_text().ok_or("greeting is not text")?;
+///     assert_eq!(greeting, "hello");
+///
+///     let upload = entries.fields.remove("upload").ok_or("missing upload")?.remove(0);
+///     assert_eq!(Vec::<u8>::try_from(upload)?, b"\x00\x01\x02");
+///
+///     Ok(())
+/// }
+///
+/// let body = b"--boundary\r\n\
+///              Content-Disposition: form-data; name=\"greeting\"\r\n\r\n\
+///              hello\r\n\
+///              --boundary\r\n\
+///              Content-Disposition: form-data; name=\"upload\"; filename=\"bytes.bin\"\r\n\
+///              Content-Type: application/octet-stream\r\n\r\n\
+///              \x00\x01\x02\r\n\
+///              --boundary--\r\n";
+///
+/// handle_form(body, "multipart/form-data; boundary=boundary").unwrap();
+/// ```
+///
+/// The `Debug` impl does not print the contents of in-memory data; use `summary()` for a
+/// description suitable for logging.
+#[derive(Debug)]
+pub struct SavedField {
+    /// The headers of the field that was saved.
+    pub headers: FieldHeaders,
+    /// The data of the field which may reside in memory or on disk.
+    pub data: SavedData,
+    /// The type detected from the first bytes of the data by the `TypeSniffer` set with
+    /// `SaveBuilder::sniffer()`, or `None` if none was set or the type is unknown.
+    ///
+    /// Unlike `headers.content_type`, this is not simply taken from the client, but a client
+    /// can still craft data which looks like a given type.
+    pub detected_type: Option<Mime>,
+    /// When the data of the field arrived, or `None` if it wasn't saved by a `SaveBuilder`.
+    ///
+    /// See also `read_duration()` and `throughput_bytes_per_sec()`.
+    pub timing: Option<FieldTiming>,
+    /// Where the field was in the request body, if `SaveBuilder::collect_raw_ranges()` was set.
+    pub raw_range: Option<RawRange>,
+}
+
+impl SavedField {
+    /// Get the data of this field if it was saved as text.
+    pub fn as_text(&self) -> Option<&str> {
+        match self.data {
+            SavedData::Text(ref text) => Some(text),
+            SavedData::Pooled(ref buf) => buf.as_str(),
+            _ => None,
+        }
+    }
+
+    /// Get the data of this field if it is in memory, whether text or binary.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self.data {
+            SavedData::Text(ref text) => Some(text.as_bytes()),
+            SavedData::Bytes(ref bytes) => Some(bytes),
+            SavedData::Pooled(ref buf) => Some(buf),
+            SavedData::File(..) | SavedData::Chunked(_) | SavedData::Streamed { .. } | SavedData::Redacted { .. }
+            | SavedData::Validated { .. } => None,
+        }
+    }
+
+    /// Returns `true` if the client sent this field as a file, i.e. with a filename,
+    /// even if the file was empty.
+    ///
+    /// This depends only on the headers; see `data.is_file()` for where the data was saved.
+    pub fn is_file(&self) -> bool {
+        self.headers.filename.is_some()
+    }
+
+    /// Get the path of the file this field was saved to, if it was saved to a single file.
+    pub fn as_path(&self) -> Option<&Path> {
+        match self.data {
+            SavedData::File(ref path, _) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Get how long the data of this field took to arrive once its headers were parsed,
+    /// i.e. `timing.last_byte`, or `None` if it wasn't timed.
+    pub fn read_duration(&self) -> Option<Duration> {
+        self.timing.map(|timing| timing.last_byte)
+    }
+
+    /// Get the size of this field over `read_duration()` in bytes per second, e.g. to find
+    /// clients which upload unusually slowly.
+    ///
+    /// `None` if the field wasn't timed or took no measurable time.
+    pub fn throughput_bytes_per_sec(&self) -> Option<f64> {
+        let secs = self.read_duration()?.as_secs_f64();
+
+        if secs > 0.0 {
+            Some(self.data.size() as f64 / secs)
+        } else {
+            None
+        }
+    }
+
+    /// Get a one-line description of this field which implements `Display`.
+    ///
+    /// See [`FieldSummary`](struct.FieldSummary.html) for details.
+    pub fn summary(&self) -> FieldSummary<'_> {
+        FieldSummary { field: self, redacted: false }
+    }
+
+    /// Get a snapshot of this field's headers and size without its data, e.g. to send to
+    /// another thread which looks up the data later.
+    ///
+    /// The name is shared with `headers.name` rather than copied.
+    pub fn meta(&self) -> FieldMeta {
+        FieldMeta {
+            name: self.headers.name.clone(),
+            filename: self.headers.filename.clone(),
+            content_type: self.headers.content_type.clone(),
+            size: self.data.size(),
+            kind: self.data.kind_str(),
+        }
+    }
+
+    /// Get the headers for sending this field's data as the body of an HTTP response.
+    ///
+    /// The content type is the one declared by the client if any, otherwise it is guessed from
+    /// the filename. Fields with neither are `text/plain` as per
+    /// [IETF RFC 7578, Section 4.4](https://tools.ietf.org/html/rfc7578#section-4.4),
+    /// unless they were saved in memory and are not valid UTF-8.
+    ///
+    /// See [`ResponseParts`](struct.ResponseParts.html) for details; use
+    /// `SavedData::into_body_reader()` or `readable()` for the body itself.
+    pub fn response_parts(&self) -> ResponseParts<'_> {
+        let content_type = match (&self.headers.content_type, &self.headers.filename) {
+            (Some(content_type), _) => content_type.clone(),
+            (None, Some(filename)) => ::mime_guess::from_path(filename).first_or_octet_stream(),
+            (None, None) if self.as_bytes().is_some() && self.as_text().is_none() =>
+                mime::APPLICATION_OCTET_STREAM,
+            (None, None) => mime::TEXT_PLAIN_UTF_8,
+        };
+
+        ResponseParts {
+            content_type,
+            content_length: self.data.size(),
+            filename: self.headers.client_filename(),
+        }
+    }
+}
+
+/// When the data of a `SavedField` arrived, relative to when its headers were parsed,
+/// so the time a client took to start sending the field isn't counted.
+///
+/// Kept as durations rather than `Instant`s so it can be stored or sent elsewhere as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldTiming {
+    /// When the first data arrived, or `None` if the field was empty.
+    ///
+    /// Reads are buffered, so this is when the first buffer of the field (about 1 KiB,
+    /// or all of a shorter field) was filled rather than the first byte on the wire.
+    pub first_byte: Option<Duration>,
+    /// When the last byte of data arrived and was saved.
+    pub last_byte: Duration,
+}
+
+/// Where a `SavedField` was in the request body, recorded with
+/// `SaveBuilder::collect_raw_ranges()`.
+///
+/// Offsets are in bytes from the start of the body, as for `ParseEvent`. A part spans from
+/// its first header line to the end of its data: the boundary line before it and the CRLF
+/// which ends the data (and precedes the next boundary) are left out, as they belong to the
+/// framing rather than the part. `range(true)` covers the header lines and the blank line
+/// which ends them, while `range(false)` covers the data alone.
+///
+/// ```
+/// # use multipart::server::Multipart;
+/// let body = b"--boundary\r\n\
+///              Content-Disposition: form-data; name=\"foo\"\r\n\r\n\
+///              bar\r\n\
+///              --boundary--\r\n";
+///
+/// let entries = Multipart::with_body(&body[..], "boundary").save().collect_raw_ranges(true)
+///     .in_memory().into_result_strict().unwrap();
+///
+/// let range = entries.fields["foo"][0].raw_range.unwrap();
+/// assert_eq!(&body[range.headers_start as usize..range.data_start as usize],
+///            &b"Content-Disposition: form-data; name=\"foo\"\r\n\r\n"[..]);
+/// assert_eq!(&body[range.data_start as usize..range.end as usize], b"bar");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawRange {
+    /// The offset of the first header line.
+    pub headers_start: u64,
+    /// The offset of the first byte of data, just after the blank line which ends the headers.
+    pub data_start: u64,
+    /// The offset just past the last byte of data.
+    pub end: u64,
+}
+
+impl RawRange {
+    /// The byte range of the part, starting at the headers if `include_headers` is set or
+    /// at the data otherwise.
+    pub fn range(&self, include_headers: bool) -> Range<u64> {
+        let start = if include_headers { self.headers_start } else { self.data_start };
+        start..self.end
+    }
+
+    /// The length of `range(include_headers)`.
+    pub fn len(&self, include_headers: bool) -> u64 {
+        let range = self.range(include_headers);
+        range.end - range.start
+    }
+}
+
+/// Pass the bytes of each of `ranges` in `source`, the raw request body, to `verify` in order,
+/// returning how many bytes were passed; see `RawRange` for what the ranges cover.
+///
+/// This is meant for checking a signature or MAC over several parts of a request, so a
+/// client and server which agree on which parts are signed don't have to agree on how they
+/// are framed. `source` may be the body itself, if it can be read again, or
+/// `Multipart::raw_body()` if `Multipart::capture_raw()` was set before the first field was
+/// read.
+///
+/// `source` is only read forwards, so `ranges` must be in the order of the body and must not
+/// overlap, as those from `Entries::raw_ranges()` are; an error of kind `InvalidInput` is
+/// returned otherwise. An error of kind `UnexpectedEof` is returned if `source` ends before
+/// the last range.
+///
+/// ```
+/// # use multipart::server::{Multipart, read_raw_ranges};
+/// let body = b"--boundary\r\n\
+///              Content-Disposition: form-data; name=\"foo\"\r\n\r\n\
+///              bar\r\n\
+///              --boundary\r\n\
+///              Content-Disposition: form-data; name=\"baz\"\r\n\r\n\
+///              qux\r\n\
+///              --boundary--\r\n";
+///
+/// let entries = Multipart::with_body(&body[..], "boundary").save().collect_raw_ranges(true)
+///     .in_memory().into_result_strict().unwrap();
+/// let ranges: Vec<_> = entries.raw_ranges().into_iter().map(|(_, range)| range).collect();
+///
+/// let mut signed = Vec::new();
+/// read_raw_ranges(&body[..], &ranges, false, |buf| signed.extend_from_slice(buf)).unwrap();
+/// assert_eq!(signed, b"barqux");
+/// ```
+pub fn read_raw_ranges<R, V>(mut source: R, ranges: &[RawRange], include_headers: bool, mut verify: V)
+                             -> io::Result<u64>
+where R: Read, V: FnMut(&[u8]) {
+    let mut pos = 0u64;
+    let mut passed = 0u64;
+    let mut buf = [0u8; 8 * 1024];
+
+    for raw_range in ranges {
+        let range = raw_range.range(include_headers);
+
+        if range.start < pos || range.end < range.start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("raw range {:?} is out of order or overlaps the previous one", range),
+            ));
+        }
+
+        let skip = range.start - pos;
+        if io::copy(&mut (&mut source).take(skip), &mut io::sink())? < skip {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "body ended before a raw range"));
+        }
+
+        let mut remaining = range.end - range.start;
+        while remaining > 0 {
+            let len = cmp::min(remaining, buf.len() as u64) as usize;
+            let read = match source.read(&mut buf[..len]) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "body ended within a raw range")),
+                Ok(read) => read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            verify(&buf[..read]);
+            remaining -= read as u64;
+        }
+
+        passed += range.end - range.start;
+        pos = range.end;
+    }
+
+    Ok(passed)
+}
+
+/// The raw range of a field which was just read to the end, if it was parsed from a raw body.
+fn raw_range_of<M: ReadEntry>(data: &MultipartData<M>, data_start: Option<u64>) -> Option<RawRange> {
+    match (data.headers_offset(), data_start, data.data_offset()) {
+        (Some(headers_start), Some(data_start), Some(end)) => Some(RawRange { headers_start, data_start, end }),
+        _ => None,
+    }
+}
+
+/// The sanitized filename of a field for `SaveBuilder::use_client_filename()`, unless nothing
+/// is left of it.
+fn client_filename(headers: &FieldHeaders) -> Option<String> {
+    headers.filename.as_ref().map(|filename| sanitize_filename(filename)).filter(|name| !name.is_empty())
+}
+
+/// Wait for the first byte of `data`, returning how long after `started` it arrived,
+/// or `None` if there is no data.
+fn first_byte_after<R: BufRead>(data: &mut R, started: Instant, clock: fn() -> Instant)
+                                -> io::Result<Option<Duration>> {
+    let empty = data.fill_buf()?.is_empty();
+    Ok(if empty { None } else { Some(clock().saturating_duration_since(started)) })
+}
+
+/// The headers for sending a `SavedField` as the body of an HTTP response, returned by
+/// `SavedField::response_parts()`.
+///
+/// ### Warning: Values are Client-Provided
+/// Serving an upload with the content type declared by the client (e.g. `text/html`) from
+/// your own origin allows the client to run scripts there. Unless the uploads are trusted,
+/// override `content_type` or send `content_disposition()` so the data is downloaded
+/// rather than displayed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResponseParts<'a> {
+    /// The value for the `Content-Type` header.
+    pub content_type: Mime,
+    /// The value for the `Content-Length` header.
+    pub content_length: u64,
+    /// The filename of the field without any directories, see `FieldHeaders::client_filename()`.
+    pub filename: Option<&'a str>,
+}
+
+impl<'a> ResponseParts<'a> {
+    /// The value for a `Content-Disposition` header telling the client to download the data
+    /// as a file, e.g. `attachment; filename="a.txt"`.
+    ///
+    /// A filename which is not printable ASCII is also given as `filename*`
+    /// ([IETF RFC 6266, Section 4.3](https://tools.ietf.org/html/rfc6266#section-4.3)),
+    /// with a fallback for older clients where such characters are replaced by `_`.
+    pub fn content_disposition(&self) -> String {
+        let filename = match self.filename {
+            Some(filename) => filename,
+            None => return "attachment".into(),
+        };
+
+        let fallback: String = filename.chars()
+            .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+            .collect();
+
+        if fallback == filename {
+            return format!("attachment; filename=\"{}\"", filename);
+        }
+
+        let mut encoded = String::new();
+
+        for &b in filename.as_bytes() {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                encoded.push(b as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", b));
+            }
+        }
+
+        format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+    }
+}
+
+/// The number of bytes of in-memory data shown by `FieldSummary`.
+const SUMMARY_PREFIX_LEN: usize = 16;
+
+/// A description of a `SavedField` suitable for logging, returned by `SavedField::summary()`.
+///
+/// Prints the name, filename and content type of the field, the kind and size of its data
+/// and the path it was saved to, if any. At most the first 16 bytes of in-memory data are
+/// printed, followed by `…(N bytes)` if there is more, or none at all if `redacted(true)`
+/// or the data is `SavedData::Redacted`.
+///
+/// ```text
+/// "greeting": Text, 5 bytes, "hello"
+/// "password": Text, 12 bytes, <redacted>
+/// "upload", filename "a.bin", application/octet-stream: File, 3000 bytes, saved to "/tmp/..."
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct FieldSummary<'a> {
+    field: &'a SavedField,
+    redacted: bool,
+}
+
+impl<'a> FieldSummary<'a> {
+    /// If `true`, do not print any of the field's in-memory data.
+    pub fn redacted(self, redacted: bool) -> Self {
+        FieldSummary { redacted, .. self }
+    }
+}
+
+impl<'a> fmt::Display for FieldSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let data = &self.field.data;
+
+        write!(f, "{}: {}, {} bytes", self.field.headers, data.kind_str(), data.size())?;
+
+        match *data {
+            SavedData::File(ref path, _) => return write!(f, ", saved to {:?}", path),
+            SavedData::Chunked(ref chunks) => return match chunks.first() {
+                Some((path, _)) => write!(f, ", saved to {} chunks from {:?}", chunks.len(), path),
+                None => f.write_str(", saved to 0 chunks"),
+            },
+            _ => (),
+        }
+
+        if self.redacted || matches!(*data, SavedData::Redacted { .. }) {
+            return f.write_str(", <redacted>");
+        }
+
+        let bytes = self.field.as_bytes().unwrap_or(&[]);
+
+        let prefix = match self.field.as_text() {
+            Some(text) => {
+                let mut end = cmp::min(text.len(), SUMMARY_PREFIX_LEN);
+                while !text.is_char_boundary(end) { end -= 1; }
+                write!(f, ", {:?}", &text[..end])?;
+                end
+            },
+            None => {
+                let end = cmp::min(bytes.len(), SUMMARY_PREFIX_LEN);
+                write!(f, ", {:?}", String::from_utf8_lossy(&bytes[..end]))?;
+                end
+            },
+        };
+
+        if prefix < bytes.len() {
+            write!(f, "…({} bytes)", bytes.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The headers and size of a `SavedField` without its data, returned by `SavedField::meta()`
+/// and `Entries::metas()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldMeta {
+    /// The name of the field, see `FieldHeaders::name`.
+    pub name: Arc<str>,
+    /// The filename supplied by the client, if any.
+    pub filename: Option<String>,
+    /// The content type supplied by the client, if any.
+    pub content_type: Option<Mime>,
+    /// The size of the data in bytes, see `SavedData::size()`.
+    pub size: u64,
+    /// The variant of `SavedData` the field was saved as: `"Text"`, `"Bytes"`, `"File"`,
+    /// `"Pooled"`, `"Chunked"`, `"Streamed"` or `"Redacted"`.
+    pub kind: &'static str,
+}
+
+/// Take the data out of `shared`, copying it only if a clone still refers to it.
+fn unwrap_or_copy<T: Clone>(shared: Arc<T>) -> T {
+    Arc::try_unwrap(shared).unwrap_or_else(|shared| (*shared).clone())
+}
+
+/// Succeeds if the field was saved as text (including pooled text).
+impl TryFrom<SavedField> for String {
+    type Error = SavedFieldError;
+
+    fn try_from(field: SavedField) -> Result<String, SavedFieldError> {
+        match field.data {
+            SavedData::Text(text) => Ok(unwrap_or_copy(text)),
+            SavedData::Pooled(ref buf) => buf.as_str().map(str::to_owned)
+                .ok_or(SavedFieldError::UnexpectedData("Text", "Pooled")),
+            other => Err(SavedFieldError::UnexpectedData("Text", other.kind_str())),
+        }
+    }
+}
+
+/// Succeeds for any kind of data but `Streamed`; a file is read into memory.
+impl TryFrom<SavedField> for Vec<u8> {
+    type Error = SavedFieldError;
+
+    fn try_from(field: SavedField) -> Result<Vec<u8>, SavedFieldError> {
+        match field.data {
+            SavedData::Text(text) => Ok(unwrap_or_copy(text).into_bytes()),
+            SavedData::Bytes(bytes) => Ok(unwrap_or_copy(bytes)),
+            SavedData::Pooled(ref buf) => Ok(buf.to_vec()),
+            SavedData::File(ref path, _) => fs::read(path).map_err(Into::into),
+            ref chunked @ SavedData::Chunked(_) => {
+                let mut bytes = Vec::with_capacity(chunked.size() as usize);
+                chunked.readable()?.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            },
+            SavedData::Streamed { .. } => Err(SavedFieldError::UnexpectedData("Bytes", "Streamed")),
+            SavedData::Redacted { .. } => Err(SavedFieldError::UnexpectedData("Bytes", "Redacted")),
+            SavedData::Validated { .. } => Err(SavedFieldError::UnexpectedData("Bytes", "Validated")),
+        }
+    }
+}
+
+/// Succeeds if the field was saved to a file.
+impl<'a> TryFrom<&'a SavedField> for PathBuf {
+    type Error = SavedFieldError;
+
+    fn try_from(field: &'a SavedField) -> Result<PathBuf, SavedFieldError> {
+        field.as_path().map(Path::to_path_buf)
+            .ok_or_else(|| SavedFieldError::UnexpectedData("File", field.data.kind_str()))
+    }
+}
+
+quick_error! {
+    /// An error returned by the `TryFrom` conversions from `SavedField`.
+    #[derive(Debug)]
+    pub enum SavedFieldError {
+        /// The field's data was not of the expected kind: (expected, found).
+        ///
+        /// The kinds are the names of the variants of `SavedData`.
+        UnexpectedData(expected: &'static str, found: &'static str) {
+            description("saved field data was not of the expected kind")
+            display("saved field data was not of the expected kind: expected {}, found {}",
+                    expected, found)
+        }
+        /// An error occurred while reading the field's file.
+        Io(err: io::Error) {
+            description("an error occurred while reading the saved field")
+            display("an error occurred while reading the saved field: {}", err)
+            cause(err)
+            from()
+        }
+    }
+}
+
+/// A saved field's data container (in memory or on disk)
+///
+/// Cloning is cheap, e.g. to hand the same field to several workers: data in memory is shared
+/// between the clones rather than copied, and for data in files only the paths are copied.
+/// Methods which replace the data, like `spill_to_dir()`, only affect the handle they're
+/// called on.
+///
+/// The `Debug` impl does not print in-memory data, only its size.
+#[derive(Clone)]
+pub enum SavedData {
+    /// Validated UTF-8 text data.
+    Text(Arc<String>),
+    /// Binary data.
+    Bytes(Arc<Vec<u8>>),
+    /// A path to a file on the filesystem and its size.
+    File(PathBuf, FileSize),
+    /// Text or binary data in a buffer from a `BufferPool`, set with `SaveBuilder::buffer_pool()`.
+    Pooled(PooledBuf),
+    /// The paths and sizes of the files a field was split across, in order,
+    /// set with `SaveBuilder::chunk_files()`.
+    Chunked(Vec<(PathBuf, u64)>),
+    /// The number of bytes of a field written to the caller's writer by
+    /// `SaveBuilder::stream_field_to()`; the data itself wasn't kept.
+    Streamed {
+        /// The number of bytes written.
+        bytes: u64,
+    },
+    /// The length of a field matched by `SaveBuilder::redact_fields()`, whose data was read
+    /// and dropped.
+    Redacted {
+        /// The number of bytes of data the field had.
+        original_len: u64,
+    },
+    /// What `SaveBuilder::dry_run()` found out about a field, whose data wasn't kept.
+    Validated {
+        /// The number of bytes of data the field had.
+        len: u64,
+        /// The type detected by `SaveBuilder::sniffer()`, as in `SavedField::detected_type`.
+        detected_type: Option<Mime>,
+        /// The SHA-256 digest of the data, with the `digest` feature.
+        digest: Option<[u8; 32]>,
+    },
+}
+
+impl SavedData {
+    /// Get an adapter for this data which implements `Read`.
+    ///
+    /// If the data is in a file, the file is opened in read-only mode and no more than
+    /// the recorded size (`FileSize::len`) is read, even if the file has grown since.
+    /// If it has shrunk, reading ends early; compare with `DataReader::expected_len()`.
+    ///
+    /// Returns an error for `Streamed`, `Redacted` and `Validated`, which have no data to read.
+    pub fn readable(&self) -> io::Result<DataReader> {
+        use self::SavedData::*;
+
+        match *self {
+            Text(ref text) => Ok(DataReader::Bytes(text.as_bytes())),
+            Bytes(ref bytes) => Ok(DataReader::Bytes(bytes)),
+            File(ref path, size) => DataReader::open_file(path, Some(size.len)),
+            Pooled(ref buf) => Ok(DataReader::Bytes(buf)),
+            Chunked(ref chunks) => Ok(DataReader::Chunked(ChunkedReader::new(Cow::Borrowed(chunks))?)),
+            Streamed { .. } => Err(streamed_error()),
+            Redacted { .. } => Err(redacted_error()),
+            Validated { .. } => Err(validated_error()),
+        }
+    }
+
+    /// Like `readable()`, but a file is read to the end, however long it is now.
+    ///
+    /// The same as `readable()` for data which isn't in a single file.
+    pub fn readable_unbounded(&self) -> io::Result<DataReader<'_>> {
+        match *self {
+            SavedData::File(ref path, _) => DataReader::open_file(path, None),
+            _ => self.readable(),
+        }
+    }
+
+    /// Get an adapter for this data which implements `Read` and owns the data,
+    /// e.g. to send it as the body of a response after `Entries` is dropped.
+    ///
+    /// If the data is in a file, the file is opened in read-only mode and, as with `readable()`,
+    /// no more than the recorded size is read.
+    pub fn into_body_reader(self) -> io::Result<DataReader<'static>> {
+        use self::SavedData::*;
+
+        match self {
+            Text(text) => Ok(DataReader::Owned(io::Cursor::new(unwrap_or_copy(text).into_bytes()))),
+            Bytes(bytes) => Ok(DataReader::Owned(io::Cursor::new(unwrap_or_copy(bytes)))),
+            File(path, size) => DataReader::open_file(&path, Some(size.len)),
+            Pooled(buf) => Ok(DataReader::Pooled(io::Cursor::new(buf))),
+            Chunked(chunks) => Ok(DataReader::Chunked(ChunkedReader::new(Cow::Owned(chunks))?)),
+            Streamed { .. } => Err(streamed_error()),
+            Redacted { .. } => Err(redacted_error()),
+            Validated { .. } => Err(validated_error()),
+        }
+    }
+
+    /// Get the size of the data, in memory or on disk.
+    ///
+    /// For `File`, this is the length of the file after it was saved, `FileSize::len`,
+    /// which is all `readable()` reads; it includes any data the file already had.
+    ///
+    /// #### Note
+    /// The size on disk may not match the size of the file if it is externally modified.
+    pub fn size(&self) -> u64 {
+        use self::SavedData::*;
+
+        match *self {
+            Text(ref text) => text.len() as u64,
+            Bytes(ref bytes) => bytes.len() as u64,
+            File(_, size) => size.len,
+            Pooled(ref buf) => buf.len() as u64,
+            Chunked(ref chunks) => chunks.iter().map(|&(_, size)| size).sum(),
+            Streamed { bytes } => bytes,
+            Redacted { original_len } => original_len,
+            Validated { len, .. } => len,
+        }
+    }
+
+    /// Returns `true` if the data is known to be in memory (`Text | Bytes | Pooled`)
+    pub fn is_memory(&self) -> bool {
+        use self::SavedData::*;
+
+        match *self {
+            Text(_) | Bytes(_) | Pooled(_) => true,
+            File(_, _) | Chunked(_) | Streamed { .. } | Redacted { .. } | Validated { .. } => false,
+        }
+    }
+
+    /// Returns `true` for the placeholder `Validated` left by `SaveBuilder::dry_run()`.
+    pub fn is_validated(&self) -> bool {
+        matches!(*self, SavedData::Validated { .. })
+    }
+
+    /// Returns `true` if the data is in one or more files (`File | Chunked`)
+    pub fn is_file(&self) -> bool {
+        matches!(*self, SavedData::File(_, _) | SavedData::Chunked(_))
+    }
+
+    /// Get the path of the file if the data is in a single file (`File`) and the path is
+    /// valid UTF-8. Enabled with the `camino` feature.
+    #[cfg(feature = "camino")]
+    pub fn utf8_path(&self) -> Option<&Utf8Path> {
+        match *self {
+            SavedData::File(ref path, _) => Utf8Path::from_path(path),
+            _ => None,
+        }
+    }
+
+    /// Write in-memory data out to a new file with a random name in `dir`, replacing it
+    /// with `File`. Does nothing if the data is already in a file.
+    ///
+    /// Any missing directories in the path are created. On error, the data is unchanged.
+    pub fn spill_to_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let path = dir.join(rand_filename());
+
+        let size = {
+            let bytes: &[u8] = match *self {
+                SavedData::Text(ref text) => text.as_bytes(),
+                SavedData::Bytes(ref bytes) => bytes,
+                SavedData::Pooled(ref buf) => buf,
+                SavedData::File(..) | SavedData::Chunked(_) | SavedData::Streamed { .. }
+                | SavedData::Redacted { .. } | SavedData::Validated { .. } => return Ok(()),
+            };
+
+            create_dir_all(&path)?;
+
+            let mut file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+
+            if let Err(e) = file.write_all(bytes).and_then(|_| file.flush()) {
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+
+            bytes.len() as u64
+        };
+
+        *self = SavedData::File(path, FileSize::new(size));
+        Ok(())
+    }
+
+    /// Read data in files into memory as `Bytes` and delete the files.
+    /// Does nothing if the data is already in memory, or was `Streamed`.
+    ///
+    /// If the data is larger than `cap` bytes, an error of kind `InvalidData` is returned.
+    /// On error, the data (and its files) are unchanged.
+    ///
+    /// Clones of this data refer to the same files, which will no longer exist.
+    pub fn load_into_memory(&mut self, cap: u64) -> io::Result<()> {
+        if !self.is_file() {
+            return Ok(());
+        }
+
+        // check the recorded size first, but files may have been modified since
+        let too_large = || io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("saved data is larger than the limit of {}", ByteSize(cap))
+        );
+
+        if self.size() > cap {
+            return Err(too_large());
+        }
+
+        let mut bytes = Vec::with_capacity(self.size() as usize);
+        self.readable_unbounded()?.take(cap.saturating_add(1)).read_to_end(&mut bytes)?;
+
+        if bytes.len() as u64 > cap {
+            return Err(too_large());
+        }
+
+        remove_files(self);
+        *self = SavedData::Bytes(bytes.into());
+        Ok(())
+    }
+
+    fn kind_str(&self) -> &'static str {
+        use self::SavedData::*;
+
+        match *self {
+            Text(_) => "Text",
+            Bytes(_) => "Bytes",
+            File(_, _) => "File",
+            Pooled(_) => "Pooled",
+            Chunked(_) => "Chunked",
+            Streamed { .. } => "Streamed",
+            Redacted { .. } => "Redacted",
+            Validated { .. } => "Validated",
+        }
+    }
+
+    /// The number of bytes saved by `multipart`, which for `File` excludes any data the file
+    /// already had, and is zero for `Streamed` and `Redacted`.
+    fn written(&self) -> u64 {
+        match *self {
+            SavedData::File(_, size) => size.written,
+            SavedData::Streamed { .. } | SavedData::Redacted { .. } => 0,
+            ref other => other.size(),
+        }
+    }
+}
+
+/// The size of a file saved as `SavedData::File`.
+///
+/// By default a new file is created for each field, so both sizes are the same.
+/// If the file was opened with `append(true)` instead (see `SaveBuilder::mod_open_opts()`),
+/// e.g. to resume an interrupted upload, it may have already had data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileSize {
+    /// The number of bytes of the field written to the file.
+    pub written: u64,
+    /// The length of the file once the field was written, read from its metadata.
+    pub len: u64,
+}
+
+impl FileSize {
+    /// The size of a file which had no data before `written` bytes were written to it.
+    pub fn new(written: u64) -> Self {
+        FileSize { written, len: written }
+    }
+}
+
+impl fmt::Debug for SavedData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SavedData::*;
+
+        match *self {
+            File(ref path, size) => f.debug_tuple("File").field(path).field(&size).finish(),
+            Pooled(ref buf) => f.debug_tuple("Pooled").field(buf).finish(),
+            Chunked(ref chunks) => f.debug_tuple("Chunked").field(chunks).finish(),
+            Streamed { bytes } => f.debug_struct("Streamed").field("bytes", &bytes).finish(),
+            Redacted { original_len } => f.debug_struct("Redacted").field("original_len", &original_len).finish(),
+            Validated { len, ref detected_type, ref digest } => f.debug_struct("Validated")
+                .field("len", &len)
+                .field("detected_type", detected_type)
+                .field("digest", &digest.map(|digest| digest.iter().map(|b| format!("{:02x}", b)).collect::<String>()))
+                .finish(),
+            ref other => f.debug_tuple(other.kind_str()).field(&ElidedData(other.size() as usize))
+                .finish(),
+        }
+    }
+}
+
+/// Prints `<N bytes>` in place of data in `Debug` impls.
+struct ElidedData(usize);
+
+impl fmt::Debug for ElidedData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} bytes>", self.0)
+    }
+}
+
+impl From<String> for SavedData {
+    fn from(s: String) -> Self {
+        SavedData::Text(s.into())
+    }
+}
+
+impl From<Vec<u8>> for SavedData {
+    fn from(b: Vec<u8>) -> Self {
+        SavedData::Bytes(b.into())
+    }
+}
+
+/// A `Read` (and `BufRead`) adapter for `SavedData`
+pub enum DataReader<'a> {
+    /// In-memory data source (`SavedData::Bytes | Text | Pooled`)
+    Bytes(&'a [u8]),
+    /// On-disk data source (`SavedData::File`), limited to the recorded size of the file,
+    /// which is also kept (`None` from `readable_unbounded()`, which isn't limited)
+    File(io::Take<BufReader<File>>, Option<u64>),
+    /// On-disk data source split across several files (`SavedData::Chunked`)
+    Chunked(ChunkedReader<'a>),
+    /// Owned in-memory data source (`SavedData::Bytes | Text`), from `into_body_reader()`;
+    /// the data is only copied if it was shared with a clone
+    Owned(io::Cursor<Vec<u8>>),
+    /// Owned pooled data source (`SavedData::Pooled`), from `into_body_reader()`
+    Pooled(io::Cursor<PooledBuf>),
+}
+
+impl<'a> DataReader<'a> {
+    /// Open the file at `path`, to read at most `len` bytes if set.
+    fn open_file(path: &Path, len: Option<u64>) -> io::Result<Self> {
+        let file = BufReader::new(fs::File::open(path)?);
+        Ok(DataReader::File(file.take(len.unwrap_or(u64::MAX)), len))
+    }
+
+    /// The number of bytes this reader is expected to yield in total: the recorded size
+    /// for a file, or the length of the data otherwise.
+    ///
+    /// If a file has shrunk since it was saved, reading ends short of this.
+    /// `None` from `SavedData::readable_unbounded()`.
+    pub fn expected_len(&self) -> Option<u64> {
+        use self::DataReader::*;
+
+        match *self {
+            Bytes(bytes) => Some(bytes.len() as u64),
+            File(_, len) => len,
+            Chunked(ref chunks) => Some(chunks.chunks.iter().map(|&(_, size)| size).sum()),
+            Owned(ref bytes) => Some(bytes.get_ref().len() as u64),
+            Pooled(ref buf) => Some(buf.get_ref().len() as u64),
+        }
+    }
+}
+
+impl<'a> Read for DataReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use self::DataReader::*;
+
+        match *self {
+            Bytes(ref mut bytes) => bytes.read(buf),
+            File(ref mut file, _) => file.read(buf),
+            Chunked(ref mut chunks) => chunks.read(buf),
+            Owned(ref mut bytes) => bytes.read(buf),
+            Pooled(ref mut buf_) => buf_.read(buf),
+        }
+    }
+}
+
+impl<'a> BufRead for DataReader<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        use self::DataReader::*;
+
+        match *self {
+            Bytes(ref mut bytes) => bytes.fill_buf(),
+            File(ref mut file, _) => file.fill_buf(),
+            Chunked(ref mut chunks) => chunks.fill_buf(),
+            Owned(ref mut bytes) => bytes.fill_buf(),
+            Pooled(ref mut buf) => buf.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        use self::DataReader::*;
+
+        match *self {
+            Bytes(ref mut bytes) => bytes.consume(amt),
+            File(ref mut file, _) => file.consume(amt),
+            Chunked(ref mut chunks) => chunks.consume(amt),
+            Owned(ref mut bytes) => bytes.consume(amt),
+            Pooled(ref mut buf) => buf.consume(amt),
+        }
+    }
+}
+
+/// A `Read` (and `BufRead`) adapter which reads the files of `SavedData::Chunked` in sequence.
+///
+/// Each file is only opened once the previous one has been read to the end.
+pub struct ChunkedReader<'a> {
+    current: BufReader<File>,
+    chunks: Cow<'a, [(PathBuf, u64)]>,
+    /// The index of the chunk after `current`.
+    next: usize,
+}
+
+impl<'a> ChunkedReader<'a> {
+    fn new(chunks: Cow<'a, [(PathBuf, u64)]>) -> io::Result<Self> {
+        let current = {
+            let first = chunks.first()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no chunks to read"))?;
+            BufReader::new(fs::File::open(&first.0)?)
+        };
+
+        Ok(ChunkedReader { current, chunks, next: 1 })
+    }
+}
+
+impl<'a> Read for ChunkedReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.fill_buf()?.read(buf)?;
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<'a> BufRead for ChunkedReader<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.current.fill_buf()?.is_empty() {
+            let next = match self.chunks.get(self.next) {
+                Some(next) => BufReader::new(fs::File::open(&next.0)?),
+                None => break,
+            };
+
+            self.current = next;
+            self.next += 1;
+        }
+
+        self.current.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.current.consume(amt)
+    }
+}
+
+/// A result of `Multipart::save()`.
+///
+/// The `Debug` impl does not print the contents of in-memory fields; use `summary()` for a
+/// description suitable for logging.
+#[derive(Debug)]
+pub struct Entries {
+    /// The fields of the multipart request, mapped by field name -> value.
+    ///
+    /// A field name may have multiple actual fields associated with it, in the order they
+    /// appeared in the request, but the most common case is a single field.
+    ///
+    /// Each vector is guaranteed not to be empty unless externally modified.
+    ///
+    /// The names are sorted, so iterating over the fields gives the same order for the same
+    /// request every time, e.g. for snapshot tests.
+    pub fields: BTreeMap<Arc<str>, Vec<SavedField>>,
+    /// The directory that the entries in `fields` were saved into.
+    pub save_dir: SaveDir,
+    /// Statistics for the request, updated by `SaveBuilder::with_entries()`.
+    pub stats: SaveStats,
+    /// Whether the request ended with the closing boundary, set by `SaveBuilder::with_entries()`
+    /// once it reaches the end of the request.
+    ///
+    /// See `Multipart::finished_cleanly()`.
+    pub clean_termination: bool,
+    /// The fields which couldn't be saved, in the order they appeared in the request.
+    ///
+    /// Always empty unless `SaveBuilder::on_field_error(FieldErrorPolicy::SkipAndContinue)`
+    /// is set, so `SaveResult::Full` may still be missing some fields; see `is_clean()`.
+    pub failures: Vec<FieldFailure>,
+    /// The headers of the fields which were discarded because they aren't in
+    /// `SaveBuilder::allowed_fields()`, in the order they appeared in the request.
+    pub ignored: Vec<FieldHeaders>,
+    /// Set by `SaveBuilder::stream_field_to()` if the request ended without the field
+    /// it was to stream.
+    pub streamed_field_missing: bool,
+    fields_count: u32,
+    totals: SaveTotals,
+}
+
+impl Entries {
+    /// Create a new `Entries` with the given `SaveDir`
+    pub fn new(save_dir: SaveDir) -> Self {
+        Entries {
+            fields: BTreeMap::new(),
+            save_dir,
+            stats: SaveStats::default(),
+            clean_termination: false,
+            failures: Vec::new(),
+            ignored: Vec::new(),
+            streamed_field_missing: false,
+            fields_count: 0,
+            totals: SaveTotals::default(),
+        }
+    }
+
+    /// Returns `true` if `fields` is empty, `false` otherwise.
+    ///
+    /// This is the case for a request with no parts, which is valid, so check
+    /// `clean_termination` to tell it apart from a body without any boundary (in lenient mode),
+    /// and `failures` or `part_count()` for parts which weren't saved.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns `true` if no field failed to save, i.e. `failures` is empty.
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Keep the saved files on-drop by converting a temporary `save_dir` to permanent.
+    ///
+    /// This is a no-op if `save_dir` is already permanent or `SaveDir::Memory`, so it's safe
+    /// to call more than once. See `SaveDir::keep()` for caveats.
+    pub fn keep_files(&mut self) {
+        self.save_dir.keep();
+    }
+
+    /// Get the directory the files were saved in, or `None` if everything was saved
+    /// in memory (`SaveDir::Memory`).
+    pub fn files_dir(&self) -> Option<&Path> {
+        match self.save_dir {
+            SaveDir::Memory => None,
+            ref save_dir => Some(save_dir.as_path()),
+        }
+    }
+
+    /// Keep the saved files with `keep_files()` and return the now-permanent directory
+    /// alongside `self`, or `None` if everything was saved in memory.
+    pub fn into_kept(mut self) -> (Entries, Option<PathBuf>) {
+        self.keep_files();
+        let dir = self.files_dir().map(Path::to_path_buf);
+        (self, dir)
+    }
+
+    /// The number of actual fields contained within this `Entries`.
+    ///
+    /// Effectively `self.fields.values().map(Vec::len).sum()` but maintained separately.
+    ///
+    /// ## Note
+    /// This will be incorrect if `fields` is modified externally. Call `recount_fields()`
+    /// to get the correct count.
+    pub fn fields_count(&self) -> u32 {
+        self.fields_count
+    }
+
+    /// The number of parts read from the request: the fields in `fields`, `failures` and
+    /// `ignored`, plus the parts with no headers or data which were skipped (`stats.empty_parts`).
+    ///
+    /// Zero for a request with no parts, e.g. a body which is just the closing boundary.
+    /// Fields discarded by `UnnamedPolicy::Skip` are not counted.
+    pub fn part_count(&self) -> u64 {
+        u64::from(self.fields_count) + self.failures.len() as u64 + self.ignored.len() as u64
+            + u64::from(self.stats.empty_parts)
+    }
+
+    /// The number of bytes and fields in memory and on disk.
+    ///
+    /// Maintained as fields are added and by `spill_all()` and `load_all()`, so this doesn't
+    /// iterate over `fields`.
+    ///
+    /// ## Note
+    /// Like `fields_count()`, this will be incorrect if `fields` is modified externally.
+    /// Call `recount_totals()` to get the correct totals.
+    pub fn totals(&self) -> SaveTotals {
+        self.totals
+    }
+
+    /// Sum the sizes of the fields in this `Entries` and then return the updated `totals()`.
+    pub fn recount_totals(&mut self) -> SaveTotals {
+        let mut totals = SaveTotals::default();
+
+        for field in self.fields.values().flatten() {
+            totals.add(&field.data);
+        }
+
+        self.totals = totals;
+        totals
+    }
+
+    /// Sum the number of fields in this `Entries` and then return the updated value.
+    pub fn recount_fields(&mut self) -> u32 {
+        let fields_count = self.fields.values().map(Vec::len).sum();
+        // saturating cast
+        self.fields_count = cmp::min(u32::MAX as usize, fields_count) as u32;
+        self.fields_count
+    }
+
+    fn push_field(&mut self, headers: FieldHeaders, data: SavedData) {
+        self.push_saved(SavedField { headers, data, detected_type: None, timing: None, raw_range: None });
+    }
+
+    fn push_saved(&mut self, mut field: SavedField) {
+        use std::collections::btree_map::Entry::*;
+
+        self.totals.add(&field.data);
+        self.stats.add_timing(&field);
+
+        match self.fields.entry(field.headers.name.clone()) {
+            Vacant(vacant) => { vacant.insert(vec![field]); },
+            Occupied(occupied) => {
+                // dedup the field name by reusing the key's `Arc`
+                field.headers.name = occupied.key().clone();
+                occupied.into_mut().push(field);
+            },
+        }
+
+        self.fields_count = self.fields_count.saturating_add(1);
+    }
+
+    /// Get a description of these entries which implements `Display`, with one line per field.
+    ///
+    /// See [`EntriesSummary`](struct.EntriesSummary.html) for details.
+    pub fn summary(&self) -> EntriesSummary<'_> {
+        EntriesSummary { entries: self, redact: Vec::new() }
+    }
+
+    /// Get the `SavedField::meta()` of every field, sorted by name, with fields of the same
+    /// name in the order they were saved.
+    pub fn metas<'a>(&'a self) -> impl Iterator<Item = FieldMeta> + 'a {
+        self.fields.values().flat_map(|fields| fields.iter().map(SavedField::meta))
+    }
+
+    /// Get the file fields (see `SavedField::is_file()`) which were uploaded without any data,
+    /// in the same order as `metas()`.
+    ///
+    /// These are often placeholders rather than failed uploads; fields with content are the
+    /// ones for which `data.size()` is nonzero.
+    pub fn empty_files<'a>(&'a self) -> impl Iterator<Item = &'a SavedField> + 'a {
+        self.fields.values().flatten().filter(|field| field.is_file() && field.data.size() == 0)
+    }
+
+    /// Compute a SHA-256 digest over the name, `filename` and `content_type` of every field
+    /// and its data, e.g. to detect changes or to compare the result of a save in tests.
+    ///
+    /// Fields are hashed in the order of `fields`; the data of files is read in a streaming
+    /// fashion. Nothing else goes into the hash, so it is the same whether the data is in memory
+    /// or in files, and regardless of where they were saved, of the other headers and of `stats`.
+    ///
+    /// ### Stability
+    /// The hash is the same on every platform for a given version of this crate, but how it
+    /// is computed may change in any new version, so don't persist it across upgrades.
+    ///
+    /// ### Errors
+    /// If the data of a field can't be read, which includes `SavedData::Streamed`.
+    ///
+    /// #### Feature: `digest`
+    #[cfg(feature = "digest")]
+    pub fn content_hash(&self) -> io::Result<[u8; 32]> {
+        /// Hash `bytes` with their length, so adjacent values can't run into each other.
+        fn update_prefixed(hasher: &mut Hasher, bytes: &[u8]) {
+            hasher.update(&(bytes.len() as u64).to_be_bytes());
+            hasher.update(bytes);
+        }
+
+        fn update_opt(hasher: &mut Hasher, bytes: Option<&[u8]>) {
+            match bytes {
+                Some(bytes) => {
+                    hasher.update(&[1]);
+                    update_prefixed(hasher, bytes);
+                },
+                None => hasher.update(&[0]),
+            }
+        }
+
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+
+        for field in self.fields.values().flatten() {
+            update_prefixed(&mut hasher, field.headers.name.as_bytes());
+            update_opt(&mut hasher, field.headers.filename.as_ref().map(|filename| filename.as_bytes()));
+            update_opt(&mut hasher, field.headers.content_type.as_ref().map(|ct| ct.as_ref().as_bytes()));
+
+            // the data is hashed on its own, as its length is only known at the end
+            let mut data_hasher = Hasher::new(Algorithm::Sha256);
+            let mut reader = field.data.readable()?;
+
+            loop {
+                let read = {
+                    let buf = reader.fill_buf()?;
+                    data_hasher.update(buf);
+                    buf.len()
+                };
+
+                if read == 0 { break; }
+                reader.consume(read);
+            }
+
+            hasher.update(&data_hasher.finish());
+        }
+
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&hasher.finish());
+        Ok(hash)
+    }
+
+    /// Apply `SavedData::spill_to_dir()` to every field, so that all of them are in files.
+    ///
+    /// A field which fails is left as it was and the rest are still spilled;
+    /// the name of each failed field is returned with its error.
+    pub fn spill_all(&mut self, dir: &Path) -> Vec<(Arc<str>, io::Error)> {
+        self.for_each_data(|data| data.spill_to_dir(dir))
+    }
+
+    /// Apply `SavedData::load_into_memory()` to every field, so that all of them are in memory.
+    ///
+    /// `cap` applies to each field individually. A field which fails is left as it was and
+    /// the rest are still loaded; the name of each failed field is returned with its error.
+    pub fn load_all(&mut self, cap: u64) -> Vec<(Arc<str>, io::Error)> {
+        self.for_each_data(|data| data.load_into_memory(cap))
+    }
+
+    /// Write the fields to `dest` as a tar archive, returning the number of bytes written,
+    /// e.g. to bundle the uploads of a request for cold storage.
+    ///
+    /// Each field with data, in memory or in files, becomes a file in the root of the archive,
+    /// in the order of `fields`; the data of files is streamed. Fields which were streamed,
+    /// redacted or only validated are left out.
+    ///
+    /// An entry is named after the last component of the field's filename, or else after the
+    /// field name, without control characters or the trailing dots and spaces which Windows
+    /// strips, and cut off at 100 bytes. A name which was already used, ignoring case,
+    /// gets a number before its extension, e.g. `photo (1).jpg`.
+    ///
+    /// If the data of a field can't be read, or a file is shorter than its recorded size,
+    /// an error is returned with the archive written up to that field.
+    ///
+    /// #### Feature: `archive`
+    #[cfg(feature = "archive")]
+    pub fn write_tar<W: Write>(&self, dest: W) -> io::Result<u64> {
+        archive::write_tar(self, dest)
+    }
+
+    /// Write the fields to `dest` as a zip archive, returning the number of bytes written.
+    ///
+    /// The same as `write_tar()` otherwise. The entries are stored without compression,
+    /// with their size and CRC after the data, and with Zip64 records for fields or archives
+    /// of 4 GiB or more.
+    ///
+    /// #### Feature: `archive`
+    #[cfg(feature = "archive")]
+    pub fn write_zip<W: Write>(&self, dest: W) -> io::Result<u64> {
+        archive::write_zip(self, dest)
+    }
+
+    /// Get the fields with a `raw_range`, in the order they were in the request body,
+    /// with their ranges; see `SaveBuilder::collect_raw_ranges()`.
+    ///
+    /// For example, the ranges of the fields before a `signature` field are those up to the
+    /// first one named `signature`, which can be passed to `read_raw_ranges()`. Only saved
+    /// fields are included, so a part which wasn't saved, e.g. one in `failures` or without
+    /// a name, is left out rather than signed.
+    pub fn raw_ranges(&self) -> Vec<(&SavedField, RawRange)> {
+        let mut ranges: Vec<_> = self.fields.values().flatten()
+            .filter_map(|field| field.raw_range.map(|range| (field, range)))
+            .collect();
+
+        ranges.sort_by_key(|&(_, range)| range.headers_start);
+        ranges
+    }
+
+    fn for_each_data<F>(&mut self, mut f: F) -> Vec<(Arc<str>, io::Error)>
+    where F: FnMut(&mut SavedData) -> io::Result<()> {
+        let mut errors = Vec::new();
+
+        for (name, fields) in &mut self.fields {
+            for field in fields {
+                self.totals.sub(&field.data);
+
+                if let Err(e) = f(&mut field.data) {
+                    errors.push((name.clone(), e));
+                }
+
+                self.totals.add(&field.data);
+            }
+        }
+
+        errors
+    }
+
+    /// Print all fields and their contents to stdout. Mostly for testing purposes.
+    pub fn print_debug(&self) -> io::Result<()> {
+        let stdout = io::stdout();
+        let stdout_lock = stdout.lock();
+        self.write_debug(stdout_lock)
+    }
+
+    /// Write all fields and their contents to the given output. Mostly for testing purposes.
+    pub fn write_debug<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (name, entries) in &self.fields {
+            writeln!(writer, "Field {:?} has {} entries:", name, entries.len())?;
+
+            for (idx, field) in entries.iter().enumerate() {
+                let mut data = field.data.readable()?;
+                let headers = &field.headers;
+                writeln!(writer, "{}: {:?} ({:?}):", idx, headers.filename, headers.content_type)?;
+                io::copy(&mut data, &mut writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A description of `Entries` suitable for logging, returned by `Entries::summary()`.
+///
+/// Prints the number of fields and the save directory, followed by the
+/// [summary](struct.FieldSummary.html) of each field on its own line, ordered by name.
+/// The in-memory data of fields with a name passed to `redact()` is not printed at all.
+///
+/// ```rust
+/// # use multipart::server::parse_form_data;
+/// let body = b"--boundary\r\n\
+///              Content-Disposition: form-data; name=\"user\"\r\n\r\n\
+///              alice\r\n\
+///              --boundary\r\n\
+///              Content-Disposition: form-data; name=\"password\"\r\n\r\n\
+///              hunter2\r\n\
+///              --boundary--\r\n";
+///
+/// let entries = parse_form_data(body, "multipart/form-data; boundary=boundary").unwrap();
+/// let summary = entries.summary().redact("password").to_string();
+///
+/// assert!(summary.contains("\"alice\""));
+/// assert!(!summary.contains("hunter2"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct EntriesSummary<'a> {
+    entries: &'a Entries,
+    redact: Vec<&'a str>,
+}
+
+impl<'a> EntriesSummary<'a> {
+    /// Do not print any in-memory data of fields with this name (case-sensitive).
+    ///
+    /// May be called multiple times.
+    pub fn redact(mut self, name: &'a str) -> Self {
+        self.redact.push(name);
+        self
+    }
+}
+
+impl<'a> fmt::Display for EntriesSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.entries.save_dir {
+            SaveDir::Memory => write!(f, "{} fields in memory", self.entries.fields_count)?,
+            ref dir => write!(f, "{} fields in {:?}", self.entries.fields_count, dir.as_path())?,
+        }
+
+        let mut names: Vec<_> = self.entries.fields.keys().collect();
+        names.sort();
+
+        for name in names {
+            let redacted = self.redact.contains(&&**name);
+
+            for field in &self.entries.fields[name] {
+                write!(f, "\n  {}", field.summary().redacted(redacted))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics for a request saved with `SaveBuilder::with_entries()`.
+///
+/// These are updated as each field is completed, so after a partial save they reflect
+/// the request up to the end of the last field that was saved in full.
+#[derive(Clone, Debug, Default)]
+pub struct SaveStats {
+    /// The number of bytes of field data read from the request,
+    /// including fields which were not saved.
+    ///
+    /// See `Multipart::payload_bytes()`.
+    pub payload_bytes: u64,
+    /// The number of bytes read from the request which were not field data:
+    /// the preamble, boundary lines and field headers.
+    ///
+    /// See `Multipart::overhead_bytes()`.
+    pub overhead_bytes: u64,
+    /// The number of parts with no headers and no data which were skipped.
+    ///
+    /// See `Multipart::empty_parts()`.
+    pub empty_parts: u32,
+    /// The most files which were open for writing at once.
+    ///
+    /// See `SaveBuilder::max_open_files()`.
+    pub peak_open_files: u32,
+    /// The directories created for the files saved, in the order they were created.
+    ///
+    /// Directories removed again by `SaveBuilder::cleanup_created_dirs()` are left out.
+    pub created_dirs: Vec<PathBuf>,
+    /// The number of times a random file name was already taken and another one was tried.
+    ///
+    /// See `SaveBuilder::name_prefix()`.
+    pub name_collisions: u32,
+    /// The shortest, longest and mean read times of the fields saved, or `None` if no field
+    /// was timed.
+    ///
+    /// See `SavedField::timing`.
+    pub field_timing: Option<FieldTimingStats>,
+}
+
+impl SaveStats {
+    fn record<M: ReadEntry>(&mut self, savable: &M, open_files: &OpenFiles, created_dirs: &CreatedDirs,
+                            name_collisions: &AtomicU32) {
+        self.payload_bytes = savable.payload_bytes();
+        self.overhead_bytes = savable.overhead_bytes();
+        self.empty_parts = savable.empty_parts();
+        self.peak_open_files = cmp::max(self.peak_open_files, open_files.peak());
+        self.created_dirs = created_dirs.created();
+        self.name_collisions = name_collisions.load(Ordering::Relaxed);
+    }
+
+    fn add_timing(&mut self, field: &SavedField) {
+        let duration = match field.read_duration() {
+            Some(duration) => duration,
+            None => return,
+        };
+
+        let throughput = field.throughput_bytes_per_sec();
+
+        let stats = self.field_timing.get_or_insert(FieldTimingStats {
+            fields: 0,
+            min_duration: duration,
+            max_duration: duration,
+            mean_duration: duration,
+            min_throughput: throughput,
+            max_throughput: throughput,
+            mean_throughput: throughput,
+            total_duration: Duration::from_secs(0),
+            throughput_fields: 0,
+        });
+
+        stats.fields = stats.fields.saturating_add(1);
+        stats.min_duration = cmp::min(stats.min_duration, duration);
+        stats.max_duration = cmp::max(stats.max_duration, duration);
+        stats.total_duration = stats.total_duration.saturating_add(duration);
+        stats.mean_duration = stats.total_duration / stats.fields;
+
+        if let Some(throughput) = throughput {
+            stats.throughput_fields = stats.throughput_fields.saturating_add(1);
+            stats.min_throughput = Some(stats.min_throughput.map_or(throughput, |min| min.min(throughput)));
+            stats.max_throughput = Some(stats.max_throughput.map_or(throughput, |max| max.max(throughput)));
+
+            let mean = stats.mean_throughput.unwrap_or(0.0);
+            stats.mean_throughput = Some(mean + (throughput - mean) / f64::from(stats.throughput_fields));
+        }
+    }
+}
+
+/// The read times of the fields of a request, in `SaveStats::field_timing`.
+///
+/// Only fields which were saved in full are counted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldTimingStats {
+    /// The number of fields timed.
+    pub fields: u32,
+    /// The shortest `SavedField::read_duration()`.
+    pub min_duration: Duration,
+    /// The longest `SavedField::read_duration()`.
+    pub max_duration: Duration,
+    /// The mean `SavedField::read_duration()`.
+    pub mean_duration: Duration,
+    /// The lowest `SavedField::throughput_bytes_per_sec()`, or `None` if no field took
+    /// a measurable time.
+    pub min_throughput: Option<f64>,
+    /// The highest `SavedField::throughput_bytes_per_sec()`, as above.
+    pub max_throughput: Option<f64>,
+    /// The mean `SavedField::throughput_bytes_per_sec()`, as above.
+    pub mean_throughput: Option<f64>,
+    total_duration: Duration,
+    throughput_fields: u32,
+}
+
+/// The number of bytes and fields of `Entries` in memory and on disk, returned by
+/// `Entries::totals()`.
+///
+/// `SavedData::Streamed` fields are in neither.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SaveTotals {
+    /// The total size of the fields saved to files (`SavedData::is_file()`).
+    pub disk_bytes: u64,
+    /// The total size of the fields kept in memory (`SavedData::is_memory()`).
+    pub memory_bytes: u64,
+    /// The number of fields saved to files.
+    pub file_count: u32,
+    /// The number of fields saved as text (`SavedData::Text`), which are also in memory.
+    pub text_count: u32,
+}
+
+impl SaveTotals {
+    fn add(&mut self, data: &SavedData) {
+        if data.is_file() {
+            self.disk_bytes = self.disk_bytes.saturating_add(data.size());
+            self.file_count = self.file_count.saturating_add(1);
+        } else if data.is_memory() {
+            self.memory_bytes = self.memory_bytes.saturating_add(data.size());
+        }
+
+        if let SavedData::Text(_) = *data {
+            self.text_count = self.text_count.saturating_add(1);
+        }
+    }
+
+    fn sub(&mut self, data: &SavedData) {
+        if data.is_file() {
+            self.disk_bytes = self.disk_bytes.saturating_sub(data.size());
+            self.file_count = self.file_count.saturating_sub(1);
+        } else if data.is_memory() {
+            self.memory_bytes = self.memory_bytes.saturating_sub(data.size());
+        }
+
+        if let SavedData::Text(_) = *data {
+            self.text_count = self.text_count.saturating_sub(1);
+        }
+    }
+}
+
+/// Counts the files open for writing during a save, for `SaveBuilder::max_open_files()`.
+#[derive(Debug, Default)]
+struct OpenFiles {
+    /// `(open, peak)`
+    counts: Mutex<(u32, u32)>,
+}
+
+impl OpenFiles {
+    fn open(this: &Arc<Self>, max: u32) -> io::Result<OpenFileGuard> {
+        let mut counts = this.counts.lock().unwrap_or_else(|e| e.into_inner());
+
+        if counts.0 >= max {
+            return Err(io::Error::other(
+                format!("opening another file would exceed max_open_files({})", max)
+            ));
+        }
+
+        counts.0 += 1;
+        counts.1 = cmp::max(counts.0, counts.1);
+
+        Ok(OpenFileGuard(this.clone()))
+    }
+
+    fn peak(&self) -> u32 {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner()).1
+    }
+}
+
+/// Counts a file as open in `OpenFiles` until dropped.
+struct OpenFileGuard(Arc<OpenFiles>);
+
+impl Drop for OpenFileGuard {
+    fn drop(&mut self) {
+        self.0.counts.lock().unwrap_or_else(|e| e.into_inner()).0 -= 1;
+    }
+}
+
+/// The directories created during a save, for `SaveBuilder::max_created_dirs()`
+/// and `cleanup_created_dirs()`.
+#[derive(Debug, Default)]
+struct CreatedDirs {
+    /// `(not yet removed, in the order they were created; total created)`
+    dirs: Mutex<(Vec<PathBuf>, u32)>,
+}
+
+impl CreatedDirs {
+    /// Create any missing parent directories of `path`, unless that would make more than `max`.
+    ///
+    /// The directories created are given `mode`, if set.
+    fn create_parents(&self, path: &Path, max: u32, mode: Option<u32>) -> io::Result<()> {
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return create_dir_all(path),
+        };
+
+        // deepest first
+        let missing: Vec<&Path> = parent.ancestors()
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.is_dir())
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let mut dirs = self.dirs.lock().unwrap_or_else(|e| e.into_inner());
+
+        if u64::from(dirs.1) + missing.len() as u64 > u64::from(max) {
+            return Err(io::Error::other(DirLimitExceeded { limit: max, path: parent.into() }));
+        }
+
+        for dir in missing.into_iter().rev() {
+            match create_dir_with_mode(dir, mode) {
+                Ok(()) => {
+                    dirs.0.push(dir.into());
+                    dirs.1 += 1;
+                },
+                // created concurrently
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && dir.is_dir() => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The directories created so far which haven't been removed.
+    fn created(&self) -> Vec<PathBuf> {
+        self.dirs.lock().unwrap_or_else(|e| e.into_inner()).0.clone()
+    }
+
+    /// Remove the directories created so far which are empty, deepest first.
+    fn remove_empty(&self) {
+        let mut dirs = self.dirs.lock().unwrap_or_else(|e| e.into_inner());
+
+        while let Some(dir) = dirs.0.pop() {
+            if let Err(e) = fs::remove_dir(&dir) {
+                debug!("Not removing created directory {:?}: {}", dir, e);
+            }
+        }
+    }
+}
+
+/// Create `dir`, giving it exactly `mode` if set rather than masking it with the umask.
+#[cfg(unix)]
+fn create_dir_with_mode(dir: &Path, mode: Option<u32>) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let mode = match mode {
+        Some(mode) => mode,
+        None => return fs::create_dir(dir),
+    };
+
+    // only the owner has access until the full mode is set
+    fs::DirBuilder::new().mode(mode & 0o700).create(dir)?;
+
+    fs::set_permissions(dir, fs::Permissions::from_mode(mode)).inspect_err(|_| {
+        let _ = fs::remove_dir(dir);
+    })
+}
+
+#[cfg(not(unix))]
+fn create_dir_with_mode(dir: &Path, _mode: Option<u32>) -> io::Result<()> {
+    fs::create_dir(dir)
+}
+
+/// The error wrapped in `io::Error` when saving a file would create more directories than
+/// set with `SaveBuilder::max_created_dirs()`.
+#[derive(Debug)]
+pub struct DirLimitExceeded {
+    /// The limit which was exceeded.
+    pub limit: u32,
+    /// The directory which would have been created, along with any missing parents.
+    pub path: PathBuf,
+}
+
+impl fmt::Display for DirLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "creating {:?} would exceed max_created_dirs({})", self.path, self.limit)
+    }
+}
+
+impl ::std::error::Error for DirLimitExceeded {
+    fn description(&self) -> &str {
+        "directory limit exceeded"
+    }
+}
+
+/// The error wrapped in `io::Error` when `SaveBuilder::temp()` couldn't create a temporary
+/// directory in any of the directories it tried (see `SaveBuilder::temp_fallbacks()`).
+///
+/// The `io::Error` has the kind of the first attempt's error.
+#[derive(Debug)]
+pub struct TempDirError {
+    /// Each directory tried, in order, with the error creating a temporary directory in it.
+    pub attempts: Vec<(PathBuf, io::Error)>,
+    /// Whether the first directory was the OS temporary directory, as opposed to one
+    /// set with `SaveBuilder::temp_parent()`.
+    pub os_temp_dir: bool,
+}
+
+/// The environment variables `env::temp_dir()` consults, for `TempDirError`.
+#[cfg(windows)]
+const TEMP_DIR_VARS: &str = "TMP or TEMP";
+#[cfg(not(windows))]
+const TEMP_DIR_VARS: &str = "TMPDIR";
+
+impl fmt::Display for TempDirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("could not create a temporary directory")?;
+
+        for (idx, (path, err)) in self.attempts.iter().enumerate() {
+            match (idx, self.os_temp_dir) {
+                (0, true) => write!(f, " in {:?} (the OS temporary directory, set with {})", path, TEMP_DIR_VARS)?,
+                (0, false) => write!(f, " in {:?}", path)?,
+                _ => write!(f, ", nor in fallback {:?}", path)?,
+            }
+
+            write!(f, ": {}", err)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ::std::error::Error for TempDirError {
+    fn description(&self) -> &str {
+        "could not create a temporary directory"
+    }
+}
+
+/// A directory shared by many requests, each saved to its own subdirectory with
+/// `SaveBuilder::with_shared_root()`.
+///
+/// Create one at startup and clone it (cheaply) for each request. If it is temporary,
+/// the directory and everything in it is deleted once the last clone is dropped,
+/// including clones held by `SaveDir::PermIn`.
+#[derive(Clone, Debug)]
+pub struct SharedSaveRoot {
+    dir: Arc<SaveDir>,
+}
+
+/// The number of random names to try for a subdirectory before giving up.
+const SUBDIR_ATTEMPTS: u32 = 8;
+
+impl SharedSaveRoot {
+    /// Create a new temporary directory prefixed with `multipart-rs` in the OS temporary
+    /// directory.
+    pub fn temp() -> io::Result<Self> {
+        tempfile::Builder::new().prefix("multipart-rs").tempdir().map(Self::with_temp_dir)
+    }
+
+    /// Use the given `TempDir`, which is deleted once the last clone of the root is dropped.
+    pub fn with_temp_dir(tempdir: ::tempfile::TempDir) -> Self {
+        SharedSaveRoot { dir: Arc::new(SaveDir::Temp(tempdir)) }
+    }
+
+    /// Use a permanent directory with the given path, creating any nonexistent directories.
+    pub fn with_dir<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        create_dir_all(&dir)?;
+        Ok(SharedSaveRoot { dir: Arc::new(SaveDir::Perm(dir)) })
+    }
+
+    /// Get the path of the root directory.
+    pub fn as_path(&self) -> &Path {
+        self.dir.as_path()
+    }
+
+    /// Returns `true` if the root directory will be deleted once the last clone is dropped.
+    pub fn is_temporary(&self) -> bool {
+        self.dir.is_temporary()
+    }
+
+    fn create_subdir(&self) -> io::Result<PathBuf> {
+        let mut attempts = 0;
+
+        loop {
+            let sub = self.as_path().join(rand_filename());
+
+            match fs::create_dir(&sub) {
+                Ok(()) => return Ok(sub),
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists
+                    && attempts < SUBDIR_ATTEMPTS => attempts += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// The save directory for `Entries`. May be temporary (delete-on-drop) or permanent.
+#[derive(Debug)]
+pub enum SaveDir {
+    /// This directory is temporary and will be deleted, along with its contents, when this wrapper
+    /// is dropped.
+    Temp(::tempfile::TempDir),
+    /// This directory is permanent and will be left on the filesystem when this wrapper is dropped.
+    ///
+    /// **N.B.** If this directory is in the OS temporary directory then it may still be
+    /// deleted at any time.
+    Perm(PathBuf),
+    /// There is no directory because all fields were saved to memory with
+    /// `SaveBuilder::in_memory()`. The path is empty.
+    Memory,
+    /// A permanent subdirectory of a `SharedSaveRoot`, created by
+    /// `SaveBuilder::with_shared_root()`.
+    ///
+    /// It is left on the filesystem when this wrapper is dropped, but if the root is temporary
+    /// it is deleted along with the root once the last clone of the root is dropped.
+    PermIn {
+        /// The root which `sub` is in.
+        root: SharedSaveRoot,
+        /// The path of the subdirectory, inside the root.
+        sub: PathBuf,
+    },
+}
+
+impl SaveDir {
+    /// Get the path of this directory, either temporary or permanent.
+    pub fn as_path(&self) -> &Path {
+        use self::SaveDir::*;
+        match *self {
+            Temp(ref tempdir) => tempdir.path(),
+            Perm(ref pathbuf) => &*pathbuf,
+            Memory => Path::new(""),
+            PermIn { ref sub, .. } => sub,
+        }
+    }
+
+    /// Get the path of this directory if it is valid UTF-8 (empty for `Memory`).
+    /// Enabled with the `camino` feature.
+    #[cfg(feature = "camino")]
+    pub fn as_utf8_path(&self) -> Option<&Utf8Path> {
+        Utf8Path::from_path(self.as_path())
+    }
+
+    /// Get the directory this one is in, e.g. to tell which of `SaveBuilder::temp_parent()`
+    /// and `SaveBuilder::temp_fallbacks()` was used. `None` for `Memory`.
+    pub fn parent(&self) -> Option<&Path> {
+        match *self {
+            SaveDir::Memory => None,
+            _ => self.as_path().parent(),
+        }
+    }
+
+    /// Returns `true` if this is a temporary directory which will be deleted on-drop.
+    pub fn is_temporary(&self) -> bool {
+        use self::SaveDir::*;
+        match *self {
+            Temp(_) => true,
+            Perm(_) | Memory | PermIn { .. } => false,
+        }
+    }
+
+    /// Unwrap the `PathBuf` from `self`; if this is a temporary directory,
+    /// it will be converted to a permanent one.
+    ///
+    /// A `PermIn` subdirectory of a temporary root is still deleted along with the root.
+    pub fn into_path(self) -> PathBuf {
+        use self::SaveDir::*;
+
+        match self {
+            Temp(tempdir) => tempdir.into_path(),
+            Perm(pathbuf) => pathbuf,
+            Memory => PathBuf::new(),
+            PermIn { sub, .. } => sub,
+        }
+    }
+
+    /// If this `SaveDir` is temporary, convert it to permanent.
+    /// This is a no-op if it already is permanent.
+    ///
+    /// ### Warning: Potential Data Loss
+    /// Even though this will prevent deletion on-drop, the temporary folder on most OSes
+    /// (where this directory is created by default) can be automatically cleared by the OS at any
+    /// time, usually on reboot or when free space is low.
+    ///
+    /// It is recommended that you relocate the files from a request which you want to keep to a
+    /// permanent folder on the filesystem.
+    pub fn keep(&mut self) {
+        use self::SaveDir::*;
+        *self = match mem::replace(self, Perm(PathBuf::new())) {
+            Temp(tempdir) => Perm(tempdir.into_path()),
+            old_self => old_self,
+        };
+    }
+
+    /// Delete this directory and its contents, regardless of its permanence.
+    ///
+    /// ### Warning: Potential Data Loss
+    /// This is very likely irreversible, depending on the OS implementation.
+    ///
+    /// Files deleted programmatically are deleted directly from disk, as compared to most file
+    /// manager applications which use a staging area from which deleted files can be safely
+    /// recovered (i.e. Windows' Recycle Bin, OS X's Trash Can, etc.).
+    pub fn delete(self) -> io::Result<()> {
+        use self::SaveDir::*;
+        match self {
+            Temp(tempdir) => tempdir.close(),
+            Perm(pathbuf) => fs::remove_dir_all(&pathbuf),
+            Memory => Ok(()),
+            PermIn { sub, .. } => fs::remove_dir_all(&sub),
+        }
+    }
+}
+
+impl AsRef<Path> for SaveDir {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+/// Move the files of `entries` from `staging` into `dir` for `SaveBuilder::transactional()`,
+/// then remove `staging`.
+///
+/// The names of the files are written to `COMMIT_MARKER` first, for `recover_staging()`.
+/// If a file can't be moved, the files moved so far are moved back.
+fn commit_staged(entries: &mut Entries, staging: &Path, dir: &Path, name_prefix: &str, rename_hook: fn(usize))
+                 -> io::Result<()> {
+    // a rename would replace a file of the same name, which a client's filename may be
+    for field in entries.fields.values_mut().flatten() {
+        if let SavedData::File(ref mut path, _) = field.data {
+            if path.file_name().is_some_and(|name| dir.join(name).symlink_metadata().is_ok()) {
+                let renamed = staging.join(format!("{}{}", name_prefix, rand_filename()));
+                debug!("{:?} already taken in {:?}, renaming to {:?}", path, dir, renamed);
+
+                fs::rename(&*path, &renamed)?;
+                *path = renamed;
+            }
+        }
+    }
+
+    let names: Vec<String> = entries.fields.values().flatten()
+        .flat_map(|field| file_paths(&field.data))
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+
+    if !names.is_empty() {
+        let marker = staging.join(COMMIT_MARKER);
+
+        // renamed into place so it's never seen half-written
+        let marker_tmp = staging.join(format!("{}.tmp", COMMIT_MARKER));
+        let mut file = OpenOptions::new().write(true).create_new(true).open(&marker_tmp)?;
+        file.write_all(names.join("\n").as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&marker_tmp, &marker)?;
+
+        for (moved, name) in names.iter().rev().enumerate() {
+            rename_hook(moved);
+
+            if let Err(e) = fs::rename(staging.join(name), dir.join(name)) {
+                for name in names.iter().rev().take(moved) {
+                    if let Err(e) = fs::rename(dir.join(name), staging.join(name)) {
+                        warn!("Failed to move {:?} back to staging directory {:?}: {}", name, staging, e);
+                    }
+                }
+
+                let _ = fs::remove_file(&marker);
+                return Err(e);
+            }
+        }
+
+        for field in entries.fields.values_mut().flatten() {
+            move_paths(&mut field.data, dir);
+        }
+
+        if let Err(e) = fs::remove_file(&marker) {
+            warn!("Failed to remove commit marker {:?}: {}", marker, e);
+        }
+    }
+
+    match fs::remove_dir(staging) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => warn!("Failed to remove staging directory {:?}: {}", staging, e),
+        Ok(()) => (),
+    }
+
+    entries.save_dir = SaveDir::Perm(dir.into());
+    Ok(())
+}
+
+/// Point the paths of `data` to files with the same names in `dir`.
+fn move_paths(data: &mut SavedData, dir: &Path) {
+    let moved = |path: &Path| match path.file_name() {
+        Some(name) => dir.join(name),
+        None => path.into(),
+    };
+
+    match *data {
+        SavedData::File(ref mut path, _) => *path = moved(path),
+        SavedData::Chunked(ref mut chunks) => for chunk in chunks.iter_mut() {
+            chunk.0 = moved(&chunk.0);
+        },
+        _ => (),
+    }
+}
+
+/// What `recover_staging()` did with the staging directories it found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// The staging directories of saves which were interrupted while their files were being
+    /// moved into place; the rest of their files were moved, and the directories removed.
+    pub completed: Vec<PathBuf>,
+    /// The staging directories of saves which were interrupted before that; they were deleted
+    /// along with their files.
+    pub discarded: Vec<PathBuf>,
+    /// The number of files moved into place.
+    pub files_moved: u64,
+}
+
+/// Clean up after `SaveBuilder::transactional()` saves into `dir` which were interrupted,
+/// e.g. by a crash: saves which had started moving their files into `dir` are finished,
+/// and the rest are deleted.
+///
+/// Call this before saving anything into `dir`, e.g. at startup, as the staging directories
+/// of saves still in progress would be deleted.
+pub fn recover_staging<P: AsRef<Path>>(dir: P) -> io::Result<RecoveryReport> {
+    let dir = dir.as_ref();
+    let mut report = RecoveryReport::default();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_staging = entry.file_name().to_str().is_some_and(|name| name.starts_with(STAGING_PREFIX));
+
+        if !is_staging || !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let staging = entry.path();
+
+        match fs::read_to_string(staging.join(COMMIT_MARKER)) {
+            Ok(names) => {
+                for name in names.lines().rev() {
+                    if Path::new(name).file_name() != Some(name.as_ref()) {
+                        warn!("Skipping invalid file name {:?} in {:?}", name, staging);
+                        continue;
+                    }
+
+                    match fs::rename(staging.join(name), dir.join(name)) {
+                        Ok(()) => report.files_moved += 1,
+                        // moved before the interruption
+                        Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                fs::remove_dir_all(&staging)?;
+                report.completed.push(staging);
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                fs::remove_dir_all(&staging)?;
+                report.discarded.push(staging);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(report)
+}
+
+/// The reason the save operation quit partway through.
+///
+/// The `Display` impl gives a message suitable for the client, e.g.
+/// ``field `video` exceeded the size limit of 100 MiB (saved 100 MiB)``; see also
+/// `suggested_status()`.
+#[derive(Debug)]
+pub enum PartialReason {
+    /// The limit on the number of fields in the request, set with `SaveBuilder::max_parts()`,
+    /// was hit.
+    ///
+    /// The next field has not been saved.
+    CountLimit {
+        /// The `max_parts` that was set.
+        limit: u32,
+    },
+    /// The limit on the number of files in the request, set with
+    /// `SaveBuilder::max_file_parts()`, was hit.
+    ///
+    /// The file beyond the limit has not been saved; it's in `PartialEntries::partial`.
+    FilePartLimit {
+        /// The `max_file_parts` that was set.
+        limit: u32,
+    },
+    /// The limit on the number of fields without a filename in the request, set with
+    /// `SaveBuilder::max_text_parts()`, was hit.
+    ///
+    /// The field beyond the limit has not been saved; it's in `PartialEntries::partial`.
+    TextPartLimit {
+        /// The `max_text_parts` that was set.
+        limit: u32,
+    },
+    /// The size limit for an individual file was hit.
+    ///
+    /// The file was partially written to the filesystem.
+    SizeLimit {
+        /// The name of the field, or empty if a single field was saved with
+        /// `MultipartData::save()`.
+        field: Arc<str>,
+        /// The `size_limit` that was set.
+        limit: u64,
+        /// The number of bytes of the field which were saved.
+        written: u64,
+    },
+    /// The limit on the number of bytes saved across all fields, set with
+    /// `SaveBuilder::total_size_limit()`, was hit.
+    ///
+    /// The field which crossed the limit was partially saved; it's in `PartialEntries::partial`.
+    TotalSizeLimit {
+        /// The `total_size_limit` that was set.
+        limit: u64,
+        /// The number of bytes of all fields which were saved, including the partial field.
+        saved: u64,
+    },
+    /// The size limit for a field without a filename, set with `SaveBuilder::text_size_limit()`,
+    /// was hit.
+    ///
+    /// The field was partially saved; it's in `PartialEntries::partial`.
+    TextSizeLimit {
+        /// The name of the field.
+        field: Arc<str>,
+        /// The `text_size_limit` that was set.
+        limit: u64,
+        /// The number of bytes of the field which were saved.
+        written: u64,
+    },
+    /// An error occurred during the operation.
+    IoError(io::Error),
+    /// There wasn't enough space on the filesystem (or the disk quota was exceeded):
+    /// an `io::Error` with the OS error code `ENOSPC` or `EDQUOT` (`ERROR_DISK_FULL`,
+    /// `ERROR_HANDLE_DISK_FULL` or `ERROR_DISK_QUOTA_EXCEEDED` on Windows).
+    ///
+    /// With `SavePhase::DirSetup`, nothing has been read from the request and
+    /// `Entries::save_dir` is `SaveDir::Memory`, so it can be saved to another volume instead.
+    /// With `SavePhase::FieldWrite`, the field being saved was partially written.
+    InsufficientStorage {
+        /// What was being done when the storage ran out.
+        phase: SavePhase,
+        /// The error returned by the filesystem.
+        error: io::Error,
+    },
+    /// An error returned from validating a field as UTF-8 due to `SaveBuilder::force_text()`
+    Utf8Error(str::Utf8Error),
+    /// A text field contained this noncharacter, with `SaveBuilder::force_text()` and
+    /// `TextNorm::RejectNoncharacters`.
+    Noncharacter(char),
+    /// The field's data did not match a digest declared in its headers,
+    /// with `SaveBuilder::verify_digests()`.
+    ///
+    /// Any files the field was saved to have been deleted.
+    DigestMismatch {
+        /// The name of the field.
+        field: Arc<str>,
+        /// The algorithm of the digest which did not match, e.g. `sha-256`.
+        algorithm: String,
+        /// The digest declared by the client.
+        expected: String,
+        /// The digest of the data that was received, base64-encoded.
+        actual: String,
+    },
+    /// With `SaveBuilder::in_memory()`, a field was larger than `memory_threshold`.
+    ///
+    /// The data which was read, if any, is in `PartialSavedField::dest`; the rest of the field
+    /// can still be read from `PartialSavedField::source`.
+    MemoryLimit {
+        /// The name of the field.
+        field: Arc<str>,
+        /// The `memory_threshold` that was set.
+        limit: u64,
+        /// The number of bytes of the field which were read into memory.
+        written: u64,
+    },
+    /// With `SaveBuilder::reject_suspicious_filenames()`, a field's filename was suspicious.
+    ///
+    /// The field has not been read.
+    SuspiciousFilename {
+        /// The name of the field.
+        field: Arc<str>,
+        /// The filename as declared by the client.
+        filename: String,
+        /// What was found in the filename.
+        report: FilenameReport,
+    },
+    /// The check passed to `SaveBuilder::require_first()` rejected the request, or the one
+    /// passed to `SaveBuilder::validate_redacted()` rejected a field.
+    ///
+    /// With `require_first()`, no fields of the request have been kept. With
+    /// `validate_redacted()`, the fields before the rejected one have.
+    Aborted(Abort),
+    /// With `SaveBuilder::require_first()`, the fields held back before the required fields
+    /// exceeded the spool budget.
+    ///
+    /// No fields of the request have been kept.
+    SpoolBudget {
+        /// The `spool_budget` that was set.
+        limit: u64,
+        /// The number of bytes which were held back, including the field that hit the limit.
+        spooled: u64,
+    },
+    /// With `SaveBuilder::require_first()`, the request ended before the named field arrived.
+    ///
+    /// No fields of the request have been kept.
+    RequiredFieldMissing {
+        /// The name of the (first) missing field.
+        field: Arc<str>,
+    },
+    /// The `CancelToken` set with `SaveBuilder::cancel_token()` was cancelled.
+    ///
+    /// The field being saved, if any, is in `PartialEntries::partial` as usual.
+    Cancelled,
+    /// A callback panicked: the one passed to `SaveBuilder::with_file_namer()`, `sniffer()`,
+    /// `require_first()` or `validate_redacted()`, or the sink set with
+    /// `Multipart::set_event_sink()`. Says which one, with the message it panicked with.
+    ///
+    /// The panic is caught and not resumed, so the save returns as usual instead of unwinding
+    /// and dropping the entries (and with them a temporary directory and everything in it).
+    /// No files are deleted: the fields saved so far are kept, including any held back by
+    /// `require_first()`, which haven't been checked, and the field being saved is in
+    /// `PartialEntries::partial`. The panic message is still printed by the panic hook.
+    CallbackPanicked(String),
+}
+
+/// What a save was doing when it failed with `PartialReason::InsufficientStorage`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SavePhase {
+    /// Creating the directory to save the request in, before anything was read.
+    DirSetup,
+    /// Writing the data of a field.
+    FieldWrite,
+}
+
+/// The OS error codes for a full filesystem and an exceeded disk quota: `ENOSPC` and `EDQUOT`,
+/// or `ERROR_DISK_FULL`, `ERROR_HANDLE_DISK_FULL` and `ERROR_DISK_QUOTA_EXCEEDED` on Windows.
+///
+/// These are matched by number as `io::ErrorKind` has no kinds for them on older compilers.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const STORAGE_FULL_CODES: &[i32] = &[28, 122];
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd",
+          target_os = "netbsd", target_os = "dragonfly"))]
+const STORAGE_FULL_CODES: &[i32] = &[28, 69];
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android", target_os = "macos",
+                        target_os = "ios", target_os = "freebsd", target_os = "openbsd",
+                        target_os = "netbsd", target_os = "dragonfly"))))]
+const STORAGE_FULL_CODES: &[i32] = &[28];
+#[cfg(windows)]
+const STORAGE_FULL_CODES: &[i32] = &[112, 39, 1295];
+#[cfg(not(any(unix, windows)))]
+const STORAGE_FULL_CODES: &[i32] = &[];
+
+/// Whether `err` means the filesystem is full or the disk quota was exceeded, going by the
+/// first attempt of a `TempDirError`.
+fn is_storage_full(err: &io::Error) -> bool {
+    let err = match err.get_ref().and_then(|e| e.downcast_ref::<TempDirError>()) {
+        Some(temp_dir) => &temp_dir.attempts[0].1,
+        None => err,
+    };
+
+    err.raw_os_error().is_some_and(|code| STORAGE_FULL_CODES.contains(&code))
+}
+
+/// The result of failing to create the directory to save a request in.
+fn dir_setup_failed<M: ReadEntry>(error: io::Error) -> EntriesSaveResult<M> {
+    if is_storage_full(&error) {
+        let entries = Entries::new(SaveDir::Memory);
+        Partial(PartialEntries { entries, partial: None }, InsufficientStorage { phase: SavePhase::DirSetup, error })
+    } else {
+        Error(error)
+    }
+}
+
+/// Why the check passed to `SaveBuilder::require_first()` rejected a request.
+///
+/// The message is included in the `Display` of `PartialReason::Aborted`, so it may be
+/// shown to the client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Abort(pub String);
+
+impl fmt::Display for Abort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Selects fields by name for `SaveBuilder::redact_fields()`: by exact name, by prefix
+/// or with a closure, e.g. one which runs a regex.
+///
+/// A field matches if any of these match. Names are compared as is, so a closure is needed
+/// to ignore case.
+#[derive(Clone, Default)]
+pub struct FieldMatcher {
+    names: Vec<String>,
+    prefixes: Vec<String>,
+    predicates: Vec<NamePredicate>,
+}
+
+/// A closure passed to `FieldMatcher::matching()`.
+type NamePredicate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+impl FieldMatcher {
+    /// A matcher which doesn't match any field yet.
+    pub fn new() -> Self {
+        FieldMatcher::default()
+    }
+
+    /// Match fields named exactly `name`.
+    pub fn name<N: Into<String>>(mut self, name: N) -> Self {
+        self.names.push(name.into());
+        self
+    }
+
+    /// Match fields whose names start with `prefix`.
+    pub fn prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    /// Match fields whose names `predicate` returns `true` for.
+    pub fn matching<F>(mut self, predicate: F) -> Self where F: Fn(&str) -> bool + Send + Sync + 'static {
+        self.predicates.push(Arc::new(predicate));
+        self
+    }
+
+    /// Returns `true` if a field named `name` matches.
+    pub fn matches(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name)
+            || self.prefixes.iter().any(|prefix| name.starts_with(&**prefix))
+            || self.predicates.iter().any(|predicate| predicate(name))
+    }
+}
+
+impl fmt::Debug for FieldMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FieldMatcher")
+            .field("names", &self.names)
+            .field("prefixes", &self.prefixes)
+            .field("predicates", &self.predicates.len())
+            .finish()
+    }
+}
+
+/// A flag to stop saving with, set with `SaveBuilder::cancel_token()`.
+///
+/// Clones share the same flag, so one token can cancel every save in progress, e.g. on
+/// shutdown. Cancelling is a single atomic store, so it can be done from any thread,
+/// including a signal handler.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a token which isn't cancelled yet.
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Cancel every save using this token or its clones. This can't be undone.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if `cancel()` was called on this token or one of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Use an existing flag as a token, e.g. one set by `signal_hook::flag::register()`.
+impl From<Arc<AtomicBool>> for CancelToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        CancelToken(flag)
+    }
+}
+
+/// `InsufficientStorage` in `SavePhase::FieldWrite` if the filesystem is full,
+/// `CallbackPanicked` if the event sink panicked, `IoError` otherwise.
+impl From<io::Error> for PartialReason {
+    fn from(e: io::Error) -> Self {
+        if let Some(panicked) = e.get_ref().and_then(|e| e.downcast_ref::<EventSinkPanicked>()) {
+            CallbackPanicked(panicked.to_string())
+        } else if is_storage_full(&e) {
+            InsufficientStorage { phase: SavePhase::FieldWrite, error: e }
+        } else {
+            IoError(e)
+        }
+    }
+}
+
+impl From<str::Utf8Error> for PartialReason {
+    fn from(e: str::Utf8Error) -> Self {
+        Utf8Error(e)
+    }
+}
+
+impl PartialReason {
+    /// A `SizeLimit` whose details are filled in by `SaveBuilder::limit_details()`.
+    fn size_limit() -> Self {
+        SizeLimit { field: "".into(), limit: 0, written: 0 }
+    }
+
+    /// A `MemoryLimit` whose details are filled in by `SaveBuilder::limit_details()`.
+    fn memory_limit() -> Self {
+        MemoryLimit { field: "".into(), limit: 0, written: 0 }
+    }
+
+    /// Whether `FieldErrorPolicy::SkipAndContinue` skips a field which failed for this reason.
+    fn is_field_error(&self) -> bool {
+        matches!(*self, IoError(_) | Utf8Error(_) | Noncharacter(_) | DigestMismatch { .. })
+    }
+
+    /// The HTTP status code to respond with if the request is rejected for this reason.
+    ///
+    /// * `413 Payload Too Large` for `CountLimit`, `FilePartLimit`, `TextPartLimit`, `SizeLimit`,
+    ///   `TextSizeLimit`, `TotalSizeLimit`, `MemoryLimit` and `SpoolBudget`
+    /// * `400 Bad Request` for `Utf8Error`, `Noncharacter`, `DigestMismatch`, `SuspiciousFilename`, `Aborted`,
+    ///   `RequiredFieldMissing` and `IoError`s caused by malformed or truncated requests
+    ///   (`InvalidData`, `InvalidInput` or `UnexpectedEof`)
+    /// * `500 Internal Server Error` for other `IoError`s and `CallbackPanicked`
+    /// * `503 Service Unavailable` for `Cancelled`
+    /// * `507 Insufficient Storage` for `InsufficientStorage`
+    pub fn suggested_status(&self) -> u16 {
+        match *self {
+            CountLimit { .. } | FilePartLimit { .. } | TextPartLimit { .. } | SizeLimit { .. }
+            | TextSizeLimit { .. } | TotalSizeLimit { .. } | MemoryLimit { .. } | SpoolBudget { .. } => 413,
+            Utf8Error(_) | Noncharacter(_) | DigestMismatch { .. } | SuspiciousFilename { .. } | Aborted(_)
+            | RequiredFieldMissing { .. } => 400,
+            IoError(ref e) => match e.kind() {
+                io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput
+                | io::ErrorKind::UnexpectedEof => 400,
+                _ => 500,
+            },
+            CallbackPanicked(_) => 500,
+            Cancelled => 503,
+            InsufficientStorage { .. } => 507,
+        }
+    }
+
+    /// Return `io::Error` in the `IoError` (or `InsufficientStorage`) case or panic otherwise.
+    pub fn unwrap_err(self) -> io::Error {
+        self.expect_err("`PartialReason` was not `IoError`")
+    }
+
+    /// Return `io::Error` in the `IoError` (or `InsufficientStorage`) case or panic with
+    /// the given message otherwise.
+    pub fn expect_err(self, msg: &str) -> io::Error {
+        match self {
+            PartialReason::IoError(e) | PartialReason::InsufficientStorage { error: e, .. } => e,
+            _ => panic!("{}: {:?}", msg, self),
+        }
+    }
+}
+
+impl fmt::Display for PartialReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CountLimit { limit } => write!(f, "the request reached the limit of {} fields", limit),
+            FilePartLimit { limit } => write!(f, "the request exceeded the limit of {} files", limit),
+            TextPartLimit { limit } =>
+                write!(f, "the request exceeded the limit of {} fields without a filename", limit),
+            SizeLimit { ref field, limit, written } => {
+                FieldName(field).fmt(f)?;
+                write!(f, " exceeded the size limit of {} (saved {})", ByteSize(limit), ByteSize(written))
+            },
+            TextSizeLimit { ref field, limit, written } => {
+                FieldName(field).fmt(f)?;
+                write!(f, " exceeded the text size limit of {} (saved {})", ByteSize(limit), ByteSize(written))
+            },
+            MemoryLimit { ref field, limit, written } => {
+                FieldName(field).fmt(f)?;
+                write!(f, " exceeded the memory limit of {} (read {})", ByteSize(limit), ByteSize(written))
+            },
+            TotalSizeLimit { limit, saved } => write!(
+                f, "the request exceeded the total size limit of {} (saved {})", ByteSize(limit), ByteSize(saved)
+            ),
+            IoError(ref e) => write!(f, "an error occurred while saving the request: {}", e),
+            InsufficientStorage { phase: SavePhase::DirSetup, ref error } =>
+                write!(f, "there is not enough storage to save the request: {}", error),
+            InsufficientStorage { phase: SavePhase::FieldWrite, ref error } =>
+                write!(f, "storage ran out while saving the request: {}", error),
+            Utf8Error(ref e) => write!(f, "a text field was not valid UTF-8: {}", e),
+            Noncharacter(c) => write!(f, "a text field contained the noncharacter U+{:04X}", c as u32),
+            DigestMismatch { ref field, ref algorithm, ref expected, ref actual } => {
+                FieldName(field).fmt(f)?;
+                write!(f, " did not match its {} digest: expected {}, got {}", algorithm, expected, actual)
+            },
+            SuspiciousFilename { ref field, ref filename, .. } => {
+                FieldName(field).fmt(f)?;
+                write!(f, " has a suspicious filename: {:?}", filename)
+            },
+            Aborted(ref abort) => write!(f, "the request was rejected: {}", abort),
+            Cancelled => f.write_str("saving the request was cancelled"),
+            SpoolBudget { limit, spooled } => write!(
+                f, "the request sent more than {} before its required fields (at least {})",
+                ByteSize(limit), ByteSize(spooled)
+            ),
+            RequiredFieldMissing { ref field } => {
+                write!(f, "the request ended without the required ")?;
+                FieldName(field).fmt(f)
+            },
+            CallbackPanicked(ref message) => f.write_str(message),
+        }
+    }
+}
+
+/// Writes ``field `name` ``, or just `field` if the name is empty.
+struct FieldName<'a>(&'a str);
+
+impl<'a> fmt::Display for FieldName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            f.write_str("field")
+        } else {
+            write!(f, "field `{}`", self.0)
+        }
+    }
+}
+
+/// A number of bytes which implements `Display` in binary units, e.g. `1.5 KiB` or `100 MiB`.
+///
+/// Sizes of a whole unit or more are rounded down to one decimal place, which is left out
+/// if zero; smaller ones are printed in bytes, e.g. `512 bytes`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} byte{}", self.0, if self.0 == 1 { "" } else { "s" });
+        }
+
+        let mut unit = 0;
+        let mut scale = 1024u64;
+
+        while unit + 1 < UNITS.len() && self.0 / scale >= 1024 {
+            unit += 1;
+            scale *= 1024;
+        }
+
+        let whole = self.0 / scale;
+        // `scale` is at most 2^60, so this can't overflow
+        let tenths = self.0 % scale * 10 / scale;
+
+        if tenths == 0 {
+            write!(f, "{} {}", whole, UNITS[unit])
+        } else {
+            write!(f, "{}.{} {}", whole, tenths, UNITS[unit])
+        }
+    }
+}
+
+/// Parse a size in bytes like `4096`, `16MiB` or `1.5 GB`, e.g. from configuration.
+///
+/// The suffixes `B`, `KB`, `MB`, `GB` and `TB` are powers of 1000, and `KiB`, `MiB`, `GiB`
+/// and `TiB` powers of 1024; they are case-insensitive and may be separated from the number
+/// by spaces. `max` is `u64::MAX`, i.e. no limit. Returns `None` for anything else or if the
+/// size doesn't fit in a `u64`.
+///
+/// ```rust
+/// # use multipart::server::save::parse_size_str;
+/// assert_eq!(parse_size_str("16MiB"), Some(16 * 1024 * 1024));
+/// assert_eq!(parse_size_str("1.5 kb"), Some(1500));
+/// assert_eq!(parse_size_str("max"), Some(u64::max_value()));
+/// assert_eq!(parse_size_str("16 MiBs"), None);
+/// ```
+pub fn parse_size_str(size: &str) -> Option<u64> {
+    const UNITS: [(&str, u64); 9] = [
+        ("b", 1), ("kb", 1000), ("mb", 1000 * 1000), ("gb", 1000 * 1000 * 1000),
+        ("tb", 1000 * 1000 * 1000 * 1000), ("kib", 1 << 10), ("mib", 1 << 20), ("gib", 1 << 30),
+        ("tib", 1 << 40),
+    ];
+
+    let size = size.trim().to_ascii_lowercase();
+
+    if size == "max" {
+        return Some(u64::MAX);
+    }
+
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let (number, unit) = (&size[..split], size[split..].trim_start());
+
+    let scale = if unit.is_empty() {
+        1
+    } else {
+        UNITS.iter().find(|&&(name, _)| name == unit)?.1
+    };
+
+    let (whole, fraction) = match number.find('.') {
+        Some(dot) => (&number[..dot], &number[dot + 1..]),
+        None => (number, ""),
+    };
+
+    if whole.is_empty() || (number.contains('.') && fraction.is_empty()) {
+        return None;
+    }
+
+    if fraction.contains('.') {
+        return None;
+    }
+
+    let bytes = whole.parse::<u64>().ok()?.checked_mul(scale)?;
+
+    // rounded down to whole bytes; digits beyond the 18th can't add a byte even to a TiB
+    let fraction = &fraction[..cmp::min(fraction.len(), 18)];
+    let fraction_bytes = if fraction.is_empty() {
+        0
+    } else {
+        let numerator: u128 = fraction.parse().ok()?;
+        (u128::from(scale) * numerator / 10u128.pow(fraction.len() as u32)) as u64
+    };
+
+    bytes.checked_add(fraction_bytes)
+}
+
+/// The field that was being read when the save operation quit.
+///
+/// May be partially saved to the filesystem if `dest` is `Some`.
+#[derive(Debug)]
+pub struct PartialSavedField<M: ReadEntry> {
+    /// The field that was being read.
+    ///
+    /// May be partially read if `dest` is `Some`.
+    pub source: MultipartField<M>,
+    /// The data from the saving operation, if it got that far.
+    pub dest: Option<SavedData>,
+}
+
+impl<M: ReadEntry> PartialSavedField<M> {
+    /// Delete the file(s) `dest` was partially saved to, if any, and set it to `None`.
+    ///
+    /// Returns `Ok(false)` without doing anything if `dest` is `None` or in memory.
+    /// A file which is already gone, e.g. because its `TempDir` was dropped, counts as deleted.
+    pub fn delete_dest(&mut self) -> io::Result<bool> {
+        match self.dest {
+            Some(ref data) if data.is_file() => delete_files(data)?,
+            _ => return Ok(false),
+        }
+
+        self.dest = None;
+        Ok(true)
+    }
+}
+
+/// The partial result type for `Multipart::save*()`.
+///
+/// Contains the successfully saved entries as well as the partially
+/// saved file that was in the process of being read when the error occurred,
+/// if applicable.
+#[derive(Debug)]
+pub struct PartialEntries<M: ReadEntry> {
+    /// The entries that were saved successfully.
+    pub entries: Entries,
+    /// The field that was in the process of being read. `None` if the error
+    /// occurred between entries.
+    pub partial: Option<PartialSavedField<M>>,
+}
+
+/// Discards `partial`
+impl<M: ReadEntry> Into<Entries> for PartialEntries<M> {
+    fn into(self) -> Entries {
+        self.entries
+    }
+}
+
+impl<M: ReadEntry> PartialEntries<M> {
+    /// If `partial` is present and contains a `SavedFile` then just
+    /// add it to the `Entries` instance and return it.
+    ///
+    /// Otherwise, returns `self.entries`
+    pub fn keep_partial(mut self) -> Entries {
+        if let Some(partial) = self.partial {
+            if let Some(saved) = partial.dest {
+                self.entries.push_field(partial.source.headers, saved);
+            }
+        }
+
+        self.entries
+    }
+
+    /// Delete any file `partial` was saved to and discard it, returning the entries that were
+    /// saved successfully along with the result of deleting the file.
+    ///
+    /// See `PartialSavedField::delete_dest()`.
+    pub fn discard_partial(self) -> (Entries, io::Result<()>) {
+        let res = match self.partial {
+            Some(mut partial) => partial.delete_dest().map(|_| ()),
+            None => Ok(()),
+        };
+
+        (self.entries, res)
+    }
+}
+
+/// The ternary result type used for the `SaveBuilder<_>` API.
+#[derive(Debug)]
+pub enum SaveResult<Success, Partial> {
+    /// The operation was a total success. Contained is the complete result.
+    Full(Success),
+    /// The operation quit partway through. Included is the partial
+    /// result along with the reason.
+    Partial(Partial, PartialReason),
+    /// An error occurred at the start of the operation, before anything was done.
+    Error(io::Error),
+}
+
+/// Shorthand result for methods that return `Entries`
+pub type EntriesSaveResult<M> = SaveResult<Entries, PartialEntries<M>>;
+
+/// Shorthand result for methods that return `FieldData`s.
+///
+/// The `MultipartData` is not provided here because it is not necessary to return
+/// a borrow when the owned version is probably in the same scope. This hopefully
+/// saves some headache with the borrow-checker.
+pub type FieldSaveResult = SaveResult<SavedData, SavedData>;
+
+impl<M: ReadEntry> EntriesSaveResult<M> {
+    /// Take the `Entries` from `self`, if applicable, and discarding
+    /// the error, if any.
+    pub fn into_entries(self) -> Option<Entries> {
+        match self {
+            Full(entries) | Partial(PartialEntries { entries, .. }, _) => Some(entries),
+            Error(_) => None,
+        }
+    }
+}
+
+impl<S, P> SaveResult<S, P> where P: Into<S> {
+    /// Convert `self` to `Option<S>`; there may still have been an error.
+    pub fn okish(self) -> Option<S> {
+        self.into_opt_both().0
+    }
+
+    /// Map the `Full` or `Partial` values to a new type, retaining the reason
+    /// in the `Partial` case.
+    pub fn map<T, Map>(self, map: Map) -> SaveResult<T, T> where Map: FnOnce(S) -> T {
+        match self {
+            Full(full) => Full(map(full)),
+            Partial(partial, reason) => Partial(map(partial.into()), reason),
+            Error(e) => Error(e),
+        }
+    }
+
+    /// Decompose `self` to `(Option<S>, Option<io::Error>)`
+    pub fn into_opt_both(self) -> (Option<S>, Option<io::Error>) {
+        match self {
+            Full(full)  => (Some(full), None),
+            Partial(partial, IoError(e)) | Partial(partial, InsufficientStorage { error: e, .. }) =>
+                (Some(partial.into()), Some(e)),
+            Partial(partial, _) => (Some(partial.into()), None),
+            Error(error) => (None, Some(error)),
+        }
+    }
+
+    /// Map `self` to an `io::Result`, discarding the error in the `Partial` case.
+    pub fn into_result(self) -> io::Result<S> {
+        match self {
+            Full(entries) => Ok(entries),
+            Partial(partial, _) => Ok(partial.into()),
+            Error(error) => Err(error),
+        }
+    }
+
+    /// Pessimistic version of `into_result()` which will return an error even
+    /// for the `Partial` case.
+    ///
+    /// ### Note: Possible Storage Leak
+    /// It's generally not a good idea to ignore the `Partial` case, as there may still be a
+    /// partially written file on-disk. If you're not using a temporary directory
+    /// (OS-managed or via `TempDir`) then partially written files will remain on-disk until
+    /// explicitly removed which could result in excessive disk usage if not monitored closely.
+    pub fn into_result_strict(self) -> io::Result<S> {
+        match self {
+            Full(entries) => Ok(entries),
+            Partial(_, PartialReason::IoError(e)) | Partial(_, PartialReason::InsufficientStorage { error: e, .. })
+            | Error(e) => Err(e),
+            Partial(partial, _) => Ok(partial.into()),
+        }
+    }
+}
+
+/// The total memory limit used by `parse_form_data()`: 8 MiB.
+pub const FORM_DATA_MEMORY_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// Parse a complete `multipart/form-data` body into `Entries`, entirely in memory.
+///
+/// `content_type` is the value of the request's `Content-Type` header, from which the
+/// boundary is taken.
+///
+/// Text fields are stored as `SavedData::Text` and all others as `SavedData::Bytes`;
+/// the filesystem is never touched and `Entries::save_dir` is `SaveDir::Memory`.
+/// The combined size of all fields is limited to `FORM_DATA_MEMORY_LIMIT`; use
+/// `parse_form_data_with_limit()` to choose a different limit.
+/// `Entries::clean_termination` is set as by `SaveBuilder`.
+///
+/// ```rust
+/// # use multipart::server::parse_form_data;
+/// let body = b"--boundary\r\n\
+///              Content-Disposition: form-data; name=\"hello\"\r\n\r\n\
+///              world\r\n\
+///              --boundary--\r\n";
+///
+/// let entries = parse_form_data(body, "multipart/form-data; boundary=boundary").unwrap();
+/// assert_eq!(entries.fields_count(), 1);
+/// ```
+pub fn parse_form_data(body: &[u8], content_type: &str) -> Result<Entries, MultipartError> {
+    parse_form_data_with_limit(body, content_type, FORM_DATA_MEMORY_LIMIT)
+}
+
+/// Parse a complete `multipart/form-data` body into `Entries` with the given total memory limit.
+///
+/// See `parse_form_data()` for more info.
+pub fn parse_form_data_with_limit(body: &[u8], content_type: &str, limit: u64)
+                                  -> Result<Entries, MultipartError> {
+    let parsed = match parse_multipart_type(content_type) {
+        Some(ref parsed) if parsed.kind == MultipartKind::FormData => parsed.clone(),
+        _ => return Err(MultipartError::InvalidContentType(content_type.into())),
+    };
+
+    let boundary = parsed.boundary().filter(|boundary| !boundary.is_empty())
+        .ok_or(MultipartError::MissingBoundary)?;
+
+    let mut multipart = Multipart::with_body(body, boundary);
+    let mut entries = Entries::new(SaveDir::Memory);
+    let mut remaining = limit;
+
+    while let Some(mut field) = multipart.read_entry_mut().into_result()? {
+        let data = {
+            // `memory_threshold == size_limit`, so a field over the limit is a `SizeLimit`
+            let is_text = field.is_text();
+            let builder = field.data.save().size_limit(remaining).memory_threshold(remaining);
+            let mut builder = if is_text { builder } else { builder.ignore_text() };
+
+            match builder.in_mem() {
+                Full(data) => data,
+                Partial(_, SizeLimit { .. }) => return Err(MultipartError::LimitExceeded(limit)),
+                Partial(_, reason) => return Err(reason.unwrap_err().into()),
+                Error(e) => return Err(e.into()),
+            }
+        };
+
+        remaining -= data.size();
+        entries.push_field(field.headers, data);
+    }
+
+    entries.clean_termination = multipart.finished_cleanly();
+    Ok(entries)
+}
+
+quick_error! {
+    /// An error returned by `parse_form_data()`.
+    #[derive(Debug)]
+    pub enum MultipartError {
+        /// The `Content-Type` could not be parsed or was not `multipart/form-data`.
+        InvalidContentType(content_type: String) {
+            description("expected a Content-Type of multipart/form-data")
+            display("expected a Content-Type of multipart/form-data: {:?}", content_type)
+        }
+        /// The `Content-Type` did not have a `boundary` parameter.
+        MissingBoundary {
+            description("Content-Type is missing the boundary parameter")
+            display("Content-Type is missing the boundary parameter")
+        }
+        /// The combined size of the fields exceeded the memory limit.
+        LimitExceeded(limit: u64) {
+            description("form data exceeded the memory limit")
+            display("form data exceeded the memory limit of {} bytes", limit)
+        }
+        /// An error occurred while parsing the body.
+        Io(err: io::Error) {
+            description("an error occurred while parsing the form data")
+            display("an error occurred while parsing the form data: {}", err)
+            cause(err)
+            from()
+        }
+    }
+}
+
+/// Compare the digests declared in `headers` with those of `data`.
+///
+/// Returns `PartialReason::DigestMismatch` for the first digest which does not match.
+#[cfg(feature = "digest")]
+fn check_digests(headers: &FieldHeaders, data: &SavedData) -> io::Result<Option<PartialReason>> {
+    let mut hashers = declared_hashers(headers);
+
+    if hashers.is_empty() {
+        return Ok(None);
+    }
+
+    try_read_buf(data.readable()?, |buf| {
+        for &mut (_, ref mut hasher) in &mut hashers {
+            hasher.update(buf);
+        }
+        Full(buf.len())
+    }).into_result_strict()?;
+
+    Ok(compare_digests(headers, hashers))
+}
+
+/// Without the `digest` feature, `verify_digests` can't be set.
+#[cfg(not(feature = "digest"))]
+fn check_digests(_headers: &FieldHeaders, _data: &SavedData) -> io::Result<Option<PartialReason>> {
+    Ok(None)
+}
+
+/// A hasher for each digest declared in `headers` with a supported algorithm.
+#[cfg(feature = "digest")]
+fn declared_hashers(headers: &FieldHeaders) -> Vec<(&FieldDigest, Hasher)> {
+    headers.digests.iter()
+        .filter_map(|declared| match Algorithm::from_name(&declared.algorithm) {
+            Some(algorithm) => Some((declared, Hasher::new(algorithm))),
+            None => {
+                warn!("Skipping unsupported digest algorithm {:?} for field {:?}",
+                      declared.algorithm, headers.name);
+                None
+            },
+        })
+        .collect()
+}
+
+/// `PartialReason::DigestMismatch` for the first of `hashers`, fed all of the data,
+/// which doesn't match its declared digest.
+#[cfg(feature = "digest")]
+fn compare_digests(headers: &FieldHeaders, hashers: Vec<(&FieldDigest, Hasher)>) -> Option<PartialReason> {
+    for (declared, hasher) in hashers {
+        let actual = hasher.finish();
+
+        if digest::base64_decode(&declared.value).as_ref() != Some(&actual) {
+            return Some(DigestMismatch {
+                field: headers.name.clone(),
+                algorithm: declared.algorithm.clone(),
+                expected: declared.value.clone(),
+                actual: digest::base64_encode(&actual),
+            });
+        }
+    }
+
+    None
+}
+
+/// Detect the type of a saved field with `sniffer` from up to `SNIFF_LEN` of its first bytes.
+fn sniff_type(sniffer: &dyn TypeSniffer, headers: &FieldHeaders, data: &SavedData) -> Option<Mime> {
+    let mut reader = match data.readable() {
+        Ok(reader) => reader,
+        Err(e) => {
+            warn!("Not sniffing the type of field {:?} which can't be read: {}", headers.name, e);
+            return None;
+        },
+    };
+
+    match reader.fill_buf() {
+        Ok(buf) => sniffer.sniff(&buf[..cmp::min(buf.len(), SNIFF_LEN)], headers),
+        Err(e) => {
+            warn!("Not sniffing the type of field {:?} which can't be read: {}", headers.name, e);
+            None
+        },
+    }
+}
+
+/// `PartialReason::SuspiciousFilename` if the field's filename fails any of `checks`.
+fn check_filename(headers: &FieldHeaders, checks: &FilenameChecks) -> Option<PartialReason> {
+    let filename = headers.filename.as_ref()?;
+    let report = analyze_filename(filename);
+
+    if report.is_suspicious(checks) {
+        warn!("Rejecting suspicious filename {:?} of field {:?}: {:?}", filename, headers.name, report);
+
+        Some(SuspiciousFilename {
+            field: headers.name.clone(),
+            filename: filename.clone(),
+            report,
+        })
+    } else {
+        None
+    }
+}
+
+/// `SavedData::File` for `path` after `written` bytes of the field were saved to `file`.
+///
+/// `written` includes any prefix of the field read into memory before the memory threshold
+/// was reached, so a field saved to a file is always one `File` of its whole size; the sizes
+/// of in-memory data are never added to. The length of the file is taken from its metadata,
+/// as it may have already had data.
+fn saved_file(path: PathBuf, file: &mut File, written: u64) -> SavedData {
+    let len = match file.flush().and_then(|_| file.metadata()) {
+        Ok(meta) => meta.len(),
+        Err(e) => {
+            warn!("Failed to get the length of {:?}: {}", path, e);
+            written
+        },
+    };
+
+    SavedData::File(path, FileSize { written, len })
+}
+
+/// The paths of the files of `data`, if any.
+fn file_paths(data: &SavedData) -> Vec<&Path> {
+    match *data {
+        SavedData::File(ref path, _) => vec![path],
+        SavedData::Chunked(ref chunks) => chunks.iter().map(|(path, _)| &**path).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Delete the files of `data`, if any, returning the first error after trying all of them.
+///
+/// Files which don't exist are skipped.
+fn delete_files(data: &SavedData) -> io::Result<()> {
+    let mut res = Ok(());
+
+    for path in file_paths(data) {
+        match fs::remove_file(path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => if res.is_ok() { res = Err(e) },
+            Ok(()) => (),
+        }
+    }
+
+    res
+}
+
+/// Delete the files of `data`, if any; errors are logged.
+fn remove_files(data: &SavedData) {
+    for path in file_paths(data) {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Failed to remove file {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Returned when reading `SavedData::Streamed`.
+fn streamed_error() -> io::Error {
+    io::Error::other("the field was streamed to a writer and not saved")
+}
+
+fn redacted_error() -> io::Error {
+    io::Error::other("the field was redacted and not saved")
+}
+
+fn validated_error() -> io::Error {
+    io::Error::other("the field was only validated by a dry run and not saved")
+}
+
+/// The charset named by a `_charset_` field, or UTF-8 if it's unknown.
+fn charset_field_value(data: &SavedData) -> Charset {
+    let label = match *data {
+        SavedData::Text(ref text) => Some(text.as_str()),
+        SavedData::Pooled(ref buf) => buf.as_str(),
+        _ => None,
+    };
+
+    match label.and_then(Charset::from_label) {
+        Some(charset) => charset,
+        None => {
+            warn!("Unsupported charset in `_charset_` field: {:?}; falling back to UTF-8", label);
+            Charset::Utf8
+        },
+    }
+}
+
+/// `<path>.part000`, `<path>.part001`, etc.
+fn chunk_path(path: &Path, idx: usize) -> PathBuf {
+    let mut name = path.file_name().map_or_else(OsString::new, Into::into);
+    name.push(format!(".part{:03}", idx));
+    path.with_file_name(name)
+}
+
+fn create_dir_all(path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+    } else {
+        // RFC: return an error instead?
+        warn!("Attempting to save file in what looks like a root directory. File path: {:?}", path);
+        Ok(())
+    }
+}
+
+fn try_copy_limited<R: BufRead, Wb: FnMut(&[u8]) -> SaveResult<usize, usize>>(src: R, mut with_buf: Wb, limit: u64) -> SaveResult<u64, u64> {
+    let mut copied = 0u64;
+    try_read_buf(src, |buf| {
+        // `buf` isn't empty, so there's more data than the limit allows
+        if copied == limit { return Partial(0, PartialReason::size_limit()) }
+
+        let len = cmp::min(buf.len() as u64, limit - copied) as usize;
+        let truncated = len < buf.len();
+
+        match with_buf(&buf[..len]) {
+            Full(written) => {
+                copied += written as u64;
+                if truncated && written == len { Partial(written, PartialReason::size_limit()) }
+                else { Full(written) }
+            },
+            // e.g. a character split by the limit
+            Partial(written, PartialReason::Utf8Error(_)) if truncated => {
+                copied += written as u64;
+                Partial(written, PartialReason::size_limit())
+            },
+            Partial(written, reason) => {
+                copied += written as u64;
+                Partial(written, reason)
+            },
+            Error(e) => Error(e),
+        }
+    })
+}
+
+/// Sleep for the bytes consumed according to `res`, if throttling.
+fn paced(res: SaveResult<usize, usize>, throttle: Option<&Throttle>) -> SaveResult<usize, usize> {
+    if let Some(throttle) = throttle {
+        match res {
+            Full(copied) | Partial(copied, _) => throttle.pace(copied),
+            Error(_) => (),
+        }
+    }
+
+    res
+}
+
+/// Stop with `PartialReason::Cancelled` after the bytes consumed according to `res`,
+/// if `cancel` was cancelled.
+fn checked(res: SaveResult<usize, usize>, cancel: Option<&CancelToken>) -> SaveResult<usize, usize> {
+    match res {
+        Full(copied) if cancel.is_some_and(CancelToken::is_cancelled) => Partial(copied, Cancelled),
+        other => other,
+    }
+}
+
+fn try_read_buf<R: BufRead, Wb: FnMut(&[u8]) -> SaveResult<usize, usize>>(mut src: R, mut with_buf: Wb) -> SaveResult<u64, u64> {
+    let mut total_copied = 0u64;
 
     macro_rules! try_here (
         ($try:expr) => (
@@ -1027,19 +6307,5131 @@ fn try_write_all<W: Write>(mut buf: &[u8], mut dest: W) -> SaveResult<usize, usi
                 Err(e) => return if total_copied == 0 { Error(e) }
                                  else { Partial(total_copied, e.into()) },
             }
-        )
-    );
+        )
+    );
+
+    loop {
+        let res = {
+            let buf = try_here!(src.fill_buf());
+            if buf.is_empty() { break; }
+            with_buf(buf)
+        };
+
+        match res {
+            Full(copied) => { src.consume(copied); total_copied += copied as u64; }
+            Partial(copied, reason) => {
+                src.consume(copied); total_copied += copied as u64;
+                return Partial(total_copied, reason);
+            },
+            Error(err) => {
+                return Partial(total_copied, err.into());
+            }
+        }
+    }
+
+    Full(total_copied)
+}
+
+/// A `Write` adapter writing to a `File` at an offset, for `SaveBuilder::write_at()`.
+struct FileAt<'a> {
+    file: &'a File,
+    offset: u64,
+}
+
+impl<'a> Write for FileAt<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = write_file_at(self.file, buf, self.offset)?;
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn write_file_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.write_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn write_file_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_write(buf, offset)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn write_file_at(mut file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    file.seek(io::SeekFrom::Start(offset))?;
+    file.write(buf)
+}
+
+/// Where `SaveBuilder::dry_run()` writes each field instead of a file: counts and hashes
+/// the data, keeping only its first `SNIFF_LEN` bytes for the sniffer.
+struct DryRunSink<'h> {
+    len: u64,
+    prefix: Vec<u8>,
+    digests: DryRunDigests<'h>,
+    /// `Some` to check the data as text for `SaveBuilder::force_text()`.
+    text: Option<TextCheck>,
+}
+
+impl<'h> Write for DryRunSink<'h> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.len += buf.len() as u64;
+
+        let keep = cmp::min(SNIFF_LEN - self.prefix.len(), buf.len());
+        self.prefix.extend_from_slice(&buf[..keep]);
+
+        self.digests.update(buf);
+
+        if let Some(ref mut text) = self.text {
+            text.update(buf);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The digests `SaveBuilder::dry_run()` computes of a field: its SHA-256 digest for
+/// `SavedData::Validated`, and the ones declared in its headers for `verify_digests()`.
+#[cfg(feature = "digest")]
+struct DryRunDigests<'h> {
+    sha256: Hasher,
+    declared: Vec<(&'h FieldDigest, Hasher)>,
+}
+
+#[cfg(feature = "digest")]
+impl<'h> DryRunDigests<'h> {
+    fn new(headers: &'h FieldHeaders, verify: bool) -> Self {
+        DryRunDigests {
+            sha256: Hasher::new(Algorithm::Sha256),
+            declared: if verify { declared_hashers(headers) } else { Vec::new() },
+        }
+    }
+
+    fn update(&mut self, buf: &[u8]) {
+        self.sha256.update(buf);
+
+        for &mut (_, ref mut hasher) in &mut self.declared {
+            hasher.update(buf);
+        }
+    }
+
+    /// The SHA-256 digest, and the first declared digest which doesn't match.
+    fn finish(self, headers: &FieldHeaders) -> (Option<[u8; 32]>, Option<PartialReason>) {
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&self.sha256.finish());
+        (Some(digest), compare_digests(headers, self.declared))
+    }
+}
+
+/// Without the `digest` feature, nothing is computed.
+#[cfg(not(feature = "digest"))]
+struct DryRunDigests<'h>(::std::marker::PhantomData<&'h FieldHeaders>);
+
+#[cfg(not(feature = "digest"))]
+impl<'h> DryRunDigests<'h> {
+    fn new(_headers: &'h FieldHeaders, _verify: bool) -> Self {
+        DryRunDigests(::std::marker::PhantomData)
+    }
+
+    fn update(&mut self, _buf: &[u8]) {}
+
+    fn finish(self, _headers: &FieldHeaders) -> (Option<[u8; 32]>, Option<PartialReason>) {
+        (None, None)
+    }
+}
+
+/// Validates data written in pieces as UTF-8, failing where `save_text()` would.
+///
+/// Only the first `limit` bytes are checked, as a field which doesn't fit in memory is saved
+/// to a file without being checked.
+struct TextCheck {
+    /// The start of a character split between writes.
+    split: Vec<u8>,
+    reject_nonchars: bool,
+    limit: u64,
+    /// Whether the data went past `limit`.
+    truncated: bool,
+    error: Option<PartialReason>,
+}
+
+impl TextCheck {
+    fn new(reject_nonchars: bool, limit: u64) -> Self {
+        TextCheck { split: Vec::new(), reject_nonchars, limit, truncated: false, error: None }
+    }
+
+    fn update(&mut self, mut buf: &[u8]) {
+        if buf.len() as u64 > self.limit {
+            buf = &buf[..self.limit as usize];
+            self.truncated = true;
+        }
+
+        self.limit -= buf.len() as u64;
+
+        if self.error.is_some() {
+            return;
+        }
+
+        if !self.split.is_empty() {
+            let split_len = self.split.len();
+            let take = cmp::min(4 - split_len, buf.len());
+            self.split.extend_from_slice(&buf[..take]);
+
+            let valid = match str::from_utf8(&self.split) {
+                Ok(s) => s.len(),
+                Err(e) if e.valid_up_to() > 0 => e.valid_up_to(),
+                // still incomplete, so `buf` was too short to finish it
+                Err(ref e) if e.error_len().is_none() => return,
+                Err(e) => {
+                    self.error = Some(Utf8Error(e));
+                    return;
+                },
+            };
+
+            let split = mem::take(&mut self.split);
+            self.check_chars(str::from_utf8(&split[..valid]).unwrap());
+            buf = &buf[valid - split_len..];
+        }
+
+        match str::from_utf8(buf) {
+            Ok(s) => self.check_chars(s),
+            Err(e) => {
+                let valid = e.valid_up_to();
+                self.check_chars(str::from_utf8(&buf[..valid]).unwrap());
+
+                if e.error_len().is_none() {
+                    self.split.extend_from_slice(&buf[valid..]);
+                } else if self.error.is_none() {
+                    self.error = Some(Utf8Error(e));
+                }
+            },
+        }
+    }
+
+    fn check_chars(&mut self, s: &str) {
+        if self.reject_nonchars && self.error.is_none() {
+            self.error = s.chars().find(|&c| is_noncharacter(c)).map(Noncharacter);
+        }
+    }
+
+    /// The reason the data isn't valid text, once all of it was written.
+    fn finish(self) -> Option<PartialReason> {
+        match self.error {
+            Some(reason) => Some(reason),
+            None if self.truncated => None,
+            // the data ended in the middle of a character
+            None => str::from_utf8(&self.split).err().map(Utf8Error),
+        }
+    }
+}
+
+fn try_write_all<W: Write>(mut buf: &[u8], mut dest: W, retry_policy: &RetryPolicy)
+                           -> SaveResult<usize, usize> {
+    let mut total_copied = 0;
+    let mut retry = 0;
+
+    macro_rules! try_here (
+        ($try:expr) => (
+            match $try {
+                Ok(val) => val,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    if let Some(delay) = retry_policy.delay(retry, &e) {
+                        warn!("Write failed, retrying in {:?}: {}", delay, e);
+                        thread::sleep(delay);
+                        retry += 1;
+                        continue;
+                    }
+
+                    return if total_copied == 0 { Error(e) }
+                           else { Partial(total_copied, e.into()) };
+                },
+            }
+        )
+    );
+
+    while !buf.is_empty() {
+        match try_here!(dest.write(buf)) {
+            0 => try_here!(Err(io::Error::new(io::ErrorKind::WriteZero,
+                                          "failed to write whole buffer"))),
+            copied => {
+                buf = &buf[copied..];
+                total_copied += copied;
+                retry = 0;
+            },
+        }
+    }
+
+    Full(total_copied)
+}
+
+/// `try_write_all()`, flushing `dest` whenever `flush_every` bytes have been written since
+/// the last flush, counting from `unflushed`.
+fn try_write_flushing<W: Write>(mut buf: &[u8], mut dest: W, retry_policy: &RetryPolicy,
+                                flush_every: u64, unflushed: &mut u64) -> SaveResult<usize, usize> {
+    let mut total_copied = 0;
+
+    while !buf.is_empty() {
+        let room = cmp::min(flush_every.saturating_sub(*unflushed), buf.len() as u64) as usize;
+
+        match try_write_all(&buf[..room], &mut dest, retry_policy) {
+            Full(copied) => {
+                buf = &buf[copied..];
+                total_copied += copied;
+                *unflushed += copied as u64;
+            },
+            Partial(copied, reason) => return Partial(total_copied + copied, reason),
+            Error(e) => return if total_copied == 0 { Error(e) } else { Partial(total_copied, e.into()) },
+        }
+
+        if *unflushed >= flush_every {
+            if let Err(e) = try_flush(&mut dest, retry_policy) {
+                return Partial(total_copied, e.into());
+            }
+
+            *unflushed = 0;
+        }
+    }
+
+    Full(total_copied)
+}
+
+/// Flush `dest`, retrying on interrupts and according to `retry_policy` as for writes.
+fn try_flush<W: Write>(mut dest: W, retry_policy: &RetryPolicy) -> io::Result<()> {
+    let mut retry = 0;
+
+    loop {
+        match dest.flush() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => match retry_policy.delay(retry, &e) {
+                Some(delay) => {
+                    warn!("Flush failed, retrying in {:?}: {}", delay, e);
+                    thread::sleep(delay);
+                    retry += 1;
+                },
+                None => return Err(e),
+            },
+            Ok(()) => return Ok(()),
+        }
+    }
+}
+
+/// Fails to compile if any of the results of saving can no longer be sent to or shared with
+/// another thread, e.g. with `tokio::task::spawn_blocking()`.
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn send_sync<T: Send + Sync + 'static>() {}
+
+    send_sync::<Entries>();
+    send_sync::<PartialEntries<Multipart<&'static [u8]>>>();
+    send_sync::<SavedField>();
+    send_sync::<SavedData>();
+    send_sync::<FieldMeta>();
+    send_sync::<SaveDir>();
+    send_sync::<SaveStats>();
+    send_sync::<FieldHeaders>();
+    send_sync::<PartialReason>();
+    send_sync::<SavedFieldError>();
+    send_sync::<EntriesSaveResult<Multipart<&'static [u8]>>>();
+    send_sync::<DataReader<'static>>();
+    send_sync::<SaveBuilderConfig>();
+    send_sync::<FieldFailure>();
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Abort, BufferPool, ByteSize, CancelToken, DirLimitExceeded, Entries, EntriesSaveResult, FieldErrorPolicy, FileBackend, FieldMatcher, FieldMeta, FileSize, FilenameChecks, MAX_PREALLOC, MultipartError, PartialReason,
+                PartialSavedField, RetryPolicy, SaveBuilder, SaveBuilderConfig, SaveDir, SavePhase, SaveResult, SaveTotals, SavedData, SavedFieldError, SharedSaveRoot, STORAGE_FULL_CODES, TempDirError, TextNorm, dir_setup_failed, extend_zeroing, parse_form_data, parse_form_data_with_limit, try_write_all};
+
+    use std::convert::TryFrom;
+
+    use mock::{Fault, FaultyReader, FaultyWriter};
+    use std::path::{Path, PathBuf};
+
+    use server::{FieldHeaders, MagicSniffer, Multipart, NewlinePolicy, ReadEntry};
+
+    use std::io::prelude::*;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use std::{cmp, fs, io};
+
+    /// The backends the tests which save files are run against: regular writes, and a shallow
+    /// ring (so writes are batched) with the `io-uring` feature.
+    fn file_backends() -> Vec<FileBackend> {
+        vec![
+            FileBackend::Std,
+            #[cfg(feature = "io-uring")]
+            FileBackend::IoUring(2),
+        ]
+    }
+
+    fn small_fields_body(count: usize, field_len: usize) -> Vec<u8> {
+        let mut body = Vec::new();
+
+        for i in 0 .. count {
+            write!(body, "--boundary\r\n\
+                          Content-Disposition: form-data; name=\"field{}\"\r\n\r\n", i).unwrap();
+            body.extend((0 .. field_len).map(|j| b'a' + ((i + j) % 26) as u8));
+            body.extend_from_slice(b"\r\n");
+        }
+
+        body.extend_from_slice(b"--boundary--\r\n");
+        body
+    }
+
+    #[test]
+    fn test_buffer_pool() {
+        ::init_log();
+
+        let body = small_fields_body(4, 200);
+        let pool = Arc::new(BufferPool::new(1024, 2));
+
+        for _ in 0 .. 2 {
+            let entries = Multipart::with_body(&body[..], "boundary").save()
+                .memory_threshold(1024)
+                .buffer_pool(pool.clone())
+                .temp()
+                .into_result_strict()
+                .unwrap();
+
+            assert_eq!(entries.fields_count(), 4);
+            // all buffers are in use
+            assert_eq!(pool.available(), 0);
+
+            for (name, fields) in &entries.fields {
+                match fields[0].data {
+                    SavedData::Pooled(ref buf) => {
+                        let text = buf.as_str().expect("field should be text");
+                        assert_eq!(text.len(), 200);
+                    },
+                    ref other => panic!("expected pooled data for {:?}, got {:?}", name, other),
+                }
+            }
+
+            drop(entries);
+
+            // the pool is bounded
+            assert_eq!(pool.available(), 2);
+        }
+    }
+
+    #[test]
+    fn test_buffer_pool_spill() {
+        ::init_log();
+
+        let body = small_fields_body(1, 2048);
+        let pool = Arc::new(BufferPool::new(1024, 2));
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(1024)
+            .buffer_pool(pool.clone())
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        match entries.fields["field0"][0].data {
+            SavedData::File(_, size) => assert_eq!(size, FileSize::new(2048)),
+            ref other => panic!("expected file, got {:?}", other),
+        }
+
+        // the buffer was returned after spilling to disk
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_save_stats() {
+        ::init_log();
+
+        for backend in file_backends() {
+            let bodies = [(small_fields_body(100, 10), 1000), (small_fields_body(1, 1 << 20), 1 << 20)];
+
+            for &(ref body, payload) in &bodies {
+                let mut with_preamble = b"preamble\r\n".to_vec();
+                with_preamble.extend_from_slice(body);
+
+                // the CRLF following the closing boundary is never read
+                let total = with_preamble.len() as u64 - 2;
+
+                let mut multipart = Multipart::with_body(&with_preamble[..], "boundary");
+
+                let entries = multipart.save().size_limit(None).text_size_limit(None).max_parts(None)
+                    .file_backend(backend).temp().into_result_strict().unwrap();
+
+                let stats = &entries.stats;
+                assert_eq!(stats.payload_bytes, payload);
+                assert_eq!(stats.payload_bytes + stats.overhead_bytes, total);
+                assert_eq!(multipart.overhead_bytes(), stats.overhead_bytes);
+
+                // field data which is skipped is still counted as payload
+                let mut multipart = Multipart::with_body(&with_preamble[..], "boundary");
+                multipart.foreach_entry(|_| ()).unwrap();
+
+                assert_eq!(multipart.payload_bytes(), payload);
+                assert_eq!(multipart.payload_bytes() + multipart.overhead_bytes(), total);
+            }
+        }
+    }
+
+    #[test]
+    fn test_saved_field_conversions() {
+        ::init_log();
+
+        let mut entries = parse_form_data(FORM_BODY, FORM_CONTENT_TYPE).unwrap();
+        let text = entries.fields.remove("text").unwrap().remove(0);
+        let bytes = entries.fields.remove("file").unwrap().remove(0);
+
+        let mut entries = Multipart::with_body(FORM_BODY, "boundary").save()
+            .memory_threshold(0)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        let file = entries.fields.remove("file").unwrap().remove(0);
+
+        assert_eq!(text.as_text(), Some("hello"));
+        assert_eq!(text.as_bytes(), Some(&b"hello"[..]));
+        assert_eq!(text.as_path(), None);
+
+        assert_eq!(file.as_text(), None);
+        assert_eq!(file.as_bytes(), None);
+        assert!(file.as_path().is_some());
+
+        match PathBuf::try_from(&text) {
+            Err(SavedFieldError::UnexpectedData("File", "Text")) => (),
+            other => panic!("expected unexpected data error, got {:?}", other),
+        }
+
+        assert_eq!(PathBuf::try_from(&file).unwrap(), file.as_path().unwrap());
+        assert_eq!(String::try_from(text).unwrap(), "hello");
+
+        assert_eq!(Vec::<u8>::try_from(file).unwrap(), b"world");
+
+        assert_eq!(bytes.as_bytes(), Some(&b"world"[..]));
+
+        match String::try_from(bytes) {
+            Err(SavedFieldError::UnexpectedData("Text", "Bytes")) => (),
+            other => panic!("expected unexpected data error, got {:?}", other),
+        }
+    }
+
+    const FORM_CONTENT_TYPE: &str = "multipart/form-data; boundary=boundary";
+
+    const FORM_BODY: &[u8] = b"--boundary\r\n\
+                                       Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                                       hello\r\n\
+                                       --boundary\r\n\
+                                       Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+                                       Content-Type: application/octet-stream\r\n\r\n\
+                                       world\r\n\
+                                       --boundary--\r\n";
+
+    #[test]
+    fn test_parse_form_data() {
+        ::init_log();
+
+        let entries = parse_form_data(FORM_BODY, FORM_CONTENT_TYPE).unwrap();
+        assert_eq!(entries.fields_count(), 2);
+        assert!(matches!(entries.save_dir, SaveDir::Memory));
+
+        match entries.fields["text"][0].data {
+            SavedData::Text(ref text) => assert_eq!(&**text, "hello"),
+            ref other => panic!("expected text, got {:?}", other),
+        }
+
+        match entries.fields["file"][0].data {
+            SavedData::Bytes(ref bytes) => assert_eq!(&**bytes, b"world"),
+            ref other => panic!("expected bytes, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_form_data_limit() {
+        ::init_log();
+
+        // the limit is for all fields combined
+        parse_form_data_with_limit(FORM_BODY, FORM_CONTENT_TYPE, 10).unwrap();
+
+        match parse_form_data_with_limit(FORM_BODY, FORM_CONTENT_TYPE, 9) {
+            Err(MultipartError::LimitExceeded(9)) => (),
+            other => panic!("expected limit error, got {:?}", other),
+        }
+    }
+
+    /// The names in the working directory, where a field saved `with_dir("")` would go.
+    fn cwd_names() -> ::std::collections::HashSet<::std::ffi::OsString> {
+        fs::read_dir(".").unwrap().map(|entry| entry.unwrap().file_name()).collect()
+    }
+
+    #[test]
+    fn test_parse_form_data_zero_limit() {
+        ::init_log();
+
+        let before = cwd_names();
+
+        match parse_form_data_with_limit(FORM_BODY, FORM_CONTENT_TYPE, 0) {
+            Err(MultipartError::LimitExceeded(0)) => (),
+            other => panic!("expected limit error, got {:?}", other),
+        }
+
+        // empty fields fit
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"empty\"; filename=\"empty.bin\"\r\n\r\n\
+                     \r\n\
+                     --boundary--\r\n";
+        let entries = parse_form_data_with_limit(body, FORM_CONTENT_TYPE, 0).unwrap();
+        assert_eq!(entries.fields["empty"][0].as_bytes(), Some(&b""[..]));
+
+        assert_eq!(cwd_names(), before);
+    }
+
+    #[test]
+    fn test_parse_form_data_limit_used_up() {
+        ::init_log();
+
+        let before = cwd_names();
+
+        // the limit is reached exactly by the first two fields, leaving nothing for the last
+        let body = |last: &str| format!("--boundary\r\n\
+                                         Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                                         hello\r\n\
+                                         --boundary\r\n\
+                                         Content-Disposition: form-data; name=\"file\"; filename=\"file.bin\"\r\n\r\n\
+                                         world\r\n\
+                                         --boundary\r\n\
+                                         Content-Disposition: form-data; name=\"last\"; filename=\"last.bin\"\r\n\r\n\
+                                         {}\r\n\
+                                         --boundary--\r\n", last);
+
+        let entries = parse_form_data_with_limit(body("").as_bytes(), FORM_CONTENT_TYPE, 10).unwrap();
+        assert_eq!(entries.fields_count(), 3);
+        assert_eq!(entries.fields["last"][0].as_bytes(), Some(&b""[..]));
+        assert!(entries.clean_termination);
+
+        match parse_form_data_with_limit(body("x").as_bytes(), FORM_CONTENT_TYPE, 10) {
+            Err(MultipartError::LimitExceeded(10)) => (),
+            other => panic!("expected limit error, got {:?}", other),
+        }
+
+        assert_eq!(cwd_names(), before);
+    }
+
+    #[test]
+    fn test_parse_form_data_content_type() {
+        ::init_log();
+
+        match parse_form_data(FORM_BODY, "text/plain") {
+            Err(MultipartError::InvalidContentType(_)) => (),
+            other => panic!("expected content type error, got {:?}", other),
+        }
+
+        match parse_form_data(FORM_BODY, "multipart/form-data") {
+            Err(MultipartError::MissingBoundary) => (),
+            other => panic!("expected missing boundary, got {:?}", other),
+        }
+
+        // without the boundary the body is read as having no fields, but not ending cleanly
+        let entries = parse_form_data(FORM_BODY, "multipart/form-data; boundary=other").unwrap();
+        assert!(entries.is_empty());
+        assert!(!entries.clean_termination);
+    }
+
+    #[cfg(feature = "bench")]
+    mod bench {
+        extern crate test;
+        use self::test::Bencher;
+
+        use super::*;
+
+        const FIELDS: usize = 1000;
+        const FIELD_LEN: usize = 200;
+
+        fn bench_save(b: &mut Bencher, pool: Option<Arc<BufferPool>>) {
+            let body = small_fields_body(FIELDS, FIELD_LEN);
+            b.bytes = body.len() as u64;
+
+            b.iter(|| {
+                let mut multipart = Multipart::with_body(&body[..], "boundary");
+                let mut builder = multipart.save().max_parts(None);
+
+                if let Some(ref pool) = pool {
+                    builder = builder.buffer_pool(pool.clone());
+                }
+
+                builder.temp().into_result_strict().unwrap()
+            });
+        }
+
+        #[bench]
+        fn bench_save_small_fields(b: &mut Bencher) {
+            bench_save(b, None);
+        }
+
+        #[bench]
+        fn bench_save_small_fields_pooled(b: &mut Bencher) {
+            bench_save(b, Some(Arc::new(BufferPool::new(10 * 1024, FIELDS))));
+        }
+
+        /// Save a request of `fields` short fields in memory, mostly measuring `Entries`.
+        fn bench_entries(b: &mut Bencher, fields: usize) {
+            let body = small_fields_body(fields, 16);
+            b.bytes = body.len() as u64;
+
+            b.iter(|| {
+                Multipart::with_body(&body[..], "boundary").save().max_parts(None).in_memory()
+                    .into_result_strict().unwrap()
+            });
+        }
+
+        #[bench]
+        fn bench_entries_2_fields(b: &mut Bencher) {
+            bench_entries(b, 2);
+        }
+
+        #[bench]
+        fn bench_entries_200_fields(b: &mut Bencher) {
+            bench_entries(b, 200);
+        }
+    }
+
+    #[test]
+    fn test_entries_summary() {
+        ::init_log();
+
+        const PASSWORD: &str = "correct horse battery staple";
+
+        let body = format!("--boundary\r\n\
+                            Content-Disposition: form-data; name=\"password\"\r\n\r\n\
+                            {}\r\n\
+                            --boundary\r\n\
+                            Content-Disposition: form-data; name=\"bio\"\r\n\r\n\
+                            héllo wörld, this is my biography\r\n\
+                            --boundary--\r\n", PASSWORD);
+
+        let mut entries = parse_form_data(body.as_bytes(), FORM_CONTENT_TYPE).unwrap();
+
+        let summary = entries.summary().redact("password").to_string();
+        assert!(!summary.contains("correct"), "{}", summary);
+        assert!(summary.contains("\"password\": Text, 28 bytes, <redacted>"), "{}", summary);
+        // cut at a char boundary
+        assert!(summary.contains("\"bio\": Text, 35 bytes, \"héllo wörld, t\"…(35 bytes)"),
+                "{}", summary);
+
+        // `Debug` never prints in-memory data
+        let debug = format!("{:?}", entries);
+        assert!(!debug.contains("correct"), "{}", debug);
+        assert!(!debug.contains("llo"), "{}", debug);
+
+        let password = entries.fields.remove("password").unwrap().remove(0);
+        assert!(!format!("{:?}", password).contains("correct"));
+        assert!(password.summary().to_string().contains("\"correct horse ba\"…(28 bytes)"));
+
+        let pool = Arc::new(BufferPool::new(64, 1));
+        let entries = Multipart::with_body(FORM_BODY, "boundary").save()
+            .buffer_pool(pool)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert!(!format!("{:?}", entries).contains("hello"));
+
+        let file = Multipart::with_body(FORM_BODY, "boundary").save()
+            .memory_threshold(0)
+            .temp()
+            .into_result_strict()
+            .unwrap()
+            .fields.remove("file").unwrap().remove(0);
+
+        assert_eq!(file.summary().to_string(),
+                   format!("\"file\", filename \"a.bin\", application/octet-stream: File, 5 bytes, \
+                            saved to {:?}", file.as_path().unwrap()));
+    }
+
+    fn save_chunked(field_len: usize, memory_threshold: u64, size_limit: u64)
+                    -> (super::SaveDir, SaveResult<SavedData, SavedData>, Vec<u8>) {
+        let body = small_fields_body(1, field_len);
+        let expected = body[body.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4 ..]
+            [.. field_len].to_vec();
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let dir = super::SaveDir::Temp(::tempfile::tempdir().unwrap());
+
+        let res = {
+            let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+            field.data.save()
+                .memory_threshold(memory_threshold)
+                .size_limit(size_limit)
+                .chunk_files(1000)
+                .with_path(dir.as_path().join("field"))
+        };
+
+        (dir, res, expected)
+    }
+
+    fn chunk_sizes(data: &SavedData) -> Vec<u64> {
+        match *data {
+            SavedData::Chunked(ref chunks) => chunks.iter().map(|&(_, size)| size).collect(),
+            ref other => panic!("expected chunked data, got {:?}", other),
+        }
+    }
+
+    fn read_data(data: &SavedData) -> Vec<u8> {
+        let mut contents = Vec::new();
+        data.readable().unwrap().read_to_end(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_spill_and_load() {
+        ::init_log();
+
+        let dir = ::tempfile::TempDir::new().unwrap();
+        let binary: Vec<u8> = (0 .. 300u32).map(|i| (i % 256) as u8).collect();
+
+        for original in [SavedData::Text(Arc::new("h\u{e9}llo".into())), SavedData::Bytes(binary.into()), SavedData::Bytes(Vec::new().into())] {
+            let expected = read_data(&original);
+            let mut data = original;
+
+            data.spill_to_dir(dir.path()).unwrap();
+            assert!(data.is_file());
+            assert_eq!(data.size(), expected.len() as u64);
+            assert_eq!(read_data(&data), expected);
+
+            let path = match data {
+                SavedData::File(ref path, _) => path.clone(),
+                ref other => panic!("expected a file, got {:?}", other),
+            };
+
+            // already in a file
+            data.spill_to_dir(dir.path()).unwrap();
+            assert!(data.is_file());
+
+            data.load_into_memory(u64::MAX).unwrap();
+            assert!(data.is_memory());
+            assert_eq!(read_data(&data), expected);
+            assert!(!path.exists());
+        }
+
+        // chunked data is loaded back into one buffer
+        let (chunk_dir, res, expected) = save_chunked(2500, 0, u64::MAX);
+        let mut data = res.into_result_strict().unwrap();
+
+        data.load_into_memory(2500).unwrap();
+        assert_eq!(read_data(&data), expected);
+        assert_eq!(fs::read_dir(chunk_dir.as_path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_load_into_memory_cap() {
+        ::init_log();
+
+        let dir = ::tempfile::TempDir::new().unwrap();
+        let mut data = SavedData::Bytes(vec![b'x'; 300].into());
+        data.spill_to_dir(dir.path()).unwrap();
+
+        let err = data.load_into_memory(299).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(data.is_file());
+        assert_eq!(read_data(&data), vec![b'x'; 300]);
+
+        // the file grew after it was saved, so the recorded size is out of date
+        if let SavedData::File(ref path, _) = data {
+            fs::OpenOptions::new().append(true).open(path).unwrap().write_all(b"more").unwrap();
+        }
+
+        let err = data.load_into_memory(300).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(data.is_file());
+
+        data.load_into_memory(304).unwrap();
+        assert_eq!(data.size(), 304);
+    }
+
+    #[test]
+    fn test_clone_shares_data() {
+        ::init_log();
+
+        let text = SavedData::Text(Arc::new("shared".into()));
+        let bytes = SavedData::Bytes(vec![b'x'; 300].into());
+
+        match (&text, &text.clone()) {
+            (SavedData::Text(a), SavedData::Text(b)) => assert!(Arc::ptr_eq(a, b)),
+            _ => panic!("expected text"),
+        }
+
+        match (&bytes, &bytes.clone()) {
+            (SavedData::Bytes(a), SavedData::Bytes(b)) => assert!(Arc::ptr_eq(a, b)),
+            _ => panic!("expected bytes"),
+        }
+
+        let pool = Arc::new(BufferPool::new(1024, 2));
+        let mut buf = pool.get();
+        buf.extend_from_slice(b"pooled");
+        let pooled = SavedData::Pooled(super::PooledBuf::new(pool.clone(), buf, true));
+        let pooled_clone = pooled.clone();
+
+        match (&pooled, &pooled_clone) {
+            (SavedData::Pooled(a), SavedData::Pooled(b)) => assert!(a.ptr_eq(b)),
+            _ => panic!("expected pooled data"),
+        }
+
+        // the buffer goes back to the pool once the last clone is dropped
+        drop(pooled);
+        assert_eq!(pool.available(), 0);
+        assert_eq!(read_data(&pooled_clone), b"pooled");
+        drop(pooled_clone);
+        assert_eq!(pool.available(), 1);
+
+        // spilling one clone leaves the others in memory
+        let dir = ::tempfile::TempDir::new().unwrap();
+        let mut spilled = bytes.clone();
+        spilled.spill_to_dir(dir.path()).unwrap();
+
+        assert!(spilled.is_file());
+        assert!(!bytes.is_file());
+        assert_eq!(read_data(&spilled), vec![b'x'; 300]);
+
+        // clones of a file share the path
+        let file_clone = spilled.clone();
+        match (&spilled, &file_clone) {
+            (SavedData::File(a, _), SavedData::File(b, _)) => assert_eq!(a, b),
+            _ => panic!("expected files"),
+        }
+
+        // loading makes a new buffer, not shared with the original bytes
+        spilled.load_into_memory(300).unwrap();
+        match (&spilled, &bytes) {
+            (SavedData::Bytes(a), SavedData::Bytes(b)) => {
+                assert!(!Arc::ptr_eq(a, b));
+                assert_eq!(a, b);
+            },
+            _ => panic!("expected bytes"),
+        }
+
+        // the shared buffers can still be read as a body
+        let mut body = String::new();
+        text.clone().into_body_reader().unwrap().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "shared");
+    }
+
+    #[test]
+    fn test_try_from_unshared() {
+        ::init_log();
+
+        let field = |data| super::SavedField {
+            headers: FieldHeaders::new("field"), data, detected_type: None, timing: None, raw_range: None,
+        };
+
+        let text = Arc::new(String::from("shared"));
+        let text_ptr = text.as_ptr();
+        let bytes = Arc::new(vec![b'x'; 300]);
+        let bytes_ptr = bytes.as_ptr();
+
+        // a clone still refers to the data, so it's copied
+        let copied = String::try_from(field(SavedData::Text(text.clone()))).unwrap();
+        assert_eq!(copied, "shared");
+        assert_ne!(copied.as_ptr(), text_ptr);
+
+        let copied = Vec::try_from(field(SavedData::Bytes(bytes.clone()))).unwrap();
+        assert_eq!(copied, vec![b'x'; 300]);
+        assert_ne!(copied.as_ptr(), bytes_ptr);
+
+        // the last handle gives up its buffer
+        let moved = String::try_from(field(SavedData::Text(text))).unwrap();
+        assert_eq!(moved.as_ptr(), text_ptr);
+
+        let moved = Vec::try_from(field(SavedData::Bytes(bytes))).unwrap();
+        assert_eq!(moved.as_ptr(), bytes_ptr);
+    }
+
+    #[test]
+    fn test_readable_recorded_size() {
+        ::init_log();
+
+        let dir = ::tempfile::TempDir::new().unwrap();
+        let mut data = SavedData::Bytes(b"original".to_vec().into());
+        data.spill_to_dir(dir.path()).unwrap();
+
+        let path = match data {
+            SavedData::File(ref path, _) => path.clone(),
+            _ => panic!("expected a file"),
+        };
+
+        fn read_all(mut reader: super::DataReader) -> Vec<u8> {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).unwrap();
+            buf
+        }
+
+        // the file grew: only the recorded size is read, unless unbounded
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b" and more").unwrap();
+
+        let reader = data.readable().unwrap();
+        assert_eq!(reader.expected_len(), Some(8));
+        assert_eq!(read_all(reader), b"original");
+        assert_eq!(read_all(data.clone().into_body_reader().unwrap()), b"original");
+
+        let reader = data.readable_unbounded().unwrap();
+        assert_eq!(reader.expected_len(), None);
+        assert_eq!(read_all(reader), b"original and more");
+
+        // the file shrank: reading ends short of the expected length
+        fs::write(&path, b"orig").unwrap();
+
+        let reader = data.readable().unwrap();
+        assert_eq!(reader.expected_len(), Some(8));
+        assert_eq!(read_all(reader), b"orig");
+        assert_eq!(read_all(data.readable_unbounded().unwrap()), b"orig");
+    }
+
+    #[test]
+    fn test_spill_and_load_all() {
+        ::init_log();
+
+        let body = small_fields_body(3, 100);
+        let mut entries = Multipart::with_body(&body[..], "boundary").save()
+            .buffer_pool(Arc::new(BufferPool::new(128, 4)))
+            .temp().into_result_strict().unwrap();
+
+        let expected: Vec<(Arc<str>, Vec<u8>)> = (0 .. 3).map(|i| {
+            let name: Arc<str> = format!("field{}", i).into();
+            let data = read_data(&entries.fields[&name][0].data);
+            (name, data)
+        }).collect();
+
+        let dir = ::tempfile::TempDir::new().unwrap();
+        assert!(entries.spill_all(dir.path()).is_empty());
+        assert!(entries.fields.values().all(|fields| fields[0].data.is_file()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        // every field is over the cap, and each one is reported
+        let mut failed: Vec<Arc<str>> = entries.load_all(99).into_iter().map(|(name, _)| name).collect();
+        failed.sort();
+        assert_eq!(failed, expected.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>());
+
+        assert!(entries.load_all(100).is_empty());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        for (name, data) in &expected {
+            let field = &entries.fields[name][0];
+            assert!(field.data.is_memory());
+            assert_eq!(read_data(&field.data), *data);
+        }
+    }
+
+    #[test]
+    fn test_totals() {
+        ::init_log();
+
+        for backend in file_backends() {
+            let body = b"--boundary\r\n\
+                         Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                         hello\r\n\
+                         --boundary\r\n\
+                         Content-Disposition: form-data; name=\"bytes\"\r\n\
+                         Content-Type: application/octet-stream\r\n\r\n\
+                         binary\r\n\
+                         --boundary\r\n\
+                         Content-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\r\n\
+                         a file larger than the memory threshold\r\n\
+                         --boundary--\r\n";
+
+            let mut entries = Multipart::with_body(&body[..], "boundary").save()
+                .memory_threshold(16).file_backend(backend).temp().into_result_strict().unwrap();
+            assert_eq!(entries.totals(), SaveTotals { disk_bytes: 39, memory_bytes: 11, file_count: 1, text_count: 1 });
+
+            let dir = ::tempfile::TempDir::new().unwrap();
+            assert!(entries.spill_all(dir.path()).is_empty());
+            assert_eq!(entries.totals(), SaveTotals { disk_bytes: 50, memory_bytes: 0, file_count: 3, text_count: 0 });
+
+            // the file is over the cap, so it stays on disk
+            assert_eq!(entries.load_all(16).len(), 1);
+            assert_eq!(entries.totals(), SaveTotals { disk_bytes: 39, memory_bytes: 11, file_count: 1, text_count: 0 });
+
+            // the file is cut off by the size limit and kept as it is
+            let entries = match Multipart::with_body(&body[..], "boundary").save()
+                .memory_threshold(16).size_limit(20).file_backend(backend).temp() {
+                SaveResult::Partial(partial, PartialReason::SizeLimit { .. }) => partial.keep_partial(),
+                other => panic!("expected size limit, got {:?}", other.into_opt_both()),
+            };
+
+            let upload = &entries.fields["upload"][0].data;
+            assert!(upload.is_file());
+            assert_eq!(entries.totals(), SaveTotals {
+                disk_bytes: upload.size(), memory_bytes: 11, file_count: 1, text_count: 1
+            });
+
+            let mut recounted = entries;
+            assert_eq!(recounted.recount_totals(), recounted.totals());
+        }
+    }
+
+    #[test]
+    fn test_chunk_files() {
+        ::init_log();
+
+        for &(field_len, memory_threshold, ref sizes) in &[
+            (2500, 0, vec![1000, 1000, 500]),
+            // ends exactly on a chunk boundary
+            (3000, 0, vec![1000, 1000, 1000]),
+            // in-memory data spanning several chunks
+            (3000, 2500, vec![1000, 1000, 1000]),
+        ] {
+            let (dir, res, expected) = save_chunked(field_len, memory_threshold, u64::MAX);
+
+            let data = match res {
+                SaveResult::Full(data) => data,
+                other => panic!("expected full save, got {:?}", other),
+            };
+
+            assert_eq!(chunk_sizes(&data), *sizes);
+            assert_eq!(data.size(), field_len as u64);
+            assert_eq!(read_data(&data), expected);
+
+            if let SavedData::Chunked(ref chunks) = data {
+                for (idx, &(ref path, size)) in chunks.iter().enumerate() {
+                    assert_eq!(*path, dir.as_path().join(format!("field.part{:03}", idx)));
+                    assert_eq!(::std::fs::metadata(path).unwrap().len(), size);
+                }
+            }
+
+            // no empty trailing chunk
+            assert_eq!(::std::fs::read_dir(dir.as_path()).unwrap().count(), sizes.len());
+        }
+    }
+
+    #[test]
+    fn test_chunk_files_size_limit() {
+        ::init_log();
+
+        // the limit applies to the whole field, not each chunk
+        let (_dir, res, expected) = save_chunked(3000, 0, 2500);
+
+        let data = match res {
+            SaveResult::Partial(data, PartialReason::SizeLimit { .. }) => data,
+            other => panic!("expected size limit, got {:?}", other),
+        };
+
+        assert_eq!(chunk_sizes(&data), vec![1000, 1000, 500]);
+        assert_eq!(read_data(&data), &expected[..2500]);
+
+        // a field at the limit
+        let (_dir, res, _) = save_chunked(2000, 0, 2000);
+
+        match res {
+            SaveResult::Full(ref data) => assert_eq!(chunk_sizes(data), vec![1000, 1000]),
+            other => panic!("expected full save, got {:?}", other),
+        }
+    }
+
+    /// Save a 40 byte field to `path`, of which `memory_threshold` bytes are read to memory first.
+    fn save_to_path<F>(path: &Path, memory_threshold: u64, opts_fn: F) -> SaveResult<SavedData, SavedData>
+    where F: FnOnce(&mut fs::OpenOptions) {
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n\
+                     0123456789012345678901234567890123456789\r\n\
+                     --boundary--\r\n";
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        let res = field.data.save()
+            .memory_threshold(memory_threshold)
+            .mod_open_opts(opts_fn)
+            .with_path(path);
+        res
+    }
+
+    #[test]
+    fn test_file_size_open_opts() {
+        ::init_log();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let path = dir.path().join("upload.bin");
+
+        // the default `create_new(true)`
+        for &memory_threshold in &[0, 10] {
+            let _ = fs::remove_file(&path);
+
+            match save_to_path(&path, memory_threshold, |_| ()) {
+                SaveResult::Full(SavedData::File(_, size)) => assert_eq!(size, FileSize::new(40)),
+                other => panic!("expected file, got {:?}", other),
+            }
+
+            match save_to_path(&path, memory_threshold, |_| ()) {
+                SaveResult::Error(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+                other => panic!("expected error, got {:?}", other),
+            }
+        }
+
+        // resuming an upload appends to the data already saved
+        let data = match save_to_path(&path, 10, |opts| { opts.create_new(false).append(true); }) {
+            SaveResult::Full(data) => data,
+            other => panic!("expected full save, got {:?}", other),
+        };
+
+        match data {
+            SavedData::File(_, size) => assert_eq!(size, FileSize { written: 40, len: 80 }),
+            ref other => panic!("expected file, got {:?}", other),
+        }
+        assert_eq!(data.size(), 80);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 80);
+
+        let mut read = Vec::new();
+        data.readable().unwrap().read_to_end(&mut read).unwrap();
+        assert_eq!(&read[..40], &read[40..]);
+
+        // truncating overwrites it
+        match save_to_path(&path, 10, |opts| { opts.create_new(false).truncate(true); }) {
+            SaveResult::Full(SavedData::File(_, size)) => assert_eq!(size, FileSize::new(40)),
+            other => panic!("expected file, got {:?}", other),
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len(), 40);
+    }
+
+    fn digest_body(text_headers: &str, file_headers: &str) -> Vec<u8> {
+        format!("--boundary\r\n\
+                 Content-Disposition: form-data; name=\"text\"\r\n{}\r\n\
+                 hello\r\n\
+                 --boundary\r\n\
+                 Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+                 Content-Type: application/octet-stream\r\n{}\r\n\
+                 world\r\n\
+                 --boundary--\r\n", text_headers, file_headers).into_bytes()
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_verify_digests() {
+        ::init_log();
+
+        let body = digest_body("Content-MD5: XUFAKrxLKna5cZ2REBfFkg==\r\n",
+                               "Digest: SHA-256=SG6kYiTRu0+2gPNPfJrZao8k7Ii+c+qOWmxlJg6cuKc=, \
+                                md5=fXkwN6B2AYZXSwKC8vQ15w==\r\n");
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .verify_digests(true)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        let file = &entries.fields["file"][0];
+        assert_eq!(file.headers.digests.len(), 2);
+        assert_eq!(file.headers.digests[0].algorithm, "sha-256");
+        assert_eq!(file.headers.digests[1].value, "fXkwN6B2AYZXSwKC8vQ15w==");
+        assert!(file.as_path().is_some());
+
+        assert_eq!(entries.fields["text"][0].headers.digests[0].algorithm, "md5");
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_verify_digests_mismatch() {
+        ::init_log();
+
+        for backend in file_backends() {
+            // the text field's digest is for "world"
+            let body = digest_body("Content-MD5: fXkwN6B2AYZXSwKC8vQ15w==\r\n",
+                                   "Digest: sha-256=SG6kYiTRu0+2gPNPfJrZao8k7Ii+c+qOWmxlJg6cuKc=\r\n");
+
+            // not checked unless enabled
+            Multipart::with_body(&body[..], "boundary").save().file_backend(backend).temp().into_result_strict().unwrap();
+
+            let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+            let partial = match multipart.save().verify_digests(true).file_backend(backend).temp() {
+                SaveResult::Partial(partial, PartialReason::DigestMismatch { field, algorithm, expected, actual }) => {
+                    assert_eq!(&*field, "text");
+                    assert_eq!(algorithm, "md5");
+                    assert_eq!(expected, "fXkwN6B2AYZXSwKC8vQ15w==");
+                    assert_eq!(actual, "XUFAKrxLKna5cZ2REBfFkg==");
+                    partial
+                },
+                other => panic!("expected digest mismatch, got {:?}", other.into_opt_both()),
+            };
+
+            let field = partial.partial.unwrap();
+            assert!(field.dest.is_none());
+
+            // the rest of the request can still be saved
+            let entries = field.source.data.into_inner().save().verify_digests(true).file_backend(backend)
+                .with_entries(partial.entries)
+                .into_result_strict()
+                .unwrap();
+            assert!(!entries.fields.contains_key("text"));
+            assert!(entries.fields.contains_key("file"));
+
+            // a mismatched file is deleted
+            let body = digest_body("", "Content-MD5: XUFAKrxLKna5cZ2REBfFkg==\r\n");
+            let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+            match multipart.save().memory_threshold(0).verify_digests(true).file_backend(backend).temp() {
+                SaveResult::Partial(partial, PartialReason::DigestMismatch { ref field, .. }) => {
+                    assert_eq!(&**field, "file");
+
+                    // only the text field's file is left
+                    let save_dir = partial.entries.save_dir.as_path();
+                    let files: Vec<_> = ::std::fs::read_dir(save_dir).unwrap()
+                        .map(|entry| entry.unwrap().path())
+                        .collect();
+                    assert_eq!(files, [partial.entries.fields["text"][0].as_path().unwrap()]);
+                },
+                other => panic!("expected digest mismatch, got {:?}", other.into_opt_both()),
+            }
+        }
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_verify_digests_malformed() {
+        ::init_log();
+
+        // unsupported algorithms and items without a value are skipped
+        let body = digest_body("Digest: sha-512=AAAA, unixsum=30637, sha-256\r\n",
+                               "Digest: ,\r\n");
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .verify_digests(true)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields["text"][0].headers.digests.len(), 2);
+        assert!(entries.fields["file"][0].headers.digests.is_empty());
+
+        // a supported algorithm with a value which isn't base64 never matches
+        let body = digest_body("Content-MD5: not base64!\r\n", "");
+
+        match Multipart::with_body(&body[..], "boundary").save().verify_digests(true).temp() {
+            SaveResult::Partial(_, PartialReason::DigestMismatch { expected, .. }) =>
+                assert_eq!(expected, "not base64!"),
+            other => panic!("expected digest mismatch, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    fn charset_body(charset: &str) -> Vec<u8> {
+        let mut body = format!("--boundary\r\n\
+                                Content-Disposition: form-data; name=\"_charset_\"\r\n\r\n\
+                                {}\r\n\
+                                --boundary\r\n\
+                                Content-Disposition: form-data; name=\"name\"\r\n\r\n", charset)
+            .into_bytes();
+        body.extend_from_slice(b"Caf\xE9 cr\xE8me\r\n\
+                                 --boundary\r\n\
+                                 Content-Disposition: form-data; name=\"utf8\"\r\n\
+                                 Content-Type: text/plain; charset=utf-8\r\n\r\n\
+                                 Caf\xC3\xA9\r\n\
+                                 --boundary--\r\n");
+        body
+    }
+
+    #[test]
+    fn test_honor_charset_field() {
+        ::init_log();
+
+        let body = charset_body("ISO-8859-1");
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .honor_charset_field(true)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields["name"][0].as_text(), Some("Caf\u{E9} cr\u{E8}me"));
+        // an explicit charset takes precedence
+        assert_eq!(entries.fields["utf8"][0].as_text(), Some("Caf\u{E9}"));
+        assert!(!entries.fields.contains_key("_charset_"));
+        assert_eq!(entries.fields_count(), 2);
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .honor_charset_field(true)
+            .keep_charset_field(true)
+            .buffer_pool(Arc::new(BufferPool::new(64, 4)))
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields["_charset_"][0].as_text(), Some("ISO-8859-1"));
+        assert_eq!(entries.fields["name"][0].as_text(), Some("Caf\u{E9} cr\u{E8}me"));
+
+        // not decoded unless enabled
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields["name"][0].as_bytes(), Some(&b"Caf\xE9 cr\xE8me"[..]));
+        assert_eq!(entries.fields["name"][0].as_text(), None);
+        assert!(entries.fields.contains_key("_charset_"));
+    }
+
+    #[test]
+    fn test_honor_charset_field_unknown() {
+        ::init_log();
+
+        let body = charset_body("x-unknown");
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .honor_charset_field(true)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        // falls back to UTF-8, which the Latin-1 text isn't
+        assert_eq!(entries.fields["name"][0].as_text(), None);
+        assert_eq!(entries.fields["utf8"][0].as_text(), Some("Caf\u{E9}"));
+    }
+
+    /// Fails with `WouldBlock` `failures[n]` times before the `n`th write (repeating the last
+    /// count), and writes at most `max_write` bytes at once.
+    struct FlakyWriter {
+        out: Vec<u8>,
+        failures: Vec<u32>,
+        writes: usize,
+        failed: u32,
+        max_write: usize,
+    }
+
+    impl FlakyWriter {
+        fn new(failures: Vec<u32>, max_write: usize) -> Self {
+            FlakyWriter { out: Vec::new(), failures, writes: 0, failed: 0, max_write }
+        }
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let failures = *self.failures.get(self.writes).or(self.failures.last()).unwrap_or(&0);
+
+            if self.failed < failures {
+                self.failed += 1;
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "try again"));
+            }
+
+            self.failed = 0;
+            self.writes += 1;
+            let len = cmp::min(buf.len(), self.max_write);
+            self.out.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn test_retry_writes() {
+        ::init_log();
+
+        let data: Vec<u8> = (0 .. 100u8).collect();
+        let policy = RetryPolicy::new(2, Duration::from_millis(1));
+
+        let mut writer = FlakyWriter::new(vec![2], 7);
+        match try_write_all(&data, &mut writer, &policy) {
+            SaveResult::Full(written) => assert_eq!(written, data.len()),
+            other => panic!("expected full write, got {:?}", other),
+        }
+        // no bytes were duplicated or lost
+        assert_eq!(writer.out, data);
+
+        // attempts exhausted after some data was written
+        let mut writer = FlakyWriter::new(vec![0, 3], 7);
+        match try_write_all(&data, &mut writer, &policy) {
+            SaveResult::Partial(written, PartialReason::IoError(e)) => {
+                assert_eq!(written, 7);
+                assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+            },
+            other => panic!("expected partial write, got {:?}", other),
+        }
+        assert_eq!(writer.out, &data[..7]);
+
+        // not retried without attempts, or for other error kinds
+        for policy in &[RetryPolicy::default(),
+                        policy.clone().kinds(vec![io::ErrorKind::TimedOut])] {
+            let mut writer = FlakyWriter::new(vec![1], 7);
+            match try_write_all(&data, &mut writer, policy) {
+                SaveResult::Error(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                other => panic!("expected error, got {:?}", other),
+            }
+            assert_eq!(writer.failed, 1);
+        }
+    }
+
+    #[test]
+    fn test_write_at() {
+        use std::thread;
+
+        ::init_log();
+
+        const CHUNK: usize = 1000;
+        let original: Vec<u8> = (0 .. 4500u32).map(|i| (i * 13 % 251) as u8).collect();
+
+        // the pieces arrive out of order, split between two requests
+        let bodies: Vec<Vec<u8>> = [[3usize, 0, 4], [1, 2, 5]].iter().map(|order| {
+            let mut body = Vec::new();
+
+            for &n in order.iter().filter(|&&n| n * CHUNK < original.len()) {
+                let end = cmp::min((n + 1) * CHUNK, original.len());
+                write!(body, "--boundary\r\n\
+                              Content-Disposition: form-data; name=\"chunk-{}\"; filename=\"blob\"\r\n\
+                              Content-Type: application/octet-stream\r\n\r\n", n).unwrap();
+                body.extend_from_slice(&original[n * CHUNK .. end]);
+                body.extend_from_slice(b"\r\n");
+            }
+
+            body.extend_from_slice(b"--boundary--\r\n");
+            body
+        }).collect();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let path = dir.path().join("assembled");
+        let file = Arc::new(fs::File::create(&path).unwrap());
+        file.set_len(original.len() as u64).unwrap();
+
+        let threads: Vec<_> = bodies.into_iter().map(|body| {
+            let file = file.clone();
+            thread::spawn(move || {
+                let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+                while let Some(mut field) = multipart.read_entry_mut().into_result().unwrap() {
+                    let n: usize = field.headers.name["chunk-".len() ..].parse().unwrap();
+                    let offset = (n * CHUNK) as u64;
+
+                    match field.data.save().size_limit(CHUNK as u64).write_at(&file, offset) {
+                        SaveResult::Full(written) => assert!(written as usize <= CHUNK),
+                        other => panic!("chunk {} failed: {:?}", n, other),
+                    }
+                }
+            })
+        }).collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(fs::read(&path).unwrap(), original);
+
+        // only `size_limit` bytes are written at the offset
+        let body = small_fields_body(1, 64);
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().unwrap();
+
+        match field.data.save().size_limit(10).write_at(&file, 5) {
+            SaveResult::Partial(10, PartialReason::SizeLimit { .. }) => (),
+            other => panic!("expected size limit, got {:?}", other),
+        }
+
+        let contents = fs::read(&path).unwrap();
+        assert_eq!(contents.len(), original.len());
+        assert_eq!(&contents[.. 5], &original[.. 5]);
+        assert_eq!(&contents[15 ..], &original[15 ..]);
+        assert_ne!(&contents[5 .. 15], &original[5 .. 15]);
+    }
+
+    fn fault_body() -> (Vec<u8>, Vec<u8>) {
+        let payload: Vec<u8> = (0 .. 5000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        let mut body = b"--boundary\r\n\
+                         Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n".to_vec();
+        body.extend_from_slice(&payload);
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+        (body, payload)
+    }
+
+    /// Every script of up to two faults from `faults`, after each of `prefixes`.
+    fn fault_scripts(prefixes: &[Vec<Fault>], faults: &[Fault]) -> Vec<Vec<Fault>> {
+        let mut scripts = Vec::new();
+
+        for prefix in prefixes {
+            for &first in faults {
+                for &second in faults {
+                    let mut script = prefix.clone();
+                    script.push(first);
+                    script.push(second);
+                    scripts.push(script);
+                }
+            }
+        }
+
+        scripts
+    }
+
+    /// The kind of the error the faults `used` should end a write with, if any: `Zero` is a
+    /// write of no bytes, and `WouldBlock` fails once it's repeated more than `attempts` times
+    /// in a row.
+    fn expected_write_error(used: &[Fault], attempts: u32) -> Option<io::ErrorKind> {
+        let mut blocked = 0;
+
+        for &fault in used {
+            match fault {
+                Fault::Zero | Fault::Short(0) => return Some(io::ErrorKind::WriteZero),
+                Fault::WouldBlock if blocked == attempts => return Some(io::ErrorKind::WouldBlock),
+                Fault::WouldBlock => blocked += 1,
+                Fault::Interrupted => (),
+                Fault::Pass | Fault::Short(_) => blocked = 0,
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn test_write_faults() {
+        ::init_log();
+
+        let (body, payload) = fault_body();
+        let prefixes = [vec![], vec![Fault::Short(1)], vec![Fault::Short(100), Fault::Pass]];
+
+        for script in fault_scripts(&prefixes, &Fault::all(&[1, 999])) {
+            for &attempts in &[0, 2] {
+                let mut multipart = Multipart::with_body(&body[..], "boundary");
+                let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+                let mut dest = FaultyWriter::new(Vec::new(), script.clone());
+                let res = field.data.save()
+                    .retry_writes(RetryPolicy::new(attempts, Duration::from_millis(0)))
+                    .write_to(&mut dest);
+
+                // nothing duplicated or dropped, and every byte written accounted for
+                let written = dest.get_ref();
+                assert_eq!(written.len() as u64, dest.written(), "{:?}", script);
+                assert!(payload.starts_with(written), "{:?}: data differs", script);
+
+                let used = &script[.. script.len() - dest.remaining_script().len()];
+
+                match (res, expected_write_error(used, attempts)) {
+                    (SaveResult::Full(copied), None) => {
+                        assert_eq!(copied, written.len() as u64, "{:?}", script);
+                        assert_eq!(written.len(), payload.len(), "{:?}", script);
+                    },
+                    (SaveResult::Partial(copied, PartialReason::IoError(e)), Some(kind)) => {
+                        assert_eq!(copied, written.len() as u64, "{:?}", script);
+                        assert_eq!(e.kind(), kind, "{:?}", script);
+                    },
+                    (SaveResult::Error(e), Some(kind)) => {
+                        assert!(written.is_empty(), "{:?}", script);
+                        assert_eq!(e.kind(), kind, "{:?}", script);
+                    },
+                    (other, expected) => panic!("{:?} with {} attempts: expected {:?}, got {:?}",
+                                                script, attempts, expected, other),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_faults() {
+        ::init_log();
+
+        let (body, payload) = fault_body();
+        // the first read fills the buffer past the headers, so the faults hit the field data
+        let prefixes = [vec![Fault::Short(2000)], vec![Fault::Short(2000), Fault::Short(1), Fault::Pass],
+                        vec![Fault::Short(2000), Fault::Short(1500)]];
+
+        for script in fault_scripts(&prefixes, &Fault::all(&[1, 999])) {
+            let source = FaultyReader::new(&body[..], script.clone());
+            let mut multipart = Multipart::with_body(source, "boundary");
+            let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+            let mut dest = Vec::new();
+            let res = field.data.save().write_to(&mut dest);
+
+            assert!(payload.starts_with(&dest), "{:?}: data differs", script);
+
+            // `Zero` ends the body early, which isn't an error for the field data itself
+            let would_block = script.iter().position(|&f| f == Fault::WouldBlock);
+            let zero = script.iter().position(|&f| f == Fault::Zero || f == Fault::Short(0));
+
+            match res {
+                SaveResult::Full(copied) => {
+                    assert_eq!(copied, dest.len() as u64, "{:?}", script);
+                    assert!(would_block.is_none() || zero < would_block, "{:?}", script);
+
+                    if zero.is_none() {
+                        assert_eq!(dest, payload, "{:?}", script);
+                    }
+                },
+                SaveResult::Partial(copied, PartialReason::IoError(e)) => {
+                    assert_eq!(copied, dest.len() as u64, "{:?}", script);
+                    assert_eq!(e.kind(), io::ErrorKind::WouldBlock, "{:?}", script);
+                },
+                SaveResult::Error(e) => {
+                    assert!(dest.is_empty(), "{:?}", script);
+                    assert_eq!(e.kind(), io::ErrorKind::WouldBlock, "{:?}", script);
+                },
+                other => panic!("{:?}: unexpected {:?}", script, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_writes_field() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n\
+                     some binary data, written a few bytes at a time\r\n\
+                     --boundary--\r\n";
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        let mut writer = FlakyWriter::new(vec![2], 5);
+        let written = field.data.save()
+            .retry_writes(RetryPolicy::new(2, Duration::from_millis(0)))
+            .write_to(&mut writer)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(written, 47);
+        assert_eq!(writer.out, &b"some binary data, written a few bytes at a time"[..]);
+    }
+
+    /// Records the number of bytes written before each flush, optionally failing the flushes.
+    #[derive(Default)]
+    struct FlushLog {
+        out: Vec<u8>,
+        flushes: Vec<usize>,
+        fail_flushes: bool,
+    }
+
+    impl Write for FlushLog {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.out.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.fail_flushes {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "flush failed"));
+            }
+
+            self.flushes.push(self.out.len());
+            Ok(())
+        }
+    }
+
+    /// Reads at most `.1` bytes at once.
+    struct ChunkReader<'a>(&'a [u8], usize);
+
+    impl<'a> Read for ChunkReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = cmp::min(buf.len(), self.1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_flush_every() {
+        ::init_log();
+
+        let payload: Vec<u8> = (0 .. 1000u32).map(|i| b'a' + (i % 26) as u8).collect();
+        let mut body = b"--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n".to_vec();
+        body.extend_from_slice(&payload);
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+        for &chunk in &[1, 7, 64, 100, 4096] {
+            for &flush_every in &[1u64, 10, 64, 100, 333, 1000, 5000] {
+                let mut multipart = Multipart::with_body(ChunkReader(&body, chunk), "boundary");
+                let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+                let mut dest = FlushLog::default();
+                let written = field.data.save().flush_every(flush_every)
+                    .write_to(&mut dest).into_result_strict().unwrap();
+
+                assert_eq!(written, 1000);
+                assert_eq!(dest.out, payload, "chunk {}, flush_every {}", chunk, flush_every);
+
+                // exactly every `flush_every` bytes, then once more at the end
+                let mut expected: Vec<usize> = (1 ..= 1000 / flush_every as usize)
+                    .map(|n| n * flush_every as usize).collect();
+                expected.push(1000);
+
+                assert_eq!(dest.flushes, expected, "chunk {}, flush_every {}", chunk, flush_every);
+            }
+        }
+
+        // the writer decides by default
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+        let mut dest = FlushLog::default();
+        field.data.save().write_to(&mut dest).into_result_strict().unwrap();
+        assert!(dest.flushes.is_empty());
+
+        // a streamed field of a whole request
+        let mut dest = FlushLog::default();
+        let entries = Multipart::with_body(ChunkReader(&body, 64), "boundary").save()
+            .flush_every(400)
+            .stream_field_to("file", &mut dest)
+            .into_result_strict()
+            .unwrap();
+
+        assert!(!entries.streamed_field_missing);
+        assert_eq!(dest.flushes, vec![400, 800, 1000]);
+    }
+
+    #[test]
+    fn test_flush_every_fails() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n\
+                     0123456789abcdefghij\r\n\
+                     --boundary--\r\n";
+
+        // flushed in the middle of the field, or only at the end
+        for &(flush_every, copied) in &[(8, 8), (50, 20)] {
+            let mut multipart = Multipart::with_body(&body[..], "boundary");
+            let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+            let mut dest = FlushLog { fail_flushes: true, ..FlushLog::default() };
+
+            match field.data.save().flush_every(flush_every).write_to(&mut dest) {
+                SaveResult::Partial(written, PartialReason::IoError(e)) => {
+                    assert_eq!(written, copied);
+                    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+                },
+                other => panic!("flush_every {}: expected a flush error, got {:?}", flush_every, other),
+            }
+
+            assert_eq!(dest.out.len() as u64, copied);
+        }
+    }
+
+    #[test]
+    fn test_save_parts_source() {
+        use server::{FieldHeaders, Part, PartsSource, UnnamedPolicy};
+        use std::io::{BufRead, BufReader};
+
+        ::init_log();
+
+        let mut file_headers = FieldHeaders::new("file");
+        file_headers.filename = Some("data.bin".into());
+        file_headers.content_type = Some(::mime::APPLICATION_OCTET_STREAM);
+
+        let file_data: Vec<u8> = (0 .. 4096u32).map(|i| i as u8).collect();
+
+        let parts: Vec<Part> = vec![
+            (FieldHeaders::new("text"), Box::new(&b"hello"[..])),
+            (FieldHeaders::new(""), Box::new(&b"skipped"[..])),
+            // small reads shouldn't matter
+            (file_headers, Box::new(BufReader::with_capacity(3, io::Cursor::new(file_data.clone())))),
+            (FieldHeaders::new("text"), Box::new(io::empty()) as Box<dyn BufRead>),
+        ];
+
+        let mut source = PartsSource::new(parts);
+        source.on_unnamed(UnnamedPolicy::Skip);
+
+        let entries = source.save()
+            .memory_threshold(1024)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 3);
+        assert_eq!(entries.fields["text"][0].as_text(), Some("hello"));
+        assert_eq!(entries.fields["text"][1].as_text(), Some(""));
+
+        let file = &entries.fields["file"][0];
+        assert_eq!(file.headers.filename.as_deref(), Some("data.bin"));
+        let mut saved = Vec::new();
+        file.data.readable().unwrap().read_to_end(&mut saved).unwrap();
+        assert_eq!(saved, file_data);
+
+        // the skipped field isn't read
+        assert_eq!(entries.stats.payload_bytes, 5 + 4096);
+        assert_eq!(entries.stats.overhead_bytes, 0);
+    }
+
+    /// A request body which must not be read.
+    struct Unreadable;
+
+    impl Read for Unreadable {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            panic!("request body was read");
+        }
+    }
+
+    #[test]
+    fn test_count_limit_zero() {
+        ::init_log();
+
+        let mut multipart = Multipart::with_body(Unreadable, "boundary");
+
+        match multipart.save().max_parts(0).in_memory() {
+            SaveResult::Partial(partial, PartialReason::CountLimit { limit: 0 }) => {
+                assert!(partial.partial.is_none());
+                assert!(partial.entries.is_empty());
+            },
+            other => panic!("expected count limit, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_part_limits() {
+        ::init_log();
+
+        // 3 files and 5 other fields
+        let names = ["t0", "f0", "t1", "t2", "f1", "t3", "f2", "t4"];
+        let mut body = Vec::new();
+
+        for name in &names {
+            let filename = if name.starts_with('f') { format!("; filename=\"{}.txt\"", name) } else { String::new() };
+            write!(body, "--boundary\r\n\
+                          Content-Disposition: form-data; name=\"{}\"{}\r\n\r\n\
+                          data\r\n", name, filename).unwrap();
+        }
+        body.extend_from_slice(b"--boundary--\r\n");
+
+        let save = || SaveBuilder::new(Multipart::with_body(&body[..], "boundary")).memory_threshold(0);
+
+        // the limit trips at the first part beyond it, which is left unsaved
+        let limits: Vec<(SaveBuilder<_>, &str, usize, &str)> = vec![
+            (save().max_file_parts(2), "f2", 6, "the request exceeded the limit of 2 files"),
+            (save().max_text_parts(3), "t3", 5, "the request exceeded the limit of 3 fields without a filename"),
+            (save().max_file_parts(0), "f0", 1, "the request exceeded the limit of 0 files"),
+        ];
+
+        for (builder, tripped, saved, message) in limits {
+            let (partial, reason) = match builder.temp() {
+                SaveResult::Partial(partial, reason) => (partial, reason),
+                other => panic!("expected a part limit, got {:?}", other.into_opt_both()),
+            };
+
+            assert!(match reason {
+                PartialReason::FilePartLimit { .. } => tripped.starts_with('f'),
+                PartialReason::TextPartLimit { .. } => tripped.starts_with('t'),
+                _ => false,
+            }, "{:?}", reason);
+            assert_eq!(reason.to_string(), message);
+            assert_eq!(reason.suggested_status(), 413);
+
+            assert_eq!(partial.entries.fields_count() as usize, saved);
+            let field = partial.partial.unwrap();
+            assert_eq!(&*field.source.headers.name, tripped);
+            assert!(field.dest.is_none());
+        }
+
+        match save().max_parts(4).temp() {
+            SaveResult::Partial(partial, PartialReason::CountLimit { limit: 4 }) =>
+                assert_eq!(partial.entries.fields_count(), 4),
+            other => panic!("expected count limit, got {:?}", other.into_opt_both()),
+        }
+
+        // `count_limit()` still works the same as `max_parts()`
+        #[allow(deprecated)]
+        let res = save().count_limit(4).temp();
+        assert!(matches!(res, SaveResult::Partial(_, PartialReason::CountLimit { limit: 4 })));
+
+        // each limit is independent of the others
+        let entries = save().max_file_parts(3).max_text_parts(5).temp().into_result_strict().unwrap();
+        assert_eq!(entries.fields_count(), 8);
+    }
+
+    #[test]
+    fn test_capture_raw() {
+        ::init_log();
+
+        fn hash(data: &[u8]) -> u64 {
+            use std::collections::hash_map::DefaultHasher;
+            use std::hash::Hasher;
+
+            let mut hasher = DefaultHasher::new();
+            hasher.write(data);
+            hasher.finish()
+        }
+
+        let bodies: [&[u8]; 5] = [
+            // bare LF line endings
+            b"--boundary\nContent-Disposition: form-data; name=\"a\"\n\nvalue\n--boundary--\n",
+            // preamble, an empty part, and an epilogue after the closing boundary
+            b"preamble\r\n--boundary\r\n\r\n--boundary\r\n\
+              Content-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n--boundary--\r\nepilogue",
+            // no closing boundary
+            b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n",
+            // no CRLF before the closing boundary
+            b"--boundary\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue--boundary--",
+            // headers with a bare CR and padding after the boundary
+            b"--boundary  \r\nContent-Disposition: form-data; name=\"a\r\"\r\n\r\nvalue\r\n--boundary--",
+        ];
+
+        let dir = ::tempfile::tempdir().unwrap();
+
+        for (idx, body) in bodies.iter().enumerate() {
+            // in memory, then in a file
+            for &threshold in &[1024, 0] {
+                let mut multipart = Multipart::with_body(*body, "boundary");
+                multipart.capture_raw(&SaveBuilder::config().memory_threshold(threshold).temp_parent(dir.path()));
+
+                // however the parser reads the body, the copy is exact
+                let _ = multipart.save().temp();
+
+                let raw = multipart.raw_body().unwrap().unwrap();
+                assert_eq!(raw.is_file(), threshold == 0, "{}", idx);
+
+                let mut copy = Vec::new();
+                raw.readable().unwrap().read_to_end(&mut copy).unwrap();
+                assert_eq!(hash(&copy), hash(body), "{}: {:?}", idx, String::from_utf8_lossy(&copy));
+
+                // no more fields, and nothing more to copy
+                assert!(multipart.read_entry_mut().unwrap_opt().is_none(), "{}", idx);
+                assert!(multipart.raw_body().unwrap().is_none(), "{}", idx);
+            }
+        }
+
+        // nothing is captured without `capture_raw()`
+        let mut multipart = Multipart::with_body(bodies[0], "boundary");
+        assert!(multipart.raw_body().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_empty_files() {
+        ::init_log();
+
+        for backend in file_backends() {
+            let body = b"--boundary\r\n\
+                         Content-Disposition: form-data; name=\"placeholder\"; filename=\"empty.txt\"\r\n\
+                         Content-Type: text/plain\r\n\r\n\
+                         \r\n--boundary\r\n\
+                         Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                         \r\n--boundary\r\n\
+                         Content-Disposition: form-data; name=\"upload\"; filename=\"a.bin\"\r\n\r\n\
+                         data\r\n--boundary--\r\n";
+
+            for &threshold in &[0, super::DEFAULT_MEMORY_THRESHOLD] {
+                let entries = Multipart::with_body(&body[..], "boundary").save()
+                    .memory_threshold(threshold)
+                    .file_backend(backend)
+                    .temp()
+                    .into_result_strict()
+                    .unwrap();
+
+                let placeholder = &entries.fields["placeholder"][0];
+                assert!(placeholder.is_file());
+                assert!(!entries.fields["text"][0].is_file());
+                assert!(entries.fields["upload"][0].is_file());
+
+                let empty: Vec<_> = entries.empty_files().map(|field| &*field.headers.name).collect();
+                assert_eq!(empty, ["placeholder"]);
+
+                // with no threshold the empty file is on disk, like any other
+                if threshold == 0 {
+                    let path = placeholder.as_path().expect("expected a file");
+                    assert_eq!(fs::metadata(path).unwrap().len(), 0);
+                    assert_eq!(placeholder.data.size(), 0);
+                    assert!(entries.fields["text"][0].data.is_file());
+                } else {
+                    assert_eq!(placeholder.as_bytes(), Some(&b""[..]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_field_to() {
+        ::init_log();
+
+        let upload = vec![b'x'; 100_000];
+        let mut body = Vec::new();
+        body.extend_from_slice(b"--boundary\r\n\
+                                 Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+                                 hello\r\n--boundary\r\n\
+                                 Content-Disposition: form-data; name=\"upload\"; filename=\"big.bin\"\r\n\r\n");
+        body.extend_from_slice(&upload);
+        body.extend_from_slice(b"\r\n--boundary\r\n\
+                                 Content-Disposition: form-data; name=\"tag\"\r\n\r\n\
+                                 test\r\n--boundary--\r\n");
+
+        let mut streamed = Vec::new();
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .stream_field_to("upload", &mut streamed)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(streamed, upload);
+        assert!(!entries.streamed_field_missing);
+        assert_eq!(entries.fields["title"][0].as_text(), Some("hello"));
+        assert_eq!(entries.fields["tag"][0].as_text(), Some("test"));
+
+        let field = &entries.fields["upload"][0];
+        assert_eq!(field.headers.filename, Some("big.bin".into()));
+        assert_eq!(field.data.size(), upload.len() as u64);
+        assert!(!field.data.is_memory() && !field.data.is_file());
+        assert!(field.data.readable().is_err());
+
+        // the size limit applies to the streamed field
+        let mut streamed = Vec::new();
+        match Multipart::with_body(&body[..], "boundary").save()
+            .size_limit(1000)
+            .stream_field_to("upload", &mut streamed) {
+            SaveResult::Partial(partial, PartialReason::SizeLimit { written, .. }) => {
+                assert_eq!(written, 1000);
+                assert_eq!(partial.entries.fields["title"][0].as_text(), Some("hello"));
+            },
+            _ => panic!("expected a size limit"),
+        }
+        assert_eq!(streamed.len(), 1000);
+
+        // a missing field is only reported
+        let mut streamed = Vec::new();
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .stream_field_to("missing", &mut streamed)
+            .into_result_strict()
+            .unwrap();
+
+        assert!(entries.streamed_field_missing && streamed.is_empty());
+        assert_eq!(entries.fields["upload"][0].data.size(), upload.len() as u64);
+    }
+
+    #[test]
+    fn test_truncated_names() {
+        ::init_log();
+
+        let prefix = "x".repeat(1000);
+        let mut body = Vec::new();
+
+        for suffix in &["a", "b"] {
+            write!(body, "--boundary\r\n\
+                          Content-Disposition: form-data; name=\"{}{}\"\r\n\r\n\
+                          {}\r\n", prefix, suffix, suffix).unwrap();
+        }
+        body.extend_from_slice(b"--boundary--\r\n");
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(64)
+            .in_memory()
+            .into_result_strict()
+            .unwrap();
+
+        // names which only differ past the limit are still different keys
+        assert_eq!(entries.fields.len(), 2);
+
+        for (name, fields) in &entries.fields {
+            assert!(name.len() <= 256);
+            assert!(fields[0].headers.name_truncated);
+            assert_eq!(fields[0].headers.name, *name);
+        }
+    }
+
+    /// The memory prefix and the rest of a field read after it add up to one `File`.
+    #[test]
+    fn test_threshold_boundaries() {
+        ::init_log();
+
+        for backend in file_backends() {
+            const THRESHOLD: usize = 1024;
+
+            // (a) fits in memory, (b) over by one byte, (c) over by many buffers
+            for &len in &[THRESHOLD, THRESHOLD + 1, THRESHOLD * 200 + 7] {
+                for &filename in &["; filename=\"data.bin\"", ""] {
+                    let data: Vec<u8> = (0..len).map(|i| b'a' + (i % 26) as u8).collect();
+                    let mut body = Vec::new();
+                    write!(body, "--boundary\r\n\
+                                  Content-Disposition: form-data; name=\"field\"{}\r\n\r\n", filename).unwrap();
+                    body.extend_from_slice(&data);
+                    body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+                    let entries = Multipart::with_body(&body[..], "boundary").save()
+                        .memory_threshold(THRESHOLD as u64)
+                        .file_backend(backend)
+                        .temp()
+                        .into_result_strict()
+                        .unwrap();
+
+                    let saved = &entries.fields["field"][0].data;
+                    assert_eq!(saved.size(), len as u64, "{} {:?}", len, filename);
+
+                    match *saved {
+                        SavedData::File(ref path, size) => {
+                            assert!(len > THRESHOLD, "{} {:?}", len, filename);
+                            assert_eq!(size, FileSize::new(len as u64));
+                            assert_eq!(fs::read(path).unwrap(), data);
+                        },
+                        ref other => {
+                            assert_eq!(len, THRESHOLD, "{}: {:?}", filename, other);
+                            assert_eq!(entries.fields["field"][0].as_bytes(), Some(&data[..]));
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_in_memory() {
+        ::init_log();
+
+        let body = small_fields_body(3, 64);
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(64)
+            .in_memory()
+            .into_result_strict()
+            .unwrap();
+
+        assert!(matches!(entries.save_dir, SaveDir::Memory));
+        assert_eq!(entries.fields_count(), 3);
+        assert!(entries.fields.values().all(|fields| fields[0].as_bytes().map(<[u8]>::len) == Some(64)));
+        assert!(entries.summary().to_string().starts_with("3 fields in memory"));
+
+        // the limit is hit instead of spilling to the filesystem
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+        match multipart.save().memory_threshold(32).in_memory() {
+            SaveResult::Partial(partial, PartialReason::MemoryLimit { .. }) => {
+                assert!(partial.entries.is_empty());
+                let field = partial.partial.unwrap();
+                assert_eq!(&*field.source.headers.name, "field0");
+                // the rest of the field is left unread
+                let mut rest = Vec::new();
+                let mut data = field.source.data;
+                data.read_to_end(&mut rest).unwrap();
+                assert_eq!(field.dest.unwrap().size() + rest.len() as u64, 64);
+            },
+            other => panic!("expected memory limit, got {:?}", other.into_opt_both()),
+        }
+
+        // but the size limit still applies as usual
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+        match multipart.save().memory_threshold(32).size_limit(32).in_memory() {
+            SaveResult::Partial(_, PartialReason::SizeLimit { .. }) => (),
+            other => panic!("expected size limit, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_keep_files() {
+        ::init_log();
+
+        for backend in file_backends() {
+            let body = small_fields_body(2, 64);
+            let save = || SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+                .memory_threshold(0)
+                .file_backend(backend);
+
+            // temporary: kept once, then left alone
+            let mut entries = save().temp().into_result_strict().unwrap();
+            let dir = entries.files_dir().unwrap().to_path_buf();
+            assert!(format!("{:?}", entries.save_dir).starts_with("Temp("));
+
+            entries.keep_files();
+            entries.keep_files();
+            assert!(!entries.save_dir.is_temporary());
+            assert_eq!(entries.files_dir(), Some(&*dir));
+            assert!(format!("{:?}", entries.save_dir).starts_with("Perm("));
+
+            drop(entries);
+            assert!(dir.exists());
+            fs::remove_dir_all(&dir).unwrap();
+
+            let (entries, kept) = save().temp().into_result_strict().unwrap().into_kept();
+            let kept = kept.unwrap();
+            assert_eq!(entries.files_dir(), Some(&*kept));
+            drop(entries);
+            assert_eq!(fs::read_dir(&kept).unwrap().count(), 2);
+            fs::remove_dir_all(&kept).unwrap();
+
+            // permanent: unchanged
+            let perm = ::tempfile::tempdir().unwrap();
+            let (entries, kept) = save().with_dir(perm.path()).into_result_strict().unwrap().into_kept();
+            assert_eq!(kept, Some(perm.path().to_path_buf()));
+            assert_eq!(entries.files_dir(), Some(perm.path()));
+
+            // memory: no directory at all
+            let mut entries = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+                .in_memory().into_result_strict().unwrap();
+            assert_eq!(entries.files_dir(), None);
+            entries.keep_files();
+            assert!(matches!(entries.save_dir, SaveDir::Memory));
+            assert_eq!(entries.into_kept().1, None);
+        }
+    }
+
+    #[test]
+    fn test_shared_root() {
+        use std::collections::HashSet;
+        use std::thread;
+
+        ::init_log();
+
+        let root = SharedSaveRoot::temp().unwrap();
+        let root_path = root.as_path().to_owned();
+
+        let threads: Vec<_> = (0 .. 8).map(|_| {
+            let root = root.clone();
+            thread::spawn(move || {
+                let body = small_fields_body(4, 32);
+                Multipart::with_body(&body[..], "boundary").save()
+                    .memory_threshold(0)
+                    .with_shared_root(&root)
+                    .into_result_strict()
+                    .unwrap()
+            })
+        }).collect();
+
+        let mut all_entries: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+        let mut paths = HashSet::new();
+
+        for entries in &all_entries {
+            let sub = entries.save_dir.as_path();
+            assert_eq!(sub.parent(), Some(&*root_path));
+            assert!(!entries.save_dir.is_temporary());
+
+            for field in entries.fields.values().flatten() {
+                let path = field.as_path().unwrap();
+                assert!(path.starts_with(sub));
+                assert!(paths.insert(path.to_owned()), "duplicate path {:?}", path);
+            }
+        }
+
+        assert_eq!(paths.len(), 8 * 4);
+
+        // deleting one request's files leaves the others alone
+        let deleted = all_entries.pop().unwrap();
+        let deleted_sub = deleted.save_dir.as_path().to_owned();
+        deleted.save_dir.delete().unwrap();
+        assert!(!deleted_sub.exists());
+
+        for entries in &all_entries {
+            assert!(entries.fields.values().flatten()
+                        .all(|field| field.as_path().unwrap().exists()));
+        }
+
+        // the root is kept alive by the entries
+        drop(root);
+        assert!(root_path.exists());
+
+        drop(all_entries);
+        assert!(!root_path.exists());
+    }
+
+    #[test]
+    fn test_size_limit_boundary() {
+        ::init_log();
+
+        for backend in file_backends() {
+            let dir = ::tempfile::tempdir().unwrap();
+
+            for &limit in &[0u64, 1, 20_000] {
+                for size in limit.saturating_sub(2) ..= limit + 2 {
+                    let data: Vec<u8> = (0 .. size).map(|i| b'a' + (i % 26) as u8).collect();
+
+                    let mut body = b"--boundary\r\n\
+                                     Content-Disposition: form-data; name=\"field\"\r\n\r\n".to_vec();
+                    body.extend_from_slice(&data);
+                    body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+                    // in memory, on disk, and split between the two
+                    for &threshold in &[u64::MAX, 0, limit / 2] {
+                        let mut multipart = Multipart::with_body(&body[..], "boundary");
+                        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+                        let res = field.data.save()
+                            .size_limit(limit)
+                            .memory_threshold(threshold)
+                            .file_backend(backend)
+                            .with_dir(dir.path());
+
+                        let (saved, full) = match res {
+                            SaveResult::Full(saved) => (saved, true),
+                            SaveResult::Partial(saved, PartialReason::SizeLimit { .. }) => (saved, false),
+                            other => panic!("limit: {}, size: {}, threshold: {}: unexpected result {:?}",
+                                            limit, size, threshold, other.into_opt_both()),
+                        };
+
+                        let mut contents = Vec::new();
+                        saved.readable().unwrap().read_to_end(&mut contents).unwrap();
+
+                        let expected = cmp::min(size, limit);
+                        assert_eq!(full, size <= limit, "limit: {}, size: {}, threshold: {}",
+                                   limit, size, threshold);
+                        assert_eq!(saved.size(), expected, "limit: {}, size: {}, threshold: {}",
+                                   limit, size, threshold);
+                        assert_eq!(contents, &data[..expected as usize]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_size_limit_split_char() {
+        ::init_log();
+
+        // the limit falls in the middle of `é`
+        let body = "--boundary\r\n\
+                    Content-Disposition: form-data; name=\"field\"\r\n\r\n\
+                    abcdé\r\n\
+                    --boundary--\r\n".as_bytes();
+
+        let mut multipart = Multipart::with_body(body, "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        match field.data.save().size_limit(5).force_text().temp() {
+            SaveResult::Partial(saved, PartialReason::SizeLimit { .. }) =>
+                assert_eq!(saved.size(), 5),
+            other => panic!("expected size limit, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    fn text_field_body(text: &str) -> Vec<u8> {
+        format!("--boundary\r\n\
+                 Content-Disposition: form-data; name=\"field\"\r\n\r\n\
+                 {}\r\n\
+                 --boundary--\r\n", text).into_bytes()
+    }
+
+    #[test]
+    fn test_text_noncharacters() {
+        ::init_log();
+
+        for &text in &["x\u{FFFE}y", "\u{FDD0}", "end\u{10FFFF}"] {
+            let body = text_field_body(text);
+            let save = || SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+                .text_normalization(TextNorm::RejectNoncharacters);
+
+            match save().force_text().temp() {
+                SaveResult::Partial(_, PartialReason::Noncharacter(c)) =>
+                    assert_eq!(Some(c), text.chars().find(|&c| super::is_noncharacter(c))),
+                other => panic!("expected a noncharacter, got {:?}", other.into_opt_both()),
+            }
+
+            // like invalid UTF-8, saved as bytes
+            let entries = save().temp().into_result_strict().unwrap();
+            match entries.fields["field"][0].data {
+                SavedData::Bytes(ref bytes) => assert_eq!(&**bytes, text.as_bytes()),
+                ref other => panic!("expected bytes, got {:?}", other),
+            }
+
+            let entries = Multipart::with_body(&body[..], "boundary").save().force_text().temp()
+                .into_result_strict().unwrap();
+            assert_eq!(entries.fields["field"][0].as_text(), Some(text));
+        }
+
+        for &c in &['\u{FFFD}', '\u{FDCF}', '\u{FDF0}', '\u{1FFFD}'] {
+            assert!(!super::is_noncharacter(c), "{:?}", c);
+        }
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_text_nfc() {
+        ::init_log();
+
+        for &(text, nfc) in &[("cafe\u{301}", "caf\u{e9}"), ("caf\u{e9}", "caf\u{e9}"), ("plain", "plain")] {
+            let body = text_field_body(text);
+
+            let entries = Multipart::with_body(&body[..], "boundary").save()
+                .text_normalization(TextNorm::Nfc)
+                .temp()
+                .into_result_strict()
+                .unwrap();
+            assert_eq!(entries.fields["field"][0].as_text(), Some(nfc));
+
+            let entries = Multipart::with_body(&body[..], "boundary").save().temp()
+                .into_result_strict().unwrap();
+            assert_eq!(entries.fields["field"][0].as_text(), Some(text));
+        }
+    }
+
+    #[test]
+    fn test_normalize_newlines() {
+        ::init_log();
+
+        let data = "a,b\r\nc,d\ne,f\rg,h\r\r\n";
+        let body = format!("--boundary\r\n\
+                            Content-Disposition: form-data; name=\"csv\"\r\n\r\n\
+                            {data}\r\n\
+                            --boundary\r\n\
+                            Content-Disposition: form-data; name=\"bin\"\r\n\
+                            Content-Type: application/octet-stream\r\n\r\n\
+                            {data}\r\n\
+                            --boundary--\r\n", data = data).into_bytes();
+
+        let expected = [(NewlinePolicy::Preserve, data), (NewlinePolicy::Lf, "a,b\nc,d\ne,f\ng,h\n\n"),
+                        (NewlinePolicy::CrLf, "a,b\r\nc,d\r\ne,f\r\ng,h\r\n\r\n")];
+
+        // short reads split `\r\n` pairs between buffers
+        for &chunk in &[1, 2, 3, 5, 4096] {
+            for &(policy, text) in &expected {
+                for &threshold in &[u64::MAX, 0] {
+                    let reader = FaultyReader::new(&body[..], vec![Fault::Short(chunk); body.len()]);
+                    let entries = Multipart::with_body(reader, "boundary").save()
+                        .normalize_newlines(policy)
+                        .memory_threshold(threshold)
+                        .temp()
+                        .into_result_strict()
+                        .unwrap();
+
+                    let csv = &entries.fields["csv"][0].data;
+                    assert_eq!(read_data(csv), text.as_bytes(), "{:?} in chunks of {}", policy, chunk);
+                    assert_eq!(csv.size(), text.len() as u64);
+                    assert_eq!(read_data(&entries.fields["bin"][0].data), data.as_bytes());
+                }
+            }
+        }
+
+        // the size limit counts the converted bytes
+        let crlf_len = expected[2].1.len() as u64;
+
+        for &(limit, full) in &[(crlf_len, true), (crlf_len - 1, false)] {
+            let multipart = Multipart::with_body(&body[..], "boundary");
+            let mut field = multipart.read_entry().unwrap();
+
+            let res = field.data.save().normalize_newlines(NewlinePolicy::CrLf).size_limit(limit)
+                .write_to(Vec::new());
+
+            match res {
+                SaveResult::Full(written) if full => assert_eq!(written, crlf_len),
+                SaveResult::Partial(written, PartialReason::SizeLimit { .. }) if !full =>
+                    assert_eq!(written, limit),
+                other => panic!("unexpected result for limit {}: {:?}", limit, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_partial_reason_details() {
+        ::init_log();
+
+        let body = small_fields_body(3, 3000);
+
+        // the limit falls in the second field
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+        match multipart.save().size_limit(2048).max_parts(3).temp() {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(partial.entries.fields_count(), 0);
+                assert_eq!(reason.suggested_status(), 413);
+                assert_eq!(reason.to_string(),
+                           "field `field0` exceeded the size limit of 2 KiB (saved 2 KiB)");
+
+                match reason {
+                    PartialReason::SizeLimit { field, limit, written } => {
+                        assert_eq!(&*field, "field0");
+                        assert_eq!(limit, 2048);
+                        assert_eq!(written, 2048);
+                        assert_eq!(partial.partial.unwrap().dest.unwrap().size(), written);
+                    },
+                    other => panic!("expected size limit, got {:?}", other),
+                }
+            },
+            other => panic!("expected size limit, got {:?}", other.into_opt_both()),
+        }
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+        match multipart.save().max_parts(2).temp() {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(partial.entries.fields_count(), 2);
+                assert_eq!(reason.to_string(), "the request reached the limit of 2 fields");
+                assert!(matches!(reason, PartialReason::CountLimit { limit: 2 }));
+            },
+            other => panic!("expected count limit, got {:?}", other.into_opt_both()),
+        }
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+        match multipart.save().memory_threshold(1024).in_memory() {
+            SaveResult::Partial(partial, reason) => {
+                let written = partial.partial.unwrap().dest.unwrap().size();
+                assert_eq!(reason.to_string(), format!(
+                    "field `field0` exceeded the memory limit of 1 KiB (read {})", ByteSize(written)
+                ));
+
+                match reason {
+                    PartialReason::MemoryLimit { ref field, limit: 1024, written: w } =>
+                        assert_eq!((&**field, w), ("field0", written)),
+                    other => panic!("expected memory limit, got {:?}", other),
+                }
+            },
+            other => panic!("expected memory limit, got {:?}", other.into_opt_both()),
+        }
+
+        // a single field has no name to report
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        match field.data.save().size_limit(100).write_to(Vec::new()) {
+            SaveResult::Partial(100, reason) => assert_eq!(
+                reason.to_string(), "field exceeded the size limit of 100 bytes (saved 100 bytes)"
+            ),
+            other => panic!("expected size limit, got {:?}", other.into_opt_both()),
+        }
+
+        let reason = PartialReason::from(io::Error::new(io::ErrorKind::UnexpectedEof, "eof"));
+        assert_eq!(reason.suggested_status(), 400);
+        let reason = PartialReason::from(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        assert_eq!(reason.suggested_status(), 500);
+    }
+
+    fn file_path(data: &SavedData) -> Option<PathBuf> {
+        match *data {
+            SavedData::File(ref path, _) => Some(path.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_discard_partial() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                     hello\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n\
+                     0123456789012345678901234567890123456789\r\n\
+                     --boundary--\r\n";
+
+        for &memory_threshold in &[0, 1024] {
+            let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+            let partial = match multipart.save().size_limit(32).memory_threshold(memory_threshold).temp() {
+                SaveResult::Partial(partial, PartialReason::SizeLimit { .. }) => partial,
+                other => panic!("expected size limit, got {:?}", other.into_opt_both()),
+            };
+
+            let path = partial.partial.as_ref().and_then(|partial| partial.dest.as_ref())
+                .and_then(file_path);
+            assert_eq!(path.is_some(), memory_threshold == 0);
+
+            let (entries, res) = partial.discard_partial();
+            res.unwrap();
+
+            if let Some(ref path) = path {
+                assert!(!path.exists(), "{:?}", path);
+            }
+
+            // the other entries and their files are untouched
+            assert_eq!(entries.fields_count(), 1);
+            assert_eq!(read_data(&entries.fields["text"][0].data), b"hello");
+            assert!(entries.save_dir.as_path().exists());
+        }
+
+        // a single field
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        let field = multipart.into_entry().unwrap_opt().unwrap();
+        let mut partial = PartialSavedField { source: field, dest: None };
+        let dir = ::tempfile::tempdir().unwrap();
+
+        match partial.source.data.save().size_limit(32).memory_threshold(0).with_dir(dir.path()) {
+            SaveResult::Partial(data, _) => partial.dest = Some(data),
+            other => panic!("expected size limit, got {:?}", other),
+        }
+
+        let path = partial.dest.as_ref().and_then(file_path).unwrap();
+        assert!(path.exists());
+        assert!(partial.delete_dest().unwrap());
+        assert!(!path.exists() && partial.dest.is_none());
+
+        // nothing left to delete
+        assert!(!partial.delete_dest().unwrap());
+
+        partial.dest = Some(SavedData::Bytes(b"in memory".to_vec().into()));
+        assert!(!partial.delete_dest().unwrap());
+        assert!(partial.dest.is_some());
+    }
+
+    #[test]
+    fn test_byte_size() {
+        let cases: &[(u64, &str)] = &[
+            (0, "0 bytes"), (1, "1 byte"), (1023, "1023 bytes"), (1024, "1 KiB"),
+            (1536, "1.5 KiB"), (1024 * 1024 - 1, "1023.9 KiB"), (100 * 1024 * 1024, "100 MiB"),
+            (5 * 1024 * 1024 * 1024 + 1, "5 GiB"), (u64::MAX, "15.9 EiB"),
+        ];
+
+        for &(size, expected) in cases {
+            assert_eq!(ByteSize(size).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_size_str() {
+        let cases: &[(&str, Option<u64>)] = &[
+            ("0", Some(0)), ("4096", Some(4096)), (" 12 ", Some(12)), ("7B", Some(7)),
+            ("16MiB", Some(16 << 20)), ("16 mib", Some(16 << 20)), ("16MIB", Some(16 << 20)),
+            ("2KB", Some(2000)), ("2kib", Some(2048)), ("3 MB", Some(3_000_000)),
+            ("1GiB", Some(1 << 30)), ("1gb", Some(1_000_000_000)), ("2TiB", Some(2 << 40)),
+            ("1.5KiB", Some(1536)), ("0.5 MB", Some(500_000)), ("1.0001KiB", Some(1024)),
+            ("max", Some(u64::MAX)), ("MAX", Some(u64::MAX)),
+            ("18446744073709551615", Some(u64::MAX)), ("18446744073709551616", None),
+            ("17000000TiB", None), ("", None), ("MiB", None), ("-1", None), ("1.", None),
+            (".5KiB", None), ("1.2.3", None), ("16 MiBs", None), ("16M", None), ("1 0", None),
+        ];
+
+        for &(size, expected) in cases {
+            assert_eq!(super::parse_size_str(size), expected, "{:?}", size);
+        }
+    }
+
+    #[test]
+    fn test_memory_threshold_from_env() {
+        use std::env;
+
+        let threshold = |var| SaveBuilderConfig::config().memory_threshold(1).memory_threshold_from_env(var)
+            .opts.memory_threshold;
+
+        env::set_var("MULTIPART_TEST_THRESHOLD_VALID", "16MiB");
+        assert_eq!(threshold("MULTIPART_TEST_THRESHOLD_VALID"), 16 << 20);
+
+        env::set_var("MULTIPART_TEST_THRESHOLD_ZERO", "0");
+        assert_eq!(threshold("MULTIPART_TEST_THRESHOLD_ZERO"), 0);
+
+        env::set_var("MULTIPART_TEST_THRESHOLD_INVALID", "lots");
+        assert_eq!(threshold("MULTIPART_TEST_THRESHOLD_INVALID"), super::DEFAULT_MEMORY_THRESHOLD);
+
+        env::remove_var("MULTIPART_TEST_THRESHOLD_UNSET");
+        assert_eq!(threshold("MULTIPART_TEST_THRESHOLD_UNSET"), super::DEFAULT_MEMORY_THRESHOLD);
+    }
+
+    #[test]
+    fn test_max_open_files() {
+        ::init_log();
+
+        let body = small_fields_body(64, 100);
+
+        // every file is closed before the next is opened, chunks included
+        for &chunk_size in &[u64::MAX, 16] {
+            let entries = Multipart::with_body(&body[..], "boundary").save()
+                .memory_threshold(0)
+                .chunk_files(chunk_size)
+                .max_open_files(1)
+                .temp()
+                .into_result_strict()
+                .unwrap();
+
+            assert_eq!(entries.fields_count(), 64);
+            assert_eq!(entries.stats.peak_open_files, 1);
+        }
+
+        // no files are opened for fields kept in memory
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .max_open_files(0)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 64);
+        assert_eq!(entries.stats.peak_open_files, 0);
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+        match multipart.save().memory_threshold(0).max_open_files(0).temp() {
+            SaveResult::Partial(partial, PartialReason::IoError(e)) => {
+                assert!(partial.entries.is_empty());
+                assert_eq!(partial.entries.stats.peak_open_files, 0);
+                assert!(e.to_string().contains("max_open_files(0)"), "{}", e);
+            },
+            other => panic!("expected I/O error, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_name_collisions() {
+        ::init_log();
+
+        let body = small_fields_body(1, 100);
+        let dir = ::tempfile::tempdir().unwrap();
+
+        // take the first two names the seeded generator will give
+        ::seed_random_names(Some(42));
+
+        for _ in 0 .. 2 {
+            fs::File::create(dir.path().join(super::rand_filename())).unwrap();
+        }
+
+        ::seed_random_names(Some(42));
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .with_dir(dir.path())
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 1);
+        assert_eq!(entries.stats.name_collisions, 2);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        // every name is taken
+        ::seed_random_names(Some(7));
+
+        for _ in 0 .. super::NAME_ATTEMPTS {
+            fs::File::create(dir.path().join(super::rand_filename())).unwrap();
+        }
+
+        ::seed_random_names(Some(7));
+
+        match Multipart::with_body(&body[..], "boundary").save().memory_threshold(0).with_dir(dir.path()) {
+            SaveResult::Partial(partial, PartialReason::IoError(e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+                assert_eq!(partial.entries.stats.name_collisions, super::NAME_ATTEMPTS - 1);
+            },
+            other => panic!("expected I/O error, got {:?}", other.into_opt_both()),
+        }
+
+        // an explicit path is never replaced
+        ::seed_random_names(None);
+
+        let path = dir.path().join("taken");
+        fs::File::create(&path).unwrap();
+
+        match save_to_path(&path, 0, |_| ()) {
+            SaveResult::Error(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+            other => panic!("expected `AlreadyExists`, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_name_prefix() {
+        ::init_log();
+
+        let body = small_fields_body(3, 100);
+        let dir = ::tempfile::tempdir().unwrap();
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .name_prefix("worker-7-")
+            .with_dir(dir.path())
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 3);
+        assert_eq!(entries.stats.name_collisions, 0);
+
+        for field in entries.fields.values().flatten() {
+            let name = field.as_path().unwrap().file_name().unwrap().to_str().unwrap();
+            assert!(name.starts_with("worker-7-"), "{}", name);
+            assert_eq!(name.len(), "worker-7-".len() + super::RANDOM_FILENAME_LEN);
+        }
+
+        // and for a single field
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+        let data = field.data.save().memory_threshold(0).name_prefix("solo-").with_dir(dir.path())
+            .into_result_strict().unwrap();
+
+        match data {
+            SavedData::File(ref path, _) => {
+                let name = path.file_name().unwrap().to_str().unwrap();
+                assert!(name.starts_with("solo-"), "{}", name);
+            },
+            other => panic!("expected a file, got {:?}", other),
+        }
+    }
+
+    /// A body with a file field named `file{i}` for each filename, with the data `data{i}`.
+    fn client_filenames_body(filenames: &[&str]) -> Vec<u8> {
+        let mut body = Vec::new();
+
+        for (i, filename) in filenames.iter().enumerate() {
+            write!(body, "--boundary\r\n\
+                          Content-Disposition: form-data; name=\"file{}\"; filename=\"{}\"\r\n\r\n\
+                          data{}\r\n", i, filename, i).unwrap();
+        }
+
+        body.extend_from_slice(b"--boundary--\r\n");
+        body
+    }
+
+    #[test]
+    fn test_use_client_filename() {
+        ::init_log();
+
+        let root = ::tempfile::tempdir().unwrap();
+        let dir = root.path().join("uploads");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("taken.txt"), "old").unwrap();
+
+        let filenames = ["../../etc/passwd", "C:\\\\windows\\\\foo", "photo.jpg", "PHOTO.jpg", "..", "taken.txt",
+                         "/etc/../../x/.htaccess"];
+        let body = client_filenames_body(&filenames);
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .use_client_filename(true)
+            .with_dir(&dir)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), filenames.len() as u32);
+        assert_eq!(entries.stats.name_collisions, 0);
+
+        let name_of = |i: usize| {
+            let path = entries.fields[&*format!("file{}", i)][0].as_path().unwrap();
+            assert_eq!(path.parent(), Some(&*dir));
+            assert_eq!(fs::read_to_string(path).unwrap(), format!("data{}", i));
+            path.file_name().unwrap().to_str().unwrap().to_string()
+        };
+
+        assert_eq!(name_of(0), "passwd");
+        assert_eq!(name_of(1), "foo");
+        assert_eq!(name_of(2), "photo.jpg");
+        assert_eq!(name_of(6), "htaccess");
+
+        // nothing usable, or taken: a random name
+        for i in 4 ..= 5 {
+            assert_eq!(name_of(i).len(), super::RANDOM_FILENAME_LEN, "{}", i);
+        }
+        assert_eq!(fs::read_to_string(dir.join("taken.txt")).unwrap(), "old");
+
+        // may be taken, depending on whether the filesystem ignores case
+        let name = name_of(3);
+        assert!(name == "PHOTO.jpg" || name.len() == super::RANDOM_FILENAME_LEN, "{}", name);
+
+        // nothing escaped the directory
+        let outside: Vec<_> = fs::read_dir(root.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(outside, ["uploads"]);
+
+        // random names by default
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        for field in entries.fields.values().flatten() {
+            assert_eq!(field.as_path().unwrap().file_name().unwrap().len(), super::RANDOM_FILENAME_LEN);
+        }
+    }
+
+    #[test]
+    fn test_use_client_filename_transactional() {
+        ::init_log();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let body = client_filenames_body(&["new.txt", "taken.txt"]);
+
+        // free in the staging directory, but taken in the target directory
+        fs::write(dir.path().join("taken.txt"), "old").unwrap();
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .use_client_filename(true)
+            .transactional(true)
+            .with_dir(dir.path())
+            .into_result_strict()
+            .unwrap();
+
+        let new = entries.fields["file0"][0].as_path().unwrap();
+        assert_eq!(new, dir.path().join("new.txt"));
+
+        let renamed = entries.fields["file1"][0].as_path().unwrap();
+        assert_eq!(renamed.parent(), Some(dir.path()));
+        assert_eq!(renamed.file_name().unwrap().len(), super::RANDOM_FILENAME_LEN);
+        assert_eq!(fs::read_to_string(renamed).unwrap(), "data1");
+        assert_eq!(fs::read_to_string(dir.path().join("taken.txt")).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_percent_decode_names() {
+        ::init_log();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"user%5Bemail%5D\"\r\n\r\n\
+                     a@example.com\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"100%\"\r\n\r\n\
+                     cotton\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"user%5Bcv%5D\"; filename=\"r%C3%A9sum%C3%A9.txt\"\r\n\r\n\
+                     cv\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"a%255B\"\r\n\r\n\
+                     twice\r\n\
+                     --boundary--\r\n";
+
+        let save = |decode_in_parser: bool| {
+            let mut multipart = Multipart::with_body(&body[..], "boundary");
+            multipart.percent_decode_names(decode_in_parser);
+
+            multipart.save()
+                .memory_threshold(0)
+                .percent_decode_names(true)
+                .use_client_filename(true)
+                .with_dir(dir.path())
+                .into_result_strict()
+                .unwrap()
+        };
+
+        for &decode_in_parser in &[false, true] {
+            let entries = save(decode_in_parser);
+            assert_eq!(entries.fields_count(), 4);
+
+            let email = &entries.fields["user[email]"][0];
+            assert_eq!(email.headers.name_raw(), Some("user%5Bemail%5D"));
+            assert_eq!(fs::read_to_string(email.as_path().unwrap()).unwrap(), "a@example.com");
+
+            let cotton = &entries.fields["100%"][0];
+            assert_eq!(cotton.headers.name_raw(), Some("100%"));
+
+            let cv = &entries.fields["user[cv]"][0];
+            assert_eq!(cv.headers.filename, Some("r\u{E9}sum\u{E9}.txt".into()));
+            assert_eq!(cv.headers.filename_raw(), Some("r%C3%A9sum%C3%A9.txt"));
+            assert_eq!(cv.as_path().unwrap(), dir.path().join("r\u{E9}sum\u{E9}.txt"));
+
+            // not decoded twice
+            let twice = &entries.fields["a%5B"][0];
+            assert_eq!(twice.headers.name_raw(), Some("a%255B"));
+
+            fs::remove_file(cv.as_path().unwrap()).unwrap();
+        }
+
+        // off by default
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .in_memory()
+            .into_result_strict()
+            .unwrap();
+
+        assert!(entries.fields.contains_key("user%5Bemail%5D"));
+        assert!(!entries.fields.contains_key("user[email]"));
+        assert_eq!(entries.fields["user%5Bcv%5D"][0].headers.filename, Some("r%C3%A9sum%C3%A9.txt".into()));
+    }
+
+    #[test]
+    fn test_with_file_namer() {
+        ::init_log();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let body = client_filenames_body(&["a.txt", "b.txt", "c.txt"]);
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            // the namer wins
+            .use_client_filename(true)
+            .with_file_namer(|headers, index| {
+                assert_eq!(&*headers.name, format!("file{}", index));
+                PathBuf::from(format!("req-1/{:03}.bin", index))
+            })
+            .with_dir(dir.path())
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 3);
+        assert_eq!(entries.stats.created_dirs, [dir.path().join("req-1")]);
+
+        for i in 0 .. 3 {
+            let path = entries.fields[&*format!("file{}", i)][0].as_path().unwrap();
+            assert_eq!(path, dir.path().join(format!("req-1/{:03}.bin", i)));
+            assert_eq!(fs::read_to_string(path).unwrap(), format!("data{}", i));
+        }
+
+        // resuming counts the fields already saved
+        let resumed = Multipart::with_body(&client_filenames_body(&["d.txt"])[..], "boundary").save()
+            .memory_threshold(0)
+            .with_file_namer(|_, index| PathBuf::from(format!("req-1/{:03}.bin", index)))
+            .with_entries(entries)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(resumed.fields["file0"].len(), 2);
+        assert_eq!(fs::read_to_string(dir.path().join("req-1/003.bin")).unwrap(), "data0");
+    }
+
+    #[test]
+    fn test_with_file_namer_errors() {
+        ::init_log();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("taken"), "old").unwrap();
+        let body = client_filenames_body(&["a.txt"]);
+
+        let save_err = |name: String, kind: io::ErrorKind| match Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            // not skipped like a field the request is at fault for
+            .on_field_error(FieldErrorPolicy::SkipAndContinue)
+            .with_file_namer(move |_, _| PathBuf::from(&name))
+            .with_dir(dir.path()) {
+            SaveResult::Error(ref e) if e.kind() == kind => (),
+            SaveResult::Full(entries) => panic!("unexpected result: {:?}", entries),
+            SaveResult::Partial(_, reason) => panic!("unexpected reason: {:?}", reason),
+            SaveResult::Error(e) => panic!("unexpected error: {:?}", e),
+        };
+
+        // never replaced with the default `OpenOptions`
+        save_err("taken".into(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(dir.path().join("taken")).unwrap(), "old");
+
+        let outside = dir.path().join("outside");
+
+        for name in &["../escaped", "a/../../escaped", outside.to_str().unwrap(), ""] {
+            save_err(name.to_string(), io::ErrorKind::InvalidInput);
+        }
+        assert!(!dir.path().parent().unwrap().join("escaped").exists());
+        assert!(!outside.exists());
+
+        // the `OpenOptions` may allow it
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .mod_open_opts(|opts| { opts.create_new(false).create(true).truncate(true); })
+            .with_file_namer(|_, _| PathBuf::from("taken"))
+            .with_dir(dir.path())
+            .into_result_strict()
+            .unwrap();
+        assert_eq!(entries.fields["file0"][0].as_path(), Some(&*dir.path().join("taken")));
+        assert_eq!(fs::read_to_string(dir.path().join("taken")).unwrap(), "data0");
+
+        match Multipart::with_body(&body[..], "boundary").save()
+            .with_file_namer(|_, _| PathBuf::from("staged"))
+            .transactional(true)
+            .with_dir(dir.path()) {
+            SaveResult::Error(ref e) if e.kind() == io::ErrorKind::InvalidInput => (),
+            SaveResult::Full(entries) => panic!("unexpected result: {:?}", entries),
+            SaveResult::Partial(_, reason) => panic!("unexpected reason: {:?}", reason),
+            SaveResult::Error(e) => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_with_file_namer_panic() {
+        ::init_log();
+
+        let body = client_filenames_body(&["a.txt", "b.txt", "c.txt"]);
+
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(0)
+            .with_file_namer(|_, index| {
+                assert!(index == 0, "no name for field {}", index);
+                PathBuf::from("first.bin")
+            })
+            .temp();
+
+        match res {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.suggested_status(), 500);
+                assert_eq!(reason.to_string(),
+                           "the callback passed to `with_file_namer()` panicked: no name for field 1");
+
+                // the temporary directory wasn't dropped, so the first file is still there
+                assert_eq!(partial.entries.fields_count(), 1);
+                let first = partial.entries.fields["file0"][0].as_path().unwrap();
+                assert_eq!(first, partial.entries.save_dir.as_path().join("first.bin"));
+                assert_eq!(fs::read_to_string(first).unwrap(), "data0");
+
+                let partial = partial.partial.unwrap();
+                assert_eq!(&*partial.source.headers.name, "file1");
+                assert!(partial.dest.is_none());
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_event_sink_panic() {
+        ::init_log();
+
+        let body = small_fields_body(3, 100);
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut parts = 0;
+
+        multipart.set_event_sink(move |event| if let ::server::ParseEvent::PartStart { .. } = event {
+            parts += 1;
+            assert!(parts < 2, "no room for part {}", parts);
+        });
+
+        match multipart.save().memory_threshold(0).temp() {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.to_string(), "the event sink panicked: no room for part 2");
+
+                assert_eq!(partial.entries.fields_count(), 1);
+                let path = partial.entries.fields["field0"][0].as_path().unwrap();
+                assert_eq!(fs::metadata(path).unwrap().len(), 100);
+                assert!(partial.partial.is_none());
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+
+        // the sink was dropped, and the request can't be read any further
+        match multipart.read_entry_mut().into_result() {
+            Err(e) => assert!(e.get_ref().unwrap().is::<::server::EventSinkPanicked>(), "{:?}", e),
+            Ok(_) => panic!("expected the sink's panic"),
+        }
+    }
+
+    #[test]
+    fn test_temp_parent() {
+        ::init_log();
+
+        let body = small_fields_body(2, 100);
+        let outer = ::tempfile::tempdir().unwrap();
+        let parent = outer.path().join("scratch");
+        fs::create_dir(&parent).unwrap();
+
+        for &with_builder in &[false, true] {
+            let builder = SaveBuilder::new(Multipart::with_body(&body[..], "boundary")).memory_threshold(0);
+
+            let entries = if with_builder {
+                builder.temp_parent(&parent).temp()
+            } else {
+                builder.temp_in_with_prefix(&parent, "upload")
+            }.into_result_strict().unwrap();
+
+            let dir = entries.save_dir.as_path().to_path_buf();
+            assert!(entries.save_dir.is_temporary());
+            assert_eq!(dir.parent(), Some(&*parent));
+            assert_eq!(dir.file_name().unwrap().to_str().unwrap().starts_with("upload"), !with_builder);
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+            drop(entries);
+            assert!(!dir.exists());
+        }
+
+        assert_eq!(fs::read_dir(&parent).unwrap().count(), 0);
+
+        // the parent isn't created
+        match Multipart::with_body(&body[..], "boundary").save().temp_in(outer.path().join("missing")) {
+            SaveResult::Error(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            other => panic!("expected not found, got {:?}", other.into_opt_both()),
+        }
+
+        // a single field
+        let spool = SaveBuilderConfig::config().memory_threshold(0).temp_parent(&parent);
+        let field = Multipart::with_body(&body[..], "boundary").into_entry().unwrap_opt().unwrap();
+        let (owned, _) = field.into_owned(&spool).unwrap();
+
+        let path = owned.as_path().unwrap();
+        assert_eq!(path.parent(), Some(&*parent));
+        fs::remove_file(path).unwrap();
+    }
+
+    /// The deprecated re-export is still usable where a `tempfile::TempDir` is expected.
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_temp_dir() {
+        let dir: super::TempDir = ::tempfile::tempdir().unwrap();
+        let root = SharedSaveRoot::with_temp_dir(dir);
+        assert!(root.as_path().is_dir() && root.is_temporary());
+    }
+
+    #[test]
+    fn test_temp_fallbacks() {
+        ::init_log();
+
+        let body = small_fields_body(2, 100);
+        let outer = ::tempfile::tempdir().unwrap();
+
+        let missing = outer.path().join("missing");
+        let not_dir = outer.path().join("file");
+        fs::write(&not_dir, b"").unwrap();
+        let fallback = outer.path().join("fallback");
+        fs::create_dir(&fallback).unwrap();
+
+        let save = |parent: &Path, fallbacks: &[&Path]| {
+            SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+                .memory_threshold(0)
+                .temp_parent(parent)
+                .temp_fallbacks(fallbacks.iter().cloned())
+                .temp()
+        };
+
+        let entries = save(&missing, &[&not_dir, &fallback]).into_result_strict().unwrap();
+        assert_eq!(entries.save_dir.parent(), Some(&*fallback));
+        assert_eq!(entries.fields_count(), 2);
+        drop(entries);
+
+        // a read-only directory, unless we can write to it anyway (e.g. as root)
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let read_only = outer.path().join("read-only");
+            fs::create_dir(&read_only).unwrap();
+            fs::set_permissions(&read_only, fs::Permissions::from_mode(0o555)).unwrap();
+
+            if fs::File::create(read_only.join("probe")).is_err() {
+                let entries = save(&read_only, &[&fallback]).into_result_strict().unwrap();
+                assert_eq!(entries.save_dir.parent(), Some(&*fallback));
+            }
+
+            fs::set_permissions(&read_only, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        // the first directory is still used if it works
+        let entries = save(&fallback, &[&missing]).into_result_strict().unwrap();
+        assert_eq!(entries.save_dir.parent(), Some(&*fallback));
+        drop(entries);
+
+        let err = match save(&missing, &[&not_dir]) {
+            SaveResult::Error(e) => e,
+            other => panic!("expected an error, got {:?}", other.into_opt_both()),
+        };
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let message = err.to_string();
+        assert!(message.contains(&format!("{:?}", missing)) && message.contains(&format!("{:?}", not_dir)),
+                "{}", message);
+
+        let err: Box<TempDirError> = err.into_inner().unwrap().downcast().unwrap();
+        assert!(!err.os_temp_dir);
+        assert_eq!(err.attempts.iter().map(|a| &a.0).collect::<Vec<_>>(), [&missing, &not_dir]);
+
+        assert_eq!(SaveDir::Memory.parent(), None);
+    }
+
+    /// The error of a full disk: `ENOSPC` or `ERROR_DISK_FULL`.
+    fn storage_full() -> io::Error {
+        io::Error::from_raw_os_error(STORAGE_FULL_CODES[0])
+    }
+
+    /// Accepts `capacity` bytes, then fails like a full disk.
+    struct FullDisk {
+        written: usize,
+        capacity: usize,
+    }
+
+    impl Write for FullDisk {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written == self.capacity {
+                return Err(storage_full());
+            }
+
+            let len = cmp::min(buf.len(), self.capacity - self.written);
+            self.written += len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn test_insufficient_storage() {
+        ::init_log();
+
+        let body = small_fields_body(3, 100);
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        match field.data.save().write_to(FullDisk { written: 0, capacity: 40 }) {
+            SaveResult::Partial(40, ref reason @ PartialReason::InsufficientStorage { .. }) => {
+                assert_eq!(reason.suggested_status(), 507);
+                assert!(reason.to_string().starts_with("storage ran out"), "{}", reason);
+            },
+            other => panic!("expected insufficient storage, got {:?}", other),
+        }
+
+        // not skipped like other field errors, since the next field would fail the same way
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .on_field_error(FieldErrorPolicy::SkipAndContinue)
+            .stream_field_to("field1", FullDisk { written: 0, capacity: 0 });
+
+        match res {
+            SaveResult::Partial(partial, PartialReason::InsufficientStorage { phase, error }) => {
+                assert_eq!(phase, SavePhase::FieldWrite);
+                assert_eq!(error.raw_os_error(), Some(STORAGE_FULL_CODES[0]));
+                assert_eq!(partial.entries.fields_count(), 1);
+                assert!(partial.entries.failures.is_empty());
+            },
+            other => panic!("expected insufficient storage, got {:?}", other.into_opt_both()),
+        }
+
+        // the save directory couldn't be created, e.g. in any of `temp_fallbacks()`
+        let err = TempDirError {
+            attempts: vec![(PathBuf::from("/full"), storage_full())],
+            os_temp_dir: false,
+        };
+
+        match dir_setup_failed::<Multipart<&[u8]>>(io::Error::other(err)) {
+            SaveResult::Partial(partial, PartialReason::InsufficientStorage { phase, error }) => {
+                assert_eq!(phase, SavePhase::DirSetup);
+                assert!(error.get_ref().is_some_and(|e| e.is::<TempDirError>()));
+                assert_eq!(partial.entries.fields_count(), 0);
+                assert_eq!(partial.entries.save_dir.parent(), None);
+                assert!(partial.partial.is_none());
+            },
+            other => panic!("expected insufficient storage, got {:?}", other.into_opt_both()),
+        }
+
+        // as is an exceeded quota
+        for &code in STORAGE_FULL_CODES {
+            match dir_setup_failed::<Multipart<&[u8]>>(io::Error::from_raw_os_error(code)) {
+                SaveResult::Partial(_, PartialReason::InsufficientStorage { phase: SavePhase::DirSetup, .. }) => (),
+                other => panic!("expected insufficient storage for {}, got {:?}", code, other.into_opt_both()),
+            }
+        }
+
+        // other errors are unchanged
+        match dir_setup_failed::<Multipart<&[u8]>>(io::ErrorKind::PermissionDenied.into()) {
+            SaveResult::Error(e) => assert_eq!(e.kind(), io::ErrorKind::PermissionDenied),
+            other => panic!("expected an error, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    /// A real full disk: `/dev/full` fails every write with `ENOSPC`.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_insufficient_storage_dev_full() {
+        ::init_log();
+
+        let dev_full = match fs::OpenOptions::new().write(true).open("/dev/full") {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+
+        let body = small_fields_body(1, 100);
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        match field.data.save().write_to(dev_full) {
+            SaveResult::Partial(0, PartialReason::InsufficientStorage { phase: SavePhase::FieldWrite, error }) =>
+                assert_eq!(error.raw_os_error(), Some(28)),
+            other => panic!("expected insufficient storage, got {:?}", other),
+        }
+    }
+
+    thread_local! {
+        static MOCK_START: Instant = Instant::now();
+        static MOCK_ELAPSED: ::std::cell::Cell<Duration> = const { ::std::cell::Cell::new(Duration::from_secs(0)) };
+    }
+
+    /// A clock for `SaveBuilder::clock` which only moves with `mock_advance()`.
+    fn mock_now() -> Instant {
+        MOCK_START.with(|start| *start + MOCK_ELAPSED.with(|elapsed| elapsed.get()))
+    }
+
+    fn mock_advance(by: Duration) {
+        MOCK_ELAPSED.with(|elapsed| elapsed.set(elapsed.get() + by));
+    }
+
+    /// Takes `delay` on the mock clock for each read of up to `chunk` bytes.
+    struct MockSlowReader {
+        data: io::Cursor<Vec<u8>>,
+        chunk: usize,
+        delay: Duration,
+    }
+
+    impl MockSlowReader {
+        fn new(data: Vec<u8>, chunk: usize, delay_ms: u64) -> Self {
+            MockSlowReader { data: io::Cursor::new(data), chunk, delay: Duration::from_millis(delay_ms) }
+        }
+    }
+
+    impl Read for MockSlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = cmp::min(buf.len(), self.chunk);
+            let read = self.data.read(&mut buf[..len])?;
+
+            if read > 0 {
+                mock_advance(self.delay);
+            }
+
+            Ok(read)
+        }
+    }
+
+    fn assert_between(duration: Duration, min_ms: u64, max_ms: u64) {
+        assert!(duration >= Duration::from_millis(min_ms) && duration <= Duration::from_millis(max_ms),
+                "{:?} is not within {}ms ..= {}ms", duration, min_ms, max_ms);
+    }
+
+    #[test]
+    fn test_field_timing() {
+        use server::{Part, PartsSource};
+
+        ::init_log();
+
+        let part = |name, len, chunk, delay_ms| -> Part {
+            let reader = MockSlowReader::new(vec![b'x'; len], chunk, delay_ms);
+            (FieldHeaders::new(name), Box::new(io::BufReader::with_capacity(chunk, reader)))
+        };
+
+        // each field waits 5 seconds to start, which isn't counted
+        let parts = vec![part("slow", 10 * 1024, 1024, 100), part("fast", 10 * 1024, 10 * 1024, 10),
+                         part("empty", 0, 1024, 100)]
+            .into_iter()
+            .inspect(|_| mock_advance(Duration::from_secs(5)));
+
+        let mut source = PartsSource::new(parts);
+        let mut builder = source.save();
+        builder.opts.clock = mock_now;
+        let entries = builder.temp().into_result_strict().unwrap();
+
+        // about 10 reads of 1 KiB each; the first is buffered before the field is saved
+        let slow = &entries.fields["slow"][0];
+        let timing = slow.timing.unwrap();
+        assert_between(timing.first_byte.unwrap(), 100, 200);
+        assert_between(timing.last_byte, 1000, 1200);
+        assert_eq!(slow.read_duration(), Some(timing.last_byte));
+
+        let slow_throughput = slow.throughput_bytes_per_sec().unwrap();
+        assert!(slow_throughput > 8000.0 && slow_throughput <= 10240.0, "{}", slow_throughput);
+
+        let fast = &entries.fields["fast"][0];
+        assert_between(fast.read_duration().unwrap(), 10, 20);
+        let fast_throughput = fast.throughput_bytes_per_sec().unwrap();
+        assert!(fast_throughput > 500_000.0, "{}", fast_throughput);
+
+        let empty = &entries.fields["empty"][0];
+        assert_eq!(empty.timing.unwrap().first_byte, None);
+        assert_eq!(empty.read_duration(), Some(Duration::from_secs(0)));
+        assert_eq!(empty.throughput_bytes_per_sec(), None);
+
+        let stats = entries.stats.field_timing.unwrap();
+        assert_eq!(stats.fields, 3);
+        assert_eq!(stats.min_duration, Duration::from_secs(0));
+        assert_eq!(stats.max_duration, timing.last_byte);
+        assert_eq!(stats.mean_duration, (timing.last_byte + fast.read_duration().unwrap()) / 3);
+        assert_eq!(stats.min_throughput, Some(slow_throughput));
+        assert_eq!(stats.max_throughput, Some(fast_throughput));
+
+        let mean = stats.mean_throughput.unwrap();
+        assert!((mean - (slow_throughput + fast_throughput) / 2.0).abs() < 1e-6, "{}", mean);
+    }
+
+    #[test]
+    fn test_field_timing_multipart() {
+        ::init_log();
+
+        let mut body = b"--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\r\n".to_vec();
+        body.extend_from_slice(&[b'x'; 64 * 1024]);
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+        // about 64 reads of 1 KiB for the data; the exact number depends on the buffering
+        let mut multipart = Multipart::with_body(MockSlowReader::new(body.clone(), 1024, 10), "boundary");
+        let mut builder = multipart.save();
+        builder.opts.clock = mock_now;
+        let entries = builder.memory_threshold(0).temp().into_result_strict().unwrap();
+
+        let timing = entries.fields["file"][0].timing.unwrap();
+        assert_between(timing.first_byte.unwrap(), 0, 20);
+        assert_between(timing.last_byte, 560, 680);
+
+        // the same for a single field
+        let mut multipart = Multipart::with_body(MockSlowReader::new(body, 1024, 10), "boundary");
+        let field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        let mut spool = SaveBuilderConfig::config();
+        spool.opts.clock = mock_now;
+        let (owned, _) = field.into_owned(&spool).unwrap();
+        assert_between(owned.read_duration().unwrap(), 560, 680);
+        assert!(owned.throughput_bytes_per_sec().is_some());
+    }
+
+    fn redacted_body() -> Vec<u8> {
+        let mut body = Vec::new();
+
+        for &(name, filename, data) in &[
+            ("greeting", None, "hello"),
+            ("password", None, "hunter2-PLAINTEXT"),
+            ("ssn", None, "078-05-1120-PLAINTEXT"),
+            ("secret_token", None, "token-PLAINTEXT"),
+            ("card_pin", None, "1234-PLAINTEXT"),
+            ("secret_upload", Some("keys.txt"), "file-PLAINTEXT"),
+            ("upload", Some("a.txt"), "uploaded"),
+        ] {
+            body.extend_from_slice(b"--boundary\r\n");
+
+            match filename {
+                Some(filename) => body.extend_from_slice(format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\r\n", name, filename
+                ).as_bytes()),
+                None => body.extend_from_slice(format!(
+                    "Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name
+                ).as_bytes()),
+            }
+
+            body.extend_from_slice(data.as_bytes());
+            body.extend_from_slice(b"\r\n");
+        }
+
+        body.extend_from_slice(b"--boundary--\r\n");
+        body
+    }
+
+    fn sensitive_fields() -> FieldMatcher {
+        FieldMatcher::new().name("password").name("ssn").prefix("secret_").matching(|name| name.ends_with("_pin"))
+    }
+
+    #[test]
+    fn test_redact_fields() {
+        ::init_log();
+
+        let body = redacted_body();
+        let checked = Arc::new(::std::sync::Mutex::new(Vec::new()));
+        let checked_ = checked.clone();
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .redact_fields(sensitive_fields())
+            .validate_redacted(move |headers, data| {
+                checked_.lock().unwrap().push((headers.name.to_string(), data.to_vec()));
+                Ok(())
+            })
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 7);
+
+        for &(name, len) in &[("password", 17), ("ssn", 21), ("secret_token", 15), ("card_pin", 14),
+                              ("secret_upload", 14)] {
+            match entries.fields[name][0].data {
+                SavedData::Redacted { original_len } => assert_eq!(original_len, len, "{}", name),
+                ref other => panic!("{} was not redacted: {:?}", name, other),
+            }
+        }
+
+        assert!(entries.fields["greeting"][0].data.is_file());
+        assert!(entries.fields["password"][0].data.readable().is_err());
+        assert_eq!(entries.totals().file_count, 2);
+
+        // each text field was checked once, and the file not at all
+        let checked = checked.lock().unwrap();
+        let names: Vec<&str> = checked.iter().map(|(name, _)| &**name).collect();
+        assert_eq!(names, ["password", "ssn", "secret_token", "card_pin"]);
+        assert_eq!(checked[0].1, b"hunter2-PLAINTEXT");
+
+        // the data is in neither the saved files nor the descriptions of the fields
+        for entry in fs::read_dir(entries.save_dir.as_path()).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            assert!(!String::from_utf8_lossy(&data).contains("PLAINTEXT"));
+        }
+
+        let described = format!("{:?}\n{}", entries, entries.summary());
+        assert!(!described.contains("PLAINTEXT"), "{}", described);
+        assert!(described.contains("\"password\": Redacted, 17 bytes, <redacted>"), "{}", described);
+    }
+
+    #[test]
+    fn test_redact_fields_rejected() {
+        ::init_log();
+
+        let body = redacted_body();
+
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .redact_fields(sensitive_fields())
+            .validate_redacted(|headers, data| match &*headers.name {
+                "password" if data != b"hunter2-PLAINTEXT" => Ok(()),
+                "password" => Err(Abort("wrong password".into())),
+                _ => Ok(()),
+            })
+            .temp();
+
+        match res {
+            SaveResult::Partial(partial, PartialReason::Aborted(Abort(msg))) => {
+                assert_eq!(msg, "wrong password");
+                assert_eq!(partial.entries.fields_count(), 1);
+                assert!(partial.entries.fields.contains_key("greeting"));
+
+                let partial = partial.partial.unwrap();
+                assert_eq!(&*partial.source.headers.name, "password");
+                assert!(format!("{:?}", partial.dest).contains("Redacted"));
+            },
+            other => panic!("expected Aborted, got {:?}", other.into_opt_both()),
+        }
+
+        // over the size limit
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .size_limit(10)
+            .redact_fields(sensitive_fields())
+            .validate_redacted(|_, data| {
+                assert!(data.len() <= 10);
+                Ok(())
+            })
+            .temp();
+
+        match res {
+            SaveResult::Partial(partial, PartialReason::SizeLimit { field, limit: 10, written: 10 }) => {
+                assert_eq!(&*field, "password");
+                assert!(matches!(partial.partial.unwrap().dest, Some(SavedData::Redacted { original_len: 10 })));
+            },
+            other => panic!("expected SizeLimit, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_validate_redacted_chunks() {
+        ::init_log();
+
+        // many read chunks, and more than is preallocated
+        let password: String = (0 .. MAX_PREALLOC as usize * 3 / 2).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+        let body = format!("--boundary\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\n{}\r\n\
+                            --boundary--\r\n", password);
+        let checked = Arc::new(::std::sync::Mutex::new(Vec::new()));
+        let checked_ = checked.clone();
+
+        let entries = Multipart::with_body(body.as_bytes(), "boundary").save()
+            .size_limit(MAX_PREALLOC * 2)
+            .text_size_limit(MAX_PREALLOC * 2)
+            .redact_fields(sensitive_fields())
+            .validate_redacted(move |_, data| {
+                checked_.lock().unwrap().extend_from_slice(data);
+                Ok(())
+            })
+            .in_memory()
+            .into_result_strict()
+            .unwrap();
+
+        assert!(matches!(entries.fields["password"][0].data, SavedData::Redacted { original_len } if original_len == MAX_PREALLOC * 3 / 2));
+        assert_eq!(*checked.lock().unwrap(), password.as_bytes());
+    }
+
+    #[test]
+    fn test_extend_zeroing() {
+        let mut data = Vec::with_capacity(4);
+        extend_zeroing(&mut data, b"abc");
+        assert_eq!(data.capacity(), 4);
+
+        extend_zeroing(&mut data, b"defgh");
+        assert_eq!(data, b"abcdefgh");
+        assert!(data.capacity() >= 8);
+
+        extend_zeroing(&mut data, b"");
+        assert_eq!(data, b"abcdefgh");
+    }
+
+    #[test]
+    fn test_validate_redacted_panic() {
+        ::init_log();
+
+        let body = redacted_body();
+
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(0)
+            .redact_fields(sensitive_fields())
+            .validate_redacted(|headers, _| {
+                assert!(&*headers.name != "ssn", "can't check {}", headers.name);
+                Ok(())
+            })
+            .temp();
+
+        match res {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.to_string(), "the callback passed to `validate_redacted()` panicked: can't check ssn");
+
+                assert_eq!(partial.entries.fields_count(), 2);
+                assert!(partial.entries.fields["greeting"][0].as_path().unwrap().exists());
+                assert!(matches!(partial.entries.fields["password"][0].data, SavedData::Redacted { original_len: 17 }));
+
+                let partial = partial.partial.unwrap();
+                assert_eq!(&*partial.source.headers.name, "ssn");
+                assert!(matches!(partial.dest, Some(SavedData::Redacted { original_len: 21 })));
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_allowed_fields() {
+        ::init_log();
+
+        let junk = vec![b'x'; 64 * 1024];
+        let mut body = Vec::new();
+
+        for &(name, filename, data) in &[
+            ("junk", None, &junk[..]),
+            ("file", Some("a.txt"), &b"file data"[..]),
+            ("junk_file", Some("junk.bin"), &junk[..]),
+            ("description", None, &b"hi"[..]),
+            ("File", None, &b"wrong case"[..]),
+        ] {
+            body.extend_from_slice(b"--boundary\r\nContent-Disposition: form-data; name=\"");
+            body.extend_from_slice(name.as_bytes());
+            body.extend_from_slice(b"\"");
+
+            if let Some(filename) = filename {
+                body.extend_from_slice(format!("; filename=\"{}\"", filename).as_bytes());
+            }
+
+            body.extend_from_slice(b"\r\n\r\n");
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+
+        body.extend_from_slice(b"--boundary--\r\n");
+
+        let allowed = || vec!["file".to_string(), "description".to_string()];
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .allowed_fields(allowed())
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 2);
+        assert_eq!(fs::read_to_string(entries.fields["file"][0].as_path().unwrap()).unwrap(), "file data");
+        assert_eq!(fs::read_to_string(entries.fields["description"][0].as_path().unwrap()).unwrap(), "hi");
+        assert!(entries.clean_termination);
+
+        let ignored: Vec<&str> = entries.ignored.iter().map(|headers| &*headers.name).collect();
+        assert_eq!(ignored, ["junk", "junk_file", "File"]);
+        assert_eq!(entries.ignored[1].filename, Some("junk.bin".into()));
+        assert_eq!(entries.part_count(), 5);
+
+        // only the allowed fields were written, but the ignored ones were still read
+        assert_eq!(fs::read_dir(entries.save_dir.as_path()).unwrap().count(), 2);
+        assert_eq!(entries.totals().file_count, 2);
+        assert!(entries.stats.payload_bytes >= 2 * junk.len() as u64);
+
+        // ignored fields count toward `max_parts()`
+        match Multipart::with_body(&body[..], "boundary").save()
+            .allowed_fields(allowed())
+            .max_parts(3)
+            .in_memory() {
+            SaveResult::Partial(partial, PartialReason::CountLimit { limit: 3 }) => {
+                assert_eq!(partial.entries.fields_count(), 1);
+                assert_eq!(partial.entries.ignored.len(), 2);
+            },
+            other => panic!("expected CountLimit, got {:?}", other.into_opt_both()),
+        }
+
+        // an error draining an ignored field stops the save
+        struct Broken;
+
+        impl Read for Broken {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset"))
+            }
+        }
+
+        match Multipart::with_body((&body[..4096]).chain(Broken), "boundary").save()
+            .allowed_fields(allowed())
+            .in_memory() {
+            SaveResult::Partial(partial, PartialReason::IoError(e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::ConnectionReset);
+                assert!(partial.entries.is_empty());
+                assert!(partial.entries.ignored.is_empty());
+                assert!(partial.partial.is_none());
+            },
+            other => panic!("expected an IoError, got {:?}", other.into_opt_both()),
+        }
+
+        // every field is saved by default
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 5);
+        assert!(entries.ignored.is_empty());
+    }
+
+    #[test]
+    fn test_redact_fields_capture_raw() {
+        ::init_log();
+
+        let body = redacted_body();
+        let dir = ::tempfile::tempdir().unwrap();
+
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        multipart.capture_raw(&SaveBuilderConfig::config().memory_threshold(0).temp_parent(dir.path()));
+
+        match multipart.save().redact_fields(sensitive_fields()).temp_in(dir.path()) {
+            SaveResult::Error(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                assert!(e.to_string().contains("capture_raw()"), "{}", e);
+            },
+            other => panic!("expected an error, got {:?}", other.into_opt_both()),
+        }
+
+        // no field was read, so neither a save directory nor a spool file was left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        // a config with `redact_fields()` is rejected the same way
+        let config = SaveBuilderConfig::config().redact_fields(sensitive_fields());
+        assert!(matches!(config.bind(&mut multipart).temp(), SaveResult::Error(_)));
+    }
+
+    #[cfg(feature = "camino")]
+    #[test]
+    fn test_utf8_paths() {
+        use camino::Utf8PathBuf;
+
+        ::init_log();
+
+        let body = small_fields_body(3, 100);
+        let outer = ::tempfile::tempdir().unwrap();
+        let dir = Utf8PathBuf::from_path_buf(outer.path().join("téléchargés")).unwrap();
+
+        let entries = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(0)
+            .with_dir_utf8(dir.clone())
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.save_dir.as_utf8_path(), Some(&*dir));
+        assert_eq!(entries.fields_count(), 3);
+
+        for field in entries.fields.values().flatten() {
+            let path = field.data.utf8_path().unwrap();
+            assert_eq!(path.parent(), Some(&*dir));
+            assert_eq!(path.as_std_path(), field.as_path().unwrap());
+        }
+
+        assert_eq!(SavedData::Bytes(Vec::new().into()).utf8_path(), None);
+
+        // a path which isn't UTF-8 gives `None`
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+
+            let path = outer.path().join(OsStr::from_bytes(b"invalid\xff"));
+            assert_eq!(SavedData::File(path.clone(), FileSize::new(0)).utf8_path(), None);
+            assert_eq!(SaveDir::Perm(path).as_utf8_path(), None);
+        }
+    }
+
+    #[test]
+    fn test_max_created_dirs() {
+        ::init_log();
+
+        let body = small_fields_body(2, 100);
+        let root = ::tempfile::tempdir().unwrap();
+
+        // `d` is created for the first file
+        let dir = root.path().join("a/b/c/d");
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .max_created_dirs(4)
+            .with_dir(&dir)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 2);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // one short
+        let dir = root.path().join("e/f/g/h");
+
+        match Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .max_created_dirs(3)
+            .with_dir(&dir) {
+            SaveResult::Partial(partial, PartialReason::IoError(e)) => {
+                assert!(partial.entries.is_empty());
+
+                let err = e.get_ref().and_then(|e| e.downcast_ref::<DirLimitExceeded>())
+                    .unwrap_or_else(|| panic!("expected DirLimitExceeded, got {:?}", e));
+                assert_eq!((err.limit, &*err.path), (3, &*dir));
+            },
+            other => panic!("expected I/O error, got {:?}", other.into_opt_both()),
+        }
+
+        // the directories that were created are removed
+        assert!(!root.path().join("e").exists());
+
+        // fields in memory create no directories
+        let dir = root.path().join("i/j");
+        Multipart::with_body(&body[..], "boundary").save()
+            .max_created_dirs(1)
+            .with_dir(&dir)
+            .into_result_strict()
+            .unwrap();
+
+        assert!(root.path().join("i").is_dir() && !dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        ::init_log();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+
+        let body = small_fields_body(2, 100);
+        let root = ::tempfile::tempdir().unwrap();
+
+        let existing = root.path().join("a");
+        fs::create_dir(&existing).unwrap();
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let dir = existing.join("b/c");
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .dir_mode(0o2770)
+            .with_dir(&dir)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.stats.created_dirs, [existing.join("b"), dir.clone()]);
+        assert_eq!(mode(&existing.join("b")), 0o2770);
+        assert_eq!(mode(&dir), 0o2770);
+        assert_eq!(mode(&existing), 0o755);
+
+        // without a mode, the umask applies as usual
+        let dir = root.path().join("d");
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .with_dir(&dir)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(mode(&dir) & 0o7000, 0);
+        assert_eq!(entries.stats.created_dirs, [dir]);
+    }
+
+    #[test]
+    fn test_cleanup_created_dirs() {
+        ::init_log();
+
+        let body = require_first_body(None);
+        let root = ::tempfile::tempdir().unwrap();
+
+        for &cleanup in &[true, false] {
+            let dir = root.path().join(format!("{}/a/b", cleanup));
+
+            // both files are deleted once the check fails
+            match SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+                .memory_threshold(0)
+                .cleanup_created_dirs(cleanup)
+                .require_first(&["notes"], 1024, |_| Err(Abort("rejected".into())))
+                .with_dir(&dir) {
+                SaveResult::Partial(partial, PartialReason::Aborted(_)) =>
+                    assert!(partial.entries.is_empty()),
+                other => panic!("expected abort, got {:?}", other.into_opt_both()),
+            }
+
+            assert_eq!(root.path().join(cleanup.to_string()).exists(), !cleanup);
+            assert_eq!(dir.is_dir(), !cleanup);
+        }
+
+        // directories with files are kept
+        let dir = root.path().join("c/d");
+
+        match Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .max_parts(1)
+            .with_dir(&dir) {
+            SaveResult::Partial(partial, PartialReason::CountLimit { .. }) =>
+                assert_eq!(partial.entries.fields_count(), 1),
+            other => panic!("expected count limit, got {:?}", other.into_opt_both()),
+        }
+
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // a single field
+        let path = root.path().join("e/f/file");
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+
+        match field.data.save().memory_threshold(0).max_open_files(0).with_path(&path) {
+            SaveResult::Error(_) => (),
+            other => panic!("expected error, got {:?}", other),
+        }
+
+        assert!(!root.path().join("e").exists());
+    }
+
+    #[test]
+    fn test_into_owned() {
+        ::init_log();
+
+        let body = small_fields_body(3, 100);
+        let spool = SaveBuilderConfig::config().memory_threshold(16);
+
+        let field = Multipart::with_body(&body[..], "boundary").into_entry().unwrap_opt().unwrap();
+        let (owned, multipart) = field.into_owned(&spool).unwrap();
+
+        // the next field parses normally while the first is handled on another thread
+        let handle = ::std::thread::spawn(move || {
+            let mut data = Vec::new();
+            owned.data.readable().unwrap().read_to_end(&mut data).unwrap();
+            fs::remove_file(owned.as_path().unwrap()).unwrap();
+            (owned.headers.name, data)
+        });
+
+        let field = multipart.into_entry().unwrap_opt().unwrap();
+        assert_eq!(&*field.headers.name, "field1");
+
+        let (owned, multipart) = field.into_owned(&spool.size_limit(200)).unwrap();
+        assert_eq!(owned.as_text(), None);
+        fs::remove_file(owned.as_path().unwrap()).unwrap();
+
+        let (name, data) = handle.join().unwrap();
+        assert_eq!(&*name, "field0");
+        assert_eq!(data, (0 .. 100).map(|j| b'a' + (j % 26) as u8).collect::<Vec<u8>>());
+
+        // a field over the limit is an error
+        let field = multipart.into_entry().unwrap_opt().unwrap();
+        let err = match field.into_owned(&SaveBuilderConfig::config().memory_threshold(16).size_limit(50)) {
+            Err(e) => e,
+            Ok((owned, _)) => panic!("expected an error, got {:?}", owned),
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("size limit"), "{}", err);
+    }
+
+    #[test]
+    fn test_reject_suspicious_filenames() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"ok\"; filename=\"photo.jpg\"\r\n\r\n\
+                     data\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"bad\"; filename=\"invoice.pdf.exe\"\r\n\r\n\
+                     MZ\r\n\
+                     --boundary--\r\n";
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .temp().into_result_strict().unwrap();
+        assert_eq!(entries.fields_count(), 2);
+
+        match Multipart::with_body(&body[..], "boundary").save().reject_suspicious_filenames(true).temp() {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(partial.entries.fields_count(), 1);
+                assert_eq!(reason.suggested_status(), 400);
+                assert_eq!(reason.to_string(), "field `bad` has a suspicious filename: \"invoice.pdf.exe\"");
+
+                match reason {
+                    PartialReason::SuspiciousFilename { report, .. } => {
+                        assert_eq!(report.extensions, ["pdf", "exe"]);
+                        assert!(report.double_extension);
+                    },
+                    other => panic!("unexpected reason: {:?}", other),
+                }
+
+                let mut partial = partial.partial.expect("expected the rejected field");
+                assert!(partial.dest.is_none());
+
+                let mut data = String::new();
+                partial.source.data.read_to_string(&mut data).unwrap();
+                assert_eq!(data, "MZ");
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+
+        // the checks can be picked individually
+        let checks = FilenameChecks { double_extension: false, .. FilenameChecks::default() };
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .filename_checks(checks).temp().into_result_strict().unwrap();
+        assert_eq!(entries.fields_count(), 2);
+    }
+
+    /// Two fields of 40 bytes, followed by a `token` field if one is given.
+    fn require_first_body(token: Option<&str>) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(b"--boundary\r\n\
+                                 Content-Disposition: form-data; name=\"upload\"; filename=\"a.bin\"\r\n\r\n");
+        body.extend_from_slice(&[b'u'; 40]);
+        body.extend_from_slice(b"\r\n--boundary\r\n\
+                                 Content-Disposition: form-data; name=\"notes\"\r\n\r\n");
+        body.extend_from_slice(&[b'n'; 40]);
+
+        if let Some(token) = token {
+            write!(body, "\r\n--boundary\r\n\
+                          Content-Disposition: form-data; name=\"token\"\r\n\r\n{}", token).unwrap();
+        }
+
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+        body
+    }
+
+    /// Save `body` with every field in a file, requiring a `token` field of `secret`.
+    fn save_requiring_token(body: &[u8], spool_budget: u64) -> EntriesSaveResult<Multipart<&[u8]>> {
+        SaveBuilder::new(Multipart::with_body(body, "boundary"))
+            .memory_threshold(0)
+            .require_first(&["token"], spool_budget, |entries| {
+                // the held-back fields aren't visible yet
+                assert_eq!(entries.fields_count(), 1);
+
+                let mut token = String::new();
+                entries.fields["token"][0].data.readable().unwrap().read_to_string(&mut token).unwrap();
+
+                if token == "secret" { Ok(()) } else { Err(Abort("invalid token".into())) }
+            })
+            .temp()
+    }
+
+    fn saved_files(entries: &Entries) -> usize {
+        fs::read_dir(entries.save_dir.as_path()).unwrap().count()
+    }
+
+    #[test]
+    fn test_require_first() {
+        ::init_log();
+
+        let body = require_first_body(Some("secret"));
+        let entries = save_requiring_token(&body, 1024).into_result_strict().unwrap();
+
+        assert_eq!(entries.fields_count(), 3);
+        assert_eq!(saved_files(&entries), 3);
+        assert_eq!(entries.fields["upload"][0].data.size(), 40);
+        assert_eq!(entries.fields["notes"][0].data.size(), 40);
+
+        // without any required fields, the check runs before anything is read
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let res = multipart.save().require_first(&[], 0, |_| Err(Abort("closed".into()))).temp();
+
+        match res {
+            SaveResult::Partial(partial, PartialReason::Aborted(abort)) => {
+                assert_eq!(abort, Abort("closed".into()));
+                assert!(partial.entries.is_empty());
+            },
+            other => panic!("expected `Aborted`, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_require_first_abort() {
+        ::init_log();
+
+        let body = require_first_body(Some("wrong"));
+
+        match save_requiring_token(&body, 1024) {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.suggested_status(), 400);
+                assert_eq!(reason.to_string(), "the request was rejected: invalid token");
+
+                // the held-back fields and the token itself are gone, files and all
+                assert!(partial.entries.is_empty());
+                assert_eq!(partial.entries.fields_count(), 0);
+                assert_eq!(saved_files(&partial.entries), 0);
+                assert!(partial.partial.is_none());
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+
+        let body = require_first_body(None);
+
+        match save_requiring_token(&body, 1024) {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.suggested_status(), 400);
+                assert_eq!(reason.to_string(), "the request ended without the required field `token`");
+                assert!(partial.entries.is_empty());
+                assert_eq!(saved_files(&partial.entries), 0);
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_require_first_budget() {
+        ::init_log();
+
+        let body = require_first_body(Some("secret"));
+
+        // `upload` fits in the budget, `notes` doesn't
+        match save_requiring_token(&body, 60) {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.suggested_status(), 413);
+
+                match reason {
+                    PartialReason::SpoolBudget { limit, spooled } => {
+                        assert_eq!(limit, 60);
+                        assert!(spooled >= 60, "{}", spooled);
+                    },
+                    other => panic!("unexpected reason: {:?}", other),
+                }
+
+                assert!(partial.entries.is_empty());
+                assert_eq!(saved_files(&partial.entries), 0);
+
+                let partial = partial.partial.expect("expected the field over the budget");
+                assert_eq!(&*partial.source.headers.name, "notes");
+                assert!(partial.dest.is_none());
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_require_first_panic() {
+        ::init_log();
+
+        let body = require_first_body(Some("secret"));
+
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(0)
+            .require_first(&["token"], 1024, |_| -> Result<(), Abort> { panic!("no token store") })
+            .temp();
+
+        match res {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.to_string(), "the callback passed to `require_first()` panicked: no token store");
+
+                // nothing is deleted: the held-back fields are kept, unchecked
+                assert_eq!(partial.entries.fields_count(), 3);
+                assert_eq!(saved_files(&partial.entries), 3);
+                assert_eq!(partial.entries.fields["upload"][0].data.size(), 40);
+                assert!(partial.partial.is_none());
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_throttle() {
+        ::init_log();
+
+        // 6400 bytes of field data at 32000 bytes per second
+        let body = small_fields_body(64, 100);
+
+        for &memory_threshold in &[10 * 1024, 0] {
+            let start = ::std::time::Instant::now();
+
+            let entries = Multipart::with_body(&body[..], "boundary").save()
+                .memory_threshold(memory_threshold)
+                .throttle(32_000)
+                .temp()
+                .into_result_strict()
+                .unwrap();
+
+            assert_eq!(entries.fields_count(), 64);
+            assert!(start.elapsed() >= Duration::from_millis(190), "{:?}", start.elapsed());
+        }
+    }
+
+    /// A client sending at most 256 bytes every 5 milliseconds.
+    struct SlowReader<'a>(&'a [u8]);
+
+    impl<'a> Read for SlowReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            ::std::thread::sleep(Duration::from_millis(5));
+            let len = cmp::min(buf.len(), 256);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_cancel_token() {
+        use std::thread;
+        use std::time::Instant;
+
+        ::init_log();
+
+        // would take at least 1.6 seconds to read in full
+        let body = small_fields_body(2, 40_000);
+
+        for &memory_threshold in &[0, u64::MAX] {
+            let token = CancelToken::new();
+            let canceller = token.clone();
+
+            let start = Instant::now();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                canceller.cancel();
+            });
+
+            let res = SaveBuilder::new(Multipart::with_body(SlowReader(&body), "boundary"))
+                .memory_threshold(memory_threshold)
+                .cancel_token(token)
+                .temp();
+            assert!(start.elapsed() < Duration::from_secs(1), "took {:?}", start.elapsed());
+
+            match res {
+                SaveResult::Partial(partial, PartialReason::Cancelled) => {
+                    assert!(partial.entries.is_empty());
+
+                    // everything read so far is accounted for
+                    let field = partial.partial.unwrap();
+                    let saved = field.dest.unwrap();
+                    let mut rest = Vec::new();
+                    let mut data = field.source.data;
+                    data.read_to_end(&mut rest).unwrap();
+
+                    assert!(saved.size() > 0 && saved.size() < 40_000, "saved {}", saved.size());
+                    assert_eq!(saved.size() + rest.len() as u64, 40_000);
+                    let expected: Vec<u8> = (0 .. saved.size()).map(|j| b'a' + (j % 26) as u8).collect();
+                    assert_eq!(read_data(&saved), expected);
+                },
+                other => panic!("expected cancelled, got {:?}", other.into_opt_both()),
+            }
+        }
 
-    while !buf.is_empty() {
-        match try_here!(dest.write(buf)) {
-            0 => try_here!(Err(io::Error::new(io::ErrorKind::WriteZero,
-                                          "failed to write whole buffer"))),
-            copied => {
-                buf = &buf[copied..];
-                total_copied += copied;
+        // a cancelled token stops saving before the request is read
+        let token = CancelToken::new();
+        token.cancel();
+
+        match Multipart::with_body(Unreadable, "boundary").save().cancel_token(token.clone()).temp() {
+            SaveResult::Partial(partial, reason @ PartialReason::Cancelled) => {
+                assert!(partial.partial.is_none());
+                assert_eq!(reason.suggested_status(), 503);
             },
+            other => panic!("expected cancelled, got {:?}", other.into_opt_both()),
+        }
+
+        // a single field stops after the first buffer
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+        let mut field = multipart.read_entry_mut().unwrap();
+
+        match field.data.save().cancel_token(token).write_to(Vec::new()) {
+            SaveResult::Partial(written, PartialReason::Cancelled) => assert!(written < 40_000),
+            other => panic!("expected cancelled, got {:?}", other),
         }
     }
 
-    Full(total_copied)
+    #[test]
+    fn test_response_parts() {
+        ::init_log();
+
+        let file: Vec<u8> = (0 .. 4096u32).map(|i| (i % 251) as u8).collect();
+
+        let mut body = Vec::new();
+        body.extend_from_slice(b"--boundary\r\n\
+                                 Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                                 hello\r\n\
+                                 --boundary\r\n\
+                                 Content-Disposition: form-data; name=\"file\"; filename=\"data.bin\"\r\n\
+                                 Content-Type: image/png\r\n\r\n");
+        body.extend_from_slice(&file);
+        body.extend_from_slice(b"\r\n\
+                                 --boundary\r\n\
+                                 Content-Disposition: form-data; name=\"guessed\"; filename=\"r\xC3\xA9sum\xC3\xA9.pdf\"\r\n\r\n\
+                                 \x00\xFF\r\n\
+                                 --boundary--\r\n");
+
+        // in memory and on disk
+        for &threshold in &[u64::MAX, 0] {
+            let mut entries = Multipart::with_body(&body[..], "boundary").save()
+                .memory_threshold(threshold)
+                .temp()
+                .into_result_strict()
+                .unwrap();
+
+            let expected: &[(&str, &str, &[u8], &str)] = &[
+                ("text", "text/plain; charset=utf-8", b"hello", "attachment"),
+                ("file", "image/png", &file, "attachment; filename=\"data.bin\""),
+                ("guessed", "application/pdf", b"\x00\xFF",
+                 "attachment; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"),
+            ];
+
+            for &(name, content_type, data, disposition) in expected {
+                let field = entries.fields.remove(name).unwrap().remove(0);
+                let parts = field.response_parts();
+
+                assert_eq!(parts.content_type.to_string(), content_type);
+                assert_eq!(parts.content_length, data.len() as u64);
+                assert_eq!(parts.content_disposition(), disposition);
+
+                // the "response" outlives the saved field
+                let mut response = Vec::new();
+                let mut reader = field.data.into_body_reader().unwrap();
+                io::copy(&mut reader, &mut response).unwrap();
+                assert_eq!(response, data);
+            }
+        }
+    }
+
+    /// Flags data starting with `MYFMT` as `application/x-myfmt`.
+    fn myfmt_sniffer(first_bytes: &[u8], _headers: &FieldHeaders) -> Option<::mime::Mime> {
+        if first_bytes.starts_with(b"MYFMT") { "application/x-myfmt".parse().ok() } else { None }
+    }
+
+    #[test]
+    fn test_sniffer() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"doc\"; filename=\"a.txt\"\r\n\
+                     Content-Type: text/plain\r\n\r\n\
+                     MYFMT\x01\x02 with enough data to be saved to a file\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"small\"\r\n\r\n\
+                     MYFMT\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                     hello\r\n\
+                     --boundary--\r\n";
+
+        let myfmt: ::mime::Mime = "application/x-myfmt".parse().unwrap();
+
+        // the check passed to `require_first()` sees the verdict of the sniffer
+        let entries = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(16)
+            .sniffer(myfmt_sniffer)
+            .require_first(&["doc"], 1024, |entries| {
+                let doc = &entries.fields["doc"][0];
+                assert!(doc.data.is_file());
+
+                match doc.detected_type {
+                    Some(ref detected) if *detected != *doc.headers.content_type.as_ref().unwrap() =>
+                        Err(Abort(format!("declared as text, but is {}", detected))),
+                    _ => Ok(()),
+                }
+            })
+            .temp();
+
+        match entries {
+            SaveResult::Partial(partial, PartialReason::Aborted(Abort(msg))) => {
+                assert!(partial.entries.is_empty());
+                assert_eq!(msg, "declared as text, but is application/x-myfmt");
+            },
+            other => panic!("expected abort, got {:?}", other.into_opt_both()),
+        }
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(16)
+            .sniffer(myfmt_sniffer)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields["doc"][0].detected_type.as_ref(), Some(&myfmt));
+        assert_eq!(entries.fields["small"][0].detected_type.as_ref(), Some(&myfmt));
+        assert_eq!(entries.fields["text"][0].detected_type, None);
+
+        // without a sniffer, nothing is detected
+        let entries = Multipart::with_body(&body[..], "boundary").save().temp().into_result_strict().unwrap();
+        assert!(entries.fields.values().flatten().all(|field| field.detected_type.is_none()));
+
+        // the config applies to `into_owned()`
+        let config = SaveBuilderConfig::config().sniffer(MagicSniffer);
+        let pdf = b"--boundary\r\n\
+                    Content-Disposition: form-data; name=\"pdf\"\r\n\r\n\
+                    %PDF-1.4\r\n\
+                    --boundary--\r\n";
+
+        let mut multipart = Multipart::with_body(&pdf[..], "boundary");
+        let field = multipart.read_entry_mut().into_result().unwrap().unwrap();
+        let (owned, _) = field.into_owned(&config).unwrap();
+        assert_eq!(owned.detected_type, Some(::mime::APPLICATION_PDF));
+    }
+
+    /// Panics for the field `field1`.
+    fn panicking_sniffer(_first_bytes: &[u8], headers: &FieldHeaders) -> Option<::mime::Mime> {
+        assert!(&*headers.name != "field1", "can't sniff {}", headers.name);
+        None
+    }
+
+    #[test]
+    fn test_sniffer_panic() {
+        ::init_log();
+
+        let body = small_fields_body(3, 100);
+
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(0)
+            .sniffer(panicking_sniffer)
+            .temp();
+
+        match res {
+            SaveResult::Partial(partial, reason) => {
+                assert_eq!(reason.to_string(), "the callback passed to `sniffer()` panicked: can't sniff field1");
+                assert_eq!(partial.entries.fields_count(), 1);
+                assert!(partial.entries.fields["field0"][0].as_path().unwrap().exists());
+
+                // the field was saved before it was sniffed, and is returned with its file
+                let partial = partial.partial.unwrap();
+                assert_eq!(&*partial.source.headers.name, "field1");
+                match partial.dest {
+                    Some(SavedData::File(ref path, _)) => assert_eq!(fs::metadata(path).unwrap().len(), 100),
+                    ref other => panic!("expected a file, got {:?}", other),
+                }
+            },
+            other => panic!("expected a partial result, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_content_hash() {
+        ::init_log();
+
+        let body = small_fields_body(5, 100);
+
+        let save = |body: &[u8], memory_threshold| Multipart::with_body(body, "boundary").save()
+            .memory_threshold(memory_threshold)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        let entries = save(&body, u64::MAX);
+        let names: Vec<&str> = entries.fields.keys().map(|name| &**name).collect();
+        assert_eq!(names, ["field0", "field1", "field2", "field3", "field4"]);
+
+        let hash = entries.content_hash().unwrap();
+        assert_eq!(save(&body, u64::MAX).content_hash().unwrap(), hash);
+        // the same data in files
+        assert_eq!(save(&body, 0).content_hash().unwrap(), hash);
+
+        // one byte of data
+        let mut changed = body.clone();
+        let idx = changed.len() - 20;
+        changed[idx] ^= 1;
+        assert_ne!(save(&changed, 0).content_hash().unwrap(), hash);
+
+        // one byte of a name
+        let changed: Vec<u8> = String::from_utf8(body).unwrap().replacen("field3", "field5", 1).into();
+        assert_ne!(save(&changed, u64::MAX).content_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn test_config_apply() {
+        ::init_log();
+
+        let body = small_fields_body(4, 100);
+        let config = SaveBuilderConfig::config().max_parts(2).memory_threshold(0);
+
+        match config.apply(SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))).temp() {
+            SaveResult::Partial(partial, PartialReason::CountLimit { limit: 2 }) => {
+                assert_eq!(partial.entries.fields_count(), 2);
+                assert!(partial.entries.fields.values().flatten().all(|field| field.data.is_file()));
+            },
+            other => panic!("expected count limit, got {:?}", other.into_opt_both()),
+        }
+
+        // settings made on the builder before are replaced, and after are kept
+        let entries = config.apply(Multipart::with_body(&body[..], "boundary").save().max_parts(1))
+            .max_parts(4)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.fields_count(), 4);
+
+        // `save()` borrows the `Multipart`
+        let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+        match SaveBuilderConfig::config().size_limit(10).apply(multipart.save()).temp() {
+            SaveResult::Partial(_, PartialReason::SizeLimit { .. }) => (),
+            other => panic!("expected size limit, got {:?}", other.into_opt_both()),
+        }
+    }
+
+    #[test]
+    fn test_config_bind_concurrently() {
+        use std::thread;
+
+        ::init_log();
+
+        let config = SaveBuilderConfig::config().max_parts(3).memory_threshold(0).name_prefix("upload-");
+
+        thread::scope(|scope| {
+            let saves: Vec<_> = [1, 2, 5].iter().map(|&count| {
+                let config = &config;
+
+                scope.spawn(move || {
+                    let body = small_fields_body(count, 100);
+                    let mut multipart = Multipart::with_body(&body[..], "boundary");
+
+                    let entries = match multipart.save_with(config).temp() {
+                        SaveResult::Partial(partial, PartialReason::CountLimit { limit: 3 }) if count > 3 =>
+                            partial.entries,
+                        SaveResult::Full(entries) if count < 3 => entries,
+                        other => panic!("{} fields: unexpected result {:?}", count, other.into_opt_both()),
+                    };
+
+                    assert_eq!(entries.fields_count(), count.min(3) as u32);
+
+                    for field in entries.fields.values().flatten() {
+                        let name = field.as_path().unwrap().file_name().unwrap().to_str().unwrap();
+                        assert!(name.starts_with("upload-"), "{:?}", name);
+                    }
+                })
+            }).collect();
+
+            for save in saves {
+                save.join().unwrap();
+            }
+        });
+
+        // binding doesn't consume the config
+        let body = small_fields_body(1, 100);
+        let entries = config.bind(Multipart::with_body(&body[..], "boundary")).temp().into_result_strict().unwrap();
+        assert_eq!(entries.fields_count(), 1);
+    }
+
+    #[test]
+    fn test_field_error_policy() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"first\"\r\n\r\n\
+                     one\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"second\"; filename=\"a.bin\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n\
+                     too large to keep in memory\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"third\"\r\n\r\n\
+                     three\r\n\
+                     --boundary--\r\n";
+
+        // only the second field goes to a file, which can't be created under a regular file
+        let dir = ::tempfile::TempDir::new().unwrap();
+        let not_a_dir = dir.path().join("file");
+        fs::write(&not_a_dir, b"").unwrap();
+
+        let save = |policy| SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(16)
+            .on_field_error(policy)
+            .with_entries(Entries::new(SaveDir::Perm(not_a_dir.clone())));
+
+        match save(FieldErrorPolicy::Abort) {
+            SaveResult::Partial(partial, PartialReason::IoError(_)) => {
+                assert_eq!(partial.entries.fields.keys().map(|k| &**k).collect::<Vec<_>>(), ["first"]);
+                assert_eq!(&*partial.partial.unwrap().source.headers.name, "second");
+            },
+            other => panic!("expected an I/O error, got {:?}", other.into_opt_both()),
+        }
+
+        let entries = save(FieldErrorPolicy::SkipAndContinue).into_result_strict().unwrap();
+        assert_eq!(entries.fields.keys().map(|k| &**k).collect::<Vec<_>>(), ["first", "third"]);
+        assert_eq!(read_data(&entries.fields["third"][0].data), b"three");
+        assert!(entries.clean_termination);
+
+        assert!(!entries.is_clean());
+        assert_eq!(entries.failures.len(), 1);
+        assert_eq!(&*entries.failures[0].headers.name, "second");
+        match entries.failures[0].reason {
+            PartialReason::IoError(_) => (),
+            ref other => panic!("expected an I/O error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_field_meta() {
+        ::init_log();
+
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                     hello\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\n\
+                     Content-Type: image/png\r\n\r\n\
+                     0123456789\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"b.png\"\r\n\
+                     Content-Type: image/png\r\n\r\n\
+                     abc\r\n\
+                     --boundary--\r\n";
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(8)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+
+        let mut metas: Vec<FieldMeta> = entries.metas().collect();
+        assert_eq!(metas.len(), 3);
+        metas.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let text = &entries.fields["text"][0];
+        assert_eq!(metas[2], text.meta());
+        assert_eq!((metas[2].size, metas[2].kind, metas[2].filename.clone()), (5, "Text", None));
+
+        for (meta, field) in metas[..2].iter().zip(&entries.fields["file"]) {
+            assert_eq!(*meta, field.meta());
+            assert_eq!(meta.filename, field.headers.filename);
+            assert_eq!(meta.content_type, Some(::mime::IMAGE_PNG));
+            assert_eq!(meta.size, field.data.size());
+        }
+
+        assert_eq!((metas[0].kind, metas[0].size), ("File", 10));
+        assert_eq!((metas[1].kind, metas[1].size), ("Bytes", 3));
+
+        // neither the headers nor the metas copy the name, and the metas outlive the entries
+        let headers = text.headers.clone();
+        assert!(Arc::ptr_eq(&headers.name, &text.headers.name));
+        assert!(Arc::ptr_eq(&metas[2].name, &text.headers.name));
+
+        drop(entries);
+        assert_eq!(&*metas[2].name, "text");
+    }
+
+    /// The fields saved (name, size and detected type) and the reason the save stopped, if any.
+    type SaveOutcome = (Vec<(String, u64, Option<::mime::Mime>)>, Option<String>);
+
+    fn save_outcome<M: ReadEntry>(res: EntriesSaveResult<M>) -> SaveOutcome {
+        let (entries, reason) = match res {
+            SaveResult::Full(entries) => (entries, None),
+            SaveResult::Partial(partial, reason) => (partial.entries, Some(reason.to_string())),
+            SaveResult::Error(e) => panic!("unexpected error: {}", e),
+        };
+
+        let mut fields: Vec<_> = entries.fields.values().flatten()
+            .map(|field| (field.headers.name.to_string(), field.data.size(), field.detected_type.clone()))
+            .collect();
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+        (fields, reason)
+    }
+
+    #[test]
+    fn test_dry_run() {
+        ::init_log();
+
+        let sha256 = "SG6kYiTRu0+2gPNPfJrZao8k7Ii+c+qOWmxlJg6cuKc=";
+        let valid = digest_body("", &format!("Digest: sha-256={}\r\n", sha256));
+        #[cfg(feature = "digest")]
+        let mismatch = digest_body("Content-MD5: fXkwN6B2AYZXSwKC8vQ15w==\r\n", "");
+        let invalid_text = digest_body("", "").iter()
+            .map(|&b| if b == b'h' { 0xFF } else { b }).collect::<Vec<u8>>();
+        let nonchar_text = String::from_utf8(digest_body("", "")).unwrap()
+            .replace("hello", "hel\u{FFFF}lo").into_bytes();
+        let sniffed = b"--boundary\r\n\
+                        Content-Disposition: form-data; name=\"doc\"; filename=\"a.txt\"\r\n\r\n\
+                        MYFMT\x01\x02\r\n\
+                        --boundary--\r\n".to_vec();
+
+        let base = || SaveBuilderConfig::config().memory_threshold(0).sniffer(myfmt_sniffer);
+
+        #[allow(unused_mut)]
+        let mut cases = vec![
+            (&valid, base(), None),
+            (&sniffed, base(), None),
+            (&valid, base().size_limit(4), Some("size limit")),
+            (&invalid_text, base().memory_threshold(64).force_text(), Some("UTF-8")),
+            // saved to a file without being checked
+            (&invalid_text, base().force_text(), None),
+            (&invalid_text, base(), None),
+            (&nonchar_text, base().memory_threshold(64).force_text().text_normalization(TextNorm::RejectNoncharacters),
+             Some("noncharacter")),
+            (&valid, base().max_file_parts(0), Some("files")),
+        ];
+
+        #[cfg(feature = "digest")]
+        cases.extend(vec![
+            (&valid, base().verify_digests(true), None),
+            (&mismatch, base().verify_digests(true), Some("md5 digest")),
+        ]);
+
+        for (body, config, expected) in cases {
+            let saved = save_outcome(config.apply(SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))).temp());
+
+            let res = config.apply(SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))).dry_run();
+            {
+                let (entries, dest) = match res {
+                    SaveResult::Full(ref entries) => (entries, None),
+                    SaveResult::Partial(ref partial, _) =>
+                        (&partial.entries, partial.partial.as_ref().and_then(|partial| partial.dest.as_ref())),
+                    SaveResult::Error(ref e) => panic!("unexpected error: {}", e),
+                };
+
+                // nothing was written, even with a `memory_threshold` of zero
+                assert!(matches!(entries.save_dir, SaveDir::Memory));
+                assert!(entries.fields.values().flatten().all(|field| field.data.is_validated()));
+                assert!(dest.is_none_or(SavedData::is_validated));
+            }
+            let dry_run = save_outcome(res);
+
+            assert_eq!(dry_run, saved);
+
+            match expected {
+                Some(expected) => {
+                    let reason = dry_run.1.expect("expected the dry run to stop");
+                    assert!(reason.contains(expected), "{:?} doesn't mention {:?}", reason, expected);
+                },
+                None => assert_eq!(dry_run.1, None),
+            }
+        }
+
+        let entries = Multipart::with_body(&valid[..], "boundary").save()
+            .sniffer(myfmt_sniffer)
+            .dry_run()
+            .into_result_strict()
+            .unwrap();
+
+        match entries.fields["file"][0].data {
+            SavedData::Validated { len, ref detected_type, ref digest } => {
+                assert_eq!(len, 5);
+                assert_eq!(*detected_type, None);
+                #[cfg(feature = "digest")]
+                assert_eq!(digest.map(|digest| super::digest::base64_encode(&digest)), Some(sha256.into()));
+                #[cfg(not(feature = "digest"))]
+                assert_eq!(*digest, None);
+            },
+            ref other => panic!("expected Validated, got {:?}", other),
+        }
+
+        assert!(entries.fields["text"][0].data.readable().is_err());
+    }
+
+    #[test]
+    fn test_dry_run_text_check() {
+        let check = |pieces: &[&[u8]]| {
+            let mut check = super::TextCheck::new(true, u64::MAX);
+            for piece in pieces {
+                check.update(piece);
+            }
+            check.finish().map(|reason| reason.to_string())
+        };
+
+        let text = "h\u{e9}llo \u{1F600}".as_bytes();
+
+        // split into single bytes, so every multibyte character is split
+        assert_eq!(check(&text.chunks(1).collect::<Vec<_>>()), None);
+        assert_eq!(check(&text.chunks(3).collect::<Vec<_>>()), None);
+
+        // ends in the middle of a character
+        assert!(check(&[&text[..text.len() - 1]]).is_some());
+        assert!(check(&text[..text.len() - 1].chunks(1).collect::<Vec<_>>()).is_some());
+
+        assert!(check(&[b"ab", b"\xC3", b"\x28"]).is_some());
+        assert!(check(&["\u{FFFE}".as_bytes()]).unwrap().contains("noncharacter"));
+
+        // past the limit, nothing is checked
+        let mut check = super::TextCheck::new(false, 3);
+        check.update(b"ab\xC3");
+        check.update(b"\xFF");
+        assert!(check.finish().is_none());
+
+        let mut check = super::TextCheck::new(false, 3);
+        check.update(b"ab\xC3");
+        assert!(check.finish().is_some());
+    }
+
+    fn staging_dirs(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_dir())
+            .collect()
+    }
+
+    #[test]
+    fn test_transactional() {
+        ::init_log();
+
+        let tempdir = ::tempfile::TempDir::new().unwrap();
+        let dir = tempdir.path().join("target");
+        let body = small_fields_body(3, 100);
+
+        let entries = Multipart::with_body(&body[..], "boundary").save()
+            .memory_threshold(0)
+            .chunk_files(64)
+            .transactional(true)
+            .with_dir(&dir)
+            .into_result_strict()
+            .unwrap();
+
+        assert_eq!(entries.save_dir.as_path(), dir);
+        assert!(staging_dirs(&dir).is_empty());
+
+        // 3 fields in 2 chunks each
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 6);
+
+        for field in entries.fields.values().flatten() {
+            for path in super::file_paths(&field.data) {
+                assert_eq!(path.parent(), Some(&*dir));
+            }
+            assert_eq!(read_data(&field.data).len(), 100);
+        }
+
+        // a save which fails leaves the target as it was
+        let body = small_fields_body(3, 100);
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(0)
+            .size_limit(100)
+            .max_parts(2)
+            .transactional(true)
+            .with_dir(&dir);
+
+        match res {
+            SaveResult::Partial(partial, PartialReason::CountLimit { .. }) => {
+                assert_eq!(partial.entries.fields_count(), 2);
+                assert!(partial.entries.fields.values().flatten().all(|field| !files_exist(&field.data)));
+            },
+            other => panic!("expected CountLimit, got {:?}", other.into_opt_both()),
+        }
+
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 6);
+
+        // nor is the target directory created
+        let missing = tempdir.path().join("missing");
+        let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+            .memory_threshold(0)
+            .max_parts(2)
+            .transactional(true)
+            .with_dir(&missing);
+
+        assert!(matches!(res, SaveResult::Partial(_, PartialReason::CountLimit { .. })));
+        assert!(!missing.exists());
+    }
+
+    fn files_exist(data: &SavedData) -> bool {
+        super::file_paths(data).iter().any(|path| path.exists())
+    }
+
+    fn crash_after_two(moved: usize) {
+        if moved == 2 {
+            panic!("simulated crash");
+        }
+    }
+
+    #[test]
+    fn test_transactional_recovery() {
+        ::init_log();
+
+        let tempdir = ::tempfile::TempDir::new().unwrap();
+        let dir = tempdir.path();
+        let body = small_fields_body(4, 100);
+
+        let crashed = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            let mut builder = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+                .memory_threshold(0)
+                .transactional(true);
+            builder.opts.rename_hook = crash_after_two;
+            builder.with_dir(dir)
+        }));
+        assert!(crashed.is_err());
+
+        // two files were moved before the crash, two are left in the staging directory
+        let staging = staging_dirs(dir);
+        assert_eq!(staging.len(), 1);
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 3);
+        assert!(staging[0].join(super::COMMIT_MARKER).exists());
+
+        // a save which was interrupted before it committed
+        let unfinished = dir.join(format!("{}unfinished", super::STAGING_PREFIX));
+        fs::create_dir(&unfinished).unwrap();
+        fs::write(unfinished.join("partial"), b"data").unwrap();
+
+        // other directories are left alone
+        fs::create_dir(dir.join("other")).unwrap();
+
+        let report = super::recover_staging(dir).unwrap();
+        assert_eq!(report.completed, staging);
+        assert_eq!(report.discarded, vec![unfinished]);
+        assert_eq!(report.files_moved, 2);
+
+        assert_eq!(staging_dirs(dir), vec![dir.join("other")]);
+        let files: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_file())
+            .collect();
+        assert_eq!(files.len(), 4);
+
+        for path in files {
+            assert_eq!(fs::read(&path).unwrap().len(), 100);
+        }
+
+        // nothing left to do
+        assert_eq!(super::recover_staging(dir).unwrap(), super::RecoveryReport::default());
+    }
+
+    #[test]
+    fn test_total_size_limit() {
+        ::init_log();
+
+        for backend in file_backends() {
+            let tempdir = ::tempfile::TempDir::new().unwrap();
+            let body = small_fields_body(4, 100);
+
+            // on disk, in memory, and split between the two
+            for &threshold in &[0, u64::MAX, 150] {
+                let res = SaveBuilder::new(Multipart::with_body(&body[..], "boundary"))
+                    .memory_threshold(threshold)
+                    .total_size_limit(250)
+                    .file_backend(backend)
+                    .with_dir(tempdir.path());
+
+                match res {
+                    SaveResult::Partial(partial, reason) => {
+                        match reason {
+                            PartialReason::TotalSizeLimit { limit: 250, saved: 250 } => (),
+                            ref other => panic!("threshold: {}: unexpected reason {:?}", threshold, other),
+                        }
+                        assert_eq!(reason.suggested_status(), 413);
+                        assert!(!reason.is_field_error());
+
+                        assert_eq!(partial.entries.fields_count(), 2);
+
+                        let field = partial.partial.expect("the field which crossed the limit");
+                        assert_eq!(&*field.source.headers.name, "field2");
+                        assert!(field.dest.is_some());
+                        assert_eq!(field.dest.unwrap().size(), 50);
+                    },
+                    SaveResult::Full(_) => panic!("threshold: {}: expected Partial", threshold),
+                    SaveResult::Error(e) => panic!("threshold: {}: {}", threshold, e),
+                }
+            }
+
+            // a request which fits exactly
+            let entries = Multipart::with_body(&body[..], "boundary").save()
+                .total_size_limit(400)
+                .file_backend(backend)
+                .with_dir(tempdir.path())
+                .into_result_strict()
+                .unwrap();
+            assert_eq!(entries.fields_count(), 4);
+
+            // the fields already in the `Entries` count toward the limit
+            let entries = Multipart::with_body(&small_fields_body(1, 250)[..], "boundary").save()
+                .in_memory()
+                .into_result_strict()
+                .unwrap();
+
+            match Multipart::with_body(&body[..], "boundary").save()
+                .total_size_limit(400)
+                .file_backend(backend)
+                .with_entries(entries)
+            {
+                SaveResult::Partial(partial, PartialReason::TotalSizeLimit { saved: 400, .. }) => {
+                    assert_eq!(partial.entries.fields_count(), 2);
+                    assert_eq!(partial.partial.unwrap().dest.unwrap().size(), 50);
+                },
+                SaveResult::Partial(_, reason) => panic!("unexpected reason {:?}", reason),
+                SaveResult::Full(_) => panic!("expected Partial"),
+                SaveResult::Error(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_size_limit() {
+        ::init_log();
+
+        let limit = super::DEFAULT_TEXT_SIZE_LIMIT as usize;
+
+        // limited by default
+        match Multipart::with_body(&small_fields_body(1, limit + 1)[..], "boundary").save().temp() {
+            SaveResult::Partial(partial, reason) => {
+                match reason {
+                    PartialReason::TextSizeLimit { ref field, limit: l, written } => {
+                        assert_eq!(&**field, "field0");
+                        assert_eq!((l, written), (limit as u64, limit as u64));
+                    },
+                    ref other => panic!("unexpected reason {:?}", other),
+                }
+                assert_eq!(reason.suggested_status(), 413);
+                assert!(!reason.is_field_error());
+
+                assert_eq!(partial.entries.fields_count(), 0);
+                assert_eq!(partial.partial.unwrap().dest.unwrap().size(), limit as u64);
+            },
+            SaveResult::Full(_) => panic!("expected Partial"),
+            SaveResult::Error(e) => panic!("{}", e),
+        }
+
+        // `None` clears the limit
+        let entries = Multipart::with_body(&small_fields_body(1, limit + 1)[..], "boundary").save()
+            .text_size_limit(None)
+            .temp()
+            .into_result_strict()
+            .unwrap();
+        assert_eq!(entries.fields["field0"][0].data.size(), limit as u64 + 1);
+
+        // fields with a filename are only limited by `size_limit`
+        let body = b"--boundary\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n\
+                     0123456789\r\n\
+                     --boundary\r\n\
+                     Content-Disposition: form-data; name=\"text\"\r\n\r\n\
+                     0123456789\r\n\
+                     --boundary--\r\n";
+
+        match Multipart::with_body(&body[..], "boundary").save().text_size_limit(5).in_memory() {
+            SaveResult::Partial(partial, PartialReason::TextSizeLimit { ref field, limit: 5, written: 5 }) => {
+                assert_eq!(&**field, "text");
+                assert_eq!(partial.entries.fields["file"][0].data.size(), 10);
+            },
+            SaveResult::Partial(_, reason) => panic!("unexpected reason {:?}", reason),
+            SaveResult::Full(_) => panic!("expected Partial"),
+            SaveResult::Error(e) => panic!("{}", e),
+        }
+
+        // the lower of the two limits applies
+        match Multipart::with_body(&body[..], "boundary").save().size_limit(3).text_size_limit(5).in_memory() {
+            SaveResult::Partial(_, PartialReason::SizeLimit { limit: 3, .. }) => (),
+            SaveResult::Partial(_, reason) => panic!("unexpected reason {:?}", reason),
+            SaveResult::Full(_) => panic!("expected Partial"),
+            SaveResult::Error(e) => panic!("{}", e),
+        }
+    }
 }
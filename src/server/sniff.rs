@@ -0,0 +1,123 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Detecting the type of field data from its first bytes, for `SaveBuilder::sniffer()`.
+
+use mime::Mime;
+
+use server::FieldHeaders;
+
+/// The most bytes from the start of a field passed to `TypeSniffer::sniff()`.
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+/// Detects the type of a field from the first bytes of its data, e.g. by looking for
+/// magic numbers, regardless of the content type declared by the client.
+///
+/// Set with `SaveBuilder::sniffer()`; the result is stored in `SavedField::detected_type`.
+/// Implemented for closures with the same signature as `sniff()`.
+pub trait TypeSniffer: Send + Sync {
+    /// Detect the type of a field from `first_bytes`, or return `None` if it's unknown.
+    ///
+    /// `first_bytes` holds up to the first 8 KiB of the field, or less if that's
+    /// all that could be read at once.
+    fn sniff(&self, first_bytes: &[u8], headers: &FieldHeaders) -> Option<Mime>;
+}
+
+impl<F> TypeSniffer for F where F: Fn(&[u8], &FieldHeaders) -> Option<Mime> + Send + Sync {
+    fn sniff(&self, first_bytes: &[u8], headers: &FieldHeaders) -> Option<Mime> {
+        self(first_bytes, headers)
+    }
+}
+
+/// `(offset, magic number, content type)`
+const MAGIC: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1A\n", "image/png"),
+    (0, b"\xFF\xD8\xFF", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (8, b"WEBP", "image/webp"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\x1F\x8B", "application/gzip"),
+    (0, b"\x7FELF", "application/x-executable"),
+    (0, b"\0asm", "application/wasm"),
+];
+
+/// A `TypeSniffer` recognizing a small table of common formats by their magic numbers:
+/// PNG, JPEG, GIF, WebP, PDF, ZIP, gzip, ELF executables and WebAssembly.
+///
+/// Formats based on ZIP, like `.docx` or `.jar`, are detected as `application/zip`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MagicSniffer;
+
+impl TypeSniffer for MagicSniffer {
+    fn sniff(&self, first_bytes: &[u8], _headers: &FieldHeaders) -> Option<Mime> {
+        // WebP is `RIFF<size>WEBP`
+        let is_riff = first_bytes.starts_with(b"RIFF");
+
+        MAGIC.iter()
+            .find(|&&(offset, magic, _)| {
+                (offset == 0 || is_riff) && first_bytes.len() >= offset
+                    && first_bytes[offset..].starts_with(magic)
+            })
+            .and_then(|&(_, _, content_type)| content_type.parse().ok())
+    }
+}
+
+/// A `TypeSniffer` using the [`infer`](https://crates.io/crates/infer) crate, which recognizes
+/// many more formats than `MagicSniffer`. Enabled with the `infer` feature.
+#[cfg(feature = "infer")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InferSniffer;
+
+#[cfg(feature = "infer")]
+impl TypeSniffer for InferSniffer {
+    fn sniff(&self, first_bytes: &[u8], _headers: &FieldHeaders) -> Option<Mime> {
+        ::infer::get(first_bytes).and_then(|kind| kind.mime_type().parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MagicSniffer, TypeSniffer};
+
+    use server::FieldHeaders;
+
+    #[test]
+    fn test_magic_sniffer() {
+        let headers = FieldHeaders::new("file");
+
+        let known: &[(&[u8], &str)] = &[
+            (b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR", "image/png"),
+            (b"\xFF\xD8\xFF\xE0\0\x10JFIF", "image/jpeg"),
+            (b"GIF89a\x01\0\x01\0", "image/gif"),
+            (b"RIFF\x24\0\0\0WEBPVP8 ", "image/webp"),
+            (b"%PDF-1.7\n", "application/pdf"),
+            (b"PK\x03\x04\x14\0", "application/zip"),
+        ];
+
+        for &(bytes, content_type) in known {
+            let sniffed = MagicSniffer.sniff(bytes, &headers);
+            assert_eq!(sniffed.as_ref().map(|m| m.as_ref()), Some(content_type), "{:?}", bytes);
+        }
+
+        // `WEBP` only counts in a RIFF container
+        for &bytes in &[&b""[..], b"hello, world", b"\x89PN", b"12345678WEBP", b"RIFF\0\0\0\0WAVE"] {
+            assert_eq!(MagicSniffer.sniff(bytes, &headers), None, "{:?}", bytes);
+        }
+    }
+
+    #[cfg(feature = "infer")]
+    #[test]
+    fn test_infer_sniffer() {
+        let headers = FieldHeaders::new("file");
+
+        let sniffed = super::InferSniffer.sniff(b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR", &headers);
+        assert_eq!(sniffed, Some(::mime::IMAGE_PNG));
+        assert_eq!(super::InferSniffer.sniff(b"hello, world", &headers), None);
+    }
+}
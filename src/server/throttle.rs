@@ -0,0 +1,212 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Limiting how fast field data is read, for `SaveBuilder::throttle()` and `Throttled`.
+
+use std::io::{self, BufRead, Read};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Don't sleep until at least this far ahead of the rate, so small reads don't each sleep.
+const GRANULARITY: Duration = Duration::from_millis(10);
+
+/// How far a reader may fall behind the rate before the time is forfeited, i.e. the size of
+/// the bucket: after a pause, at most this long's worth of bytes is read without sleeping.
+const BURST: Duration = Duration::from_secs(1);
+
+/// A token bucket limiting reads to a number of bytes per second.
+///
+/// Clones share the same bucket, so one `Throttle` can pace the data of every field
+/// of a request, or of several requests.
+///
+/// Sleeps in the thread doing the reading; time spent sleeping counts toward any timeouts
+/// of the underlying connection like any other slow read.
+#[derive(Clone, Debug)]
+pub struct Throttle {
+    bytes_per_sec: u64,
+    /// The time when the bytes read so far are paid for, or `None` before the first read.
+    next: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Throttle {
+    /// Limit reads to `bytes_per_sec`.
+    ///
+    /// ### Panics
+    /// If `bytes_per_sec` is `0`.
+    pub fn new(bytes_per_sec: u64) -> Throttle {
+        assert_ne!(bytes_per_sec, 0, "throttle rate must not be zero");
+
+        Throttle {
+            bytes_per_sec,
+            next: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The rate this was created with.
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Account for `amt` bytes having been read, sleeping if that puts the reader ahead
+    /// of the rate.
+    pub fn pace(&self, amt: usize) {
+        if let Some(delay) = self.delay(amt, Instant::now()) {
+            thread::sleep(delay);
+        }
+    }
+
+    /// Account for `amt` bytes read at `now`, returning how long to wait before reading more.
+    fn delay(&self, amt: usize, now: Instant) -> Option<Duration> {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+
+        // the bucket starts empty, and only holds up to `BURST`'s worth of bytes
+        let start = match *next {
+            Some(next) if next + BURST < now => now - BURST,
+            Some(next) => next,
+            None => now,
+        };
+
+        let due = start + self.duration_of(amt as u64);
+        *next = Some(due);
+
+        if due > now + GRANULARITY { Some(due - now) } else { None }
+    }
+
+    /// The time reading `bytes` takes at the rate.
+    fn duration_of(&self, bytes: u64) -> Duration {
+        let secs = bytes / self.bytes_per_sec;
+        let nanos = u128::from(bytes % self.bytes_per_sec) * 1_000_000_000
+            / u128::from(self.bytes_per_sec);
+
+        Duration::new(secs, nanos as u32)
+    }
+}
+
+/// A reader limited to the rate of a `Throttle`.
+///
+/// This is the equivalent of `SaveBuilder::throttle()` for reading `MultipartData` directly:
+///
+/// ```rust
+/// # use multipart::server::{Multipart, Throttle, Throttled};
+/// # use std::io::Read;
+/// # let body = &b"--boundary\r\nContent-Disposition: form-data; name=\"foo\"\r\n\r\nbar\r\n--boundary--"[..];
+/// let mut multipart = Multipart::with_body(body, "boundary");
+/// // shared by all fields, so the rate applies to the whole request
+/// let throttle = Throttle::new(1024 * 1024);
+///
+/// multipart.foreach_entry(|field| {
+///     let mut data = String::new();
+///     Throttled::new(field.data, throttle.clone()).read_to_string(&mut data).unwrap();
+/// }).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Throttled<R> {
+    inner: R,
+    throttle: Throttle,
+}
+
+impl<R> Throttled<R> {
+    /// Limit reads from `inner` to the rate of `throttle`.
+    pub fn new(inner: R, throttle: Throttle) -> Self {
+        Throttled { inner, throttle }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    ///
+    /// Reads made through it aren't throttled.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get the inner reader back.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.throttle.pace(read);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for Throttled<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.throttle.pace(amt);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Throttle, Throttled, GRANULARITY};
+
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    /// Read `total` bytes in `chunk`-sized reads against a virtual clock which only advances
+    /// while sleeping, returning the time taken.
+    fn simulate(throttle: &Throttle, start: Instant, total: u64, chunk: u64) -> Duration {
+        let mut now = start;
+        let mut read = 0;
+
+        while read < total {
+            let amt = ::std::cmp::min(chunk, total - read);
+            read += amt;
+
+            if let Some(delay) = throttle.delay(amt as usize, now) {
+                now += delay;
+            }
+        }
+
+        now - start
+    }
+
+    #[test]
+    fn test_throttle_rate() {
+        const MB: u64 = 1_000_000;
+
+        let throttle = Throttle::new(MB);
+        let start = Instant::now();
+        let elapsed = simulate(&throttle, start, 10 * MB, 64 * 1024);
+
+        assert!(elapsed >= Duration::from_secs(9) && elapsed <= Duration::from_secs(11),
+                "{:?}", elapsed);
+
+        // after a pause the bucket has refilled, but only by a second's worth
+        let resumed = start + elapsed + Duration::from_secs(60);
+        let elapsed = simulate(&throttle, resumed, 3 * MB, 64 * 1024);
+
+        assert!(elapsed >= Duration::from_secs(2) - GRANULARITY
+                && elapsed <= Duration::from_secs(2) + GRANULARITY, "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_throttled_reader() {
+        let data = vec![0u8; 2048];
+        let start = Instant::now();
+
+        let mut read = Vec::new();
+        Throttled::new(&data[..], Throttle::new(10 * 1024)).read_to_end(&mut read).unwrap();
+
+        assert_eq!(read, data);
+        // the first read is paid for when it's made
+        assert!(start.elapsed() >= Duration::from_millis(190), "{:?}", start.elapsed());
+    }
+}
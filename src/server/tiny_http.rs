@@ -14,20 +14,16 @@ impl<'r> HttpRequest for &'r mut TinyHttpRequest {
     type Body = &'r mut dyn Read;
 
     fn multipart_boundary(&self) -> Option<&str> {
-        const BOUNDARY: &str = "boundary=";
+        super::form_data_boundary(self.content_type()?)
+    }
 
-        let content_type = try_opt!(self
+    fn content_type(&self) -> Option<&str> {
+        let header = self
             .headers()
             .iter()
-            .find(|header| header.field.equiv("Content-Type")))
-        .value
-        .as_str();
-        let start = try_opt!(content_type.find(BOUNDARY)) + BOUNDARY.len();
-        let end = content_type[start..]
-            .find(';')
-            .map_or(content_type.len(), |end| start + end);
-
-        Some(&content_type[start..end])
+            .find(|header| header.field.equiv("Content-Type"))?;
+
+        Some(header.value.as_str())
     }
 
     fn body(self) -> Self::Body {
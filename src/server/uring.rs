@@ -0,0 +1,315 @@
+// Copyright 2016 `multipart` Crate Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! `io_uring`-backed file writer used by `SaveBuilder::io_uring()`.
+//!
+//! The parser thread copies each buffer into a bounded queue; a dedicated thread drains the
+//! queue and submits the writes to the ring in (linked, so ordered) batches.
+extern crate io_uring;
+
+use self::io_uring::{opcode, squeue, types, IoUring};
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Seek, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+/// `ECANCELED` on Linux; returned for linked writes following a short or failed write.
+const ECANCELED: i32 = 125;
+
+/// A `Write` adapter which hands buffers off to a ring driven by a dedicated thread.
+///
+/// `write()` only queues the data; call `finish()` to wait for all writes to complete and
+/// get the actual number of bytes written along with the first error, if any.
+pub struct RingWriter {
+    sender: Option<SyncSender<Vec<u8>>>,
+    thread: Option<JoinHandle<(u64, Option<io::Error>)>>,
+}
+
+impl RingWriter {
+    /// Create a ring with the given queue depth and start the writer thread for `file`.
+    ///
+    /// Returns an error if the ring could not be created, e.g. if the kernel does not
+    /// support `io_uring`.
+    pub fn new(file: &File, queue_depth: u32) -> io::Result<RingWriter> {
+        let queue_depth = queue_depth.max(1);
+        let ring = IoUring::new(queue_depth)?;
+        let file = file.try_clone()?;
+
+        let (sender, receiver) = mpsc::sync_channel(queue_depth as usize);
+
+        let thread = thread::Builder::new()
+            .name("multipart-io-uring".into())
+            .spawn(move || run_ring(ring, file, receiver, queue_depth as usize))?;
+
+        Ok(RingWriter {
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Wait for all queued writes to complete.
+    ///
+    /// Returns the number of bytes actually written and the first error encountered, if any.
+    pub fn finish(mut self) -> (u64, Option<io::Error>) {
+        self.join()
+    }
+
+    fn join(&mut self) -> (u64, Option<io::Error>) {
+        // hang up so the ring thread exits once it has drained the queue
+        self.sender = None;
+
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(res)) => res,
+            Some(Err(_)) => (0, Some(io::Error::other("io_uring writer thread panicked"))),
+            None => (0, None),
+        }
+    }
+}
+
+impl Write for RingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let sent = self.sender.as_ref().is_some_and(|sender| sender.send(buf.to_vec()).is_ok());
+
+        if sent {
+            return Ok(buf.len());
+        }
+
+        // the ring thread only hangs up on an error
+        match self.join() {
+            (_, Some(err)) => Err(err),
+            (_, None) => Err(io::Error::new(io::ErrorKind::BrokenPipe, "io_uring writer thread exited")),
+        }
+    }
+
+    /// Writes are only known to be complete after `finish()`.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RingWriter {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+fn run_ring(mut ring: IoUring, mut file: File, receiver: Receiver<Vec<u8>>, queue_depth: usize)
+            -> (u64, Option<io::Error>) {
+    let fd = types::Fd(file.as_raw_fd());
+
+    let mut offset = match file.stream_position() {
+        Ok(offset) => offset,
+        Err(e) => return (0, Some(e)),
+    };
+
+    let mut written = 0u64;
+
+    // buffers and how many bytes of each have been written so far
+    let mut pending: VecDeque<(Vec<u8>, usize)> = VecDeque::with_capacity(queue_depth);
+
+    loop {
+        if pending.is_empty() {
+            match receiver.recv() {
+                Ok(buf) => pending.push_back((buf, 0)),
+                // writer was finished or dropped
+                Err(_) => return (written, None),
+            }
+        }
+
+        while pending.len() < queue_depth {
+            match receiver.try_recv() {
+                Ok(buf) => pending.push_back((buf, 0)),
+                Err(_) => break,
+            }
+        }
+
+        let batch = pending.len();
+
+        {
+            let mut submission = ring.submission();
+            let mut entry_offset = offset;
+
+            for (idx, &(ref buf, done)) in pending.iter().enumerate() {
+                let remaining = &buf[done..];
+
+                let mut entry = opcode::Write::new(fd, remaining.as_ptr(), remaining.len() as u32)
+                    .offset(entry_offset)
+                    .build()
+                    .user_data(idx as u64);
+
+                // link the writes so they are performed in order
+                if idx + 1 < batch {
+                    entry = entry.flags(squeue::Flags::IO_LINK);
+                }
+
+                // the submission queue is as deep as the batch is long, unless the ring was set up
+                // with fewer entries than `queue_depth`
+                if unsafe { submission.push(&entry) }.is_err() {
+                    return (written, Some(io::Error::other("io_uring submission queue full")));
+                }
+
+                entry_offset += remaining.len() as u64;
+            }
+        }
+
+        if let Err(e) = ring.submit_and_wait(batch) {
+            return (written, Some(e));
+        }
+
+        let mut results = vec![-ECANCELED; batch];
+
+        for completion in ring.completion() {
+            results[completion.user_data() as usize] = completion.result();
+        }
+
+        for res in results {
+            if res == -ECANCELED {
+                // resubmit everything from here on
+                break;
+            }
+
+            if res < 0 {
+                return (written, Some(io::Error::from_raw_os_error(-res)));
+            }
+
+            if res == 0 {
+                return (written, Some(io::Error::new(io::ErrorKind::WriteZero,
+                                                      "failed to write whole buffer")));
+            }
+
+            let res = res as usize;
+
+            offset += res as u64;
+            written += res as u64;
+
+            let complete = {
+                let front = pending.front_mut().expect("more completions than submissions");
+                front.1 += res;
+                front.1 == front.0.len()
+            };
+
+            if complete {
+                pending.pop_front();
+            } else {
+                // short write, the rest of the chain was cancelled
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run_ring, RingWriter};
+
+    use super::io_uring::IoUring;
+
+    use server::Multipart;
+    use server::save::SavedData;
+
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::sync::mpsc;
+
+    const BODY: &[u8] = b"--boundary\r\n\
+                                  Content-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+                                  Content-Type: application/octet-stream\r\n\r\n\
+                                  0123456789abcdef0123456789abcdef\r\n\
+                                  --boundary--\r\n";
+
+    #[test]
+    fn test_ring_writer() {
+        ::init_log();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let path = dir.path().join("ring");
+        let file = File::create(&path).unwrap();
+
+        let mut writer = match RingWriter::new(&file, 4) {
+            Ok(writer) => writer,
+            Err(e) => {
+                warn!("io_uring not available, skipping test: {}", e);
+                return;
+            }
+        };
+
+        let mut expected = Vec::new();
+
+        for i in 0 .. 64u8 {
+            let chunk = [i; 100];
+            writer.write_all(&chunk).unwrap();
+            expected.extend_from_slice(&chunk);
+        }
+
+        let (written, err) = writer.finish();
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(written, expected.len() as u64);
+
+        let mut contents = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, expected);
+    }
+
+    #[test]
+    fn test_submission_queue_full() {
+        ::init_log();
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let file = File::create(dir.path().join("ring")).unwrap();
+
+        let ring = match IoUring::new(1) {
+            Ok(ring) => ring,
+            Err(e) => {
+                warn!("io_uring not available, skipping test: {}", e);
+                return;
+            }
+        };
+
+        // two buffers are batched for a ring with room for one
+        let (sender, receiver) = mpsc::sync_channel(2);
+        sender.send(vec![1; 10]).unwrap();
+        sender.send(vec![2; 10]).unwrap();
+        drop(sender);
+
+        match run_ring(ring, file, receiver, 2) {
+            (0, Some(err)) => assert_eq!(err.to_string(), "io_uring submission queue full"),
+            other => panic!("expected the queue to be full, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_save_both_backends() {
+        ::init_log();
+
+        let save = |io_uring: bool| {
+            let mut multipart = Multipart::with_body(BODY, "boundary");
+            let builder = multipart.save().memory_threshold(0);
+            let builder = if io_uring { builder.io_uring(8) } else { builder };
+            let entries = builder.temp().into_result_strict().unwrap();
+
+            let field = &entries.fields["file"][0];
+            let mut contents = Vec::new();
+            field.data.readable().unwrap().read_to_end(&mut contents).unwrap();
+
+            match field.data {
+                SavedData::File(_, size) => assert_eq!(size.len, contents.len() as u64),
+                ref other => panic!("expected file, got {:?}", other),
+            }
+
+            contents
+        };
+
+        assert_eq!(save(false), save(true));
+    }
+}
@@ -0,0 +1,330 @@
+//! Runs the request bodies captured from browsers and HTTP libraries in `tests/corpus/` through
+//! both the raw field API and `SaveBuilder`, checking them against `tests/corpus/manifest.txt`.
+#![cfg(feature = "server")]
+
+extern crate mime;
+extern crate multipart;
+extern crate tempfile;
+
+#[cfg(feature = "multer")]
+extern crate futures_util;
+#[cfg(feature = "multer")]
+extern crate multer;
+
+use mime::Mime;
+
+use multipart::server::{Entries, FieldHeaders, Multipart, SaveBuilderConfig};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// A fixture from the manifest.
+#[derive(Debug)]
+struct Fixture {
+    name: String,
+    boundary: String,
+    fields: Vec<ExpectedField>,
+}
+
+/// A `field` line from the manifest.
+#[derive(Debug)]
+struct ExpectedField {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<Mime>,
+    size: u64,
+    data: Option<Vec<u8>>,
+}
+
+impl ExpectedField {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut field = ExpectedField {
+            name: String::new(),
+            filename: None,
+            content_type: None,
+            size: 0,
+            data: None,
+        };
+        let mut size = None;
+
+        for (key, value) in parse_pairs(line)? {
+            match &*key {
+                "name" => field.name = value,
+                "filename" => field.filename = Some(value),
+                "type" => field.content_type = Some(value.parse()
+                    .map_err(|_| format!("invalid type: {:?}", value))?),
+                "size" => size = Some(value.parse()
+                    .map_err(|_| format!("invalid size: {:?}", value))?),
+                "text" => field.data = Some(value.into_bytes()),
+                "hex" => field.data = Some(parse_hex(&value)?),
+                _ => return Err(format!("unknown key: {:?}", key)),
+            }
+        }
+
+        field.size = match size {
+            Some(size) if !field.name.is_empty() => size,
+            _ => return Err("`name` and `size` are required".into()),
+        };
+
+        if field.data.as_ref().is_some_and(|data| data.len() as u64 != field.size) {
+            return Err("the data doesn't match the size".into());
+        }
+
+        Ok(field)
+    }
+
+    fn check_headers(&self, headers: &FieldHeaders, fixture: &str) {
+        assert_eq!(&*headers.name, self.name, "{}: name", fixture);
+        assert_eq!(headers.filename, self.filename, "{}: filename of {:?}", fixture, self.name);
+        assert_eq!(headers.content_type, self.content_type, "{}: content type of {:?}", fixture, self.name);
+    }
+
+    fn check_data(&self, data: &[u8], fixture: &str) {
+        assert_eq!(data.len() as u64, self.size, "{}: size of {:?}", fixture, self.name);
+
+        if let Some(ref expected) = self.data {
+            assert_eq!(data, &**expected, "{}: data of {:?}", fixture, self.name);
+        }
+    }
+}
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus")
+}
+
+fn load_manifest() -> Vec<Fixture> {
+    let manifest = fs::read_to_string(corpus_dir().join("manifest.txt")).unwrap();
+    let mut fixtures: Vec<Fixture> = Vec::new();
+
+    for (idx, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            fixtures.push(Fixture {
+                name: line[1 .. line.len() - 1].into(),
+                boundary: String::new(),
+                fields: Vec::new(),
+            });
+            continue;
+        }
+
+        let fixture = fixtures.last_mut()
+            .unwrap_or_else(|| manifest_err(idx + 1, "expected `[name]` first"));
+
+        if let Some(boundary) = line.strip_prefix("boundary = ") {
+            fixture.boundary = boundary.into();
+        } else if let Some(field) = line.strip_prefix("field ") {
+            fixture.fields.push(ExpectedField::parse(field)
+                .unwrap_or_else(|msg| manifest_err(idx + 1, &msg)));
+        } else {
+            manifest_err(idx + 1, &format!("unexpected line: {:?}", line));
+        }
+    }
+
+    for fixture in &fixtures {
+        assert!(!fixture.boundary.is_empty(), "{}: no boundary", fixture.name);
+    }
+
+    fixtures
+}
+
+fn manifest_err(line: usize, msg: &str) -> ! {
+    panic!("manifest.txt:{}: {}", line, msg)
+}
+
+/// Parse space-separated `key=value` pairs, with optionally quoted values.
+fn parse_pairs(line: &str) -> Result<Vec<(String, String)>, String> {
+    let mut pairs = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+
+    while chars.peek().is_some() {
+        let key: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        let mut value = String::new();
+
+        if chars.peek() == Some(&'"') {
+            chars.next();
+
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => value.push(match chars.next() {
+                        Some('r') => '\r',
+                        Some('n') => '\n',
+                        Some(c @ '"') | Some(c @ '\\') => c,
+                        other => return Err(format!("invalid escape: {:?}", other)),
+                    }),
+                    Some(c) => value.push(c),
+                    None => return Err(format!("unterminated value of {:?}", key)),
+                }
+            }
+        } else {
+            value.extend(chars.by_ref().take_while(|&c| c != ' '));
+        }
+
+        pairs.push((key.trim().to_string(), value));
+
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.len() % 2 == 1 {
+        return Err(format!("odd number of hex digits: {:?}", hex));
+    }
+
+    (0 .. hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i .. i + 2], 16).map_err(|e| format!("{}: {:?}", e, hex)))
+        .collect()
+}
+
+fn read_body(fixture: &Fixture) -> Vec<u8> {
+    fs::read(corpus_dir().join(format!("{}.multipart", fixture.name))).unwrap()
+}
+
+fn check_entries(entries: &Entries, fixture: &Fixture) {
+    assert_eq!(entries.fields_count() as usize, fixture.fields.len(), "{}", fixture.name);
+    assert_eq!(entries.part_count(), fixture.fields.len() as u64, "{}", fixture.name);
+    assert!(entries.clean_termination, "{}", fixture.name);
+
+    // fields with the same name are kept in order
+    let mut seen = HashMap::new();
+
+    for expected in &fixture.fields {
+        let idx = seen.entry(&*expected.name).or_insert(0);
+        let saved = &entries.fields[&*expected.name][*idx];
+        *idx += 1;
+
+        expected.check_headers(&saved.headers, &fixture.name);
+
+        let mut data = Vec::new();
+        saved.data.readable().unwrap().read_to_end(&mut data).unwrap();
+        expected.check_data(&data, &fixture.name);
+    }
+}
+
+#[test]
+fn manifest_covers_corpus() {
+    let fixtures = load_manifest();
+
+    let mut bodies: Vec<String> = fs::read_dir(corpus_dir()).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "multipart"))
+        .map(|path| path.file_stem().unwrap().to_str().unwrap().to_string())
+        .collect();
+    bodies.sort();
+
+    let mut names: Vec<String> = fixtures.iter().map(|fixture| fixture.name.clone()).collect();
+    names.sort();
+
+    assert_eq!(names, bodies);
+}
+
+#[test]
+fn corpus_raw_fields() {
+    for fixture in load_manifest() {
+        let body = read_body(&fixture);
+
+        // every client in the corpus produces well-formed bodies
+        let mut multipart = Multipart::with_body(&body[..], &*fixture.boundary);
+        multipart.strict_crlf(true);
+
+        for expected in &fixture.fields {
+            let mut field = multipart.read_entry()
+                .unwrap_or_else(|e| panic!("{}: {}", fixture.name, e))
+                .unwrap_or_else(|| panic!("{}: missing field {:?}", fixture.name, expected.name));
+
+            expected.check_headers(&field.headers, &fixture.name);
+
+            let mut data = Vec::new();
+            field.data.read_to_end(&mut data).unwrap();
+            expected.check_data(&data, &fixture.name);
+        }
+
+        assert!(multipart.read_entry().unwrap().is_none(), "{}: extra fields", fixture.name);
+        assert!(multipart.finished_cleanly(), "{}", fixture.name);
+    }
+}
+
+#[test]
+fn corpus_save() {
+    for fixture in load_manifest() {
+        let body = read_body(&fixture);
+
+        // in memory, then with every field in a file
+        for &memory_threshold in &[10 * 1024, 0] {
+            let entries = Multipart::with_body(&body[..], &*fixture.boundary).save()
+                .memory_threshold(memory_threshold)
+                .temp()
+                .into_result_strict()
+                .unwrap_or_else(|e| panic!("{}: {}", fixture.name, e));
+
+            check_entries(&entries, &fixture);
+        }
+    }
+}
+
+#[test]
+fn corpus_capture_raw() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for fixture in load_manifest() {
+        let body = read_body(&fixture);
+
+        for &memory_threshold in &[10 * 1024 * 1024, 0] {
+            let mut multipart = Multipart::with_body(&body[..], &*fixture.boundary);
+            multipart.capture_raw(&SaveBuilderConfig::config()
+                .memory_threshold(memory_threshold)
+                .temp_parent(dir.path()));
+
+            let entries = multipart.save().temp().into_result_strict()
+                .unwrap_or_else(|e| panic!("{}: {}", fixture.name, e));
+            assert_eq!(entries.fields_count() as usize, fixture.fields.len(), "{}", fixture.name);
+
+            let mut raw = Vec::new();
+            multipart.raw_body().unwrap().expect("capture_raw() was called")
+                .readable().unwrap().read_to_end(&mut raw).unwrap();
+            assert!(raw == body, "{}: the captured body differs", fixture.name);
+        }
+    }
+}
+
+#[cfg(feature = "multer")]
+#[test]
+fn corpus_multer() {
+    use multipart::server::multer::{save, to_multer, MulterMultipart};
+
+    for mut fixture in load_manifest() {
+        let body = read_body(&fixture);
+
+        // in small chunks, so fields and headers are split between them
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = body.chunks(7).map(|chunk| Ok(chunk.to_vec())).collect();
+        let multipart = MulterMultipart::new(futures_util::stream::iter(chunks), &*fixture.boundary);
+
+        let entries = save(multipart, |builder| builder.memory_threshold(64).temp())
+            .into_result_strict()
+            .unwrap_or_else(|e| panic!("{}: {}", fixture.name, e));
+        check_entries(&entries, &fixture);
+
+        // and back through `multer` again, where files are always sent with a content type
+        for expected in &mut fixture.fields {
+            if expected.filename.is_some() && expected.content_type.is_none() {
+                expected.content_type = Some(mime::APPLICATION_OCTET_STREAM);
+            }
+        }
+
+        let multipart = to_multer(&entries).unwrap_or_else(|e| panic!("{}: {}", fixture.name, e));
+        let entries = save(multipart, |builder| builder.temp())
+            .into_result_strict()
+            .unwrap_or_else(|e| panic!("{}: {}", fixture.name, e));
+        check_entries(&entries, &fixture);
+    }
+}